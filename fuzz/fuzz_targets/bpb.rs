@@ -0,0 +1,15 @@
+#![no_main]
+
+use fat_forensics::Bpb;
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(bpb) = Bpb::from(&mut Cursor::new(data), 0, false, 512) {
+        let _ = bpb.cluster_count();
+        let _ = bpb.fat_sz();
+        let _ = bpb.tot_sec();
+        let _ = bpb.to_string();
+    }
+    let _ = Bpb::from(&mut Cursor::new(data), 0, true, 512);
+});
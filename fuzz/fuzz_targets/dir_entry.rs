@@ -0,0 +1,13 @@
+#![no_main]
+
+use fat_forensics::DirEntry;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(entry) = DirEntry::from_slice(data) {
+        let _ = entry.to_string();
+        let _ = entry.short_name();
+        let _ = entry.cluster_number();
+        let _ = entry.same_short_name("FILE.TXT");
+    }
+});
@@ -0,0 +1,27 @@
+#![no_main]
+
+use fat_forensics::Disk;
+use fat_forensics::traits::{LayoutDisplay, LayoutOptions, LayoutUnit};
+use libfuzzer_sys::fuzz_target;
+use std::fs;
+
+fuzz_target!(|data: &[u8]| {
+    // Disk::from_file works on paths, so the input is written to a per-process temporary image
+    let path = std::env::temp_dir().join(format!("fat_forensics_fuzz_{}.img", std::process::id()));
+    if fs::write(&path, data).is_err() {
+        return;
+    }
+
+    for validation in [true, false] {
+        if let Ok(disk) = Disk::from_file(&path, 512, validation) {
+            let options = LayoutOptions {
+                unit: LayoutUnit::Human,
+                all: true,
+            };
+            let _ = disk.part_table().display_layout(0, &options);
+            for vol in disk.volumes() {
+                let _ = vol.display_layout(0, &options);
+            }
+        }
+    }
+});
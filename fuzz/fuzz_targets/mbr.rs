@@ -0,0 +1,12 @@
+#![no_main]
+
+use fat_forensics::Mbr;
+use fat_forensics::traits::{LayoutDisplay, LayoutOptions};
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mbr) = Mbr::from(&mut Cursor::new(data), data.len() as u64, 512) {
+        let _ = mbr.display_layout(0, &LayoutOptions::default());
+    }
+});
@@ -0,0 +1,118 @@
+//! Detection of entries hidden through their attributes.
+//!
+//! The hidden and system attributes keep an entry out of default directory listings, and tools
+//! skip the entries carrying the volume ID attribute as labels. Setting them on a regular file is
+//! the quickest way to hide it from a casual examiner.
+
+use getset::Getters;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::filesystem::dir_entry::{Attribute, DirEntry};
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::utils::escape_path;
+
+/// An entry whose attributes hide it from listings.
+#[derive(Debug, Getters)]
+pub struct FlaggedEntry {
+    /// The path of the entry, relative to the root directory.
+    #[get = "pub"]
+    path: PathBuf,
+    /// The directory entry.
+    #[get = "pub"]
+    entry: DirEntry,
+    /// Why the entry is flagged.
+    #[get = "pub"]
+    reasons: Vec<String>,
+}
+
+impl fmt::Display for FlaggedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "/{} {}B [{}]: {}",
+            escape_path(&self.path),
+            self.entry.file_size(),
+            self.entry.attributes(),
+            self.reasons.join(", ")
+        )
+    }
+}
+
+/// Finds the live entries flagged hidden or system, and the volume ID attributes set on anything
+/// but the volume label.
+///
+/// A volume label is misused when it lies outside of the root directory, points to data, or
+/// follows another label of the root directory.
+///
+/// # Parameters
+/// - `vol`: The volume to scan.
+///
+/// # Returns
+/// - `Ok(Vec<FlaggedEntry>)`: The flagged entries, parents before their children.
+/// - `Err(FATError)`: If the directory tree cannot be walked.
+pub fn hidden_entries(vol: &FATVol) -> Result<Vec<FlaggedEntry>, FATError> {
+    let mut flagged = vec![];
+    let mut root_label_seen = false;
+
+    for (path, entry) in vol.walk_entries()? {
+        let mut reasons = vec![];
+        for attr in [Attribute::Hidden, Attribute::System] {
+            if entry.has_attribute(attr) {
+                reasons.push(format!("{attr} attribute"));
+            }
+        }
+
+        if entry.is_volume_id() {
+            let in_root = path
+                .parent()
+                .is_none_or(|parent| parent.as_os_str().is_empty());
+            if !in_root {
+                reasons.push(String::from("volume label outside of the root directory"));
+            } else if root_label_seen {
+                reasons.push(String::from("second volume label of the root directory"));
+            }
+            root_label_seen |= in_root;
+
+            if entry.cluster_number() != 0 || *entry.file_size() != 0 {
+                reasons.push(format!(
+                    "volume label pointing to {} bytes from cluster {}",
+                    entry.file_size(),
+                    entry.cluster_number()
+                ));
+            }
+        }
+
+        if !reasons.is_empty() {
+            flagged.push(FlaggedEntry {
+                path,
+                entry,
+                reasons,
+            });
+        }
+    }
+
+    Ok(flagged)
+}
+
+/// Lists the live entries carrying an attribute.
+///
+/// # Parameters
+/// - `vol`: The volume to scan.
+/// - `attr`: The attribute to look for.
+///
+/// # Returns
+/// - `Ok(Vec<(PathBuf, DirEntry)>)`: The path and the entry of each match, parents before their
+///   children.
+/// - `Err(FATError)`: If the directory tree cannot be walked.
+pub fn entries_with_attribute(
+    vol: &FATVol,
+    attr: Attribute,
+) -> Result<Vec<(PathBuf, DirEntry)>, FATError> {
+    Ok(vol
+        .walk_entries()?
+        .into_iter()
+        .filter(|(_, entry)| entry.has_attribute(attr))
+        .collect())
+}
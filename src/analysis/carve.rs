@@ -0,0 +1,403 @@
+//! Signature-based carving of unallocated space.
+//!
+//! Deleted files keep their content in unallocated clusters until it is overwritten. Carving
+//! recovers them without the file system metadata: a file starts at a cluster boundary with a known
+//! header and ends at its footer. The same object is often recovered several times (e.g. an old
+//! copy and the remnant of a move), so carved files are deduplicated by hash, against each other
+//! and against the allocated files.
+//!
+//! Formats are recovered by [`Carver`] implementations. The built-in ones are the
+//! [`CARVE_SIGNATURES`]; custom carvers can be added to a [`CarverRegistry`].
+
+use getset::Getters;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+
+use super::Provenance;
+use super::formats::{gif_end, jpeg_end, pdf_end, png_end, zip_end};
+use super::hash::HashAlgorithm;
+use crate::events::Event;
+use crate::filesystem::clusters::ClusterKind;
+use crate::filesystem::fat::{FATVol, contiguous_runs};
+use crate::filesystem::fat_error::FATError;
+use crate::traits::Carver;
+use crate::utils::escape_path;
+
+/// Maximum number of free clusters read at once while looking for headers.
+const SCAN_RUN_LEN: u32 = 2048;
+
+/// A file format recovered by carving, described by its header and footer.
+///
+/// The length of a file is computed by parsing its structure rather than by looking for the first
+/// footer, so that carved files are trimmed to their true size.
+#[derive(Debug, Clone, Copy)]
+pub struct CarveSignature {
+    /// The name of the format.
+    pub name: &'static str,
+    /// The extension given to the carved files.
+    pub extension: &'static str,
+    /// The bytes starting every file of the format.
+    pub header: &'static [u8],
+    /// The bytes ending every file of the format, if any.
+    pub footer: Option<&'static [u8]>,
+    /// The maximum size of a file, in bytes.
+    pub max_size: u64,
+    /// Computes the length of a file from its structure, see [`super::formats`].
+    pub end: fn(&[u8]) -> Option<usize>,
+}
+
+/// The formats recovered by the default [`CarverRegistry`].
+pub const CARVE_SIGNATURES: &[CarveSignature] = &[
+    CarveSignature {
+        name: "JPEG image",
+        extension: "jpg",
+        header: b"\xFF\xD8\xFF",
+        footer: Some(b"\xFF\xD9"),
+        max_size: 20 << 20,
+        end: jpeg_end,
+    },
+    CarveSignature {
+        name: "PNG image",
+        extension: "png",
+        header: b"\x89PNG\r\n\x1A\n",
+        footer: Some(b"IEND\xAE\x42\x60\x82"),
+        max_size: 20 << 20,
+        end: png_end,
+    },
+    CarveSignature {
+        name: "GIF image",
+        extension: "gif",
+        header: b"GIF8",
+        footer: Some(b"\x00\x3B"),
+        max_size: 20 << 20,
+        end: gif_end,
+    },
+    CarveSignature {
+        name: "PDF document",
+        extension: "pdf",
+        header: b"%PDF-",
+        footer: Some(b"%%EOF"),
+        max_size: 50 << 20,
+        end: pdf_end,
+    },
+    CarveSignature {
+        name: "ZIP archive",
+        extension: "zip",
+        header: b"PK\x03\x04",
+        footer: Some(b"PK\x05\x06"),
+        max_size: 100 << 20,
+        end: zip_end,
+    },
+];
+
+impl Carver for CarveSignature {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn extension(&self) -> &str {
+        self.extension
+    }
+
+    fn header(&self) -> &[u8] {
+        self.header
+    }
+
+    fn footer(&self) -> Option<&[u8]> {
+        self.footer
+    }
+
+    fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    fn trim(&self, data: &[u8]) -> Option<usize> {
+        (self.end)(data)
+    }
+}
+
+/// The carvers run by [`carve`], tried in registration order.
+pub struct CarverRegistry {
+    carvers: Vec<Box<dyn Carver>>,
+}
+
+impl CarverRegistry {
+    /// Creates a registry without any carver.
+    pub fn empty() -> Self {
+        Self { carvers: vec![] }
+    }
+
+    /// Adds a carver to the registry.
+    ///
+    /// Carvers registered first take precedence when several headers match the same cluster.
+    pub fn register(&mut self, carver: Box<dyn Carver>) {
+        self.carvers.push(carver);
+    }
+
+    /// Returns the registered carvers.
+    pub fn carvers(&self) -> &[Box<dyn Carver>] {
+        &self.carvers
+    }
+}
+
+impl Default for CarverRegistry {
+    /// Creates a registry with the built-in [`CARVE_SIGNATURES`].
+    fn default() -> Self {
+        Self {
+            carvers: CARVE_SIGNATURES
+                .iter()
+                .map(|signature| Box::new(*signature) as Box<dyn Carver>)
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Debug for CarverRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.carvers.iter().map(|carver| carver.name()))
+            .finish()
+    }
+}
+
+/// A file recovered from unallocated space.
+#[derive(Debug, Clone, Getters)]
+pub struct CarvedFile {
+    /// The name of the format of the file.
+    #[get = "pub"]
+    format: String,
+    /// The extension of the format of the file.
+    #[get = "pub"]
+    extension: String,
+    /// Absolute byte offset of the file on disk.
+    #[get = "pub"]
+    offset: u64,
+    /// The content of the file.
+    #[get = "pub"]
+    data: Vec<u8>,
+    /// SHA-256 digest of the content.
+    #[get = "pub"]
+    digest: String,
+    /// Where the file lies in the image.
+    #[get = "pub"]
+    provenance: Provenance,
+}
+
+impl fmt::Display for CarvedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:X}-0x{:X} ({} bytes) {} {}",
+            self.offset,
+            self.offset + self.data.len() as u64,
+            self.data.len(),
+            self.format,
+            self.digest
+        )
+    }
+}
+
+/// Carves the unallocated clusters of a volume.
+///
+/// A file is assumed to be stored in contiguous free clusters: it starts with a header at a cluster
+/// boundary and its length is computed by the carver, from the data up to the maximum size of the
+/// format or the end of the free clusters. Candidates rejected by the carver are ignored.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to carve.
+/// - `registry`: The carvers to run.
+///
+/// # Returns
+/// - `Ok(Vec<CarvedFile>)`: The carved files, sorted by offset.
+/// - `Err(FATError::LimitExceeded)`: If the carved files exceed the carved bytes limit of the
+///   volume.
+/// - `Err(FATError)`: If the volume cannot be read.
+pub fn carve(vol: &FATVol, registry: &CarverRegistry) -> Result<Vec<CarvedFile>, FATError> {
+    let cluster_size = vol.cluster_size() as u64;
+    let free_clusters: Vec<u32> = vol
+        .clusters(ClusterKind::Free)?
+        .map(|region| *region.cluster())
+        .collect();
+    // Clusters in the holes of a sparse image are zeros, no header can start there
+    let data_clusters = vol.data_clusters(&free_clusters)?;
+
+    let max_carved_bytes = vol.limits().max_carved_bytes;
+    let mut carved_bytes = 0;
+    let mut carved = vec![];
+    let mut scanned = 0;
+    for (first, count) in contiguous_runs(&free_clusters, u32::MAX) {
+        let run_end = vol.cluster_offset(first) + count as u64 * cluster_size;
+
+        for chunk_first in (first..first + count).step_by(SCAN_RUN_LEN as usize) {
+            vol.check_cancelled()?;
+            let clusters: Vec<u32> = (chunk_first..(chunk_first + SCAN_RUN_LEN).min(first + count))
+                .filter(|cluster| data_clusters.binary_search(cluster).is_ok())
+                .collect();
+            let chunk = vol.read_chain(&clusters)?;
+
+            for (cluster, data) in clusters.iter().zip(chunk.chunks(cluster_size as usize)) {
+                let Some(carver) = registry
+                    .carvers
+                    .iter()
+                    .find(|carver| data.starts_with(carver.header()))
+                else {
+                    continue;
+                };
+
+                let offset = vol.cluster_offset(*cluster);
+                let len = carver.max_size().min(run_end - offset);
+                let mut data = vol.read_bytes(offset, len as usize)?;
+                let Some(len) = carver.trim(&data) else {
+                    vol.emit(|| Event::Skipped {
+                        offset,
+                        reason: format!("{} header rejected by the carver", carver.name()),
+                    });
+                    continue;
+                };
+                data.truncate(len);
+
+                carved_bytes += data.len() as u64;
+                if carved_bytes > max_carved_bytes {
+                    return Err(FATError::LimitExceeded {
+                        what: "carved bytes",
+                        max: max_carved_bytes,
+                    });
+                }
+                vol.emit(|| Event::Recovered {
+                    offset,
+                    len: data.len() as u64,
+                    description: format!("carved {} file", carver.name()),
+                });
+                carved.push(CarvedFile {
+                    format: carver.name().to_string(),
+                    extension: carver.extension().to_string(),
+                    offset,
+                    digest: HashAlgorithm::Sha256.hex_digest(&data),
+                    provenance: Provenance::from_extents(vol, vec![(offset, data.len() as u64)]),
+                    data,
+                });
+            }
+
+            scanned += ((chunk_first + SCAN_RUN_LEN).min(first + count) - chunk_first) as u64;
+            vol.emit(|| Event::Progress {
+                task: "carve",
+                done: scanned,
+                total: free_clusters.len() as u64,
+            });
+        }
+    }
+
+    Ok(carved)
+}
+
+/// A carved object, recovered one or more times.
+#[derive(Debug, Getters)]
+pub struct UniqueRecovery {
+    /// The first recovered copy.
+    #[get = "pub"]
+    carved: CarvedFile,
+    /// The absolute byte offsets of all the recovered copies.
+    #[get = "pub"]
+    offsets: Vec<u64>,
+}
+
+impl fmt::Display for UniqueRecovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.carved)?;
+        if self.offsets.len() > 1 {
+            let offsets: Vec<String> = self.offsets.iter().map(|o| format!("0x{o:X}")).collect();
+            write!(
+                f,
+                " ({} copies at {})",
+                self.offsets.len(),
+                offsets.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The carved files, deduplicated.
+#[derive(Debug, Getters)]
+pub struct CarveReport {
+    /// The objects not matching any allocated file, in order of first recovery.
+    #[get = "pub"]
+    recoveries: Vec<UniqueRecovery>,
+    /// The carved copies of allocated files, with the path of the matching file.
+    #[get = "pub"]
+    allocated_copies: Vec<(u64, PathBuf)>,
+    /// The total number of carved files.
+    #[get = "pub"]
+    carved_count: usize,
+}
+
+impl fmt::Display for CarveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Carved {} file(s): {} unique recovery(ies), {} copy(ies) of allocated files.",
+            self.carved_count,
+            self.recoveries.len(),
+            self.allocated_copies.len()
+        )?;
+        for recovery in &self.recoveries {
+            writeln!(f, "{recovery}")?;
+            writeln!(f, "  evidence: {}", recovery.carved.provenance)?;
+        }
+        for (offset, path) in &self.allocated_copies {
+            writeln!(f, "0x{offset:X} is a copy of {}", escape_path(path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Deduplicates carved files by hash, against each other and against the allocated files.
+///
+/// Only the allocated files whose size matches a carved file are hashed.
+///
+/// # Parameters
+/// - `vol`: The FAT volume the files were carved from.
+/// - `carved`: The carved files.
+///
+/// # Returns
+/// - `Ok(CarveReport)`: The unique recoveries and the copies of allocated files.
+/// - `Err(FATError)`: If an allocated file cannot be read.
+pub fn dedup_carved(vol: &FATVol, carved: Vec<CarvedFile>) -> Result<CarveReport, FATError> {
+    let carved_sizes: HashSet<u64> = carved.iter().map(|c| c.data.len() as u64).collect();
+    let mut allocated: HashMap<String, PathBuf> = HashMap::new();
+    for (path, entry) in vol.walk_files()? {
+        if carved_sizes.contains(&(*entry.file_size() as u64)) {
+            let digest = HashAlgorithm::Sha256.hex_digest(&vol.read_entry(&entry)?);
+            allocated.entry(digest).or_insert(path);
+        }
+    }
+
+    let carved_count = carved.len();
+    let mut recoveries: Vec<UniqueRecovery> = vec![];
+    let mut by_digest: HashMap<String, usize> = HashMap::new();
+    let mut allocated_copies = vec![];
+    for file in carved {
+        if let Some(path) = allocated.get(&file.digest) {
+            allocated_copies.push((file.offset, path.clone()));
+            continue;
+        }
+
+        match by_digest.get(&file.digest) {
+            Some(idx) => recoveries[*idx].offsets.push(file.offset),
+            None => {
+                by_digest.insert(file.digest.clone(), recoveries.len());
+                recoveries.push(UniqueRecovery {
+                    offsets: vec![file.offset],
+                    carved: file,
+                });
+            }
+        }
+    }
+
+    Ok(CarveReport {
+        recoveries,
+        allocated_copies,
+        carved_count,
+    })
+}
@@ -0,0 +1,285 @@
+//! Classification of the content of unallocated clusters.
+//!
+//! Before carving a large volume, it pays to know what its free space holds. This module guesses
+//! the content of each unallocated cluster from its bytes alone (text, JPEG data, ZIP data,
+//! random-looking data, zeros) and sums the clusters of each class, with their location, so that
+//! the examiner carves the promising regions first.
+//!
+//! The classes are guesses: the middle of a ZIP file is deflated data, indistinguishable from
+//! encrypted data, and only the clusters holding a ZIP record are classified as ZIP data. Likewise,
+//! the clusters of a JPEG image holding no `FF` byte look random, which is frequent with clusters
+//! of 512 bytes.
+
+use getset::Getters;
+use std::fmt;
+
+use super::entropy::shannon_entropy;
+use crate::events::Event;
+use crate::filesystem::clusters::ClusterKind;
+use crate::filesystem::fat::{FATVol, contiguous_runs};
+use crate::filesystem::fat_error::FATError;
+
+/// Maximum number of free clusters read at once.
+const SCAN_RUN_LEN: u32 = 2048;
+
+/// Minimum share of printable bytes of a text cluster, its trailing zeros excluded.
+const TEXT_RATIO: f64 = 0.95;
+
+/// Entropy, in bits per byte, above which a cluster looks random. It is lower than the threshold
+/// of files, as the entropy of a few hundred random bytes is noticeably below 8.
+const RANDOM_ENTROPY_THRESHOLD: f64 = 7.2;
+
+/// Minimum entropy of the entropy-coded data of a JPEG image.
+const JPEG_ENTROPY_THRESHOLD: f64 = 6.5;
+
+/// Minimum number of `FF` bytes of JPEG data: the encoders avoid them, and a cluster without any
+/// can't be told apart from random data.
+const JPEG_MIN_MARKERS: usize = 1;
+
+/// Minimum share of the `FF` bytes of JPEG data followed by a stuffed `00` or a restart marker.
+const JPEG_STUFFING_RATIO: f64 = 0.9;
+
+/// The signatures of the records of a ZIP file: local file header, central directory header and
+/// end of central directory.
+const ZIP_RECORDS: [&[u8]; 3] = [b"PK\x03\x04", b"PK\x01\x02", b"PK\x05\x06"];
+
+/// Maximum number of cluster runs listed per class.
+const MAX_LISTED_RUNS: usize = 8;
+
+/// The content class of a cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentClass {
+    /// The header or the entropy-coded data of a JPEG image.
+    Jpeg,
+    /// A cluster holding a record of a ZIP file.
+    Zip,
+    /// Printable text, ASCII or UTF-8.
+    Text,
+    /// Random-looking data: encrypted or compressed.
+    Random,
+    /// Any other data, e.g. executables or structured binary files.
+    Binary,
+    /// Zeros only, e.g. never written or wiped.
+    Zeros,
+}
+
+impl ContentClass {
+    /// Every class, in carving priority order.
+    pub const ALL: [ContentClass; 6] = [
+        ContentClass::Jpeg,
+        ContentClass::Zip,
+        ContentClass::Text,
+        ContentClass::Random,
+        ContentClass::Binary,
+        ContentClass::Zeros,
+    ];
+}
+
+impl fmt::Display for ContentClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ContentClass::Jpeg => "JPEG data",
+            ContentClass::Zip => "ZIP data",
+            ContentClass::Text => "Text",
+            ContentClass::Random => "Random/encrypted",
+            ContentClass::Binary => "Other binary",
+            ContentClass::Zeros => "Zeros",
+        };
+        f.pad(name)
+    }
+}
+
+/// Guesses the content class of a cluster.
+///
+/// # Parameters
+/// - `data`: The content of the cluster.
+///
+/// # Returns
+/// - The class of the content, the most specific first: zeros, JPEG header, ZIP record, text,
+///   JPEG data, random data, and other binary data otherwise.
+pub fn classify_cluster(data: &[u8]) -> ContentClass {
+    // A text file or a JPEG image usually ends before its last cluster, the rest being zeros
+    let content = &data[..data.iter().rposition(|b| *b != 0).map_or(0, |pos| pos + 1)];
+    if content.is_empty() {
+        return ContentClass::Zeros;
+    }
+    if content.starts_with(b"\xFF\xD8\xFF") {
+        return ContentClass::Jpeg;
+    }
+    if ZIP_RECORDS.iter().any(|record| {
+        content
+            .windows(record.len())
+            .any(|window| window == *record)
+    }) {
+        return ContentClass::Zip;
+    }
+    if is_text(content) {
+        return ContentClass::Text;
+    }
+
+    let entropy = shannon_entropy(content);
+    if entropy >= JPEG_ENTROPY_THRESHOLD && is_jpeg_scan(content) {
+        ContentClass::Jpeg
+    } else if entropy >= RANDOM_ENTROPY_THRESHOLD {
+        ContentClass::Random
+    } else {
+        ContentClass::Binary
+    }
+}
+
+/// Checks whether a buffer is mostly printable text.
+fn is_text(data: &[u8]) -> bool {
+    let printable = match std::str::from_utf8(data) {
+        Ok(text) => {
+            text.chars()
+                .filter(|c| !c.is_control() || c.is_ascii_whitespace())
+                .count() as f64
+                / text.chars().count() as f64
+        }
+        Err(_) => {
+            data.iter()
+                .filter(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+                .count() as f64
+                / data.len() as f64
+        }
+    };
+    printable >= TEXT_RATIO
+}
+
+/// Checks whether a buffer looks like the entropy-coded data of a JPEG image.
+///
+/// An `FF` byte of such data is always followed by a stuffed `00`, a restart marker (`D0` to `D7`)
+/// or the end-of-image marker (`D9`), while it is followed by any byte in random data.
+fn is_jpeg_scan(data: &[u8]) -> bool {
+    let followers: Vec<u8> = data
+        .windows(2)
+        .filter(|pair| pair[0] == 0xFF)
+        .map(|pair| pair[1])
+        .collect();
+    let stuffed = followers
+        .iter()
+        .filter(|b| matches!(b, 0x00 | 0xD0..=0xD7 | 0xD9))
+        .count();
+    followers.len() >= JPEG_MIN_MARKERS
+        && stuffed as f64 >= JPEG_STUFFING_RATIO * followers.len() as f64
+}
+
+/// The unallocated clusters of a content class.
+#[derive(Debug, Getters)]
+pub struct ClassShare {
+    /// The content class.
+    #[get = "pub"]
+    class: ContentClass,
+    /// The clusters of the class, in order.
+    #[get = "pub"]
+    clusters: Vec<u32>,
+}
+
+/// The content classes of the unallocated clusters of a volume.
+#[derive(Debug, Getters)]
+pub struct FreeSpaceBreakdown {
+    /// The size of a cluster in bytes.
+    #[get = "pub"]
+    cluster_size: u64,
+    /// The number of unallocated clusters.
+    #[get = "pub"]
+    free_clusters: u64,
+    /// The clusters of each class found, in carving priority order.
+    #[get = "pub"]
+    shares: Vec<ClassShare>,
+}
+
+impl fmt::Display for FreeSpaceBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} unallocated clusters of {} bytes:",
+            self.free_clusters, self.cluster_size
+        )?;
+        for share in &self.shares {
+            let count = share.clusters.len() as u64;
+            let runs = contiguous_runs(&share.clusters, u32::MAX);
+            let mut listed: Vec<String> = runs
+                .iter()
+                .take(MAX_LISTED_RUNS)
+                .map(|(first, count)| match count {
+                    1 => first.to_string(),
+                    _ => format!("{}-{}", first, first + count - 1),
+                })
+                .collect();
+            if runs.len() > MAX_LISTED_RUNS {
+                listed.push(format!("(+{} runs)", runs.len() - MAX_LISTED_RUNS));
+            }
+            writeln!(
+                f,
+                "{:<17} {:>10} clusters {:>14} bytes {:>6.1}%  clusters {}",
+                share.class,
+                count,
+                count * self.cluster_size,
+                100.0 * count as f64 / self.free_clusters as f64,
+                listed.join(" ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Classifies the content of every unallocated cluster of the volume.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to scan.
+///
+/// # Returns
+/// - `Ok(FreeSpaceBreakdown)`: The clusters of each class found.
+/// - `Err(FATError)`: If the volume cannot be read.
+pub fn classify_free_space(vol: &FATVol) -> Result<FreeSpaceBreakdown, FATError> {
+    let free_clusters: Vec<u32> = vol
+        .clusters(ClusterKind::Free)?
+        .map(|region| *region.cluster())
+        .collect();
+    // The clusters in the holes of a sparse image are zeros, they aren't read
+    let data_clusters = vol.data_clusters(&free_clusters)?;
+    let cluster_size = vol.cluster_size() as u64;
+    let mut shares: Vec<ClassShare> = ContentClass::ALL
+        .iter()
+        .map(|class| ClassShare {
+            class: *class,
+            clusters: vec![],
+        })
+        .collect();
+    let mut scanned = 0;
+
+    for (first, count) in contiguous_runs(&free_clusters, SCAN_RUN_LEN) {
+        vol.check_cancelled()?;
+        let clusters: Vec<u32> = (first..first + count).collect();
+        let read: Vec<u32> = clusters
+            .iter()
+            .copied()
+            .filter(|cluster| data_clusters.binary_search(cluster).is_ok())
+            .collect();
+        let run = vol.read_chain(&read)?;
+        let mut data = run.chunks(cluster_size as usize);
+
+        for cluster in clusters {
+            let class = match read.binary_search(&cluster) {
+                Ok(_) => data.next().map_or(ContentClass::Zeros, classify_cluster),
+                Err(_) => ContentClass::Zeros,
+            };
+            shares[class as usize].clusters.push(cluster);
+        }
+
+        scanned += count as u64;
+        vol.emit(|| Event::Progress {
+            task: "free space classification",
+            done: scanned,
+            total: free_clusters.len() as u64,
+        });
+    }
+
+    shares.retain(|share| !share.clusters.is_empty());
+    Ok(FreeSpaceBreakdown {
+        cluster_size,
+        free_clusters: free_clusters.len() as u64,
+        shares,
+    })
+}
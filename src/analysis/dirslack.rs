@@ -0,0 +1,133 @@
+//! Analysis of directory slack.
+//!
+//! A directory ends at its first entry starting with a 0 byte, but its clusters go on to their
+//! end. When a directory shrinks, or when its clusters held another directory before, the slots
+//! past the end marker may still hold the entries of deleted files, invisible to the tools that
+//! stop at the marker.
+
+use getset::Getters;
+use std::fmt;
+use std::path::PathBuf;
+
+use super::Provenance;
+use crate::filesystem::dir_entry::DirEntry;
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::utils::escape_path;
+
+/// A stale entry found past the end of a directory.
+#[derive(Debug, Getters)]
+pub struct RemnantEntry {
+    /// The path of the directory, relative to the root directory.
+    #[get = "pub"]
+    dir: PathBuf,
+    /// The stale entry.
+    #[get = "pub"]
+    entry: DirEntry,
+    /// Absolute byte offset of the entry on disk.
+    #[get = "pub"]
+    offset: u64,
+    /// Whether the first cluster of the entry is still free, so that its data may be recovered.
+    #[get = "pub"]
+    recoverable: bool,
+    /// Where the entry lies in the image.
+    #[get = "pub"]
+    provenance: Provenance,
+}
+
+impl fmt::Display for RemnantEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.dir.join(self.entry.short_name());
+        write!(
+            f,
+            "0x{:X} /{} ({} bytes from cluster {}, {}): {}",
+            self.offset,
+            escape_path(&name),
+            self.entry.file_size(),
+            self.entry.cluster_number(),
+            match self.entry.is_dir() {
+                true => "directory",
+                false => "file",
+            },
+            match (self.entry.cluster_number(), self.recoverable) {
+                (0, _) => "no data",
+                (_, true) => "first cluster free, recoverable",
+                (_, false) => "first cluster reallocated",
+            }
+        )
+    }
+}
+
+/// Parses the slack of every directory, past its end marker, for remnant entries.
+///
+/// Only the short entries consistent with a directory entry are reported, long name entries and
+/// the "." and ".." entries are skipped.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to scan.
+///
+/// # Returns
+/// - `Ok(Vec<RemnantEntry>)`: The remnant entries, directory by directory, in on-disk order.
+/// - `Err(FATError)`: If the directory tree or the FAT cannot be read.
+pub fn scan_dir_slack(vol: &FATVol) -> Result<Vec<RemnantEntry>, FATError> {
+    let fat = vol.fat_entries()?;
+    let mut dirs = vec![(PathBuf::new(), vol.root_cluster()?)];
+    dirs.extend(
+        vol.walk_entries()?
+            .into_iter()
+            .filter(|(_, entry)| entry.is_regular_dir())
+            .map(|(path, entry)| (path, entry.cluster_number())),
+    );
+
+    let slot_size = DirEntry::SIZE as usize;
+    let cluster_size = vol.cluster_size() as usize;
+    let mut remnants = vec![];
+    for (dir, cluster) in dirs {
+        vol.check_cancelled()?;
+        // The fixed root directory of FAT12/16 volumes is a single block, numbered cluster 0
+        let (offsets, data, block_len) = match (cluster, vol.root_dir_extent()) {
+            (0, Some((offset, len))) => (
+                vec![offset],
+                vol.read_bytes(offset, len as usize)?,
+                len.max(1) as usize,
+            ),
+            _ => {
+                let chain = vol.cluster_chain(cluster).collect::<Result<Vec<_>, _>>()?;
+                (
+                    chain.iter().map(|c| vol.cluster_offset(*c)).collect(),
+                    vol.read_chain(&chain)?,
+                    cluster_size,
+                )
+            }
+        };
+        let Some(end) = (0..data.len())
+            .step_by(slot_size)
+            .find(|off| data[*off] == 0)
+        else {
+            continue;
+        };
+
+        for off in (end + slot_size..data.len()).step_by(slot_size) {
+            let slot = &data[off..off + slot_size];
+            if slot.iter().all(|b| *b == 0) {
+                continue;
+            }
+            let entry = DirEntry::from_slice(slot)?;
+            if !entry.is_plausible() || entry.is_long_name() || entry.is_dot() {
+                continue;
+            }
+
+            let offset = offsets[off / block_len] + (off % block_len) as u64;
+            let first = entry.cluster_number();
+            remnants.push(RemnantEntry {
+                recoverable: first != 0 && fat.get(first as usize) == Some(&0),
+                provenance: Provenance::from_extents(vol, vec![(offset, slot_size as u64)]),
+                dir: dir.clone(),
+                entry,
+                offset,
+            });
+        }
+    }
+
+    Ok(remnants)
+}
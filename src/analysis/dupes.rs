@@ -0,0 +1,117 @@
+//! Detection of duplicate files.
+//!
+//! Copying a file under an innocuous name, or to a second image, is a cheap way to keep it around
+//! once the original is deleted. Files are grouped by size first, and only the files sharing their
+//! size with another one are read and hashed with SHA-256.
+
+use getset::Getters;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use super::hash::HashAlgorithm;
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::utils::escape_path;
+
+/// A file of a volume.
+#[derive(Debug, Clone, Getters)]
+pub struct FileLocation {
+    /// The name given to the volume holding the file.
+    #[get = "pub"]
+    volume: String,
+    /// The path of the file, relative to the root directory.
+    #[get = "pub"]
+    path: PathBuf,
+}
+
+impl fmt::Display for FileLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:/{}", self.volume, escape_path(&self.path))
+    }
+}
+
+/// Files with the same content.
+#[derive(Debug, Getters)]
+pub struct DuplicateSet {
+    /// The size of each file in bytes.
+    #[get = "pub"]
+    size: u64,
+    /// The hexadecimal SHA-256 digest of the content.
+    #[get = "pub"]
+    sha256: String,
+    /// The files, in the order the volumes were walked.
+    #[get = "pub"]
+    files: Vec<FileLocation>,
+}
+
+impl fmt::Display for DuplicateSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} copies of {} bytes (sha256 {})",
+            self.files.len(),
+            self.size,
+            self.sha256
+        )?;
+        for file in &self.files {
+            write!(f, "\n  {file}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Groups the files of one or several volumes by content.
+///
+/// Empty files all share the same content and are ignored.
+///
+/// # Parameters
+/// - `volumes`: The volumes to search, each with the name its files are reported under.
+///
+/// # Returns
+/// - `Ok(Vec<DuplicateSet>)`: The sets of at least two files with the same content, largest
+///   files first.
+/// - `Err(FATError)`: If a directory tree or a file cannot be read.
+pub fn find_duplicates<'a>(
+    volumes: impl IntoIterator<Item = (String, &'a FATVol)>,
+) -> Result<Vec<DuplicateSet>, FATError> {
+    let mut by_size: HashMap<u64, Vec<(&FATVol, FileLocation, _)>> = HashMap::new();
+    for (name, vol) in volumes {
+        for (path, entry) in vol.walk_files()? {
+            if *entry.file_size() == 0 {
+                continue;
+            }
+            let location = FileLocation {
+                volume: name.clone(),
+                path,
+            };
+            by_size
+                .entry(*entry.file_size() as u64)
+                .or_default()
+                .push((vol, location, entry));
+        }
+    }
+
+    let mut sets = vec![];
+    for (size, files) in by_size.into_iter().filter(|(_, files)| files.len() > 1) {
+        let mut by_hash: HashMap<String, Vec<FileLocation>> = HashMap::new();
+        for (vol, location, entry) in files {
+            vol.check_cancelled()?;
+            let digest = HashAlgorithm::Sha256.hex_digest(&vol.read_entry(&entry)?);
+            by_hash.entry(digest).or_default().push(location);
+        }
+        sets.extend(
+            by_hash
+                .into_iter()
+                .filter(|(_, files)| files.len() > 1)
+                .map(|(sha256, files)| DuplicateSet {
+                    size,
+                    sha256,
+                    files,
+                }),
+        );
+    }
+    sets.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.sha256.cmp(&b.sha256)));
+
+    Ok(sets)
+}
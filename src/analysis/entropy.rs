@@ -0,0 +1,113 @@
+//! Per-file entropy measurement.
+//!
+//! Encrypted and compressed data look random: their Shannon entropy is close to 8 bits per byte.
+//! Archives and compressed media are expected to be high-entropy, but a `.TXT` or `.DAT` file that
+//! is likely hides an encrypted container or a packed payload.
+
+use getset::Getters;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::utils::escape_path;
+
+/// Entropy, in bits per byte, above which a file is considered encrypted or compressed.
+pub const HIGH_ENTROPY_THRESHOLD: f64 = 7.5;
+
+/// Files smaller than this cannot reach a high entropy and are never flagged.
+const MIN_FLAGGED_SIZE: usize = 1024;
+
+/// The extensions of the formats that are compressed by design, in uppercase.
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "ZIP", "RAR", "7Z", "GZ", "TGZ", "BZ2", "XZ", "ZST", "CAB", "JAR", "APK", "DOCX", "XLSX",
+    "PPTX", "ODT", "ODS", "EPUB", "JPG", "JPEG", "PNG", "GIF", "WEBP", "MP3", "MP4", "M4A", "AVI",
+    "MOV", "MKV", "OGG", "FLAC", "PDF",
+];
+
+/// Computes the Shannon entropy of a buffer.
+///
+/// # Returns
+/// - The entropy in bits per byte, from 0 (constant data) to 8 (uniformly random data).
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    data.iter().for_each(|b| counts[*b as usize] += 1);
+
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+/// The entropy of a file of the volume.
+#[derive(Debug, Getters)]
+pub struct FileEntropy {
+    /// The path of the file.
+    #[get = "pub"]
+    path: PathBuf,
+    /// The size of the file in bytes.
+    #[get = "pub"]
+    size: u32,
+    /// The entropy of the content of the file, in bits per byte.
+    #[get = "pub"]
+    entropy: f64,
+    /// Whether the file is high-entropy without using the extension of a compressed format.
+    #[get = "pub"]
+    flagged: bool,
+}
+
+impl fmt::Display for FileEntropy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.3} {:>10} {}",
+            self.entropy,
+            self.size,
+            escape_path(&self.path)
+        )?;
+        if self.flagged {
+            write!(f, " (high entropy: likely encrypted or packed)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Measures the entropy of every file of the volume.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to inspect.
+///
+/// # Returns
+/// - `Ok(Vec<FileEntropy>)`: The entropy of each file, in walk order.
+/// - `Err(FATError)`: If a file cannot be read.
+pub fn file_entropies(vol: &FATVol) -> Result<Vec<FileEntropy>, FATError> {
+    let mut entropies = vec![];
+    for (path, entry) in vol.walk_files()? {
+        vol.check_cancelled()?;
+        let data = vol.read_entry(&entry)?;
+        let entropy = shannon_entropy(&data);
+
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        let flagged = data.len() >= MIN_FLAGGED_SIZE
+            && entropy >= HIGH_ENTROPY_THRESHOLD
+            && !COMPRESSED_EXTENSIONS.contains(&extension.as_str());
+
+        entropies.push(FileEntropy {
+            path,
+            size: *entry.file_size(),
+            entropy,
+            flagged,
+        });
+    }
+
+    Ok(entropies)
+}
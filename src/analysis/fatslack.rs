@@ -0,0 +1,123 @@
+//! Analysis of FAT slack.
+//!
+//! A FAT is a whole number of sectors, so it usually holds more entries than the data region has
+//! clusters, and formatting tools often oversize it. The entries past the last cluster are never
+//! used by the filesystem: they stay zero on a genuine volume, and any other value there is data
+//! hidden in the allocation table.
+
+use getset::Getters;
+use std::fmt;
+
+use super::Provenance;
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+
+/// The number of entries read at once.
+const READ_LEN: u64 = 16 * 1024;
+
+/// The number of values shown when displaying a run.
+const SHOWN_VALUES: usize = 8;
+
+/// A run of consecutive non-zero entries past the last cluster of a FAT.
+#[derive(Debug, Getters)]
+pub struct FatSlackRun {
+    /// The number of the FAT holding the run, from 0.
+    #[get = "pub"]
+    fat_nb: u8,
+    /// The cluster number the first entry of the run would map.
+    #[get = "pub"]
+    first_cluster: u32,
+    /// The raw values of the entries, in order, reserved bits included.
+    #[get = "pub"]
+    values: Vec<u32>,
+    /// Where the run lies in the image.
+    #[get = "pub"]
+    provenance: Provenance,
+}
+
+impl fmt::Display for FatSlackRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offset = self
+            .provenance
+            .extents()
+            .first()
+            .map_or(0, |(offset, _)| *offset);
+        let values: Vec<String> = self
+            .values
+            .iter()
+            .take(SHOWN_VALUES)
+            .map(|value| format!("0x{value:08X}"))
+            .collect();
+        write!(
+            f,
+            "0x{:X} FAT #{} entries {}-{} ({} non-zero): {}",
+            offset,
+            self.fat_nb,
+            self.first_cluster,
+            self.first_cluster as u64 + self.values.len() as u64 - 1,
+            self.values.len(),
+            values.join(" ")
+        )?;
+        if self.values.len() > SHOWN_VALUES {
+            write!(f, " ...")?;
+        }
+        Ok(())
+    }
+}
+
+/// Scans the entries of every FAT past the last cluster of the data region for non-zero values.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to scan.
+///
+/// # Returns
+/// - `Ok(Vec<FatSlackRun>)`: The runs of non-zero entries, FAT by FAT, in on-disk order.
+/// - `Err(FATError)`: If a FAT cannot be read.
+pub fn scan_fat_slack(vol: &FATVol) -> Result<Vec<FatSlackRun>, FATError> {
+    let bits = vol.fat_entry_bit_sz() as u64;
+    let first = vol.cluster_count() as u64 + 2;
+    let end = vol.fat_size() * 8 / bits;
+    // The runs found, as (FAT, first cluster, values)
+    let mut runs: Vec<(u8, u32, Vec<u32>)> = vec![];
+
+    for fat_nb in 0..vol.num_fat() {
+        let mut cluster = first;
+        let mut in_run = false;
+        while cluster < end {
+            vol.check_cancelled()?;
+            let count = READ_LEN.min(end - cluster);
+            let offset = vol.fat_entry_offset(cluster as u32, fat_nb);
+            let buf = vol.read_bytes(offset, (count * bits).div_ceil(8) as usize + 1)?;
+            for i in 0..count {
+                let entry_nb = (cluster + i) as u32;
+                let off = vol.fat_entry_offset(entry_nb, fat_nb) - offset;
+                let value = vol.decode_fat_entry(&buf[off as usize..], entry_nb);
+                match (value, runs.last_mut()) {
+                    (0, _) => in_run = false,
+                    (_, Some((_, _, values))) if in_run => values.push(value),
+                    _ => {
+                        runs.push((fat_nb, entry_nb, vec![value]));
+                        in_run = true;
+                    }
+                }
+            }
+            cluster += count;
+        }
+    }
+
+    Ok(runs
+        .into_iter()
+        .map(|(fat_nb, first_cluster, values)| FatSlackRun {
+            provenance: Provenance::from_extents(
+                vol,
+                vec![(
+                    vol.fat_entry_offset(first_cluster, fat_nb),
+                    (values.len() as u64 * bits).div_ceil(8),
+                )],
+            ),
+            fat_nb,
+            first_cluster,
+            values,
+        })
+        .collect())
+}
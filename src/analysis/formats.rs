@@ -0,0 +1,181 @@
+//! Length computation of common file formats.
+//!
+//! Carved data and file content can continue past the real end of a file: cluster padding, the
+//! next file, or an appended payload. These parsers follow the structure of each format to find
+//! its true end.
+//!
+//! Every function takes the data starting at the header of the file and returns the offset
+//! following its last byte, or `None` if the data isn't a complete file of the format.
+
+/// A function computing the length of a file of a format, from the data starting at its header.
+pub type EndParser = fn(&[u8]) -> Option<usize>;
+
+/// The magic bytes of a PNG image.
+pub const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1A\n";
+
+const ZIP_EOCD_MAGIC: &[u8] = b"PK\x05\x06";
+
+/// Size of the End Of Central Directory record of a ZIP archive, without its comment.
+const ZIP_EOCD_LEN: usize = 22;
+
+/// Returns the offset following the End Of Image marker of a JPEG image.
+///
+/// Markers are followed segment by segment, so the end markers of embedded thumbnails are skipped.
+pub fn jpeg_end(data: &[u8]) -> Option<usize> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            0xD9 => return Some(pos + 2),
+            // Fill byte
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                pos += 2;
+                continue;
+            }
+            _ => {}
+        }
+        let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+        pos += 2 + len;
+
+        // Start Of Scan: skip the entropy-coded data up to the next marker
+        if marker == 0xDA {
+            loop {
+                if *data.get(pos)? == 0xFF {
+                    match *data.get(pos + 1)? {
+                        0x00 | 0xD0..=0xD7 => pos += 1,
+                        0xFF => {}
+                        _ => break,
+                    }
+                }
+                pos += 1;
+            }
+        }
+    }
+}
+
+/// Returns the offset following the IEND chunk of a PNG image.
+pub fn png_end(data: &[u8]) -> Option<usize> {
+    if !data.starts_with(PNG_MAGIC) {
+        return None;
+    }
+
+    let mut pos = PNG_MAGIC.len();
+    loop {
+        let len = u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let chunk_type = data.get(pos + 4..pos + 8)?;
+        // Length, type, data and CRC
+        pos = pos.checked_add(12 + len)?;
+        if chunk_type == b"IEND" {
+            return (pos <= data.len()).then_some(pos);
+        }
+    }
+}
+
+/// Returns the offset following the trailer of a GIF image.
+///
+/// Blocks are followed one by one, so a `3B` byte inside image data isn't taken for the trailer.
+pub fn gif_end(data: &[u8]) -> Option<usize> {
+    if !data.starts_with(b"GIF87a") && !data.starts_with(b"GIF89a") {
+        return None;
+    }
+
+    // Header and logical screen descriptor, followed by the global color table if any
+    let flags = *data.get(10)?;
+    let mut pos = 13 + color_table_len(flags);
+    loop {
+        match *data.get(pos)? {
+            // Trailer
+            0x3B => return Some(pos + 1),
+            // Extension: label, then sub-blocks
+            0x21 => pos = skip_sub_blocks(data, pos + 2)?,
+            // Image descriptor, local color table, LZW minimum code size, then sub-blocks
+            0x2C => {
+                let flags = *data.get(pos + 9)?;
+                pos = skip_sub_blocks(data, pos + 10 + color_table_len(flags) + 1)?;
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Returns the size of the color table described by the flags of a GIF descriptor.
+fn color_table_len(flags: u8) -> usize {
+    match flags & 0x80 {
+        0 => 0,
+        _ => 3 << ((flags & 0x07) + 1),
+    }
+}
+
+/// Returns the offset following a chain of GIF sub-blocks, ended by an empty one.
+fn skip_sub_blocks(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *data.get(pos)? as usize;
+        pos += 1 + len;
+        if len == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+/// Returns the offset following the End Of Central Directory record of a ZIP archive.
+///
+/// A record only ends the archive if the central directory it describes lies right before it,
+/// which rules out the records of nested archives.
+pub fn zip_end(data: &[u8]) -> Option<usize> {
+    if !data.starts_with(b"PK\x03\x04") {
+        return None;
+    }
+
+    data.windows(ZIP_EOCD_MAGIC.len())
+        .enumerate()
+        .filter(|(_, window)| *window == ZIP_EOCD_MAGIC)
+        .find_map(|(pos, _)| {
+            let record = data.get(pos..pos + ZIP_EOCD_LEN)?;
+            let u32_at = |i: usize| u32::from_le_bytes(record[i..i + 4].try_into().unwrap());
+            let cd_len = u32_at(12) as usize;
+            let cd_offset = u32_at(16) as usize;
+            let comment_len = u16::from_le_bytes([record[20], record[21]]) as usize;
+
+            let end = pos + ZIP_EOCD_LEN + comment_len;
+            (cd_offset.checked_add(cd_len)? == pos && end <= data.len()).then_some(end)
+        })
+}
+
+/// Returns the offset following the last `%%EOF` marker of a PDF document.
+///
+/// Incremental updates append a new `%%EOF` each, so the last marker found before the header of
+/// the next document ends the file. The end-of-line following the marker is kept.
+pub fn pdf_end(data: &[u8]) -> Option<usize> {
+    if !data.starts_with(b"%PDF-") {
+        return None;
+    }
+
+    let limit = data
+        .windows(5)
+        .skip(1)
+        .position(|window| window == b"%PDF-")
+        .map_or(data.len(), |pos| pos + 1);
+    let marker = data[..limit]
+        .windows(5)
+        .rposition(|window| window == b"%%EOF")?;
+
+    let mut end = marker + 5;
+    for eol in [b'\r', b'\n'] {
+        if data.get(end) == Some(&eol) {
+            end += 1;
+        }
+    }
+    Some(end)
+}
@@ -0,0 +1,119 @@
+//! Detection of data hidden between the MBR and the first partition.
+//!
+//! Partitioning tools align the first partition, leaving the sectors after the MBR unused. They are
+//! zeroed on a fresh disk and invisible to every file system, which makes them a classic hiding
+//! place. Boot loaders also store code there (e.g. the core image of GRUB), so non-zero content
+//! is a lead to examine rather than proof of hidden data.
+
+use getset::Getters;
+use std::fmt;
+
+use crate::filesystem::fat::FATVol;
+use crate::partition::disk::Disk;
+use crate::partition::disk_error::DiskError;
+use crate::partition::mbr::Mbr;
+
+/// The content of the gap between the MBR and the first partition.
+#[derive(Debug, Getters)]
+pub struct MbrGap {
+    /// The absolute byte offset of the gap.
+    #[get = "pub"]
+    offset: u64,
+    /// The length of the gap in bytes.
+    #[get = "pub"]
+    len: u64,
+    /// The runs of sectors holding non-zero bytes: first sector and number of sectors.
+    #[get = "pub"]
+    sectors: Vec<(u64, u64)>,
+    /// The absolute byte offset of the first non-zero byte of the gap.
+    #[get = "pub"]
+    data_offset: u64,
+    /// The content of the gap from its first to its last non-zero byte, empty if the gap is zeroed.
+    #[get = "pub"]
+    data: Vec<u8>,
+}
+
+impl MbrGap {
+    /// Returns whether the gap holds non-zero bytes.
+    pub fn holds_data(&self) -> bool {
+        !self.data.is_empty()
+    }
+}
+
+impl fmt::Display for MbrGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Gap between the MBR and the first partition: 0x{:X}-0x{:X} ({} bytes)",
+            self.offset,
+            self.offset + self.len,
+            self.len
+        )?;
+        if !self.holds_data() {
+            return write!(f, "\nThe gap is zeroed.");
+        }
+
+        let sectors: Vec<String> = self
+            .sectors
+            .iter()
+            .map(|(first, count)| match count {
+                1 => format!("{first}"),
+                _ => format!("{first}-{}", first + count - 1),
+            })
+            .collect();
+        write!(
+            f,
+            "\n{} byte(s) of data at 0x{:X}-0x{:X}, in sector(s) {}",
+            self.data.len(),
+            self.data_offset,
+            self.data_offset + self.data.len() as u64,
+            sectors.join(", ")
+        )
+    }
+}
+
+/// Reads the gap between the MBR and the first partition, and locates its non-zero content.
+///
+/// # Parameters
+/// - `disk`: The disk to inspect.
+///
+/// # Returns
+/// - `Ok(Some(MbrGap))`: The content of the gap.
+/// - `Ok(None)`: If the disk has no partition, or its first partition follows the MBR.
+/// - `Err(DiskError)`: If the gap cannot be read.
+pub fn scan_mbr_gap(disk: &Disk<FATVol, Mbr>) -> Result<Option<MbrGap>, DiskError> {
+    let Some((first, count)) = disk.part_table().post_mbr_gap() else {
+        return Ok(None);
+    };
+    let sector_size = *disk.sector_size() as u64;
+    let offset = first * sector_size;
+    let len = count * sector_size;
+    let gap = disk.image().read_at(offset, len as usize)?;
+
+    let mut sectors: Vec<(u64, u64)> = vec![];
+    for (i, sector) in gap.chunks(sector_size as usize).enumerate() {
+        if sector.iter().all(|b| *b == 0) {
+            continue;
+        }
+        let sector_nb = first + i as u64;
+        match sectors.last_mut() {
+            Some((run_first, run_count)) if *run_first + *run_count == sector_nb => *run_count += 1,
+            _ => sectors.push((sector_nb, 1)),
+        }
+    }
+
+    let start = gap.iter().position(|b| *b != 0);
+    let end = gap.iter().rposition(|b| *b != 0);
+    let (data_offset, data) = match (start, end) {
+        (Some(start), Some(end)) => (offset + start as u64, gap[start..=end].to_vec()),
+        _ => (offset, vec![]),
+    };
+
+    Ok(Some(MbrGap {
+        offset,
+        len,
+        sectors,
+        data_offset,
+        data,
+    }))
+}
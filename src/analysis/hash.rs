@@ -0,0 +1,329 @@
+//! Piecewise hashing of files and regions.
+//!
+//! A full-file hash changes as soon as a single byte is overwritten. Hashing fixed-size blocks
+//! instead (hashdeep's piecewise mode) lets a partially overwritten file, or a fragment of it, be
+//! matched against reference data block by block.
+//!
+//! Whole files are also hashed into hashdeep manifests, so that a volume can be verified against
+//! other tools, or re-verified later.
+
+use getset::Getters;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::filesystem::fat::{DEFAULT_COPY_BUFFER_LEN, FATVol};
+use crate::filesystem::fat_error::FATError;
+
+/// Hash algorithm used to digest blocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Md5,
+    #[default]
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Returns the lowercase hexadecimal digest of the data.
+    pub fn hex_digest(&self, data: &[u8]) -> String {
+        let digest = match self {
+            HashAlgorithm::Md5 => Md5::digest(data).to_vec(),
+            HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        };
+        hex(&digest)
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgorithm::Md5 => write!(f, "md5"),
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+/// The digest of a block of data.
+#[derive(Debug, Clone, Getters)]
+pub struct BlockHash {
+    /// Offset of the block from the start of the hashed data.
+    #[get = "pub"]
+    offset: u64,
+    /// Length of the block in bytes. Only the last block can be shorter than the block size.
+    #[get = "pub"]
+    len: u64,
+    /// Hexadecimal digest of the block.
+    #[get = "pub"]
+    digest: String,
+}
+
+impl fmt::Display for BlockHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} {}",
+            self.offset,
+            self.offset + self.len - 1,
+            self.digest
+        )
+    }
+}
+
+/// The digests of a whole file, as listed in a hashdeep manifest.
+#[derive(Debug, Clone, Getters)]
+pub struct FileDigest {
+    /// The path of the file, relative to the root directory.
+    #[get = "pub"]
+    path: PathBuf,
+    /// The size of the file in bytes.
+    #[get = "pub"]
+    size: u64,
+    /// The hexadecimal MD5 digest of the file.
+    #[get = "pub"]
+    md5: String,
+    /// The hexadecimal SHA-256 digest of the file.
+    #[get = "pub"]
+    sha256: String,
+}
+
+impl fmt::Display for FileDigest {
+    /// Formats the digests as a line of a hashdeep manifest.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.size,
+            self.md5,
+            self.sha256,
+            self.path.to_string_lossy().replace('\\', "/")
+        )
+    }
+}
+
+/// Hashes every regular file of a volume with MD5 and SHA-256.
+///
+/// # Parameters
+/// - `vol`: The volume whose files are hashed.
+///
+/// # Returns
+/// - `Ok(Vec<FileDigest>)`: The digests of each file, in directory tree order.
+/// - `Err(FATError)`: If the directory tree or a file cannot be read.
+pub fn hash_all_files(vol: &FATVol) -> Result<Vec<FileDigest>, FATError> {
+    vol.walk_files()?
+        .into_iter()
+        .map(|(path, entry)| {
+            vol.check_cancelled()?;
+            let mut hasher = FileHasher::default();
+            let size = vol.copy_entry(&entry, &mut hasher, DEFAULT_COPY_BUFFER_LEN, |_, _| {})?;
+            Ok(FileDigest {
+                path,
+                size,
+                md5: hex(&hasher.md5.finalize()),
+                sha256: hex(&hasher.sha256.finalize()),
+            })
+        })
+        .collect()
+}
+
+/// Computes the MD5 and SHA-256 digests of a file streamed into it.
+#[derive(Default)]
+struct FileHasher {
+    md5: Md5,
+    sha256: Sha256,
+}
+
+impl Write for FileHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.md5.update(buf);
+        self.sha256.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Encodes a digest as lowercase hexadecimal.
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Writes digests as a hashdeep manifest, which `hashdeep -a -k` and `md5deep -m` accept as known
+/// hashes.
+///
+/// # Parameters
+/// - `digests`: The digests of the files.
+/// - `invocation`: The command that produced the digests, recorded in the header.
+/// - `out`: The sink the manifest is written to.
+///
+/// # Returns
+/// - `Ok(())`: If the manifest is written.
+/// - `Err(io::Error)`: If writing fails.
+pub fn write_hashdeep_manifest<W: Write>(
+    digests: &[FileDigest],
+    invocation: &str,
+    mut out: W,
+) -> io::Result<()> {
+    writeln!(out, "%%%% HASHDEEP-1.0")?;
+    writeln!(out, "%%%% size,md5,sha256,filename")?;
+    writeln!(out, "## Invoked from: {}", env!("CARGO_PKG_NAME"))?;
+    writeln!(out, "## $ {invocation}")?;
+    writeln!(out, "##")?;
+    for digest in digests {
+        writeln!(out, "{digest}")?;
+    }
+    out.flush()
+}
+
+/// Hashes a buffer block by block.
+///
+/// # Parameters
+/// - `data`: The data to hash.
+/// - `block_size`: The size in bytes of a block. Must be greater than 0.
+/// - `algorithm`: The hash algorithm.
+///
+/// # Returns
+/// - One digest per block, in order.
+pub fn piecewise_hashes(
+    data: &[u8],
+    block_size: usize,
+    algorithm: HashAlgorithm,
+) -> Vec<BlockHash> {
+    data.chunks(block_size.max(1))
+        .enumerate()
+        .map(|(i, block)| BlockHash {
+            offset: (i * block_size.max(1)) as u64,
+            len: block.len() as u64,
+            digest: algorithm.hex_digest(block),
+        })
+        .collect()
+}
+
+/// Hashes the content of a file block by block.
+///
+/// # Parameters
+/// - `vol`: The FAT volume containing the file.
+/// - `file_path`: The path of the file.
+/// - `block_size`: The size in bytes of a block. Defaults to the cluster size, so that each block
+///   maps to one cluster of the file.
+/// - `algorithm`: The hash algorithm.
+///
+/// # Returns
+/// - `Ok(Vec<BlockHash>)`: One digest per block, with offsets relative to the start of the file.
+/// - `Err(FATError)`: If the file cannot be read.
+pub fn hash_file(
+    vol: &FATVol,
+    file_path: &Path,
+    block_size: Option<usize>,
+    algorithm: HashAlgorithm,
+) -> Result<Vec<BlockHash>, FATError> {
+    let data = vol.read_file(file_path)?;
+    let block_size = block_size.unwrap_or(vol.cluster_size() as usize);
+    Ok(piecewise_hashes(&data, block_size, algorithm))
+}
+
+/// Hashes a region of the disk block by block.
+///
+/// The blocks lying entirely in holes of a sparse image aren't read: they hold zeros, whose digest
+/// is computed once.
+///
+/// # Parameters
+/// - `vol`: The FAT volume containing the region.
+/// - `offset`: The absolute byte offset of the region.
+/// - `len`: The length of the region in bytes.
+/// - `block_size`: The size in bytes of a block. Defaults to the cluster size.
+/// - `algorithm`: The hash algorithm.
+///
+/// # Returns
+/// - `Ok(Vec<BlockHash>)`: One digest per block, with offsets relative to the start of the region.
+/// - `Err(FATError)`: If the region cannot be read.
+pub fn hash_region(
+    vol: &FATVol,
+    offset: u64,
+    len: usize,
+    block_size: Option<usize>,
+    algorithm: HashAlgorithm,
+) -> Result<Vec<BlockHash>, FATError> {
+    let block_size = block_size.unwrap_or(vol.cluster_size() as usize).max(1);
+    let extents = vol.data_extents(offset, len as u64)?;
+    let holds_data = |block: usize| {
+        let start = offset + (block * block_size) as u64;
+        let idx = extents.partition_point(|(ext_start, ext_len)| ext_start + ext_len <= start);
+        extents
+            .get(idx)
+            .is_some_and(|(ext_start, _)| *ext_start < start + block_size as u64)
+    };
+
+    let block_cnt = len.div_ceil(block_size);
+    let mut hashes = Vec::with_capacity(block_cnt);
+    let mut zero_digest = None;
+    let mut block = 0;
+    // Blocks are handled in runs of data blocks, read at once, and runs of hole blocks
+    while block < block_cnt {
+        let data = holds_data(block);
+        let run_end = (block..block_cnt)
+            .find(|next| holds_data(*next) != data)
+            .unwrap_or(block_cnt);
+        let start = block * block_size;
+        let end = (run_end * block_size).min(len);
+
+        if data {
+            vol.check_cancelled()?;
+            let bytes = vol.read_bytes(offset + start as u64, end - start)?;
+            hashes.extend(
+                piecewise_hashes(&bytes, block_size, algorithm)
+                    .into_iter()
+                    .map(|hash| BlockHash {
+                        offset: hash.offset + start as u64,
+                        ..hash
+                    }),
+            );
+        } else {
+            for hole in block..run_end {
+                let block_len = block_size.min(len - hole * block_size);
+                let digest = match block_len == block_size {
+                    true => zero_digest
+                        .get_or_insert_with(|| algorithm.hex_digest(&vec![0; block_size]))
+                        .clone(),
+                    false => algorithm.hex_digest(&vec![0; block_len]),
+                };
+                hashes.push(BlockHash {
+                    offset: (hole * block_size) as u64,
+                    len: block_len as u64,
+                    digest,
+                });
+            }
+        }
+        block = run_end;
+    }
+
+    Ok(hashes)
+}
+
+/// Matches the blocks of a candidate against the blocks of a reference.
+///
+/// Blocks are matched by digest regardless of their position, so a shifted or partially
+/// overwritten copy still matches the blocks it kept intact.
+///
+/// # Parameters
+/// - `reference`: The block hashes of the reference data.
+/// - `candidate`: The block hashes of the data to match.
+///
+/// # Returns
+/// - The `(candidate, reference)` indices of each matching block, in candidate order.
+pub fn match_blocks(reference: &[BlockHash], candidate: &[BlockHash]) -> Vec<(usize, usize)> {
+    let mut by_digest: HashMap<&str, usize> = HashMap::new();
+    for (i, block) in reference.iter().enumerate() {
+        by_digest.entry(&block.digest).or_insert(i);
+    }
+
+    candidate
+        .iter()
+        .enumerate()
+        .filter_map(|(i, block)| by_digest.get(block.digest.as_str()).map(|j| (i, *j)))
+        .collect()
+}
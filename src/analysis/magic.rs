@@ -0,0 +1,319 @@
+//! File type identification from magic bytes.
+//!
+//! Renaming a file with an innocuous extension (e.g. a JPEG saved as `HOMEWORK.TXT`) is a classic
+//! hiding technique. This module identifies the type of each file from its header, like the
+//! `file` utility, and flags the files whose extension disagrees with their content.
+
+use getset::Getters;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::formats::{EndParser, gif_end, jpeg_end, pdf_end, png_end, zip_end};
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::utils::escape_path;
+
+/// A file format recognised by its magic bytes.
+#[derive(Debug)]
+pub struct FileSignature {
+    /// The name of the format.
+    pub name: &'static str,
+    /// The offset of the magic bytes from the start of the file.
+    pub offset: usize,
+    /// The magic bytes.
+    pub magic: &'static [u8],
+    /// The extensions used by files of this format, in uppercase.
+    pub extensions: &'static [&'static str],
+    /// Computes the length of a file from its structure, see [`super::formats`], for the
+    /// formats whose end can be checked.
+    pub end: Option<EndParser>,
+}
+
+/// Signatures are compared by format, their end parsers being functions without a meaningful
+/// equality.
+impl PartialEq for FileSignature {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.offset == other.offset
+            && self.magic == other.magic
+            && self.extensions == other.extensions
+    }
+}
+
+impl Eq for FileSignature {}
+
+/// The formats recognised by [`identify`], most specific first.
+pub const SIGNATURES: &[FileSignature] = &[
+    FileSignature {
+        name: "JPEG image",
+        offset: 0,
+        magic: b"\xFF\xD8\xFF",
+        extensions: &["JPG", "JPEG", "JPE", "JFIF"],
+        end: Some(jpeg_end),
+    },
+    FileSignature {
+        name: "PNG image",
+        offset: 0,
+        magic: b"\x89PNG\r\n\x1A\n",
+        extensions: &["PNG"],
+        end: Some(png_end),
+    },
+    FileSignature {
+        name: "GIF image",
+        offset: 0,
+        magic: b"GIF8",
+        extensions: &["GIF"],
+        end: Some(gif_end),
+    },
+    FileSignature {
+        name: "BMP image",
+        offset: 0,
+        magic: b"BM",
+        extensions: &["BMP", "DIB"],
+        end: None,
+    },
+    FileSignature {
+        name: "TIFF image",
+        offset: 0,
+        magic: b"II*\x00",
+        extensions: &["TIF", "TIFF"],
+        end: None,
+    },
+    FileSignature {
+        name: "TIFF image",
+        offset: 0,
+        magic: b"MM\x00*",
+        extensions: &["TIF", "TIFF"],
+        end: None,
+    },
+    FileSignature {
+        name: "PDF document",
+        offset: 0,
+        magic: b"%PDF-",
+        extensions: &["PDF"],
+        end: Some(pdf_end),
+    },
+    FileSignature {
+        name: "ZIP archive",
+        offset: 0,
+        magic: b"PK\x03\x04",
+        extensions: &[
+            "ZIP", "DOCX", "XLSX", "PPTX", "ODT", "ODS", "JAR", "APK", "EPUB",
+        ],
+        end: Some(zip_end),
+    },
+    FileSignature {
+        name: "RAR archive",
+        offset: 0,
+        magic: b"Rar!\x1A\x07",
+        extensions: &["RAR"],
+        end: None,
+    },
+    FileSignature {
+        name: "7-Zip archive",
+        offset: 0,
+        magic: b"7z\xBC\xAF\x27\x1C",
+        extensions: &["7Z"],
+        end: None,
+    },
+    FileSignature {
+        name: "gzip archive",
+        offset: 0,
+        magic: b"\x1F\x8B",
+        extensions: &["GZ", "TGZ"],
+        end: None,
+    },
+    FileSignature {
+        name: "OLE2 document",
+        offset: 0,
+        magic: b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1",
+        extensions: &["DOC", "XLS", "PPT", "MSI", "MSG"],
+        end: None,
+    },
+    FileSignature {
+        name: "SQLite database",
+        offset: 0,
+        magic: b"SQLite format 3\x00",
+        extensions: &["SQLITE", "DB", "SQLITE3"],
+        end: None,
+    },
+    FileSignature {
+        name: "ELF executable",
+        offset: 0,
+        magic: b"\x7FELF",
+        extensions: &["", "SO", "O", "ELF", "BIN"],
+        end: None,
+    },
+    FileSignature {
+        name: "DOS/PE executable",
+        offset: 0,
+        magic: b"MZ",
+        extensions: &["EXE", "DLL", "SYS", "COM", "SCR"],
+        end: None,
+    },
+    FileSignature {
+        name: "MP3 audio",
+        offset: 0,
+        magic: b"ID3",
+        extensions: &["MP3"],
+        end: None,
+    },
+    FileSignature {
+        name: "WAVE audio",
+        offset: 8,
+        magic: b"WAVE",
+        extensions: &["WAV"],
+        end: None,
+    },
+    FileSignature {
+        name: "AVI video",
+        offset: 8,
+        magic: b"AVI ",
+        extensions: &["AVI"],
+        end: None,
+    },
+    FileSignature {
+        name: "MP4 video",
+        offset: 4,
+        magic: b"ftyp",
+        extensions: &["MP4", "M4A", "M4V", "MOV", "3GP"],
+        end: None,
+    },
+];
+
+/// The type of a file, as detected from its content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectedType {
+    /// The file matches a known signature.
+    Signature(&'static FileSignature),
+    /// The file contains printable text only.
+    Text,
+    /// The file is empty.
+    Empty,
+    /// The file doesn't match any known signature.
+    Unknown,
+}
+
+impl fmt::Display for DetectedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectedType::Signature(signature) => write!(f, "{}", signature.name),
+            DetectedType::Text => write!(f, "text"),
+            DetectedType::Empty => write!(f, "empty"),
+            DetectedType::Unknown => write!(f, "data"),
+        }
+    }
+}
+
+/// Identifies the type of a file from its first bytes.
+///
+/// # Parameters
+/// - `header`: The first bytes of the file. A full cluster gives reliable results for text files.
+///
+/// # Returns
+/// - The detected type.
+pub fn identify(header: &[u8]) -> DetectedType {
+    if header.is_empty() {
+        return DetectedType::Empty;
+    }
+
+    if let Some(signature) = SIGNATURES.iter().find(|signature| {
+        header
+            .get(signature.offset..signature.offset + signature.magic.len())
+            .is_some_and(|magic| magic == signature.magic)
+    }) {
+        return DetectedType::Signature(signature);
+    }
+
+    let is_text = match std::str::from_utf8(header) {
+        Ok(text) => text
+            .chars()
+            .all(|c| !c.is_control() || c.is_ascii_whitespace()),
+        // The header may cut a multi-byte character
+        Err(err) => {
+            err.error_len().is_none()
+                && identify(&header[..err.valid_up_to()]) == DetectedType::Text
+        }
+    };
+    match is_text {
+        true => DetectedType::Text,
+        false => DetectedType::Unknown,
+    }
+}
+
+/// Returns true if the extension of a file disagrees with its detected type.
+///
+/// A file matching a signature must use one of its extensions, and a file whose extension belongs
+/// to a known signature must match it.
+pub fn is_mismatch(path: &Path, detected: DetectedType) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+
+    match detected {
+        DetectedType::Signature(signature) => !signature.extensions.contains(&extension.as_str()),
+        DetectedType::Text | DetectedType::Unknown => {
+            !extension.is_empty()
+                && SIGNATURES
+                    .iter()
+                    .any(|signature| signature.extensions.contains(&extension.as_str()))
+        }
+        DetectedType::Empty => false,
+    }
+}
+
+/// The detected type of a file of the volume.
+#[derive(Debug, Getters)]
+pub struct FileIdentification {
+    /// The path of the file.
+    #[get = "pub"]
+    path: PathBuf,
+    /// The type detected from the content of the file.
+    #[get = "pub"]
+    detected: DetectedType,
+    /// Whether the extension of the file disagrees with its content.
+    #[get = "pub"]
+    mismatch: bool,
+}
+
+impl fmt::Display for FileIdentification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", escape_path(&self.path), self.detected)?;
+        if self.mismatch {
+            write!(f, " (extension mismatch)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Identifies the type of every file of the volume.
+///
+/// Only the first cluster of each file is read.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to inspect.
+///
+/// # Returns
+/// - `Ok(Vec<FileIdentification>)`: The detected type of each file.
+/// - `Err(FATError)`: If the directory tree cannot be walked.
+pub fn identify_files(vol: &FATVol) -> Result<Vec<FileIdentification>, FATError> {
+    let mut identifications = vec![];
+    for (path, entry) in vol.walk_files()? {
+        let mut header = match entry.cluster_number() {
+            0 => vec![],
+            cluster => vol.read_cluster(cluster)?,
+        };
+        header.truncate(*entry.file_size() as usize);
+
+        let detected = identify(&header);
+        identifications.push(FileIdentification {
+            mismatch: is_mismatch(&path, detected),
+            path,
+            detected,
+        });
+    }
+
+    Ok(identifications)
+}
@@ -0,0 +1,245 @@
+//! Comparison of a volume against a reference manifest of its files.
+//!
+//! A lab base image, or an image examined over several sessions, is expected to keep the same
+//! files. Its hashdeep manifest (see [`write_hashdeep_manifest`](super::hash::write_hashdeep_manifest))
+//! lists them with their size and digests; comparing the volume against it reports the files
+//! missing, added or modified since.
+//!
+//! Manifests written by hashdeep are accepted too, whatever their columns, as long as they hold
+//! the size, the file name and an MD5 or SHA-256 digest. Paths are compared without regard to case
+//! or leading separators, so they must be the short name paths of the files relative to the root
+//! directory.
+
+use getset::Getters;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+use super::hash::{FileDigest, hash_all_files};
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::utils::escape_path;
+
+/// The columns of a manifest without a hashdeep header, those written by this crate.
+const DEFAULT_COLUMNS: [&str; 4] = ["size", "md5", "sha256", "filename"];
+
+/// A file listed in a manifest.
+#[derive(Debug, Clone, Getters)]
+pub struct ManifestEntry {
+    /// The path of the file, relative to the root directory.
+    #[get = "pub"]
+    path: PathBuf,
+    /// The size of the file in bytes.
+    #[get = "pub"]
+    size: u64,
+    /// The hexadecimal MD5 digest of the file, if listed.
+    #[get = "pub"]
+    md5: Option<String>,
+    /// The hexadecimal SHA-256 digest of the file, if listed.
+    #[get = "pub"]
+    sha256: Option<String>,
+}
+
+impl ManifestEntry {
+    /// Lists how a file differs from the entry, empty if it matches.
+    fn differences(&self, digest: &FileDigest) -> Vec<String> {
+        let mut differences = vec![];
+        if self.size != *digest.size() {
+            differences.push(format!("size {} -> {} bytes", self.size, digest.size()));
+        }
+        if self
+            .md5
+            .as_ref()
+            .is_some_and(|md5| !md5.eq_ignore_ascii_case(digest.md5()))
+        {
+            differences.push(String::from("MD5 differs"));
+        }
+        if self
+            .sha256
+            .as_ref()
+            .is_some_and(|sha256| !sha256.eq_ignore_ascii_case(digest.sha256()))
+        {
+            differences.push(String::from("SHA-256 differs"));
+        }
+        differences
+    }
+}
+
+/// Reads the files listed in a hashdeep manifest.
+///
+/// # Parameters
+/// - `manifest`: The content of the manifest.
+///
+/// # Returns
+/// - `Ok(Vec<ManifestEntry>)`: The files, in the order of the manifest.
+/// - `Err(io::Error)`: If the manifest cannot be read, lacks a size, file name or digest column,
+///   or a line doesn't match its columns.
+pub fn read_manifest<R: BufRead>(manifest: R) -> io::Result<Vec<ManifestEntry>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut columns: Vec<String> = DEFAULT_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let mut entries = vec![];
+
+    for (line_nb, line) in manifest.lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if let Some(header) = line.strip_prefix("%%%% ") {
+            if !header.starts_with("HASHDEEP") {
+                columns = header.split(',').map(str::to_string).collect();
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // The file name is the last column, and may hold commas
+        let fields: Vec<&str> = line.splitn(columns.len(), ',').collect();
+        if fields.len() != columns.len() {
+            return Err(invalid(format!(
+                "line {}: expected {} columns",
+                line_nb + 1,
+                columns.len()
+            )));
+        }
+        let field = |name: &str| {
+            columns
+                .iter()
+                .position(|column| column == name)
+                .map(|i| fields[i].to_string())
+        };
+
+        let size = field("size")
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(|| invalid(format!("line {}: missing or invalid size", line_nb + 1)))?;
+        let path = field("filename")
+            .ok_or_else(|| invalid(format!("line {}: missing file name", line_nb + 1)))?;
+        let (md5, sha256) = (field("md5"), field("sha256"));
+        if md5.is_none() && sha256.is_none() {
+            return Err(invalid(String::from(
+                "the manifest holds neither MD5 nor SHA-256 digests",
+            )));
+        }
+        entries.push(ManifestEntry {
+            path: PathBuf::from(path),
+            size,
+            md5,
+            sha256,
+        });
+    }
+    Ok(entries)
+}
+
+/// The differences between a volume and a manifest.
+#[derive(Debug, Default, Getters)]
+pub struct ManifestComparison {
+    /// The number of files matching the manifest.
+    #[get = "pub"]
+    matched: usize,
+    /// The files of the manifest missing from the volume.
+    #[get = "pub"]
+    missing: Vec<ManifestEntry>,
+    /// The files of the volume missing from the manifest.
+    #[get = "pub"]
+    extra: Vec<FileDigest>,
+    /// The files whose size or digests differ: the entry of the manifest, the file of the volume
+    /// and the differences.
+    #[get = "pub"]
+    modified: Vec<(ManifestEntry, FileDigest, Vec<String>)>,
+}
+
+impl ManifestComparison {
+    /// Returns whether the volume holds exactly the files of the manifest.
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.modified.is_empty()
+    }
+
+    /// Returns the number of files missing, extra or modified.
+    pub fn difference_count(&self) -> usize {
+        self.missing.len() + self.extra.len() + self.modified.len()
+    }
+}
+
+impl fmt::Display for ManifestComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.missing {
+            writeln!(
+                f,
+                "Missing:  /{} ({} bytes)",
+                escape_path(entry.path().strip_prefix("/").unwrap_or(entry.path())),
+                entry.size()
+            )?;
+        }
+        for digest in &self.extra {
+            writeln!(
+                f,
+                "Extra:    /{} ({} bytes, SHA-256 {})",
+                escape_path(digest.path()),
+                digest.size(),
+                digest.sha256()
+            )?;
+        }
+        for (_, digest, differences) in &self.modified {
+            writeln!(
+                f,
+                "Modified: /{}: {}",
+                escape_path(digest.path()),
+                differences.join(", ")
+            )?;
+        }
+        writeln!(
+            f,
+            "{} file(s) matching, {} missing, {} extra, {} modified.",
+            self.matched,
+            self.missing.len(),
+            self.extra.len(),
+            self.modified.len()
+        )
+    }
+}
+
+/// Returns the key a path is matched on: upper case, `/` separated, without leading separators.
+pub(super) fn path_key(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "/")
+        .trim_start_matches('/')
+        .to_uppercase()
+}
+
+/// Compares the regular files of a volume against the files listed in a manifest.
+///
+/// # Parameters
+/// - `vol`: The volume to verify.
+/// - `expected`: The files of the manifest.
+///
+/// # Returns
+/// - `Ok(ManifestComparison)`: The files missing, extra and modified, in the order of the
+///   manifest and of the directory tree.
+/// - `Err(FATError)`: If the directory tree or a file cannot be read.
+pub fn compare_manifest(
+    vol: &FATVol,
+    expected: &[ManifestEntry],
+) -> Result<ManifestComparison, FATError> {
+    let mut files: HashMap<String, FileDigest> = HashMap::new();
+    let mut order = vec![];
+    for digest in hash_all_files(vol)? {
+        let key = path_key(digest.path());
+        order.push(key.clone());
+        files.insert(key, digest);
+    }
+
+    let mut comparison = ManifestComparison::default();
+    for entry in expected {
+        match files.remove(&path_key(entry.path())) {
+            None => comparison.missing.push(entry.clone()),
+            Some(digest) => match entry.differences(&digest) {
+                differences if differences.is_empty() => comparison.matched += 1,
+                differences => comparison
+                    .modified
+                    .push((entry.clone(), digest, differences)),
+            },
+        }
+    }
+    comparison.extra = order.iter().filter_map(|key| files.remove(key)).collect();
+    Ok(comparison)
+}
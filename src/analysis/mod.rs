@@ -0,0 +1,191 @@
+//! Forensic analysis passes over FAT volumes.
+//!
+//! This module provides read-only scanners that inspect the regions of a volume
+//! (unallocated clusters, file slack, volume slack, reserved sectors, ...) or of the disk (the gap after the MBR)
+//! and report suspicious content.
+//!
+//! Each artifact they report carries its [`Provenance`], the exact location of the evidence in
+//! the image, so that findings can be verified independently with a hex editor.
+
+pub mod attrs;
+#[cfg(feature = "carving")]
+pub mod carve;
+pub mod classify;
+pub mod dirslack;
+pub mod dupes;
+pub mod entropy;
+pub mod fatslack;
+pub mod formats;
+pub mod gap;
+pub mod hash;
+pub mod magic;
+pub mod manifest;
+pub mod names;
+pub mod payload;
+pub mod profile;
+pub mod recover;
+pub mod reserved;
+pub mod slack;
+pub mod strings;
+pub mod summary;
+pub mod treediff;
+pub mod wipe;
+
+use getset::Getters;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::filesystem::fat::{FATVol, contiguous_runs};
+use crate::utils::escape_path;
+
+/// Class of the region of a volume in which a finding was made.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegionClass {
+    /// Clusters not allocated to any file.
+    Unallocated,
+    /// The bytes between the end of a file and the end of its last cluster.
+    FileSlack(PathBuf),
+    /// The content of a file.
+    FileData(PathBuf),
+    /// The sectors between the end of the data region and the end of the partition.
+    VolumeSlack,
+}
+
+impl fmt::Display for RegionClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegionClass::Unallocated => write!(f, "Unallocated"),
+            RegionClass::FileSlack(path) => write!(f, "File slack of {}", escape_path(path)),
+            RegionClass::FileData(path) => write!(f, "Content of {}", escape_path(path)),
+            RegionClass::VolumeSlack => write!(f, "Volume slack"),
+        }
+    }
+}
+
+/// Kind of region selected for a scan, regardless of the file owning it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    /// Clusters not allocated to any file.
+    Unallocated,
+    /// The slack of every file.
+    FileSlack,
+    /// The volume slack.
+    VolumeSlack,
+}
+
+impl RegionKind {
+    /// Every kind of region.
+    pub const ALL: [RegionKind; 3] = [
+        RegionKind::Unallocated,
+        RegionKind::FileSlack,
+        RegionKind::VolumeSlack,
+    ];
+}
+
+/// The location of a finding in the image.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct Provenance {
+    /// The first sector of the partition holding the finding.
+    #[get = "pub"]
+    partition_start: u32,
+    /// The absolute byte offset and length of each contiguous range of the finding, in order.
+    #[get = "pub"]
+    extents: Vec<(u64, u64)>,
+    /// The clusters holding the finding, in order, empty outside of the data region.
+    #[get = "pub"]
+    clusters: Vec<u32>,
+}
+
+impl Provenance {
+    /// Locates a finding made of byte ranges of a volume.
+    ///
+    /// # Parameters
+    /// - `vol`: The volume holding the finding.
+    /// - `extents`: The absolute byte offset and length of each range, in order. Empty ranges are
+    ///   dropped and adjacent ones merged.
+    ///
+    /// # Returns
+    /// The provenance, with the clusters overlapped by the ranges.
+    pub fn from_extents(vol: &FATVol, extents: Vec<(u64, u64)>) -> Self {
+        let mut merged: Vec<(u64, u64)> = vec![];
+        for (offset, len) in extents.into_iter().filter(|(_, len)| *len > 0) {
+            match merged.last_mut() {
+                Some((last, last_len)) if *last + *last_len == offset => *last_len += len,
+                _ => merged.push((offset, len)),
+            }
+        }
+
+        let mut clusters: Vec<u32> = vec![];
+        for (offset, len) in &merged {
+            if let (Some(first), Some(last)) = (
+                vol.offset_to_cluster(*offset),
+                vol.offset_to_cluster(offset + len - 1),
+            ) {
+                let first = match clusters.last() {
+                    Some(previous) if *previous == first => first + 1,
+                    _ => first,
+                };
+                clusters.extend(first..=last);
+            }
+        }
+
+        Self {
+            partition_start: vol.start(),
+            extents: merged,
+            clusters,
+        }
+    }
+
+    /// Locates a finding within the content of a cluster chain, e.g. part of a file.
+    ///
+    /// # Parameters
+    /// - `vol`: The volume holding the finding.
+    /// - `chain`: The clusters of the chain, in order.
+    /// - `start`: The offset of the finding from the start of the chain.
+    /// - `len`: The length of the finding in bytes, bounded by the end of the chain.
+    ///
+    /// # Returns
+    /// The provenance, with one range per fragment of the chain the finding spans.
+    pub fn from_chain(vol: &FATVol, chain: &[u32], start: u64, len: u64) -> Self {
+        let cluster_size = vol.cluster_size() as u64;
+        let end = (start + len).min(chain.len() as u64 * cluster_size);
+        let mut extents = vec![];
+        let mut pos = start;
+        while pos < end {
+            let in_cluster = pos % cluster_size;
+            let size = (cluster_size - in_cluster).min(end - pos);
+            extents.push((
+                vol.cluster_offset(chain[(pos / cluster_size) as usize]) + in_cluster,
+                size,
+            ));
+            pos += size;
+        }
+        Self::from_extents(vol, extents)
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "partition at sector {}", self.partition_start)?;
+        if self.extents.is_empty() {
+            return write!(f, ", no data");
+        }
+        let extents: Vec<String> = self
+            .extents
+            .iter()
+            .map(|(offset, len)| format!("0x{:X}-0x{:X}", offset, offset + len))
+            .collect();
+        write!(f, ", bytes {}", extents.join(" "))?;
+        if !self.clusters.is_empty() {
+            let runs: Vec<String> = contiguous_runs(&self.clusters, u32::MAX)
+                .into_iter()
+                .map(|(first, count)| match count {
+                    1 => first.to_string(),
+                    _ => format!("{}-{}", first, first + count - 1),
+                })
+                .collect();
+            write!(f, ", clusters {}", runs.join(" "))?;
+        }
+        Ok(())
+    }
+}
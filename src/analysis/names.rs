@@ -0,0 +1,68 @@
+//! Detection of names crafted to corrupt or mislead a terminal.
+//!
+//! A name holding escape sequences can rewrite the output of a listing, and a right-to-left
+//! override makes `invoice\u{202E}fdp.exe` show as `invoiceexe.pdf`. Names are escaped when
+//! displayed (see [`crate::utils::escape_name`]); this module flags the entries whose
+//! names needed it, with their raw bytes.
+
+use getset::Getters;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::filesystem::dir_entry::DirEntry;
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::utils::{escape_name, escape_path};
+
+/// An entry whose name is unusual.
+#[derive(Debug, Getters)]
+pub struct NameAnomaly {
+    /// The path of the entry, relative to the root directory.
+    #[get = "pub"]
+    path: PathBuf,
+    /// The directory entry.
+    #[get = "pub"]
+    entry: DirEntry,
+    /// What is unusual in the names of the entry (see [`DirEntry::name_anomalies`]).
+    #[get = "pub"]
+    anomalies: Vec<String>,
+}
+
+impl fmt::Display for NameAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "/{} (raw name {})",
+            escape_path(&self.path),
+            self.entry.name_hex()
+        )?;
+        if let Some(long_name) = self.entry.long_name() {
+            write!(f, " \"{}\"", escape_name(long_name))?;
+        }
+        write!(f, ": {}", self.anomalies.join(" "))
+    }
+}
+
+/// Finds the live entries whose names hold control characters, invalid code points,
+/// bidirectional formatting characters or characters FAT forbids.
+///
+/// # Parameters
+/// - `vol`: The volume to scan.
+///
+/// # Returns
+/// - `Ok(Vec<NameAnomaly>)`: The flagged entries, parents before their children.
+/// - `Err(FATError)`: If the directory tree cannot be walked.
+pub fn scan_names(vol: &FATVol) -> Result<Vec<NameAnomaly>, FATError> {
+    Ok(vol
+        .walk_entries()?
+        .into_iter()
+        .filter_map(|(path, entry)| {
+            let anomalies = entry.name_anomalies();
+            (!anomalies.is_empty()).then_some(NameAnomaly {
+                path,
+                entry,
+                anomalies,
+            })
+        })
+        .collect())
+}
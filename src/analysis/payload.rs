@@ -0,0 +1,306 @@
+//! Detection of encoded or appended payloads.
+//!
+//! Data hidden in slack space is often encoded to survive copy-pastes (base64, hex) or is a whole
+//! file dropped as is, e.g. an archive. Data can also be appended to an image past its end marker:
+//! viewers stop at the marker, so the file still renders normally. This module reports such
+//! candidates with a preview of their decoded content.
+
+use getset::Getters;
+use std::fmt;
+
+use super::formats::{PNG_MAGIC, jpeg_end, png_end};
+use super::magic::SIGNATURES;
+use super::{Provenance, RegionClass};
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+
+/// Minimum length of a base64 blob, in characters.
+const MIN_BASE64_LEN: usize = 16;
+
+/// Minimum length of a hex blob, in characters.
+const MIN_HEX_LEN: usize = 32;
+
+/// Signatures shorter than this match random data too often to be searched past offset 0.
+const MIN_EMBEDDED_MAGIC_LEN: usize = 4;
+
+/// Maximum number of bytes shown in a preview.
+const PREVIEW_LEN: usize = 48;
+
+/// The kind of a payload candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadKind {
+    /// A base64-encoded blob.
+    Base64,
+    /// A hex-encoded blob.
+    Hex,
+    /// A file identified by its magic bytes, e.g. an archive.
+    EmbeddedFile(&'static str),
+    /// Data following the end marker of a JPEG or PNG image.
+    TrailingData(&'static str),
+}
+
+impl fmt::Display for PayloadKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadKind::Base64 => write!(f, "base64 blob"),
+            PayloadKind::Hex => write!(f, "hex blob"),
+            PayloadKind::EmbeddedFile(name) => write!(f, "embedded {name}"),
+            PayloadKind::TrailingData(name) => write!(f, "data after the end of the {name}"),
+        }
+    }
+}
+
+/// A region that likely holds a hidden payload.
+#[derive(Debug, Getters)]
+pub struct PayloadCandidate {
+    /// The class of the region containing the payload.
+    #[get = "pub"]
+    class: RegionClass,
+    /// Absolute byte offset of the payload on disk.
+    #[get = "pub"]
+    offset: u64,
+    /// Length of the payload in bytes, as stored on disk.
+    #[get = "pub"]
+    len: u64,
+    /// The kind of payload.
+    #[get = "pub"]
+    kind: PayloadKind,
+    /// The first decoded bytes, non-printable bytes being replaced by dots.
+    #[get = "pub"]
+    preview: String,
+    /// Where the payload lies in the image.
+    #[get = "pub"]
+    provenance: Provenance,
+}
+
+impl fmt::Display for PayloadCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: 0x{:X}-0x{:X} ({} bytes) {}: \"{}\"",
+            self.class,
+            self.offset,
+            self.offset + self.len,
+            self.len,
+            self.kind,
+            self.preview
+        )
+    }
+}
+
+/// Renders the first bytes of a buffer, replacing the non-printable bytes by dots.
+fn preview(data: &[u8]) -> String {
+    data.iter()
+        .take(PREVIEW_LEN)
+        .map(|b| match b {
+            0x20..0x7F => *b as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+/// Decodes standard base64, padded or not.
+fn decode_base64(s: &[u8]) -> Option<Vec<u8>> {
+    let s = s.strip_suffix(b"==").or(s.strip_suffix(b"=")).unwrap_or(s);
+    if s.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut values = Vec::with_capacity(s.len());
+    for c in s {
+        values.push(match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        });
+    }
+
+    Some(
+        values
+            .chunks(4)
+            .flat_map(|chunk| {
+                let n = chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u32, |n, (i, v)| n | (*v as u32) << (18 - 6 * i));
+                n.to_be_bytes()[1..chunk.len()].to_vec()
+            })
+            .collect(),
+    )
+}
+
+/// Decodes a hex string of even length.
+fn decode_hex(s: &[u8]) -> Option<Vec<u8>> {
+    s.chunks_exact(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Looks for base64 and hex blobs and embedded files in a buffer.
+///
+/// A base64 blob must mix at least two of uppercase letters, lowercase letters and digits, so that
+/// plain words aren't reported.
+///
+/// # Parameters
+/// - `data`: The buffer to inspect.
+///
+/// # Returns
+/// - The offset in the buffer, the length, the kind and the preview of each candidate.
+pub fn find_payloads(data: &[u8]) -> Vec<(usize, usize, PayloadKind, String)> {
+    let mut payloads = vec![];
+
+    let is_base64 = |b: &u8| b.is_ascii_alphanumeric() || *b == b'+' || *b == b'/';
+    let mut start = 0;
+    while start < data.len() {
+        if !is_base64(&data[start]) {
+            start += 1;
+            continue;
+        }
+        let mut end = start + data[start..].iter().take_while(|b| is_base64(b)).count();
+        end += data[end..]
+            .iter()
+            .take(2)
+            .take_while(|b| **b == b'=')
+            .count();
+        let blob = &data[start..end];
+
+        if blob.iter().all(u8::is_ascii_hexdigit) && blob.len() >= MIN_HEX_LEN {
+            let len = blob.len() & !1;
+            if let Some(decoded) = decode_hex(&blob[..len]) {
+                payloads.push((start, len, PayloadKind::Hex, preview(&decoded)));
+            }
+        } else if blob.len() >= MIN_BASE64_LEN {
+            let classes = [
+                blob.iter().any(u8::is_ascii_uppercase),
+                blob.iter().any(u8::is_ascii_lowercase),
+                blob.iter().any(u8::is_ascii_digit),
+            ];
+            if classes.iter().filter(|class| **class).count() >= 2
+                && let Some(decoded) = decode_base64(blob)
+            {
+                payloads.push((start, blob.len(), PayloadKind::Base64, preview(&decoded)));
+            }
+        }
+        start = end;
+    }
+
+    for signature in SIGNATURES.iter().filter(|signature| {
+        signature.offset == 0 && signature.magic.len() >= MIN_EMBEDDED_MAGIC_LEN
+    }) {
+        for (pos, _) in data
+            .windows(signature.magic.len())
+            .enumerate()
+            .filter(|(_, window)| *window == signature.magic)
+        {
+            payloads.push((
+                pos,
+                data.len() - pos,
+                PayloadKind::EmbeddedFile(signature.name),
+                preview(&data[pos..]),
+            ));
+        }
+    }
+
+    payloads.sort_by_key(|(offset, _, _, _)| *offset);
+    payloads
+}
+
+/// Scans the file slack, the volume slack and the JPEG and PNG files for hidden payloads.
+///
+/// The data found after the end marker of an image is itself scanned for encoded blobs.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to scan.
+///
+/// # Returns
+/// - `Ok(Vec<PayloadCandidate>)`: The candidates, sorted by region then offset.
+/// - `Err(FATError)`: If the volume cannot be read.
+pub fn detect_payloads(vol: &FATVol) -> Result<Vec<PayloadCandidate>, FATError> {
+    let mut candidates = vec![];
+
+    for (path, entry) in vol.walk_files()? {
+        vol.check_cancelled()?;
+        if entry.cluster_number() != 0 {
+            let header = vol.read_cluster(entry.cluster_number())?;
+            if header.starts_with(&[0xFF, 0xD8]) || header.starts_with(PNG_MAGIC) {
+                let data = vol.read_entry(&entry)?;
+                let end = match header.starts_with(PNG_MAGIC) {
+                    true => png_end(&data).map(|end| (end, "PNG image")),
+                    false => jpeg_end(&data).map(|end| (end, "JPEG image")),
+                };
+
+                if let Some((end, name)) = end.filter(|(end, _)| *end < data.len()) {
+                    // Offsets in the file are mapped to the disk through the cluster chain
+                    let clusters = vol
+                        .cluster_chain(entry.cluster_number())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let cluster_size = vol.cluster_size() as usize;
+                    let disk_offset = |pos: usize| {
+                        vol.cluster_offset(clusters[pos / cluster_size])
+                            + (pos % cluster_size) as u64
+                    };
+
+                    let provenance = |pos: usize, len: usize| {
+                        Provenance::from_chain(vol, &clusters, pos as u64, len as u64)
+                    };
+
+                    let class = RegionClass::FileData(path.clone());
+                    candidates.push(PayloadCandidate {
+                        class: class.clone(),
+                        offset: disk_offset(end),
+                        len: (data.len() - end) as u64,
+                        kind: PayloadKind::TrailingData(name),
+                        preview: preview(&data[end..]),
+                        provenance: provenance(end, data.len() - end),
+                    });
+                    for (pos, len, kind, preview) in find_payloads(&data[end..]) {
+                        candidates.push(PayloadCandidate {
+                            class: class.clone(),
+                            offset: disk_offset(end + pos),
+                            len: len as u64,
+                            kind,
+                            preview,
+                            provenance: provenance(end + pos, len),
+                        });
+                    }
+                }
+            }
+        }
+
+        for (offset, len) in vol.file_slack_extents(&entry)? {
+            let data = vol.read_bytes(offset, len as usize)?;
+            let class = RegionClass::FileSlack(path.clone());
+            for (pos, len, kind, preview) in find_payloads(&data) {
+                candidates.push(PayloadCandidate {
+                    class: class.clone(),
+                    offset: offset + pos as u64,
+                    len: len as u64,
+                    kind,
+                    preview,
+                    provenance: Provenance::from_extents(
+                        vol,
+                        vec![(offset + pos as u64, len as u64)],
+                    ),
+                });
+            }
+        }
+    }
+
+    let (offset, len) = vol.volume_slack_extent();
+    let data = vol.read_bytes(offset, len as usize)?;
+    for (pos, len, kind, preview) in find_payloads(&data) {
+        candidates.push(PayloadCandidate {
+            class: RegionClass::VolumeSlack,
+            offset: offset + pos as u64,
+            len: len as u64,
+            kind,
+            preview,
+            provenance: Provenance::from_extents(vol, vec![(offset + pos as u64, len as u64)]),
+        });
+    }
+
+    Ok(candidates)
+}
@@ -0,0 +1,266 @@
+//! Triage profiles, selecting the checks of the overview and weighting their findings.
+//!
+//! The findings worth a look first depend on the case: a CTF image hides its flags in slack and
+//! unused structures, while a USB stick seized from a user is mostly about deleted and disguised
+//! documents. A profile lists the checks to run and the weight of each one, the findings of the
+//! overview being ordered by the weight of their check.
+//!
+//! Besides the built-in profiles, a profile can be read from a text file holding one setting per
+//! line, blank lines and `#` comments being skipped:
+//! - `name <name>`: The name of the profile, shown in the overview.
+//! - `<check> <weight>`: Runs a check, its findings being weighted from 0 to 100.
+//!
+//! The checks not listed are skipped.
+
+use getset::Getters;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The largest weight of a check.
+pub const MAX_WEIGHT: u8 = 100;
+
+/// An optional check of the overview.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Check {
+    /// The payloads detected in unallocated space and slack.
+    Payloads,
+    /// The data in the volume slack.
+    VolumeSlack,
+    /// The bad clusters holding data.
+    BadClusters,
+    /// The data in file slack.
+    FileSlack,
+    /// The remnants in the slack of directories.
+    DirSlack,
+    /// The deleted entries likely to be recovered.
+    Deleted,
+    /// The files whose extension doesn't match their content.
+    Types,
+    /// The entries with unusual names.
+    Names,
+    /// The non-zero FAT entries past the last cluster.
+    FatSlack,
+    /// The live entries hidden by their attributes.
+    Hidden,
+}
+
+impl Check {
+    /// Every check.
+    pub const ALL: [Check; 10] = [
+        Check::Payloads,
+        Check::VolumeSlack,
+        Check::BadClusters,
+        Check::FileSlack,
+        Check::DirSlack,
+        Check::Deleted,
+        Check::Types,
+        Check::Names,
+        Check::FatSlack,
+        Check::Hidden,
+    ];
+
+    /// Returns the name of the check in profile files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Payloads => "payloads",
+            Check::VolumeSlack => "volumeslack",
+            Check::BadClusters => "bad",
+            Check::FileSlack => "slack",
+            Check::DirSlack => "dirslack",
+            Check::Deleted => "deleted",
+            Check::Types => "types",
+            Check::Names => "names",
+            Check::FatSlack => "fatslack",
+            Check::Hidden => "hidden",
+        }
+    }
+
+    /// Finds a check by its name in profile files.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Check::ALL.into_iter().find(|check| check.name() == name)
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The checks of the overview and the weight of their findings.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct TriageProfile {
+    /// The name of the profile.
+    #[get = "pub"]
+    name: String,
+    /// The checks to run and their weight, in the order they were listed.
+    #[get = "pub"]
+    weights: Vec<(Check, u8)>,
+}
+
+impl TriageProfile {
+    /// The names of the built-in profiles, the first one being the default.
+    pub const BUILTIN: [&str; 3] = ["default", "ctf", "usb"];
+
+    /// Returns a built-in profile.
+    ///
+    /// - `default`: Every check, hidden data first.
+    /// - `ctf`: Every check, weighted towards the places flags are hidden in.
+    /// - `usb`: The user data of a seized stick, deleted and disguised files first. The bad
+    ///   clusters, directory slack and FAT slack are not checked.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the profile.
+    ///
+    /// # Returns
+    /// The profile, or `None` if no built-in profile has this name.
+    pub fn builtin(name: &str) -> Option<Self> {
+        use Check::*;
+        let weights = match name {
+            "default" => vec![
+                (Payloads, 90),
+                (VolumeSlack, 80),
+                (BadClusters, 70),
+                (FileSlack, 60),
+                (DirSlack, 50),
+                (Deleted, 40),
+                (Types, 30),
+                (Names, 20),
+                (FatSlack, 20),
+                (Hidden, 10),
+            ],
+            "ctf" => vec![
+                (Payloads, 100),
+                (FileSlack, 90),
+                (VolumeSlack, 90),
+                (BadClusters, 80),
+                (DirSlack, 80),
+                (FatSlack, 70),
+                (Types, 70),
+                (Hidden, 60),
+                (Names, 50),
+                (Deleted, 40),
+            ],
+            "usb" => vec![
+                (Deleted, 90),
+                (Types, 70),
+                (Hidden, 60),
+                (Payloads, 50),
+                (Names, 40),
+                (FileSlack, 30),
+                (VolumeSlack, 20),
+            ],
+            _ => return None,
+        };
+        Some(Self {
+            name: name.to_string(),
+            weights,
+        })
+    }
+
+    /// Parses a profile file.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the profile, unless the file sets one.
+    /// - `text`: The content of the file.
+    ///
+    /// # Returns
+    /// - `Ok(TriageProfile)`: The profile.
+    /// - `Err(String)`: A description of the first syntax error, with its line number.
+    pub fn parse(name: &str, text: &str) -> Result<Self, String> {
+        let mut profile = Self {
+            name: name.to_string(),
+            weights: vec![],
+        };
+        for (line_nb, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            profile
+                .parse_line(line)
+                .map_err(|err| format!("line {}: {err}", line_nb + 1))?;
+        }
+        Ok(profile)
+    }
+
+    /// Parses a setting of a profile file.
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["name", name] => self.name = name.to_string(),
+            [check, weight] => {
+                let check = Check::from_name(check).ok_or_else(|| {
+                    format!(
+                        "unknown check '{check}', expected one of: {}",
+                        Check::ALL.map(Check::name).join(", ")
+                    )
+                })?;
+                let weight = weight
+                    .parse()
+                    .ok()
+                    .filter(|weight| *weight <= MAX_WEIGHT)
+                    .ok_or_else(|| format!("invalid weight '{weight}', expected 0 to 100"))?;
+                match self.weights.iter_mut().find(|(listed, _)| *listed == check) {
+                    Some((_, listed)) => *listed = weight,
+                    None => self.weights.push((check, weight)),
+                }
+            }
+            _ => return Err(format!("unknown setting '{line}'")),
+        }
+        Ok(())
+    }
+
+    /// Reads a profile file.
+    ///
+    /// # Parameters
+    /// - `path`: The path of the file, its stem naming the profile unless the file sets one.
+    ///
+    /// # Returns
+    /// - `Ok(TriageProfile)`: The profile.
+    /// - `Err(io::Error)`: If the file cannot be read, or a line is not a setting.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::parse(&name, &text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Returns the built-in profile of this name, or reads the profile file at this path.
+    ///
+    /// # Parameters
+    /// - `name_or_path`: The name of a built-in profile, or the path of a profile file.
+    ///
+    /// # Returns
+    /// - `Ok(TriageProfile)`: The profile.
+    /// - `Err(io::Error)`: If the profile is not built-in and its file cannot be read.
+    pub fn resolve(name_or_path: &str) -> io::Result<Self> {
+        match Self::builtin(name_or_path) {
+            Some(profile) => Ok(profile),
+            None => Self::load(Path::new(name_or_path)),
+        }
+    }
+
+    /// Returns the weight of a check, or `None` if the profile skips it.
+    pub fn weight(&self, check: Check) -> Option<u8> {
+        self.weights
+            .iter()
+            .find(|(listed, _)| *listed == check)
+            .map(|(_, weight)| *weight)
+    }
+
+    /// Returns whether the profile runs a check.
+    pub fn runs(&self, check: Check) -> bool {
+        self.weight(check).is_some()
+    }
+}
+
+impl Default for TriageProfile {
+    fn default() -> Self {
+        Self::builtin(Self::BUILTIN[0]).expect("the default profile is built-in")
+    }
+}
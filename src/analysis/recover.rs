@@ -0,0 +1,864 @@
+//! Guided recovery of a damaged volume.
+//!
+//! Each recovery heuristic of the crate answers a specific kind of damage: a corrupt boot sector is
+//! restored from its backup, a damaged FAT from a mirror, a lost root directory from a cluster
+//! scan, a destroyed tree from the "." and ".." entries. This module checks what is damaged, runs
+//! the matching heuristics and gathers everything that can be recovered in a single list.
+//!
+//! Every item is given a confidence score, from 0 to 100, estimating how likely it is to be
+//! recovered intact: whether its clusters were reused by live files, whether its chain is
+//! contiguous, whether its content matches the expected signature. The list is sorted by score.
+
+use getset::Getters;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::Provenance;
+#[cfg(feature = "carving")]
+use super::carve::{CarvedFile, CarverRegistry, carve, dedup_carved};
+use super::magic::{DetectedType, FileSignature, identify, is_mismatch};
+use crate::events::Event;
+use crate::filesystem::dir_entry::DirEntry;
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::utils::{escape_name, escape_path};
+
+/// The priority of a recoverable item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Repairs metadata the rest of the recovery depends on.
+    High,
+    /// Recovers files or directories with their metadata.
+    Medium,
+    /// Recovers content without metadata, or content that may be overwritten.
+    Low,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Priority::High => write!(f, "high"),
+            Priority::Medium => write!(f, "medium"),
+            Priority::Low => write!(f, "low"),
+        }
+    }
+}
+
+/// Something that can be recovered from the volume.
+#[derive(Debug, Clone)]
+pub enum RecoverableItem {
+    /// The boot sector can be restored from its backup at the given absolute sector.
+    BackupBootSector(u64),
+    /// A FAT copy, identified by its index, differs from the active FAT.
+    FatCopy(u8),
+    /// A cluster may be the first cluster of the root directory.
+    RootCandidate { cluster: u32, label_matches: bool },
+    /// A deleted file or directory whose entry is still present.
+    DeletedEntry {
+        path: PathBuf,
+        cluster: u32,
+        size: u32,
+        /// Whether the first cluster has been allocated again since the deletion.
+        overwritten: bool,
+    },
+    /// A directory found from its "." and ".." entries but not reachable from the root.
+    LostDirectory { cluster: u32, name: Option<String> },
+    /// An allocated cluster chain that no entry references.
+    OrphanChain { first: u32, len: u32 },
+    /// A file carved from unallocated space.
+    CarvedFile {
+        offset: u64,
+        len: u64,
+        format: String,
+    },
+}
+
+impl RecoverableItem {
+    /// Returns the priority of the item.
+    pub fn priority(&self) -> Priority {
+        match self {
+            RecoverableItem::BackupBootSector(_)
+            | RecoverableItem::FatCopy(_)
+            | RecoverableItem::RootCandidate { .. } => Priority::High,
+            RecoverableItem::DeletedEntry {
+                overwritten: false, ..
+            }
+            | RecoverableItem::LostDirectory { .. }
+            | RecoverableItem::OrphanChain { .. } => Priority::Medium,
+            RecoverableItem::DeletedEntry {
+                overwritten: true, ..
+            }
+            | RecoverableItem::CarvedFile { .. } => Priority::Low,
+        }
+    }
+}
+
+/// Returns the absolute byte offset and length of the data recovered by an item.
+fn item_extent(vol: &FATVol, item: &RecoverableItem) -> (u64, u64) {
+    let cluster_size = vol.cluster_size() as u64;
+    match item {
+        RecoverableItem::BackupBootSector(sector) => {
+            (vol.sector_offset(*sector), vol.sector_offset(1))
+        }
+        RecoverableItem::FatCopy(fat_nb) => {
+            let start = vol.fat_entry_offset(0, *fat_nb);
+            (start, vol.fat_entry_offset(0, fat_nb + 1) - start)
+        }
+        RecoverableItem::RootCandidate { cluster, .. }
+        | RecoverableItem::LostDirectory { cluster, .. } => {
+            (vol.cluster_offset(*cluster), cluster_size)
+        }
+        RecoverableItem::DeletedEntry { cluster, size, .. } => {
+            (vol.cluster_offset(*cluster), *size as u64)
+        }
+        RecoverableItem::OrphanChain { first, len } => {
+            (vol.cluster_offset(*first), *len as u64 * cluster_size)
+        }
+        RecoverableItem::CarvedFile { offset, len, .. } => (*offset, *len),
+    }
+}
+
+/// Locates the data recovered by an item, following the chain of an orphan chain.
+fn item_provenance(vol: &FATVol, item: &RecoverableItem) -> Result<Provenance, FATError> {
+    match item {
+        RecoverableItem::OrphanChain { first, len } => {
+            let chain = vol
+                .cluster_chain(*first)
+                .take(*len as usize)
+                .collect::<Result<Vec<_>, _>>()?;
+            let cluster_size = vol.cluster_size() as u64;
+            Ok(Provenance::from_chain(
+                vol,
+                &chain,
+                0,
+                chain.len() as u64 * cluster_size,
+            ))
+        }
+        _ => Ok(Provenance::from_extents(vol, vec![item_extent(vol, item)])),
+    }
+}
+
+impl fmt::Display for RecoverableItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] ", self.priority())?;
+        match self {
+            RecoverableItem::BackupBootSector(sector) => {
+                write!(
+                    f,
+                    "Restore the boot sector from its backup at sector {sector}"
+                )
+            }
+            RecoverableItem::FatCopy(fat_nb) => write!(
+                f,
+                "Compare FAT {fat_nb} with the active FAT: it may hold the chains lost by the other"
+            ),
+            RecoverableItem::RootCandidate { cluster, .. } => {
+                write!(
+                    f,
+                    "Use cluster {cluster} as the root directory ('root set {cluster}')"
+                )
+            }
+            RecoverableItem::DeletedEntry {
+                path,
+                cluster,
+                size,
+                overwritten,
+            } => write!(
+                f,
+                "Deleted entry {} ({size} bytes from cluster {cluster}){}",
+                escape_path(path),
+                match overwritten {
+                    true => ", first cluster reused",
+                    false => "",
+                }
+            ),
+            RecoverableItem::LostDirectory { cluster, name } => write!(
+                f,
+                "Lost directory {} at cluster {cluster}",
+                name.as_deref().map_or(String::from("?"), escape_name)
+            ),
+            RecoverableItem::OrphanChain { first, len } => {
+                write!(f, "Orphan chain of {len} cluster(s) from cluster {first}")
+            }
+            RecoverableItem::CarvedFile {
+                offset,
+                len,
+                format,
+            } => write!(f, "Carved {format} at 0x{offset:X} ({len} bytes)"),
+        }
+    }
+}
+
+/// A recoverable item and the confidence in its recovery.
+#[derive(Debug, Getters)]
+pub struct ScoredItem {
+    /// The recoverable item.
+    #[get = "pub"]
+    item: RecoverableItem,
+    /// The likelihood of recovering the item intact, from 0 to 100.
+    #[get = "pub"]
+    confidence: u8,
+    /// The observations the score is based on.
+    #[get = "pub"]
+    factors: Vec<String>,
+    /// Where the data of the item lies in the image.
+    #[get = "pub"]
+    provenance: Provenance,
+    /// Which clusters of a deleted entry are allocated again, for deleted entries only.
+    #[get = "pub"]
+    reuse: Option<ClusterReuse>,
+}
+
+/// Which of the clusters of a deleted entry are allocated again since its deletion.
+///
+/// The chain of a deleted file is cleared, so its clusters are assumed to follow each other from
+/// its first cluster, as many as its size implies.
+#[derive(Debug, Clone, Getters)]
+pub struct ClusterReuse {
+    /// The number of clusters implied by the size of the entry.
+    #[get = "pub"]
+    clusters: u32,
+    /// The number of those clusters still free.
+    #[get = "pub"]
+    free: u32,
+    /// The live files and directories holding some of those clusters, with how many each, in
+    /// cluster order.
+    #[get = "pub"]
+    owners: Vec<(PathBuf, u32)>,
+    /// The number of those clusters allocated to no live file: orphan chains, bad clusters,
+    /// clusters of unreadable directories or past the end of the volume.
+    #[get = "pub"]
+    unowned: u32,
+}
+
+impl ClusterReuse {
+    /// Returns the percentage of the clusters of the entry still free, from 0 to 100.
+    pub fn recoverable(&self) -> u8 {
+        (100 * self.free as u64 / self.clusters.max(1) as u64) as u8
+    }
+}
+
+impl fmt::Display for ClusterReuse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}% recoverable, {} of {} cluster(s) free",
+            self.recoverable(),
+            self.free,
+            self.clusters
+        )?;
+        for (owner, count) in &self.owners {
+            write!(f, ", {count} reused by {}", escape_path(owner))?;
+        }
+        if self.unowned > 0 {
+            write!(f, ", {} allocated to no live file", self.unowned)?;
+        }
+        Ok(())
+    }
+}
+
+/// The clusters of the live files and directories found by a walk of the tree, and their owner.
+#[derive(Debug, Default)]
+struct LiveClusters {
+    /// The paths of the live files and directories.
+    paths: Vec<PathBuf>,
+    /// The index in `paths` of the owner of each cluster.
+    owners: HashMap<u32, usize>,
+}
+
+impl LiveClusters {
+    /// Records the clusters of a live file or directory.
+    fn insert(&mut self, path: &Path, clusters: impl Iterator<Item = u32>) {
+        let idx = self.paths.len();
+        self.paths.push(match path.as_os_str().is_empty() {
+            true => PathBuf::from("/"),
+            false => path.to_path_buf(),
+        });
+        self.owners.extend(clusters.map(|cluster| (cluster, idx)));
+    }
+
+    /// Checks whether a cluster belongs to a live file or directory.
+    fn contains(&self, cluster: &u32) -> bool {
+        self.owners.contains_key(cluster)
+    }
+
+    /// Returns the live file or directory a cluster belongs to, if any.
+    fn owner(&self, cluster: u32) -> Option<&Path> {
+        self.owners
+            .get(&cluster)
+            .map(|idx| self.paths[*idx].as_path())
+    }
+
+    /// Checks which of the clusters implied by a deleted entry are allocated again.
+    ///
+    /// # Parameters
+    /// - `fat`: The entries of the active FAT.
+    /// - `first`: The first cluster of the entry.
+    /// - `count`: The number of clusters implied by its size.
+    fn reuse(&self, fat: &[u32], first: u32, count: u32) -> ClusterReuse {
+        let mut reuse = ClusterReuse {
+            clusters: count,
+            free: 0,
+            owners: vec![],
+            unowned: 0,
+        };
+        for cluster in (0..count).map(|i| first.saturating_add(i)) {
+            if fat.get(cluster as usize) == Some(&0) {
+                reuse.free += 1;
+                continue;
+            }
+            match self.owner(cluster) {
+                Some(owner) => match reuse.owners.iter_mut().find(|(path, _)| path == owner) {
+                    Some((_, count)) => *count += 1,
+                    None => reuse.owners.push((owner.to_path_buf(), 1)),
+                },
+                None => reuse.unowned += 1,
+            }
+        }
+        reuse
+    }
+}
+
+impl fmt::Display for ScoredItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>3}% {}", self.confidence, self.item)?;
+        if !self.factors.is_empty() {
+            write!(f, " ({})", self.factors.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The damage found on a volume and what can be recovered.
+#[derive(Debug, Getters)]
+pub struct RecoveryPlan {
+    /// A description of each kind of damage found.
+    #[get = "pub"]
+    damage: Vec<String>,
+    /// The recoverable items, the most confident first.
+    #[get = "pub"]
+    items: Vec<ScoredItem>,
+}
+
+impl fmt::Display for RecoveryPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.damage.is_empty() {
+            true => writeln!(f, "No structural damage found.")?,
+            false => {
+                writeln!(f, "Damage:")?;
+                for damage in &self.damage {
+                    writeln!(f, "  - {damage}")?;
+                }
+            }
+        }
+
+        writeln!(f, "Recoverable items:")?;
+        if self.items.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for item in &self.items {
+            writeln!(f, "  {item}")?;
+            writeln!(f, "       evidence: {}", item.provenance)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the volume for damage and lists what can be recovered.
+///
+/// The boot sector, the FATs and the root directory are checked first. Deleted entries and
+/// orphan chains are only looked for if the root directory can be walked, and carved files are
+/// only deduplicated against the allocated files if every directory can. The data region is
+/// scanned for directories if the root directory or the FAT is damaged, or if orphan chains are
+/// found. Unallocated space is always carved, if the `carving` feature is enabled.
+///
+/// The items are sorted by confidence, then by priority.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to recover.
+///
+/// # Returns
+/// - `Ok(RecoveryPlan)`: The damage found and the scored recoverable items.
+/// - `Err(FATError)`: If the volume cannot be read.
+pub fn plan_recovery(vol: &FATVol) -> Result<RecoveryPlan, FATError> {
+    let mut damage = vec![];
+    let mut items = vec![];
+
+    if let Some((sector, false)) = vol.check_backup_boot()? {
+        damage.push(format!(
+            "The boot sector differs from its backup at sector {sector}."
+        ));
+        items.push(RecoverableItem::BackupBootSector(sector));
+    }
+
+    let diverging_fats = vol.fsstat()?.diverging_fats().clone();
+    if !diverging_fats.is_empty() {
+        damage.push(format!(
+            "FAT copies {diverging_fats:?} differ from the active FAT."
+        ));
+        items.extend(diverging_fats.into_iter().map(RecoverableItem::FatCopy));
+    }
+
+    // Walk the tree from the root, noting the clusters in use and the deleted entries
+    let fat = vol.fat_entries()?;
+    let mut reachable = LiveClusters::default();
+    let mut unreadable = vec![];
+    let root_ok = match vol.root_cluster() {
+        Ok(root) => {
+            let mut walk = TreeWalk {
+                vol,
+                fat: &fat,
+                reachable: &mut reachable,
+                items: &mut items,
+                unreadable: vec![],
+            };
+            let root_ok = walk.walk(root, Path::new("")).is_ok();
+            unreadable = walk.unreadable;
+            root_ok
+        }
+        Err(_) => false,
+    };
+    for path in &unreadable {
+        damage.push(format!(
+            "The directory {} cannot be read.",
+            escape_path(path)
+        ));
+    }
+    if !root_ok {
+        damage.push(String::from("The root directory cannot be read."));
+        items.extend(
+            vol.find_root_candidates()?
+                .iter()
+                .filter(|candidate| !candidate.is_current())
+                .map(|candidate| RecoverableItem::RootCandidate {
+                    cluster: *candidate.cluster(),
+                    label_matches: *candidate.label_matches(),
+                }),
+        );
+    }
+
+    let mut orphan_found = false;
+    if root_ok {
+        for (first, len) in orphan_chains(vol, &fat, &reachable) {
+            orphan_found = true;
+            items.push(RecoverableItem::OrphanChain { first, len });
+        }
+    }
+
+    if !root_ok || !damage.is_empty() || orphan_found {
+        for dir in vol.discover_dirs()?.dirs() {
+            if !reachable.contains(dir.cluster()) {
+                items.push(RecoverableItem::LostDirectory {
+                    cluster: *dir.cluster(),
+                    name: dir.name().clone(),
+                });
+            }
+        }
+    }
+
+    // Carved files can only be matched against the allocated files if the whole tree can be walked
+    #[cfg(feature = "carving")]
+    {
+        let carved = carve(vol, &CarverRegistry::default())?;
+        let carved: Vec<CarvedFile> = match root_ok && unreadable.is_empty() {
+            true => dedup_carved(vol, carved)?
+                .recoveries()
+                .iter()
+                .map(|recovery| recovery.carved().clone())
+                .collect(),
+            false => carved,
+        };
+        items.extend(carved.iter().map(|file| RecoverableItem::CarvedFile {
+            offset: *file.offset(),
+            len: file.data().len() as u64,
+            format: file.format().clone(),
+        }));
+    }
+
+    let mut items = items
+        .into_iter()
+        .map(|item| score(vol, &fat, &reachable, item))
+        .collect::<Result<Vec<_>, _>>()?;
+    items.sort_by_key(|scored| (Reverse(scored.confidence), scored.item.priority()));
+    for message in &damage {
+        vol.emit(|| Event::Warning {
+            offset: None,
+            message: message.clone(),
+        });
+    }
+    for scored in &items {
+        vol.emit(|| {
+            let (offset, len) = item_extent(vol, &scored.item);
+            Event::Recovered {
+                offset,
+                len,
+                description: scored.to_string(),
+            }
+        });
+    }
+    Ok(RecoveryPlan { damage, items })
+}
+
+/// Lists the deleted entries of the directory tree, without the rest of the recovery.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to walk.
+///
+/// # Returns
+/// - `Ok(Vec<ScoredItem>)`: The deleted entries, the most confident first.
+/// - `Err(FATError)`: If the root directory or the FAT cannot be read.
+pub fn deleted_entries(vol: &FATVol) -> Result<Vec<ScoredItem>, FATError> {
+    let fat = vol.fat_entries()?;
+    let mut reachable = LiveClusters::default();
+    let mut items = vec![];
+    TreeWalk {
+        vol,
+        fat: &fat,
+        reachable: &mut reachable,
+        items: &mut items,
+        unreadable: vec![],
+    }
+    .walk(vol.root_cluster()?, Path::new(""))?;
+
+    let mut items = items
+        .into_iter()
+        .map(|item| score(vol, &fat, &reachable, item))
+        .collect::<Result<Vec<_>, _>>()?;
+    items.sort_by_key(|scored| Reverse(scored.confidence));
+    Ok(items)
+}
+
+/// The state of the walk of the directory tree.
+struct TreeWalk<'a> {
+    vol: &'a FATVol,
+    fat: &'a [u32],
+    /// The clusters of the directories and live files found so far.
+    reachable: &'a mut LiveClusters,
+    /// The deleted entries found so far.
+    items: &'a mut Vec<RecoverableItem>,
+    /// The subdirectories that cannot be walked.
+    unreadable: Vec<PathBuf>,
+}
+
+impl TreeWalk<'_> {
+    /// Walks a directory and its subdirectories.
+    ///
+    /// # Returns
+    /// - `Err(FATError)` if the directory itself cannot be listed or holds entries that can't be
+    ///   directory entries. Errors in subdirectories are ignored, so that a damaged branch doesn't
+    ///   hide the rest of the tree.
+    fn walk(&mut self, cluster: u32, dir_path: &Path) -> Result<(), FATError> {
+        let entries = self.vol.list_dir(cluster)?;
+        if !entries.iter().all(DirEntry::is_plausible) {
+            return Err(FATError::InvalidClusterError(cluster));
+        }
+        self.reachable.insert(
+            dir_path,
+            self.vol.cluster_chain(cluster).map_while(Result::ok),
+        );
+
+        for entry in entries {
+            if entry.is_long_name() || entry.is_volume_id() || entry.is_dot() {
+                continue;
+            }
+            let cluster = entry.cluster_number();
+
+            if entry.is_deleted() {
+                if cluster != 0 {
+                    // The first character of a deleted short name is lost
+                    let name = entry.long_name().clone().unwrap_or_else(|| {
+                        format!(
+                            "?{}",
+                            entry.short_name().chars().skip(1).collect::<String>()
+                        )
+                    });
+                    self.items.push(RecoverableItem::DeletedEntry {
+                        path: dir_path.join(name),
+                        cluster,
+                        size: *entry.file_size(),
+                        overwritten: self.fat.get(cluster as usize) != Some(&0),
+                    });
+                }
+                continue;
+            }
+
+            if entry.is_dir() {
+                let path = dir_path.join(entry.short_name());
+                if !self.reachable.contains(&cluster) && self.walk(cluster, &path).is_err() {
+                    self.unreadable.push(path);
+                }
+            } else if cluster != 0 {
+                self.reachable.insert(
+                    &dir_path.join(entry.short_name()),
+                    self.vol.cluster_chain(cluster).map_while(Result::ok),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Finds the allocated cluster chains that no entry references.
+///
+/// # Returns
+/// - The first cluster and the length of each chain.
+fn orphan_chains(vol: &FATVol, fat: &[u32], reachable: &LiveClusters) -> Vec<(u32, u32)> {
+    let bad = vol.bad_cluster_marker();
+    let orphans: HashSet<u32> = fat
+        .iter()
+        .enumerate()
+        .skip(2)
+        .filter(|(cluster, value)| {
+            **value != 0 && **value != bad && !reachable.contains(&(*cluster as u32))
+        })
+        .map(|(cluster, _)| cluster as u32)
+        .collect();
+
+    // The heads of the chains are the orphans no other orphan points to
+    let successors: HashSet<u32> = orphans
+        .iter()
+        .map(|cluster| fat[*cluster as usize])
+        .filter(|next| !vol.is_eoc(*next))
+        .collect();
+    let mut heads: Vec<u32> = orphans
+        .iter()
+        .filter(|cluster| !successors.contains(cluster))
+        .copied()
+        .collect();
+    heads.sort_unstable();
+
+    heads
+        .into_iter()
+        .map(|head| {
+            let mut len = 1;
+            let mut cluster = head;
+            while let Some(next) = fat.get(cluster as usize).copied()
+                && orphans.contains(&next)
+                && len < orphans.len() as u32
+            {
+                cluster = next;
+                len += 1;
+            }
+            (head, len)
+        })
+        .collect()
+}
+
+/// Scores a recoverable item.
+///
+/// The score starts from a base depending on the kind of item and is lowered by each doubt:
+/// clusters reused by live files, a chain assumed or found fragmented, content not matching the
+/// expected signature.
+///
+/// # Parameters
+/// - `vol`: The FAT volume the item comes from.
+/// - `fat`: The entries of the active FAT.
+/// - `live`: The clusters of the live files and directories, and their owner.
+/// - `item`: The item to score.
+///
+/// # Returns
+/// - `Ok(ScoredItem)`: The item and its confidence.
+/// - `Err(FATError)`: If the content of the item cannot be read.
+fn score(
+    vol: &FATVol,
+    fat: &[u32],
+    live: &LiveClusters,
+    item: RecoverableItem,
+) -> Result<ScoredItem, FATError> {
+    let mut factors = vec![];
+    let mut reuse = None;
+    let confidence = match &item {
+        RecoverableItem::BackupBootSector(sector) => {
+            match vol.read_sectors(*sector, 1)?.ends_with(&[0x55, 0xAA]) {
+                true => {
+                    factors.push(String::from("the backup has a boot signature"));
+                    90.0
+                }
+                false => {
+                    factors.push(String::from("the backup has no boot signature"));
+                    20.0
+                }
+            }
+        }
+        RecoverableItem::FatCopy(_) => {
+            factors.push(String::from("which copy is right is unknown"));
+            50.0
+        }
+        RecoverableItem::RootCandidate { label_matches, .. } => match label_matches {
+            true => {
+                factors.push(String::from("the volume label matches the boot sector"));
+                90.0
+            }
+            false => {
+                factors.push(String::from(
+                    "the volume label differs from the boot sector",
+                ));
+                50.0
+            }
+        },
+        RecoverableItem::DeletedEntry {
+            path,
+            cluster,
+            size,
+            ..
+        } => {
+            // The chain of a deleted file is cleared: its clusters are assumed to follow each other
+            let count = size.div_ceil(vol.cluster_size()).max(1);
+            let clusters = live.reuse(fat, *cluster, count);
+            let mut confidence = 100.0 * *clusters.free() as f64 / count as f64;
+            match clusters.free() == clusters.clusters() {
+                true => factors.push(String::from("no cluster reused")),
+                false => factors.push(clusters.to_string()),
+            }
+            reuse = Some(clusters);
+            if count > 1 {
+                factors.push(String::from("contiguity assumed"));
+                confidence *= 0.9;
+            }
+            // The content is checked even if the first cluster was reused: a header or an end
+            // that doesn't match the file then shows that its recovery would be garbage
+            let mut header = vol.read_cluster(*cluster)?;
+            header.truncate(*size as usize);
+            let detected = identify(&header);
+            confidence *= signature_factor(path, detected, &mut factors);
+            if let DetectedType::Signature(signature) = detected {
+                confidence *= end_factor(vol, signature, *cluster, *size, &mut factors)?;
+            }
+            confidence
+        }
+        RecoverableItem::LostDirectory { name, .. } => match name {
+            Some(_) => {
+                factors.push(String::from("named by a parent entry"));
+                80.0
+            }
+            None => {
+                factors.push(String::from("no entry points to it"));
+                60.0
+            }
+        },
+        RecoverableItem::OrphanChain { first, len } => {
+            let mut breaks = 0;
+            let mut cluster = *first;
+            for _ in 1..*len {
+                let next = fat[cluster as usize];
+                if next != cluster + 1 {
+                    breaks += 1;
+                }
+                cluster = next;
+            }
+            let mut confidence = 70.0;
+            match breaks {
+                0 => factors.push(String::from("contiguous chain")),
+                _ => {
+                    factors.push(format!("{breaks} fragment break(s)"));
+                    confidence -= 20.0 * breaks as f64 / *len as f64;
+                }
+            }
+            match identify(&vol.read_cluster(*first)?) {
+                DetectedType::Signature(signature) => {
+                    factors.push(format!("starts with a {} signature", signature.name));
+                    confidence += 20.0;
+                }
+                DetectedType::Text => factors.push(String::from("starts with text")),
+                _ => factors.push(String::from("no known signature")),
+            }
+            confidence
+        }
+        RecoverableItem::CarvedFile { len, .. } => {
+            factors.push(String::from("structure validated by the carver"));
+            match *len > vol.cluster_size() as u64 {
+                true => {
+                    factors.push(String::from("contiguity assumed"));
+                    80.0
+                }
+                false => 90.0,
+            }
+        }
+    };
+
+    Ok(ScoredItem {
+        provenance: item_provenance(vol, &item)?,
+        item,
+        confidence: confidence.round().clamp(0.0, 100.0) as u8,
+        factors,
+        reuse,
+    })
+}
+
+/// Compares the content of a file with its extension.
+///
+/// # Returns
+/// - The factor applied to the confidence: 1 if the content is consistent with the extension.
+fn signature_factor(path: &Path, detected: DetectedType, factors: &mut Vec<String>) -> f64 {
+    if is_mismatch(path, detected) {
+        factors.push(format!("content is {detected}, not matching the extension"));
+        return 0.6;
+    }
+    if let DetectedType::Signature(signature) = detected {
+        factors.push(format!("valid {} signature", signature.name));
+    }
+    1.0
+}
+
+/// Checks that the content of a deleted file ends where its format says it does, its clusters
+/// being assumed to follow each other.
+///
+/// # Parameters
+/// - `vol`: The FAT volume the file comes from.
+/// - `signature`: The format detected from the header of the file.
+/// - `cluster`: The first cluster of the file.
+/// - `size`: The size of the file.
+/// - `factors`: The explanations of the score, completed with the result of the check.
+///
+/// # Returns
+/// - `Ok(f64)`: The factor applied to the confidence: 1 if the file ends as expected, or if its
+///   format or its size doesn't allow the check.
+/// - `Err(FATError)`: If the content cannot be read.
+fn end_factor(
+    vol: &FATVol,
+    signature: &FileSignature,
+    cluster: u32,
+    size: u32,
+    factors: &mut Vec<String>,
+) -> Result<f64, FATError> {
+    let Some(end) = signature.end else {
+        return Ok(1.0);
+    };
+    if size as u64 > vol.limits().max_carved_bytes {
+        factors.push(String::from("too large to check the end"));
+        return Ok(1.0);
+    }
+
+    let last_cluster = vol.cluster_count() + 1;
+    let count = size.div_ceil(vol.cluster_size()).max(1);
+    let clusters: Vec<u32> = (cluster..cluster.saturating_add(count))
+        .take_while(|cluster| *cluster <= last_cluster)
+        .collect();
+    let mut content = vol.read_chain(&clusters)?;
+    content.truncate(size as usize);
+
+    match end(&content) {
+        Some(end) if end == content.len() => {
+            factors.push(format!("ends like a complete {}", signature.name));
+            Ok(1.0)
+        }
+        Some(end) => {
+            factors.push(format!(
+                "{} byte(s) after the end of the {}",
+                content.len() - end,
+                signature.name
+            ));
+            Ok(0.9)
+        }
+        None => {
+            factors.push(format!(
+                "no valid end of {}: truncated or overwritten",
+                signature.name
+            ));
+            Ok(0.5)
+        }
+    }
+}
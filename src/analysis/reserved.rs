@@ -0,0 +1,138 @@
+//! Detection of data hidden in the reserved region of a volume.
+//!
+//! Besides the boot sector, the reserved region of a FAT32 volume holds the FSINFO sector, the
+//! backup boot sector and the backup FSINFO sector, the other sectors being left unused. Formatting
+//! tools zero them, as well as the reserved fields of both FSINFO sectors, and no driver reads
+//! them: data there, and especially in the sectors following the backup boot sector, survives any
+//! change to the files of the volume. The boot sectors are skipped, their boot code being
+//! legitimately non-zero.
+
+use getset::Getters;
+use std::fmt;
+
+use super::Provenance;
+use crate::filesystem::bpb::ReservedSector;
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::filesystem::fsinfo::FsInfo;
+
+/// The boot signature ending the sectors of a boot record, left out of unused sectors.
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+/// Maximum number of bytes shown in a preview.
+const PREVIEW_LEN: usize = 32;
+
+/// Non-zero bytes found where the reserved region should be zeroed.
+#[derive(Debug, Getters)]
+pub struct ReservedData {
+    /// The sector holding the data, relative to the start of the volume.
+    #[get = "pub"]
+    sector: u16,
+    /// The structure held by the sector.
+    #[get = "pub"]
+    kind: ReservedSector,
+    /// The number of non-zero bytes.
+    #[get = "pub"]
+    non_zero: usize,
+    /// The content of the sector from its first to its last non-zero byte outside of the
+    /// structure.
+    #[get = "pub"]
+    data: Vec<u8>,
+    /// Where the data lies in the image.
+    #[get = "pub"]
+    provenance: Provenance,
+}
+
+impl fmt::Display for ReservedData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offset = self
+            .provenance
+            .extents()
+            .first()
+            .map_or(0, |(offset, _)| *offset);
+        let place = match self.kind {
+            ReservedSector::FsInfo | ReservedSector::BackupFsInfo => "reserved fields of the ",
+            _ => "",
+        };
+        write!(
+            f,
+            "0x{:X} sector {} ({place}{}): {} non-zero byte(s) over {} bytes: {}",
+            offset,
+            self.sector,
+            self.kind,
+            self.non_zero,
+            self.data.len(),
+            preview(&self.data)
+        )
+    }
+}
+
+/// Renders the first bytes of a buffer, replacing the non-printable bytes by dots.
+fn preview(data: &[u8]) -> String {
+    data.iter()
+        .take(PREVIEW_LEN)
+        .map(|b| match b {
+            0x20..0x7F => *b as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+/// Scans the sectors of the reserved region for non-zero bytes outside of the structures they
+/// hold: the unused sectors, boot signature aside, and the reserved fields of the FSINFO sector
+/// and its backup.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to scan.
+///
+/// # Returns
+/// - `Ok(Vec<ReservedData>)`: The data found, one finding per sector, in sector order.
+/// - `Err(FATError)`: If the reserved region cannot be read.
+pub fn scan_reserved_sectors(vol: &FATVol) -> Result<Vec<ReservedData>, FATError> {
+    let sector_len = *vol.info().bytes_per_sector() as usize;
+    let mut findings = vec![];
+
+    for sector in 0..vol.reserved_sector_count() {
+        vol.check_cancelled()?;
+        let Some(kind) = vol.reserved_sector_kind(sector) else {
+            break;
+        };
+        let ranges = match kind {
+            ReservedSector::Boot | ReservedSector::BackupBoot => continue,
+            ReservedSector::FsInfo | ReservedSector::BackupFsInfo => {
+                FsInfo::RESERVED_RANGES.to_vec()
+            }
+            ReservedSector::Unused => vec![(0, sector_len)],
+        };
+        let offset = vol.sector_offset(vol.start() as u64 + sector as u64);
+        let mut buf = vol.read_bytes(offset, sector_len)?;
+        if kind == ReservedSector::Unused && buf.ends_with(&BOOT_SIGNATURE) {
+            let len = buf.len();
+            buf[len - BOOT_SIGNATURE.len()..].fill(0);
+        }
+
+        // Only the bytes outside of the structure are kept, the others are zeroed
+        let mut hidden = vec![0u8; sector_len];
+        for (start, end) in ranges.into_iter().filter(|(_, end)| *end <= sector_len) {
+            hidden[start..end].copy_from_slice(&buf[start..end]);
+        }
+        let (Some(first), Some(last)) = (
+            hidden.iter().position(|b| *b != 0),
+            hidden.iter().rposition(|b| *b != 0),
+        ) else {
+            continue;
+        };
+        findings.push(ReservedData {
+            sector,
+            kind,
+            non_zero: hidden.iter().filter(|b| **b != 0).count(),
+            data: hidden[first..=last].to_vec(),
+            provenance: Provenance::from_extents(
+                vol,
+                vec![(offset + first as u64, (last - first + 1) as u64)],
+            ),
+        });
+    }
+
+    Ok(findings)
+}
@@ -0,0 +1,178 @@
+//! Detection of data hidden in file slack and volume slack.
+//!
+//! The slack of a file spans from the end of its content to the end of its last cluster. Writers
+//! zero it or leave stale bytes of a previous file behind, and hiding tools (see
+//! [`SlackWriter::write_to_file_slack`](crate::traits::SlackWriter::write_to_file_slack)) store
+//! their payload there. This module flags the files whose slack holds non-zero bytes.
+//!
+//! The volume slack spans from the end of the data region to the end of the partition. No file
+//! system structure references it, which makes it the target of
+//! [`SlackWriter::write_to_volume_slack`](crate::traits::SlackWriter::write_to_volume_slack).
+
+use getset::Getters;
+use std::fmt;
+use std::path::PathBuf;
+
+use super::Provenance;
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::utils::escape_path;
+
+/// The non-zero content found in the slack of a file.
+#[derive(Debug, Getters)]
+pub struct SlackFinding {
+    /// The path of the file.
+    #[get = "pub"]
+    path: PathBuf,
+    /// The size of the slack of the file in bytes.
+    #[get = "pub"]
+    slack_size: u64,
+    /// The absolute byte offset and length of each span of slack holding data, from its first to
+    /// its last non-zero byte.
+    #[get = "pub"]
+    spans: Vec<(u64, u64)>,
+    /// The total length of the spans in bytes.
+    #[get = "pub"]
+    hidden_bytes: u64,
+    /// Where the spans lie in the image.
+    #[get = "pub"]
+    provenance: Provenance,
+}
+
+impl fmt::Display for SlackFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let spans: Vec<String> = self
+            .spans
+            .iter()
+            .map(|(offset, len)| format!("0x{:X}-0x{:X}", offset, offset + len))
+            .collect();
+        write!(
+            f,
+            "{}: {} hidden byte(s) in {} bytes of slack at {}",
+            escape_path(&self.path),
+            self.hidden_bytes,
+            self.slack_size,
+            spans.join(", ")
+        )
+    }
+}
+
+/// The content of the volume slack.
+#[derive(Debug, Getters)]
+pub struct VolumeSlack {
+    /// The absolute byte offset of the volume slack.
+    #[get = "pub"]
+    offset: u64,
+    /// The length of the volume slack in bytes.
+    #[get = "pub"]
+    len: u64,
+    /// The absolute byte offset of the first non-zero byte of the volume slack.
+    #[get = "pub"]
+    data_offset: u64,
+    /// The content of the volume slack from its first to its last non-zero byte, empty if the
+    /// volume slack is zeroed.
+    #[get = "pub"]
+    data: Vec<u8>,
+}
+
+impl VolumeSlack {
+    /// Returns whether the volume slack holds non-zero bytes.
+    pub fn holds_data(&self) -> bool {
+        !self.data.is_empty()
+    }
+}
+
+impl fmt::Display for VolumeSlack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.len == 0 {
+            return write!(
+                f,
+                "No volume slack: the data region ends with the partition."
+            );
+        }
+
+        write!(
+            f,
+            "Volume slack: 0x{:X}-0x{:X} ({} bytes)",
+            self.offset,
+            self.offset + self.len,
+            self.len
+        )?;
+        match self.holds_data() {
+            true => write!(
+                f,
+                "\n{} byte(s) of data at 0x{:X}-0x{:X}",
+                self.data.len(),
+                self.data_offset,
+                self.data_offset + self.data.len() as u64
+            ),
+            false => write!(f, "\nThe volume slack is zeroed."),
+        }
+    }
+}
+
+/// Reads the volume slack and locates its non-zero content.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to scan.
+///
+/// # Returns
+/// - `Ok(VolumeSlack)`: The content of the volume slack.
+/// - `Err(FATError)`: If the volume slack cannot be read.
+pub fn scan_volume_slack(vol: &FATVol) -> Result<VolumeSlack, FATError> {
+    let (offset, len) = vol.volume_slack_extent();
+    let slack = vol.read_bytes(offset, len as usize)?;
+
+    let start = slack.iter().position(|b| *b != 0);
+    let end = slack.iter().rposition(|b| *b != 0);
+    let (data_offset, data) = match (start, end) {
+        (Some(start), Some(end)) => (offset + start as u64, slack[start..=end].to_vec()),
+        _ => (offset, vec![]),
+    };
+
+    Ok(VolumeSlack {
+        offset,
+        len,
+        data_offset,
+        data,
+    })
+}
+
+/// Scans the slack of every file for non-zero content.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to scan.
+///
+/// # Returns
+/// - `Ok(Vec<SlackFinding>)`: The files whose slack holds non-zero bytes, in walk order.
+/// - `Err(FATError)`: If the volume cannot be read.
+pub fn scan_file_slack(vol: &FATVol) -> Result<Vec<SlackFinding>, FATError> {
+    let mut findings = vec![];
+    for (path, entry) in vol.walk_files()? {
+        vol.check_cancelled()?;
+        let mut slack_size = 0;
+        let mut spans = vec![];
+        for (offset, len) in vol.file_slack_extents(&entry)? {
+            slack_size += len;
+            let data = vol.read_bytes(offset, len as usize)?;
+            if let (Some(first), Some(last)) = (
+                data.iter().position(|b| *b != 0),
+                data.iter().rposition(|b| *b != 0),
+            ) {
+                spans.push((offset + first as u64, (last - first + 1) as u64));
+            }
+        }
+
+        if !spans.is_empty() {
+            findings.push(SlackFinding {
+                provenance: Provenance::from_extents(vol, spans.clone()),
+                path,
+                slack_size,
+                hidden_bytes: spans.iter().map(|(_, len)| len).sum(),
+                spans,
+            });
+        }
+    }
+
+    Ok(findings)
+}
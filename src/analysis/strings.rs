@@ -0,0 +1,333 @@
+//! Extraction of printable strings from unallocated and slack space.
+//!
+//! Like the `strings` utility, this module extracts the runs of printable ASCII and UTF-16LE
+//! characters, but only from the regions where hidden or leftover data lives, and attributes each
+//! string to the region it was found in.
+//!
+//! Patterns are also searched within the cluster chains of the files under a directory, slack
+//! included, reporting each match with its offset and some context.
+
+use getset::Getters;
+use regex::Regex;
+use regex::bytes::Regex as BytesRegex;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::{RegionClass, RegionKind};
+use crate::events::Event;
+use crate::filesystem::clusters::ClusterKind;
+use crate::filesystem::fat::{FATVol, contiguous_runs};
+use crate::filesystem::fat_error::FATError;
+use crate::utils::escape_path;
+
+/// Maximum number of free clusters read at once.
+const SCAN_RUN_LEN: u32 = 2048;
+
+/// Default minimum length of an extracted string, in characters.
+pub const DEFAULT_MIN_LEN: usize = 4;
+
+/// Number of bytes of context shown on each side of a match.
+const CONTEXT_LEN: usize = 16;
+
+/// Encoding of an extracted string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringEncoding {
+    Ascii,
+    Utf16Le,
+}
+
+impl fmt::Display for StringEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StringEncoding::Ascii => write!(f, "ascii"),
+            StringEncoding::Utf16Le => write!(f, "utf16le"),
+        }
+    }
+}
+
+/// Options of a string extraction.
+#[derive(Debug, Clone)]
+pub struct StringsOptions {
+    /// The kinds of region to extract strings from.
+    pub regions: Vec<RegionKind>,
+    /// The minimum length of a string, in characters.
+    pub min_len: usize,
+    /// Only the strings matching this pattern are kept.
+    pub filter: Option<Regex>,
+}
+
+impl Default for StringsOptions {
+    fn default() -> Self {
+        Self {
+            regions: RegionKind::ALL.to_vec(),
+            min_len: DEFAULT_MIN_LEN,
+            filter: None,
+        }
+    }
+}
+
+/// A string found in a region of the volume.
+#[derive(Debug, Getters)]
+pub struct ExtractedString {
+    /// The class of the region containing the string.
+    #[get = "pub"]
+    class: RegionClass,
+    /// Absolute byte offset of the string on disk.
+    #[get = "pub"]
+    offset: u64,
+    /// The encoding of the string.
+    #[get = "pub"]
+    encoding: StringEncoding,
+    /// The decoded string.
+    #[get = "pub"]
+    text: String,
+}
+
+impl fmt::Display for ExtractedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: 0x{:X} [{}] {}",
+            self.class, self.offset, self.encoding, self.text
+        )
+    }
+}
+
+/// Returns true for the printable ASCII characters and the tab.
+fn is_printable(b: u8) -> bool {
+    b == b'\t' || (0x20..0x7F).contains(&b)
+}
+
+/// Extracts the printable ASCII and UTF-16LE strings of a buffer.
+///
+/// Only the UTF-16LE characters of the ASCII range are recognised.
+///
+/// # Parameters
+/// - `data`: The buffer to inspect.
+/// - `min_len`: The minimum length of a string, in characters.
+///
+/// # Returns
+/// - The offset in the buffer, the encoding and the text of each string, sorted by offset.
+pub fn extract_strings(data: &[u8], min_len: usize) -> Vec<(usize, StringEncoding, String)> {
+    let min_len = min_len.max(1);
+    let mut strings = vec![];
+
+    let mut start = 0;
+    for (i, b) in data.iter().chain(std::iter::once(&0)).enumerate() {
+        if is_printable(*b) {
+            continue;
+        }
+        if i - start >= min_len {
+            let text = String::from_utf8_lossy(&data[start..i]).into_owned();
+            strings.push((start, StringEncoding::Ascii, text));
+        }
+        start = i + 1;
+    }
+
+    // UTF-16LE strings starting at even and at odd offsets
+    for parity in 0..2 {
+        let units = data.get(parity..).unwrap_or_default().chunks_exact(2);
+        let mut start = 0;
+        for (i, unit) in units.chain(std::iter::once(&[0u8, 1][..])).enumerate() {
+            if is_printable(unit[0]) && unit[1] == 0 {
+                continue;
+            }
+            if i - start >= min_len {
+                let text = data[parity + 2 * start..parity + 2 * i]
+                    .iter()
+                    .step_by(2)
+                    .map(|b| *b as char)
+                    .collect();
+                strings.push((parity + 2 * start, StringEncoding::Utf16Le, text));
+            }
+            start = i + 1;
+        }
+    }
+
+    strings.sort_by_key(|(offset, _, _)| *offset);
+    strings
+}
+
+/// Extracts the strings of the selected regions of a volume.
+///
+/// Strings spanning contiguous unallocated clusters are extracted whole.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to scan.
+/// - `options`: The regions to scan, the minimum length and the optional filter.
+///
+/// # Returns
+/// - `Ok(Vec<ExtractedString>)`: The strings, sorted by region class then offset.
+/// - `Err(FATError)`: If the volume cannot be read.
+pub fn volume_strings(
+    vol: &FATVol,
+    options: &StringsOptions,
+) -> Result<Vec<ExtractedString>, FATError> {
+    let mut strings = vec![];
+
+    if options.regions.contains(&RegionKind::Unallocated) {
+        let free_clusters: Vec<u32> = vol
+            .clusters(ClusterKind::Free)?
+            .map(|region| *region.cluster())
+            .collect();
+        // The holes of a sparse image are zeros, which hold no string
+        let free_clusters = vol.data_clusters(&free_clusters)?;
+        let mut scanned = 0;
+
+        for (first, count) in contiguous_runs(&free_clusters, SCAN_RUN_LEN) {
+            vol.check_cancelled()?;
+            let clusters: Vec<u32> = (first..first + count).collect();
+            let data = vol.read_chain(&clusters)?;
+            let offset = vol.cluster_offset(first);
+            push_strings(
+                RegionClass::Unallocated,
+                offset,
+                &data,
+                options,
+                &mut strings,
+            );
+
+            scanned += count as u64;
+            vol.emit(|| Event::Progress {
+                task: "strings",
+                done: scanned,
+                total: free_clusters.len() as u64,
+            });
+        }
+    }
+
+    if options.regions.contains(&RegionKind::FileSlack) {
+        for (path, entry) in vol.walk_files()? {
+            vol.check_cancelled()?;
+            for (offset, len) in vol.file_slack_extents(&entry)? {
+                let data = vol.read_bytes(offset, len as usize)?;
+                let class = RegionClass::FileSlack(path.clone());
+                push_strings(class, offset, &data, options, &mut strings);
+            }
+        }
+    }
+
+    if options.regions.contains(&RegionKind::VolumeSlack) {
+        let (offset, len) = vol.volume_slack_extent();
+        let data = vol.read_bytes(offset, len as usize)?;
+        push_strings(
+            RegionClass::VolumeSlack,
+            offset,
+            &data,
+            options,
+            &mut strings,
+        );
+    }
+
+    Ok(strings)
+}
+
+fn push_strings(
+    class: RegionClass,
+    offset: u64,
+    data: &[u8],
+    options: &StringsOptions,
+    strings: &mut Vec<ExtractedString>,
+) {
+    strings.extend(
+        extract_strings(data, options.min_len)
+            .into_iter()
+            .filter(|(_, _, text)| options.filter.as_ref().is_none_or(|re| re.is_match(text)))
+            .map(|(pos, encoding, text)| ExtractedString {
+                class: class.clone(),
+                offset: offset + pos as u64,
+                encoding,
+                text,
+            }),
+    );
+}
+
+/// A match of a pattern in the cluster chain of a file.
+#[derive(Debug, Getters)]
+pub struct GrepMatch {
+    /// The path of the file, relative to the root directory.
+    #[get = "pub"]
+    path: PathBuf,
+    /// The offset of the match from the start of the file.
+    #[get = "pub"]
+    file_offset: u64,
+    /// Absolute byte offset of the match on disk.
+    #[get = "pub"]
+    offset: u64,
+    /// Whether the match starts past the end of the file content, in its slack.
+    #[get = "pub"]
+    in_slack: bool,
+    /// The match and the bytes around it, non-printable bytes shown as dots.
+    #[get = "pub"]
+    context: String,
+}
+
+impl fmt::Display for GrepMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "/{}+0x{:X} (0x{:X}){}: {}",
+            escape_path(&self.path),
+            self.file_offset,
+            self.offset,
+            if self.in_slack { " [slack]" } else { "" },
+            self.context
+        )
+    }
+}
+
+/// Searches a pattern within the cluster chains of the files under a directory.
+///
+/// The whole chain of each file is searched, so matches in its slack are reported too. Matches
+/// don't overlap.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to search.
+/// - `pattern`: The pattern, matched against raw bytes.
+/// - `scope`: The directory, or the file, to search under. An empty path searches every file.
+///
+/// # Returns
+/// - `Ok(Vec<GrepMatch>)`: The matches, in directory tree order then offset.
+/// - `Err(FATError)`: If the scope doesn't exist, or the volume cannot be read.
+pub fn grep_files(
+    vol: &FATVol,
+    pattern: &BytesRegex,
+    scope: &Path,
+) -> Result<Vec<GrepMatch>, FATError> {
+    if scope.components().count() > 0 && !vol.walk_entries()?.iter().any(|(path, _)| path == scope)
+    {
+        return Err(FATError::FileNotFound);
+    }
+    let cluster_size = vol.cluster_size() as u64;
+    let mut matches = vec![];
+
+    for (path, entry) in vol.walk_files()? {
+        vol.check_cancelled()?;
+        if !path.starts_with(scope) || entry.cluster_number() == 0 {
+            continue;
+        }
+        let chain = vol
+            .cluster_chain(entry.cluster_number())
+            .collect::<Result<Vec<_>, _>>()?;
+        let data = vol.read_chain(&chain)?;
+
+        for found in pattern.find_iter(&data) {
+            let pos = found.start() as u64;
+            let context = &data[found.start().saturating_sub(CONTEXT_LEN)
+                ..(found.end() + CONTEXT_LEN).min(data.len())];
+            matches.push(GrepMatch {
+                path: path.clone(),
+                file_offset: pos,
+                offset: vol.cluster_offset(chain[(pos / cluster_size) as usize])
+                    + pos % cluster_size,
+                in_slack: pos >= *entry.file_size() as u64,
+                context: context
+                    .iter()
+                    .map(|b| if is_printable(*b) { *b as char } else { '.' })
+                    .collect(),
+            });
+        }
+    }
+
+    Ok(matches)
+}
@@ -0,0 +1,369 @@
+//! One-screen triage overview of a volume.
+//!
+//! The first question on a new image is where to look. This module runs the cheap analyses of
+//! the crate at once and condenses their results: what the volume holds, what is hidden in it,
+//! what looks wrong with it and the findings worth a closer look, each pointing to the command
+//! giving the details.
+//!
+//! Which checks run and the order of the findings are set by a [`TriageProfile`].
+
+use getset::Getters;
+use std::cmp::Reverse;
+use std::fmt;
+
+use super::attrs::hidden_entries;
+use super::dirslack::scan_dir_slack;
+use super::fatslack::scan_fat_slack;
+use super::magic::identify_files;
+use super::names::scan_names;
+use super::payload::detect_payloads;
+use super::profile::{Check, TriageProfile};
+use super::recover::deleted_entries;
+use super::slack::{scan_file_slack, scan_volume_slack};
+use crate::filesystem::clusters::ClusterKind;
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::filesystem::info::VolumeInfo;
+use crate::utils::human_size;
+
+/// The number of findings shown by the overview, the others being counted.
+pub const TOP_FINDINGS: usize = 5;
+
+/// A finding of the overview.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct Finding {
+    /// The check that made the finding.
+    #[get = "pub"]
+    check: Check,
+    /// The priority of the finding, from 0 to 100, given by the weight of its check.
+    #[get = "pub"]
+    score: u8,
+    /// The description of the finding, pointing to the command giving the details.
+    #[get = "pub"]
+    description: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:>3}] {}", self.score, self.description)
+    }
+}
+
+/// The triage overview of a volume.
+///
+/// The counts of the checks skipped by the profile are `None`.
+#[derive(Debug, Getters)]
+pub struct VolumeSummary {
+    /// The name of the profile the overview was built with.
+    #[get = "pub"]
+    profile: String,
+    /// The geometry and identity of the volume.
+    #[get = "pub"]
+    info: VolumeInfo,
+    /// The number of live files.
+    #[get = "pub"]
+    files: usize,
+    /// The number of live directories.
+    #[get = "pub"]
+    dirs: usize,
+    /// The number of deleted entries still pointing to data.
+    #[get = "pub"]
+    deleted: Option<usize>,
+    /// The number of live entries hidden by their attributes (hidden, system, misused volume
+    /// label).
+    #[get = "pub"]
+    hidden: Option<usize>,
+    /// The number of clusters marked as bad, and of those holding non-zero bytes.
+    #[get = "pub"]
+    bad_clusters: Option<(usize, usize)>,
+    /// The number of files whose slack holds non-zero bytes, and the number of those bytes.
+    #[get = "pub"]
+    file_slack: Option<(usize, u64)>,
+    /// The number of bytes of the volume slack from its first to its last non-zero byte.
+    #[get = "pub"]
+    volume_slack_bytes: Option<u64>,
+    /// The structural anomalies of the volume.
+    #[get = "pub"]
+    anomalies: Vec<String>,
+    /// The findings worth a closer look, by decreasing score.
+    #[get = "pub"]
+    findings: Vec<Finding>,
+}
+
+/// Formats a count, or notes that its check was skipped.
+fn count<T: fmt::Display>(count: Option<T>) -> String {
+    match count {
+        Some(count) => count.to_string(),
+        None => String::from("not checked"),
+    }
+}
+
+impl fmt::Display for VolumeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = &self.info;
+        let cluster_size = *info.bytes_per_sector() as u64 * *info.sectors_per_cluster() as u64;
+        writeln!(
+            f,
+            "Volume:       {} \"{}\", serial {:08X}, {} clusters of {} bytes ({})",
+            info.fat_type(),
+            info.label(),
+            info.serial(),
+            info.cluster_count(),
+            cluster_size,
+            human_size(*info.total_sectors() as u64 * *info.bytes_per_sector() as u64)
+        )?;
+        writeln!(f, "Profile:      {}", self.profile)?;
+        writeln!(
+            f,
+            "Entries:      files: {}, directories: {}, deleted: {} (recover)",
+            self.files,
+            self.dirs,
+            count(self.deleted)
+        )?;
+        writeln!(
+            f,
+            "Hidden:       hidden or system entries: {} (tree --hidden-only)",
+            count(self.hidden)
+        )?;
+        match self.bad_clusters {
+            Some((bad, with_content)) => {
+                writeln!(f, "Bad clusters: {bad}, with content: {with_content}")?
+            }
+            None => writeln!(f, "Bad clusters: not checked")?,
+        }
+        writeln!(
+            f,
+            "Slack:        {} (scan slack), {} (slack volume)",
+            match self.file_slack {
+                Some((files, bytes)) => format!("{bytes} byte(s) in the slack of {files} file(s)"),
+                None => String::from("file slack not checked"),
+            },
+            match self.volume_slack_bytes {
+                Some(bytes) => format!("{bytes} byte(s) in the volume slack"),
+                None => String::from("volume slack not checked"),
+            }
+        )?;
+
+        writeln!(f, "Anomalies:")?;
+        if self.anomalies.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for anomaly in &self.anomalies {
+            writeln!(f, "  - {anomaly}")?;
+        }
+
+        writeln!(f, "Top findings:")?;
+        if self.findings.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for finding in self.findings.iter().take(TOP_FINDINGS) {
+            writeln!(f, "  - {finding}")?;
+        }
+        if self.findings.len() > TOP_FINDINGS {
+            writeln!(f, "  ... and {} more", self.findings.len() - TOP_FINDINGS)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the triage overview of a volume with the default profile.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to summarize.
+///
+/// # Returns
+/// - `Ok(VolumeSummary)`: The overview.
+/// - `Err(FATError)`: If the FATs or the directory tree cannot be read.
+pub fn summarize(vol: &FATVol) -> Result<VolumeSummary, FATError> {
+    summarize_with(vol, &TriageProfile::default())
+}
+
+/// Builds the triage overview of a volume, running the checks of a profile.
+///
+/// The directory tree is walked several times and the first cluster of every file is read, but
+/// unallocated space is neither carved nor scanned.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to summarize.
+/// - `profile`: The checks to run and the weight of their findings.
+///
+/// # Returns
+/// - `Ok(VolumeSummary)`: The overview, its findings ordered by score, then by check as listed
+///   in the profile.
+/// - `Err(FATError)`: If the FATs or the directory tree cannot be read.
+pub fn summarize_with(vol: &FATVol, profile: &TriageProfile) -> Result<VolumeSummary, FATError> {
+    let entries = vol.walk_entries()?;
+    let files = entries
+        .iter()
+        .filter(|(_, entry)| entry.is_regular_file())
+        .count();
+    let dirs = entries
+        .iter()
+        .filter(|(_, entry)| entry.is_regular_dir())
+        .count();
+
+    let mut anomalies = vec![];
+    let fsstat = vol.fsstat()?;
+    anomalies.extend(fsstat.oddities().iter().cloned());
+    if let Some((sector, false)) = vol.check_backup_boot()? {
+        anomalies.push(format!(
+            "The boot sector differs from its backup at sector {sector} (recover)."
+        ));
+    }
+    if !fsstat.diverging_fats().is_empty() {
+        anomalies.push(format!(
+            "FAT copies {:?} differ from the active FAT (fsstat).",
+            fsstat.diverging_fats()
+        ));
+    }
+    if *fsstat.clean_shutdown() == Some(false) {
+        anomalies.push(String::from("The volume was not cleanly unmounted."));
+    }
+    if *fsstat.hard_error() == Some(true) {
+        anomalies.push(String::from("The volume recorded a disk I/O error."));
+    }
+
+    let mut summary = VolumeSummary {
+        profile: profile.name().clone(),
+        info: vol.info(),
+        files,
+        dirs,
+        deleted: None,
+        hidden: None,
+        bad_clusters: None,
+        file_slack: None,
+        volume_slack_bytes: None,
+        anomalies,
+        findings: vec![],
+    };
+    for (check, weight) in profile.weights() {
+        let findings = run_check(vol, *check, &mut summary)?;
+        summary
+            .findings
+            .extend(findings.into_iter().map(|description| Finding {
+                check: *check,
+                score: *weight,
+                description,
+            }));
+    }
+    // The sort is stable, the findings of a score keep the order of the profile
+    summary
+        .findings
+        .sort_by_key(|finding| Reverse(finding.score));
+    Ok(summary)
+}
+
+/// Runs a check of the overview, recording its counts in the summary.
+///
+/// # Returns
+/// - `Ok(Vec<String>)`: The descriptions of the findings of the check.
+/// - `Err(FATError)`: If the volume cannot be read.
+fn run_check(
+    vol: &FATVol,
+    check: Check,
+    summary: &mut VolumeSummary,
+) -> Result<Vec<String>, FATError> {
+    let findings = match check {
+        Check::Payloads => detect_payloads(vol)?
+            .iter()
+            .map(|candidate| format!("{candidate} (scan payloads)"))
+            .collect(),
+        Check::VolumeSlack => {
+            let volume_slack = scan_volume_slack(vol)?;
+            summary.volume_slack_bytes = Some(volume_slack.data().len() as u64);
+            match volume_slack.holds_data() {
+                true => vec![format!(
+                    "{} byte(s) of data in the volume slack at 0x{:X} (slack volume)",
+                    volume_slack.data().len(),
+                    volume_slack.data_offset()
+                )],
+                false => vec![],
+            }
+        }
+        Check::BadClusters => {
+            // Bad clusters are never read by the file system, so content in them was put there
+            // on purpose
+            let mut bad_clusters = 0;
+            let mut bad_with_content = vec![];
+            for region in vol.clusters(ClusterKind::Bad)? {
+                vol.check_cancelled()?;
+                bad_clusters += 1;
+                if vol
+                    .read_cluster(*region.cluster())?
+                    .iter()
+                    .any(|byte| *byte != 0)
+                {
+                    bad_with_content.push(region);
+                }
+            }
+            summary.bad_clusters = Some((bad_clusters, bad_with_content.len()));
+            bad_with_content
+                .first()
+                .map(|first| {
+                    format!(
+                        "{} bad cluster(s) holding data, the first one being cluster {} at 0x{:X}",
+                        bad_with_content.len(),
+                        first.cluster(),
+                        first.offset()
+                    )
+                })
+                .into_iter()
+                .collect()
+        }
+        Check::FileSlack => {
+            let slack = scan_file_slack(vol)?;
+            summary.file_slack = Some((
+                slack.len(),
+                slack.iter().map(|finding| finding.hidden_bytes()).sum(),
+            ));
+            slack
+                .iter()
+                .map(|finding| format!("{finding} (scan slack)"))
+                .collect()
+        }
+        Check::DirSlack => scan_dir_slack(vol)?
+            .iter()
+            .map(|remnant| format!("{remnant} (scan dirslack)"))
+            .collect(),
+        Check::Deleted => {
+            let deleted = deleted_entries(vol)?;
+            summary.deleted = Some(deleted.len());
+            deleted
+                .iter()
+                .filter(|scored| *scored.confidence() >= 50)
+                .map(|scored| format!("{scored} (recover)"))
+                .collect()
+        }
+        Check::Types => {
+            let mismatches = identify_files(vol)?
+                .iter()
+                .filter(|file| *file.mismatch())
+                .count();
+            match mismatches {
+                0 => vec![],
+                _ => vec![format!(
+                    "{mismatches} file(s) whose extension doesn't match their content (scan types)"
+                )],
+            }
+        }
+        Check::Names => match scan_names(vol)?.len() {
+            0 => vec![],
+            names => vec![format!("{names} entries with unusual names (scan names)")],
+        },
+        Check::FatSlack => match scan_fat_slack(vol)?.len() {
+            0 => vec![],
+            runs => vec![format!(
+                "{runs} run(s) of non-zero FAT entries past the last cluster (scan fatslack)"
+            )],
+        },
+        Check::Hidden => {
+            let hidden = hidden_entries(vol)?;
+            summary.hidden = Some(hidden.len());
+            hidden
+                .iter()
+                .map(|flagged| format!("{flagged} (tree --hidden-only)"))
+                .collect()
+        }
+    };
+    Ok(findings)
+}
@@ -0,0 +1,161 @@
+//! Comparison of the directory trees of two volumes.
+//!
+//! Comparing a lab image before and after it was tampered with, or two acquisitions of the same
+//! stick, shows what changed in between. The regular files of both volumes are hashed and matched
+//! by path, without regard to case; a file removed from one path and added at another with the
+//! same content is reported as renamed rather than as a removal and an addition.
+
+use getset::Getters;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::hash::{FileDigest, hash_all_files};
+use super::manifest::path_key;
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::utils::escape_path;
+
+/// The differences between the files of two volumes.
+#[derive(Debug, Default, Getters)]
+pub struct TreeDiff {
+    /// The number of files at the same path with the same content.
+    #[get = "pub"]
+    unchanged: usize,
+    /// The files of the second volume missing from the first one.
+    #[get = "pub"]
+    added: Vec<FileDigest>,
+    /// The files of the first volume missing from the second one.
+    #[get = "pub"]
+    removed: Vec<FileDigest>,
+    /// The files at the same path whose content differs: the file of the first volume, the file
+    /// of the second one and the differences.
+    #[get = "pub"]
+    modified: Vec<(FileDigest, FileDigest, Vec<String>)>,
+    /// The files moved to another path with the same content: the file of the first volume and
+    /// the file of the second one.
+    #[get = "pub"]
+    renamed: Vec<(FileDigest, FileDigest)>,
+}
+
+impl TreeDiff {
+    /// Returns whether both volumes hold the same files.
+    pub fn is_clean(&self) -> bool {
+        self.difference_count() == 0
+    }
+
+    /// Returns the number of files added, removed, modified or renamed.
+    pub fn difference_count(&self) -> usize {
+        self.added.len() + self.removed.len() + self.modified.len() + self.renamed.len()
+    }
+}
+
+impl fmt::Display for TreeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for digest in &self.removed {
+            writeln!(
+                f,
+                "Removed:  /{} ({} bytes)",
+                escape_path(digest.path()),
+                digest.size()
+            )?;
+        }
+        for digest in &self.added {
+            writeln!(
+                f,
+                "Added:    /{} ({} bytes, SHA-256 {})",
+                escape_path(digest.path()),
+                digest.size(),
+                digest.sha256()
+            )?;
+        }
+        for (_, after, differences) in &self.modified {
+            writeln!(
+                f,
+                "Modified: /{}: {}",
+                escape_path(after.path()),
+                differences.join(", ")
+            )?;
+        }
+        for (before, after) in &self.renamed {
+            writeln!(
+                f,
+                "Renamed:  /{} -> /{} ({} bytes)",
+                escape_path(before.path()),
+                escape_path(after.path()),
+                after.size()
+            )?;
+        }
+        writeln!(
+            f,
+            "{} file(s) unchanged, {} added, {} removed, {} modified, {} renamed.",
+            self.unchanged,
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len(),
+            self.renamed.len()
+        )
+    }
+}
+
+/// Lists how the content of a file differs between two volumes, empty if it is the same.
+fn differences(before: &FileDigest, after: &FileDigest) -> Vec<String> {
+    let mut differences = vec![];
+    if before.size() != after.size() {
+        differences.push(format!("size {} -> {} bytes", before.size(), after.size()));
+    }
+    if before.sha256() != after.sha256() {
+        differences.push(String::from("SHA-256 differs"));
+    }
+    differences
+}
+
+/// Compares the regular files of two volumes.
+///
+/// # Parameters
+/// - `before`: The reference volume, e.g. the base image of a lab.
+/// - `after`: The volume compared against it.
+///
+/// # Returns
+/// - `Ok(TreeDiff)`: The files added, removed, modified and renamed, in directory tree order.
+/// - `Err(FATError)`: If the directory tree or a file of either volume cannot be read.
+pub fn diff_trees(before: &FATVol, after: &FATVol) -> Result<TreeDiff, FATError> {
+    let mut after_files: HashMap<String, FileDigest> = HashMap::new();
+    let mut order = vec![];
+    for digest in hash_all_files(after)? {
+        let key = path_key(digest.path());
+        order.push(key.clone());
+        after_files.insert(key, digest);
+    }
+
+    let mut diff = TreeDiff::default();
+    for digest in hash_all_files(before)? {
+        match after_files.remove(&path_key(digest.path())) {
+            None => diff.removed.push(digest),
+            Some(other) => match differences(&digest, &other) {
+                differences if differences.is_empty() => diff.unchanged += 1,
+                differences => diff.modified.push((digest, other, differences)),
+            },
+        }
+    }
+    let mut added: Vec<FileDigest> = order
+        .iter()
+        .filter_map(|key| after_files.remove(key))
+        .collect();
+
+    // A removed file whose content was added elsewhere was moved, each added file matching once.
+    // Empty files all share the same digest, so they are never paired.
+    let mut removed = vec![];
+    for digest in diff.removed {
+        match added
+            .iter()
+            .position(|other| *other.size() > 0 && other.sha256() == digest.sha256())
+        {
+            Some(idx) => diff.renamed.push((digest, added.remove(idx))),
+            None => removed.push(digest),
+        }
+    }
+    diff.removed = removed;
+    diff.added = added;
+
+    Ok(diff)
+}
@@ -0,0 +1,182 @@
+//! Detection of deliberately wiped regions.
+//!
+//! Wiping tools overwrite data with constant bytes (`00`, `FF`) or short repeating patterns
+//! (`DEADBEEF`). This module looks for such regions in unallocated clusters and slack space.
+
+use getset::Getters;
+use std::fmt;
+
+use super::RegionClass;
+use crate::events::Event;
+use crate::filesystem::clusters::ClusterKind;
+use crate::filesystem::fat::{FATVol, contiguous_runs};
+use crate::filesystem::fat_error::FATError;
+
+/// The pattern periods, in bytes, checked by the detection.
+const PATTERN_PERIODS: [usize; 4] = [1, 2, 4, 8];
+
+/// Maximum number of free clusters read at once.
+const SCAN_RUN_LEN: u32 = 2048;
+
+/// Regions shorter than this are too small for a pattern to be meaningful.
+const MIN_REGION_LEN: u64 = 16;
+
+/// A region filled with a constant byte or a repeating pattern.
+#[derive(Debug, Getters)]
+pub struct WipedRegion {
+    /// The class of the region.
+    #[get = "pub"]
+    class: RegionClass,
+    /// Absolute byte offset of the region on disk.
+    #[get = "pub"]
+    offset: u64,
+    /// Length of the region in bytes.
+    #[get = "pub"]
+    len: u64,
+    /// The repeating pattern filling the region.
+    #[get = "pub"]
+    pattern: Vec<u8>,
+}
+
+impl fmt::Display for WipedRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: 0x{:X}-0x{:X} ({} bytes) filled with {}",
+            self.class,
+            self.offset,
+            self.offset + self.len,
+            self.len,
+            self.pattern
+                .iter()
+                .map(|b| format!("{b:02X}"))
+                .collect::<String>()
+        )
+    }
+}
+
+/// Returns the shortest repeating pattern filling the whole buffer, if any.
+///
+/// # Parameters
+/// - `data`: The buffer to inspect.
+///
+/// # Returns
+/// - `Some(pattern)` if the buffer is a repetition of a pattern of 1, 2, 4 or 8 bytes.
+/// - `None` otherwise.
+pub fn repeating_pattern(data: &[u8]) -> Option<Vec<u8>> {
+    PATTERN_PERIODS
+        .iter()
+        .filter(|period| data.len() >= 2 * **period)
+        .find(|period| {
+            data.iter()
+                .zip(data.iter().skip(**period))
+                .all(|(a, b)| a == b)
+        })
+        .map(|period| data[..*period].to_vec())
+}
+
+/// Scans the unallocated clusters, the file slack and the volume slack for wiped regions.
+///
+/// Contiguous unallocated clusters filled with the same pattern are reported as a single region.
+///
+/// # Parameters
+/// - `vol`: The FAT volume to scan.
+///
+/// # Returns
+/// - `Ok(Vec<WipedRegion>)`: The wiped regions, sorted by region class then offset.
+/// - `Err(FATError)`: If the volume cannot be read.
+pub fn detect_wiped_regions(vol: &FATVol) -> Result<Vec<WipedRegion>, FATError> {
+    let mut regions: Vec<WipedRegion> = vec![];
+
+    let free_clusters: Vec<u32> = vol
+        .clusters(ClusterKind::Free)?
+        .map(|region| *region.cluster())
+        .collect();
+    // The clusters in the holes of a sparse image are zeros, they aren't read
+    let data_clusters = vol.data_clusters(&free_clusters)?;
+    let cluster_size = vol.cluster_size() as u64;
+    let mut scanned = 0;
+
+    for (first, count) in contiguous_runs(&free_clusters, SCAN_RUN_LEN) {
+        vol.check_cancelled()?;
+        let clusters: Vec<u32> = (first..first + count).collect();
+        let read: Vec<u32> = clusters
+            .iter()
+            .copied()
+            .filter(|cluster| data_clusters.binary_search(cluster).is_ok())
+            .collect();
+        let run = vol.read_chain(&read)?;
+        let mut data = run.chunks(cluster_size as usize);
+
+        for cluster in clusters {
+            let pattern = match read.binary_search(&cluster) {
+                Ok(_) => data.next().and_then(repeating_pattern),
+                Err(_) => Some(vec![0]),
+            };
+            let Some(pattern) = pattern else {
+                continue;
+            };
+
+            let offset = vol.cluster_offset(cluster);
+            match regions.last_mut() {
+                Some(last) if last.offset + last.len == offset && last.pattern == pattern => {
+                    last.len += cluster_size;
+                }
+                _ => regions.push(WipedRegion {
+                    class: RegionClass::Unallocated,
+                    offset,
+                    len: cluster_size,
+                    pattern,
+                }),
+            }
+        }
+
+        scanned += count as u64;
+        vol.emit(|| Event::Progress {
+            task: "wipe detection",
+            done: scanned,
+            total: free_clusters.len() as u64,
+        });
+    }
+
+    for (path, entry) in vol.walk_files()? {
+        vol.check_cancelled()?;
+        for (offset, len) in vol.file_slack_extents(&entry)? {
+            check_region(
+                vol,
+                RegionClass::FileSlack(path.clone()),
+                offset,
+                len,
+                &mut regions,
+            )?;
+        }
+    }
+
+    let (offset, len) = vol.volume_slack_extent();
+    check_region(vol, RegionClass::VolumeSlack, offset, len, &mut regions)?;
+
+    Ok(regions)
+}
+
+fn check_region(
+    vol: &FATVol,
+    class: RegionClass,
+    offset: u64,
+    len: u64,
+    regions: &mut Vec<WipedRegion>,
+) -> Result<(), FATError> {
+    if len < MIN_REGION_LEN {
+        return Ok(());
+    }
+
+    if let Some(pattern) = repeating_pattern(&vol.read_bytes(offset, len as usize)?) {
+        regions.push(WipedRegion {
+            class,
+            offset,
+            len,
+            pattern,
+        });
+    }
+
+    Ok(())
+}
@@ -2,16 +2,69 @@
 //!
 //! The program provides an interactive command-line interface for analyzing FAT32 disk images.
 //! Users can open disk images, print their layout, and quit the program using commands.
+//!
+//! Commands can also be run non-interactively, from `-c "<command>; <command>"` or from a script
+//! piped on the standard input. The run then stops at the first failing command, prints a
+//! machine-parsable error line on the standard error (`error:<kind>:<line>:<message>`) and exits
+//! with the code of the kind of error:
+//! - 2 (`usage`): unknown or malformed command, or command issued before opening a disk or
+//!   selecting a volume.
+//! - 3 (`io`): a file cannot be read or written.
+//! - 4 (`validation`): the disk or volume structures are invalid or unsupported.
+//! - 5 (`not-found`): the file, directory or volume doesn't exist.
 
-use fat_forensics::commands::Command;
-use fat_forensics::traits::TreeDisplay;
-use fat_forensics::utils::write_file_at;
-use fat_forensics::{Disk, traits::LayoutDisplay};
-use log::{error, warn};
+use fat_forensics::analysis::attrs::hidden_entries;
+use fat_forensics::analysis::carve::{CarverRegistry, carve, dedup_carved};
+use fat_forensics::analysis::classify::classify_free_space;
+use fat_forensics::analysis::dirslack::scan_dir_slack;
+use fat_forensics::analysis::dupes::find_duplicates;
+use fat_forensics::analysis::entropy::file_entropies;
+use fat_forensics::analysis::fatslack::scan_fat_slack;
+use fat_forensics::analysis::gap::scan_mbr_gap;
+use fat_forensics::analysis::hash;
+use fat_forensics::analysis::magic::identify_files;
+use fat_forensics::analysis::manifest::{compare_manifest, read_manifest};
+use fat_forensics::analysis::names::scan_names;
+use fat_forensics::analysis::payload::detect_payloads;
+use fat_forensics::analysis::profile::TriageProfile;
+use fat_forensics::analysis::recover::plan_recovery;
+use fat_forensics::analysis::reserved::scan_reserved_sectors;
+use fat_forensics::analysis::slack::{scan_file_slack, scan_volume_slack};
+use fat_forensics::analysis::strings::{grep_files, volume_strings};
+use fat_forensics::analysis::summary::summarize_with;
+use fat_forensics::analysis::treediff::diff_trees;
+use fat_forensics::analysis::wipe::detect_wiped_regions;
+use fat_forensics::cache::DEFAULT_CACHE_CAPACITY;
+use fat_forensics::cancel::CancellationToken;
+use fat_forensics::commands::{
+    BootSectorAction, Command, DotTarget, FatAction, HashOptions, HashTarget, JournalAction,
+    MbrAction, RootAction, ScanTarget, Setting, SnapshotAction, WipeTarget, WriteAddress,
+    help_text,
+};
+use fat_forensics::events::LogSink;
+use fat_forensics::export::archive::{ArchiveFormat, export_archive};
+use fat_forensics::export::dfxml::export_dfxml;
+use fat_forensics::export::dot;
+use fat_forensics::image::{ImageFormat, ImageSource, IoStats, convert_image};
+use fat_forensics::journal::{self, Journal, JournalEntry};
+use fat_forensics::limits::Limits;
+use fat_forensics::logging::LogOptions;
+use fat_forensics::snapshot;
+use fat_forensics::traits::{TraitError, TreeDisplay};
+use fat_forensics::utils::{escape_path, human_size, write_file_at};
+use fat_forensics::{Attribute, DirEntryAddress, DiskError, FATError};
+use fat_forensics::{Disk, FATVol, Mbr, TableOptions, traits::LayoutDisplay};
+use log::{LevelFilter, debug, error};
 use std::{
-    fs::File,
-    io::{self, Write},
+    env, fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write},
+    ops::ControlFlow,
     path::Path,
+    process::{self, Stdio},
+    sync::Arc,
+    sync::OnceLock,
+    time::{Duration, Instant},
 };
 
 /// Represents the runtime state of the program.
@@ -26,147 +79,1519 @@ struct RunState<T: LayoutDisplay + TreeDisplay, U: LayoutDisplay> {
     bpb_validation: bool,
     /// The size of a sector
     sector_size: usize,
+    /// The number of sectors cached by each volume
+    cache_capacity: usize,
+    /// The limits enforced by the walks and scans of each volume
+    limits: Limits,
+    /// The number of lines of long outputs shown per page, if they are paged
+    page_len: Option<usize>,
+    /// How the partition tables of opened images are parsed
+    table_options: TableOptions,
+    /// Whether the commands modifying the image only report the bytes they would change
+    dry_run: bool,
+    /// Whether the commands modifying the image are confirmed in interactive sessions
+    confirm: bool,
 }
 
-fn main() {
-    stderrlog::new().module(module_path!()).init().unwrap();
+/// The category of a failed command.
+#[derive(Debug, Clone, Copy)]
+enum ErrorKind {
+    /// The command is unknown or malformed, or needs a disk or a volume that isn't selected.
+    Usage,
+    /// A file cannot be read or written.
+    Io,
+    /// The disk or volume structures are invalid or unsupported.
+    Validation,
+    /// The file, directory or volume doesn't exist.
+    NotFound,
+    /// The command was stopped by Ctrl-C.
+    Interrupted,
+}
+
+impl ErrorKind {
+    /// Returns the exit code of a non-interactive run failing with this kind of error.
+    fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Usage => 2,
+            ErrorKind::Io => 3,
+            ErrorKind::Validation => 4,
+            ErrorKind::NotFound => 5,
+            ErrorKind::Interrupted => 130,
+        }
+    }
+
+    /// Returns the name of the kind in machine-parsable error lines.
+    fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Usage => "usage",
+            ErrorKind::Io => "io",
+            ErrorKind::Validation => "validation",
+            ErrorKind::NotFound => "not-found",
+            ErrorKind::Interrupted => "interrupted",
+        }
+    }
+}
+
+/// The error of a failed command.
+#[derive(Debug)]
+struct CliError {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl CliError {
+    fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Wraps an error of the file system, prefixed with the action that failed.
+    fn fat(action: &str, err: FATError) -> Self {
+        let kind = match err {
+            FATError::IOError(_) => ErrorKind::Io,
+            FATError::FileNotFound | FATError::InvalidFilenameError(_) => ErrorKind::NotFound,
+            FATError::Cancelled => ErrorKind::Interrupted,
+            _ => ErrorKind::Validation,
+        };
+        Self::new(kind, format!("{action}: {err}"))
+    }
 
+    /// Wraps an I/O error, prefixed with the action that failed.
+    fn io(action: &str, err: io::Error) -> Self {
+        let kind = match err.kind() {
+            io::ErrorKind::NotFound => ErrorKind::NotFound,
+            _ => ErrorKind::Io,
+        };
+        Self::new(kind, format!("{action}: {err}"))
+    }
+}
+
+/// How the commands are read.
+enum Mode {
+    /// From a terminal, with a prompt. Errors are logged and the session goes on.
+    Interactive,
+    /// From a script or the command line. The first error ends the run.
+    Batch,
+}
+
+/// Cancelled by Ctrl-C in interactive sessions, so that a long command stops instead of the
+/// session. Installed on every opened volume.
+static INTERRUPT: OnceLock<CancellationToken> = OnceLock::new();
+
+/// Returns the token cancelled by Ctrl-C.
+fn interrupt_token() -> &'static CancellationToken {
+    INTERRUPT.get_or_init(CancellationToken::new)
+}
+
+/// Catches Ctrl-C, which then interrupts the running command rather than the session.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn catch_interrupts() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        if let Some(token) = INTERRUPT.get() {
+            token.cancel();
+        }
+    }
+
+    interrupt_token();
+    // SAFETY: The handler only loads and stores atomics, which is async-signal-safe
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn catch_interrupts() {}
+
+fn main() {
     let mut run_state = RunState {
         disk: None,
         vol_nb: None,
         bpb_validation: true,
         sector_size: 512,
+        cache_capacity: DEFAULT_CACHE_CAPACITY,
+        limits: Limits::default(),
+        page_len: None,
+        table_options: TableOptions::default(),
+        dry_run: false,
+        confirm: true,
+    };
+
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // The warnings of the library are reported through its event sink
+    let logging = LogOptions::take_from_args(&mut args).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(ErrorKind::Usage.exit_code());
+    });
+    if let Err(err) = logging.init(LevelFilter::Warn, &[module_path!(), "fat_forensics"]) {
+        eprintln!("Can't open the log file: {err}");
+        process::exit(ErrorKind::Io.exit_code());
+    }
+    // Paging needs a terminal to show the pages and to read the key pressed after each one
+    let no_pager = args.iter().any(|arg| arg == "--no-pager");
+    let timing = args.iter().any(|arg| arg == "--timing");
+    run_state.dry_run = args.iter().any(|arg| arg == "--dry-run");
+    args.retain(|arg| arg != "--no-pager" && arg != "--timing" && arg != "--dry-run");
+    if !no_pager && io::stdin().is_terminal() && io::stdout().is_terminal() {
+        run_state.page_len = Some(terminal_rows().saturating_sub(1).max(1));
+    }
+
+    let (mode, lines): (Mode, Box<dyn Iterator<Item = String>>) = match args.as_slice() {
+        [] if io::stdin().is_terminal() => (Mode::Interactive, Box::new(prompt_lines())),
+        [] => (
+            Mode::Batch,
+            Box::new(io::stdin().lock().lines().map_while(Result::ok)),
+        ),
+        [flag, commands] if flag == "-c" => (
+            Mode::Batch,
+            Box::new(
+                commands
+                    .split(';')
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            ),
+        ),
+        _ => {
+            eprintln!(
+                "Usage: main [-v|-vv|-q] [--log-file <path>] [--no-pager] [--timing] [--dry-run] [-c \"<command>; <command>...\"]"
+            );
+            process::exit(ErrorKind::Usage.exit_code());
+        }
+    };
+
+    if matches!(mode, Mode::Interactive) {
+        catch_interrupts();
+    }
+
+    for (line_nb, line) in lines.enumerate() {
+        let cmd = Command::from_string(&line);
+        if matches!(cmd, Command::Quit) {
+            break;
+        }
+
+        let mutating = cmd.is_mutating() && run_state.disk.is_some();
+        if mutating
+            && matches!(mode, Mode::Interactive)
+            && run_state.confirm
+            && !run_state.dry_run
+            && !confirm_write(&run_state)
+        {
+            println!("Cancelled.");
+            continue;
+        }
+
+        interrupt_token().reset();
+        let before = timing.then(|| (Instant::now(), Metrics::of(&run_state)));
+        let result = run_command(&mut run_state, cmd);
+        if mutating && run_state.dry_run {
+            // The changes planned by a failed command are dropped with it
+            let planned = planned_changes(&run_state);
+            if result.is_ok() {
+                report_planned(&run_state, planned);
+            }
+        }
+        if let Some((start, before)) = before {
+            Metrics::of(&run_state).report_since(&before, start.elapsed());
+        }
+        if let Err(err) = result {
+            match mode {
+                Mode::Interactive => error!("{}", err.message),
+                Mode::Batch => {
+                    eprintln!("error:{}:{}:{}", err.kind.name(), line_nb + 1, err.message);
+                    process::exit(err.kind.exit_code());
+                }
+            }
+        }
+    }
+}
+
+/// Asks whether a command may modify the opened image.
+///
+/// # Returns
+/// `true` if the user answered yes, `false` otherwise or if the answer cannot be read.
+fn confirm_write(run_state: &RunState<FATVol, Mbr>) -> bool {
+    let Some(disk) = &run_state.disk else {
+        return true;
     };
+    print!(
+        "This command modifies {}. Continue? [y/N] ",
+        disk.image().path().display()
+    );
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim(), "y" | "Y" | "yes"),
+        Err(_) => false,
+    }
+}
+
+/// Removes and returns the changes planned in dry-run mode on the opened image.
+fn planned_changes(run_state: &RunState<FATVol, Mbr>) -> Vec<JournalEntry> {
+    run_state
+        .disk
+        .as_ref()
+        .and_then(|disk| disk.image().journal())
+        .map_or_else(Vec::new, Journal::take_planned)
+}
+
+/// Prints the changes planned by a command in dry-run mode, with the structures they affect.
+fn report_planned(run_state: &RunState<FATVol, Mbr>, planned: Vec<JournalEntry>) {
+    let Some(disk) = &run_state.disk else {
+        return;
+    };
+    println!(
+        "Dry run, nothing was written. {} change(s) planned:",
+        planned.len()
+    );
+    for entry in planned {
+        let Some((offset, len)) = entry.extent() else {
+            println!("  {entry}");
+            continue;
+        };
+        let first = describe_offset(disk, offset);
+        let last = describe_offset(disk, offset + len.saturating_sub(1));
+        match first == last {
+            true => println!("  {entry} ({first})"),
+            false => println!("  {entry} ({first} to {last})"),
+        }
+    }
+}
+
+/// Names the structure of the opened disk holding an absolute byte offset.
+fn describe_offset(disk: &Disk<FATVol, Mbr>, offset: u64) -> String {
+    if let Some(description) = disk.volumes().iter().enumerate().find_map(|(i, vol)| {
+        vol.describe_offset(offset)
+            .map(|description| format!("volume {}, {description}", i + 1))
+    }) {
+        return description;
+    }
+    let first_volume = disk
+        .volumes()
+        .iter()
+        .map(|vol| vol.sector_offset(vol.start() as u64))
+        .min();
+    match first_volume {
+        _ if !disk.is_bare() && offset < *disk.sector_size() as u64 => String::from("MBR"),
+        Some(start) if offset < start => String::from("gap before the first partition"),
+        _ => String::from("unpartitioned space"),
+    }
+}
+
+/// The I/O and cache counters of the opened disk, to report what a command cost.
+struct Metrics {
+    /// The image the counters belong to.
+    image: Option<Arc<ImageSource>>,
+    io: IoStats,
+    /// Sectors served from and missed by the caches of every volume.
+    cache: (u64, u64),
+}
+
+impl Metrics {
+    /// Reads the counters of the opened disk, zero if none is opened.
+    fn of(run_state: &RunState<FATVol, Mbr>) -> Self {
+        let Some(disk) = &run_state.disk else {
+            return Self {
+                image: None,
+                io: IoStats::default(),
+                cache: (0, 0),
+            };
+        };
+        let cache = disk.volumes().iter().fold((0, 0), |(hits, misses), vol| {
+            let stats = vol.cache_stats();
+            (hits + stats.hits(), misses + stats.misses())
+        });
+        Self {
+            image: Some(disk.image().clone()),
+            io: disk.image().io_stats(),
+            cache,
+        }
+    }
 
-    loop {
+    /// Prints the duration of a command and what it read, wrote and found in the caches since
+    /// `before`, to the standard error so that the output of the command stays clean.
+    fn report_since(&self, before: &Metrics, elapsed: Duration) {
+        // A command opening another image starts from its counters
+        let before = match (&self.image, &before.image) {
+            (Some(image), Some(earlier)) if Arc::ptr_eq(image, earlier) => before,
+            _ => &Metrics {
+                image: None,
+                io: IoStats::default(),
+                cache: (0, 0),
+            },
+        };
+        let io = self.io.since(&before.io);
+        let hits = self.cache.0.saturating_sub(before.cache.0);
+        let misses = self.cache.1.saturating_sub(before.cache.1);
+        let hit_rate = match hits + misses {
+            0 => String::from("no cache access"),
+            total => format!(
+                "cache hit rate {:.1}% ({hits} hits, {misses} misses)",
+                hits as f64 * 100.0 / total as f64
+            ),
+        };
+        eprintln!(
+            "timing: {:.3} s, {} read, {} written, {hit_rate}",
+            elapsed.as_secs_f64(),
+            human_size(*io.bytes_read()),
+            human_size(*io.bytes_written()),
+        );
+    }
+}
+
+/// Reads the commands typed in the terminal, after a prompt.
+fn prompt_lines() -> impl Iterator<Item = String> {
+    std::iter::from_fn(|| {
         print!("> ");
-        io::stdout().flush().unwrap();
+        io::stdout().flush().ok()?;
 
         let mut s = String::new();
-        io::stdin()
-            .read_line(&mut s)
-            .expect("Failed to read command");
-        let cmd = Command::from_string(&s);
-
-        match cmd {
-            Command::Open(path) => {
-                match Disk::from_file(
-                    Path::new(&path),
-                    run_state.sector_size,
-                    run_state.bpb_validation,
-                ) {
-                    Ok(disk) => {
-                        run_state.disk = Some(disk);
-                    }
-                    Err(err) => {
-                        error!("{err}");
-                    }
-                }
+        match io::stdin().read_line(&mut s) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(s),
+        }
+    })
+}
+
+/// The number of rows of a terminal whose size is unknown.
+const DEFAULT_TERMINAL_ROWS: usize = 24;
+
+/// Returns the number of rows of the terminal the standard output is attached to.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn terminal_rows() -> usize {
+    // SAFETY: TIOCGWINSZ only fills the winsize structure passed to it
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    match unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } {
+        0 if size.ws_row > 0 => size.ws_row as usize,
+        _ => DEFAULT_TERMINAL_ROWS,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn terminal_rows() -> usize {
+    DEFAULT_TERMINAL_ROWS
+}
+
+/// Writes long outputs to the standard output a page at a time, waiting for Enter after each
+/// page. Typing `q` instead drops the rest of the output.
+struct Pager {
+    /// The number of lines of a page, or `None` to write the output in one go.
+    page_len: Option<usize>,
+    /// The number of lines written on the current page.
+    lines: usize,
+    /// Whether the rest of the output is dropped.
+    stopped: bool,
+}
+
+impl Pager {
+    fn new(page_len: Option<usize>) -> Self {
+        Self {
+            page_len,
+            lines: 0,
+            stopped: false,
+        }
+    }
+
+    /// Returns whether the rest of the output is dropped, so that the command can stop early.
+    fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Writes one item per line, until the output is stopped.
+    fn print_all<T: fmt::Display>(&mut self, items: &[T]) {
+        for item in items {
+            if writeln!(self, "{item}").is_err() || self.stopped {
+                break;
             }
-            Command::Quit => break,
-            Command::Print => match &run_state.disk {
-                Some(disk) => {
-                    if let Err(e) = disk.print_layout(3) {
-                        error!("Print layout error: {e}");
-                    }
+        }
+    }
+
+    /// Waits for the key pressed at the end of a page.
+    fn wait(&mut self) -> io::Result<()> {
+        print!("-- More -- (Enter: next page, q: stop) ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        self.stopped = answer.trim() == "q";
+        Ok(())
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.stopped {
+            return Ok(buf.len());
+        }
+        let Some(page_len) = self.page_len else {
+            return io::stdout().write(buf);
+        };
+
+        // Lines are counted as they end, to wait at the end of each page
+        match buf.iter().position(|b| *b == b'\n') {
+            None => io::stdout().write(buf),
+            Some(pos) => {
+                io::stdout().write_all(&buf[..=pos])?;
+                self.lines += 1;
+                if self.lines == page_len {
+                    self.lines = 0;
+                    self.wait()?;
                 }
-                None => error!("Open disk image first"),
-            },
-            Command::Partition(vol_nb) => {
-                let part_index: isize = vol_nb as isize - 1;
-
-                if let Some(disk) = &run_state.disk {
-                    if part_index < 0 || part_index >= disk.volumes().len() as isize {
-                        error!(
-                            "Invalid volume number. There are {} valid volumes on disk.",
-                            disk.volumes().len()
-                        );
-                    }
+                Ok(pos + 1)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
 
-                    run_state.vol_nb = Some(vol_nb);
-                } else {
-                    warn!("Open disk image first");
+/// Runs a command other than `quit`.
+fn run_command(run_state: &mut RunState<FATVol, Mbr>, cmd: Command) -> Result<(), CliError> {
+    match cmd {
+        Command::Open(path) => {
+            let disk = Disk::from_file_with_options(
+                Path::new(&path),
+                run_state.sector_size,
+                run_state.bpb_validation,
+                &run_state.table_options,
+            )
+            .map_err(|err| {
+                let kind = match &err {
+                    DiskError::Io(err) if err.kind() == io::ErrorKind::NotFound => {
+                        ErrorKind::NotFound
+                    }
+                    DiskError::Io(_) => ErrorKind::Io,
+                    _ => ErrorKind::Validation,
+                };
+                CliError::new(kind, format!("Failed to open {path}: {err}"))
+            })?;
+            for vol in disk.volumes() {
+                vol.set_cache_capacity(run_state.cache_capacity);
+                vol.set_limits(run_state.limits);
+                vol.set_cancel_token(interrupt_token().clone());
+            }
+            for vol in disk.exfat_volumes() {
+                vol.set_limits(run_state.limits);
+            }
+            disk.set_event_sink(Some(Arc::new(LogSink)));
+            disk.image().set_dry_run(run_state.dry_run);
+            if disk.is_bare() {
+                let kind = match (
+                    disk.exfat_volumes().is_empty(),
+                    disk.iso_volumes().is_empty(),
+                ) {
+                    (_, false) => "ISO 9660",
+                    (false, _) => "exFAT",
+                    _ => "FAT",
+                };
+                println!("This looks like a bare {kind} volume; opened without a partition table.");
+            }
+            for overlap in disk.part_table().overlaps() {
+                match overlap.by_volume {
+                    true => println!("Warning: {overlap}."),
+                    false => println!("Warning: {overlap}; neither is opened."),
                 }
             }
-            Command::Skip => run_state.bpb_validation = false,
-            Command::Write((file_path, sector)) => {
-                write_file_to_disk(&mut run_state, Path::new(&file_path), sector)
+            for anomaly in disk.part_table().boot_anomalies() {
+                println!("Warning: {anomaly}.");
             }
-            Command::Tree => {
-                if let Some(disk) = run_state.disk.as_ref() {
-                    if let Err(err) = disk.print_tree() {
-                        error!("Tree printing failed: {err}");
+            run_state.disk = Some(disk);
+        }
+        Command::Quit => {}
+        Command::Print(options) => opened_disk(run_state)?
+            .print_layout(3, &options)
+            .map_err(|err| CliError::new(ErrorKind::Io, format!("Print layout error: {err}")))?,
+        Command::Partition(vol_nb) => {
+            volume(run_state, vol_nb)?;
+            run_state.vol_nb = Some(vol_nb);
+        }
+        Command::Skip => run_state.bpb_validation = false,
+        Command::Write((file_path, address)) => {
+            write_file_to_disk(run_state, Path::new(&file_path), address)?
+        }
+        Command::Tree(true) => hidden_tree(opened_disk(run_state)?)?,
+        Command::Tree(false) => opened_disk(run_state)?
+            .write_tree(&mut Pager::new(run_state.page_len))
+            .map_err(|err| match err {
+                TraitError::FATError(err) => CliError::fat("Tree printing failed", err),
+                TraitError::IOError(err) => CliError::io("Tree printing failed", err),
+            })?,
+        Command::Wipe((target, pattern)) => wipe(run_state, target, &pattern)?,
+        Command::Shred((path, passes, scrub)) => {
+            selected_volume(run_state)?
+                .shred(Path::new(&path), passes, scrub)
+                .map_err(|err| CliError::fat("Shred failed", err))?;
+            println!("Shredded {path}.");
+        }
+        Command::Scan(target) => scan(selected_volume(run_state)?, target)
+            .map_err(|err| CliError::fat("Scan failed", err))?,
+        Command::Dot((target, out)) => export_dot(
+            selected_volume(run_state)?,
+            target,
+            out.as_deref().map(Path::new),
+        )?,
+        Command::Strings(options) => {
+            let strings = volume_strings(selected_volume(run_state)?, &options)
+                .map_err(|err| CliError::fat("String extraction failed", err))?;
+            Pager::new(run_state.page_len).print_all(&strings)
+        }
+        Command::Grep((pattern, path)) => {
+            let scope = Path::new(path.trim_start_matches('/'));
+            let found = grep_files(selected_volume(run_state)?, &pattern, scope)
+                .map_err(|err| CliError::fat("Search failed", err))?;
+            Pager::new(run_state.page_len).print_all(&found)
+        }
+        Command::Extract((path, out, buffer_len)) => extract(
+            selected_volume(run_state)?,
+            Path::new(&path),
+            Path::new(&out),
+            buffer_len,
+        )?,
+        Command::ExtractAll((format, out)) => {
+            extract_all(selected_volume(run_state)?, format, Path::new(&out))?
+        }
+        Command::Dfxml(out) => dfxml(selected_volume(run_state)?, out.as_deref().map(Path::new))?,
+        Command::VolumeSlack(out) => {
+            volume_slack(selected_volume(run_state)?, out.as_deref().map(Path::new))?
+        }
+        Command::Gap(out) => mbr_gap(opened_disk(run_state)?, out.as_deref().map(Path::new))?,
+        Command::Carve(out) => {
+            carve_files(selected_volume(run_state)?, out.as_deref().map(Path::new))?
+        }
+        Command::Root(action) => root(selected_volume(run_state)?, action)?,
+        Command::Snapshot(action) => snapshot(run_state, action)?,
+        Command::Journal(action) => journal(run_state, action)?,
+        Command::Dupes(images) => dupes(run_state, &images)?,
+        Command::Convert((image, out, format)) => convert(&image, &out, format)?,
+        Command::Fat(action) => fat(selected_volume(run_state)?, action)?,
+        Command::BootSector(action) => boot_sector(selected_volume_mut(run_state)?, action)?,
+        Command::Mbr(action) => mbr(run_state, action)?,
+        Command::Summary(profile) => {
+            let profile = match profile {
+                Some(profile) => TriageProfile::resolve(&profile)
+                    .map_err(|err| CliError::io(&format!("Can't read profile {profile}"), err))?,
+                None => TriageProfile::default(),
+            };
+            print!(
+                "{}",
+                summarize_with(selected_volume(run_state)?, &profile)
+                    .map_err(|err| CliError::fat("Summary failed", err))?
+            )
+        }
+        Command::Recover => print!(
+            "{}",
+            plan_recovery(selected_volume(run_state)?)
+                .map_err(|err| CliError::fat("Recovery failed", err))?
+        ),
+        Command::HashAll(out) => {
+            hash_all(selected_volume(run_state)?, out.as_deref().map(Path::new))?
+        }
+        Command::TreeDiff((before, after, vol_nb)) => {
+            tree_diff(run_state, &before, &after, vol_nb)?
+        }
+        Command::CompareManifest(manifest) => {
+            compare_with_manifest(selected_volume(run_state)?, Path::new(&manifest))?
+        }
+        Command::Hash((target, options)) => {
+            hash_blocks(selected_volume(run_state)?, target, options)?
+        }
+        Command::FsStat(vol_nb) => {
+            let vol = match vol_nb {
+                Some(vol_nb) => volume(run_state, vol_nb)?,
+                None => selected_volume(run_state)?,
+            };
+            let fsstat = vol
+                .fsstat()
+                .map_err(|err| CliError::fat("Fsstat failed", err))?;
+            println!("{fsstat}");
+        }
+        Command::Ls((attr, hex)) => {
+            list_entries(selected_volume(run_state)?, attr, hex, run_state.page_len)?
+        }
+        Command::Stat(path) => {
+            let stat = selected_volume(run_state)?
+                .stat(Path::new(&path))
+                .map_err(|err| CliError::fat("Stat failed", err))?;
+            println!("{stat}");
+        }
+        Command::DirEnt((cluster, index)) => {
+            let record = selected_volume(run_state)?
+                .dir_entry_at(DirEntryAddress::new(cluster, index))
+                .map_err(|err| CliError::fat("Reading the directory entry failed", err))?;
+            println!("{record}");
+        }
+        Command::Set(setting) => {
+            let limits = &mut run_state.limits;
+            match setting {
+                Setting::Cache(capacity) => run_state.cache_capacity = capacity,
+                Setting::MaxDepth(max) => limits.max_depth = max,
+                Setting::MaxDirEntries(max) => limits.max_dir_entries = max,
+                Setting::MaxChainLen(max) => limits.max_chain_len = max,
+                Setting::MaxCarvedBytes(max) => limits.max_carved_bytes = max,
+                Setting::Pager(page_len) => run_state.page_len = page_len,
+                Setting::Geometry(geometry) => run_state.table_options.geometry = geometry,
+                Setting::LenientOverlaps(lenient) => run_state.table_options.lenient = lenient,
+                Setting::DryRun(dry_run) => run_state.dry_run = dry_run,
+                Setting::Confirm(confirm) => run_state.confirm = confirm,
+                Setting::Fat(fat_nb) => {
+                    let vol = selected_volume(run_state)?;
+                    match fat_nb {
+                        Some(fat_nb) => vol
+                            .set_active_fat(fat_nb)
+                            .map_err(|err| CliError::fat("Can't read from this FAT", err))?,
+                        None => vol.reset_active_fat(),
                     }
-                } else {
-                    warn!("Open disk image first")
+                    println!("Reading the cluster chains from FAT #{}.", vol.active_fat());
+                }
+            }
+            if let Some(disk) = &run_state.disk {
+                disk.image().set_dry_run(run_state.dry_run);
+                for vol in disk.volumes() {
+                    vol.set_cache_capacity(run_state.cache_capacity);
+                    vol.set_limits(run_state.limits);
                 }
             }
-            Command::Unknown(s) => error!("Unknown command: {s:?}"),
-            Command::Invalid(s) => error!("{s}"),
-            Command::Empty => {}
         }
+        Command::Help(name) => match help_text(name.as_deref()) {
+            Some(help) => println!("{help}"),
+            None => {
+                return Err(CliError::new(
+                    ErrorKind::Usage,
+                    format!("Unknown command: {:?}", name.unwrap_or_default()),
+                ));
+            }
+        },
+        Command::Unknown(s) => {
+            return Err(CliError::new(
+                ErrorKind::Usage,
+                format!("Unknown command: {s:?}. Type 'help' for the list of commands."),
+            ));
+        }
+        Command::Invalid(s) => return Err(CliError::new(ErrorKind::Usage, s)),
+        Command::Empty => {}
     }
+
+    Ok(())
 }
 
-fn write_file_to_disk<T: LayoutDisplay + TreeDisplay, U: LayoutDisplay>(
-    run_state: &mut RunState<T, U>,
+fn write_file_to_disk<U: LayoutDisplay>(
+    run_state: &RunState<FATVol, U>,
     file_path: &Path,
-    sector: u64,
-) {
-    let disk = match &mut run_state.disk {
-        Some(disk) => disk,
-        None => {
-            warn!("Open disk image first");
-            return;
+    address: WriteAddress,
+) -> Result<(), CliError> {
+    let disk = opened_disk(run_state)?;
+    let (offset, limit) = write_offset(run_state, address)?;
+
+    // Open the disk image
+    let mut disk_file = disk
+        .image()
+        .open_for_write()
+        .map_err(|err| CliError::io("Failed to open disk image file", err))?;
+
+    // Open the file to copy on disk
+    let name = file_path.to_str().unwrap_or("invalid_file_name");
+    let mut f =
+        File::open(file_path).map_err(|err| CliError::io(&format!("Can't open {name}"), err))?;
+    let f_len = f
+        .metadata()
+        .map_err(|err| CliError::io(&format!("Can't read metadata of {name}"), err))?
+        .len();
+
+    write_file_at(
+        &mut disk_file,
+        offset,
+        &mut f,
+        f_len,
+        run_state.sector_size,
+        limit,
+    )
+    .map_err(|err| CliError::io("Write failed", err))?;
+
+    // The write bypassed the volumes, so their cached sectors may be stale
+    for vol in disk.volumes() {
+        vol.clear_cache();
+    }
+    println!("Write succeeded at byte offset 0x{offset:X}!");
+    Ok(())
+}
+
+/// Converts the address of a `write` command to an absolute byte offset.
+///
+/// # Returns
+/// - `Ok((u64, u64))`: The offset, and the end of the volume addressed, which the write must not
+///   cross, or 0 for an absolute sector.
+/// - `Err(CliError)`: If the volume doesn't exist, or the address lies outside of it.
+fn write_offset<U: LayoutDisplay>(
+    run_state: &RunState<FATVol, U>,
+    address: WriteAddress,
+) -> Result<(u64, u64), CliError> {
+    let out_of_range = |sector: u64| {
+        CliError::new(
+            ErrorKind::Validation,
+            format!("Sector {sector} is out of the addressable range."),
+        )
+    };
+    let (vol_nb, offset) = match address {
+        WriteAddress::Sector(sector) => {
+            let offset = sector
+                .checked_mul(run_state.sector_size as u64)
+                .ok_or_else(|| out_of_range(sector))?;
+            return Ok((offset, 0));
+        }
+        WriteAddress::VolumeSector(vol_nb, sector) => {
+            let vol = volume(run_state, vol_nb)?;
+            let offset = (vol.start() as u64)
+                .checked_add(sector)
+                .and_then(|sector| vol.checked_sector_offset(sector))
+                .ok_or_else(|| out_of_range(sector))?;
+            (vol_nb, offset)
+        }
+        WriteAddress::Cluster(vol_nb, cluster) => {
+            let vol = volume(run_state, vol_nb)?;
+            if !(2..vol.cluster_count() as u64 + 2).contains(&(cluster as u64)) {
+                return Err(CliError::new(
+                    ErrorKind::Validation,
+                    format!(
+                        "Cluster {cluster} is out of the data region (clusters 2 to {}).",
+                        vol.cluster_count() + 1
+                    ),
+                ));
+            }
+            (vol_nb, vol.cluster_offset(cluster))
         }
     };
 
-    // Open the disk image
-    let mut disk_file = match File::options()
-        .read(true)
-        .write(true)
-        .open(disk.file_path())
-    {
-        Ok(file) => file,
-        Err(e) => {
-            error!("Failed to open disk image file: {e}");
-            return;
+    // The volume slack runs up to the end of the volume
+    let (slack_offset, slack_len) = volume(run_state, vol_nb)?.volume_slack_extent();
+    let end = slack_offset + slack_len;
+    if offset >= end {
+        return Err(CliError::new(
+            ErrorKind::Validation,
+            format!("Byte offset 0x{offset:X} is past the end of volume {vol_nb} (0x{end:X})."),
+        ));
+    }
+    Ok((offset, end))
+}
+
+/// Returns the open disk.
+fn opened_disk<U: LayoutDisplay>(
+    run_state: &RunState<FATVol, U>,
+) -> Result<&Disk<FATVol, U>, CliError> {
+    run_state
+        .disk
+        .as_ref()
+        .ok_or_else(|| CliError::new(ErrorKind::Usage, "Open disk image first"))
+}
+
+/// Returns the volume selected with the `part` command.
+fn selected_volume<U: LayoutDisplay>(run_state: &RunState<FATVol, U>) -> Result<&FATVol, CliError> {
+    opened_disk(run_state)?;
+    match run_state.vol_nb {
+        Some(vol_nb) => volume(run_state, vol_nb),
+        None => Err(CliError::new(
+            ErrorKind::Usage,
+            "Select a valid partition first",
+        )),
+    }
+}
+
+/// Returns the volume selected with the `part` command, mutably.
+fn selected_volume_mut<U: LayoutDisplay>(
+    run_state: &mut RunState<FATVol, U>,
+) -> Result<&mut FATVol, CliError> {
+    let vol_nb = run_state
+        .vol_nb
+        .ok_or_else(|| CliError::new(ErrorKind::Usage, "Select a valid partition first"))?;
+    run_state
+        .disk
+        .as_mut()
+        .ok_or_else(|| CliError::new(ErrorKind::Usage, "Open disk image first"))?
+        .volume_mut((vol_nb as usize).saturating_sub(1))
+        .ok_or_else(|| CliError::new(ErrorKind::NotFound, "Invalid volume number."))
+}
+
+/// Returns the volume with the given 1-based number.
+fn volume<U: LayoutDisplay>(
+    run_state: &RunState<FATVol, U>,
+    vol_nb: u8,
+) -> Result<&FATVol, CliError> {
+    let disk = opened_disk(run_state)?;
+    (vol_nb as usize)
+        .checked_sub(1)
+        .and_then(|idx| disk.volume(idx))
+        .ok_or_else(|| {
+            CliError::new(
+                ErrorKind::NotFound,
+                format!(
+                    "Invalid volume number. There are {} valid volumes on disk.",
+                    disk.volumes().len()
+                ),
+            )
+        })
+}
+
+fn wipe<U: LayoutDisplay>(
+    run_state: &RunState<FATVol, U>,
+    target: WipeTarget,
+    pattern: &[u8],
+) -> Result<(), CliError> {
+    let vol = selected_volume(run_state)?;
+    let mut disk_file = opened_disk(run_state)?
+        .image()
+        .open_for_write()
+        .map_err(|err| CliError::io("Failed to open disk image file", err))?;
+
+    let wiped = match target {
+        WipeTarget::AllSlack => vol.wipe_all_slack(&mut disk_file, pattern),
+        WipeTarget::FileSlack(path) => {
+            vol.wipe_file_slack(&mut disk_file, Path::new(&path), pattern)
         }
+        WipeTarget::Free => vol.wipe_free(&mut disk_file, pattern),
+    }
+    .map_err(|err| CliError::fat("Wipe failed", err))?;
+
+    println!("Wiped {wiped} bytes.");
+    Ok(())
+}
+
+/// Prints the hashdeep manifest of the files of the volume, or saves it to `out`.
+fn hash_all(vol: &FATVol, out: Option<&Path>) -> Result<(), CliError> {
+    let digests = hash::hash_all_files(vol).map_err(|err| CliError::fat("Hashing failed", err))?;
+    let invocation = match out {
+        Some(out) => format!("hash-all --out {}", out.display()),
+        None => String::from("hash-all"),
     };
 
-    // Open the file to copy on disk
-    let mut f = match File::open(file_path) {
-        Err(e) => {
-            error!(
-                "Can't open {}: {}",
-                file_path.to_str().unwrap_or("invalid_file_name"),
-                e
-            );
-            return;
+    match out {
+        Some(out) => {
+            let file = File::create(out)
+                .map_err(|err| CliError::io(&format!("Can't create {}", out.display()), err))?;
+            hash::write_hashdeep_manifest(&digests, &invocation, BufWriter::new(file))
+                .map_err(|err| CliError::io("Failed to write the manifest", err))?;
+            println!("{} files hashed to {}", digests.len(), out.display());
+        }
+        None => hash::write_hashdeep_manifest(&digests, &invocation, io::stdout().lock())
+            .map_err(|err| CliError::io("Failed to write the manifest", err))?,
+    }
+    Ok(())
+}
+
+/// Prints the differences between the files of the volume and a manifest, failing if there are
+/// any so that scripts detect the tampering.
+fn compare_with_manifest(vol: &FATVol, manifest: &Path) -> Result<(), CliError> {
+    let expected = File::open(manifest)
+        .map(BufReader::new)
+        .and_then(read_manifest)
+        .map_err(|err| CliError::io(&format!("Can't read {}", manifest.display()), err))?;
+    let comparison =
+        compare_manifest(vol, &expected).map_err(|err| CliError::fat("Comparison failed", err))?;
+    print!("{comparison}");
+
+    match comparison.is_clean() {
+        true => Ok(()),
+        false => Err(CliError::new(
+            ErrorKind::Validation,
+            format!(
+                "{} file(s) differ from {}",
+                comparison.difference_count(),
+                manifest.display()
+            ),
+        )),
+    }
+}
+
+/// Prints the block hashes of a file or region, and how many of them match a reference file.
+fn hash_blocks(vol: &FATVol, target: HashTarget, options: HashOptions) -> Result<(), CliError> {
+    let block_size = options.block_size.unwrap_or(vol.cluster_size() as usize);
+    let blocks = match target {
+        HashTarget::File(path) => {
+            hash::hash_file(vol, Path::new(&path), Some(block_size), options.algorithm)
+        }
+        HashTarget::Region((offset, len)) => {
+            hash::hash_region(vol, offset, len, Some(block_size), options.algorithm)
         }
-        Ok(file) => file,
+    }
+    .map_err(|err| CliError::fat("Hashing failed", err))?;
+
+    let matched: Vec<(usize, usize)> = match &options.reference {
+        Some(reference) => {
+            let data = fs::read(reference)
+                .map_err(|err| CliError::io(&format!("Can't read {reference}"), err))?;
+            hash::match_blocks(
+                &hash::piecewise_hashes(&data, block_size, options.algorithm),
+                &blocks,
+            )
+        }
+        None => vec![],
     };
-    let f_len = match f.metadata() {
-        Ok(metadata) => metadata.len(),
-        Err(e) => {
-            error!(
-                "Can't read meatadata of {}: {}",
-                file_path.to_str().unwrap_or("invalid_file_name"),
-                e
-            );
-            return;
+
+    println!("{} blocks of {block_size} bytes:", options.algorithm);
+    for (i, block) in blocks.iter().enumerate() {
+        match matched.iter().find(|(candidate, _)| *candidate == i) {
+            Some((_, ref_idx)) => println!("{block} matches reference block {ref_idx}"),
+            None => println!("{block}"),
         }
+    }
+    if options.reference.is_some() {
+        println!(
+            "{}/{} blocks match the reference.",
+            matched.len(),
+            blocks.len()
+        );
+    }
+    Ok(())
+}
+
+fn scan(vol: &FATVol, target: ScanTarget) -> Result<(), FATError> {
+    match target {
+        ScanTarget::Wipe => match detect_wiped_regions(vol)? {
+            regions if regions.is_empty() => println!("No wiped region found."),
+            regions => regions.iter().for_each(|region| println!("{region}")),
+        },
+        ScanTarget::Payloads => match detect_payloads(vol)? {
+            candidates if candidates.is_empty() => println!("No payload found."),
+            candidates => candidates.iter().for_each(|candidate| {
+                println!("{candidate}");
+                println!("  evidence: {}", candidate.provenance());
+            }),
+        },
+        ScanTarget::Entropy => file_entropies(vol)?
+            .iter()
+            .for_each(|file| println!("{file}")),
+        ScanTarget::Dirs => print!("{}", vol.discover_dirs()?),
+        ScanTarget::Slack => match scan_file_slack(vol)? {
+            findings if findings.is_empty() => println!("No data found in file slack."),
+            findings => {
+                findings.iter().for_each(|finding| {
+                    println!("{finding}");
+                    println!("  evidence: {}", finding.provenance());
+                });
+                println!(
+                    "{} file(s) with data in their slack, {} hidden byte(s) in total.",
+                    findings.len(),
+                    findings.iter().map(|f| f.hidden_bytes()).sum::<u64>()
+                );
+            }
+        },
+        ScanTarget::DirSlack => match scan_dir_slack(vol)? {
+            remnants if remnants.is_empty() => println!("No entry found in directory slack."),
+            remnants => remnants.iter().for_each(|remnant| {
+                println!("{remnant}");
+                println!("  evidence: {}", remnant.provenance());
+            }),
+        },
+        ScanTarget::FatSlack => match scan_fat_slack(vol)? {
+            runs if runs.is_empty() => println!("No data found past the last cluster of the FATs."),
+            runs => runs.iter().for_each(|run| {
+                println!("{run}");
+                println!("  evidence: {}", run.provenance());
+            }),
+        },
+        ScanTarget::Names => match scan_names(vol)? {
+            names if names.is_empty() => println!("No unusual name found."),
+            names => names.iter().for_each(|name| println!("{name}")),
+        },
+        ScanTarget::Reserved => match scan_reserved_sectors(vol)? {
+            findings if findings.is_empty() => println!("No data found in the reserved sectors."),
+            findings => findings.iter().for_each(|finding| {
+                println!("{finding}");
+                println!("  evidence: {}", finding.provenance());
+            }),
+        },
+        ScanTarget::Types => identify_files(vol)?
+            .iter()
+            .for_each(|file| println!("{file}")),
+        ScanTarget::FreeSpace => match classify_free_space(vol)? {
+            breakdown if *breakdown.free_clusters() == 0 => println!("No unallocated cluster."),
+            breakdown => print!("{breakdown}"),
+        },
+    }
+    Ok(())
+}
+
+/// Carves the unallocated clusters, prints the deduplicated recoveries and saves them to `out`.
+fn carve_files(vol: &FATVol, out: Option<&Path>) -> Result<(), CliError> {
+    let report = carve(vol, &CarverRegistry::default())
+        .and_then(|carved| dedup_carved(vol, carved))
+        .map_err(|err| CliError::fat("Carving failed", err))?;
+    print!("{report}");
+
+    let Some(out) = out else {
+        return Ok(());
     };
+    fs::create_dir_all(out)
+        .map_err(|err| CliError::io(&format!("Failed to create {}", out.display()), err))?;
+    for recovery in report.recoveries() {
+        let carved = recovery.carved();
+        let path = out.join(format!("{:X}.{}", carved.offset(), carved.extension()));
+        fs::write(&path, carved.data())
+            .map_err(|err| CliError::io(&format!("Failed to save {}", path.display()), err))?;
+        println!("Saved {}", path.display());
+    }
+    Ok(())
+}
 
-    match write_file_at(
-        &mut disk_file,
-        sector * run_state.sector_size as u64,
-        &mut f,
-        f_len,
+fn root(vol: &FATVol, action: RootAction) -> Result<(), CliError> {
+    match action {
+        RootAction::Scan => {
+            let candidates = vol
+                .find_root_candidates()
+                .map_err(|err| CliError::fat("Root directory scan failed", err))?;
+            match candidates.is_empty() {
+                true => println!("No root directory candidate found."),
+                false => candidates
+                    .iter()
+                    .for_each(|candidate| println!("{candidate}")),
+            }
+        }
+        RootAction::Set(cluster) => {
+            vol.set_root_cluster(cluster)
+                .map_err(|err| CliError::fat("Failed to set the root directory", err))?;
+            println!("Using cluster {cluster} as the root directory.");
+        }
+        RootAction::Reset => {
+            vol.reset_root_cluster();
+            println!("Using the root directory of the boot sector.");
+        }
+    }
+    Ok(())
+}
+
+fn snapshot(run_state: &mut RunState<FATVol, Mbr>, action: SnapshotAction) -> Result<(), CliError> {
+    let image = opened_disk(run_state)?.image();
+    match action {
+        SnapshotAction::Create(name) => {
+            let snapshot = snapshot::create(image, &name)
+                .map_err(|err| CliError::io("Snapshot failed", err))?;
+            println!("Saved snapshot {snapshot}");
+        }
+        SnapshotAction::Restore(name) => {
+            let rewritten = snapshot::restore(image, &name)
+                .map_err(|err| CliError::io("Restore failed", err))?;
+            println!("Restored snapshot '{name}', {rewritten} bytes rewritten.");
+            if image.is_dry_run() {
+                return Ok(());
+            }
+
+            // The partition table may have changed too, so the image is opened again, keeping its
+            // journal
+            let path = image.path().display().to_string();
+            let entries = image.journal().map(|journal| journal.take());
+            run_command(run_state, Command::Open(path))?;
+            if let (Some(entries), Some(journal)) =
+                (entries, opened_disk(run_state)?.image().journal())
+            {
+                journal.extend(entries);
+            }
+        }
+        SnapshotAction::List => {
+            let snapshots =
+                snapshot::list(image).map_err(|err| CliError::io("Listing failed", err))?;
+            match snapshots.is_empty() {
+                true => println!("No snapshot."),
+                false => snapshots.iter().for_each(|snapshot| println!("{snapshot}")),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn journal(run_state: &RunState<FATVol, Mbr>, action: JournalAction) -> Result<(), CliError> {
+    match action {
+        JournalAction::Show => {
+            let entries = image_journal(run_state)?.entries();
+            match entries.is_empty() {
+                true => println!("No write since the image was opened."),
+                false => entries.iter().for_each(|entry| println!("{entry}")),
+            }
+        }
+        JournalAction::Export(script) => {
+            let entries = image_journal(run_state)?.entries();
+            let base = opened_disk(run_state)?.image().path().display().to_string();
+            File::create(&script)
+                .and_then(|file| journal::write_script(&entries, &base, BufWriter::new(file)))
+                .map_err(|err| CliError::io(&format!("Can't write {script}"), err))?;
+            println!("Exported {} operations to {script}.", entries.len());
+        }
+        JournalAction::Clear => {
+            let cleared = image_journal(run_state)?.take().len();
+            println!("Cleared {cleared} operations.");
+        }
+        JournalAction::Replay { script, base, copy } => {
+            let entries = File::open(&script)
+                .map(BufReader::new)
+                .and_then(journal::read_script)
+                .map_err(|err| CliError::io(&format!("Can't read {script}"), err))?;
+            journal::replay(&entries, Path::new(&base), Path::new(&copy))
+                .map_err(|err| CliError::io("Replay failed", err))?;
+            println!("Replayed {} operations onto {copy}.", entries.len());
+        }
+    }
+    Ok(())
+}
+
+/// Exports a FAT of `vol` to a file, or imports one from a file.
+fn fat(vol: &FATVol, action: FatAction) -> Result<(), CliError> {
+    match action {
+        FatAction::Export { fat_nb, file } => {
+            let mut out = File::create(&file)
+                .map(BufWriter::new)
+                .map_err(|err| CliError::io(&format!("Can't create {file}"), err))?;
+            let written = vol
+                .export_fat(fat_nb, &mut out)
+                .map_err(|err| CliError::fat("FAT export failed", err))?;
+            println!("Exported FAT #{fat_nb} ({written} bytes) to {file}.");
+        }
+        FatAction::Import { fat_nb, file } => {
+            let data =
+                fs::read(&file).map_err(|err| CliError::io(&format!("Can't read {file}"), err))?;
+            vol.import_fat(fat_nb, &data)
+                .map_err(|err| CliError::fat("FAT import failed", err))?;
+            println!("Imported {file} into FAT #{fat_nb} ({} bytes).", data.len());
+        }
+    }
+    Ok(())
+}
+
+/// Exports the boot sector of `vol` to a file, or imports one from a file.
+fn boot_sector(vol: &mut FATVol, action: BootSectorAction) -> Result<(), CliError> {
+    match action {
+        BootSectorAction::Export(file) => {
+            let mut out = File::create(&file)
+                .map_err(|err| CliError::io(&format!("Can't create {file}"), err))?;
+            let written = vol
+                .export_boot_sector(&mut out)
+                .map_err(|err| CliError::fat("Boot sector export failed", err))?;
+            println!("Exported the boot sector ({written} bytes) to {file}.");
+        }
+        BootSectorAction::Import { file, sync_backup } => {
+            let data =
+                fs::read(&file).map_err(|err| CliError::io(&format!("Can't read {file}"), err))?;
+            let backup = vol
+                .import_boot_sector(&data, sync_backup)
+                .map_err(|err| CliError::fat("Boot sector import failed", err))?;
+            match backup {
+                Some(sector) => {
+                    println!(
+                        "Imported {file} into the boot sector and its backup (sector {sector})."
+                    )
+                }
+                None => println!("Imported {file} into the boot sector, the backup is untouched."),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Edits the partition table of the opened disk.
+fn mbr(run_state: &mut RunState<FATVol, Mbr>, action: MbrAction) -> Result<(), CliError> {
+    let disk = run_state
+        .disk
+        .as_mut()
+        .ok_or_else(|| CliError::new(ErrorKind::Usage, "Open disk image first"))?;
+    match action {
+        MbrAction::Boot { number, bootable } => {
+            let previous = disk.set_bootable(number, bootable).map_err(|err| {
+                let kind = match err {
+                    DiskError::Io(_) => ErrorKind::Io,
+                    DiskError::NoSuchPartition(_) => ErrorKind::NotFound,
+                    _ => ErrorKind::Usage,
+                };
+                CliError::new(kind, format!("Boot indicator change failed: {err}"))
+            })?;
+            let state = if bootable { "set" } else { "cleared" };
+            println!("Boot indicator of partition #{number} {state} (was 0x{previous:02X}).");
+            for anomaly in disk.part_table().boot_anomalies() {
+                println!("Warning: {anomaly}.");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the write journal of the opened image, or an error for a compressed image.
+fn image_journal<U: LayoutDisplay>(run_state: &RunState<FATVol, U>) -> Result<&Journal, CliError> {
+    opened_disk(run_state)?.image().journal().ok_or_else(|| {
+        CliError::new(
+            ErrorKind::Io,
+            "Compressed images are read-only and have no journal",
+        )
+    })
+}
+
+/// Re-encodes a disk image in another format, reporting the progress at the debug level.
+fn convert(image: &str, out: &str, format: ImageFormat) -> Result<(), CliError> {
+    let source = ImageSource::open(Path::new(image))
+        .map_err(|err| CliError::io(&format!("Can't open {image}"), err))?;
+    let len = convert_image(&source, Path::new(out), format, |done, total| {
+        debug!("convert: {done}/{total} bytes")
+    })
+    .map_err(|err| CliError::io("Conversion failed", err))?;
+    println!("Converted {image} ({len} bytes) to {out}");
+    Ok(())
+}
+
+/// Opens an image besides the opened disk, with the settings of the session.
+fn open_other_disk(
+    run_state: &RunState<FATVol, Mbr>,
+    image: &str,
+) -> Result<Disk<FATVol, Mbr>, CliError> {
+    let disk = Disk::from_file_with_options(
+        Path::new(image),
         run_state.sector_size,
-        0,
-    ) {
-        Ok(()) => println!("Write succeeded!"),
-        Err(err) => error!("Write failed: {err}"),
+        run_state.bpb_validation,
+        &run_state.table_options,
+    )
+    .map_err(|err| CliError::new(ErrorKind::Io, format!("Failed to open {image}: {err}")))?;
+    for vol in disk.volumes() {
+        vol.set_limits(run_state.limits);
+        vol.set_cancel_token(interrupt_token().clone());
+    }
+    Ok(disk)
+}
+
+/// Prints the differences between the files of a volume of two images, failing if there are any
+/// so that scripts detect the tampering.
+fn tree_diff(
+    run_state: &RunState<FATVol, Mbr>,
+    before: &str,
+    after: &str,
+    vol_nb: u8,
+) -> Result<(), CliError> {
+    let disks = [
+        open_other_disk(run_state, before)?,
+        open_other_disk(run_state, after)?,
+    ];
+    let [before_vol, after_vol] = [0, 1].map(|i| {
+        disks[i].volume(vol_nb as usize - 1).ok_or_else(|| {
+            CliError::new(
+                ErrorKind::NotFound,
+                format!(
+                    "{} has no volume {vol_nb}, only {} valid volumes.",
+                    disks[i].file_path().display(),
+                    disks[i].volumes().len()
+                ),
+            )
+        })
+    });
+    let diff = diff_trees(before_vol?, after_vol?)
+        .map_err(|err| CliError::fat("Comparison failed", err))?;
+    print!("{diff}");
+
+    match diff.is_clean() {
+        true => Ok(()),
+        false => Err(CliError::new(
+            ErrorKind::Validation,
+            format!(
+                "{} file(s) differ between {before} and {after}",
+                diff.difference_count()
+            ),
+        )),
+    }
+}
+
+/// Prints the files with the same content, across the volumes of the opened disk and of `images`.
+fn dupes(run_state: &RunState<FATVol, Mbr>, images: &[String]) -> Result<(), CliError> {
+    let disks = images
+        .iter()
+        .map(|image| open_other_disk(run_state, image))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let volumes = std::iter::once(opened_disk(run_state)?)
+        .chain(disks.iter())
+        .flat_map(|disk| {
+            disk.volumes().iter().enumerate().map(|(idx, vol)| {
+                let name = format!("{}#{}", disk.file_path().display(), idx + 1);
+                (name, vol)
+            })
+        });
+    let sets =
+        find_duplicates(volumes).map_err(|err| CliError::fat("Duplicate search failed", err))?;
+
+    for set in &sets {
+        println!("{set}");
+    }
+    println!("{} sets of duplicate files", sets.len());
+    Ok(())
+}
+
+/// Prints the entries of every volume hidden through their attributes.
+fn hidden_tree(disk: &Disk<FATVol, Mbr>) -> Result<(), CliError> {
+    for vol in disk.volumes() {
+        for flagged in
+            hidden_entries(vol).map_err(|err| CliError::fat("Hidden entries scan failed", err))?
+        {
+            println!("{flagged}");
+        }
+    }
+    Ok(())
+}
+
+/// Prints the entries of the volume, or those carrying an attribute.
+fn list_entries(
+    vol: &FATVol,
+    attr: Option<Attribute>,
+    hex: bool,
+    page_len: Option<usize>,
+) -> Result<(), CliError> {
+    // Entries are printed as the directories are read, so large ones show up progressively
+    let mut pager = Pager::new(page_len);
+    let _ = vol
+        .visit_entries(|path, entry| {
+            if attr.is_some_and(|attr| !entry.has_attribute(attr)) {
+                return ControlFlow::Continue(());
+            }
+            let name = match hex {
+                true => format!("{} [{}]", escape_path(path), entry.name_hex()),
+                false => escape_path(path),
+            };
+            let line = writeln!(
+                pager,
+                "/{:<40} {:>10}B {}",
+                name,
+                entry.file_size(),
+                entry.attributes()
+            );
+            match line.is_err() || pager.stopped() {
+                true => ControlFlow::Break(()),
+                false => ControlFlow::Continue(()),
+            }
+        })
+        .map_err(|err| CliError::fat("Listing failed", err))?;
+    Ok(())
+}
+
+/// Streams a file of `vol` to `out`, reporting the progress at the debug level.
+fn extract(vol: &FATVol, path: &Path, out: &Path, buffer_len: usize) -> Result<(), CliError> {
+    let mut file = File::create(out)
+        .map_err(|err| CliError::io(&format!("Can't create {}", out.display()), err))?;
+    let written = vol
+        .copy_file(path, &mut file, buffer_len, |done, total| {
+            debug!("extract: {done}/{total} bytes")
+        })
+        .map_err(|err| CliError::fat("Extraction failed", err))?;
+    println!(
+        "Extracted {} ({written} bytes) to {}",
+        escape_path(path),
+        out.display()
+    );
+    Ok(())
+}
+
+/// Writes the files of the volume to an archive, and their metadata to `<archive>.metadata.csv`.
+fn extract_all(vol: &FATVol, format: ArchiveFormat, out: &Path) -> Result<(), CliError> {
+    let mut sidecar = out.as_os_str().to_owned();
+    sidecar.push(".metadata.csv");
+    let create = |path: &Path| {
+        File::create(path)
+            .map(BufWriter::new)
+            .map_err(|err| CliError::io(&format!("Can't create {}", path.display()), err))
+    };
+
+    let summary = export_archive(vol, format, create(out)?, create(Path::new(&sidecar))?)
+        .map_err(|err| CliError::fat("Extraction failed", err))?;
+    println!("{summary} to {}", out.display());
+    println!("Metadata saved to {}", Path::new(&sidecar).display());
+    Ok(())
+}
+
+/// Prints the DFXML file objects of the volume, or saves them to `out`.
+fn dfxml(vol: &FATVol, out: Option<&Path>) -> Result<(), CliError> {
+    match out {
+        Some(out) => {
+            let file = File::create(out)
+                .map_err(|err| CliError::io(&format!("Can't create {}", out.display()), err))?;
+            let count = export_dfxml(vol, BufWriter::new(file))
+                .map_err(|err| CliError::fat("Export failed", err))?;
+            println!("{count} file object(s) exported to {}", out.display());
+        }
+        None => {
+            export_dfxml(vol, io::stdout().lock())
+                .map_err(|err| CliError::fat("Export failed", err))?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the content of the volume slack, and extracts its data to `out`.
+fn volume_slack(vol: &FATVol, out: Option<&Path>) -> Result<(), CliError> {
+    let slack = scan_volume_slack(vol)
+        .map_err(|err| CliError::fat("Reading the volume slack failed", err))?;
+    println!("{slack}");
+
+    if let Some(out) = out.filter(|_| slack.holds_data()) {
+        fs::write(out, slack.data()).map_err(|err| CliError::io("Failed to save the data", err))?;
+        println!("Data saved to {}", out.display());
+    }
+    Ok(())
+}
+
+/// Prints the content of the gap between the MBR and the first partition, and saves its data to
+/// `out`.
+fn mbr_gap(disk: &Disk<FATVol, Mbr>, out: Option<&Path>) -> Result<(), CliError> {
+    let gap = scan_mbr_gap(disk).map_err(|err| {
+        CliError::new(ErrorKind::Io, format!("Reading the MBR gap failed: {err}"))
+    })?;
+    let Some(gap) = gap else {
+        println!("No gap between the MBR and the first partition.");
+        return Ok(());
+    };
+    println!("{gap}");
+
+    if let Some(out) = out.filter(|_| gap.holds_data()) {
+        fs::write(out, gap.data()).map_err(|err| CliError::io("Failed to save the data", err))?;
+        println!("Data saved to {}", out.display());
+    }
+    Ok(())
+}
+
+/// Renders a DOT graph and prints it, or saves it to `out`.
+///
+/// Files with the `.svg` extension are rendered with the Graphviz `dot` executable.
+fn export_dot(vol: &FATVol, target: DotTarget, out: Option<&Path>) -> Result<(), CliError> {
+    let graph = match target {
+        DotTarget::Chain(path) => dot::chain_to_dot(vol, Path::new(&path)),
+        DotTarget::Map => dot::allocation_map_to_dot(vol),
+        DotTarget::Tree => dot::tree_to_dot(vol),
+    }
+    .map_err(|err| CliError::fat("DOT export failed", err))?;
+
+    let Some(out) = out else {
+        print!("{graph}");
+        return Ok(());
+    };
+    match out.extension().is_some_and(|ext| ext == "svg") {
+        true => render_svg(&graph, out),
+        false => fs::write(out, graph),
+    }
+    .map_err(|err| CliError::io("Failed to save the graph", err))?;
+
+    println!("Graph saved to {}", out.display());
+    Ok(())
+}
+
+fn render_svg(graph: &str, out: &Path) -> io::Result<()> {
+    let mut child = process::Command::new("dot")
+        .arg("-Tsvg")
+        .arg("-o")
+        .arg(out)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(stdin) = child.stdin.as_mut() {
+        stdin.write_all(graph.as_bytes())?;
+    }
+
+    match child.wait()?.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(
+            "Graphviz 'dot' failed to render the graph",
+        )),
     }
 }
@@ -110,23 +110,27 @@ fn hide_flag_after_mbr<T: LayoutDisplay + TreeDisplay, U: LayoutDisplay>(
 fn hide_flag_in_volume_slack(flag_file_path: &str, disk: &mut File, fat_vol: &FATVol) {
     let data: Vec<u8> = fs::read(flag_file_path).expect("Failed to read flag file.");
 
-    fat_vol
-        .write_to_volume_slack(disk, &data)
+    // The flag is read back, so it landed where expected
+    let evidence = fat_vol
+        .write_to_volume_slack_verified(disk, &data)
         .unwrap_or_else(|e| {
             error!("Failed to write to volume slack: {e}");
             std::process::exit(1);
         });
+    println!("Flag hidden in the volume slack: {evidence}");
 }
 
 fn hide_flag_in_file_slack(flag_file_path: &str, disk: &mut File, fat_vol: &FATVol) {
     let data: Vec<u8> = fs::read(flag_file_path).expect("Failed to read flag file.");
 
-    fat_vol
-        .write_to_file_slack(disk, Path::new("1/t.txt"), &data)
+    // The flag is read back, so it landed where expected
+    let evidence = fat_vol
+        .write_to_file_slack_verified(disk, Path::new("1/t.txt"), &data)
         .unwrap_or_else(|e| {
-            error!("Failed to write to volume slack: {e}");
+            error!("Failed to write to file slack: {e}");
             std::process::exit(1);
         });
+    println!("Flag hidden in the file slack of 1/t.txt: {evidence}");
 }
 
 fn hide_file_in_bad_clusters(flag_file_path: &str, disk: &mut File, fat_vol: &FATVol) {
@@ -0,0 +1,43 @@
+//! Serves the read-only analyses of a disk image over HTTP/JSON (see [`fat_forensics::server`]).
+//!
+//! Usage: `serve <image> [address]`, the address defaulting to `127.0.0.1:8080`.
+
+use fat_forensics::Disk;
+use fat_forensics::server::serve;
+use log::{error, info};
+use std::path::Path;
+use std::{env, process, thread};
+
+const SECTOR_SIZE: usize = 512;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+
+fn main() {
+    stderrlog::new()
+        .module(module_path!())
+        .verbosity(log::Level::Info)
+        .init()
+        .unwrap();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (image, addr) = match args.as_slice() {
+        [image] => (image.as_str(), DEFAULT_ADDR),
+        [image, addr] => (image.as_str(), addr.as_str()),
+        _ => {
+            eprintln!("Usage: serve <image> [address]");
+            process::exit(2);
+        }
+    };
+
+    let disk = Disk::from_file(Path::new(image), SECTOR_SIZE, true).unwrap_or_else(|err| {
+        error!("Can't open {image}: {err}");
+        process::exit(1);
+    });
+
+    let workers = thread::available_parallelism().map_or(1, |workers| workers.get());
+    info!("Serving {image} on http://{addr} with {workers} workers");
+    if let Err(err) = serve(&disk, addr, workers) {
+        error!("Server failed: {err}");
+        process::exit(1);
+    }
+}
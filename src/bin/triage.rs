@@ -0,0 +1,505 @@
+//! Triage of a directory of disk images.
+//!
+//! Every image of the directory is opened and run through a set of analyses: layout, directory
+//! tree, file hashes, deleted entries, slack scan and the overview of each volume, its checks and
+//! the order of its findings set by a triage profile. One report is written per image, along with
+//! a `summary.csv` file holding one line per image, so a whole seizure can be sorted before any
+//! image is examined in depth.
+//!
+//! The reports are rendered through a template, `templates/report.txt.hbs` by default; an
+//! institution supplies its own with `-t` to match its report format. The values of the template
+//! are:
+//! - `image`, `image_name`, `sha256`, `volumes`: The path, file name and digest of the image, and
+//!   its number of volumes.
+//! - `open_error`: Why the image couldn't be opened, empty otherwise.
+//! - `sections`: The analyses run, in order, each with its `name`, `content` and `error`.
+//! - `layout`, `tree`, `hashes`, `deleted`, `slack`, `summary`: The same sections, by name, to
+//!   order them freely; an analysis not run is missing.
+//! - `events`: The parse warnings and skipped entries, once each.
+//! - `errors`: The analyses that failed, with their error.
+//! - `tool`, `version`: The name and version of the crate.
+//!
+//! Usage: `triage <image_dir> <report_dir> [-j <jobs>] [-a <analysis>,...] [-p <profile>] [-t <template>] [--skip-validation]`
+
+use fat_forensics::analysis::RegionClass;
+use fat_forensics::analysis::hash::HashAlgorithm;
+use fat_forensics::analysis::payload::detect_payloads;
+use fat_forensics::analysis::profile::TriageProfile;
+use fat_forensics::analysis::recover::deleted_entries;
+use fat_forensics::analysis::summary::summarize_with;
+use fat_forensics::events::Event;
+use fat_forensics::export::template::{Template, TemplateValue};
+use fat_forensics::image::data_extents;
+use fat_forensics::logging::LogOptions;
+use fat_forensics::traits::LayoutOptions;
+use fat_forensics::{Disk, FATVol, Mbr};
+use log::{LevelFilter, error};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{env, process, thread};
+
+const SECTOR_SIZE: usize = 512;
+
+/// Name of the summary file written in the report directory.
+const SUMMARY_FILE: &str = "summary.csv";
+
+/// The template of the reports, unless another one is given with `-t`.
+const DEFAULT_TEMPLATE: &str = include_str!("../../templates/report.txt.hbs");
+
+/// An analysis run on every volume of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Analysis {
+    /// The partition and volume layout.
+    Layout,
+    /// The directory tree.
+    Tree,
+    /// The SHA-256 digest of every file.
+    Hashes,
+    /// The deleted entries, with the confidence in their recovery.
+    Deleted,
+    /// The payloads hidden in the file and volume slack.
+    Slack,
+    /// The overview of each volume, its findings ordered by the triage profile.
+    Summary,
+}
+
+impl Analysis {
+    const ALL: [Analysis; 6] = [
+        Analysis::Layout,
+        Analysis::Tree,
+        Analysis::Hashes,
+        Analysis::Deleted,
+        Analysis::Slack,
+        Analysis::Summary,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Analysis::Layout => "layout",
+            Analysis::Tree => "tree",
+            Analysis::Hashes => "hashes",
+            Analysis::Deleted => "deleted",
+            Analysis::Slack => "slack",
+            Analysis::Summary => "summary",
+        }
+    }
+}
+
+/// The options of a triage run.
+struct Options {
+    image_dir: PathBuf,
+    report_dir: PathBuf,
+    /// The number of images processed in parallel.
+    jobs: usize,
+    analyses: Vec<Analysis>,
+    /// The profile of the overviews.
+    profile: TriageProfile,
+    /// The template of the reports, and the extension of the report files.
+    template: Template,
+    report_ext: String,
+    bpb_validation: bool,
+}
+
+/// The line of an image in the summary file.
+#[derive(Default)]
+struct Summary {
+    image: String,
+    sha256: String,
+    volumes: usize,
+    /// The counts of the analyses run, `None` for the others.
+    files: Option<usize>,
+    deleted: Option<usize>,
+    slack_payloads: Option<usize>,
+    /// The highest score of the findings of the overviews.
+    top_score: Option<u8>,
+    /// The analyses that failed, with their error.
+    errors: Vec<String>,
+}
+
+impl Summary {
+    const CSV_HEADER: &str =
+        "image,sha256,volumes,files,deleted,slack_payloads,top_score,status,errors";
+
+    fn csv_line(&self) -> String {
+        let count = |count: Option<usize>| count.map(|c| c.to_string()).unwrap_or_default();
+        let score = self.top_score.map(|s| s.to_string()).unwrap_or_default();
+        let status = match self.errors.is_empty() {
+            true => "ok",
+            false => "error",
+        };
+        [
+            csv_field(&self.image),
+            self.sha256.clone(),
+            self.volumes.to_string(),
+            count(self.files),
+            count(self.deleted),
+            count(self.slack_payloads),
+            score,
+            status.to_string(),
+            csv_field(&self.errors.join("; ")),
+        ]
+        .join(",")
+    }
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let logging = LogOptions::take_from_args(&mut args).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(2);
+    });
+    if let Err(err) = logging.init(LevelFilter::Error, &[module_path!(), "fat_forensics"]) {
+        eprintln!("Can't open the log file: {err}");
+        process::exit(1);
+    }
+
+    let options = parse_args(args).unwrap_or_else(|err| {
+        error!("{err}");
+        eprintln!(
+            "Usage: triage <image_dir> <report_dir> [-j <jobs>] [-a <analysis>,...] [-p <profile>] [-t <template>] [--skip-validation] [-v|-vv|-q] [--log-file <path>]"
+        );
+        process::exit(2);
+    });
+
+    let mut images: Vec<PathBuf> = fs::read_dir(&options.image_dir)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect()
+        })
+        .unwrap_or_else(|err| {
+            error!("Can't list {}: {err}", options.image_dir.display());
+            process::exit(1);
+        });
+    images.retain(|path| path.is_file());
+    images.sort();
+
+    if let Err(err) = fs::create_dir_all(&options.report_dir) {
+        error!("Can't create {}: {err}", options.report_dir.display());
+        process::exit(1);
+    }
+
+    // Each worker takes the next image until none is left
+    let next = AtomicUsize::new(0);
+    let summaries = Mutex::new(vec![]);
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.min(images.len()) {
+            scope.spawn(|| {
+                while let Some(image) = images.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let summary = triage(image, &options);
+                    println!(
+                        "{}: {}",
+                        summary.image,
+                        match summary.errors.is_empty() {
+                            true => "ok",
+                            false => "error",
+                        }
+                    );
+                    summaries.lock().unwrap().push(summary);
+                }
+            });
+        }
+    });
+
+    let mut summaries = summaries.into_inner().unwrap();
+    summaries.sort_by(|a, b| a.image.cmp(&b.image));
+    let csv: Vec<String> = std::iter::once(Summary::CSV_HEADER.to_string())
+        .chain(summaries.iter().map(Summary::csv_line))
+        .collect();
+    let summary_path = options.report_dir.join(SUMMARY_FILE);
+    if let Err(err) = fs::write(&summary_path, csv.join("\n") + "\n") {
+        error!("Can't write {}: {err}", summary_path.display());
+        process::exit(1);
+    }
+
+    let failed = summaries.iter().filter(|s| !s.errors.is_empty()).count();
+    println!(
+        "{} image(s) processed, {failed} with errors. Summary written to {}",
+        summaries.len(),
+        summary_path.display()
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut positional = vec![];
+    let mut jobs = 1;
+    let mut analyses = Analysis::ALL.to_vec();
+    let mut profile = TriageProfile::default();
+    let mut template = None;
+    let mut report_ext = String::from("txt");
+    let mut bpb_validation = true;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-j" | "--jobs" => {
+                jobs = args
+                    .next()
+                    .and_then(|jobs| jobs.parse().ok())
+                    .filter(|jobs| *jobs > 0)
+                    .ok_or("-j expects a positive number of jobs")?;
+            }
+            "-a" | "--analyses" => {
+                let list = args.next().ok_or("-a expects a list of analyses")?;
+                analyses = list
+                    .split(',')
+                    .map(|name| {
+                        Analysis::ALL
+                            .into_iter()
+                            .find(|analysis| analysis.name() == name.trim())
+                            .ok_or(format!(
+                                "Unknown analysis {name:?}, expected one of: {}",
+                                Analysis::ALL.map(Analysis::name).join(", ")
+                            ))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "-p" | "--profile" => {
+                let name = args.next().ok_or("-p expects a profile")?;
+                profile = TriageProfile::resolve(&name)
+                    .map_err(|err| format!("Can't read profile {name}: {err}"))?;
+            }
+            "-t" | "--template" => {
+                let path = PathBuf::from(args.next().ok_or("-t expects a template file")?);
+                template = Some(
+                    Template::load(&path)
+                        .map_err(|err| format!("Can't read template {}: {err}", path.display()))?,
+                );
+                report_ext = Template::output_extension(&path);
+            }
+            "--skip-validation" => bpb_validation = false,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {arg:?}")),
+            _ => positional.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [image_dir, report_dir] = <[PathBuf; 2]>::try_from(positional)
+        .map_err(|_| "Expected an image directory and a report directory")?;
+    let template = match template {
+        Some(template) => template,
+        None => Template::parse(DEFAULT_TEMPLATE, false)
+            .map_err(|err| format!("Invalid default template: {err}"))?,
+    };
+    Ok(Options {
+        image_dir,
+        report_dir,
+        jobs,
+        analyses,
+        profile,
+        template,
+        report_ext,
+        bpb_validation,
+    })
+}
+
+/// Runs the analyses on an image and writes its report.
+///
+/// # Returns
+/// - The summary of the image. A failing analysis is recorded in the report and the summary, and
+///   doesn't stop the other ones.
+fn triage(image: &Path, options: &Options) -> Summary {
+    let name = image
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut summary = Summary {
+        image: name.clone(),
+        ..Default::default()
+    };
+
+    match sha256_file(image) {
+        Ok(digest) => summary.sha256 = digest,
+        Err(err) => summary.errors.push(format!("hash of the image: {err}")),
+    }
+
+    let mut open_error = String::new();
+    let mut sections = vec![];
+    let mut unique_events = vec![];
+    match Disk::from_file(image, SECTOR_SIZE, options.bpb_validation) {
+        Ok(disk) => {
+            // The parse warnings and skipped entries are listed at the end of the report
+            let events = Arc::new(Mutex::new(vec![]));
+            let sink = events.clone();
+            disk.set_event_sink(Some(Arc::new(move |event: &Event| {
+                if matches!(event, Event::Warning { .. } | Event::Skipped { .. }) {
+                    sink.lock().unwrap().push(event.to_string());
+                }
+            })));
+
+            summary.volumes = disk.volumes().len();
+            for analysis in &options.analyses {
+                let mut content = String::new();
+                let mut error = String::new();
+                if let Err(err) =
+                    run_analysis(&disk, *analysis, options, &mut content, &mut summary)
+                {
+                    summary.errors.push(format!("{}: {err}", analysis.name()));
+                    error = err;
+                }
+                sections.push((analysis.name(), content, error));
+            }
+
+            // The analyses walk the same directories, each event is listed once
+            let mut seen = HashSet::new();
+            unique_events = events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|event| seen.insert(*event))
+                .cloned()
+                .collect();
+        }
+        Err(err) => {
+            open_error = err.to_string();
+            summary.errors.push(format!("open: {err}"));
+        }
+    }
+
+    let section = |(name, content, error): &(&str, String, String)| {
+        TemplateValue::map([
+            ("name", TemplateValue::from(*name)),
+            ("content", content.clone().into()),
+            ("error", error.clone().into()),
+        ])
+    };
+    let mut context = vec![
+        ("tool", TemplateValue::from(env!("CARGO_PKG_NAME"))),
+        ("version", env!("CARGO_PKG_VERSION").into()),
+        ("image", image.display().to_string().into()),
+        ("image_name", name.clone().into()),
+        ("sha256", summary.sha256.clone().into()),
+        ("volumes", summary.volumes.into()),
+        ("open_error", open_error.into()),
+        (
+            "sections",
+            TemplateValue::List(sections.iter().map(section).collect()),
+        ),
+        ("events", unique_events.into()),
+        ("errors", summary.errors.clone().into()),
+    ];
+    context.extend(sections.iter().map(|entry| (entry.0, section(entry))));
+    let report = options.template.render(&TemplateValue::map(context));
+
+    let report_path = options
+        .report_dir
+        .join(format!("{name}.{}", options.report_ext));
+    if let Err(err) = fs::write(&report_path, report) {
+        summary.errors.push(format!("report: {err}"));
+    }
+    summary
+}
+
+/// Runs an analysis on every volume of a disk, appending its results to the report.
+fn run_analysis(
+    disk: &Disk<FATVol, Mbr>,
+    analysis: Analysis,
+    options: &Options,
+    report: &mut String,
+    summary: &mut Summary,
+) -> Result<(), String> {
+    if analysis == Analysis::Layout {
+        report.push_str(
+            &disk
+                .layout(0, &LayoutOptions::default())
+                .map_err(|err| err.to_string())?,
+        );
+        return Ok(());
+    }
+    if analysis == Analysis::Tree {
+        let mut tree = vec![];
+        disk.write_tree(&mut tree).map_err(|err| err.to_string())?;
+        report.push_str(&String::from_utf8_lossy(&tree));
+        return Ok(());
+    }
+
+    for (i, vol) in disk.volumes().iter().enumerate() {
+        let _ = writeln!(report, "Volume {}:", i + 1);
+        match analysis {
+            Analysis::Hashes => {
+                let files = vol.walk_files().map_err(|err| err.to_string())?;
+                for (path, entry) in &files {
+                    let data = vol.read_entry(entry).map_err(|err| err.to_string())?;
+                    let digest = HashAlgorithm::Sha256.hex_digest(&data);
+                    let _ = writeln!(report, "{digest}  {}", path.display());
+                }
+                *summary.files.get_or_insert(0) += files.len();
+            }
+            Analysis::Deleted => {
+                let deleted = deleted_entries(vol).map_err(|err| err.to_string())?;
+                deleted.iter().for_each(|item| {
+                    let _ = writeln!(report, "{item}");
+                    let _ = writeln!(report, "  evidence: {}", item.provenance());
+                });
+                *summary.deleted.get_or_insert(0) += deleted.len();
+            }
+            Analysis::Slack => {
+                let payloads: Vec<_> = detect_payloads(vol)
+                    .map_err(|err| err.to_string())?
+                    .into_iter()
+                    .filter(|candidate| {
+                        matches!(
+                            candidate.class(),
+                            RegionClass::FileSlack(_) | RegionClass::VolumeSlack
+                        )
+                    })
+                    .collect();
+                payloads.iter().for_each(|candidate| {
+                    let _ = writeln!(report, "{candidate}");
+                    let _ = writeln!(report, "  evidence: {}", candidate.provenance());
+                });
+                *summary.slack_payloads.get_or_insert(0) += payloads.len();
+            }
+            Analysis::Summary => {
+                let overview =
+                    summarize_with(vol, &options.profile).map_err(|err| err.to_string())?;
+                report.push_str(&overview.to_string());
+                if let Some(best) = overview.findings().first() {
+                    let top_score = summary.top_score.get_or_insert(0);
+                    *top_score = (*top_score).max(*best.score());
+                }
+            }
+            Analysis::Layout | Analysis::Tree => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// Computes the SHA-256 digest of a file, without loading it in memory.
+///
+/// The holes of a sparse file are hashed as the zeros they read as, without being read.
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut hasher = Sha256::new();
+    let mut pos = 0;
+    for (start, extent_len) in data_extents(&file, 0, len)? {
+        io::copy(&mut io::repeat(0).take(start - pos), &mut hasher)?;
+        file.seek(SeekFrom::Start(start))?;
+        io::copy(&mut (&mut file).take(extent_len), &mut hasher)?;
+        pos = start + extent_len;
+    }
+    io::copy(&mut io::repeat(0).take(len - pos), &mut hasher)?;
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+/// Quotes a CSV field if it holds a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
@@ -0,0 +1,136 @@
+//! LRU cache of disk sectors.
+//!
+//! Tree walks and lookups repeatedly read the same metadata (FAT sectors, directory clusters).
+//! The [`SectorCache`] keeps the most recently used sectors in memory so that these reads don't
+//! hit the disk every time.
+
+use getset::Getters;
+use std::collections::{BTreeMap, HashMap};
+
+/// Default capacity of the cache, in sectors.
+pub const DEFAULT_CACHE_CAPACITY: usize = 4096;
+
+/// Hit and miss counters of a cache.
+#[derive(Debug, Default, Clone, Copy, Getters)]
+pub struct CacheStats {
+    /// Number of sectors served from the cache.
+    #[get = "pub"]
+    hits: u64,
+    /// Number of sectors that had to be read from the disk.
+    #[get = "pub"]
+    misses: u64,
+}
+
+/// A least-recently-used cache of sectors, keyed by absolute sector number.
+#[derive(Debug)]
+pub struct SectorCache {
+    /// Maximum number of sectors kept in the cache. A capacity of 0 disables the cache.
+    capacity: usize,
+    /// Cached sectors with the tick of their last access.
+    sectors: HashMap<u64, (Vec<u8>, u64)>,
+    /// Sector numbers ordered by last access tick.
+    recency: BTreeMap<u64, u64>,
+    /// Monotonic access counter.
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl SectorCache {
+    /// Creates an empty cache.
+    ///
+    /// # Parameters
+    /// - `capacity`: The maximum number of sectors kept in the cache. 0 disables the cache.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            sectors: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns the maximum number of sectors kept in the cache.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes the capacity of the cache, evicting the least recently used sectors if needed.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.sectors.len() > self.capacity {
+            self.evict();
+        }
+    }
+
+    /// Returns the hit and miss counters.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Returns a copy of a cached sector and marks it as recently used.
+    pub fn get(&mut self, sector: u64) -> Option<Vec<u8>> {
+        self.tick += 1;
+        match self.sectors.get_mut(&sector) {
+            Some((data, last_access)) => {
+                self.recency.remove(last_access);
+                *last_access = self.tick;
+                self.recency.insert(self.tick, sector);
+                self.stats.hits += 1;
+                Some(data.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Inserts a sector, evicting the least recently used one if the cache is full.
+    pub fn insert(&mut self, sector: u64, data: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.invalidate(sector, 1);
+        while self.sectors.len() >= self.capacity {
+            self.evict();
+        }
+
+        self.tick += 1;
+        self.sectors.insert(sector, (data, self.tick));
+        self.recency.insert(self.tick, sector);
+    }
+
+    /// Drops the cached copies of a range of sectors, e.g. after they were written.
+    pub fn invalidate(&mut self, first_sector: u64, count: u64) {
+        if count > self.sectors.len() as u64 {
+            self.sectors.retain(|sector, (_, last_access)| {
+                let keep = *sector < first_sector || *sector >= first_sector + count;
+                if !keep {
+                    self.recency.remove(last_access);
+                }
+                keep
+            });
+            return;
+        }
+
+        for sector in first_sector..first_sector + count {
+            if let Some((_, last_access)) = self.sectors.remove(&sector) {
+                self.recency.remove(&last_access);
+            }
+        }
+    }
+
+    /// Drops every cached sector.
+    pub fn clear(&mut self) {
+        self.sectors.clear();
+        self.recency.clear();
+    }
+
+    fn evict(&mut self) {
+        if let Some((_, sector)) = self.recency.pop_first() {
+            self.sectors.remove(&sector);
+        }
+    }
+}
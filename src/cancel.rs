@@ -0,0 +1,43 @@
+//! Cooperative cancellation of long operations.
+//!
+//! Scanning, carving or extracting a large volume can take minutes. A [`CancellationToken`]
+//! installed on a volume (see [`FATVol::set_cancel_token`](crate::FATVol::set_cancel_token)) is
+//! checked between the chunks of these operations: once it is cancelled, e.g. from a signal
+//! handler or another thread, the running operation fails with
+//! [`FATError::Cancelled`](crate::filesystem::fat_error::FATError::Cancelled) at the next chunk,
+//! leaving the volume usable.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A flag shared between the operations of a volume and the party able to cancel them.
+///
+/// Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that isn't cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests the cancellation of the operations checking the token.
+    ///
+    /// Only stores to an atomic, so it may be called from a signal handler.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Clears the request, so that the next operations run to completion.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+}
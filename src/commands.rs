@@ -4,6 +4,198 @@
 //! The `Command` enum represents various commands that the user can input,
 //! such as quitting the program, opening a file, printing information, or handling
 //! invalid or unknown commands.
+//!
+//! Every command is declared once in the [`COMMANDS`] registry, with its usage, its help text and
+//! the parser of its arguments. The registry drives the parsing, the `help` command, the error
+//! messages and the completion of command names.
+
+use crate::analysis::RegionKind;
+use crate::analysis::hash::HashAlgorithm;
+use crate::analysis::strings::StringsOptions;
+use crate::export::archive::ArchiveFormat;
+use crate::filesystem::dir_entry::Attribute;
+use crate::filesystem::fat::DEFAULT_COPY_BUFFER_LEN;
+use crate::image::ImageFormat;
+use crate::partition::mbr::Geometry;
+use crate::traits::{LayoutOptions, LayoutUnit};
+use regex::Regex;
+use regex::bytes::Regex as BytesRegex;
+
+/// Represents the region overwritten by a `wipe` command.
+#[derive(Debug)]
+pub enum WipeTarget {
+    /// The slack space of every file, and the volume slack.
+    AllSlack,
+    /// The slack space of a single file, encapsulating its path as a `String`.
+    FileSlack(String),
+    /// Every unallocated cluster.
+    Free,
+}
+
+/// Where a `write` command writes a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteAddress {
+    /// An absolute sector of the disk.
+    Sector(u64),
+    /// A sector of a volume, counted from its first sector: (1-based volume number, sector).
+    VolumeSector(u8, u64),
+    /// A cluster of a volume: (1-based volume number, cluster).
+    Cluster(u8, u32),
+}
+
+/// Represents the analysis pass run by a `scan` command.
+#[derive(Debug)]
+pub enum ScanTarget {
+    /// Look for regions filled with constant bytes or repeating patterns.
+    Wipe,
+    /// Identify the type of each file from its content and flag extension mismatches.
+    Types,
+    /// Look for encoded blobs and appended files in slack space and past the end of images.
+    Payloads,
+    /// Measure the entropy of each file and flag the likely encrypted or packed ones.
+    Entropy,
+    /// Find the directories from their "." and ".." entries, without the FAT.
+    Dirs,
+    /// Flag the files whose slack holds non-zero bytes.
+    Slack,
+    /// Parse the slots past the end of every directory for stale entries.
+    DirSlack,
+    /// Flag the non-zero entries of the FATs past the last cluster of the data region.
+    FatSlack,
+    /// Flag the names holding control characters, invalid code points or direction overrides.
+    Names,
+    /// Flag the non-zero bytes of the unused reserved sectors and of the reserved fields of the
+    /// FSINFO sectors.
+    Reserved,
+    /// Classify the content of the unallocated clusters.
+    FreeSpace,
+}
+
+/// Represents the structure rendered by a `dot` command.
+#[derive(Debug)]
+pub enum DotTarget {
+    /// The cluster chain of a file, encapsulating its path as a `String`.
+    Chain(String),
+    /// The allocation map of the whole volume.
+    Map,
+    /// The directory tree of the volume.
+    Tree,
+}
+
+/// Represents the data hashed by a `hash` command.
+#[derive(Debug)]
+pub enum HashTarget {
+    /// The content of a file, encapsulating its path as a `String`.
+    File(String),
+    /// A region of the disk: (absolute byte offset, length).
+    Region((u64, usize)),
+}
+
+/// Represents the options of a `hash` command.
+#[derive(Debug, Default)]
+pub struct HashOptions {
+    /// The size in bytes of a block, or `None` for the cluster size.
+    pub block_size: Option<usize>,
+    /// The hash algorithm.
+    pub algorithm: HashAlgorithm,
+    /// A local file whose blocks are matched against the hashed data.
+    pub reference: Option<String>,
+}
+
+/// Represents the action of a `root` command.
+#[derive(Debug)]
+pub enum RootAction {
+    /// List the clusters that may be the first cluster of the root directory.
+    Scan,
+    /// Use the given cluster as the root directory.
+    Set(u32),
+    /// Use the root directory of the BPB again.
+    Reset,
+}
+
+/// An action on the snapshots of the open image.
+#[derive(Debug)]
+pub enum SnapshotAction {
+    /// Save the current state of the image under the given name.
+    Create(String),
+    /// Roll the image back to the snapshot of the given name.
+    Restore(String),
+    /// List the snapshots of the image.
+    List,
+}
+
+/// An action on the write journal of the open image.
+#[derive(Debug)]
+pub enum JournalAction {
+    /// List the writes made to the image since it was opened.
+    Show,
+    /// Export the journal as a patch script to the given file.
+    Export(String),
+    /// Forget the writes recorded so far.
+    Clear,
+    /// Replay a patch script onto a copy of a base image.
+    Replay {
+        script: String,
+        base: String,
+        copy: String,
+    },
+}
+
+/// An action on a whole FAT of the selected volume.
+#[derive(Debug)]
+pub enum FatAction {
+    /// Copy the FAT of the given number to a file.
+    Export { fat_nb: u8, file: String },
+    /// Overwrite the FAT of the given number with the content of a file.
+    Import { fat_nb: u8, file: String },
+}
+
+/// An action on the boot sector of the selected volume.
+#[derive(Debug)]
+pub enum BootSectorAction {
+    /// Copy the boot sector to a file.
+    Export(String),
+    /// Overwrite the boot sector with the content of a file, and its backup unless `sync_backup`
+    /// is false.
+    Import { file: String, sync_backup: bool },
+}
+
+/// An edit of the partition table of the open disk.
+#[derive(Debug)]
+pub enum MbrAction {
+    /// Set or clear the boot indicator of the partition of the given 1-based number.
+    Boot { number: usize, bootable: bool },
+}
+
+/// Represents a runtime setting changed by a `set` command.
+#[derive(Debug)]
+pub enum Setting {
+    /// The number of sectors kept in the sector cache of each volume.
+    Cache(usize),
+    /// The maximum depth of nested directories walked from the root.
+    MaxDepth(usize),
+    /// The maximum number of entries listed in a directory.
+    MaxDirEntries(usize),
+    /// The maximum number of clusters of a chain.
+    MaxChainLen(u32),
+    /// The maximum number of bytes recovered by a carving run.
+    MaxCarvedBytes(u64),
+    /// The number of lines of long outputs shown per page in a terminal, or `None` to disable
+    /// paging.
+    Pager(Option<usize>),
+    /// The geometry converting the CHS addresses of partition entries, used by the next `open`.
+    Geometry(Geometry),
+    /// Whether the next `open` accepts a table with overlapping partitions, leaving them
+    /// unopened, rather than rejecting it.
+    LenientOverlaps(bool),
+    /// Whether the commands modifying the image only report the bytes they would change.
+    DryRun(bool),
+    /// Whether the commands modifying the image ask for a confirmation in interactive sessions.
+    Confirm(bool),
+    /// The FAT copy the selected volume reads its cluster chains from, or `None` for the active
+    /// FAT of its BPB.
+    Fat(Option<u8>),
+}
 
 /// Represents a user command in the FAT32 file system tool.
 #[derive(Debug)]
@@ -12,16 +204,90 @@ pub enum Command {
     Quit,
     /// Command to open a disk image, encapsulating the file path as a `String`.
     Open(String),
-    /// Command to print general disk information.
-    Print,
+    /// Command to print general disk information with the given layout options.
+    Print(LayoutOptions),
     /// Select the partition to analyse (by index).
     Partition(u8),
     /// Skip the MBR validation.
     Skip,
-    /// Write a file to a given sector: (file path, starting sector).
-    Write((String, u64)),
-    /// Print the tree directory of every supported volume
-    Tree,
+    /// Write a file to a given address: (file path, address).
+    Write((String, WriteAddress)),
+    /// Print the tree directory of every supported volume, or only the entries hidden through
+    /// their attributes.
+    Tree(bool),
+    /// List every entry of the selected volume, or those carrying the given attribute, with their
+    /// raw names in hexadecimal if the flag is set.
+    Ls((Option<Attribute>, bool)),
+    /// Overwrite slack or free space of the selected volume: (target, pattern).
+    Wipe((WipeTarget, Vec<u8>)),
+    /// Overwrite and delete a file: (file path, overwrite passes, scrub the directory entry).
+    Shred((String, u32, bool)),
+    /// Run an analysis pass over the selected volume.
+    Scan(ScanTarget),
+    /// Render a structure of the selected volume as a Graphviz graph: (target, optional output file).
+    Dot((DotTarget, Option<String>)),
+    /// Hash a file or a region block by block: (target, options).
+    Hash((HashTarget, HashOptions)),
+    /// Hash every file of the selected volume into a hashdeep manifest, printed or saved to the
+    /// given file.
+    HashAll(Option<String>),
+    /// Compare the files of the selected volume against the hashdeep manifest at the given path.
+    CompareManifest(String),
+    /// Compare the files of a volume of two images: (first image, second image, 1-based volume
+    /// number).
+    TreeDiff((String, String, u8)),
+    /// Extract the printable strings of unallocated and slack space with the given options.
+    Strings(StringsOptions),
+    /// Search a pattern within the files under a path of the selected volume: (pattern, path).
+    Grep((BytesRegex, String)),
+    /// Carve the unallocated clusters and report the unique recoveries, optionally saving them to
+    /// the given directory.
+    Carve(Option<String>),
+    /// Write the files of the selected volume to an archive, with a metadata sidecar: (format,
+    /// archive file).
+    ExtractAll((ArchiveFormat, String)),
+    /// Write the file objects of the selected volume, with the byte runs of their content, as
+    /// DFXML, printed or saved to the given file.
+    Dfxml(Option<String>),
+    /// Stream a file of the selected volume to a file: (path, output file, buffer size in bytes).
+    Extract((String, String, usize)),
+    /// Check the volume slack of the selected volume for data, optionally extracting it to the
+    /// given file.
+    VolumeSlack(Option<String>),
+    /// Check the gap between the MBR and the first partition for data, optionally saving it to
+    /// the given file.
+    Gap(Option<String>),
+    /// Find or override the root directory of the selected volume.
+    Root(RootAction),
+    /// Save, restore or list the snapshots of the open image.
+    Snapshot(SnapshotAction),
+    /// Show, export or clear the write journal of the open image, or replay a patch script.
+    Journal(JournalAction),
+    /// Export a FAT of the selected volume to a file, or import one from a file.
+    Fat(FatAction),
+    /// Export the boot sector of the selected volume to a file, or import one from a file.
+    BootSector(BootSectorAction),
+    /// Edit the partition table of the open disk.
+    Mbr(MbrAction),
+    /// Check the selected volume for damage and list what can be recovered.
+    Recover,
+    /// Group the files of the opened disk, and of the given images, by content.
+    Dupes(Vec<String>),
+    /// Re-encode a disk image in another format: (source image, converted image, format).
+    Convert((String, String, ImageFormat)),
+    /// Print the details of a volume: the selected one, or the given 1-based volume number.
+    FsStat(Option<u8>),
+    /// Print a one-screen triage overview of the selected volume, with the given profile: the
+    /// name of a built-in one or the path of a profile file.
+    Summary(Option<String>),
+    /// Print the metadata of a file or directory, encapsulating its path as a `String`.
+    Stat(String),
+    /// Print the raw directory entry record at an address: (cluster, index in the cluster).
+    DirEnt((u32, u32)),
+    /// Change a runtime setting.
+    Set(Setting),
+    /// Print the list of commands, or the help of the given command.
+    Help(Option<String>),
     /// Command for an unknown input, encapsulating the raw input as a `String`.
     Unknown(String),
     /// Command for invalid input, encapsulating an error message as a `String`.
@@ -30,69 +296,983 @@ pub enum Command {
     Empty,
 }
 
+/// A command of the CLI, as listed by `help`.
+#[derive(Debug)]
+pub struct CommandSpec {
+    /// The word typing the command.
+    pub name: &'static str,
+    /// The arguments of the command, e.g. `<file> <sector>`.
+    pub usage: &'static str,
+    /// A one-line description of the command.
+    pub help: &'static str,
+    /// Builds the command from its arguments, or describes why they are invalid.
+    parse: fn(&[&str]) -> Result<Command, String>,
+}
+
+/// Every command of the CLI, in the order they are listed by `help`.
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "open",
+        usage: "<file>",
+        help: "Open a disk image.",
+        parse: parse_open,
+    },
+    CommandSpec {
+        name: "skip",
+        usage: "",
+        help: "Skip the BPB validation of the images opened next.",
+        parse: |args| no_args(args, Command::Skip),
+    },
+    CommandSpec {
+        name: "print",
+        usage: "[--unit <sectors|bytes|human>] [--all]",
+        help: "Print the layout of the disk and its volumes.",
+        parse: parse_print,
+    },
+    CommandSpec {
+        name: "part",
+        usage: "<idx>",
+        help: "Select the volume to analyse, by its 1-based number.",
+        parse: parse_part,
+    },
+    CommandSpec {
+        name: "tree",
+        usage: "[--hidden-only]",
+        help: "Print the directory tree of every supported volume, or only its hidden entries.",
+        parse: parse_tree,
+    },
+    CommandSpec {
+        name: "ls",
+        usage: "[--attr <read_only|hidden|system|volume_id|directory|archive>] [--hex]",
+        help: "List the entries of the selected volume, optionally only those with an attribute, or with their raw names in hex.",
+        parse: parse_ls,
+    },
+    CommandSpec {
+        name: "fsstat",
+        usage: "[vol]",
+        help: "Print the details of the selected volume, or of the given volume.",
+        parse: parse_fsstat,
+    },
+    CommandSpec {
+        name: "summary",
+        usage: "[<profile>]",
+        help: "Print a one-screen triage overview of the selected volume: contents, hidden data, anomalies and top findings. The profile, a built-in one (default, ctf, usb) or a profile file, selects the checks and orders the findings.",
+        parse: parse_summary,
+    },
+    CommandSpec {
+        name: "stat",
+        usage: "<path>",
+        help: "Print the metadata of a file or directory.",
+        parse: parse_stat,
+    },
+    CommandSpec {
+        name: "dirent",
+        usage: "<cluster> <index>",
+        help: "Decode the raw directory entry at an index of a cluster, reachable or not.",
+        parse: parse_dirent,
+    },
+    CommandSpec {
+        name: "write",
+        usage: "<file> <sector> | <file> --vol <n> --sector <sector> | <file> --vol <n> --cluster <cluster>",
+        help: "Write a local file to the disk, from an absolute sector, a sector of a volume counted from its start, or a cluster of a volume.",
+        parse: parse_write,
+    },
+    CommandSpec {
+        name: "wipe",
+        usage: "slack [path|all] | free [--pattern <hex>]",
+        help: "Overwrite file slack, volume slack or free clusters with zeros or a pattern.",
+        parse: parse_wipe,
+    },
+    CommandSpec {
+        name: "shred",
+        usage: "<path> [passes] [--scrub]",
+        help: "Overwrite a file, then delete it.",
+        parse: parse_shred,
+    },
+    CommandSpec {
+        name: "scan",
+        usage: "wipe|types|payloads|entropy|dirs|slack|dirslack|fatslack|names|reserved|freespace",
+        help: "Run an analysis pass over the selected volume.",
+        parse: parse_scan,
+    },
+    CommandSpec {
+        name: "strings",
+        usage: "[unalloc] [slack] [volslack] [--min <chars>] [--regex <pattern>]",
+        help: "Extract the printable strings of unallocated and slack space.",
+        parse: parse_strings,
+    },
+    CommandSpec {
+        name: "grep",
+        usage: "<pattern> [path]",
+        help: "Search a regex within the cluster chains of the files under a directory.",
+        parse: parse_grep,
+    },
+    CommandSpec {
+        name: "hash",
+        usage: "file <path> | region <offset> <len> [--block <bytes>] [--algo <md5|sha256>] [--ref <file>]",
+        help: "Hash a file or a region block by block, optionally matching a reference file.",
+        parse: parse_hash,
+    },
+    CommandSpec {
+        name: "hash-all",
+        usage: "[--out <manifest>]",
+        help: "Hash every file with MD5 and SHA-256 into a hashdeep manifest.",
+        parse: parse_hash_all,
+    },
+    CommandSpec {
+        name: "compare-manifest",
+        usage: "<manifest>",
+        help: "Report the files missing from, added to or modified since a hashdeep manifest.",
+        parse: |args| match args {
+            [manifest] => Ok(Command::CompareManifest(manifest.to_string())),
+            _ => Err(String::from("expected the path of a manifest")),
+        },
+    },
+    CommandSpec {
+        name: "tree-diff",
+        usage: "<imageA> <imageB> [--vol <n>]",
+        help: "List the files added, removed, modified or renamed between a volume of two images (the first volume by default), matched by path and content.",
+        parse: parse_tree_diff,
+    },
+    CommandSpec {
+        name: "carve",
+        usage: "[out_dir]",
+        help: "Carve the unallocated clusters, optionally saving the recoveries.",
+        parse: parse_carve,
+    },
+    CommandSpec {
+        name: "extract",
+        usage: "[--buffer <bytes>] <path> <file>",
+        help: "Copy a file of the selected volume to a file, reading a buffer at a time (1 MiB by default).",
+        parse: parse_extract,
+    },
+    CommandSpec {
+        name: "extract-all",
+        usage: "[--format zip|tar] <archive>",
+        help: "Write every file of the selected volume to an archive, with a CSV metadata sidecar.",
+        parse: parse_extract_all,
+    },
+    CommandSpec {
+        name: "dfxml",
+        usage: "[--out <file>]",
+        help: "Export the metadata of every file of the selected volume as DFXML, with the byte runs locating its content in the image.",
+        parse: |args| match args {
+            [] => Ok(Command::Dfxml(None)),
+            ["--out", out] => Ok(Command::Dfxml(Some(out.to_string()))),
+            _ => Err(String::from("expected at most '--out <file>'")),
+        },
+    },
+    CommandSpec {
+        name: "slack",
+        usage: "volume [--extract <file>]",
+        help: "Check the volume slack for data, optionally extracting it.",
+        parse: parse_slack,
+    },
+    CommandSpec {
+        name: "gap",
+        usage: "[out_file]",
+        help: "Check the sectors between the MBR and the first partition for data, optionally saving it.",
+        parse: parse_gap,
+    },
+    CommandSpec {
+        name: "root",
+        usage: "scan | set <cluster> | reset",
+        help: "Find or override the root directory of the selected volume.",
+        parse: parse_root,
+    },
+    CommandSpec {
+        name: "dupes",
+        usage: "[<image>...]",
+        help: "List the files with the same content, on the opened disk and the given images.",
+        parse: |args| {
+            Ok(Command::Dupes(
+                args.iter().map(|arg| arg.to_string()).collect(),
+            ))
+        },
+    },
+    CommandSpec {
+        name: "convert",
+        usage: "<image> <out> [--format raw|vhd]",
+        help: "Re-encode a disk image as a raw or fixed VHD image, in the format given by the extension of the output by default.",
+        parse: parse_convert,
+    },
+    CommandSpec {
+        name: "recover",
+        usage: "",
+        help: "Check the selected volume for damage and list what can be recovered.",
+        parse: |args| no_args(args, Command::Recover),
+    },
+    CommandSpec {
+        name: "dot",
+        usage: "chain <path> | map | tree [out]",
+        help: "Render a structure of the selected volume as a Graphviz graph.",
+        parse: parse_dot,
+    },
+    CommandSpec {
+        name: "snapshot",
+        usage: "create <name> | restore <name> | list",
+        help: "Save the state of the open image, or roll it back to a saved state.",
+        parse: parse_snapshot,
+    },
+    CommandSpec {
+        name: "journal",
+        usage: "[export <script> | clear | replay <script> <base> <copy>]",
+        help: "List the writes made to the open image, export them as a patch script, or replay one.",
+        parse: parse_journal,
+    },
+    CommandSpec {
+        name: "fat",
+        usage: "export <n> <file> | import <n> <file>",
+        help: "Dump a FAT of the selected volume to a file, or overwrite it with a FAT image of the same size.",
+        parse: parse_fat,
+    },
+    CommandSpec {
+        name: "bootsec",
+        usage: "export <file> | import [--no-sync] <file>",
+        help: "Dump the boot sector of the selected volume to a file, or overwrite it and its backup with a valid boot sector.",
+        parse: parse_bootsec,
+    },
+    CommandSpec {
+        name: "mbr",
+        usage: "boot <idx> on|off",
+        help: "Set or clear the boot indicator of a partition in the partition table.",
+        parse: parse_mbr,
+    },
+    CommandSpec {
+        name: "set",
+        usage: "cache <sectors> | max-depth <dirs> | max-entries <entries> | max-chain <clusters> | max-carved <bytes> | pager <lines>|off | geometry <heads> <sectors> | overlaps strict|lenient | dry-run on|off | confirm on|off | fat <n>|auto",
+        help: "Change a runtime setting.",
+        parse: parse_set,
+    },
+    CommandSpec {
+        name: "help",
+        usage: "[command]",
+        help: "List the commands, or describe one.",
+        parse: parse_help,
+    },
+    CommandSpec {
+        name: "quit",
+        usage: "",
+        help: "Quit the program.",
+        parse: |args| no_args(args, Command::Quit),
+    },
+];
+
+impl CommandSpec {
+    /// Returns the usage line of the command, e.g. `write <file> <sector>`.
+    pub fn usage_line(&self) -> String {
+        match self.usage.is_empty() {
+            true => self.name.to_string(),
+            false => format!("{} {}", self.name, self.usage),
+        }
+    }
+}
+
+/// Returns the command typed by a word.
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.name == name)
+}
+
+/// Returns the names of the commands starting with a prefix, for tab completion.
+pub fn complete(prefix: &str) -> Vec<&'static str> {
+    COMMANDS
+        .iter()
+        .map(|spec| spec.name)
+        .filter(|name| name.starts_with(prefix))
+        .collect()
+}
+
+/// Returns the help text of a command, or the list of every command.
+///
+/// # Parameters
+/// - `name`: The command to describe, or `None` to list them all.
+///
+/// # Returns
+/// - `Some(String)`: The help text.
+/// - `None`: If the command doesn't exist.
+pub fn help_text(name: Option<&str>) -> Option<String> {
+    match name {
+        Some(name) => {
+            let spec = find_command(name)?;
+            Some(format!("{}\n    {}", spec.usage_line(), spec.help))
+        }
+        None => {
+            let width = COMMANDS.iter().map(|spec| spec.name.len()).max()?;
+            let lines: Vec<String> = COMMANDS
+                .iter()
+                .map(|spec| format!("{:width$}  {}", spec.name, spec.help))
+                .collect();
+            Some(format!(
+                "{}\nType 'help <command>' for its arguments.",
+                lines.join("\n")
+            ))
+        }
+    }
+}
+
 impl Command {
     /// Parses a string into a `Command` instance.
     ///
+    /// The first word names the command, looked up in [`COMMANDS`]; the following words are its
+    /// arguments.
+    ///
     /// # Parameters
     /// - `s`: A string slice representing the user input.
     ///
     /// # Returns
     /// - The corresponding `Command` variant based on the input string.
-    ///
-    /// # Behavior
-    /// - Recognizes commands: `quit`, `open <file>`, `print`, `part <idx>`, `skip`, `write <file> <sector>`
-    /// - Returns `Command::Invalid` for missing or malformed arguments.
-    /// - Returns `Command::Unknown` for unrecognized commands.
-    /// - Returns `Command::Empty` for empty or whitespace-only input.
+    /// - `Command::Invalid` for missing or malformed arguments, with the usage of the command.
+    /// - `Command::Unknown` for unrecognized commands.
+    /// - `Command::Empty` for empty or whitespace-only input.
     pub fn from_string(s: &str) -> Self {
         let mut parts = s.split_whitespace();
-        match parts.next() {
-            Some("quit") => Command::Quit,
-            Some("open") => match parts.next() {
-                Some(arg) => Command::Open(arg.to_string()),
-                None => Command::Invalid(String::from(
-                    "Missing arg: 'open' expects the path to a '.img' file.",
-                )),
-            },
-            Some("print") => Command::Print,
-            Some("part") => match parts.next() {
-                Some(arg) => match arg.parse::<u8>() {
-                    Ok(nb) => Command::Partition(nb),
-                    Err(_) => Command::Invalid(String::from(
-                        "Arg parsing error: 'part' expects the partition number as an unsigned integer.",
-                    )),
-                },
-                None => Command::Invalid(String::from(
-                    "Missing arg: 'part' expects the partition number.",
-                )),
+        let Some(name) = parts.next() else {
+            return Command::Empty;
+        };
+        let Some(spec) = find_command(name) else {
+            return Command::Unknown(name.to_string());
+        };
+
+        let args: Vec<&str> = parts.collect();
+        (spec.parse)(&args).unwrap_or_else(|err| {
+            Command::Invalid(format!(
+                "Invalid args for '{}': {err}. Usage: {}",
+                spec.name,
+                spec.usage_line()
+            ))
+        })
+    }
+
+    /// Returns whether the command writes to the open image.
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Command::Write(_)
+                | Command::Wipe(_)
+                | Command::Shred(_)
+                | Command::Snapshot(SnapshotAction::Restore(_))
+                | Command::Fat(FatAction::Import { .. })
+                | Command::BootSector(BootSectorAction::Import { .. })
+                | Command::Mbr(_)
+        )
+    }
+}
+
+/// Builds a command without arguments.
+fn no_args(args: &[&str], command: Command) -> Result<Command, String> {
+    match args.is_empty() {
+        true => Ok(command),
+        false => Err(String::from("no argument expected")),
+    }
+}
+
+fn parse_open(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [path] => Ok(Command::Open(path.to_string())),
+        [] => Err(String::from("missing the path to a '.img' file")),
+        _ => Err(String::from("too many arguments")),
+    }
+}
+
+fn parse_print(args: &[&str]) -> Result<Command, String> {
+    let mut options = LayoutOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--all" => options.all = true,
+            "--unit" => {
+                options.unit = match args.next() {
+                    Some(&"sectors") => LayoutUnit::Sectors,
+                    Some(&"bytes") => LayoutUnit::Bytes,
+                    Some(&"human") => LayoutUnit::Human,
+                    Some(unit) => return Err(format!("unknown unit '{unit}'")),
+                    None => return Err(String::from("'--unit' expects a unit")),
+                }
+            }
+            other => return Err(format!("unexpected argument '{other}'")),
+        }
+    }
+
+    Ok(Command::Print(options))
+}
+
+fn parse_part(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [nb] => nb
+            .parse::<u8>()
+            .map(Command::Partition)
+            .map_err(|_| String::from("the partition number must be an unsigned integer")),
+        [] => Err(String::from("missing the partition number")),
+        _ => Err(String::from("too many arguments")),
+    }
+}
+
+fn parse_fsstat(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::FsStat(None)),
+        [nb] => nb
+            .parse::<u8>()
+            .map(|nb| Command::FsStat(Some(nb)))
+            .map_err(|_| String::from("the volume number must be an unsigned integer")),
+        _ => Err(String::from("too many arguments")),
+    }
+}
+
+fn parse_summary(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::Summary(None)),
+        [profile] => Ok(Command::Summary(Some(profile.to_string()))),
+        _ => Err(String::from("too many arguments")),
+    }
+}
+
+fn parse_tree(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::Tree(false)),
+        ["--hidden-only"] => Ok(Command::Tree(true)),
+        _ => Err(String::from("expected at most '--hidden-only'")),
+    }
+}
+
+fn parse_ls(args: &[&str]) -> Result<Command, String> {
+    let mut attr = None;
+    let mut hex = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--attr" => {
+                let name = args.next().ok_or("missing the attribute after '--attr'")?;
+                attr = Some(
+                    Attribute::from_name(name)
+                        .ok_or_else(|| format!("unknown attribute '{name}'"))?,
+                );
+            }
+            "--hex" => hex = true,
+            other => return Err(format!("unknown option '{other}'")),
+        }
+    }
+    Ok(Command::Ls((attr, hex)))
+}
+
+fn parse_stat(args: &[&str]) -> Result<Command, String> {
+    // Long names may contain spaces: the path is the rest of the line
+    match args.is_empty() {
+        false => Ok(Command::Stat(args.join(" "))),
+        true => Err(String::from("missing the path of a file or directory")),
+    }
+}
+
+fn parse_write(args: &[&str]) -> Result<Command, String> {
+    let address = match args {
+        [_, sector] => WriteAddress::Sector(
+            sector
+                .parse()
+                .map_err(|_| String::from("the starting sector must be an unsigned integer"))?,
+        ),
+        [_, "--vol", vol_nb, mode, value] => {
+            let vol_nb = vol_nb
+                .parse()
+                .map_err(|_| String::from("the volume number must be an unsigned integer"))?;
+            match *mode {
+                "--sector" => WriteAddress::VolumeSector(
+                    vol_nb,
+                    value
+                        .parse()
+                        .map_err(|_| String::from("the sector must be an unsigned integer"))?,
+                ),
+                "--cluster" => WriteAddress::Cluster(
+                    vol_nb,
+                    value
+                        .parse()
+                        .map_err(|_| String::from("the cluster must be an unsigned integer"))?,
+                ),
+                _ => return Err(format!("unknown addressing mode '{mode}'")),
+            }
+        }
+        _ => {
+            return Err(String::from(
+                "expected the file and the starting sector, or '--vol <n>' followed by '--sector <sector>' or '--cluster <cluster>'",
+            ));
+        }
+    };
+    Ok(Command::Write((args[0].to_string(), address)))
+}
+
+fn parse_wipe(args: &[&str]) -> Result<Command, String> {
+    let (args, pattern) = match args.iter().position(|arg| *arg == "--pattern") {
+        Some(idx) => match args.get(idx + 1).and_then(|hex| parse_hex(hex)) {
+            Some(pattern) => ([&args[..idx], &args[idx + 2..]].concat(), pattern),
+            None => {
+                return Err(String::from(
+                    "'--pattern' expects a non-empty hexadecimal byte string",
+                ));
+            }
+        },
+        None => (args.to_vec(), vec![0]),
+    };
+
+    match args.as_slice() {
+        ["slack"] | ["slack", "all"] => Ok(Command::Wipe((WipeTarget::AllSlack, pattern))),
+        ["slack", path] => Ok(Command::Wipe((
+            WipeTarget::FileSlack(path.to_string()),
+            pattern,
+        ))),
+        ["free"] => Ok(Command::Wipe((WipeTarget::Free, pattern))),
+        _ => Err(String::from("expected 'slack [path|all]' or 'free'")),
+    }
+}
+
+fn parse_dirent(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [cluster, index] => {
+            let cluster = parse_u64(cluster)
+                .and_then(|cluster| u32::try_from(cluster).ok())
+                .ok_or("the cluster number must be a 32-bit unsigned integer")?;
+            let index = parse_u64(index)
+                .and_then(|index| u32::try_from(index).ok())
+                .ok_or("the entry index must be a 32-bit unsigned integer")?;
+            Ok(Command::DirEnt((cluster, index)))
+        }
+        _ => Err(String::from("expected a cluster number and an entry index")),
+    }
+}
+
+fn parse_shred(args: &[&str]) -> Result<Command, String> {
+    let scrub = args.contains(&"--scrub");
+    let args: Vec<&str> = args
+        .iter()
+        .filter(|arg| **arg != "--scrub")
+        .copied()
+        .collect();
+
+    match args.as_slice() {
+        [path] => Ok(Command::Shred((path.to_string(), 1, scrub))),
+        [path, passes] => passes
+            .parse::<u32>()
+            .map(|passes| Command::Shred((path.to_string(), passes, scrub)))
+            .map_err(|_| String::from("the number of passes must be an unsigned integer")),
+        _ => Err(String::from("expected the file path")),
+    }
+}
+
+fn parse_scan(args: &[&str]) -> Result<Command, String> {
+    let target = match args {
+        ["wipe"] => ScanTarget::Wipe,
+        ["types"] => ScanTarget::Types,
+        ["payloads"] => ScanTarget::Payloads,
+        ["entropy"] => ScanTarget::Entropy,
+        ["dirs"] => ScanTarget::Dirs,
+        ["dirslack"] => ScanTarget::DirSlack,
+        ["fatslack"] => ScanTarget::FatSlack,
+        ["slack"] => ScanTarget::Slack,
+        ["names"] => ScanTarget::Names,
+        ["reserved"] => ScanTarget::Reserved,
+        ["freespace"] => ScanTarget::FreeSpace,
+        [other] => return Err(format!("unknown scan '{other}'")),
+        _ => return Err(String::from("expected the analysis to run")),
+    };
+
+    Ok(Command::Scan(target))
+}
+
+fn parse_strings(args: &[&str]) -> Result<Command, String> {
+    let mut options = StringsOptions {
+        regions: vec![],
+        ..Default::default()
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "unalloc" => options.regions.push(RegionKind::Unallocated),
+            "slack" => options.regions.push(RegionKind::FileSlack),
+            "volslack" => options.regions.push(RegionKind::VolumeSlack),
+            "--min" => match args.next().map(|len| len.parse::<usize>()) {
+                Some(Ok(len)) if len > 0 => options.min_len = len,
+                _ => {
+                    return Err(String::from(
+                        "'--min' expects a positive number of characters",
+                    ));
+                }
             },
-            Some("skip") => Command::Skip,
-            Some("write") => {
-                // Get the filepath
-                let filepath = match parts.next() {
-                    Some(arg) => arg,
-                    None => {
-                        return Command::Invalid(String::from(
-                            "Missing arg: 'write' expects the file and the starting sector to write it.",
-                        ));
-                    }
+            "--regex" => {
+                let Some(pattern) = args.next() else {
+                    return Err(String::from("'--regex' expects a pattern"));
                 };
+                options.filter = Some(
+                    Regex::new(pattern)
+                        .map_err(|err| format!("invalid regex '{pattern}': {err}"))?,
+                );
+            }
+            other => return Err(format!("unexpected argument '{other}'")),
+        }
+    }
+    if options.regions.is_empty() {
+        options.regions = RegionKind::ALL.to_vec();
+    }
+
+    Ok(Command::Strings(options))
+}
 
-                match parts.next() {
-                    Some(arg) => match arg.parse::<u64>() {
-                        Ok(sector) => Command::Write((filepath.to_string(), sector)),
-                        Err(_) => Command::Invalid(String::from(
-                            "Arg parsing error: 'write' expects the starting sector as an unsigned integer.",
-                        )),
-                    },
-                    None => Command::Invalid(String::from(
-                        "Missing arg: 'write' expects the file and the starting sector to write it.",
-                    )),
+fn parse_grep(args: &[&str]) -> Result<Command, String> {
+    let (pattern, path) = match args {
+        [pattern] => (pattern, ""),
+        [pattern, path] => (pattern, *path),
+        _ => return Err(String::from("expected a pattern and an optional path")),
+    };
+    let pattern =
+        BytesRegex::new(pattern).map_err(|err| format!("invalid regex '{pattern}': {err}"))?;
+    Ok(Command::Grep((pattern, path.to_string())))
+}
+
+fn parse_hash_all(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::HashAll(None)),
+        ["--out", out] => Ok(Command::HashAll(Some(out.to_string()))),
+        _ => Err(String::from("expected at most '--out <manifest>'")),
+    }
+}
+
+fn parse_tree_diff(args: &[&str]) -> Result<Command, String> {
+    let (before, after, vol_nb) = match args {
+        [before, after] => (before, after, "1"),
+        [before, after, "--vol", vol_nb] => (before, after, *vol_nb),
+        _ => {
+            return Err(String::from(
+                "expected two images, optionally followed by '--vol <n>'",
+            ));
+        }
+    };
+    let vol_nb = vol_nb
+        .parse::<u8>()
+        .ok()
+        .filter(|vol_nb| *vol_nb > 0)
+        .ok_or("the volume number must be a positive integer")?;
+    Ok(Command::TreeDiff((
+        before.to_string(),
+        after.to_string(),
+        vol_nb,
+    )))
+}
+
+fn parse_hash(args: &[&str]) -> Result<Command, String> {
+    let mut options = HashOptions::default();
+    let mut positional = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--block" => match args.next().map(|size| size.parse::<usize>()) {
+                Some(Ok(size)) if size > 0 => options.block_size = Some(size),
+                _ => return Err(String::from("'--block' expects a positive number of bytes")),
+            },
+            "--algo" => {
+                options.algorithm = match args.next() {
+                    Some(&"md5") => HashAlgorithm::Md5,
+                    Some(&"sha256") => HashAlgorithm::Sha256,
+                    _ => return Err(String::from("'--algo' expects 'md5' or 'sha256'")),
                 }
             }
-            Some("tree") => Command::Tree,
-            Some(other) => Command::Unknown(other.to_string()),
-            None => Command::Empty,
+            "--ref" => match args.next() {
+                Some(path) => options.reference = Some(path.to_string()),
+                None => return Err(String::from("'--ref' expects a file")),
+            },
+            other if other.starts_with("--") => {
+                return Err(format!("unknown option '{other}'"));
+            }
+            other => positional.push(other),
         }
     }
+
+    match positional.as_slice() {
+        ["file", path] => Ok(Command::Hash((HashTarget::File(path.to_string()), options))),
+        ["region", offset, len] => match (parse_u64(offset), parse_u64(len)) {
+            (Some(offset), Some(len)) => Ok(Command::Hash((
+                HashTarget::Region((offset, len as usize)),
+                options,
+            ))),
+            _ => Err(String::from(
+                "the offset and the length must be unsigned integers",
+            )),
+        },
+        _ => Err(String::from(
+            "expected 'file <path>' or 'region <offset> <len>'",
+        )),
+    }
+}
+
+fn parse_carve(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::Carve(None)),
+        [out] => Ok(Command::Carve(Some(out.to_string()))),
+        _ => Err(String::from("expected at most the output directory")),
+    }
+}
+
+fn parse_extract(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [path, out] => Ok(Command::Extract((
+            path.to_string(),
+            out.to_string(),
+            DEFAULT_COPY_BUFFER_LEN,
+        ))),
+        ["--buffer", len, path, out] => match len.parse() {
+            Ok(len) if len > 0 => Ok(Command::Extract((path.to_string(), out.to_string(), len))),
+            _ => Err(format!("invalid buffer size '{len}'")),
+        },
+        _ => Err(String::from("expected '[--buffer <bytes>] <path> <file>'")),
+    }
+}
+
+fn parse_extract_all(args: &[&str]) -> Result<Command, String> {
+    match args {
+        // The format defaults to the extension of the archive
+        [out] => {
+            let format = match out.ends_with(".tar") {
+                true => ArchiveFormat::Tar,
+                false => ArchiveFormat::Zip,
+            };
+            Ok(Command::ExtractAll((format, out.to_string())))
+        }
+        ["--format", format, out] => ArchiveFormat::from_name(format)
+            .map(|format| Command::ExtractAll((format, out.to_string())))
+            .ok_or_else(|| format!("unknown archive format '{format}'")),
+        _ => Err(String::from("expected '[--format zip|tar] <archive>'")),
+    }
+}
+
+fn parse_convert(args: &[&str]) -> Result<Command, String> {
+    let (image, out, format) = match args {
+        // The format defaults to the extension of the output
+        [image, out] => {
+            let format = match out.ends_with(".vhd") {
+                true => ImageFormat::Vhd,
+                false => ImageFormat::Raw,
+            };
+            (image, out, format)
+        }
+        [image, out, "--format", format] => (
+            image,
+            out,
+            ImageFormat::from_name(format)
+                .ok_or_else(|| format!("unknown image format '{format}'"))?,
+        ),
+        _ => return Err(String::from("expected '<image> <out> [--format raw|vhd]'")),
+    };
+    Ok(Command::Convert((
+        image.to_string(),
+        out.to_string(),
+        format,
+    )))
+}
+
+fn parse_slack(args: &[&str]) -> Result<Command, String> {
+    match args {
+        ["volume"] => Ok(Command::VolumeSlack(None)),
+        ["volume", "--extract", out] => Ok(Command::VolumeSlack(Some(out.to_string()))),
+        _ => Err(String::from("expected 'volume [--extract <file>]'")),
+    }
+}
+
+fn parse_gap(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::Gap(None)),
+        [out] => Ok(Command::Gap(Some(out.to_string()))),
+        _ => Err(String::from("expected at most the output file")),
+    }
+}
+
+fn parse_root(args: &[&str]) -> Result<Command, String> {
+    match args {
+        ["scan"] => Ok(Command::Root(RootAction::Scan)),
+        ["reset"] => Ok(Command::Root(RootAction::Reset)),
+        ["set", cluster] => cluster
+            .parse::<u32>()
+            .map(|cluster| Command::Root(RootAction::Set(cluster)))
+            .map_err(|_| String::from("the cluster number must be an unsigned integer")),
+        _ => Err(String::from("expected 'scan', 'set <cluster>' or 'reset'")),
+    }
+}
+
+fn parse_snapshot(args: &[&str]) -> Result<Command, String> {
+    match args {
+        ["create", name] => Ok(Command::Snapshot(SnapshotAction::Create(name.to_string()))),
+        ["restore", name] => Ok(Command::Snapshot(SnapshotAction::Restore(name.to_string()))),
+        ["list"] => Ok(Command::Snapshot(SnapshotAction::List)),
+        _ => Err(String::from(
+            "expected 'create <name>', 'restore <name>' or 'list'",
+        )),
+    }
+}
+
+fn parse_journal(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::Journal(JournalAction::Show)),
+        ["export", script] => Ok(Command::Journal(JournalAction::Export(script.to_string()))),
+        ["clear"] => Ok(Command::Journal(JournalAction::Clear)),
+        ["replay", script, base, copy] => Ok(Command::Journal(JournalAction::Replay {
+            script: script.to_string(),
+            base: base.to_string(),
+            copy: copy.to_string(),
+        })),
+        _ => Err(String::from(
+            "expected no argument, 'export <script>', 'clear' or 'replay <script> <base> <copy>'",
+        )),
+    }
+}
+
+fn parse_fat(args: &[&str]) -> Result<Command, String> {
+    let fat_nb = |fat_nb: &str| {
+        fat_nb
+            .parse()
+            .map_err(|_| format!("invalid FAT number '{fat_nb}'"))
+    };
+    match args {
+        ["export", n, file] => Ok(Command::Fat(FatAction::Export {
+            fat_nb: fat_nb(n)?,
+            file: file.to_string(),
+        })),
+        ["import", n, file] => Ok(Command::Fat(FatAction::Import {
+            fat_nb: fat_nb(n)?,
+            file: file.to_string(),
+        })),
+        _ => Err(String::from(
+            "expected 'export <n> <file>' or 'import <n> <file>'",
+        )),
+    }
+}
+
+fn parse_bootsec(args: &[&str]) -> Result<Command, String> {
+    match args {
+        ["export", file] => Ok(Command::BootSector(BootSectorAction::Export(
+            file.to_string(),
+        ))),
+        ["import", file] => Ok(Command::BootSector(BootSectorAction::Import {
+            file: file.to_string(),
+            sync_backup: true,
+        })),
+        ["import", "--no-sync", file] => Ok(Command::BootSector(BootSectorAction::Import {
+            file: file.to_string(),
+            sync_backup: false,
+        })),
+        _ => Err(String::from(
+            "expected 'export <file>' or 'import [--no-sync] <file>'",
+        )),
+    }
+}
+
+fn parse_mbr(args: &[&str]) -> Result<Command, String> {
+    match args {
+        ["boot", nb, state @ ("on" | "off")] => Ok(Command::Mbr(MbrAction::Boot {
+            number: nb
+                .parse()
+                .map_err(|_| String::from("the partition number must be an unsigned integer"))?,
+            bootable: *state == "on",
+        })),
+        _ => Err(String::from("expected 'boot <idx> on|off'")),
+    }
+}
+
+fn parse_dot(args: &[&str]) -> Result<Command, String> {
+    let out = |out: Option<&&str>| out.map(|out| out.to_string());
+    match args {
+        ["chain", path, rest @ ..] if rest.len() <= 1 => Ok(Command::Dot((
+            DotTarget::Chain(path.to_string()),
+            out(rest.first()),
+        ))),
+        ["map", rest @ ..] if rest.len() <= 1 => {
+            Ok(Command::Dot((DotTarget::Map, out(rest.first()))))
+        }
+        ["tree", rest @ ..] if rest.len() <= 1 => {
+            Ok(Command::Dot((DotTarget::Tree, out(rest.first()))))
+        }
+        _ => Err(String::from("expected 'chain <path>', 'map' or 'tree'")),
+    }
+}
+
+fn parse_set(args: &[&str]) -> Result<Command, String> {
+    let number =
+        |value: &str| parse_u64(value).ok_or(String::from("the value must be an unsigned integer"));
+    let setting = match args {
+        ["cache", capacity] => capacity
+            .parse::<usize>()
+            .map(Setting::Cache)
+            .map_err(|_| String::from("the number of sectors must be an unsigned integer"))?,
+        ["max-depth", max] => Setting::MaxDepth(number(max)? as usize),
+        ["max-entries", max] => Setting::MaxDirEntries(number(max)? as usize),
+        ["max-chain", max] => Setting::MaxChainLen(
+            u32::try_from(number(max)?)
+                .map_err(|_| String::from("the number of clusters must fit in 32 bits"))?,
+        ),
+        ["max-carved", max] => Setting::MaxCarvedBytes(number(max)?),
+        ["pager", "off"] => Setting::Pager(None),
+        ["pager", lines] => match number(lines)? {
+            0 => return Err(String::from("a page holds at least one line")),
+            lines => Setting::Pager(Some(lines as usize)),
+        },
+        ["geometry", heads, sectors] => match (number(heads)?, number(sectors)?) {
+            (heads @ 1..=255, sectors_per_track @ 1..=63) => Setting::Geometry(Geometry {
+                heads: heads as u8,
+                sectors_per_track: sectors_per_track as u8,
+            }),
+            _ => {
+                return Err(String::from(
+                    "a geometry has 1 to 255 heads and 1 to 63 sectors per track",
+                ));
+            }
+        },
+        ["overlaps", "strict"] => Setting::LenientOverlaps(false),
+        ["overlaps", "lenient"] => Setting::LenientOverlaps(true),
+        ["dry-run", "on"] => Setting::DryRun(true),
+        ["dry-run", "off"] => Setting::DryRun(false),
+        ["confirm", "on"] => Setting::Confirm(true),
+        ["confirm", "off"] => Setting::Confirm(false),
+        ["fat", "auto"] => Setting::Fat(None),
+        ["fat", fat_nb] => {
+            Setting::Fat(Some(fat_nb.parse::<u8>().map_err(|_| {
+                String::from("the FAT number must be an integer from 0 to 255")
+            })?))
+        }
+        _ => {
+            return Err(String::from(
+                "expected 'cache <sectors>', 'max-depth <dirs>', 'max-entries <entries>', \
+                 'max-chain <clusters>', 'max-carved <bytes>', 'pager <lines>|off', \
+                 'geometry <heads> <sectors>', 'overlaps strict|lenient', 'dry-run on|off', \
+                 'confirm on|off' or 'fat <n>|auto'",
+            ));
+        }
+    };
+
+    Ok(Command::Set(setting))
+}
+
+fn parse_help(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::Help(None)),
+        [name] => Ok(Command::Help(Some(name.to_string()))),
+        _ => Err(String::from("expected at most a command name")),
+    }
+}
+
+/// Parses an unsigned integer written in decimal or in hexadecimal with a `0x` prefix.
+fn parse_u64(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Parses a hexadecimal byte string (e.g. `DEADBEEF` or `0xFF`).
+///
+/// # Returns
+/// - `Some(Vec<u8>)` if the string is a non-empty sequence of hexadecimal byte values.
+/// - `None` otherwise.
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if s.is_empty() || !s.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
 }
@@ -0,0 +1,84 @@
+//! Structured events reported by the walks and scans of a volume.
+//!
+//! The library doesn't print anything. Parse warnings, skipped entries, recovered items and the
+//! progress of long scans are reported as [`Event`]s to the [`EventSink`] installed on a volume
+//! (see [`FATVol::set_event_sink`](crate::FATVol::set_event_sink)), so that a CLI can log them and
+//! a GUI or service can display them as they happen. Without a sink, no event is built.
+
+use std::fmt;
+
+use crate::traits::EventSink;
+
+/// An event reported by a walk or scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A structure was parsed despite an inconsistency.
+    Warning {
+        /// The absolute byte offset of the structure, if known.
+        offset: Option<u64>,
+        /// The description of the inconsistency.
+        message: String,
+    },
+    /// An entry or region was skipped by a walk or scan.
+    Skipped {
+        /// The absolute byte offset of the entry or region.
+        offset: u64,
+        /// Why it was skipped.
+        reason: String,
+    },
+    /// An item was found recoverable.
+    Recovered {
+        /// The absolute byte offset of the item.
+        offset: u64,
+        /// The length of the item in bytes.
+        len: u64,
+        /// The description of the item.
+        description: String,
+    },
+    /// A long scan made progress.
+    Progress {
+        /// The name of the scan.
+        task: &'static str,
+        /// The units processed so far.
+        done: u64,
+        /// The total units to process.
+        total: u64,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Warning {
+                offset: Some(offset),
+                message,
+            } => write!(f, "Warning at 0x{offset:X}: {message}"),
+            Event::Warning {
+                offset: None,
+                message,
+            } => write!(f, "Warning: {message}"),
+            Event::Skipped { offset, reason } => write!(f, "Skipped 0x{offset:X}: {reason}"),
+            Event::Recovered {
+                offset,
+                len,
+                description,
+            } => write!(f, "Recovered 0x{offset:X} ({len} bytes): {description}"),
+            Event::Progress { task, done, total } => write!(f, "{task}: {done}/{total}"),
+        }
+    }
+}
+
+/// A sink forwarding the events to the `log` crate: warnings at the warn level, skipped and
+/// recovered items at the info level, progress at the debug level.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogSink;
+
+impl EventSink for LogSink {
+    fn emit(&self, event: &Event) {
+        match event {
+            Event::Warning { .. } => log::warn!("{event}"),
+            Event::Skipped { .. } | Event::Recovered { .. } => log::info!("{event}"),
+            Event::Progress { .. } => log::debug!("{event}"),
+        }
+    }
+}
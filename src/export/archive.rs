@@ -0,0 +1,348 @@
+//! Export of the files of a volume into a ZIP or TAR archive.
+//!
+//! The archive is written sequentially, one file at a time, so it can be streamed to a pipe or a
+//! socket. Each file keeps its path and its last modification time; ZIP archives also keep the
+//! attribute byte of the entry. A metadata sidecar, written as CSV, records what the archive
+//! formats can't hold: the creation and access times, the attributes, the long name, the first
+//! cluster and the byte runs of each entry, each run being the absolute offset of its content in
+//! the image and its length, as `<offset>+<len>`, separated by `;`.
+//!
+//! ZIP entries are stored uncompressed, and ZIP64 isn't supported: an archive over 4 GiB or with
+//! more than 65535 entries has to be written as TAR.
+
+use getset::Getters;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::filesystem::datetime::FatDateTime;
+use crate::filesystem::dir_entry::{Attribute, DirEntry};
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+
+/// The format of an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// ZIP, entries stored without compression.
+    Zip,
+    /// POSIX ustar.
+    Tar,
+}
+
+impl ArchiveFormat {
+    /// Finds a format by its name.
+    ///
+    /// # Parameters
+    /// - `name`: `zip` or `tar`.
+    ///
+    /// # Returns
+    /// - `Some(ArchiveFormat)`: The format.
+    /// - `None`: If no format has this name.
+    pub fn from_name(name: &str) -> Option<ArchiveFormat> {
+        match name {
+            "zip" => Some(ArchiveFormat::Zip),
+            "tar" => Some(ArchiveFormat::Tar),
+            _ => None,
+        }
+    }
+}
+
+/// What was written to an archive.
+#[derive(Debug, Default, Getters)]
+pub struct ArchiveSummary {
+    /// The number of files.
+    #[get = "pub"]
+    files: usize,
+    /// The number of directories.
+    #[get = "pub"]
+    dirs: usize,
+    /// The total size of the files in bytes.
+    #[get = "pub"]
+    bytes: u64,
+}
+
+impl fmt::Display for ArchiveSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} files ({} bytes) and {} directories archived",
+            self.files, self.bytes, self.dirs
+        )
+    }
+}
+
+/// Header of the metadata sidecar.
+const METADATA_HEADER: &str =
+    "path,type,size,attributes,created,modified,accessed,first_cluster,long_name,byte_runs";
+
+/// Writes every live file and directory of a volume to an archive, and their metadata to a
+/// sidecar.
+///
+/// # Parameters
+/// - `vol`: The volume to export.
+/// - `format`: The format of the archive.
+/// - `out`: The sink the archive is written to.
+/// - `metadata`: The sink the CSV metadata sidecar is written to.
+///
+/// # Returns
+/// - `Ok(ArchiveSummary)`: What was archived.
+/// - `Err(FATError)`: If the directory tree or a file cannot be read, the archive exceeds the
+///   limits of its format, or writing fails.
+pub fn export_archive<W: Write, M: Write>(
+    vol: &FATVol,
+    format: ArchiveFormat,
+    out: W,
+    metadata: M,
+) -> Result<ArchiveSummary, FATError> {
+    match format {
+        ArchiveFormat::Zip => write_entries(vol, ZipWriter::new(out), metadata),
+        ArchiveFormat::Tar => write_entries(vol, TarWriter { out }, metadata),
+    }
+}
+
+/// Writes the entries of a volume with an archive writer.
+fn write_entries<A: ArchiveWriter, M: Write>(
+    vol: &FATVol,
+    mut archive: A,
+    mut metadata: M,
+) -> Result<ArchiveSummary, FATError> {
+    let mut summary = ArchiveSummary::default();
+    writeln!(metadata, "{METADATA_HEADER}")?;
+
+    for (path, entry) in vol.walk_entries()? {
+        vol.check_cancelled()?;
+        // Volume labels hold no data, but files disguised as labels do
+        if entry.is_volume_id() && entry.cluster_number() == 0 {
+            continue;
+        }
+        let name = path.to_string_lossy().replace('\\', "/");
+
+        match entry.is_dir() {
+            true => {
+                archive.add(&format!("{name}/"), &entry, None)?;
+                summary.dirs += 1;
+            }
+            false => {
+                let data = vol.read_entry(&entry)?;
+                archive.add(&name, &entry, Some(&data))?;
+                summary.files += 1;
+                summary.bytes += data.len() as u64;
+            }
+        }
+        let runs = vol.file_byte_runs(&entry)?;
+        writeln!(metadata, "{}", metadata_line(&name, &entry, &runs))?;
+    }
+    archive.finish()?;
+    metadata.flush()?;
+
+    Ok(summary)
+}
+
+/// Formats the metadata of an entry and its byte runs as a line of the sidecar.
+fn metadata_line(name: &str, entry: &DirEntry, runs: &[(u64, u64)]) -> String {
+    let time = |time: Option<FatDateTime>| time.map(|time| time.to_string()).unwrap_or_default();
+    [
+        csv_field(name),
+        String::from(if entry.is_dir() { "dir" } else { "file" }),
+        entry.file_size().to_string(),
+        entry.attributes(),
+        time(entry.created()),
+        time(entry.modified()),
+        time(entry.accessed()),
+        entry.cluster_number().to_string(),
+        csv_field(entry.long_name().as_deref().unwrap_or_default()),
+        runs.iter()
+            .map(|(offset, len)| format!("{offset}+{len}"))
+            .collect::<Vec<_>>()
+            .join(";"),
+    ]
+    .join(",")
+}
+
+/// Quotes a CSV field if needed.
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+/// Returns an error for an archive exceeding the limits of its format.
+fn too_large(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, what.to_string())
+}
+
+/// A sequential writer of archive entries.
+trait ArchiveWriter {
+    /// Appends an entry: a file with its content, or a directory without.
+    fn add(&mut self, name: &str, entry: &DirEntry, data: Option<&[u8]>) -> io::Result<()>;
+
+    /// Writes the end of the archive.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Writer of a ZIP archive with stored entries.
+struct ZipWriter<W: Write> {
+    out: W,
+    /// The number of bytes written so far, the offset of the next local header.
+    offset: u64,
+    /// The central directory, written once every entry is.
+    central: Vec<u8>,
+    entries: usize,
+}
+
+impl<W: Write> ZipWriter<W> {
+    /// The version needed to extract stored entries and directories.
+    const VERSION: u16 = 20;
+    /// The general purpose flag marking UTF-8 names.
+    const UTF8: u16 = 0x0800;
+
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            offset: 0,
+            central: vec![],
+            entries: 0,
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+impl<W: Write> ArchiveWriter for ZipWriter<W> {
+    fn add(&mut self, name: &str, entry: &DirEntry, data: Option<&[u8]>) -> io::Result<()> {
+        let data = data.unwrap_or_default();
+        let offset = u32::try_from(self.offset)
+            .map_err(|_| too_large("ZIP archives are limited to 4 GiB"))?;
+        if self.entries == u16::MAX as usize {
+            return Err(too_large("ZIP archives are limited to 65535 entries"));
+        }
+        let (date, time) = entry.modified().map_or((0x21, 0), |time| time.to_raw());
+        let crc = crc32fast::hash(data);
+        let size = data.len() as u32;
+
+        // Fields shared by the local header and the central directory, from the version needed
+        let mut common = vec![];
+        for field in [Self::VERSION, Self::UTF8, 0, time, date] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [crc, size, size] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        let mut local = 0x04034b50u32.to_le_bytes().to_vec();
+        local.extend_from_slice(&common);
+        local.extend_from_slice(name.as_bytes());
+        self.write(&local)?;
+        self.write(data)?;
+
+        // Made by MS-DOS, so the external attributes are the FAT attribute byte
+        self.central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        self.central.extend_from_slice(&Self::VERSION.to_le_bytes());
+        self.central.extend_from_slice(&common);
+        for field in [0u16, 0, 0] {
+            self.central.extend_from_slice(&field.to_le_bytes());
+        }
+        self.central
+            .extend_from_slice(&(*entry.attr() as u32).to_le_bytes());
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let central_offset = u32::try_from(self.offset)
+            .map_err(|_| too_large("ZIP archives are limited to 4 GiB"))?;
+        let central = std::mem::take(&mut self.central);
+        self.write(&central)?;
+
+        let mut end = 0x06054b50u32.to_le_bytes().to_vec();
+        for field in [0u16, 0, self.entries as u16, self.entries as u16] {
+            end.extend_from_slice(&field.to_le_bytes());
+        }
+        end.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        end.extend_from_slice(&central_offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.write(&end)?;
+        self.out.flush()
+    }
+}
+
+/// Writer of a POSIX ustar archive.
+struct TarWriter<W: Write> {
+    out: W,
+}
+
+/// The size of a TAR header and the unit of the data.
+const TAR_BLOCK: usize = 512;
+
+/// Writes a number as a NUL-terminated octal field.
+fn octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    let digits = format!("{value:0width$o}");
+    field[..width].copy_from_slice(digits.as_bytes());
+}
+
+impl<W: Write> ArchiveWriter for TarWriter<W> {
+    fn add(&mut self, name: &str, entry: &DirEntry, data: Option<&[u8]>) -> io::Result<()> {
+        let mut header = [0u8; TAR_BLOCK];
+
+        // Names over 100 bytes are split at a '/' between the prefix and the name fields
+        let (prefix, name) = match name.len() {
+            0..=100 => ("", name),
+            _ => name[..name.len() - 1]
+                .char_indices()
+                .filter(|(idx, c)| *c == '/' && *idx <= 155 && name.len() - idx - 1 <= 100)
+                .map(|(idx, _)| (&name[..idx], &name[idx + 1..]))
+                .next()
+                .ok_or_else(|| too_large(&format!("The path {name} is too long for TAR")))?,
+        };
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        let mode = match (data.is_some(), entry.has_attribute(Attribute::ReadOnly)) {
+            (false, _) => 0o755,
+            (true, false) => 0o644,
+            (true, true) => 0o444,
+        };
+        let size = data.map_or(0, |data| data.len() as u64);
+        let mtime = entry
+            .modified()
+            .map_or(0, |time| time.unix_timestamp().max(0) as u64);
+        octal(&mut header[100..108], mode);
+        octal(&mut header[108..116], 0);
+        octal(&mut header[116..124], 0);
+        if size >= 1 << 33 {
+            return Err(too_large("TAR entries are limited to 8 GiB"));
+        }
+        octal(&mut header[124..136], size);
+        octal(&mut header[136..148], mtime);
+        header[156] = if data.is_some() { b'0' } else { b'5' };
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // The checksum is computed with its own field filled with spaces
+        header[148..156].fill(b' ');
+        let checksum: u64 = header.iter().map(|byte| *byte as u64).sum();
+        octal(&mut header[148..155], checksum);
+
+        self.out.write_all(&header)?;
+        if let Some(data) = data {
+            self.out.write_all(data)?;
+            let padding = data.len().next_multiple_of(TAR_BLOCK) - data.len();
+            self.out.write_all(&vec![0; padding])?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.write_all(&[0; 2 * TAR_BLOCK])?;
+        self.out.flush()
+    }
+}
@@ -0,0 +1,96 @@
+//! Export of the file metadata of a volume as Digital Forensics XML (DFXML).
+//!
+//! Each live file and directory is a `fileobject` holding its path, size, first cluster and the
+//! byte runs of its content: the absolute offset in the image and the length of each run of
+//! consecutive clusters. Tools reading DFXML can then access the content of the files directly
+//! from the raw image, without parsing the FAT. The runs of a file stop at its size.
+
+use std::io::Write;
+
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+
+/// Writes the file objects of every live file and directory of a volume as a DFXML document.
+///
+/// # Parameters
+/// - `vol`: The volume to export.
+/// - `out`: The sink the document is written to.
+///
+/// # Returns
+/// - `Ok(usize)`: The number of file objects written.
+/// - `Err(FATError)`: If the directory tree or a cluster chain cannot be read, or writing fails.
+pub fn export_dfxml<W: Write>(vol: &FATVol, mut out: W) -> Result<usize, FATError> {
+    let info = vol.info();
+    let block_size = *info.bytes_per_sector() as u64 * *info.sectors_per_cluster() as u64;
+
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<dfxml version=\"1.0\">")?;
+    writeln!(out, "  <creator>")?;
+    writeln!(out, "    <program>{}</program>", env!("CARGO_PKG_NAME"))?;
+    writeln!(out, "    <version>{}</version>", env!("CARGO_PKG_VERSION"))?;
+    writeln!(out, "  </creator>")?;
+    writeln!(
+        out,
+        "  <volume offset=\"{}\">",
+        vol.sector_offset(vol.start() as u64)
+    )?;
+    writeln!(out, "    <ftype_str>{}</ftype_str>", info.fat_type())?;
+    writeln!(out, "    <block_size>{block_size}</block_size>")?;
+
+    let mut count = 0;
+    for (path, entry) in vol.walk_entries()? {
+        vol.check_cancelled()?;
+        // Volume labels hold no data, but files disguised as labels do
+        if entry.is_volume_id() && entry.cluster_number() == 0 {
+            continue;
+        }
+        let name = path.to_string_lossy().replace('\\', "/");
+
+        writeln!(out, "    <fileobject>")?;
+        writeln!(out, "      <filename>{}</filename>", escape_xml(&name))?;
+        writeln!(
+            out,
+            "      <name_type>{}</name_type>",
+            if entry.is_dir() { "d" } else { "r" }
+        )?;
+        writeln!(out, "      <filesize>{}</filesize>", entry.file_size())?;
+        writeln!(out, "      <alloc>1</alloc>")?;
+        writeln!(out, "      <inode>{}</inode>", entry.cluster_number())?;
+        writeln!(out, "      <byte_runs>")?;
+        let mut file_offset = 0;
+        for (img_offset, len) in vol.file_byte_runs(&entry)? {
+            writeln!(
+                out,
+                "        <byte_run file_offset=\"{file_offset}\" img_offset=\"{img_offset}\" len=\"{len}\"/>"
+            )?;
+            file_offset += len;
+        }
+        writeln!(out, "      </byte_runs>")?;
+        writeln!(out, "    </fileobject>")?;
+        count += 1;
+    }
+
+    writeln!(out, "  </volume>")?;
+    writeln!(out, "</dfxml>")?;
+    out.flush()?;
+
+    Ok(count)
+}
+
+/// Escapes the characters of a text that are markup in XML.
+fn escape_xml(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            // Control characters are not allowed in XML 1.0, even as references
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {
+                format!("\\x{:02x}", c as u32)
+            }
+            c => c.to_string(),
+        })
+        .collect()
+}
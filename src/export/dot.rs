@@ -0,0 +1,226 @@
+//! Graphviz DOT rendering of FAT structures.
+//!
+//! The generated graphs can be turned into images with Graphviz, e.g. `dot -Tsvg chain.dot -o chain.svg`.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+
+/// State of a run of clusters in the allocation map.
+#[derive(PartialEq, Clone, Copy)]
+enum RunKind {
+    Free,
+    Allocated,
+    Bad,
+}
+
+/// A run of consecutive clusters sharing the same state.
+///
+/// Allocated runs are also contiguous parts of a single chain: each cluster points to the next one.
+struct ClusterRun {
+    kind: RunKind,
+    first: u32,
+    last: u32,
+    /// The cluster following the last cluster of the run, if the chain continues elsewhere.
+    next: Option<u32>,
+}
+
+/// Escapes a string so it can be used in a quoted DOT label.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Renders the cluster chain of a file as a DOT graph.
+///
+/// # Parameters
+/// - `vol`: The FAT volume containing the file.
+/// - `file_path`: The path of the file.
+///
+/// # Returns
+/// - `Ok(String)`: The DOT graph, with one node per cluster.
+/// - `Err(FATError)`: If the file cannot be found or its chain cannot be read.
+pub fn chain_to_dot(vol: &FATVol, file_path: &Path) -> Result<String, FATError> {
+    let entry = vol.find_file(file_path)?;
+    let mut out = String::new();
+    writeln!(out, "digraph chain {{")?;
+    writeln!(out, "  rankdir=LR;")?;
+    writeln!(out, "  node [shape=box];")?;
+    writeln!(
+        out,
+        "  file [shape=note, label=\"{}\\n{} bytes\"];",
+        escape(&file_path.display().to_string()),
+        entry.file_size()
+    )?;
+
+    let mut previous = String::from("file");
+    let clusters = match entry.cluster_number() {
+        0 => None,
+        cluster => Some(vol.cluster_chain(cluster)),
+    };
+    for cluster in clusters.into_iter().flatten() {
+        let cluster = cluster?;
+        writeln!(
+            out,
+            "  c{cluster} [label=\"cluster {cluster}\\nsector {}\"];",
+            vol.clus_to_sector(cluster)
+        )?;
+        writeln!(out, "  {previous} -> c{cluster};")?;
+        previous = format!("c{cluster}");
+    }
+    writeln!(out, "  eoc [shape=point];")?;
+    writeln!(out, "  {previous} -> eoc;")?;
+    writeln!(out, "}}")?;
+
+    Ok(out)
+}
+
+/// Renders the allocation map of the volume as a DOT graph.
+///
+/// Consecutive clusters sharing the same state are merged into a single node, and edges link the
+/// fragments of every cluster chain. Fragments starting a file are labelled with the file path.
+///
+/// # Parameters
+/// - `vol`: The FAT volume.
+///
+/// # Returns
+/// - `Ok(String)`: The DOT graph.
+/// - `Err(FATError)`: If the FAT or the directory tree cannot be read.
+pub fn allocation_map_to_dot(vol: &FATVol) -> Result<String, FATError> {
+    let fat = vol.fat_entries()?;
+    let bad_marker = vol.bad_cluster_marker();
+
+    let mut runs: Vec<ClusterRun> = vec![];
+    for (cluster, value) in fat.iter().enumerate().skip(2) {
+        let cluster = cluster as u32;
+        let kind = match *value {
+            0 => RunKind::Free,
+            v if v == bad_marker => RunKind::Bad,
+            _ => RunKind::Allocated,
+        };
+
+        match runs.last_mut() {
+            Some(run)
+                if run.kind == kind
+                    && (kind != RunKind::Allocated || run.next == Some(cluster)) =>
+            {
+                run.last = cluster;
+                run.next = (kind == RunKind::Allocated && !vol.is_eoc(*value)).then_some(*value);
+            }
+            _ => runs.push(ClusterRun {
+                kind,
+                first: cluster,
+                last: cluster,
+                next: (kind == RunKind::Allocated && !vol.is_eoc(*value)).then_some(*value),
+            }),
+        }
+    }
+
+    let file_names: HashMap<u32, String> = vol
+        .walk_files()?
+        .into_iter()
+        .map(|(path, entry)| (entry.cluster_number(), path.display().to_string()))
+        .collect();
+
+    let mut out = String::new();
+    writeln!(out, "digraph allocation {{")?;
+    writeln!(out, "  rankdir=LR;")?;
+    writeln!(out, "  node [shape=box, style=filled];")?;
+
+    for run in &runs {
+        let (color, state) = match run.kind {
+            RunKind::Free => ("white", "free"),
+            RunKind::Allocated => ("lightblue", "allocated"),
+            RunKind::Bad => ("salmon", "bad"),
+        };
+        let mut label = format!("{}-{}\\n{state}", run.first, run.last);
+        if let Some(name) = file_names.get(&run.first) {
+            label = format!("{}\\n{label}", escape(name));
+        }
+        writeln!(
+            out,
+            "  r{} [label=\"{label}\", fillcolor={color}];",
+            run.first
+        )?;
+    }
+
+    // Keep the runs in disk order, then add the chain jumps between fragments
+    for pair in runs.windows(2) {
+        writeln!(
+            out,
+            "  r{} -> r{} [style=invis];",
+            pair[0].first, pair[1].first
+        )?;
+    }
+    for run in runs.iter().filter(|run| run.next.is_some()) {
+        let next = run.next.unwrap_or_default();
+        if let Some(target) = runs.iter().find(|r| r.first <= next && next <= r.last) {
+            writeln!(out, "  r{} -> r{} [color=blue];", run.first, target.first)?;
+        }
+    }
+    writeln!(out, "}}")?;
+
+    Ok(out)
+}
+
+/// Renders the directory tree of the volume as a DOT graph.
+///
+/// # Parameters
+/// - `vol`: The FAT volume.
+///
+/// # Returns
+/// - `Ok(String)`: The DOT graph, with one node per file or directory.
+/// - `Err(FATError)`: If a directory cannot be listed.
+pub fn tree_to_dot(vol: &FATVol) -> Result<String, FATError> {
+    let mut out = String::new();
+    writeln!(out, "digraph tree {{")?;
+    writeln!(out, "  rankdir=LR;")?;
+    writeln!(out, "  root [shape=folder, label=\"/\"];")?;
+    let mut node_cnt = 0;
+    tree_to_dot_rec(vol, vol.root_cluster()?, "root", 0, &mut node_cnt, &mut out)?;
+    writeln!(out, "}}")?;
+
+    Ok(out)
+}
+
+fn tree_to_dot_rec(
+    vol: &FATVol,
+    cluster: u32,
+    parent: &str,
+    depth: usize,
+    node_cnt: &mut usize,
+    out: &mut String,
+) -> Result<(), FATError> {
+    vol.check_depth(depth)?;
+    for entry in vol.list_dir(cluster)? {
+        if entry.is_deleted() || !(entry.is_regular_dir() || entry.is_regular_file()) {
+            continue;
+        }
+
+        *node_cnt += 1;
+        let node = format!("n{node_cnt}");
+        if entry.is_dir() {
+            writeln!(
+                out,
+                "  {node} [shape=folder, label=\"{}\"];",
+                escape(&entry.short_name())
+            )?;
+        } else {
+            writeln!(
+                out,
+                "  {node} [shape=note, label=\"{}\\n{} bytes\"];",
+                escape(&entry.short_name()),
+                entry.file_size()
+            )?;
+        }
+        writeln!(out, "  {parent} -> {node};")?;
+
+        if entry.is_dir() {
+            tree_to_dot_rec(vol, entry.cluster_number(), &node, depth + 1, node_cnt, out)?;
+        }
+    }
+
+    Ok(())
+}
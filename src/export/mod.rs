@@ -0,0 +1,12 @@
+//! Exporters rendering volume structures in external formats.
+//!
+//! This module provides:
+//! - Graphviz DOT rendering of cluster chains, the allocation map and the directory tree
+//! - ZIP and TAR archives of the files of a volume, with a metadata sidecar
+//! - DFXML file objects, locating the content of each file in the image
+//! - Reports laid out by user-supplied templates
+
+pub mod archive;
+pub mod dfxml;
+pub mod dot;
+pub mod template;
@@ -0,0 +1,416 @@
+//! Rendering of reports through user-supplied templates.
+//!
+//! Institutions have their own report format: a header with a logo, the sections in a given
+//! order, some of them left out. A template describes that format once, with the subset of the
+//! Handlebars syntax needed to lay out a report:
+//! - `{{name}}`: Inserts a value, escaped when the report is HTML. `{{{name}}}` inserts it as is.
+//!   Dotted names (`{{volume.label}}`) look up the fields of a value.
+//! - `{{#if name}}...{{else}}...{{/if}}`: Renders the first part if the value is a non-empty text
+//!   or list, the `{{else}}` part otherwise.
+//! - `{{#each name}}...{{/each}}`: Renders the content once per item of a list, the item being
+//!   `{{this}}` and its 0-based index `{{@index}}`. The names not found in the item are looked up
+//!   in the enclosing values.
+//! - `{{! comment}}`: Renders nothing.
+//!
+//! As in Handlebars, a block or comment tag alone on its line doesn't leave a blank line behind.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A value inserted into a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateValue {
+    /// A text, inserted as is.
+    Text(String),
+    /// A list, iterated by `{{#each}}`.
+    List(Vec<TemplateValue>),
+    /// Named fields, in order.
+    Map(Vec<(String, TemplateValue)>),
+}
+
+impl TemplateValue {
+    /// Builds a map from its fields.
+    pub fn map<K: Into<String>>(fields: impl IntoIterator<Item = (K, TemplateValue)>) -> Self {
+        TemplateValue::Map(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.into(), value))
+                .collect(),
+        )
+    }
+
+    /// Returns whether `{{#if}}` renders its first part for the value.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            TemplateValue::Text(text) => !text.is_empty(),
+            TemplateValue::List(items) => !items.is_empty(),
+            TemplateValue::Map(_) => true,
+        }
+    }
+
+    /// Returns a field of a map.
+    fn field(&self, name: &str) -> Option<&TemplateValue> {
+        match self {
+            TemplateValue::Map(fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for TemplateValue {
+    fn from(text: &str) -> Self {
+        TemplateValue::Text(text.to_string())
+    }
+}
+
+impl From<String> for TemplateValue {
+    fn from(text: String) -> Self {
+        TemplateValue::Text(text)
+    }
+}
+
+impl From<usize> for TemplateValue {
+    fn from(value: usize) -> Self {
+        TemplateValue::Text(value.to_string())
+    }
+}
+
+impl<T: Into<TemplateValue>> From<Vec<T>> for TemplateValue {
+    fn from(items: Vec<T>) -> Self {
+        TemplateValue::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// A parsed element of a template.
+#[derive(Debug)]
+enum Node {
+    /// Text copied to the output.
+    Text(String),
+    /// A value, escaped unless `raw`.
+    Value { name: String, raw: bool },
+    /// A `{{#if}}` block.
+    If {
+        name: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    /// A `{{#each}}` block.
+    Each { name: String, body: Vec<Node> },
+}
+
+/// A tag or text of a template, before the blocks are matched.
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    Tag {
+        content: String,
+        raw: bool,
+        line: usize,
+    },
+}
+
+impl Token {
+    /// Checks whether the token is a tag rendering nothing in place: a block or comment tag.
+    fn is_standalone_kind(&self) -> bool {
+        matches!(self, Token::Tag { content, raw: false, .. }
+            if content.starts_with(['#', '/', '!']) || content == "else")
+    }
+}
+
+/// A report template.
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+    /// Whether the values are escaped for HTML.
+    escape_html: bool,
+}
+
+impl Template {
+    /// Parses a template.
+    ///
+    /// # Parameters
+    /// - `text`: The content of the template.
+    /// - `escape_html`: Whether the values inserted with `{{name}}` are escaped for HTML.
+    ///
+    /// # Returns
+    /// - `Ok(Template)`: The template.
+    /// - `Err(String)`: A description of the first syntax error, with its line number.
+    pub fn parse(text: &str, escape_html: bool) -> Result<Self, String> {
+        let mut tokens = tokenize(text)?;
+        strip_standalone_lines(&mut tokens);
+        let mut tokens = tokens.into_iter();
+        let (nodes, end) = parse_nodes(&mut tokens)?;
+        match end {
+            None => Ok(Self { nodes, escape_html }),
+            Some((tag, line)) => Err(format!("line {line}: unexpected {{{{{tag}}}}}")),
+        }
+    }
+
+    /// Reads a template file, its values being escaped if it renders an HTML report.
+    ///
+    /// # Parameters
+    /// - `path`: The path of the file, see [`Template::output_extension`].
+    ///
+    /// # Returns
+    /// - `Ok(Template)`: The template.
+    /// - `Err(io::Error)`: If the file cannot be read, or its syntax is invalid.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let escape_html = matches!(Self::output_extension(path).as_str(), "html" | "htm");
+        Self::parse(&text, escape_html)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Returns the extension of the reports rendered by a template file: the extension of its name
+    /// once a trailing `.hbs` is removed, e.g. `html` for `report.html.hbs`, or `txt` if there is
+    /// none.
+    pub fn output_extension(path: &Path) -> String {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = name.strip_suffix(".hbs").unwrap_or(&name);
+        match Path::new(name).extension() {
+            Some(ext) => ext.to_string_lossy().to_lowercase(),
+            None => String::from("txt"),
+        }
+    }
+
+    /// Renders the template.
+    ///
+    /// # Parameters
+    /// - `context`: The values of the template, usually a [`TemplateValue::Map`]. Unknown names
+    ///   render as empty texts.
+    pub fn render(&self, context: &TemplateValue) -> String {
+        let mut out = String::new();
+        self.render_nodes(&self.nodes, &mut vec![(context, None)], &mut out);
+        out
+    }
+
+    /// Renders nodes, `scopes` holding the enclosing values and their index in their list.
+    fn render_nodes<'a>(
+        &self,
+        nodes: &'a [Node],
+        scopes: &mut Vec<(&'a TemplateValue, Option<usize>)>,
+        out: &mut String,
+    ) where
+        Self: 'a,
+    {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Value { name, raw } => {
+                    let text = match name.as_str() {
+                        "@index" => scopes
+                            .iter()
+                            .rev()
+                            .find_map(|(_, index)| *index)
+                            .map(|index| index.to_string())
+                            .unwrap_or_default(),
+                        _ => match lookup(scopes, name) {
+                            Some(TemplateValue::Text(text)) => text.clone(),
+                            _ => String::new(),
+                        },
+                    };
+                    match self.escape_html && !raw {
+                        true => out.push_str(&escape_html(&text)),
+                        false => out.push_str(&text),
+                    }
+                }
+                Node::If {
+                    name,
+                    then,
+                    otherwise,
+                } => {
+                    let branch = match lookup(scopes, name).is_some_and(TemplateValue::is_truthy) {
+                        true => then,
+                        false => otherwise,
+                    };
+                    self.render_nodes(branch, scopes, out);
+                }
+                Node::Each { name, body } => {
+                    if let Some(TemplateValue::List(items)) = lookup(scopes, name) {
+                        for (index, item) in items.iter().enumerate() {
+                            scopes.push((item, Some(index)));
+                            self.render_nodes(body, scopes, out);
+                            scopes.pop();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Looks up a dotted name in the innermost scope defining its first part.
+fn lookup<'a>(
+    scopes: &[(&'a TemplateValue, Option<usize>)],
+    name: &str,
+) -> Option<&'a TemplateValue> {
+    let mut parts = name.split('.');
+    let first = parts.next()?;
+    let value = match first {
+        "this" => scopes.last().map(|(value, _)| *value),
+        _ => scopes
+            .iter()
+            .rev()
+            .find_map(|(value, _)| value.field(first)),
+    }?;
+    parts.try_fold(value, |value, part| value.field(part))
+}
+
+/// Splits a template into texts and tags.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = text;
+    let mut line = 1;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(Token::Text(rest[..start].to_string()));
+        }
+        line += rest[..start].matches('\n').count();
+        let raw = rest[start..].starts_with("{{{");
+        let (open, close) = match raw {
+            true => ("{{{", "}}}"),
+            false => ("{{", "}}"),
+        };
+        let body = &rest[start + open.len()..];
+        let end = body
+            .find(close)
+            .ok_or_else(|| format!("line {line}: unclosed {open}"))?;
+        tokens.push(Token::Tag {
+            content: body[..end].trim().to_string(),
+            raw,
+            line,
+        });
+        line += body[..end].matches('\n').count();
+        rest = &body[end + close.len()..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest.to_string()));
+    }
+    Ok(tokens)
+}
+
+/// Removes the lines holding only a block or comment tag, and whitespace.
+fn strip_standalone_lines(tokens: &mut [Token]) {
+    // The lines are found before any text is trimmed
+    let standalone: Vec<usize> = (0..tokens.len())
+        .filter(|idx| tokens[*idx].is_standalone_kind())
+        .filter(|idx| match idx.checked_sub(1).map(|prev| &tokens[prev]) {
+            None => true,
+            Some(Token::Text(text)) => {
+                let line_start = text.rfind('\n').map_or(0, |pos| pos + 1);
+                text[line_start..].trim().is_empty() && (line_start > 0 || *idx == 1)
+            }
+            Some(_) => false,
+        })
+        .filter(|idx| match tokens.get(idx + 1) {
+            None => true,
+            Some(Token::Text(text)) => text[..text.find('\n').unwrap_or(text.len())]
+                .trim()
+                .is_empty(),
+            Some(_) => false,
+        })
+        .collect();
+
+    for idx in standalone {
+        if let Some(Token::Text(text)) = idx.checked_sub(1).map(|prev| &mut tokens[prev]) {
+            text.truncate(text.rfind('\n').map_or(0, |pos| pos + 1));
+        }
+        if let Some(Token::Text(text)) = tokens.get_mut(idx + 1) {
+            *text = match text.find('\n') {
+                Some(pos) => text[pos + 1..].to_string(),
+                None => String::new(),
+            };
+        }
+    }
+}
+
+/// A closing or `else` tag ending a sequence of nodes, and its line number.
+type EndTag = (String, usize);
+
+/// Parses nodes up to the end of the template or to the closing or `else` tag of the enclosing
+/// block, returned along with its line number.
+fn parse_nodes(
+    tokens: &mut impl Iterator<Item = Token>,
+) -> Result<(Vec<Node>, Option<EndTag>), String> {
+    let mut nodes = vec![];
+    while let Some(token) = tokens.next() {
+        let (content, raw, line) = match token {
+            Token::Text(text) => {
+                nodes.push(Node::Text(text));
+                continue;
+            }
+            Token::Tag { content, raw, line } => (content, raw, line),
+        };
+        if raw {
+            nodes.push(Node::Value { name: content, raw });
+            continue;
+        }
+        match content.split_once(char::is_whitespace) {
+            _ if content.starts_with('!') => {}
+            _ if content.starts_with('/') || content == "else" => {
+                return Ok((nodes, Some((content, line))));
+            }
+            Some(("#if", name)) => {
+                let name = name.trim().to_string();
+                let (then, end) = parse_nodes(tokens)?;
+                let (otherwise, end) = match end {
+                    Some((tag, _)) if tag == "else" => parse_nodes(tokens)?,
+                    end => (vec![], end),
+                };
+                expect_close(end, "if", line)?;
+                nodes.push(Node::If {
+                    name,
+                    then,
+                    otherwise,
+                });
+            }
+            Some(("#each", name)) => {
+                let name = name.trim().to_string();
+                let (body, end) = parse_nodes(tokens)?;
+                expect_close(end, "each", line)?;
+                nodes.push(Node::Each { name, body });
+            }
+            _ if content.starts_with('#') => {
+                return Err(format!(
+                    "line {line}: unknown block {{{{{content}}}}}, expected #if or #each"
+                ));
+            }
+            _ => nodes.push(Node::Value { name: content, raw }),
+        }
+    }
+    Ok((nodes, None))
+}
+
+/// Checks that a block opened at `line` ends with its closing tag.
+fn expect_close(end: Option<(String, usize)>, block: &str, line: usize) -> Result<(), String> {
+    match end {
+        Some((tag, _)) if tag == format!("/{block}") => Ok(()),
+        Some((tag, end_line)) => Err(format!(
+            "line {end_line}: unexpected {{{{{tag}}}}} in the {{{{#{block}}}}} block opened at line {line}"
+        )),
+        None => Err(format!("line {line}: unclosed {{{{#{block}}}}} block")),
+    }
+}
+
+/// Escapes the characters of a text that are markup in HTML.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
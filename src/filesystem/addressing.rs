@@ -0,0 +1,185 @@
+//! Address conversions of a FAT volume.
+//!
+//! This module gathers the layout math shared by the whole crate: conversions between cluster
+//! numbers, sector numbers, byte offsets, FAT entries and directory entries.
+//!
+//! Sector numbers and byte offsets are absolute, i.e. counted from the start of the disk image.
+
+use getset::Getters;
+
+use super::bpb::ReservedSector;
+use super::dir_entry::DirEntry;
+use super::fat::FATVol;
+
+/// Location of a directory entry: a cluster of a directory and the index of the entry in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Getters)]
+pub struct DirEntryAddress {
+    /// The cluster containing the entry.
+    #[get = "pub"]
+    cluster: u32,
+    /// The index of the entry in the cluster.
+    #[get = "pub"]
+    index: u32,
+}
+
+impl DirEntryAddress {
+    /// Creates the address of the `index`-th entry of a directory cluster.
+    pub fn new(cluster: u32, index: u32) -> Self {
+        Self { cluster, index }
+    }
+}
+
+impl FATVol {
+    /// Converts a cluster number to its corresponding sector number.
+    ///
+    /// # Parameters
+    /// - `cluster`: The cluster number to convert.
+    ///
+    /// # Returns
+    /// - The sector number corresponding to the given cluster.
+    pub fn clus_to_sector(&self, cluster: u32) -> u32 {
+        self.data_start().saturating_add(
+            cluster
+                .saturating_sub(2)
+                .saturating_mul(*self.bpb().sec_per_clus() as u32),
+        )
+    }
+
+    /// Converts a sector number to the cluster containing it.
+    ///
+    /// # Parameters
+    /// - `sector`: The absolute sector number.
+    ///
+    /// # Returns
+    /// - `Some(u32)`: The cluster containing the sector.
+    /// - `None`: If the sector is outside of the data region.
+    pub fn sector_to_cluster(&self, sector: u64) -> Option<u32> {
+        let data_start = self.data_start() as u64;
+        if sector < data_start || sector >= self.data_end() as u64 {
+            return None;
+        }
+
+        Some(((sector - data_start) / *self.bpb().sec_per_clus() as u64) as u32 + 2)
+    }
+
+    /// Converts a sector number to the absolute byte offset of the sector.
+    pub fn sector_offset(&self, sector: u64) -> u64 {
+        sector * *self.bpb().bytes_per_sec() as u64
+    }
+
+    /// Converts a sector number to the absolute byte offset of the sector, or `None` if the
+    /// offset overflows, e.g. for a sector given by the user.
+    pub fn checked_sector_offset(&self, sector: u64) -> Option<u64> {
+        sector.checked_mul(*self.bpb().bytes_per_sec() as u64)
+    }
+
+    /// Converts an absolute byte offset to the sector containing it.
+    pub fn offset_to_sector(&self, offset: u64) -> u64 {
+        offset
+            .checked_div(*self.bpb().bytes_per_sec() as u64)
+            .unwrap_or_default()
+    }
+
+    /// Converts a cluster number to the absolute byte offset of the cluster on disk.
+    pub fn cluster_offset(&self, cluster: u32) -> u64 {
+        self.sector_offset(self.clus_to_sector(cluster) as u64)
+    }
+
+    /// Converts an absolute byte offset to the cluster containing it.
+    ///
+    /// # Returns
+    /// - `Some(u32)`: The cluster containing the offset.
+    /// - `None`: If the offset is outside of the data region.
+    pub fn offset_to_cluster(&self, offset: u64) -> Option<u32> {
+        self.sector_to_cluster(self.offset_to_sector(offset))
+    }
+
+    /// Returns the absolute byte offset of the FAT entry of a cluster.
+    ///
+    /// # Parameters
+    /// - `cluster`: The cluster number.
+    /// - `fat_nb`: The index of the FAT, starting from 0.
+    ///
+    /// # Returns
+    /// - The offset of the first byte of the entry.
+    pub fn fat_entry_offset(&self, cluster: u32, fat_nb: u8) -> u64 {
+        let fat_start = self.fat_start() as u64 + fat_nb as u64 * self.bpb().fat_sz() as u64;
+        self.sector_offset(fat_start) + cluster as u64 * self.fat_entry_bit_sz() as u64 / 8
+    }
+
+    /// Converts the address of a directory entry to its absolute byte offset.
+    ///
+    /// # Returns
+    /// - `Some(u64)`: The offset of the entry.
+    /// - `None`: If the cluster isn't part of the data region or the index exceeds the cluster.
+    ///   Cluster 0 addresses the fixed root directory region of FAT12/16 volumes.
+    pub fn dir_entry_offset(&self, address: DirEntryAddress) -> Option<u64> {
+        if let (0, Some((root_offset, root_len))) = (address.cluster, self.root_dir_extent()) {
+            return (address.index as u64 * DirEntry::SIZE < root_len)
+                .then(|| root_offset + address.index as u64 * DirEntry::SIZE);
+        }
+        let entries_per_cluster = self.cluster_size() as u64 / DirEntry::SIZE;
+        if !self.is_valid_cluster(address.cluster) || address.index as u64 >= entries_per_cluster {
+            return None;
+        }
+
+        Some(self.cluster_offset(address.cluster) + address.index as u64 * DirEntry::SIZE)
+    }
+
+    /// Names the structure of the volume holding an absolute byte offset, e.g. to report what a
+    /// write would change.
+    ///
+    /// # Returns
+    /// - `Some(String)`: The structure: a sector of the reserved region, the entry of a FAT, the
+    ///   root directory region, a cluster or the volume slack.
+    /// - `None`: If the offset is outside of the volume.
+    pub fn describe_offset(&self, offset: u64) -> Option<String> {
+        let (slack_offset, slack_len) = self.volume_slack_extent();
+        if offset < self.sector_offset(self.start() as u64) || offset >= slack_offset + slack_len {
+            return None;
+        }
+
+        let sector = self.offset_to_sector(offset);
+        let fat_start = self.fat_start() as u64;
+        let fat_sz = self.bpb().fat_sz() as u64;
+        let fats_end = fat_start + *self.bpb().num_fat() as u64 * fat_sz;
+        let description = if sector < fat_start {
+            let reserved = (sector - self.start() as u64) as u16;
+            match self.bpb().reserved_sector_kind(reserved) {
+                Some(ReservedSector::Unused) | None => format!("reserved sector {reserved}"),
+                Some(kind) => kind.to_string(),
+            }
+        } else if sector < fats_end {
+            let fat_nb = (sector - fat_start) / fat_sz;
+            let in_fat = offset - self.sector_offset(fat_start + fat_nb * fat_sz);
+            let cluster = in_fat * 8 / self.fat_entry_bit_sz() as u64;
+            format!("FAT #{fat_nb}, entry of cluster {cluster}")
+        } else if offset >= slack_offset {
+            String::from("volume slack")
+        } else {
+            match self.offset_to_cluster(offset) {
+                Some(cluster) => format!("cluster {cluster}"),
+                None => String::from("root directory region"),
+            }
+        };
+        Some(description)
+    }
+
+    /// Converts an absolute byte offset to the address of the directory entry containing it.
+    ///
+    /// # Returns
+    /// - `Some(DirEntryAddress)`: The address of the entry, in cluster 0 for the fixed root
+    ///   directory region of FAT12/16 volumes.
+    /// - `None`: If the offset is outside of the data region and of the root directory region.
+    pub fn offset_to_dir_entry(&self, offset: u64) -> Option<DirEntryAddress> {
+        if let Some((root_offset, root_len)) = self.root_dir_extent()
+            && (root_offset..root_offset + root_len).contains(&offset)
+        {
+            let index = (offset - root_offset) / DirEntry::SIZE;
+            return Some(DirEntryAddress::new(0, index as u32));
+        }
+        let cluster = self.offset_to_cluster(offset)?;
+        let index = (offset - self.cluster_offset(cluster)) / DirEntry::SIZE;
+        Some(DirEntryAddress::new(cluster, index as u32))
+    }
+}
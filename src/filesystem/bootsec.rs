@@ -0,0 +1,83 @@
+//! Export and import of the boot sector of a volume.
+//!
+//! The boot sector holds the BPB, from which the whole layout of the volume is derived. Exporting
+//! it allows to analyze or edit it offline; importing it back rewrites sector 0 of the volume and,
+//! unless told otherwise, the backup copy declared by its `bk_boot_sec` field. Leaving the backup
+//! untouched is useful to prepare labs where the two copies disagree.
+
+use std::io::{self, Write};
+
+use super::bpb::Bpb;
+use super::fat::FATVol;
+use super::fat_error::FATError;
+use crate::utils::write_at;
+
+impl FATVol {
+    /// Copies the boot sector of the volume.
+    ///
+    /// # Parameters
+    /// - `out`: The sink the sector is written to.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of bytes written, one sector.
+    /// - `Err(FATError)`: If the sector cannot be read or written.
+    pub fn export_boot_sector<W: Write>(&self, out: &mut W) -> Result<u64, FATError> {
+        let sector = self.read_sectors(self.start() as u64, 1)?;
+        out.write_all(&sector)?;
+        out.flush()?;
+        Ok(sector.len() as u64)
+    }
+
+    /// Overwrites the boot sector of the volume, then reloads the BPB.
+    ///
+    /// The sector must hold a valid FAT32 BPB with the sector size of the volume, so that the
+    /// volume can still be read once the sector is written.
+    ///
+    /// # Parameters
+    /// - `data`: The new boot sector, one sector long.
+    /// - `sync_backup`: Whether to write the sector to the backup boot sector declared by the
+    ///   imported BPB as well.
+    ///
+    /// # Returns
+    /// - `Ok(Option<u16>)`: The backup boot sector written, relative to the start of the volume,
+    ///   if any.
+    /// - `Err(FATError::InvalidBootSector)`: If the sector size differs from the volume's.
+    /// - `Err(FATError)`: If the BPB is invalid, or the image cannot be written.
+    pub fn import_boot_sector(
+        &mut self,
+        data: &[u8],
+        sync_backup: bool,
+    ) -> Result<Option<u16>, FATError> {
+        let bytes_per_sec = *self.bpb.bytes_per_sec() as usize;
+        if data.len() != bytes_per_sec {
+            return Err(FATError::InvalidBootSector(format!(
+                "{} bytes, expected one sector of {bytes_per_sec} bytes",
+                data.len()
+            )));
+        }
+        let bpb = Bpb::from(&mut io::Cursor::new(data), 0, true, bytes_per_sec)?;
+        if *bpb.bytes_per_sec() as usize != bytes_per_sec {
+            return Err(FATError::InvalidBootSector(format!(
+                "{} bytes per sector, the volume has {bytes_per_sec}",
+                bpb.bytes_per_sec()
+            )));
+        }
+
+        let mut sectors = vec![0];
+        let backup = bpb.backup_boot_sector().filter(|_| sync_backup);
+        sectors.extend(backup);
+
+        let mut disk_file = self.image.open_for_write()?;
+        for sector in sectors {
+            let offset = self.sector_offset(self.start() as u64 + sector as u64);
+            write_at(&mut disk_file, offset, data)?;
+            self.invalidate_cache(offset, bytes_per_sec as u64);
+        }
+
+        // A dry run leaves the volume as it is on disk
+        if !self.image.is_dry_run() {
+            self.bpb = bpb;
+        }
+        Ok(backup)
+    }
+}
@@ -15,6 +15,34 @@ use super::fat_error::FATError;
 use super::fat_type::FATType;
 use crate::utils;
 
+/// The structure held by a sector of the reserved region of a volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservedSector {
+    /// The boot sector, holding the BPB.
+    Boot,
+    /// The FSINFO sector of a FAT32 volume.
+    FsInfo,
+    /// The copy of the boot sector of a FAT32 volume.
+    BackupBoot,
+    /// The copy of the FSINFO sector, following the backup boot sector as FSINFO follows the boot
+    /// sector.
+    BackupFsInfo,
+    /// A sector no structure uses, zeroed on a genuine volume but for a boot signature.
+    Unused,
+}
+
+impl fmt::Display for ReservedSector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReservedSector::Boot => write!(f, "boot sector"),
+            ReservedSector::FsInfo => write!(f, "FSINFO sector"),
+            ReservedSector::BackupBoot => write!(f, "backup boot sector"),
+            ReservedSector::BackupFsInfo => write!(f, "backup FSINFO sector"),
+            ReservedSector::Unused => write!(f, "reserved sector"),
+        }
+    }
+}
+
 /// BIOS Parameter Block structure for FAT filesystems.
 ///
 /// The Bpb contains essential information about the filesystem layout and properties.
@@ -25,6 +53,7 @@ pub struct Bpb {
     /// Jump instruction to boot code (must be 0xEB ?? 0x90 or 0xE9 ?? ??)
     jmp: [u8; 3],
     /// OEM identifier (e.g., "MSWIN4.1")
+    #[get = "pub(super)"]
     oem_name: [u8; 8],
     /// Number of bytes per sector (512, 1024, 2048, or 4096)
     #[get = "pub(super)"]
@@ -60,6 +89,7 @@ pub struct Bpb {
     /// Sectors per FAT
     fat_sz_32: u32,
     /// FAT flags (mirroring, active FAT)
+    #[get = "pub(super)"]
     ext_flags: u16,
     /// Filesystem version (should be 0:0)
     fs_ver: u16,
@@ -67,8 +97,10 @@ pub struct Bpb {
     #[get = "pub(super)"]
     root_clus: u32,
     /// Sector number of FSINFO structure
+    #[get = "pub(super)"]
     fs_info: u16,
     /// Sector number of backup boot sector
+    #[get = "pub(super)"]
     bk_boot_sec: u16,
     /// Reserved for future expansion
     reserved: [u8; 12],
@@ -79,8 +111,10 @@ pub struct Bpb {
     /// Extended boot signature (0x29)
     boot_sig: u8,
     /// Volume serial number
+    #[get = "pub(super)"]
     vol_id: u32,
     /// Volume label (11 bytes)
+    #[get = "pub(super)"]
     vol_lab: [u8; 11],
     /// Filesystem type label ("FAT32   ")
     fil_sys_type: [u8; 8],
@@ -117,34 +151,256 @@ impl Bpb {
         let mut buf = vec![0; sector_size];
         utils::read_sector(file, sector.into(), sector_size, &mut buf)?;
 
-        let mut reader = io::Cursor::new(buf);
-        let bpb: Bpb = reader.read_be()?;
+        let mut reader = io::Cursor::new(&buf);
+        let mut bpb: Bpb = reader.read_be()?;
+        if bpb.has_fat16_layout() {
+            bpb.read_fat16_fields(&buf);
+        }
 
         if validate { bpb.validate() } else { Ok(bpb) }
     }
 
+    /// Checks whether the boot sector follows the layout of FAT12/16 volumes, whose extended
+    /// fields start right after the common ones, where FAT32 stores its specific fields.
+    fn has_fat16_layout(&self) -> bool {
+        self.fat_sz_16 != 0 && self.fat_type() != FATType::FAT32
+    }
+
+    /// Reads the extended fields of a FAT12/16 boot sector, parsed at their FAT32 offsets, and
+    /// clears the FAT32-specific fields, absent from such volumes.
+    ///
+    /// # Parameters
+    /// - `sector`: The content of the boot sector.
+    fn read_fat16_fields(&mut self, sector: &[u8]) {
+        self.fat_sz_32 = 0;
+        self.ext_flags = 0;
+        self.fs_ver = 0;
+        self.root_clus = 0;
+        self.fs_info = 0;
+        self.bk_boot_sec = 0;
+        self.reserved = [0; 12];
+
+        self.drv_num = sector[36];
+        self.reserved_1 = sector[37];
+        self.boot_sig = sector[38];
+        self.vol_id = utils::u32_at(sector, 39);
+        self.vol_lab.copy_from_slice(&sector[43..54]);
+        self.fil_sys_type.copy_from_slice(&sector[54..62]);
+        self.boot_code = sector[62..510].to_vec();
+    }
+
     /// Determines the number of clusters in the data section.
     ///
     /// # Returns
-    /// - The number of data clusters.
+    /// - The number of data clusters. A corrupt Bpb (null sector or cluster size, metadata larger
+    ///   than the volume) yields 0 clusters.
     pub fn cluster_count(&self) -> u32 {
-        let root_dir_sectors = (self.root_ent_cnt * 32).div_ceil(self.bytes_per_sec) as u32;
+        if self.bytes_per_sec == 0 || self.sec_per_clus == 0 {
+            return 0;
+        }
+
+        let root_dir_sectors = (self.root_ent_cnt as u64 * 32).div_ceil(self.bytes_per_sec as u64);
 
         let fat_sz = if self.fat_sz_16 > 0 {
-            self.fat_sz_16 as u32
+            self.fat_sz_16 as u64
         } else {
-            self.fat_sz_32
+            self.fat_sz_32 as u64
         };
 
         let tot_sec = if self.tot_sec_16 != 0 {
-            self.tot_sec_16 as u32
+            self.tot_sec_16 as u64
         } else {
-            self.tot_sec_32
+            self.tot_sec_32 as u64
         };
 
-        let data_sec = tot_sec
-            - (self.rsvd_sec_cnt as u32 + (self.num_fat as u32 * fat_sz) + root_dir_sectors);
-        data_sec / self.sec_per_clus as u32
+        let data_sec = tot_sec.saturating_sub(
+            self.rsvd_sec_cnt as u64 + (self.num_fat as u64 * fat_sz) + root_dir_sectors,
+        );
+        (data_sec / self.sec_per_clus as u64) as u32
+    }
+
+    /// Determines the number of clusters whose entry fits in a FAT, clusters 0 and 1 excluded.
+    ///
+    /// # Returns
+    /// - The number of clusters a FAT can map. A FAT larger than needed maps more clusters than
+    ///   the data region holds.
+    pub fn fat_capacity(&self) -> u32 {
+        let entry_bits = match self.fat_type() {
+            FATType::FAT12 => 12,
+            FATType::FAT16 => 16,
+            FATType::FAT32 => 32,
+        };
+        let entries = self.fat_sz() as u64 * self.bytes_per_sec as u64 * 8 / entry_bits;
+        entries.saturating_sub(2).min(u32::MAX as u64) as u32
+    }
+
+    /// Returns true if every FAT copy is kept up to date.
+    ///
+    /// FAT32 volumes may disable mirroring in `ext_flags`, the other FAT types always mirror.
+    pub fn is_mirrored(&self) -> bool {
+        self.fat_type() != FATType::FAT32 || self.ext_flags & 0x80 == 0
+    }
+
+    /// Returns the index of the FAT read and written by the volume.
+    ///
+    /// # Returns
+    /// - The active FAT declared by `ext_flags` when mirroring is disabled, FAT #0 otherwise, or if
+    ///   the declared FAT doesn't exist.
+    pub fn active_fat(&self) -> u8 {
+        let declared = (self.ext_flags & 0x0F) as u8;
+        match !self.is_mirrored() && declared < self.num_fat {
+            true => declared,
+            false => 0,
+        }
+    }
+
+    /// Determines the number of clusters of the data region that can be used.
+    ///
+    /// # Returns
+    /// - The number of data clusters, bounded by the number of clusters the FAT can map: the
+    ///   clusters past the end of an undersized FAT have no entry and are never allocated.
+    pub fn usable_cluster_count(&self) -> u32 {
+        self.cluster_count().min(self.fat_capacity())
+    }
+
+    /// Returns the sector of the FSINFO structure, relative to the start of the volume.
+    ///
+    /// # Returns
+    /// - `Some(u16)`: The sector, within the reserved region.
+    /// - `None`: If the BPB declares no FSINFO structure (0 or 0xFFFF), or points outside of the
+    ///   reserved region.
+    pub(super) fn fs_info_sector(&self) -> Option<u16> {
+        self.reserved_sector(self.fs_info)
+    }
+
+    /// Returns the sector of the backup boot sector, relative to the start of the volume.
+    ///
+    /// # Returns
+    /// - `Some(u16)`: The sector, within the reserved region.
+    /// - `None`: If the BPB declares no backup (0 or 0xFFFF), or points outside of the reserved
+    ///   region.
+    pub(super) fn backup_boot_sector(&self) -> Option<u16> {
+        self.reserved_sector(self.bk_boot_sec)
+    }
+
+    /// Returns the sector of the backup FSINFO structure, relative to the start of the volume.
+    ///
+    /// The backup boot record copies the sectors of the boot record in order, so the backup FSINFO
+    /// sits as far from the backup boot sector as FSINFO from the boot sector.
+    ///
+    /// # Returns
+    /// - `Some(u16)`: The sector, within the reserved region.
+    /// - `None`: If the BPB declares no FSINFO structure or no backup, or the copy would lie
+    ///   outside of the reserved region.
+    pub(super) fn backup_fs_info_sector(&self) -> Option<u16> {
+        let backup = self
+            .backup_boot_sector()?
+            .checked_add(self.fs_info_sector()?)?;
+        self.reserved_sector(backup)
+    }
+
+    /// Classifies a sector of the reserved region.
+    ///
+    /// # Parameters
+    /// - `sector`: The sector, relative to the start of the volume.
+    ///
+    /// # Returns
+    /// - `Some(ReservedSector)`: The structure held by the sector.
+    /// - `None`: If the sector lies past the reserved region.
+    pub(super) fn reserved_sector_kind(&self, sector: u16) -> Option<ReservedSector> {
+        match sector {
+            _ if sector >= self.rsvd_sec_cnt => None,
+            0 => Some(ReservedSector::Boot),
+            _ if self.fs_info_sector() == Some(sector) => Some(ReservedSector::FsInfo),
+            _ if self.backup_boot_sector() == Some(sector) => Some(ReservedSector::BackupBoot),
+            _ if self.backup_fs_info_sector() == Some(sector) => Some(ReservedSector::BackupFsInfo),
+            _ => Some(ReservedSector::Unused),
+        }
+    }
+
+    /// Returns a sector number if it addresses a sector of the reserved region after the boot
+    /// sector.
+    fn reserved_sector(&self, sector: u16) -> Option<u16> {
+        match sector {
+            0 | 0xFFFF => None,
+            sector if sector >= self.rsvd_sec_cnt => None,
+            sector => Some(sector),
+        }
+    }
+
+    /// Lists the fields of a FAT32 BPB that depart from the values set by formatting tools.
+    ///
+    /// Such layouts are legal as long as they are self-consistent, but seldom produced by
+    /// formatting tools: they are often crafted to confuse analysis tools.
+    ///
+    /// # Returns
+    /// - One description per unusual field, empty for a common layout or a non-FAT32 volume.
+    pub fn layout_oddities(&self) -> Vec<String> {
+        let mut oddities = vec![];
+        if self.fat_type() != FATType::FAT32 {
+            return oddities;
+        }
+
+        if self.root_clus != 2 {
+            oddities.push(format!(
+                "The root directory starts at cluster {} instead of cluster 2.",
+                self.root_clus
+            ));
+        }
+        if self.rsvd_sec_cnt != 32 {
+            oddities.push(format!(
+                "{} reserved sectors instead of 32.",
+                self.rsvd_sec_cnt
+            ));
+        }
+        match self.num_fat {
+            1 => oddities.push(String::from("A single FAT, without a backup copy.")),
+            2 => {}
+            num_fat => oddities.push(format!("{num_fat} FATs instead of 2.")),
+        }
+        let declared = (self.ext_flags & 0x0F) as u8;
+        if !self.is_mirrored() && declared >= self.num_fat {
+            oddities.push(format!(
+                "Mirroring is disabled with the active FAT #{declared}, which doesn't exist: FAT #0 is used."
+            ));
+        }
+
+        let (capacity, clusters) = (self.fat_capacity(), self.cluster_count());
+        if capacity < clusters {
+            oddities.push(format!(
+                "The FATs map {capacity} clusters, the last {} clusters of the data region are unusable.",
+                clusters - capacity
+            ));
+        } else if capacity / 2 > clusters {
+            oddities.push(format!(
+                "The FATs map {capacity} clusters, more than twice the {clusters} clusters of the data region."
+            ));
+        }
+
+        for (name, sector, usual) in [
+            ("FSINFO", self.fs_info, 1),
+            ("backup boot", self.bk_boot_sec, 6),
+        ] {
+            match sector {
+                0 | 0xFFFF => oddities.push(format!("No {name}.")),
+                sector if sector >= self.rsvd_sec_cnt => oddities.push(format!(
+                    "The {name} sector {sector} is outside of the reserved region, it is ignored."
+                )),
+                sector if sector != usual => oddities.push(format!(
+                    "The {name} is at sector {sector} instead of sector {usual}."
+                )),
+                _ => {}
+            }
+        }
+
+        oddities
+    }
+
+    /// Returns the volume label, without its padding.
+    pub(super) fn label(&self) -> String {
+        String::from_utf8_lossy(&self.vol_lab)
+            .trim_end()
+            .to_string()
     }
 
     pub fn fat_sz(&self) -> u32 {
@@ -186,7 +442,7 @@ impl Bpb {
         }
     }
 
-    /// Validates the Bpb structure according to FAT32 specification requirements.
+    /// Validates the Bpb structure according to the FAT specification requirements.
     ///
     /// # Returns
     /// - `Ok(Self)`: If all validation checks pass
@@ -198,7 +454,8 @@ impl Bpb {
     /// - `FATError::InvalidSecPerClus`: If sectors per cluster is not a valid value
     /// - `FATError::InvalidClusSz`: If cluster size exceeds 32 KiB
     /// - `FATError::InvalidSignature`: If boot sector signature is not 0x55AA
-    /// - `FATError::UnsupportedFATType`: If filesystem is not FAT32
+    /// - Any error of the checks specific to the FAT type, see `validate_fat12_16` and
+    ///   `validate_fat32`
     fn validate(self) -> Result<Self, FATError> {
         // General verification
         if !((self.jmp[0] == 0xEB && self.jmp[2] == 0x90) || self.jmp[0] == 0xE9) {
@@ -233,12 +490,73 @@ impl Bpb {
         }
 
         // Specific verification depending on the type of FAT
-        let fat_type = self.fat_type();
-        if fat_type == FATType::FAT32 {
-            self.validate_fat32()
-        } else {
-            Err(FATError::UnsupportedFATType(fat_type.to_string()))
+        match self.fat_type() {
+            FATType::FAT32 => self.validate_fat32(),
+            FATType::FAT12 | FATType::FAT16 => self.validate_fat12_16(),
+        }
+    }
+
+    /// Performs the validation checks of FAT12 and FAT16 volumes, which share their layout.
+    ///
+    /// # Returns
+    /// - `Ok(Self)`: If all FAT12/16-specific validation checks pass
+    /// - `Err(FATError)`: If any validation check fails
+    ///
+    /// # Errors
+    /// - `FATError::InvalidRsvdSecCnt`: If reserved sector count is 0
+    /// - `FATError::InvalidNumFat`: If number of FATs is 0
+    /// - `FATError::InvalidRootEntCnt`: If the root directory has no entry
+    /// - `FATError::InvalidTotSec`: If both total sector fields are 0
+    /// - `FATError::InvalidFatSz`: If the FAT size is 0, or the FATs don't fit in the volume
+    fn validate_fat12_16(self) -> Result<Self, FATError> {
+        assert!(self.fat_type() != FATType::FAT32);
+
+        if self.rsvd_sec_cnt == 0 {
+            return Err(FATError::InvalidRsvdSecCnt(self.rsvd_sec_cnt));
+        }
+
+        if self.num_fat == 0 {
+            return Err(FATError::InvalidNumFat(self.num_fat));
+        }
+
+        // The root directory is a fixed region, sized by its count of entries
+        if self.root_ent_cnt == 0 {
+            return Err(FATError::InvalidRootEntCnt(self.root_ent_cnt));
         }
+
+        if self.tot_sec() == 0 {
+            return Err(FATError::InvalidTotSec(format!(
+                "BPB_TotSec16 or BPB_TotSec32 should be greater than 0 for a {} volume.",
+                self.fat_type()
+            )));
+        }
+
+        if self.fat_sz_16 == 0 {
+            return Err(FATError::InvalidFatSz(format!(
+                "BPB_FATSz16 should be greater than 0 for a {} volume.",
+                self.fat_type()
+            )));
+        }
+
+        // The reserved region, the FATs and the root directory must fit in the volume
+        let root_dir_sectors = (self.root_ent_cnt as u64 * 32).div_ceil(self.bytes_per_sec as u64);
+        if self.rsvd_sec_cnt as u64 + self.num_fat as u64 * self.fat_sz_16 as u64 + root_dir_sectors
+            >= self.tot_sec() as u64
+        {
+            return Err(FATError::InvalidFatSz(String::from(
+                "The reserved sectors, the FATs and the root directory exceed the size of the volume.",
+            )));
+        }
+
+        if self.fat_capacity() < self.cluster_count() {
+            return Err(FATError::InvalidFatSz(format!(
+                "The FATs map {} clusters, fewer than the {} clusters of the volume.",
+                self.fat_capacity(),
+                self.cluster_count()
+            )));
+        }
+
+        Ok(self)
     }
 
     /// Performs FAT32-specific validation checks.
@@ -253,7 +571,7 @@ impl Bpb {
     /// - `FATError::InvalidRootEntCnt`: If root directory entries is not 0
     /// - `FATError::InvalidTotSec`: If total sector fields are invalid for FAT32
     /// - `FATError::InvalidFatSz`: If FAT size fields are invalid for FAT32
-    /// - `FATError::InvalidRootClus`: If root directory cluster is less than 2
+    /// - `FATError::InvalidRootClus`: If root directory cluster is not a cluster of the data region
     fn validate_fat32(self) -> Result<Self, FATError> {
         assert!(self.fat_type() == FATType::FAT32);
 
@@ -293,10 +611,29 @@ impl Bpb {
             )));
         }
 
-        if self.root_clus < 2 {
+        // Any cluster can hold the root directory, not only the first one
+        if self.root_clus < 2 || self.root_clus as u64 >= self.cluster_count() as u64 + 2 {
             return Err(FATError::InvalidRootClus(self.root_clus));
         }
 
+        // The reserved region and the FATs must fit in the volume
+        if self.rsvd_sec_cnt as u64 + self.num_fat as u64 * self.fat_sz_32 as u64
+            >= self.tot_sec_32 as u64
+        {
+            return Err(FATError::InvalidFatSz(String::from(
+                "The reserved sectors and the FATs exceed the size of the volume.",
+            )));
+        }
+
+        // Larger FATs are legal, smaller ones leave clusters without an entry
+        if self.fat_capacity() < self.cluster_count() {
+            return Err(FATError::InvalidFatSz(format!(
+                "The FATs map {} clusters, fewer than the {} clusters of the volume.",
+                self.fat_capacity(),
+                self.cluster_count()
+            )));
+        }
+
         Ok(self)
     }
 }
@@ -329,13 +666,16 @@ impl fmt::Display for Bpb {
         field!("num_heds", self.num_heds, 2);
         field!("hidd_sec", self.hidd_sec, 4);
         field!("tot_sec_32", self.tot_sec_32, 4);
-        field!("fat_sz_32", self.fat_sz_32, 4);
-        field!("ext_flags", self.ext_flags, 2);
-        field!("fs_ver", self.fs_ver, 2);
-        field!("root_clus", self.root_clus, 4);
-        field!("fs_info", self.fs_info, 2);
-        field!("bk_boot_sec", self.bk_boot_sec, 2);
-        field!("reserved", format!("{:02X?}", &self.reserved[..]), 12);
+        // The extended fields of FAT12/16 volumes directly follow the common ones
+        if !self.has_fat16_layout() {
+            field!("fat_sz_32", self.fat_sz_32, 4);
+            field!("ext_flags", self.ext_flags, 2);
+            field!("fs_ver", self.fs_ver, 2);
+            field!("root_clus", self.root_clus, 4);
+            field!("fs_info", self.fs_info, 2);
+            field!("bk_boot_sec", self.bk_boot_sec, 2);
+            field!("reserved", format!("{:02X?}", &self.reserved[..]), 12);
+        }
         field!("drv_num", format!("0x{:X}", self.drv_num), 1);
         field!("reserved_1", self.reserved_1, 1);
         field!("boot_sig", format!("0x{:X}", self.boot_sig), 1);
@@ -0,0 +1,72 @@
+//! Lazy traversal of FAT cluster chains.
+
+use super::fat::FATVol;
+use super::fat_error::FATError;
+
+/// Iterator over the clusters of a chain, reading the FAT on demand.
+///
+/// Each item is either the next cluster of the chain or the error that interrupted the
+/// traversal, after which the iterator is exhausted. Loops are detected without remembering the
+/// visited clusters: a chain can't be longer than the number of clusters of the volume.
+pub struct ClusterIter<'a> {
+    vol: &'a FATVol,
+    /// The last cluster yielded, or the first cluster of the chain before the first call.
+    cluster: Option<u32>,
+    started: bool,
+    len: u32,
+}
+
+impl<'a> ClusterIter<'a> {
+    pub(super) fn new(vol: &'a FATVol, first_cluster: u32) -> Self {
+        Self {
+            vol,
+            cluster: Some(first_cluster),
+            started: false,
+            len: 0,
+        }
+    }
+
+    fn fail(&mut self, err: FATError) -> Option<Result<u32, FATError>> {
+        self.cluster = None;
+        Some(Err(err))
+    }
+}
+
+impl Iterator for ClusterIter<'_> {
+    type Item = Result<u32, FATError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut cluster = self.cluster?;
+        if self.started {
+            cluster = match self.vol.get_next_cluster(cluster) {
+                Ok(next) => next,
+                Err(err) => return self.fail(err),
+            };
+        } else if cluster < 2 {
+            return self.fail(FATError::InvalidClusterError(cluster));
+        }
+        self.started = true;
+
+        if self.vol.is_eoc(cluster) {
+            self.cluster = None;
+            return None;
+        }
+        if !self.vol.is_valid_cluster(cluster) {
+            return self.fail(FATError::ClusterOutOfRange(cluster));
+        }
+        self.len += 1;
+        if self.len > self.vol.cluster_count() {
+            return self.fail(FATError::ChainLoop(cluster));
+        }
+        let max_chain_len = self.vol.limits().max_chain_len;
+        if self.len > max_chain_len {
+            return self.fail(FATError::LimitExceeded {
+                what: "clusters in a chain",
+                max: max_chain_len as u64,
+            });
+        }
+
+        self.cluster = Some(cluster);
+        Some(Ok(cluster))
+    }
+}
@@ -0,0 +1,142 @@
+//! Classification of the clusters of a volume by region class.
+//!
+//! Carving, wiping, string extraction and statistics all start by selecting the clusters of a
+//! class, e.g. the free ones. [`FATVol::clusters`] is the single place deciding which cluster
+//! belongs to which class.
+
+use getset::Getters;
+use std::vec;
+
+use super::fat::FATVol;
+use super::fat_error::FATError;
+
+/// A class of clusters of the data region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusterKind {
+    /// Clusters part of a chain, whether a file references them or not.
+    Allocated,
+    /// Clusters with a null FAT entry.
+    Free,
+    /// Clusters marked as bad in the FAT.
+    Bad,
+    /// Clusters of a file holding slack space, past the end of its content.
+    Slack,
+}
+
+/// A cluster of a class, with the byte range of the class within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Getters)]
+pub struct ClusterRegion {
+    /// The cluster number.
+    #[get = "pub"]
+    cluster: u32,
+    /// The absolute byte offset of the range: the start of the cluster, or the end of the file
+    /// content for slack.
+    #[get = "pub"]
+    offset: u64,
+    /// The length of the range in bytes: the cluster size, or the slack size for slack.
+    #[get = "pub"]
+    len: u64,
+}
+
+/// Iterator over the clusters of a class, in cluster order.
+pub struct ClusterRegions<'a> {
+    vol: &'a FATVol,
+    source: Source,
+}
+
+/// Where the clusters of a class come from.
+enum Source {
+    /// The entries of the FAT, filtered by a predicate on their value.
+    Fat {
+        entries: vec::IntoIter<u32>,
+        cluster: u32,
+        keep: fn(&FATVol, u32) -> bool,
+    },
+    /// Regions computed upfront.
+    Regions(vec::IntoIter<ClusterRegion>),
+}
+
+impl Iterator for ClusterRegions<'_> {
+    type Item = ClusterRegion;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.source {
+            Source::Fat {
+                entries,
+                cluster,
+                keep,
+            } => loop {
+                let value = entries.next()?;
+                let current = *cluster;
+                *cluster += 1;
+                if keep(self.vol, value) {
+                    return Some(ClusterRegion {
+                        cluster: current,
+                        offset: self.vol.cluster_offset(current),
+                        len: self.vol.cluster_size() as u64,
+                    });
+                }
+            },
+            Source::Regions(regions) => regions.next(),
+        }
+    }
+}
+
+impl FATVol {
+    /// Lists the clusters of a class.
+    ///
+    /// The FAT is read once, upfront. Slack clusters are found by walking the directory tree.
+    ///
+    /// # Parameters
+    /// - `kind`: The class of the clusters.
+    ///
+    /// # Returns
+    /// - `Ok(ClusterRegions)`: The clusters of the class, in cluster order.
+    /// - `Err(FATError)`: If the FAT or the directory tree cannot be read.
+    pub fn clusters(&self, kind: ClusterKind) -> Result<ClusterRegions<'_>, FATError> {
+        let keep: fn(&FATVol, u32) -> bool = match kind {
+            ClusterKind::Allocated => |vol, value| value != 0 && value != vol.bad_cluster_marker(),
+            ClusterKind::Free => |_, value| value == 0,
+            ClusterKind::Bad => |vol, value| value == vol.bad_cluster_marker(),
+            ClusterKind::Slack => {
+                return Ok(ClusterRegions {
+                    vol: self,
+                    source: Source::Regions(self.slack_regions()?.into_iter()),
+                });
+            }
+        };
+
+        let mut entries = self.fat_entries()?.into_iter();
+        // Clusters 0 and 1 hold the media descriptor and the dirty flags
+        entries.nth(1);
+        Ok(ClusterRegions {
+            vol: self,
+            source: Source::Fat {
+                entries,
+                cluster: 2,
+                keep,
+            },
+        })
+    }
+
+    /// Lists the slack of every file, one region per cluster, in cluster order.
+    fn slack_regions(&self) -> Result<Vec<ClusterRegion>, FATError> {
+        let mut regions = vec![];
+        for (_, entry) in self.walk_files()? {
+            for (offset, len) in self.file_slack_extents(&entry)? {
+                if let Some(cluster) = self.offset_to_cluster(offset) {
+                    regions.push(ClusterRegion {
+                        cluster,
+                        offset,
+                        len,
+                    });
+                }
+            }
+        }
+        // Cross-linked files share their clusters
+        regions.sort_unstable_by_key(|region| region.cluster);
+        regions.dedup_by_key(|region| region.cluster);
+
+        Ok(regions)
+    }
+}
@@ -0,0 +1,97 @@
+//! Decoding of the packed FAT date and time fields.
+//!
+//! Dates are stored as `yyyyyyym mmmddddd` (years since 1980, month, day) and times as
+//! `hhhhhmmm mmmsssss` (hours, minutes, seconds divided by two).
+
+use getset::Getters;
+use std::fmt;
+
+/// A date and time decoded from a directory entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Getters)]
+pub struct FatDateTime {
+    #[get = "pub"]
+    year: u16,
+    #[get = "pub"]
+    month: u8,
+    #[get = "pub"]
+    day: u8,
+    #[get = "pub"]
+    hour: u8,
+    #[get = "pub"]
+    minute: u8,
+    #[get = "pub"]
+    second: u8,
+    /// Hundredths of a second, only recorded for the creation time.
+    #[get = "pub"]
+    centis: u8,
+}
+
+impl FatDateTime {
+    /// Decodes a packed date and time.
+    ///
+    /// # Parameters
+    /// - `date`: The packed date.
+    /// - `time`: The packed time.
+    /// - `tenths`: The count of 10ms units (0 to 199) added to the time.
+    ///
+    /// # Returns
+    /// - `Some(FatDateTime)` if the date is set.
+    /// - `None` if the date is 0, i.e. the field isn't used.
+    pub fn from_raw(date: u16, time: u16, tenths: u8) -> Option<Self> {
+        if date == 0 {
+            return None;
+        }
+
+        Some(Self {
+            year: 1980 + (date >> 9),
+            month: ((date >> 5) & 0x0F) as u8,
+            day: (date & 0x1F) as u8,
+            hour: (time >> 11) as u8,
+            minute: ((time >> 5) & 0x3F) as u8,
+            second: ((time & 0x1F) * 2) as u8 + tenths / 100,
+            centis: tenths % 100,
+        })
+    }
+
+    /// Encodes the date and time as packed fields, dropping the hundredths of a second and the
+    /// odd seconds.
+    ///
+    /// # Returns
+    /// - The packed date and the packed time.
+    pub fn to_raw(&self) -> (u16, u16) {
+        let date =
+            (self.year.saturating_sub(1980) << 9) | ((self.month as u16) << 5) | self.day as u16;
+        let time =
+            ((self.hour as u16) << 11) | ((self.minute as u16) << 5) | (self.second as u16 / 2);
+        (date, time)
+    }
+
+    /// Converts the date and time to seconds since the Unix epoch.
+    ///
+    /// FAT records local times without a time zone: they are read as UTC.
+    ///
+    /// # Returns
+    /// - The number of seconds since 1970-01-01 00:00:00.
+    pub fn unix_timestamp(&self) -> i64 {
+        // Days since the epoch of a proleptic Gregorian date, with years starting in March
+        let (month, day) = (self.month.clamp(1, 12) as i64, self.day as i64);
+        let year = self.year as i64 - (month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146097 + day_of_era - 719468;
+
+        days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+    }
+}
+
+impl fmt::Display for FatDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.centis
+        )
+    }
+}
@@ -5,14 +5,87 @@
 //! and contains information such as filename, attributes, timestamps, and cluster allocation.
 
 use binread::{BinRead, BinReaderExt};
-use getset::Getters;
+use getset::{Getters, Setters};
 use std::fmt;
 use std::io;
 use std::io::{Error, ErrorKind};
 use std::str::Utf8Error;
 
+use super::datetime::FatDateTime;
 use super::fat_error::FATError;
 use super::fat_type::FATType;
+use crate::utils::{escape_name, is_bidi_control};
+
+/// An attribute of a directory entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    /// Writes to the file should fail.
+    ReadOnly,
+    /// Normal directory listings should not show the entry.
+    Hidden,
+    /// The file belongs to the operating system.
+    System,
+    /// The entry holds the volume label.
+    VolumeId,
+    /// The entry is a directory.
+    Directory,
+    /// The file changed since the last backup.
+    Archive,
+}
+
+impl Attribute {
+    /// Every attribute, in the order of their bits.
+    pub const ALL: [Attribute; 6] = [
+        Attribute::ReadOnly,
+        Attribute::Hidden,
+        Attribute::System,
+        Attribute::VolumeId,
+        Attribute::Directory,
+        Attribute::Archive,
+    ];
+
+    /// Returns the bit of the attribute in the attribute byte.
+    fn bit(self) -> u8 {
+        match self {
+            Attribute::ReadOnly => DirEntry::ATTR_READ_ONLY,
+            Attribute::Hidden => DirEntry::ATTR_HIDDEN,
+            Attribute::System => DirEntry::ATTR_SYSTEM,
+            Attribute::VolumeId => DirEntry::ATTR_VOLUME_ID,
+            Attribute::Directory => DirEntry::ATTR_DIRECTORY,
+            Attribute::Archive => DirEntry::ATTR_ARCHIVE,
+        }
+    }
+
+    /// Returns the name of the attribute, e.g. `read_only`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Attribute::ReadOnly => "read_only",
+            Attribute::Hidden => "hidden",
+            Attribute::System => "system",
+            Attribute::VolumeId => "volume_id",
+            Attribute::Directory => "directory",
+            Attribute::Archive => "archive",
+        }
+    }
+
+    /// Finds an attribute by its name.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the attribute, e.g. `system`.
+    ///
+    /// # Returns
+    /// - `Some(Attribute)`: The attribute.
+    /// - `None`: If no attribute has this name.
+    pub fn from_name(name: &str) -> Option<Attribute> {
+        Attribute::ALL.into_iter().find(|attr| attr.name() == name)
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
 
 /// FAT directory entry structure.
 ///
@@ -27,18 +100,19 @@ use super::fat_type::FATType;
 /// - `file_size`: Size of the file in bytes (0 for directories)
 ///
 /// # Notes
-/// - Timestamp fields are prefixed with underscore as they're not currently used
+/// - Timestamp fields are prefixed with underscore as they're only exposed through decoding methods
 /// - The name field uses the legacy 8.3 format with space padding
-#[derive(BinRead, Debug, Clone, Getters)]
+#[derive(BinRead, Debug, Clone, Getters, Setters)]
 #[br(little)]
 pub struct DirEntry {
     /// Filename in 8.3 format (8 characters name + 3 characters extension)
     name: [u8; 11],
     /// File attributes byte
+    #[get = "pub"]
     attr: u8,
     /// NT reserved (unused)
     _n_t_res: u8,
-    /// Creation time in 10ms units
+    /// Creation time in 10ms units (checksum of the short name for long name entries)
     _ctr_time_tenth: u8,
     /// Creation time
     _crt_time: u16,
@@ -55,13 +129,26 @@ pub struct DirEntry {
     /// Low 16 bits of first cluster number
     fst_clus_lo: u16,
     /// File size in bytes (0 for directories)
-    #[get = "pub(super)"]
+    #[get = "pub"]
     file_size: u32,
+    /// Absolute byte offset of the entry on disk (not part of the on-disk structure)
+    #[br(ignore)]
+    #[getset(get = "pub", set = "pub(super)")]
+    location: u64,
+    /// Long file name assembled from the preceding long name entries (not part of the on-disk structure)
+    #[br(ignore)]
+    #[getset(get = "pub", set = "pub(super)")]
+    long_name: Option<String>,
 }
 
 impl DirEntry {
     const SELF: [u8; 11] = [46, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32];
     const PARENT: [u8; 11] = [46, 46, 32, 32, 32, 32, 32, 32, 32, 32, 32];
+    pub(super) const DELETED_MARKER: u8 = 0xE5;
+    /// Size in bytes of a directory entry.
+    pub const SIZE: u64 = 32;
+    /// Characters forbidden in short names and volume labels.
+    pub(super) const INVALID_CHARS: &[u8] = b"\"*+,./:;<=>?[\\]|";
 
     const ATTR_READ_ONLY: u8 = 0x01;
     const ATTR_HIDDEN: u8 = 0x02;
@@ -107,6 +194,19 @@ impl DirEntry {
         shortname == self.name
     }
 
+    /// Checks if a given filename matches this directory entry's short name or, case-insensitively,
+    /// its long name.
+    ///
+    /// # Parameters
+    /// - `name`: The filename to compare, short or long
+    pub fn same_name(&self, name: &str) -> bool {
+        self.same_short_name(name)
+            || self
+                .long_name()
+                .as_ref()
+                .is_some_and(|long_name| long_name.eq_ignore_ascii_case(name))
+    }
+
     fn to_8_3_name(name: &str, ext_opt: Option<&str>) -> Result<Vec<u8>, io::Error> {
         if name.len() > 8 {
             return Err(Error::new(
@@ -170,6 +270,254 @@ impl DirEntry {
         self.is_dir() && self.name != DirEntry::SELF && self.name != DirEntry::PARENT
     }
 
+    /// Returns true if this entry is the "." or ".." entry of a directory.
+    pub fn is_dot(&self) -> bool {
+        self.name == DirEntry::SELF || self.name == DirEntry::PARENT
+    }
+
+    /// Returns true if the raw fields of the entry are consistent with a directory entry.
+    ///
+    /// Used to tell directory clusters from other data when the file system metadata is damaged:
+    /// the short name must only hold valid 8.3 characters and the reserved attribute bits must be
+    /// clear.
+    pub fn is_plausible(&self) -> bool {
+        if self.is_long_name() {
+            return self.attr == DirEntry::ATTR_LONG_NAME;
+        }
+        if self.is_dot() {
+            return self.is_dir();
+        }
+
+        let valid_char = |c: &u8| *c >= 0x20 && !DirEntry::INVALID_CHARS.contains(c);
+        let first_valid = matches!(self.name[0], 0x05 | DirEntry::DELETED_MARKER)
+            || (self.name[0] != b' ' && valid_char(&self.name[0]));
+
+        self.attr & 0xC0 == 0
+            && first_valid
+            && self.name[1..].iter().all(valid_char)
+            && (!self.is_volume_id() || self.attr & DirEntry::ATTR_DIRECTORY == 0)
+    }
+
+    /// Returns true if this entry is a regular file.
+    ///
+    /// Directories, long name entries, volume labels and deleted entries are not regular files.
+    pub fn is_regular_file(&self) -> bool {
+        !self.is_dir() && !self.is_long_name() && !self.is_volume_id() && !self.is_deleted()
+    }
+
+    /// Returns true if this entry is part of a long file name.
+    pub fn is_long_name(&self) -> bool {
+        self.attr & DirEntry::ATTR_LONG_NAME == DirEntry::ATTR_LONG_NAME
+    }
+
+    /// Returns true if this entry holds the volume label.
+    pub fn is_volume_id(&self) -> bool {
+        !self.is_long_name() && self.attr & DirEntry::ATTR_VOLUME_ID == DirEntry::ATTR_VOLUME_ID
+    }
+
+    /// Returns true if this entry has been marked as deleted (first byte set to 0xE5).
+    pub fn is_deleted(&self) -> bool {
+        self.name[0] == DirEntry::DELETED_MARKER
+    }
+
+    /// Returns true if this long name entry belongs to the given short entry.
+    ///
+    /// Long name entries store a checksum of the 8.3 name of the short entry they precede.
+    pub fn is_long_name_of(&self, short_entry: &DirEntry) -> bool {
+        self.is_long_name() && self._ctr_time_tenth == short_entry.short_name_checksum()
+    }
+
+    /// Assembles the long file name of a short entry from the long name entries preceding it.
+    ///
+    /// # Parameters
+    /// - `parts`: The long name entries, in on-disk order (last part of the name first).
+    /// - `short_entry`: The short entry following them.
+    ///
+    /// # Returns
+    /// - `Some(String)` if the long name entries belong to the short entry.
+    /// - `None` otherwise.
+    ///
+    /// # Notes
+    /// Deleting an entry overwrites the checksummed first byte of its short name, so the long name
+    /// entries of a deleted entry are only required to be deleted as well.
+    pub(super) fn long_name_from(parts: &[DirEntry], short_entry: &DirEntry) -> Option<String> {
+        let belongs = |part: &DirEntry| match short_entry.is_deleted() {
+            true => part.is_long_name() && part.is_deleted(),
+            false => part.is_long_name_of(short_entry) && !part.is_deleted(),
+        };
+        if parts.is_empty() || !parts.iter().all(belongs) {
+            return None;
+        }
+
+        let chars: Vec<u16> = parts
+            .iter()
+            .rev()
+            .flat_map(|part| part.long_name_chars())
+            .take_while(|c| *c != 0)
+            .filter(|c| *c != 0xFFFF)
+            .collect();
+        Some(String::from_utf16_lossy(&chars))
+    }
+
+    /// Decodes a long name entry.
+    ///
+    /// # Returns
+    /// - `Some((sequence, checksum, text))`: The sequence number of the part (the last part of a
+    ///   name is flagged with `0x40`), the checksum of the short name it belongs to, and its
+    ///   characters up to the terminator.
+    /// - `None`: If the entry isn't a long name entry.
+    pub fn long_name_part(&self) -> Option<(u8, u8, String)> {
+        if !self.is_long_name() {
+            return None;
+        }
+
+        let chars: Vec<u16> = self
+            .long_name_chars()
+            .into_iter()
+            .take_while(|c| *c != 0)
+            .filter(|c| *c != 0xFFFF)
+            .collect();
+        Some((
+            self.name[0],
+            self._ctr_time_tenth,
+            String::from_utf16_lossy(&chars),
+        ))
+    }
+
+    /// Returns the 13 UTF-16 characters stored in a long name entry.
+    fn long_name_chars(&self) -> Vec<u16> {
+        let name_chars = self.name[1..11]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]));
+        let size_chars = [self.file_size as u16, (self.file_size >> 16) as u16];
+
+        name_chars
+            .chain([
+                self._crt_time,
+                self._crt_date,
+                self._lst_acc_date,
+                self.fst_clus_hi,
+                self._wrt_time,
+                self._wrt_date,
+            ])
+            .chain(size_chars)
+            .collect()
+    }
+
+    /// Returns the creation date and time of the entry, if set.
+    pub fn created(&self) -> Option<FatDateTime> {
+        FatDateTime::from_raw(self._crt_date, self._crt_time, self._ctr_time_tenth)
+    }
+
+    /// Returns the last modification date and time of the entry, if set.
+    pub fn modified(&self) -> Option<FatDateTime> {
+        FatDateTime::from_raw(self._wrt_date, self._wrt_time, 0)
+    }
+
+    /// Returns the last access date of the entry, if set.
+    ///
+    /// FAT only records the day of the last access, so the time is always midnight.
+    pub fn accessed(&self) -> Option<FatDateTime> {
+        FatDateTime::from_raw(self._lst_acc_date, 0, 0)
+    }
+
+    /// Returns the names of the attributes set on the entry, separated by `|`.
+    pub fn attributes(&self) -> String {
+        if self.is_long_name() {
+            return "long_name".to_string();
+        }
+
+        Attribute::ALL
+            .into_iter()
+            .filter(|attr| self.has_attribute(*attr))
+            .map(Attribute::name)
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+
+    /// Returns true if the attribute is set on the entry.
+    ///
+    /// Long name entries set the read-only, hidden, system and volume ID bits, but have none of
+    /// these attributes.
+    pub fn has_attribute(&self, attr: Attribute) -> bool {
+        !self.is_long_name() && self.attr & attr.bit() == attr.bit()
+    }
+
+    /// Computes the checksum of the 8.3 name, as stored in the associated long name entries.
+    fn short_name_checksum(&self) -> u8 {
+        self.name
+            .iter()
+            .fold(0u8, |sum, b| sum.rotate_right(1).wrapping_add(*b))
+    }
+
+    /// Returns the displayable short name of the entry (e.g. `FILE.TXT`).
+    ///
+    /// The first character of a deleted entry is lost and shown as `?`. Falls back to a lossy
+    /// conversion if the raw name is not valid UTF-8.
+    pub fn short_name(&self) -> String {
+        if self.is_deleted() {
+            let mut entry = self.clone();
+            entry.name[0] = b'?';
+            return entry.short_name();
+        }
+        self.fmt_name()
+            .unwrap_or_else(|_| String::from_utf8_lossy(&self.name).trim_end().to_string())
+    }
+
+    /// Returns the raw 11 bytes of the short name in hexadecimal, to inspect names that don't
+    /// render.
+    pub fn name_hex(&self) -> String {
+        self.name.iter().map(|b| format!("{b:02X}")).collect()
+    }
+
+    /// Lists what is unusual in the names of the entry: characters that corrupt or mislead a
+    /// terminal (see [`is_unsafe_char`](crate::utils::is_unsafe_char)), or that FAT forbids.
+    ///
+    /// # Returns
+    /// One description per anomaly, empty for a regular name, a long name entry or a dot entry.
+    pub fn name_anomalies(&self) -> Vec<String> {
+        let mut anomalies = vec![];
+        if self.is_long_name() || self.is_dot() {
+            return anomalies;
+        }
+
+        // The first byte of a deleted entry, or of a name starting with 0xE5, is a marker
+        let short_name = match self.name[0] {
+            0x05 | DirEntry::DELETED_MARKER => &self.name[1..],
+            _ => &self.name[..],
+        };
+        if short_name.iter().any(|c| *c < 0x20 || *c == 0x7F) {
+            anomalies.push(String::from("Control characters in the short name."));
+        }
+        if !self.is_volume_id()
+            && short_name
+                .iter()
+                .any(|c| DirEntry::INVALID_CHARS.contains(c))
+        {
+            anomalies.push(String::from("Characters not allowed in a short name."));
+        }
+
+        if let Some(long_name) = &self.long_name {
+            if long_name.chars().any(char::is_control) {
+                anomalies.push(String::from("Control characters in the long name."));
+            }
+            if long_name.contains(char::REPLACEMENT_CHARACTER) {
+                anomalies.push(String::from("Invalid code points in the long name."));
+            }
+            if long_name.chars().any(is_bidi_control) {
+                anomalies.push(String::from(
+                    "Bidirectional formatting characters in the long name, reordering its display.",
+                ));
+            }
+        }
+        anomalies
+    }
+
+    /// Returns the raw 11-byte name of the entry without trailing spaces, as used by volume labels.
+    pub fn raw_name(&self) -> String {
+        String::from_utf8_lossy(&self.name).trim_end().to_string()
+    }
+
     /// Returns true if the given cluster number is the end-of-chain marker for the given FAT type.
     ///
     /// # Parameters
@@ -208,37 +556,7 @@ impl fmt::Display for DirEntry {
     /// # Returns
     /// - A string representation showing the filename and file size
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let attr_str = if self.attr & DirEntry::ATTR_LONG_NAME == DirEntry::ATTR_LONG_NAME {
-            "long_name".to_string()
-        } else {
-            let mut parts = vec![];
-
-            if self.attr & DirEntry::ATTR_READ_ONLY == DirEntry::ATTR_READ_ONLY {
-                parts.push("read_only");
-            }
-            if self.attr & DirEntry::ATTR_HIDDEN == DirEntry::ATTR_HIDDEN {
-                parts.push("hidden");
-            }
-            if self.attr & DirEntry::ATTR_SYSTEM == DirEntry::ATTR_SYSTEM {
-                parts.push("system");
-            }
-            if self.attr & DirEntry::ATTR_VOLUME_ID == DirEntry::ATTR_VOLUME_ID {
-                parts.push("volume_id");
-            }
-            if self.attr & DirEntry::ATTR_ARCHIVE == DirEntry::ATTR_ARCHIVE {
-                parts.push("archive");
-            }
-
-            parts.join("|")
-        };
-
-        match self.fmt_name() {
-            Ok(fmt_name) => {
-                write!(f, "{} {}B", fmt_name, self.file_size)
-            }
-            _ => {
-                write!(f, "{:?} {}B {}", self.name, self.file_size, attr_str)
-            }
-        }
+        // Names are escaped, a crafted name must not corrupt the terminal
+        write!(f, "{} {}B", escape_name(&self.short_name()), self.file_size)
     }
 }
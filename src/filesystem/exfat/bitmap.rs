@@ -0,0 +1,62 @@
+//! exFAT allocation bitmap.
+//!
+//! exFAT tracks the allocation of the clusters in a bitmap stored in the cluster heap, one bit
+//! per cluster starting at cluster 2, rather than in the FAT: the FAT only links the clusters of
+//! fragmented files, and is left untouched for contiguous ones. A cluster is allocated if and only
+//! if its bit is set, whatever its FAT entry holds.
+
+/// The allocation bitmap of an exFAT volume.
+#[derive(Debug, Clone)]
+pub struct AllocationBitmap {
+    /// The bits of the clusters, cluster 2 being the lowest bit of the first byte.
+    bits: Vec<u8>,
+    /// The number of clusters of the cluster heap.
+    cluster_count: u32,
+}
+
+impl AllocationBitmap {
+    /// Builds the bitmap of a volume from its content.
+    ///
+    /// # Parameters
+    /// - `bits`: The content of the bitmap, as read from the cluster heap.
+    /// - `cluster_count`: The number of clusters of the cluster heap, the bits past it being
+    ///   ignored.
+    pub fn new(bits: Vec<u8>, cluster_count: u32) -> Self {
+        Self {
+            bits,
+            cluster_count,
+        }
+    }
+
+    /// Checks whether a cluster is allocated.
+    ///
+    /// # Parameters
+    /// - `cluster`: The cluster number, 2 for the first cluster of the heap.
+    ///
+    /// # Returns
+    /// - `true` if the bit of the cluster is set. Clusters outside of the heap or the bitmap are
+    ///   reported free.
+    pub fn is_allocated(&self, cluster: u32) -> bool {
+        cluster >= 2 && self.bit(cluster - 2)
+    }
+
+    /// Returns the number of allocated clusters.
+    pub fn allocated_count(&self) -> u32 {
+        (0..self.cluster_count).filter(|idx| self.bit(*idx)).count() as u32
+    }
+
+    /// Returns the number of clusters of the cluster heap.
+    pub fn cluster_count(&self) -> u32 {
+        self.cluster_count
+    }
+
+    /// Reads the bit of the cluster at the given index of the heap.
+    fn bit(&self, idx: u32) -> bool {
+        let idx = idx as usize;
+        idx < self.cluster_count as usize
+            && self
+                .bits
+                .get(idx / 8)
+                .is_some_and(|byte| byte & (1 << (idx % 8)) != 0)
+    }
+}
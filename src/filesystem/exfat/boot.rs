@@ -0,0 +1,259 @@
+//! exFAT boot sector structure and parsing.
+//!
+//! The boot sector of an exFAT volume replaces the BPB of FAT volumes: the legacy fields are
+//! zeroed, so that FAT drivers refuse the volume, and the geometry is stored as powers of two
+//! after them. The boot sector starts a boot region of 12 sectors, followed by a backup copy; the
+//! last sector of each region holds a checksum of the 11 others.
+
+use binread::{BinRead, BinReaderExt};
+use getset::Getters;
+use std::io;
+
+use crate::filesystem::fat_error::FATError;
+use crate::utils;
+
+/// The file system name of an exFAT boot sector.
+pub const EXFAT_NAME: &[u8; 8] = b"EXFAT   ";
+
+/// The number of sectors of a boot region, checksum sector included.
+pub const BOOT_REGION_SECTORS: u64 = 12;
+
+/// The base-2 logarithm of the largest cluster size, 32 MiB.
+const MAX_CLUSTER_SHIFT: u8 = 25;
+
+/// exFAT boot sector structure.
+#[derive(BinRead, Debug, Getters)]
+#[br(little)]
+pub struct ExFatBoot {
+    /// Jump instruction to boot code (must be 0xEB 0x76 0x90)
+    jmp: [u8; 3],
+    /// File system name ("EXFAT   ")
+    fs_name: [u8; 8],
+    /// Legacy BPB fields, zeroed
+    #[br(count = 53)]
+    must_be_zero: Vec<u8>,
+    /// Sector of the media where the volume starts, 0 if ignored
+    #[get = "pub"]
+    partition_offset: u64,
+    /// Number of sectors of the volume
+    #[get = "pub"]
+    volume_length: u64,
+    /// First sector of the first FAT, relative to the start of the volume
+    #[get = "pub"]
+    fat_offset: u32,
+    /// Number of sectors of a FAT
+    #[get = "pub"]
+    fat_length: u32,
+    /// First sector of the cluster heap, relative to the start of the volume
+    #[get = "pub"]
+    cluster_heap_offset: u32,
+    /// Number of clusters of the cluster heap
+    #[get = "pub"]
+    cluster_count: u32,
+    /// First cluster of the root directory
+    #[get = "pub"]
+    root_cluster: u32,
+    /// Volume serial number
+    #[get = "pub"]
+    volume_serial: u32,
+    /// File system revision, major version in the high byte
+    #[get = "pub"]
+    fs_revision: u16,
+    /// Volume flags (active FAT, volume dirty, media failure)
+    #[get = "pub"]
+    volume_flags: u16,
+    /// Base-2 logarithm of the number of bytes per sector (9 to 12)
+    bytes_per_sector_shift: u8,
+    /// Base-2 logarithm of the number of sectors per cluster
+    sectors_per_cluster_shift: u8,
+    /// Number of FATs (1, or 2 on TexFAT volumes)
+    #[get = "pub"]
+    number_of_fats: u8,
+    /// INT 13h drive number
+    #[get = "pub"]
+    drive_select: u8,
+    /// Percentage of allocated clusters, 0xFF if unknown
+    #[get = "pub"]
+    percent_in_use: u8,
+    /// Boot code, after 7 reserved bytes
+    #[get = "pub"]
+    #[br(pad_before = 7, count = 390)]
+    boot_code: Vec<u8>,
+    /// Boot sector signature (0x55 0xAA)
+    sig: [u8; 2],
+}
+
+impl ExFatBoot {
+    /// Reads and optionally validates the boot sector of an exFAT volume.
+    ///
+    /// # Parameters
+    /// - `file`: The disk image containing the volume
+    /// - `offset`: The absolute byte offset of the volume
+    /// - `validate`: Whether to check the fields and the checksum of the boot region
+    ///
+    /// # Returns
+    /// - `Ok(ExFatBoot)`: The parsed and optionally validated boot sector
+    /// - `Err(FATError::InvalidBootSector)`: If the sector isn't an exFAT boot sector, or a field
+    ///   is invalid
+    /// - `Err(FATError::BootChecksumMismatch)`: If the checksum of the boot region differs
+    pub fn from<T: io::Read + io::Seek>(
+        file: &mut T,
+        offset: u64,
+        validate: bool,
+    ) -> Result<ExFatBoot, FATError> {
+        let buf = utils::read_at(file, offset, 512)?;
+        let boot: ExFatBoot = io::Cursor::new(&buf).read_be()?;
+        if &boot.fs_name != EXFAT_NAME {
+            return Err(FATError::InvalidBootSector(String::from(
+                "the file system name isn't EXFAT",
+            )));
+        }
+        if !validate {
+            return Ok(boot);
+        }
+
+        boot.validate()?;
+        let region = utils::read_at(
+            file,
+            offset,
+            BOOT_REGION_SECTORS as usize * boot.bytes_per_sector() as usize,
+        )?;
+        boot.validate_checksum(&region)?;
+        Ok(boot)
+    }
+
+    /// Checks whether a sector starts with the file system name of an exFAT boot sector.
+    ///
+    /// # Parameters
+    /// - `sector`: The content of the sector.
+    pub fn is_exfat(sector: &[u8]) -> bool {
+        sector.get(3..11) == Some(EXFAT_NAME.as_slice())
+    }
+
+    /// Returns the number of bytes per sector.
+    pub fn bytes_per_sector(&self) -> u32 {
+        1 << self.bytes_per_sector_shift.min(12)
+    }
+
+    /// Returns the number of sectors per cluster, capped so that a cluster holds 32 MiB at most
+    /// even when the boot sector isn't validated.
+    pub fn sectors_per_cluster(&self) -> u32 {
+        let max_shift = MAX_CLUSTER_SHIFT - self.bytes_per_sector_shift.min(12);
+        1 << self.sectors_per_cluster_shift.min(max_shift)
+    }
+
+    /// Returns the size in bytes of a cluster.
+    pub fn cluster_size(&self) -> u64 {
+        self.bytes_per_sector() as u64 * self.sectors_per_cluster() as u64
+    }
+
+    /// Returns the index of the FAT and allocation bitmap in use, from the volume flags.
+    pub fn active_fat(&self) -> u8 {
+        match self.number_of_fats > 1 && self.volume_flags & 0x1 != 0 {
+            true => 1,
+            false => 0,
+        }
+    }
+
+    /// Returns true if the volume wasn't cleanly unmounted.
+    pub fn is_dirty(&self) -> bool {
+        self.volume_flags & 0x2 != 0
+    }
+
+    /// Validates the fields of the boot sector against the exFAT specification.
+    fn validate(&self) -> Result<(), FATError> {
+        let invalid = |msg: String| Err(FATError::InvalidBootSector(msg));
+
+        if self.jmp != [0xEB, 0x76, 0x90] {
+            return Err(FATError::InvalidJmp(format!("{:02X?}", self.jmp)));
+        }
+        if self.must_be_zero.iter().any(|b| *b != 0) {
+            return invalid(String::from("the legacy BPB fields aren't zeroed"));
+        }
+        if !(9..=12).contains(&self.bytes_per_sector_shift) {
+            return Err(FATError::InvalidBytesPerSec(
+                1u16.checked_shl(self.bytes_per_sector_shift as u32)
+                    .unwrap_or(0),
+            ));
+        }
+        if self.bytes_per_sector_shift as u32 + self.sectors_per_cluster_shift as u32
+            > MAX_CLUSTER_SHIFT as u32
+        {
+            return invalid(format!(
+                "clusters of 2^{} sectors exceed 32 MiB",
+                self.sectors_per_cluster_shift
+            ));
+        }
+        if !(1..=2).contains(&self.number_of_fats) {
+            return Err(FATError::InvalidNumFat(self.number_of_fats));
+        }
+        if self.fat_offset < 24 {
+            return invalid(format!(
+                "the FAT starts at sector {}, within the boot regions",
+                self.fat_offset
+            ));
+        }
+        let fats_end = self.fat_offset as u64 + self.fat_length as u64 * self.number_of_fats as u64;
+        if (self.fat_length as u64) * (self.bytes_per_sector() as u64) / 4
+            < self.cluster_count as u64 + 2
+        {
+            return Err(FATError::InvalidFatSz(format!(
+                "{} sectors for {} clusters",
+                self.fat_length, self.cluster_count
+            )));
+        }
+        if (self.cluster_heap_offset as u64) < fats_end {
+            return invalid(format!(
+                "the cluster heap starts at sector {}, within the FATs",
+                self.cluster_heap_offset
+            ));
+        }
+        let heap_end = self.cluster_heap_offset as u64
+            + self.cluster_count as u64 * self.sectors_per_cluster() as u64;
+        if heap_end > self.volume_length {
+            return Err(FATError::InvalidTotSec(format!(
+                "{} sectors for a cluster heap ending at sector {heap_end}",
+                self.volume_length
+            )));
+        }
+        if self.root_cluster < 2 || self.root_cluster - 2 >= self.cluster_count {
+            return Err(FATError::InvalidRootClus(self.root_cluster));
+        }
+        if self.sig != [0x55, 0xAA] {
+            return Err(FATError::InvalidSignature(format!("{:02X?}", self.sig)));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the checksum sector closing a boot region.
+    ///
+    /// # Parameters
+    /// - `region`: The content of the 12 sectors of the boot region.
+    fn validate_checksum(&self, region: &[u8]) -> Result<(), FATError> {
+        let sector_len = self.bytes_per_sector() as usize;
+        let expected = boot_checksum(&region[..11 * sector_len]);
+        let checksums = &region[11 * sector_len..];
+        match (0..sector_len / 4).find(|idx| utils::u32_at(checksums, idx * 4) != expected) {
+            Some(idx) => Err(FATError::BootChecksumMismatch {
+                expected,
+                actual: utils::u32_at(checksums, idx * 4),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Computes the checksum of the first 11 sectors of a boot region.
+///
+/// The volume flags and the percentage in use change while the volume is mounted, so they are
+/// left out of the checksum.
+pub fn boot_checksum(sectors: &[u8]) -> u32 {
+    sectors
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !matches!(idx, 106 | 107 | 112))
+        .fold(0u32, |sum, (_, b)| {
+            sum.rotate_right(1).wrapping_add(*b as u32)
+        })
+}
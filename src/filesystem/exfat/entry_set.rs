@@ -0,0 +1,225 @@
+//! exFAT directory entries and entry sets.
+//!
+//! An exFAT directory is a sequence of 32-byte entries. A file or directory is described by an
+//! entry set: a File entry holding its attributes and timestamps, then a Stream Extension entry
+//! holding its first cluster and size, then one File Name entry per 15 characters of its name.
+//! The File entry counts the secondary entries of the set and stores a checksum of all of them.
+//!
+//! The high bit of the type byte marks an entry in use: deleting a file clears it on every entry of
+//! the set, leaving the set readable. The root directory also holds the Allocation Bitmap, Up-case
+//! Table and Volume Label entries, which have no secondary entries.
+
+use getset::Getters;
+use std::fmt;
+
+use crate::utils::{self, escape_name};
+
+/// The size in bytes of a directory entry.
+pub const ENTRY_SIZE: usize = 32;
+
+/// The type of the entry ending a directory, and all the following ones.
+const END_OF_DIRECTORY: u8 = 0x00;
+/// The type of an Allocation Bitmap entry.
+const ALLOCATION_BITMAP: u8 = 0x81;
+/// The type of an Up-case Table entry.
+const UPCASE_TABLE: u8 = 0x82;
+/// The type of a Volume Label entry.
+const VOLUME_LABEL: u8 = 0x83;
+/// The type of a File entry, in use bit cleared.
+const FILE: u8 = 0x05;
+/// The type of a Stream Extension entry, in use bit cleared.
+const STREAM_EXTENSION: u8 = 0x40;
+/// The type of a File Name entry, in use bit cleared.
+const FILE_NAME: u8 = 0x41;
+/// The bit of the type byte set on entries in use.
+const IN_USE: u8 = 0x80;
+
+/// The number of UTF-16 characters held by a File Name entry.
+const NAME_CHARS_PER_ENTRY: usize = 15;
+
+/// The directory attribute of a File entry.
+const ATTR_DIRECTORY: u16 = 0x10;
+
+/// A file or directory, as described by its entry set.
+#[derive(Debug, Clone, Getters)]
+pub struct EntrySet {
+    /// The index of the File entry in the directory.
+    #[get = "pub"]
+    index: usize,
+    /// The name of the file.
+    #[get = "pub"]
+    name: String,
+    /// The FAT attributes (read-only, hidden, system, directory, archive).
+    #[get = "pub"]
+    attributes: u16,
+    /// The first cluster of the data, 0 for an empty file.
+    #[get = "pub"]
+    first_cluster: u32,
+    /// The size in bytes of the data.
+    #[get = "pub"]
+    data_length: u64,
+    /// The number of bytes of the data written, the remainder reading as zeros.
+    #[get = "pub"]
+    valid_data_length: u64,
+    /// Whether the clusters of the data are contiguous and absent from the FAT.
+    #[get = "pub"]
+    no_fat_chain: bool,
+    /// Whether the set was deleted.
+    #[get = "pub"]
+    deleted: bool,
+    /// Whether the checksum stored in the File entry matches the set.
+    #[get = "pub"]
+    checksum_valid: bool,
+}
+
+impl EntrySet {
+    /// Checks whether the set describes a directory.
+    pub fn is_dir(&self) -> bool {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+}
+
+impl fmt::Display for EntrySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Names are escaped, a crafted name must not corrupt the terminal
+        write!(f, "{} {}B", escape_name(&self.name), self.data_length)?;
+        if self.deleted {
+            write!(f, " (deleted)")?;
+        }
+        if !self.checksum_valid {
+            write!(f, " (bad checksum)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The entries of an exFAT directory.
+#[derive(Debug, Default)]
+pub struct ExFatDir {
+    /// The entry sets of the files and directories, deleted ones included, in directory order.
+    pub sets: Vec<EntrySet>,
+    /// The volume label, from the root directory.
+    pub label: Option<String>,
+    /// The first cluster and the length in bytes of each allocation bitmap, from the root
+    /// directory.
+    pub bitmaps: Vec<(u32, u64)>,
+    /// The first cluster and the length in bytes of the up-case table, from the root directory.
+    pub upcase: Option<(u32, u64)>,
+}
+
+/// Parses the entries of a directory.
+///
+/// Sets cut short by another primary entry or by the end of the directory are dropped, as are
+/// sets without a Stream Extension entry.
+///
+/// # Parameters
+/// - `data`: The content of the directory.
+/// - `max_entries`: The maximum number of entries read.
+///
+/// # Returns
+/// - The entries of the directory, up to the end of directory entry.
+pub fn parse_dir(data: &[u8], max_entries: usize) -> ExFatDir {
+    let entries: Vec<&[u8]> = data.chunks_exact(ENTRY_SIZE).take(max_entries).collect();
+    let mut dir = ExFatDir::default();
+
+    let mut idx = 0;
+    while idx < entries.len() {
+        let entry = entries[idx];
+        match entry[0] {
+            END_OF_DIRECTORY => break,
+            ALLOCATION_BITMAP => dir
+                .bitmaps
+                .push((utils::u32_at(entry, 20), u64_at(entry, 24))),
+            UPCASE_TABLE => dir.upcase = Some((utils::u32_at(entry, 20), u64_at(entry, 24))),
+            VOLUME_LABEL => {
+                let len = (entry[1] as usize).min(11);
+                dir.label = Some(utf16_at(entry, 2, len));
+            }
+            kind if kind & !IN_USE == FILE => {
+                let count = entry[1] as usize;
+                if let Some(set) = entries.get(idx..=idx + count)
+                    && let Some(set) = parse_set(idx, set)
+                {
+                    dir.sets.push(set);
+                    idx += count;
+                }
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+
+    dir
+}
+
+/// Parses an entry set, the File entry followed by its secondary entries.
+fn parse_set(index: usize, entries: &[&[u8]]) -> Option<EntrySet> {
+    let file = entries[0];
+    let in_use = file[0] & IN_USE;
+    // The secondary entries share the in use bit of the File entry
+    let stream = entries.get(1)?;
+    if stream[0] != STREAM_EXTENSION | in_use {
+        return None;
+    }
+    let name_len = stream[3] as usize;
+    // Vendor entries may follow the File Name entries
+    let name_entries: Vec<&&[u8]> = entries[2..]
+        .iter()
+        .take_while(|entry| entry[0] == FILE_NAME | in_use)
+        .collect();
+    if name_entries.len() < name_len.div_ceil(NAME_CHARS_PER_ENTRY) {
+        return None;
+    }
+    let name: Vec<u16> = name_entries
+        .iter()
+        .flat_map(|entry| entry[2..].chunks_exact(2).map(|c| utils::u16_at(c, 0)))
+        .take(name_len)
+        .collect();
+
+    Some(EntrySet {
+        index,
+        name: String::from_utf16_lossy(&name),
+        attributes: utils::u16_at(file, 4),
+        first_cluster: utils::u32_at(stream, 20),
+        data_length: u64_at(stream, 24),
+        valid_data_length: u64_at(stream, 8),
+        no_fat_chain: stream[1] & 0x2 != 0,
+        deleted: in_use == 0,
+        checksum_valid: set_checksum(entries) == utils::u16_at(file, 2),
+    })
+}
+
+/// Computes the checksum of an entry set, the checksum field of the File entry left out.
+///
+/// The checksum is computed while the set is in use, so the in use bits cleared by a deletion are
+/// set back.
+fn set_checksum(entries: &[&[u8]]) -> u16 {
+    entries
+        .iter()
+        .flat_map(|entry| entry.iter())
+        .enumerate()
+        .filter(|(idx, _)| !matches!(idx, 2 | 3))
+        .map(|(idx, b)| match idx % ENTRY_SIZE {
+            0 => b | IN_USE,
+            _ => *b,
+        })
+        .fold(0u16, |sum, b| sum.rotate_right(1).wrapping_add(b as u16))
+}
+
+/// Decodes `len` UTF-16 characters from a buffer at a given offset.
+fn utf16_at(buffer: &[u8], offset: usize, len: usize) -> String {
+    let chars: Vec<u16> = buffer[offset..offset + 2 * len]
+        .chunks_exact(2)
+        .map(|c| utils::u16_at(c, 0))
+        .collect();
+    String::from_utf16_lossy(&chars)
+}
+
+/// Extracts a 64-bit unsigned integer from a buffer at a given offset.
+fn u64_at(buffer: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(
+        buffer[offset..offset + 8]
+            .try_into()
+            .expect("invalid slice"),
+    )
+}
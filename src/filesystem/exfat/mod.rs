@@ -0,0 +1,462 @@
+//! exFAT volume abstraction.
+//!
+//! exFAT, the file system of SDXC cards and most large USB sticks, keeps the cluster heap and
+//! the FAT of its predecessors but little else: the boot sector has its own layout, the allocation
+//! of the clusters is tracked in a bitmap, and files are described by sets of directory entries
+//! holding their full names. [`ExFatVol`] parses these structures and displays the layout and the
+//! directory tree of the volume, next to the FAT volumes of a [`Disk`](crate::Disk).
+
+pub(crate) mod bitmap;
+pub(crate) mod boot;
+pub(crate) mod entry_set;
+
+use getset::Getters;
+use std::fmt::Write;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use self::bitmap::AllocationBitmap;
+use self::boot::{BOOT_REGION_SECTORS, ExFatBoot};
+use self::entry_set::{ENTRY_SIZE, EntrySet, ExFatDir, parse_dir};
+use super::fat::lock;
+use super::fat_error::FATError;
+use crate::image::ImageSource;
+use crate::limits::Limits;
+use crate::traits::{LayoutDisplay, LayoutOptions, TraitError, TreeDisplay};
+use crate::utils;
+
+/// The FAT entry of a bad cluster.
+const BAD_CLUSTER: u32 = 0xFFFF_FFF7;
+
+/// Represents an exFAT volume within a disk image.
+#[derive(Debug, Getters)]
+pub struct ExFatVol {
+    /// The boot sector of the volume.
+    #[get = "pub"]
+    boot: ExFatBoot,
+    /// The absolute byte offset of the volume in the image.
+    #[get = "pub"]
+    offset: u64,
+    /// The number of sectors of the partition holding the volume, in sectors of the volume.
+    #[get = "pub"]
+    partition_sectors: u64,
+    /// The volume label, from the root directory.
+    #[get = "pub"]
+    label: Option<String>,
+    /// The allocation bitmap of the active FAT.
+    #[get = "pub"]
+    bitmap: AllocationBitmap,
+    /// The first cluster and the length in bytes of the allocation bitmap.
+    #[get = "pub"]
+    bitmap_extent: (u32, u64),
+    /// The first cluster and the length in bytes of the up-case table, if any.
+    #[get = "pub"]
+    upcase_extent: Option<(u32, u64)>,
+    /// The source of the bytes of the disk image.
+    image: Arc<ImageSource>,
+    /// The limits enforced by the walks of the volume.
+    limits: Mutex<Limits>,
+}
+
+impl ExFatVol {
+    /// Reads the boot sector and the root directory of an exFAT volume.
+    ///
+    /// # Parameters
+    /// - `image`: The disk image containing the filesystem
+    /// - `start`: The sector number where the boot sector is located
+    /// - `sector_cnt`: The number of sectors of the partition
+    /// - `validate`: Whether to perform validation checks on the boot region
+    /// - `sector_size`: The size of each sector of the disk in bytes
+    ///
+    /// # Returns
+    /// - `Ok(ExFatVol)`: The exFAT volume
+    /// - `Err(FATError)`: If reading fails, validation fails or the root directory has no
+    ///   allocation bitmap
+    pub fn from_backend(
+        image: Arc<ImageSource>,
+        start: u32,
+        sector_cnt: u32,
+        validate: bool,
+        sector_size: usize,
+    ) -> Result<ExFatVol, FATError> {
+        let offset = start as u64 * sector_size as u64;
+        let boot = ExFatBoot::from(&mut image.reader()?, offset, validate)?;
+        let partition_sectors =
+            sector_cnt as u64 * sector_size as u64 / boot.bytes_per_sector() as u64;
+
+        let mut vol = ExFatVol {
+            bitmap: AllocationBitmap::new(vec![], *boot.cluster_count()),
+            boot,
+            offset,
+            partition_sectors,
+            label: None,
+            bitmap_extent: (0, 0),
+            upcase_extent: None,
+            image,
+            limits: Mutex::new(Limits::default()),
+        };
+
+        // TexFAT volumes have a bitmap per FAT, in FAT order
+        let root = vol.root_dir()?;
+        let active = vol.boot.active_fat() as usize;
+        let Some(&(cluster, len)) = root.bitmaps.get(active).or(root.bitmaps.first()) else {
+            return Err(FATError::InvalidBootSector(String::from(
+                "the root directory has no allocation bitmap",
+            )));
+        };
+        let bits = vol.read_clusters(cluster, Some(len), false)?;
+        vol.bitmap = AllocationBitmap::new(bits, *vol.boot.cluster_count());
+        vol.bitmap_extent = (cluster, len);
+        vol.upcase_extent = root.upcase;
+        vol.label = root.label;
+
+        Ok(vol)
+    }
+
+    /// Checks whether the sector at the given position starts an exFAT boot sector.
+    ///
+    /// # Parameters
+    /// - `image`: The disk image
+    /// - `start`: The sector number of the candidate boot sector
+    /// - `sector_size`: The size of each sector of the disk in bytes
+    pub fn is_exfat(image: &ImageSource, start: u32, sector_size: usize) -> bool {
+        image
+            .read_at(start as u64 * sector_size as u64, 512)
+            .is_ok_and(|sector| ExFatBoot::is_exfat(&sector))
+    }
+
+    /// Sets the limits enforced by the walks of the volume.
+    pub fn set_limits(&self, limits: Limits) {
+        *lock(&self.limits) = limits;
+    }
+
+    /// Returns the absolute byte offset of a sector of the volume.
+    pub fn sector_offset(&self, sector: u64) -> u64 {
+        self.offset + sector * self.boot.bytes_per_sector() as u64
+    }
+
+    /// Returns the absolute byte offset of a cluster of the cluster heap.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The offset of the first byte of the cluster.
+    /// - `Err(FATError::ClusterOutOfRange)`: If the cluster is outside of the cluster heap.
+    pub fn cluster_offset(&self, cluster: u32) -> Result<u64, FATError> {
+        if !self.is_heap_cluster(cluster) {
+            return Err(FATError::ClusterOutOfRange(cluster));
+        }
+        Ok(self.sector_offset(
+            *self.boot.cluster_heap_offset() as u64
+                + (cluster - 2) as u64 * self.boot.sectors_per_cluster() as u64,
+        ))
+    }
+
+    /// Reads the entry of a cluster in the active FAT.
+    ///
+    /// # Returns
+    /// - `Ok(Some(u32))`: The next cluster of the chain.
+    /// - `Ok(None)`: If the cluster ends its chain.
+    /// - `Err(FATError)`: If the entry is free, bad or outside of the cluster heap.
+    pub fn next_cluster(&self, cluster: u32) -> Result<Option<u32>, FATError> {
+        let fat_start = *self.boot.fat_offset() as u64
+            + self.boot.active_fat() as u64 * *self.boot.fat_length() as u64;
+        let entry = self
+            .image
+            .read_at(self.sector_offset(fat_start) + cluster as u64 * 4, 4)?;
+        match utils::u32_at(&entry, 0) {
+            u32::MAX => Ok(None),
+            BAD_CLUSTER => Err(FATError::InvalidClusterError(cluster)),
+            next if self.is_heap_cluster(next) => Ok(Some(next)),
+            next => Err(FATError::ClusterOutOfRange(next)),
+        }
+    }
+
+    /// Lists the clusters of a file or directory.
+    ///
+    /// # Parameters
+    /// - `first`: The first cluster, 0 for an empty file.
+    /// - `len`: The size in bytes of the data, or `None` to follow the FAT to the end of the chain.
+    /// - `no_fat_chain`: Whether the clusters are contiguous, their FAT entries being unused.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u32>)`: The clusters, in order.
+    /// - `Err(FATError)`: If the chain leaves the cluster heap, loops or exceeds the limits.
+    pub fn cluster_chain(
+        &self,
+        first: u32,
+        len: Option<u64>,
+        no_fat_chain: bool,
+    ) -> Result<Vec<u32>, FATError> {
+        if first == 0 {
+            return Ok(vec![]);
+        }
+        let max_len = (*self.boot.cluster_count()).min(lock(&self.limits).max_chain_len) as u64;
+        let cluster_cnt = len.map(|len| len.div_ceil(self.boot.cluster_size()));
+        if cluster_cnt.is_some_and(|cnt| cnt > max_len) {
+            return Err(FATError::LimitExceeded {
+                what: "clusters in a chain",
+                max: max_len,
+            });
+        }
+
+        if no_fat_chain {
+            let cnt = cluster_cnt.unwrap_or(1);
+            let last = first as u64 + cnt.max(1) - 1;
+            if !self.is_heap_cluster(first) || last > u32::MAX as u64 {
+                return Err(FATError::ClusterOutOfRange(first));
+            }
+            if !self.is_heap_cluster(last as u32) {
+                return Err(FATError::ClusterOutOfRange(last as u32));
+            }
+            return Ok((first..first + cnt as u32).collect());
+        }
+
+        if !self.is_heap_cluster(first) {
+            return Err(FATError::ClusterOutOfRange(first));
+        }
+        let mut chain = vec![first];
+        let mut cluster = first;
+        while cluster_cnt.is_none_or(|cnt| (chain.len() as u64) < cnt) {
+            let Some(next) = self.next_cluster(cluster)? else {
+                break;
+            };
+            if chain.len() as u64 >= max_len {
+                return Err(FATError::ChainLoop(next));
+            }
+            chain.push(next);
+            cluster = next;
+        }
+
+        Ok(chain)
+    }
+
+    /// Reads the data of a file or directory, see [`ExFatVol::cluster_chain`].
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The data, cut to its size if known.
+    /// - `Err(FATError::SectorOutOfRange)`: If a cluster lies past the end of the partition or of
+    ///   the image.
+    /// - `Err(FATError)`: If the chain is invalid or reading fails.
+    pub fn read_clusters(
+        &self,
+        first: u32,
+        len: Option<u64>,
+        no_fat_chain: bool,
+    ) -> Result<Vec<u8>, FATError> {
+        let cluster_size = self.boot.cluster_size();
+        let bytes_per_sector = self.boot.bytes_per_sector() as u64;
+        // A crafted size or heap must not make us allocate more than the volume can hold
+        let end = self
+            .sector_offset(self.partition_sectors)
+            .min(self.image.len()?);
+        let mut data = vec![];
+        for run in self.contiguous_runs(&self.cluster_chain(first, len, no_fat_chain)?) {
+            let offset = self.cluster_offset(run.0)?;
+            let run_len = run.1 * cluster_size;
+            if offset + run_len > end {
+                let start = (offset - self.offset) / bytes_per_sector;
+                return Err(FATError::SectorOutOfRange {
+                    start,
+                    end: start + run_len / bytes_per_sector,
+                });
+            }
+            data.extend(self.image.read_at(offset, run_len as usize)?);
+        }
+        if let Some(len) = len {
+            data.truncate(len as usize);
+        }
+
+        Ok(data)
+    }
+
+    /// Reads the entries of the root directory.
+    pub fn root_dir(&self) -> Result<ExFatDir, FATError> {
+        self.read_dir(*self.boot.root_cluster(), None, false)
+    }
+
+    /// Reads the entries of a directory.
+    ///
+    /// # Parameters
+    /// - `first`: The first cluster of the directory.
+    /// - `len`: The size in bytes of the directory, `None` for the root directory.
+    /// - `no_fat_chain`: Whether the clusters of the directory are contiguous.
+    ///
+    /// # Returns
+    /// - `Ok(ExFatDir)`: The entries of the directory, deleted entry sets included.
+    /// - `Err(FATError)`: If the directory cannot be read.
+    pub fn read_dir(
+        &self,
+        first: u32,
+        len: Option<u64>,
+        no_fat_chain: bool,
+    ) -> Result<ExFatDir, FATError> {
+        let data = self.read_clusters(first, len, no_fat_chain)?;
+        let max_entries = lock(&self.limits).max_dir_entries;
+        if data.len() / ENTRY_SIZE > max_entries {
+            return Err(FATError::LimitExceeded {
+                what: "entries in a directory",
+                max: max_entries as u64,
+            });
+        }
+        Ok(parse_dir(&data, max_entries))
+    }
+
+    /// Reads the entries of a subdirectory, described by its entry set.
+    pub fn read_subdir(&self, set: &EntrySet) -> Result<ExFatDir, FATError> {
+        self.read_dir(
+            *set.first_cluster(),
+            Some(*set.data_length()),
+            *set.no_fat_chain(),
+        )
+    }
+
+    /// Checks whether a cluster number addresses the cluster heap.
+    fn is_heap_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster - 2 < *self.boot.cluster_count()
+    }
+
+    /// Groups clusters into runs of consecutive clusters.
+    ///
+    /// # Returns
+    /// - The first cluster and the number of clusters of each run, in order.
+    fn contiguous_runs(&self, clusters: &[u32]) -> Vec<(u32, u64)> {
+        let mut runs: Vec<(u32, u64)> = vec![];
+        for cluster in clusters {
+            match runs.last_mut() {
+                Some((first, cnt)) if *first as u64 + *cnt == *cluster as u64 => *cnt += 1,
+                _ => runs.push((*cluster, 1)),
+            }
+        }
+        runs
+    }
+
+    /// Writes the entry sets of a directory and of its live subdirectories.
+    fn print_dir_rec<W: io::Write>(
+        &self,
+        dir: &ExFatDir,
+        indent: usize,
+        out: &mut W,
+    ) -> Result<(), TraitError> {
+        let max_depth = lock(&self.limits).max_depth;
+        if indent / 3 > max_depth {
+            return Err(FATError::LimitExceeded {
+                what: "nested directories",
+                max: max_depth as u64,
+            }
+            .into());
+        }
+
+        for set in dir.sets.iter() {
+            writeln!(out, "{} {}", " ".repeat(indent), set)?;
+            // The clusters of a deleted directory may have been reused
+            if set.is_dir() && !set.deleted() {
+                self.print_dir_rec(&self.read_subdir(set)?, indent + 3, out)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl LayoutDisplay for ExFatVol {
+    fn display_layout(
+        &self,
+        indent: u8,
+        options: &LayoutOptions,
+    ) -> Result<String, std::fmt::Error> {
+        let mut out = String::from("");
+        let indent = " ".repeat(indent.into());
+        let sector_size = self.boot.bytes_per_sector() as u64;
+
+        writeln!(out, "{}┌{:─^68}┐", indent, " exFAT Partition Layout ")?;
+        writeln!(
+            out,
+            "{}├{:^12}┬{:^12}┬{:^12}┬{:^12}┬{:^16}┤",
+            indent, "Region", "Start", "End", "Length", "Description"
+        )?;
+        writeln!(
+            out,
+            "{}├{:─<12}┼{:─<12}┼{:─<12}┼{:─<12}┼{:─<16}┤",
+            indent, "", "", "", "", ""
+        )?;
+
+        let row = |out: &mut String, region: &str, start: u64, end: u64, desc: &str| {
+            let [start, end, len] = options.fmt_range(start, end, sector_size);
+            writeln!(
+                out,
+                "{}│{:<12}│{:>12}│{:>12}│{:>12}│{:<16}│",
+                indent, region, start, end, len, desc
+            )
+        };
+
+        // Positions are absolute sectors, in sectors of the volume
+        let start = self.offset / sector_size;
+        row(
+            &mut out,
+            "Boot",
+            start,
+            start + BOOT_REGION_SECTORS,
+            "Main Boot",
+        )?;
+        row(
+            &mut out,
+            "Backup Boot",
+            start + BOOT_REGION_SECTORS,
+            start + 2 * BOOT_REGION_SECTORS,
+            "Backup Boot",
+        )?;
+        let fat_len = *self.boot.fat_length() as u64;
+        for i in 0..*self.boot.number_of_fats() {
+            let fat_i_start = start + *self.boot.fat_offset() as u64 + i as u64 * fat_len;
+            let desc = match i == self.boot.active_fat() {
+                true => "FAT Tables",
+                false => "Inactive FAT",
+            };
+            row(
+                &mut out,
+                &format!("FAT #{}", i),
+                fat_i_start,
+                fat_i_start + fat_len,
+                desc,
+            )?;
+        }
+        let heap_start = start + *self.boot.cluster_heap_offset() as u64;
+        let heap_end =
+            heap_start + *self.boot.cluster_count() as u64 * self.boot.sectors_per_cluster() as u64;
+        row(&mut out, "Data", heap_start, heap_end, "Cluster Heap")?;
+        if options.all {
+            let sectors_per_cluster = self.boot.sectors_per_cluster() as u64;
+            let extents = [
+                Some((self.bitmap_extent, "Alloc Bitmap")),
+                self.upcase_extent.map(|extent| (extent, "Up-case Table")),
+            ];
+            for ((cluster, len), desc) in extents.into_iter().flatten() {
+                if cluster < 2 {
+                    continue;
+                }
+                let first = heap_start + (cluster - 2) as u64 * sectors_per_cluster;
+                let sectors = len.div_ceil(self.boot.cluster_size()) * sectors_per_cluster;
+                row(&mut out, "", first, first + sectors, desc)?;
+            }
+        }
+        let end = start + self.partition_sectors;
+        if heap_end < end {
+            row(&mut out, "", heap_end, end, "Volume Slack")?;
+        }
+
+        writeln!(
+            out,
+            "{}└{:─<12}┴{:─<12}┴{:─<12}┴{:─<12}┴{:─<16}┘",
+            indent, "", "", "", "", ""
+        )?;
+
+        Ok(out)
+    }
+}
+
+impl TreeDisplay for ExFatVol {
+    fn display_tree<W: io::Write>(&self, out: &mut W) -> Result<(), TraitError> {
+        self.print_dir_rec(&self.root_dir()?, 0, out)?;
+
+        Ok(())
+    }
+}
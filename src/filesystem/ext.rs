@@ -0,0 +1,590 @@
+//! ext2, ext3 and ext4 superblock detection.
+//!
+//! Linux volumes are not analyzed, but identifying them keeps the partitions of mixed-OS images
+//! from being silently skipped. The superblock, 1024 bytes from the start of the volume, gives the
+//! block size, the number of blocks and inodes and their division into block groups, and the
+//! feature flags telling ext2, ext3 and ext4 apart. Copies of the superblock are kept at the start
+//! of some block groups.
+
+use binread::{BinRead, BinReaderExt};
+use getset::Getters;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use super::fat_error::FATError;
+use crate::image::ImageSource;
+use crate::traits::{LayoutDisplay, LayoutOptions};
+
+/// The byte offset of the superblock from the start of the volume.
+pub const SUPERBLOCK_OFFSET: u64 = 1024;
+
+/// The magic number of an ext superblock.
+pub const EXT_MAGIC: u16 = 0xEF53;
+
+/// The byte offset of the magic number within the superblock.
+const MAGIC_OFFSET: usize = 56;
+
+/// Compatible feature: the volume has a journal (ext3 and later).
+const COMPAT_HAS_JOURNAL: u32 = 0x4;
+
+/// Compatible feature: the backup superblocks are only in the groups listed by the superblock.
+const COMPAT_SPARSE_SUPER2: u32 = 0x200;
+
+/// Incompatible feature: the block numbers are 64-bit, as is the size of the group descriptors.
+const INCOMPAT_64BIT: u32 = 0x80;
+
+/// Read-only compatible feature: the backup superblocks are only in groups 0, 1 and the powers of
+/// 3, 5 and 7.
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+
+/// Incompatible features introduced by ext4.
+const EXT4_INCOMPAT: u32 = 0x40 | 0x80 | 0x100 | 0x200 | 0x400 | 0x2000 | 0x4000 | 0x8000;
+
+/// Read-only compatible features introduced by ext4.
+const EXT4_RO_COMPAT: u32 = 0x8 | 0x10 | 0x20 | 0x40 | 0x200 | 0x400;
+
+/// Maximum number of backup superblocks listed, for the volumes without the sparse_super
+/// feature.
+pub const MAX_BACKUP_GROUPS: usize = 64;
+
+/// The names of the compatible features.
+const COMPAT_FEATURES: &[(u32, &str)] = &[
+    (0x1, "dir_prealloc"),
+    (0x2, "imagic_inodes"),
+    (COMPAT_HAS_JOURNAL, "has_journal"),
+    (0x8, "ext_attr"),
+    (0x10, "resize_inode"),
+    (0x20, "dir_index"),
+    (0x40, "lazy_bg"),
+    (0x80, "exclude_inode"),
+    (0x100, "exclude_bitmap"),
+    (COMPAT_SPARSE_SUPER2, "sparse_super2"),
+    (0x400, "fast_commit"),
+    (0x800, "stable_inodes"),
+    (0x1000, "orphan_file"),
+];
+
+/// The names of the incompatible features.
+const INCOMPAT_FEATURES: &[(u32, &str)] = &[
+    (0x1, "compression"),
+    (0x2, "filetype"),
+    (0x4, "needs_recovery"),
+    (0x8, "journal_dev"),
+    (0x10, "meta_bg"),
+    (0x40, "extent"),
+    (INCOMPAT_64BIT, "64bit"),
+    (0x100, "mmp"),
+    (0x200, "flex_bg"),
+    (0x400, "ea_inode"),
+    (0x1000, "dirdata"),
+    (0x2000, "metadata_csum_seed"),
+    (0x4000, "large_dir"),
+    (0x8000, "inline_data"),
+    (0x10000, "encrypt"),
+    (0x20000, "casefold"),
+];
+
+/// The names of the read-only compatible features.
+const RO_COMPAT_FEATURES: &[(u32, &str)] = &[
+    (RO_COMPAT_SPARSE_SUPER, "sparse_super"),
+    (0x2, "large_file"),
+    (0x4, "btree_dir"),
+    (0x8, "huge_file"),
+    (0x10, "uninit_bg"),
+    (0x20, "dir_nlink"),
+    (0x40, "extra_isize"),
+    (0x100, "quota"),
+    (0x200, "bigalloc"),
+    (0x400, "metadata_csum"),
+    (0x800, "replica"),
+    (0x1000, "read-only"),
+    (0x2000, "project"),
+    (0x4000, "shared_blocks"),
+    (0x8000, "verity"),
+    (0x10000, "orphan_present"),
+];
+
+/// ext superblock structure, the fields not used by the detection being skipped.
+#[derive(BinRead, Debug, Getters)]
+#[br(little)]
+pub struct ExtSuperblock {
+    /// Number of inodes of the volume
+    #[get = "pub"]
+    inodes_count: u32,
+    /// Number of blocks of the volume, low 32 bits
+    blocks_count_lo: u32,
+    /// Number of free blocks, low 32 bits, after the reserved block count
+    #[br(pad_before = 4)]
+    #[get = "pub"]
+    free_blocks_count_lo: u32,
+    /// Number of free inodes
+    #[get = "pub"]
+    free_inodes_count: u32,
+    /// Block holding the superblock: 1 with blocks of 1024 bytes, 0 otherwise
+    #[get = "pub"]
+    first_data_block: u32,
+    /// Base-2 logarithm of the block size, minus 10
+    log_block_size: u32,
+    /// Number of blocks per group, after the cluster size
+    #[br(pad_before = 4)]
+    #[get = "pub"]
+    blocks_per_group: u32,
+    /// Number of inodes per group, after the clusters per group
+    #[br(pad_before = 4)]
+    #[get = "pub"]
+    inodes_per_group: u32,
+    /// State of the volume: 0x1 if cleanly unmounted, 0x2 if errors were detected, after the
+    /// mount and write times, the mount counts and the magic number
+    #[br(pad_before = 14)]
+    #[get = "pub"]
+    state: u16,
+    /// Operating system that created the volume (0 for Linux), after the error behaviour, the
+    /// minor revision and the check times
+    #[br(pad_before = 12)]
+    #[get = "pub"]
+    creator_os: u32,
+    /// Revision level: 0 for the original format, 1 for inodes of variable size and features
+    #[get = "pub"]
+    rev_level: u32,
+    /// Size of an inode in bytes, 128 with revision 0, after the reserved UID and GID and the
+    /// first non-reserved inode
+    #[br(pad_before = 8)]
+    inode_size: u16,
+    /// Compatible features, after the group of this superblock copy
+    #[br(pad_before = 2)]
+    #[get = "pub"]
+    feature_compat: u32,
+    /// Incompatible features, the volume can't be mounted without supporting them
+    #[get = "pub"]
+    feature_incompat: u32,
+    /// Read-only compatible features, the volume can only be mounted read-only without them
+    #[get = "pub"]
+    feature_ro_compat: u32,
+    /// Volume UUID
+    #[get = "pub"]
+    uuid: [u8; 16],
+    /// Volume label, NUL-padded
+    #[get = "pub"]
+    volume_name: [u8; 16],
+    /// Directory where the volume was last mounted, NUL-padded
+    #[get = "pub"]
+    #[br(count = 64)]
+    last_mounted: Vec<u8>,
+    /// Number of blocks reserved for the growth of the group descriptor table, after the
+    /// preallocation settings
+    #[br(pad_before = 6)]
+    #[get = "pub"]
+    reserved_gdt_blocks: u16,
+    /// Size of a group descriptor in bytes with the 64bit feature, after the journal settings
+    /// and the hash seed
+    #[br(pad_before = 46)]
+    desc_size: u16,
+    /// Number of blocks of the volume, high 32 bits with the 64bit feature, after the mount
+    /// options, the first meta group, the creation time and the journal inode backup
+    #[br(pad_before = 80)]
+    blocks_count_hi: u32,
+}
+
+impl ExtSuperblock {
+    /// Checks whether a buffer holds the magic number of an ext superblock.
+    ///
+    /// # Parameters
+    /// - `superblock`: The content of the superblock, 1024 bytes from the start of the volume.
+    pub fn is_ext(superblock: &[u8]) -> bool {
+        superblock.get(MAGIC_OFFSET..MAGIC_OFFSET + 2) == Some(EXT_MAGIC.to_le_bytes().as_slice())
+    }
+
+    /// Returns the size in bytes of a block.
+    pub fn block_size(&self) -> u64 {
+        1024u64.checked_shl(self.log_block_size).unwrap_or(0)
+    }
+
+    /// Returns the number of blocks of the volume.
+    pub fn blocks_count(&self) -> u64 {
+        let hi = match self.feature_incompat & INCOMPAT_64BIT != 0 {
+            true => self.blocks_count_hi as u64,
+            false => 0,
+        };
+        hi << 32 | self.blocks_count_lo as u64
+    }
+
+    /// Returns the size in bytes of an inode.
+    pub fn inode_size(&self) -> u16 {
+        match self.rev_level {
+            0 => 128,
+            _ => self.inode_size,
+        }
+    }
+
+    /// Returns the size in bytes of a group descriptor.
+    pub fn desc_size(&self) -> u64 {
+        match self.feature_incompat & INCOMPAT_64BIT != 0 {
+            true => (self.desc_size as u64).max(64),
+            false => 32,
+        }
+    }
+
+    /// Returns the number of block groups of the volume.
+    pub fn group_count(&self) -> u64 {
+        self.blocks_count()
+            .saturating_sub(self.first_data_block as u64)
+            .div_ceil((self.blocks_per_group as u64).max(1))
+    }
+
+    /// Returns the version of the filesystem, from its features: "ext4" if it uses a feature
+    /// introduced by ext4, "ext3" if it has a journal, "ext2" otherwise.
+    pub fn version(&self) -> &'static str {
+        if self.feature_incompat & EXT4_INCOMPAT != 0
+            || self.feature_ro_compat & EXT4_RO_COMPAT != 0
+        {
+            "ext4"
+        } else if self.feature_compat & COMPAT_HAS_JOURNAL != 0 {
+            "ext3"
+        } else {
+            "ext2"
+        }
+    }
+
+    /// Returns the names of the features of the volume, compatible, incompatible then read-only
+    /// compatible ones. Unknown flags are named after their category and value.
+    pub fn features(&self) -> Vec<String> {
+        [
+            ("compat", self.feature_compat, COMPAT_FEATURES),
+            ("incompat", self.feature_incompat, INCOMPAT_FEATURES),
+            ("ro_compat", self.feature_ro_compat, RO_COMPAT_FEATURES),
+        ]
+        .iter()
+        .flat_map(|(category, flags, names)| {
+            (0..32)
+                .map(|bit| 1u32 << bit)
+                .filter(move |flag| flags & flag != 0)
+                .map(
+                    move |flag| match names.iter().find(|(value, _)| *value == flag) {
+                        Some((_, name)) => name.to_string(),
+                        None => format!("{category}_0x{flag:X}"),
+                    },
+                )
+        })
+        .collect()
+    }
+
+    /// Returns the volume label, without its padding.
+    pub fn label(&self) -> String {
+        nul_terminated(&self.volume_name)
+    }
+
+    /// Returns the block groups holding a backup copy of the superblock, in order.
+    ///
+    /// # Returns
+    /// - Groups 1 and the powers of 3, 5 and 7 with the sparse_super feature, every group but
+    ///   the first one otherwise, at most 64 of them. None with the
+    ///   sparse_super2 feature, whose two backup groups are listed in fields not parsed here.
+    pub fn backup_groups(&self) -> Vec<u64> {
+        let count = self.group_count();
+        if self.feature_compat & COMPAT_SPARSE_SUPER2 != 0 {
+            return vec![];
+        }
+        if self.feature_ro_compat & RO_COMPAT_SPARSE_SUPER == 0 {
+            return (1..count).take(MAX_BACKUP_GROUPS).collect();
+        }
+
+        let mut groups = vec![1];
+        for base in [3u64, 5, 7] {
+            let mut power = base;
+            while power < count {
+                groups.push(power);
+                power = power.saturating_mul(base);
+            }
+        }
+        groups.retain(|group| *group < count);
+        groups.sort_unstable();
+        groups
+    }
+
+    /// Validates the fields of the superblock.
+    ///
+    /// # Parameters
+    /// - `partition_len`: The size in bytes of the partition holding the volume.
+    fn validate(&self, partition_len: u64) -> Result<(), FATError> {
+        let invalid = |msg: String| Err(FATError::InvalidBootSector(msg));
+
+        if self.log_block_size > 6 {
+            return invalid(format!(
+                "blocks of 2^{} bytes exceed 64 KiB",
+                self.log_block_size as u64 + 10
+            ));
+        }
+        let expected_first = match self.block_size() {
+            1024 => 1,
+            _ => 0,
+        };
+        if self.first_data_block != expected_first {
+            return invalid(format!(
+                "the first data block is {} with blocks of {} bytes",
+                self.first_data_block,
+                self.block_size()
+            ));
+        }
+        if self.blocks_count() == 0
+            || self.blocks_count().saturating_mul(self.block_size()) > partition_len
+        {
+            return Err(FATError::InvalidTotSec(format!(
+                "{} blocks of {} bytes in a partition of {partition_len} bytes",
+                self.blocks_count(),
+                self.block_size()
+            )));
+        }
+        if self.blocks_per_group == 0 || self.inodes_per_group == 0 {
+            return invalid(format!(
+                "{} blocks and {} inodes per group",
+                self.blocks_per_group, self.inodes_per_group
+            ));
+        }
+        if self.inodes_count as u64 > self.group_count() * self.inodes_per_group as u64 {
+            return invalid(format!(
+                "{} inodes for {} groups of {} inodes",
+                self.inodes_count,
+                self.group_count(),
+                self.inodes_per_group
+            ));
+        }
+        let inode_size = self.inode_size() as u64;
+        if !inode_size.is_power_of_two() || inode_size < 128 || inode_size > self.block_size() {
+            return invalid(format!("invalid inode size: {inode_size} bytes"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes a NUL-padded string of the superblock.
+fn nul_terminated(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Represents an ext2, ext3 or ext4 volume within a disk image, identified but not analyzed.
+#[derive(Debug, Getters)]
+pub struct ExtVol {
+    /// The superblock of the volume.
+    #[get = "pub"]
+    superblock: ExtSuperblock,
+    /// The absolute byte offset of the volume in the image.
+    #[get = "pub"]
+    offset: u64,
+    /// The size in bytes of the partition holding the volume.
+    #[get = "pub"]
+    partition_len: u64,
+    /// The size in bytes of a sector of the disk, the unit of the layout.
+    #[get = "pub"]
+    sector_size: usize,
+}
+
+impl ExtVol {
+    /// Reads the superblock of an ext volume.
+    ///
+    /// # Parameters
+    /// - `image`: The disk image containing the filesystem
+    /// - `start`: The sector number where the volume starts
+    /// - `sector_cnt`: The number of sectors of the partition
+    /// - `validate`: Whether to validate the superblock
+    /// - `sector_size`: The size of each sector of the disk in bytes
+    ///
+    /// # Returns
+    /// - `Ok(ExtVol)`: The ext volume
+    /// - `Err(FATError)`: If reading fails, the superblock has no ext magic number or validation
+    ///   fails
+    pub fn from_backend(
+        image: Arc<ImageSource>,
+        start: u32,
+        sector_cnt: u32,
+        validate: bool,
+        sector_size: usize,
+    ) -> Result<ExtVol, FATError> {
+        let offset = start as u64 * sector_size as u64;
+        let buf = image.read_at(offset + SUPERBLOCK_OFFSET, 1024)?;
+        if !ExtSuperblock::is_ext(&buf) {
+            return Err(FATError::InvalidBootSector(String::from(
+                "the superblock has no ext magic number",
+            )));
+        }
+        let superblock: ExtSuperblock = std::io::Cursor::new(&buf).read_be()?;
+        let partition_len = sector_cnt as u64 * sector_size as u64;
+        if validate {
+            superblock.validate(partition_len)?;
+        }
+
+        Ok(ExtVol {
+            superblock,
+            offset,
+            partition_len,
+            sector_size,
+        })
+    }
+
+    /// Checks whether the volume at the given position has an ext superblock.
+    ///
+    /// # Parameters
+    /// - `image`: The disk image
+    /// - `start`: The sector number where the candidate volume starts
+    /// - `sector_size`: The size of each sector of the disk in bytes
+    pub fn is_ext(image: &ImageSource, start: u32, sector_size: usize) -> bool {
+        image
+            .read_at(start as u64 * sector_size as u64 + SUPERBLOCK_OFFSET, 1024)
+            .is_ok_and(|superblock| ExtSuperblock::is_ext(&superblock))
+    }
+
+    /// Returns the size in bytes of the volume.
+    pub fn volume_len(&self) -> u64 {
+        self.superblock
+            .blocks_count()
+            .saturating_mul(self.superblock.block_size())
+    }
+}
+
+impl LayoutDisplay for ExtVol {
+    fn display_layout(
+        &self,
+        indent: u8,
+        options: &LayoutOptions,
+    ) -> Result<String, std::fmt::Error> {
+        let mut out = String::from("");
+        let indent = " ".repeat(indent.into());
+        let sb = &self.superblock;
+        let sector_size = self.sector_size as u64;
+
+        let title = format!(" {} Partition Layout ", sb.version());
+        writeln!(out, "{}┌{:─^68}┐", indent, title)?;
+        let info = [
+            ("Block Size", sb.block_size().to_string()),
+            ("Blocks", sb.blocks_count().to_string()),
+            ("Inodes", sb.inodes_count.to_string()),
+            ("Inode Size", sb.inode_size().to_string()),
+            ("Block Groups", sb.group_count().to_string()),
+        ];
+        for (label, value) in info {
+            writeln!(out, "{}├{:<56}{:>12}┤", indent, label, value)?;
+        }
+        let label = sb.label();
+        if !label.is_empty() {
+            writeln!(out, "{}├{:<36}{:>32}┤", indent, "Volume Name", label)?;
+        }
+        let state = match sb.state {
+            state if state & 0x2 != 0 => "errors",
+            state if state & 0x1 != 0 => "clean",
+            _ => "not clean",
+        };
+        writeln!(out, "{}├{:<56}{:>12}┤", indent, "State", state)?;
+
+        // The features are wrapped at the width of the table
+        let mut line = String::from("Features:");
+        for feature in sb.features() {
+            if line.len() + 1 + feature.len() > 68 {
+                writeln!(out, "{}├{:<68}┤", indent, line)?;
+                line = String::from("         ");
+            }
+            line.push(' ');
+            line.push_str(&feature);
+        }
+        writeln!(out, "{}├{:<68}┤", indent, line)?;
+
+        writeln!(out, "{}├{:─^68}┤", indent, "")?;
+        writeln!(
+            out,
+            "{}├{:^12}┬{:^12}┬{:^12}┬{:^12}┬{:^16}┤",
+            indent, "Region", "Start", "End", "Length", "Description"
+        )?;
+        writeln!(
+            out,
+            "{}├{:─<12}┼{:─<12}┼{:─<12}┼{:─<12}┼{:─<16}┤",
+            indent, "", "", "", "", ""
+        )?;
+
+        // Positions are absolute byte offsets, shown in sectors of the disk
+        let row = |out: &mut String, region: &str, start: u64, end: u64, desc: &str| {
+            let [start, end, len] =
+                options.fmt_range(start / sector_size, end.div_ceil(sector_size), sector_size);
+            writeln!(
+                out,
+                "{}│{:<12}│{:>12}│{:>12}│{:>12}│{:<16}│",
+                indent, region, start, end, len, desc
+            )
+        };
+
+        let block_size = sb.block_size();
+        let block = |number: u64| {
+            self.offset
+                .saturating_add(number.saturating_mul(block_size))
+        };
+        let superblock_start = self.offset + SUPERBLOCK_OFFSET;
+        row(
+            &mut out,
+            "Boot",
+            self.offset,
+            superblock_start,
+            "Boot Block",
+        )?;
+        row(
+            &mut out,
+            "Superblock",
+            superblock_start,
+            superblock_start + 1024,
+            "Superblock",
+        )?;
+        let gdt_start = *sb.first_data_block() as u64 + 1;
+        let gdt_end = gdt_start
+            + sb.group_count()
+                .saturating_mul(sb.desc_size())
+                .div_ceil(block_size.max(1));
+        row(
+            &mut out,
+            "GDT",
+            block(gdt_start),
+            block(gdt_end),
+            "Descriptors",
+        )?;
+        let reserved_end = gdt_end + *sb.reserved_gdt_blocks() as u64;
+        if options.all && reserved_end > gdt_end {
+            row(
+                &mut out,
+                "Reserved GDT",
+                block(gdt_end),
+                block(reserved_end),
+                "GDT Growth",
+            )?;
+        }
+        let volume_end = self.offset.saturating_add(self.volume_len());
+        row(
+            &mut out,
+            "Groups",
+            block(reserved_end),
+            volume_end,
+            "Block Groups",
+        )?;
+        if options.all {
+            for group in sb.backup_groups() {
+                let group_start = *sb.first_data_block() as u64
+                    + group.saturating_mul(*sb.blocks_per_group() as u64);
+                row(
+                    &mut out,
+                    "Backup SB",
+                    block(group_start),
+                    block(group_start + 1),
+                    &format!("Group {group}"),
+                )?;
+            }
+        }
+        let end = self.offset + self.partition_len;
+        if volume_end < end {
+            row(&mut out, "", volume_end, end, "Volume Slack")?;
+        }
+
+        writeln!(
+            out,
+            "{}└{:─<12}┴{:─<12}┴{:─<12}┴{:─<12}┴{:─<16}┘",
+            indent, "", "", "", "", ""
+        )?;
+
+        Ok(out)
+    }
+}
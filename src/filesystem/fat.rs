@@ -7,28 +7,97 @@
 //! - Writing to slack space
 //! - Displaying the volume layout
 
+use sha2::{Digest, Sha256};
 use std::fmt::Write as FmtWrite;
-use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 use std::{io, result};
 
-use super::bpb::Bpb;
+use super::bpb::{Bpb, ReservedSector};
+use super::cluster_iter::ClusterIter;
+use super::clusters::ClusterKind;
 use super::dir_entry::DirEntry;
 use super::fat_error::FATError;
 use super::fat_type::FATType;
+use super::fsinfo::FsInfo;
+use crate::cache::{CacheStats, DEFAULT_CACHE_CAPACITY, SectorCache};
+use crate::cancel::CancellationToken;
+use crate::events::Event;
 use crate::filesystem::dir_entry;
-use crate::traits::{LayoutDisplay, SlackWriter, TraitError, TreeDisplay};
-use crate::utils::{read_sector, u32_at, write_at};
+use crate::image::ImageSource;
+use crate::limits::Limits;
+use crate::traits::{
+    EventSink, LayoutDisplay, LayoutOptions, SlackWrite, SlackWriter, TraitError, TreeDisplay,
+};
+use crate::utils::{escape_path, read_at, u16_at, u32_at, write_at, write_pattern};
+
+/// Maximum number of clusters merged into a single read.
+const MAX_RUN_LEN: u32 = 2048;
+
+/// Number of directory clusters read at once when listing a directory.
+const DIR_READ_LEN: usize = 16;
+
+/// Number of FAT sectors read at once when loading the whole FAT.
+pub(super) const FAT_READ_LEN: u64 = 64;
+
+/// Default number of bytes read at once when streaming a file (see [`FATVol::copy_entry`]).
+pub const DEFAULT_COPY_BUFFER_LEN: usize = 1024 * 1024;
+
+/// Groups a list of clusters into runs of consecutive cluster numbers.
+///
+/// # Parameters
+/// - `clusters`: The clusters to group, in order.
+/// - `max_len`: The maximum number of clusters in a run.
+///
+/// # Returns
+/// - The first cluster and the length of each run.
+pub(crate) fn contiguous_runs(clusters: &[u32], max_len: u32) -> Vec<(u32, u32)> {
+    let mut runs: Vec<(u32, u32)> = vec![];
+    for cluster in clusters {
+        match runs.last_mut() {
+            Some((first, count)) if *first + *count == *cluster && *count < max_len => *count += 1,
+            _ => runs.push((*cluster, 1)),
+        }
+    }
+
+    runs
+}
+
+/// Locks a mutex of a volume, even if a thread panicked while holding it: the caches and settings
+/// it guards stay consistent between two operations.
+pub(super) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// Volumes are shared across threads, a field breaking it must not go unnoticed
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<FATVol>();
+};
 
 /// Structure for a FAT volume.
 ///
-/// Essentially, it is a wrapper around the Bpb.
+/// Essentially, it is a wrapper around the Bpb. Its caches and settings are behind locks, so one
+/// parsed volume can be shared by the worker threads of a server or batch frontend.
 pub struct FATVol {
-    bpb: Bpb,
+    pub(super) bpb: Bpb,
     start: u32,
     end: u32,
-    disk_path: PathBuf,
+    /// The disk image the volume is read from.
+    pub(super) image: Arc<ImageSource>,
+    /// Recently read sectors of the disk, shared by every read path.
+    cache: Mutex<SectorCache>,
+    /// Root directory cluster used instead of the one of the BPB, e.g. when it is corrupt.
+    pub(super) root_override: Mutex<Option<u32>>,
+    /// FAT copy read instead of the active one of the BPB, e.g. when the copies disagree.
+    fat_override: Mutex<Option<u8>>,
+    /// The limits enforced by the walks and scans of the volume.
+    limits: Mutex<Limits>,
+    /// The sink receiving the events of the walks and scans, if any.
+    event_sink: RwLock<Option<Arc<dyn EventSink>>>,
+    /// The token stopping the walks and scans when cancelled.
+    cancel_token: Mutex<CancellationToken>,
 }
 
 impl FATVol {
@@ -54,207 +123,1355 @@ impl FATVol {
         validate: bool,
         sector_size: usize,
     ) -> Result<FATVol, FATError> {
-        let mut file = File::open(disk_path)?;
-        let bpb = Bpb::from(&mut file, start, validate, sector_size)?;
+        let image = Arc::new(ImageSource::open(disk_path)?);
+        Self::from_backend(image, start, sector_cnt, validate, sector_size)
+    }
+
+    /// Reads the Bpb from an opened disk image and optionally validates the volume.
+    ///
+    /// The volumes of a disk share the image, so that a compressed image is indexed once. An
+    /// application holding a parsed [`Disk`](crate::Disk) opens any range of its image as a volume
+    /// by passing [`Disk::image`](crate::Disk::image), without reading the partition table again.
+    ///
+    /// # Parameters
+    /// - `image`: The disk image containing the filesystem
+    /// - `start`: The sector number where the Bpb is located
+    /// - `sector_cnt`: The number of sectors of the volume
+    /// - `validate`: Whether to perform validation checks on the Bpb
+    /// - `sector_size`: The size of each sector in bytes
+    ///
+    /// # Returns
+    /// - `Ok(FATVol)`: The FAT volume
+    /// - `Err(FATError)`: If reading fails or validation fails
+    pub fn from_backend(
+        image: Arc<ImageSource>,
+        start: u32,
+        sector_cnt: u32,
+        validate: bool,
+        sector_size: usize,
+    ) -> Result<FATVol, FATError> {
+        let bpb = Bpb::from(&mut image.reader()?, start, validate, sector_size)?;
+        let end = start
+            .checked_add(sector_cnt)
+            .ok_or_else(|| FATError::InvalidTotSec(format!("{start} + {sector_cnt} sectors")))?;
+
+        Ok(Self {
+            bpb,
+            start,
+            end,
+            image,
+            cache: Mutex::new(SectorCache::new(DEFAULT_CACHE_CAPACITY)),
+            root_override: Mutex::new(None),
+            fat_override: Mutex::new(None),
+            limits: Mutex::new(Limits::default()),
+            event_sink: RwLock::new(None),
+            cancel_token: Mutex::new(CancellationToken::new()),
+        })
+    }
+
+    /// Find a file in the FAT volume and return its first cluster number.
+    ///
+    /// # Parameters
+    /// - `file_path`: The path of the file to find
+    ///
+    /// # Returns
+    /// - `u32`: The first cluster number of the file if found, otherwise `0`.
+    pub fn find_file(&self, file_path: &Path) -> Result<DirEntry, FATError> {
+        if file_path.components().count() == 0 {
+            return Err(FATError::FileNotFound);
+        }
+
+        self.find_file_rec(file_path, self.root_cluster()?)
+    }
+
+    fn find_file_rec(
+        &self,
+        file_path: &Path,
+        fst_cluster: u32,
+    ) -> Result<dir_entry::DirEntry, FATError> {
+        let mut parts = file_path.components();
+        let current_part = match parts.next() {
+            Some(part) => part,
+            None => return Err(FATError::FileNotFound),
+        };
+        let remaining: PathBuf = parts.clone().collect();
+
+        let dir_entries: Vec<DirEntry> = if parts.count() > 0 {
+            self.list_dir(fst_cluster)?
+                .iter()
+                .filter(|entry| entry.is_dir())
+                .cloned()
+                .collect()
+        } else {
+            self.list_dir(fst_cluster)?
+                .iter()
+                .filter(|entry| !entry.is_dir())
+                .cloned()
+                .collect()
+        };
+
+        for dir_entry in dir_entries.iter() {
+            if dir_entry.same_short_name(current_part.as_os_str().to_str().unwrap()) {
+                if dir_entry.is_dir() {
+                    return self.find_file_rec(remaining.as_path(), dir_entry.cluster_number());
+                } else {
+                    return Ok(dir_entry.clone());
+                }
+            }
+        }
+
+        Err(FATError::FileNotFound)
+    }
+
+    pub fn list_dir(&self, first_cluster: u32) -> Result<Vec<DirEntry>, FATError> {
+        let mut dir_entries = vec![];
+        let _ = self.visit_dir(first_cluster, |entry| {
+            dir_entries.push(entry);
+            Ok(ControlFlow::Continue(()))
+        })?;
+        Ok(dir_entries)
+    }
+
+    /// Reads the entries of a directory as its clusters are read, handing each one to `visit`.
+    ///
+    /// Unlike [`FATVol::list_dir`], the entries of the first clusters are available before the
+    /// rest of the chain is read, and a very large directory can be abandoned midway.
+    ///
+    /// # Parameters
+    /// - `first_cluster`: The first cluster of the directory, 0 for the root directory of a
+    ///   FAT12/16 volume.
+    /// - `visit`: Called on each entry, in order, long name entries included. Returns
+    ///   `ControlFlow::Break` to stop reading the directory.
+    ///
+    /// # Returns
+    /// - `Ok(ControlFlow<()>)`: `Break` if `visit` stopped the reading, `Continue` otherwise.
+    /// - `Err(FATError)`: If the directory cannot be read, an entry is invalid, or `visit` fails.
+    pub fn visit_dir(
+        &self,
+        first_cluster: u32,
+        mut visit: impl FnMut(DirEntry) -> Result<ControlFlow<()>, FATError>,
+    ) -> Result<ControlFlow<()>, FATError> {
+        // Cluster 0 is the fixed root directory region of FAT12/16 volumes
+        let mut root_dir = match first_cluster {
+            0 => Some(
+                self.root_dir_extent()
+                    .ok_or(FATError::InvalidClusterError(0))?,
+            ),
+            1 => return Err(FATError::InvalidClusterError(1)),
+            _ => None,
+        };
+
+        let max_entries = lock(&self.limits).max_dir_entries;
+        let mut entry_count = 0;
+        let mut long_name_parts = vec![];
+        let mut chain = self.cluster_chain(first_cluster);
+        loop {
+            self.check_cancelled()?;
+            // The blocks read next: their absolute byte offsets, content and length
+            let (offsets, data, block_len) = match root_dir.take() {
+                Some((offset, len)) => (
+                    vec![offset],
+                    self.read_bytes(offset, len as usize)?,
+                    len.max(1) as usize,
+                ),
+                None if first_cluster == 0 => return Ok(ControlFlow::Continue(())),
+                None => {
+                    let clusters = chain
+                        .by_ref()
+                        .take(DIR_READ_LEN)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if clusters.is_empty() {
+                        return Ok(ControlFlow::Continue(()));
+                    }
+                    (
+                        clusters.iter().map(|c| self.cluster_offset(*c)).collect(),
+                        self.read_chain(&clusters)?,
+                        self.cluster_size() as usize,
+                    )
+                }
+            };
+
+            for (block_offset, buf) in offsets.iter().zip(data.chunks(block_len)) {
+                for off in (0..buf.len()).step_by(32) {
+                    if u32_at(buf, off) == 0 {
+                        long_name_parts.clear();
+                        continue;
+                    }
+
+                    if entry_count == max_entries {
+                        return Err(FATError::LimitExceeded {
+                            what: "entries in a directory",
+                            max: max_entries as u64,
+                        });
+                    }
+                    entry_count += 1;
+                    let mut entry = DirEntry::from_slice(&buf[off..])?;
+                    entry.set_location(block_offset + off as u64);
+                    if entry.is_long_name() {
+                        long_name_parts.push(entry.clone());
+                    } else {
+                        entry.set_long_name(DirEntry::long_name_from(&long_name_parts, &entry));
+                        if entry.long_name().is_none() && !long_name_parts.is_empty() {
+                            self.emit(|| Event::Warning {
+                                offset: Some(*entry.location()),
+                                message: format!(
+                                    "{} long name entries don't belong to the entry {}",
+                                    long_name_parts.len(),
+                                    entry.short_name()
+                                ),
+                            });
+                        }
+                        long_name_parts.clear();
+                    }
+                    if visit(entry)?.is_break() {
+                        return Ok(ControlFlow::Break(()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the content of a file.
+    ///
+    /// # Parameters
+    /// - `file_path`: The path of the file to read.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The content of the file, truncated to its size.
+    /// - `Err(FATError)`: If the file cannot be found or read.
+    pub fn read_file(&self, file_path: &Path) -> Result<Vec<u8>, FATError> {
+        self.read_entry(&self.find_file(file_path)?)
+    }
+
+    /// Reads the content of the file described by a directory entry.
+    ///
+    /// # Parameters
+    /// - `entry`: The directory entry of the file.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The content of the file, truncated to its size.
+    /// - `Err(FATError)`: If the cluster chain cannot be read.
+    pub fn read_entry(&self, entry: &DirEntry) -> Result<Vec<u8>, FATError> {
+        if entry.cluster_number() == 0 {
+            return Ok(vec![]);
+        }
+
+        let clusters = self
+            .cluster_chain(entry.cluster_number())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut data = self.read_chain(&clusters)?;
+        data.truncate(*entry.file_size() as usize);
+        Ok(data)
+    }
+
+    /// Streams the content of a file into a writer, a buffer at a time, so that files of any
+    /// size are extracted in bounded memory.
+    ///
+    /// # Parameters
+    /// - `file_path`: The path of the file to extract.
+    /// - `out`: The sink the content is written to.
+    /// - `buffer_len`: The number of bytes read at once, rounded up to whole clusters.
+    /// - `progress`: Called after each buffer with the bytes written so far and the file size.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of bytes written, see [`FATVol::copy_entry`].
+    /// - `Err(FATError)`: If the file cannot be found, read or written.
+    pub fn copy_file<W: io::Write>(
+        &self,
+        file_path: &Path,
+        out: &mut W,
+        buffer_len: usize,
+        progress: impl FnMut(u64, u64),
+    ) -> Result<u64, FATError> {
+        self.copy_entry(&self.find_file(file_path)?, out, buffer_len, progress)
+    }
+
+    /// Streams the content of the file described by a directory entry into a writer, following
+    /// its cluster chain a buffer at a time.
+    ///
+    /// # Parameters
+    /// - `entry`: The directory entry of the file.
+    /// - `out`: The sink the content is written to.
+    /// - `buffer_len`: The number of bytes read at once, rounded up to whole clusters.
+    /// - `progress`: Called after each buffer with the bytes written so far and the file size.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of bytes written: the file size, or less if its chain is shorter.
+    /// - `Err(FATError)`: If the cluster chain cannot be read, the sink fails, or the operation is
+    ///   cancelled.
+    pub fn copy_entry<W: io::Write>(
+        &self,
+        entry: &DirEntry,
+        out: &mut W,
+        buffer_len: usize,
+        mut progress: impl FnMut(u64, u64),
+    ) -> Result<u64, FATError> {
+        let total = *entry.file_size() as u64;
+        let clusters_per_read = buffer_len
+            .div_ceil(self.cluster_size().max(1) as usize)
+            .max(1);
+        let mut chain = match entry.cluster_number() {
+            0 => None,
+            first => Some(self.cluster_chain(first)),
+        };
+
+        let mut written = 0;
+        let mut clusters = Vec::with_capacity(clusters_per_read);
+        while let Some(chain) = chain.as_mut().filter(|_| written < total) {
+            self.check_cancelled()?;
+            clusters.clear();
+            for cluster in chain.by_ref().take(clusters_per_read) {
+                clusters.push(cluster?);
+            }
+            if clusters.is_empty() {
+                break;
+            }
+
+            let data = self.read_chain(&clusters)?;
+            let len = data.len().min((total - written) as usize);
+            out.write_all(&data[..len])?;
+            written += len as u64;
+            progress(written, total);
+        }
+        out.flush()?;
+
+        Ok(written)
+    }
+
+    /// Reads a list of clusters, merging contiguous clusters into a single read.
+    ///
+    /// # Parameters
+    /// - `clusters`: The clusters to read, in order.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The concatenated content of the clusters.
+    /// - `Err(io::Error)`: If a cluster cannot be read.
+    pub(crate) fn read_chain(&self, clusters: &[u32]) -> io::Result<Vec<u8>> {
+        let cluster_size = self.cluster_size() as usize;
+        let sec_per_clus = *self.bpb.sec_per_clus() as u64;
+        let runs = contiguous_runs(clusters, MAX_RUN_LEN);
+
+        // Small runs go through the cache, large ones are read in a single batch
+        let mut parts: Vec<Option<Vec<u8>>> = Vec::with_capacity(runs.len());
+        let mut requests = vec![];
+        for (first, count) in &runs {
+            let sector_cnt = *count as u64 * sec_per_clus;
+            if self.is_cacheable(sector_cnt) {
+                parts.push(Some(self.read_sectors_cached(
+                    self.clus_to_sector(*first) as u64,
+                    sector_cnt,
+                )?));
+            } else {
+                parts.push(None);
+                requests.push((self.cluster_offset(*first), *count as usize * cluster_size));
+            }
+        }
+
+        let mut batch = self.image.read_batch(&requests)?.into_iter();
+        Ok(parts
+            .into_iter()
+            .map(|part| part.or_else(|| batch.next()).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .concat())
+    }
+
+    /// Reads consecutive sectors of the volume.
+    ///
+    /// # Parameters
+    /// - `start`: The absolute number of the first sector.
+    /// - `count`: The number of sectors to read.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The concatenated content of the sectors.
+    /// - `Err(FATError::SectorOutOfRange)`: If a sector lies outside of the volume.
+    /// - `Err(FATError::IOError)`: If the sectors cannot be read.
+    pub fn read_sectors(&self, start: u64, count: u64) -> Result<Vec<u8>, FATError> {
+        let end = start.saturating_add(count);
+        if start < self.start as u64 || end > self.end as u64 {
+            return Err(FATError::SectorOutOfRange { start, end });
+        }
+
+        if self.is_cacheable(count) {
+            Ok(self.read_sectors_cached(start, count)?)
+        } else {
+            let bytes_per_sec = *self.bpb.bytes_per_sec() as u64;
+            Ok(self.read_uncached(start * bytes_per_sec, (count * bytes_per_sec) as usize)?)
+        }
+    }
+
+    /// Reads a cluster of the data region.
+    ///
+    /// # Parameters
+    /// - `cluster`: The cluster number.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The content of the cluster.
+    /// - `Err(FATError::ClusterOutOfRange)`: If the cluster isn't part of the data region.
+    /// - `Err(FATError)`: If the cluster lies outside of the volume or cannot be read.
+    pub fn read_cluster(&self, cluster: u32) -> Result<Vec<u8>, FATError> {
+        if !self.is_valid_cluster(cluster) {
+            return Err(FATError::ClusterOutOfRange(cluster));
+        }
+
+        self.read_sectors(
+            self.clus_to_sector(cluster) as u64,
+            *self.bpb.sec_per_clus() as u64,
+        )
+    }
+
+    /// Reads `len` bytes of the disk image starting at the given absolute byte offset.
+    pub(crate) fn read_bytes(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let bytes_per_sec = *self.bpb.bytes_per_sec() as u64;
+        if bytes_per_sec > 0 && len > 0 {
+            let first = offset / bytes_per_sec;
+            let count = (offset + len as u64).div_ceil(bytes_per_sec) - first;
+            if self.is_cacheable(count) {
+                let skip = (offset - first * bytes_per_sec) as usize;
+                return Ok(self.read_sectors_cached(first, count)?[skip..skip + len].to_vec());
+            }
+        }
+
+        self.read_uncached(offset, len)
+    }
+
+    /// Returns the ranges of the disk image holding data (see [`ImageSource::data_extents`]).
+    pub(crate) fn data_extents(&self, offset: u64, len: u64) -> io::Result<Vec<(u64, u64)>> {
+        self.image.data_extents(offset, len)
+    }
+
+    /// Drops the clusters lying entirely in holes of a sparse image, which read as zeros.
+    ///
+    /// # Parameters
+    /// - `clusters`: The clusters to filter, sorted.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u32>)`: The clusters holding data, in order.
+    /// - `Err(io::Error)`: If the holes of the image cannot be listed.
+    pub(crate) fn data_clusters(&self, clusters: &[u32]) -> io::Result<Vec<u32>> {
+        let (Some(first), Some(last)) = (clusters.first(), clusters.last()) else {
+            return Ok(vec![]);
+        };
+        let cluster_size = self.cluster_size() as u64;
+        let start = self.cluster_offset(*first);
+        let extents =
+            self.data_extents(start, self.cluster_offset(*last) + cluster_size - start)?;
+
+        Ok(clusters
+            .iter()
+            .copied()
+            .filter(|cluster| {
+                let offset = self.cluster_offset(*cluster);
+                let idx = extents.partition_point(|(start, len)| start + len <= offset);
+                extents
+                    .get(idx)
+                    .is_some_and(|(start, _)| *start < offset + cluster_size)
+            })
+            .collect())
+    }
+
+    /// Reads consecutive sectors of the disk, serving the ones already cached from memory.
+    ///
+    /// # Parameters
+    /// - `first`: The absolute number of the first sector.
+    /// - `count`: The number of sectors to read.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The concatenated content of the sectors.
+    /// - `Err(io::Error)`: If a sector cannot be read.
+    fn read_sectors_cached(&self, first: u64, count: u64) -> io::Result<Vec<u8>> {
+        let bytes_per_sec = *self.bpb.bytes_per_sec() as usize;
+        let mut sectors: Vec<Option<Vec<u8>>> = {
+            let mut cache = lock(&self.cache);
+            (first..first + count)
+                .map(|sector| cache.get(sector))
+                .collect()
+        };
+
+        // Read every run of missing sectors at once
+        let mut idx = 0;
+        while idx < sectors.len() {
+            if sectors[idx].is_some() {
+                idx += 1;
+                continue;
+            }
+
+            let run_len = sectors[idx..].iter().take_while(|s| s.is_none()).count();
+            let data = self.read_uncached(
+                (first + idx as u64) * bytes_per_sec as u64,
+                run_len * bytes_per_sec,
+            )?;
+            // The cache isn't locked during the read, so that other threads aren't blocked
+            let mut cache = lock(&self.cache);
+            for (i, chunk) in data.chunks(bytes_per_sec).enumerate() {
+                cache.insert(first + (idx + i) as u64, chunk.to_vec());
+                sectors[idx + i] = Some(chunk.to_vec());
+            }
+            idx += run_len;
+        }
+
+        Ok(sectors.into_iter().flatten().flatten().collect())
+    }
+
+    /// Returns true if a read of `sector_cnt` sectors should go through the cache.
+    ///
+    /// Bulk reads bypass the cache so that they don't evict the hot metadata.
+    fn is_cacheable(&self, sector_cnt: u64) -> bool {
+        *self.bpb.bytes_per_sec() > 0 && sector_cnt <= (lock(&self.cache).capacity() / 4) as u64
+    }
+
+    /// Drops the cached copies of the sectors overlapping a byte range of the disk.
+    pub(super) fn invalidate_cache(&self, offset: u64, len: u64) {
+        let bytes_per_sec = *self.bpb.bytes_per_sec() as u64;
+        if bytes_per_sec == 0 || len == 0 {
+            return;
+        }
+
+        let first = offset / bytes_per_sec;
+        let count = (offset + len).div_ceil(bytes_per_sec) - first;
+        lock(&self.cache).invalidate(first, count);
+    }
+
+    /// Changes the number of sectors kept in the sector cache. A capacity of 0 disables the cache.
+    pub fn set_cache_capacity(&self, capacity: usize) {
+        lock(&self.cache).set_capacity(capacity);
+    }
+
+    /// Returns the limits enforced by the walks and scans of the volume.
+    pub fn limits(&self) -> Limits {
+        *lock(&self.limits)
+    }
+
+    /// Changes the limits enforced by the walks and scans of the volume.
+    pub fn set_limits(&self, limits: Limits) {
+        *lock(&self.limits) = limits;
+    }
+
+    /// Installs the sink receiving the events of the walks and scans, or removes it with `None`.
+    pub fn set_event_sink(&self, sink: Option<Arc<dyn EventSink>>) {
+        *self
+            .event_sink
+            .write()
+            .unwrap_or_else(|err| err.into_inner()) = sink;
+    }
+
+    /// Installs the token stopping the walks and scans of the volume when cancelled.
+    pub fn set_cancel_token(&self, token: CancellationToken) {
+        *lock(&self.cancel_token) = token;
+    }
+
+    /// Checks that the walks and scans of the volume may go on, between two chunks of work.
+    ///
+    /// # Returns
+    /// - `Err(FATError::Cancelled)`: If the cancellation token of the volume is cancelled.
+    pub(crate) fn check_cancelled(&self) -> Result<(), FATError> {
+        match lock(&self.cancel_token).is_cancelled() {
+            true => Err(FATError::Cancelled),
+            false => Ok(()),
+        }
+    }
+
+    /// Reports an event to the sink of the volume, if any.
+    ///
+    /// # Parameters
+    /// - `event`: Builds the event, only called if a sink is installed.
+    pub(crate) fn emit(&self, event: impl FnOnce() -> Event) {
+        let sink = self
+            .event_sink
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+        if let Some(sink) = sink {
+            sink.emit(&event());
+        }
+    }
+
+    /// Checks that a directory nested at the given depth below the root may be walked.
+    ///
+    /// # Returns
+    /// - `Err(FATError::LimitExceeded)`: If the depth exceeds the limit of the volume.
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<(), FATError> {
+        let max_depth = lock(&self.limits).max_depth;
+        match depth > max_depth {
+            true => Err(FATError::LimitExceeded {
+                what: "nested directories",
+                max: max_depth as u64,
+            }),
+            false => Ok(()),
+        }
+    }
+
+    /// Returns the hit and miss counters of the sector cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        lock(&self.cache).stats()
+    }
+
+    /// Drops every cached sector.
+    ///
+    /// Must be called after the disk image was modified without going through this volume.
+    pub fn clear_cache(&self) {
+        lock(&self.cache).clear();
+    }
+
+    fn read_uncached(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.image.read_at(offset, len)
+    }
+
+    /// Returns an iterator over the cluster chain starting at `first_cluster`.
+    ///
+    /// The FAT is read as the iterator advances, so looking at the first clusters of a chain doesn't
+    /// walk the whole chain.
+    ///
+    /// # Parameters
+    /// - `first_cluster`: The first cluster of the chain.
+    ///
+    /// # Returns
+    /// - A [`ClusterIter`] yielding the clusters of the chain, or the error which interrupted it.
+    pub fn cluster_chain(&self, first_cluster: u32) -> ClusterIter<'_> {
+        ClusterIter::new(self, first_cluster)
+    }
+
+    /// Returns the number of usable clusters of the data region, those mapped by the FAT.
+    pub fn cluster_count(&self) -> u32 {
+        self.bpb.usable_cluster_count()
+    }
+
+    /// Returns true if the cluster number addresses a usable cluster of the data region.
+    pub(super) fn is_valid_cluster(&self, cluster: u32) -> bool {
+        (2..self.cluster_count() as u64 + 2).contains(&(cluster as u64))
+    }
+
+    pub(super) fn get_next_cluster(&self, cluster: u32) -> Result<u32, FATError> {
+        if !self.is_valid_cluster(cluster) {
+            return Err(FATError::ClusterOutOfRange(cluster));
+        }
+
+        Ok(self.read_fat_entry(cluster, self.active_fat())? & 0x0FFFFFFF)
+    }
+
+    /// Reads the raw entry of a cluster in a FAT, reserved bits included.
+    ///
+    /// # Parameters
+    /// - `cluster`: The cluster number, which may lie past the data region.
+    /// - `fat_nb`: The number of the FAT, from 0.
+    pub(crate) fn read_fat_entry(&self, cluster: u32, fat_nb: u8) -> io::Result<u32> {
+        let offset = self.fat_entry_offset(cluster, fat_nb);
+        let buf = self.read_bytes(offset, self.fat_entry_bit_sz().div_ceil(8) as usize)?;
+        Ok(self.decode_fat_entry(&buf, cluster))
+    }
+
+    /// Decodes the raw entry of a cluster, reserved bits included.
+    ///
+    /// # Parameters
+    /// - `buf`: The bytes of the FAT starting at the entry, see [`FATVol::fat_entry_offset`].
+    /// - `cluster`: The cluster number, which tells the half-byte a FAT12 entry starts at.
+    pub(crate) fn decode_fat_entry(&self, buf: &[u8], cluster: u32) -> u32 {
+        match self.bpb.fat_type() {
+            FATType::FAT32 => u32_at(buf, 0),
+            FATType::FAT16 => u16_at(buf, 0) as u32,
+            // Two entries share three bytes, the odd one in the high 12 bits
+            FATType::FAT12 => match cluster % 2 {
+                0 => u16_at(buf, 0) as u32 & 0x0FFF,
+                _ => u16_at(buf, 0) as u32 >> 4,
+            },
+        }
+    }
+
+    /// Encodes the entry of a cluster, as written at [`FATVol::fat_entry_offset`].
+    ///
+    /// # Parameters
+    /// - `current`: The bytes of the FAT currently holding the entry, whose bits outside of the
+    ///   entry are kept. FAT12 entries share a byte with their neighbour.
+    /// - `cluster`: The cluster number.
+    /// - `value`: The new value of the entry.
+    fn encode_fat_entry(&self, current: &[u8], cluster: u32, value: u32) -> Vec<u8> {
+        match self.bpb.fat_type() {
+            FATType::FAT32 => value.to_le_bytes().to_vec(),
+            FATType::FAT16 => (value as u16).to_le_bytes().to_vec(),
+            FATType::FAT12 => {
+                let current = u16_at(current, 0);
+                let value = (value & 0x0FFF) as u16;
+                let entry = match cluster % 2 {
+                    0 => (current & 0xF000) | value,
+                    _ => (current & 0x000F) | (value << 4),
+                };
+                entry.to_le_bytes().to_vec()
+            }
+        }
+    }
+
+    /// Marks a run of free clusters as bad, e.g. to hide data the file system will never read.
+    ///
+    /// The search starts at the next free hint of the FSINFO structure, if any, and wraps around
+    /// to cluster 2, as drivers allocate. A cluster is free if its FAT entry is 0 and its content
+    /// is zeroed. The FSINFO structure is then updated: the hint points past the run and the free
+    /// count, if known, drops by the length of the run.
+    ///
+    /// # Parameters
+    /// - `cluster_cnt`: The number of contiguous clusters to mark.
+    ///
+    /// # Returns
+    /// - `Ok(u32)`: The first cluster of the run.
+    /// - `Err(FATError::NoFreeClusterChain)`: If no run of free clusters is long enough.
+    /// - `Err(FATError)`: If the volume cannot be read or written.
+    pub fn mark_as_bad(&self, cluster_cnt: u32) -> Result<u32, FATError> {
+        let end = self.cluster_count() + 2;
+        let hint = self.next_free_hint();
+        // A run may start before the hint and end after it
+        let start = match self.find_free_run(hint, end, cluster_cnt)? {
+            Some(start) => start,
+            None => self
+                .find_free_run(2, (hint + cluster_cnt).min(end), cluster_cnt)?
+                .ok_or(FATError::NoFreeClusterChain(cluster_cnt))?,
+        };
+
+        for cluster in start..start + cluster_cnt {
+            self.update_fat_entry(cluster, DirEntry::bad_cluster_marker(self.bpb.fat_type()))?;
+        }
+        let next = match start + cluster_cnt {
+            next if next < end => next,
+            _ => 2,
+        };
+        self.update_fs_info(Some(next), -(cluster_cnt as i64))?;
+
+        Ok(start)
+    }
+
+    /// Marks a chosen run of clusters as bad, e.g. to hide data at a memorable location.
+    ///
+    /// # Parameters
+    /// - `start`: The first cluster of the run.
+    /// - `count`: The number of clusters of the run.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the clusters are marked, see [`FATVol::reserve_clusters`].
+    /// - `Err(FATError)`: As [`FATVol::reserve_clusters`].
+    pub fn mark_range_bad(&self, start: u32, count: u32) -> Result<(), FATError> {
+        let end = start
+            .checked_add(count)
+            .ok_or(FATError::ClusterOutOfRange(u32::MAX))?;
+        self.reserve_clusters(&(start..end).collect::<Vec<_>>())
+    }
+
+    /// Marks chosen clusters as bad, e.g. to hide data at memorable locations.
+    ///
+    /// Every cluster is checked before any is marked, so a failure leaves the FATs untouched. A
+    /// cluster is free if its FAT entry is 0, its content being overwritten by whatever is hidden
+    /// in it. The free count of the FSINFO structure, if known, drops by the number of clusters.
+    ///
+    /// # Parameters
+    /// - `clusters`: The clusters to mark, in any order, duplicates being ignored.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the clusters are marked in every FAT.
+    /// - `Err(FATError::ClusterOutOfRange)`: If a cluster isn't part of the data region.
+    /// - `Err(FATError::ClusterNotFree)`: If a cluster is allocated, bad or reserved.
+    /// - `Err(FATError)`: If the volume cannot be read or written.
+    pub fn reserve_clusters(&self, clusters: &[u32]) -> Result<(), FATError> {
+        let mut clusters = clusters.to_vec();
+        clusters.sort_unstable();
+        clusters.dedup();
+        for cluster in &clusters {
+            if self.get_next_cluster(*cluster)? != 0 {
+                return Err(FATError::ClusterNotFree(*cluster));
+            }
+        }
+
+        for cluster in &clusters {
+            self.update_fat_entry(*cluster, DirEntry::bad_cluster_marker(self.bpb.fat_type()))?;
+        }
+        self.update_fs_info(None, -(clusters.len() as i64))
+    }
+
+    /// Finds the first run of free clusters within a range of clusters.
+    ///
+    /// # Returns
+    /// - `Ok(Some(u32))`: The first cluster of a run of `cluster_cnt` free clusters, all before
+    ///   `end`.
+    /// - `Ok(None)`: If the range holds no such run.
+    /// - `Err(FATError)`: If the FAT or the data region cannot be read.
+    fn find_free_run(
+        &self,
+        from: u32,
+        end: u32,
+        cluster_cnt: u32,
+    ) -> Result<Option<u32>, FATError> {
+        let mut start = from;
+        let mut i = 0;
+
+        while start + i < end {
+            if self.get_next_cluster(start + i)? != 0 || !self.is_zero_cluster(start + i)? {
+                start = start + i + 1;
+                i = 0;
+            } else {
+                i += 1;
+            }
+
+            if i == cluster_cnt {
+                return Ok(Some(start));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the cluster from which to look for free clusters: the next free hint of the FSINFO
+    /// structure if it is valid and addresses a cluster of the volume, cluster 2 otherwise.
+    fn next_free_hint(&self) -> u32 {
+        self.fs_info()
+            .ok()
+            .filter(FsInfo::is_valid)
+            .and_then(|fs_info| fs_info.known_nxt_free())
+            .filter(|cluster| self.is_valid_cluster(*cluster))
+            .unwrap_or(2)
+    }
+
+    /// Updates the FSINFO structure after clusters were allocated or released.
+    ///
+    /// Nothing is written if the volume has no valid FSINFO structure. An unknown free count is
+    /// left unknown.
+    ///
+    /// # Parameters
+    /// - `next_free`: The new next free hint, or `None` to keep the current one.
+    /// - `free_delta`: The change of the number of free clusters.
+    fn update_fs_info(&self, next_free: Option<u32>, free_delta: i64) -> Result<(), FATError> {
+        let Some(fs_info) = self.fs_info().ok().filter(FsInfo::is_valid) else {
+            return Ok(());
+        };
+        let Some(sector) = self.bpb.fs_info_sector() else {
+            return Ok(());
+        };
+        let free_count = fs_info
+            .known_free_count()
+            .map(|count| (count as i64 + free_delta).clamp(0, self.cluster_count() as i64) as u32);
+
+        let offset = self.sector_offset(self.start as u64 + sector as u64);
+        let mut disk_file = self.image.open_for_write()?;
+        if let Some(count) = free_count {
+            let field = offset + FsInfo::FREE_COUNT_OFFSET;
+            write_at(&mut disk_file, field, &count.to_le_bytes())?;
+            self.invalidate_cache(field, 4);
+        }
+        if let Some(cluster) = next_free {
+            let field = offset + FsInfo::NXT_FREE_OFFSET;
+            write_at(&mut disk_file, field, &cluster.to_le_bytes())?;
+            self.invalidate_cache(field, 4);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the entries of the active FAT.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u32>)`: One entry per cluster, indexed by cluster number (clusters 0 and 1 included).
+    /// - `Err(FATError)`: If the FAT cannot be read.
+    pub fn fat_entries(&self) -> Result<Vec<u32>, FATError> {
+        let bits = self.fat_entry_bit_sz() as u64;
+        // A corrupt cluster count must not make us allocate more entries than the FAT holds
+        let entry_cnt = (self.cluster_count() as u64 + 2).min(self.fat_size() * 8 / bits);
+        let fat_len = (entry_cnt * bits).div_ceil(8);
+
+        // FAT12 entries straddle sectors, so the entries are decoded once the table is read
+        let mut fat = Vec::with_capacity(fat_len as usize + 1);
+        let mut sector = self.active_fat_start() as u64;
+        let fat_end = sector + self.bpb.fat_sz() as u64;
+        while (fat.len() as u64) < fat_len && sector < fat_end {
+            let count = FAT_READ_LEN.min(fat_end - sector);
+            fat.extend(self.read_sectors_cached(sector, count)?);
+            sector += count;
+        }
+        // The last FAT12 entry may end on the first half of its second byte
+        fat.push(0);
+
+        Ok((0..entry_cnt as u32)
+            .map(|cluster| {
+                let off = (cluster as u64 * bits / 8) as usize;
+                self.decode_fat_entry(&fat[off..], cluster) & 0x0FFFFFFF
+            })
+            .collect())
+    }
+
+    /// Returns the size of each FAT in bytes.
+    pub fn fat_size(&self) -> u64 {
+        self.bpb.fat_sz() as u64 * *self.bpb.bytes_per_sec() as u64
+    }
+
+    /// Returns the number of FATs of the volume.
+    pub fn num_fat(&self) -> u8 {
+        *self.bpb.num_fat()
+    }
+
+    /// Returns the first sector of a FAT, or an error if the volume has no such FAT.
+    fn fat_copy_start(&self, fat_nb: u8) -> Result<u64, FATError> {
+        match fat_nb < *self.bpb.num_fat() {
+            true => Ok(self.fat_start() as u64 + fat_nb as u64 * self.bpb.fat_sz() as u64),
+            false => Err(FATError::FatOutOfRange {
+                fat_nb,
+                num_fat: *self.bpb.num_fat(),
+            }),
+        }
+    }
+
+    /// Copies a whole FAT, e.g. for the offline analysis of the allocation table.
+    ///
+    /// # Parameters
+    /// - `fat_nb`: The number of the FAT, from 0.
+    /// - `out`: The sink the FAT is written to.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of bytes written, see [`FATVol::fat_size`].
+    /// - `Err(FATError)`: If the FAT doesn't exist, or cannot be read or written.
+    pub fn export_fat<W: io::Write>(&self, fat_nb: u8, out: &mut W) -> Result<u64, FATError> {
+        let start = self.fat_copy_start(fat_nb)?;
+        let fat_sz = self.bpb.fat_sz() as u64;
+
+        let mut sector = 0;
+        while sector < fat_sz {
+            self.check_cancelled()?;
+            let count = FAT_READ_LEN.min(fat_sz - sector);
+            out.write_all(&self.read_sectors(start + sector, count)?)?;
+            sector += count;
+        }
+        out.flush()?;
+
+        Ok(self.fat_size())
+    }
+
+    /// Overwrites a whole FAT with an image of the same size, e.g. one exported from this or
+    /// another volume, to swap allocation tables in a controlled experiment. The other FATs are
+    /// left untouched, whatever the mirroring.
+    ///
+    /// # Parameters
+    /// - `fat_nb`: The number of the FAT, from 0.
+    /// - `data`: The content of the FAT, exactly [`FATVol::fat_size`] bytes long.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the FAT is written, and recorded in the journal of the image.
+    /// - `Err(FATError)`: If the FAT doesn't exist, the size differs or the image cannot be written.
+    pub fn import_fat(&self, fat_nb: u8, data: &[u8]) -> Result<(), FATError> {
+        let start = self.sector_offset(self.fat_copy_start(fat_nb)?);
+        if data.len() as u64 != self.fat_size() {
+            return Err(FATError::FatSizeMismatch {
+                expected: self.fat_size(),
+                actual: data.len() as u64,
+            });
+        }
+
+        // Written in chunks, so that the journal records the unallocated runs as fills
+        let mut disk_file = self.image.open_for_write()?;
+        let chunk_len = (FAT_READ_LEN * *self.bpb.bytes_per_sec() as u64) as usize;
+        for (i, chunk) in data.chunks(chunk_len).enumerate() {
+            write_at(&mut disk_file, start + (i * chunk_len) as u64, chunk)?;
+        }
+        disk_file.sync_all()?;
+        self.invalidate_cache(start, data.len() as u64);
+
+        Ok(())
+    }
+
+    /// Recursively lists every regular file of the volume.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<(PathBuf, DirEntry)>)`: The path of each file, relative to the root directory, and its entry.
+    /// - `Err(FATError)`: If a directory cannot be listed.
+    pub fn walk_files(&self) -> Result<Vec<(PathBuf, DirEntry)>, FATError> {
+        let mut files = vec![];
+        self.walk_files_rec(self.root_cluster()?, &PathBuf::new(), 0, &mut files)?;
+        Ok(files)
+    }
+
+    fn walk_files_rec(
+        &self,
+        cluster: u32,
+        dir_path: &Path,
+        depth: usize,
+        files: &mut Vec<(PathBuf, DirEntry)>,
+    ) -> Result<(), FATError> {
+        self.check_depth(depth)?;
+        for entry in self.list_dir(cluster)? {
+            if entry.is_regular_dir() && entry.is_deleted() {
+                self.emit(|| Event::Skipped {
+                    offset: *entry.location(),
+                    reason: format!(
+                        "deleted directory {}",
+                        escape_path(&dir_path.join(entry.short_name()))
+                    ),
+                });
+            } else if entry.is_regular_dir() {
+                self.walk_files_rec(
+                    entry.cluster_number(),
+                    &dir_path.join(entry.short_name()),
+                    depth + 1,
+                    files,
+                )?;
+            } else if entry.is_regular_file() {
+                files.push((dir_path.join(entry.short_name()), entry));
+            }
+        }
+
+        Ok(())
+    }
 
-        Ok(Self {
-            bpb,
-            start,
-            end: start + sector_cnt,
-            disk_path: disk_path.to_path_buf(),
-        })
+    /// Recursively lists every live entry of the volume: files, directories and volume labels.
+    ///
+    /// Deleted entries, long name entries and the "." and ".." entries are left out.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<(PathBuf, DirEntry)>)`: The path of each entry, relative to the root directory,
+    ///   and the entry, parents before their children.
+    /// - `Err(FATError)`: If a directory cannot be listed.
+    pub fn walk_entries(&self) -> Result<Vec<(PathBuf, DirEntry)>, FATError> {
+        let mut entries = vec![];
+        let _ = self.visit_entries(|path, entry| {
+            entries.push((path.to_path_buf(), entry.clone()));
+            ControlFlow::Continue(())
+        })?;
+        Ok(entries)
     }
 
-    /// Find a file in the FAT volume and return its first cluster number.
+    /// Recursively visits every live entry of the volume, as the directories are read.
+    ///
+    /// The streaming counterpart of [`FATVol::walk_entries`]: the entries are handed to `visit`
+    /// in the same order, without waiting for the whole tree to be read.
     ///
     /// # Parameters
-    /// - `file_path`: The path of the file to find
+    /// - `visit`: Called on the path of each entry, relative to the root directory, and the
+    ///   entry. Returns `ControlFlow::Break` to stop the walk.
     ///
     /// # Returns
-    /// - `u32`: The first cluster number of the file if found, otherwise `0`.
-    pub fn find_file(&self, file_path: &Path) -> Result<DirEntry, FATError> {
-        if file_path.components().count() == 0 {
-            return Err(FATError::FileNotFound);
-        }
+    /// - `Ok(ControlFlow<()>)`: `Break` if `visit` stopped the walk, `Continue` otherwise.
+    /// - `Err(FATError)`: If a directory cannot be listed.
+    pub fn visit_entries(
+        &self,
+        mut visit: impl FnMut(&Path, &DirEntry) -> ControlFlow<()>,
+    ) -> Result<ControlFlow<()>, FATError> {
+        self.visit_entries_rec(self.root_cluster()?, &PathBuf::new(), 0, &mut visit)
+    }
 
-        let fat_type = self.bpb.fat_type();
-        let root_dir_cluster = match fat_type {
-            FATType::FAT12 => return Err(FATError::UnsupportedFATType(fat_type.to_string())),
-            FATType::FAT16 => return Err(FATError::UnsupportedFATType(fat_type.to_string())),
-            _ => *self.bpb.root_clus(),
-        };
+    fn visit_entries_rec(
+        &self,
+        cluster: u32,
+        dir_path: &Path,
+        depth: usize,
+        visit: &mut dyn FnMut(&Path, &DirEntry) -> ControlFlow<()>,
+    ) -> Result<ControlFlow<()>, FATError> {
+        self.check_depth(depth)?;
+        self.visit_dir(cluster, |entry| {
+            if entry.is_long_name() || entry.is_dot() || entry.is_deleted() {
+                return Ok(ControlFlow::Continue(()));
+            }
 
-        self.find_file_rec(file_path, root_dir_cluster)
+            let path = dir_path.join(entry.short_name());
+            if visit(&path, &entry).is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+            match entry.is_regular_dir() && !entry.is_volume_id() {
+                true => self.visit_entries_rec(entry.cluster_number(), &path, depth + 1, visit),
+                false => Ok(ControlFlow::Continue(())),
+            }
+        })
     }
 
-    fn find_file_rec(
-        &self,
-        file_path: &Path,
-        fst_cluster: u32,
-    ) -> Result<dir_entry::DirEntry, FATError> {
-        let mut parts = file_path.components();
-        let current_part = match parts.next() {
-            Some(part) => part,
-            None => return Err(FATError::FileNotFound),
-        };
-        let remaining: PathBuf = parts.clone().collect();
+    /// Computes the location of the slack space of a file.
+    ///
+    /// The slack space spans from the end of the file content to the end of its last allocated cluster.
+    ///
+    /// # Parameters
+    /// - `entry`: The directory entry of the file.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<(u64, u64)>)`: The absolute byte offset and length of each slack region.
+    /// - `Err(FATError)`: If the cluster chain of the file cannot be read.
+    pub fn file_slack_extents(&self, entry: &DirEntry) -> Result<Vec<(u64, u64)>, FATError> {
+        if entry.cluster_number() == 0 {
+            return Ok(vec![]);
+        }
 
-        let dir_entries: Vec<DirEntry> = if parts.count() > 0 {
-            self.list_dir(fst_cluster)?
-                .iter()
-                .filter(|entry| entry.is_dir())
-                .cloned()
-                .collect()
-        } else {
-            self.list_dir(fst_cluster)?
-                .iter()
-                .filter(|entry| !entry.is_dir())
-                .cloned()
-                .collect()
-        };
+        let cluster_size = self.cluster_size() as u64;
+        let file_size = *entry.file_size() as u64;
+        let mut extents = vec![];
 
-        for dir_entry in dir_entries.iter() {
-            if dir_entry.same_short_name(current_part.as_os_str().to_str().unwrap()) {
-                if dir_entry.is_dir() {
-                    return self.find_file_rec(remaining.as_path(), dir_entry.cluster_number());
-                } else {
-                    return Ok(dir_entry.clone());
-                }
+        for (i, cluster) in self.cluster_chain(entry.cluster_number()).enumerate() {
+            let cluster = cluster?;
+            let clus_start = i as u64 * cluster_size;
+            let clus_end = clus_start + cluster_size;
+            if clus_end <= file_size {
+                continue;
             }
+
+            let skipped = file_size.saturating_sub(clus_start);
+            extents.push((
+                self.cluster_offset(cluster) + skipped,
+                cluster_size - skipped,
+            ));
         }
 
-        Err(FATError::FileNotFound)
+        Ok(extents)
     }
 
-    pub fn list_dir(&self, first_cluster: u32) -> Result<Vec<DirEntry>, FATError> {
-        match first_cluster {
-            0 => return Err(FATError::InvalidClusterError(0)),
-            1 => return Err(FATError::InvalidClusterError(1)),
-            _ => {}
+    /// Computes the location of the content of a file in the image, as byte runs.
+    ///
+    /// The clusters of the chain are coalesced into runs of consecutive clusters, so that a
+    /// contiguous file is a single run. The runs of a file stop at its size, slack excluded, while
+    /// those of a directory cover its whole chain.
+    ///
+    /// # Parameters
+    /// - `entry`: The directory entry of the file or directory.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<(u64, u64)>)`: The absolute byte offset and length of each run, in file order.
+    /// - `Err(FATError)`: If the cluster chain of the file cannot be read.
+    pub fn file_byte_runs(&self, entry: &DirEntry) -> Result<Vec<(u64, u64)>, FATError> {
+        if entry.cluster_number() == 0 {
+            return Ok(vec![]);
         }
 
-        let clusters = self.list_clusters(first_cluster)?;
-        let mut dir_entries = vec![];
-
-        for cluster_nb in clusters {
-            let buf = self.read_cluster(cluster_nb)?;
+        let clusters = self
+            .cluster_chain(entry.cluster_number())
+            .collect::<Result<Vec<_>, _>>()?;
+        let cluster_size = self.cluster_size() as u64;
+        let mut remaining = match entry.is_dir() {
+            true => u64::MAX,
+            false => *entry.file_size() as u64,
+        };
 
-            for off in (0..buf.len()).step_by(32) {
-                if u32_at(&buf, off) != 0 {
-                    dir_entries.push(DirEntry::from_slice(&buf[off..])?);
-                }
+        let mut runs = vec![];
+        for (first, count) in contiguous_runs(&clusters, u32::MAX) {
+            if remaining == 0 {
+                break;
             }
+            let len = (count as u64 * cluster_size).min(remaining);
+            runs.push((self.cluster_offset(first), len));
+            remaining -= len;
         }
 
-        Ok(dir_entries)
+        Ok(runs)
     }
 
-    fn read_cluster(&self, cluster_nb: u32) -> io::Result<Vec<u8>> {
-        let mut file = File::open(&self.disk_path).unwrap();
-
-        let cluster_size = *self.bpb.sec_per_clus() as u16 * *self.bpb.bytes_per_sec();
-        let mut buf: Vec<u8> = vec![0; cluster_size.into()];
+    /// Reads back data written at the start of a region, and compares its digest with the digest
+    /// of the data.
+    ///
+    /// # Parameters
+    /// - `reader`: The image the data was written to.
+    /// - `region`: The absolute byte offset and length of each range of the region, in order.
+    /// - `data`: The data written.
+    ///
+    /// # Returns
+    /// - `Ok(SlackWrite)`: The ranges holding the data, and its digest.
+    /// - `Err(FATError::SlackVerificationFailed)`: If the data read back differs.
+    /// - `Err(FATError)`: If the data cannot be read back.
+    fn verify_write<T: io::Read + io::Seek>(
+        &self,
+        reader: &mut T,
+        region: &[(u64, u64)],
+        data: &[u8],
+    ) -> Result<SlackWrite, FATError> {
+        let mut extents = vec![];
+        let mut remaining = data.len() as u64;
+        for (offset, len) in region {
+            if remaining == 0 {
+                break;
+            }
+            extents.push((*offset, remaining.min(*len)));
+            remaining -= remaining.min(*len);
+        }
 
-        file.seek(SeekFrom::Start(
-            (*self.bpb.bytes_per_sec() as u32 * self.clus_to_sector(cluster_nb)).into(),
-        ))?;
+        let mut read_back = Vec::with_capacity(data.len());
+        for (offset, len) in &extents {
+            read_back.extend(read_at(reader, *offset, *len as usize)?);
+        }
+        let digest = Sha256::digest(&read_back);
+        if digest != Sha256::digest(data) {
+            return Err(FATError::SlackVerificationFailed(
+                extents.first().map_or(0, |(offset, _)| *offset),
+            ));
+        }
+        Ok(SlackWrite::new(
+            extents,
+            digest.iter().map(|b| format!("{b:02x}")).collect(),
+        ))
+    }
 
-        file.read_exact(&mut buf).map_err(|err| {
-            io::Error::new(
-                err.kind(),
-                format!("Failed to read cluster {cluster_nb}: {err}"),
-            )
-        })?;
+    /// Returns the absolute byte offset and length of the volume slack.
+    pub fn volume_slack_extent(&self) -> (u64, u64) {
+        let bytes_per_sec = *self.bpb.bytes_per_sec() as u64;
+        let start = self.data_end() as u64 * bytes_per_sec;
+        let end = self.end as u64 * bytes_per_sec;
+        (start, end.saturating_sub(start))
+    }
 
-        Ok(buf)
+    /// Overwrites the slack space of a file with a repeated pattern.
+    ///
+    /// # Parameters
+    /// - `writer`: A mutable reference to the disk image.
+    /// - `file_path`: The path of the file whose slack space will be wiped.
+    /// - `pattern`: The byte pattern to repeat over the slack space.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of bytes overwritten.
+    /// - `Err(FATError)`: If the file cannot be found or writing fails.
+    pub fn wipe_file_slack<T: io::Write + io::Seek>(
+        &self,
+        writer: &mut T,
+        file_path: &Path,
+        pattern: &[u8],
+    ) -> Result<u64, FATError> {
+        let entry = self.find_file(file_path)?;
+        self.wipe_extents(writer, &self.file_slack_extents(&entry)?, pattern)
     }
 
-    fn list_clusters(&self, cluster: u32) -> Result<Vec<u32>, FATError> {
-        match cluster {
-            0 => return Err(FATError::InvalidClusterError(0)),
-            1 => return Err(FATError::InvalidClusterError(1)),
-            _ => {}
+    /// Overwrites the slack space of every file and the volume slack with a repeated pattern.
+    ///
+    /// # Parameters
+    /// - `writer`: A mutable reference to the disk image.
+    /// - `pattern`: The byte pattern to repeat over the slack space.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of bytes overwritten.
+    /// - `Err(FATError)`: If the directory tree cannot be walked or writing fails.
+    pub fn wipe_all_slack<T: io::Write + io::Seek>(
+        &self,
+        writer: &mut T,
+        pattern: &[u8],
+    ) -> Result<u64, FATError> {
+        let mut wiped = 0;
+        for (_, entry) in self.walk_files()? {
+            wiped += self.wipe_extents(writer, &self.file_slack_extents(&entry)?, pattern)?;
         }
 
-        let mut all_clusters = vec![];
-        let mut cluster = cluster;
-
-        while !DirEntry::is_eof(cluster, self.bpb.fat_type()) {
-            all_clusters.push(cluster);
-            cluster = self.get_next_cluster(cluster);
-        }
-        Ok(all_clusters)
+        Ok(wiped + self.wipe_extents(writer, &[self.volume_slack_extent()], pattern)?)
     }
 
-    fn get_next_cluster(&self, cluster: u32) -> u32 {
-        let mut file = File::open(&self.disk_path).unwrap();
-        let mut buf = vec![];
-        let sector = self.fat_start()
-            + (cluster * self.fat_entry_bit_sz() / 8) / (*self.bpb.bytes_per_sec() as u32);
+    /// Overwrites every unallocated cluster with a repeated pattern.
+    ///
+    /// # Parameters
+    /// - `writer`: A mutable reference to the disk image.
+    /// - `pattern`: The byte pattern to repeat over the free clusters.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of bytes overwritten.
+    /// - `Err(FATError)`: If the FAT cannot be read or writing fails.
+    pub fn wipe_free<T: io::Write + io::Seek>(
+        &self,
+        writer: &mut T,
+        pattern: &[u8],
+    ) -> Result<u64, FATError> {
+        let extents: Vec<(u64, u64)> = self
+            .clusters(ClusterKind::Free)?
+            .map(|region| (*region.offset(), *region.len()))
+            .collect();
+
+        self.wipe_extents(writer, &extents, pattern)
+    }
 
-        let err_msg = format!("Couldn't read sector {sector}").to_string();
-        read_sector(
-            &mut file,
-            sector.into(),
-            (*self.bpb.bytes_per_sec()).into(),
-            &mut buf,
-        )
-        .expect(&err_msg);
+    fn wipe_extents<T: io::Write + io::Seek>(
+        &self,
+        writer: &mut T,
+        extents: &[(u64, u64)],
+        pattern: &[u8],
+    ) -> Result<u64, FATError> {
+        let mut wiped = 0;
+        for (offset, len) in extents {
+            write_pattern(writer, *offset, *len, pattern)?;
+            self.invalidate_cache(*offset, *len);
+            wiped += len;
+        }
 
-        u32_at(
-            &buf,
-            (cluster * self.fat_entry_bit_sz() / 8 % *self.bpb.bytes_per_sec() as u32) as usize,
-        ) & 0x0FFFFFFF
+        Ok(wiped)
     }
 
-    pub fn mark_as_bad(&self, cluster_cnt: u32) -> Result<u32, FATError> {
-        let mut start = 2;
-        let mut i = 0;
+    /// Overwrites the content of a file, then deletes it.
+    ///
+    /// The clusters of the file are overwritten `passes` times, alternating `0xFF` and `0x00` so that the
+    /// last pass always leaves zeros. The cluster chain is then released in every FAT, the free count of
+    /// the FSINFO structure raised, and the directory entry, along with its long name entries, is marked
+    /// as deleted.
+    ///
+    /// # Parameters
+    /// - `file_path`: The path of the file to shred.
+    /// - `passes`: The number of overwrite passes.
+    /// - `scrub_entry`: Whether to zero the directory entries instead of only marking them as deleted.
+    ///
+    /// # Returns
+    /// - `Ok(())` on success.
+    /// - `Err(FATError)` if the file cannot be found or writing fails.
+    pub fn shred(&self, file_path: &Path, passes: u32, scrub_entry: bool) -> Result<(), FATError> {
+        let entry = self.find_file(file_path)?;
+        let clusters = match entry.cluster_number() {
+            0 => vec![],
+            cluster => self.cluster_chain(cluster).collect::<Result<Vec<_>, _>>()?,
+        };
 
-        while start + i < self.bpb.cluster_count() + 2 {
-            if self.get_next_cluster(start + i) != 0 || !self.is_zero_cluster(start + i)? {
-                start = start + i + 1;
-                i = 0;
-            } else {
-                i += 1;
+        let mut disk_file = self.image.open_for_write()?;
+        for pass in 0..passes {
+            let pattern = if (passes - pass) % 2 == 1 { 0x00 } else { 0xFF };
+            let data = vec![pattern; self.cluster_size() as usize];
+            for cluster in &clusters {
+                write_at(&mut disk_file, self.cluster_offset(*cluster), &data)?;
+                self.invalidate_cache(self.cluster_offset(*cluster), data.len() as u64);
             }
+        }
 
-            if i == cluster_cnt {
-                // Found a list of `cluster_cnt` free clusters
-                for cluster in start..start + cluster_cnt {
-                    self.update_fat_entry(
-                        cluster,
-                        DirEntry::bad_cluster_marker(self.bpb.fat_type()),
-                    )?;
-                }
+        for cluster in &clusters {
+            self.update_fat_entry(*cluster, 0)?;
+        }
+        self.update_fs_info(None, clusters.len() as i64)?;
+
+        // Long name entries directly precede the short entry they belong to
+        let parent_cluster = match file_path.parent() {
+            Some(parent) if parent.components().count() > 0 => self.find_dir(parent)?,
+            _ => self.root_cluster()?,
+        };
+        let siblings = self.list_dir(parent_cluster)?;
+        let mut locations = vec![*entry.location()];
+        if let Some(idx) = siblings
+            .iter()
+            .position(|e| e.location() == entry.location())
+        {
+            locations.extend(
+                siblings[..idx]
+                    .iter()
+                    .rev()
+                    .take_while(|e| e.is_long_name_of(&entry) && !e.is_deleted())
+                    .map(|e| *e.location()),
+            );
+        }
 
-                return Ok(start);
+        for location in locations {
+            if scrub_entry {
+                write_at(&mut disk_file, location, &[0; DirEntry::SIZE as usize])?;
+            } else {
+                write_at(&mut disk_file, location, &[DirEntry::DELETED_MARKER])?;
             }
+            self.invalidate_cache(location, DirEntry::SIZE);
         }
 
-        Err(FATError::NoFreeClusterChain(cluster_cnt))
+        Ok(())
     }
 
-    fn is_zero_cluster(&self, cluster: u32) -> io::Result<bool> {
-        let mut buffer = Vec::new();
-        let mut disk_file = File::open(&self.disk_path).unwrap();
-
-        for i in 0..*self.bpb.sec_per_clus() {
-            read_sector(
-                &mut disk_file,
-                self.clus_to_sector(cluster) as u64 + i as u64,
-                *self.bpb.bytes_per_sec() as usize,
-                &mut buffer,
-            )?;
-
-            for byte in &buffer {
-                if *byte != 0 {
-                    return Ok(false);
-                }
-            }
+    /// Finds a directory in the FAT volume and returns its first cluster number.
+    ///
+    /// Each component of the path can be a short or a long name.
+    pub(super) fn find_dir(&self, dir_path: &Path) -> Result<u32, FATError> {
+        let mut cluster = self.root_cluster()?;
+        for part in dir_path.components() {
+            let name = part.as_os_str().to_str().unwrap_or_default();
+            cluster = self
+                .list_dir(cluster)?
+                .iter()
+                .find(|entry| entry.is_regular_dir() && entry.same_name(name))
+                .map(|entry| entry.cluster_number())
+                .ok_or(FATError::FileNotFound)?;
         }
 
-        Ok(true)
+        Ok(cluster)
+    }
+
+    fn is_zero_cluster(&self, cluster: u32) -> io::Result<bool> {
+        Ok(self
+            .read_bytes(self.cluster_offset(cluster), self.cluster_size() as usize)?
+            .iter()
+            .all(|byte| *byte == 0))
     }
 
     pub fn cluster_size(&self) -> u32 {
@@ -262,32 +1479,29 @@ impl FATVol {
     }
 
     fn update_fat_entry(&self, cluster_nb: u32, value: u32) -> io::Result<()> {
-        // Prepare the data to write
-        let mut data: Vec<u8> = Vec::new();
-        let mut mask = 0xff000000;
-        for i in 1..5 {
-            data.push(((value & mask) >> (32 - i * 8)) as u8);
-            mask >>= 8;
-        }
+        let entry_len = self.fat_entry_bit_sz().div_ceil(8) as usize;
 
-        // Update the entry for every fat structure
-        for i in 0..*self.bpb.num_fat() {
-            let off = (self.fat_start() as u64 + i as u64 * self.bpb.fat_sz() as u64)
-                * *self.bpb.bytes_per_sec() as u64
-                + (cluster_nb as u64 * self.fat_entry_bit_sz() as u64 / 8);
+        // Update the entry for every fat structure, or only the active one without mirroring
+        let fats = match self.bpb.is_mirrored() {
+            true => 0..*self.bpb.num_fat(),
+            false => self.active_fat()..self.active_fat() + 1,
+        };
+        for i in fats {
+            let off = self.fat_entry_offset(cluster_nb, i);
+            // FAT entries are stored in little-endian byte order
+            let data = self.encode_fat_entry(&self.read_bytes(off, entry_len)?, cluster_nb, value);
 
-            let mut disk_file = File::options()
-                .write(true)
-                .read(true)
-                .open(&self.disk_path)?;
+            let mut disk_file = self.image.open_for_write()?;
 
-            write_at(&mut disk_file, off, &data)?
+            write_at(&mut disk_file, off, &data)?;
+            self.invalidate_cache(off, data.len() as u64);
         }
 
         Ok(())
     }
 
-    fn fat_entry_bit_sz(&self) -> u32 {
+    /// Returns the size of a FAT entry in bits.
+    pub(crate) fn fat_entry_bit_sz(&self) -> u32 {
         match self.bpb.fat_type() {
             FATType::FAT12 => 12,
             FATType::FAT16 => 16,
@@ -295,37 +1509,99 @@ impl FATVol {
         }
     }
 
-    /// Recursively prints the directory tree starting from the given cluster.
+    /// Recursively writes the directory tree starting from the given cluster.
     ///
     /// # Parameters
     /// - `cluster`: The starting cluster number for the directory.
     /// - `indent`: The indentation level for pretty-printing.
+    /// - `out`: The sink the tree is written to.
     ///
     /// # Returns
-    /// - `Ok(())` if the directory tree is printed successfully.
-    /// - `Err(FATError)` if an error occurs while listing directories.
-    fn print_dir_rec(&self, cluster: u32, indent: usize) -> Result<(), FATError> {
+    /// - `Ok(())` if the directory tree is written successfully.
+    /// - `Err(TraitError)` if an error occurs while listing directories or writing.
+    fn print_dir_rec<W: io::Write>(
+        &self,
+        cluster: u32,
+        indent: usize,
+        out: &mut W,
+    ) -> Result<(), TraitError> {
+        self.check_depth(indent / 3)?;
         let dir_entries = self.list_dir(cluster)?;
 
         for entry in dir_entries {
-            println!("{} {}", " ".repeat(indent), entry);
+            writeln!(out, "{} {}", " ".repeat(indent), entry)?;
             if entry.is_regular_dir() {
-                self.print_dir_rec(entry.cluster_number(), indent + 3)?;
+                self.print_dir_rec(entry.cluster_number(), indent + 3, out)?;
             }
         }
 
         Ok(())
     }
 
-    /// Converts a cluster number to its corresponding sector number.
+    /// Returns the first cluster of the root directory.
     ///
-    /// # Parameters
-    /// - `cluster`: The cluster number to convert.
+    /// The root directory of FAT12/16 volumes is a fixed region before the data region, numbered
+    /// cluster 0 as in the ".." entries of its subdirectories; [`FATVol::list_dir`] reads it.
+    /// The cluster set with [`FATVol::set_root_cluster`] takes precedence over the one of the BPB.
+    ///
+    /// # Returns
+    /// - `Ok(u32)`: The root directory cluster.
+    pub fn root_cluster(&self) -> Result<u32, FATError> {
+        let root = match self.bpb.fat_type() {
+            FATType::FAT32 => *self.bpb.root_clus(),
+            _ => 0,
+        };
+        Ok(lock(&self.root_override).unwrap_or(root))
+    }
+
+    /// Returns the fixed root directory region of a FAT12/16 volume.
+    ///
+    /// # Returns
+    /// - `Some((u64, u64))`: The absolute byte offset and the length of the region.
+    /// - `None`: If the root directory of the volume is a cluster chain, as on FAT32.
+    pub fn root_dir_extent(&self) -> Option<(u64, u64)> {
+        match self.bpb.fat_type() {
+            FATType::FAT32 => None,
+            _ => Some((
+                self.sector_offset(self.root_start() as u64),
+                *self.bpb.root_ent_cnt() as u64 * DirEntry::SIZE,
+            )),
+        }
+    }
+
+    /// Reads the FSINFO structure of the volume.
     ///
     /// # Returns
-    /// - The sector number corresponding to the given cluster.
-    pub fn clus_to_sector(&self, cluster: u32) -> u32 {
-        self.data_start() + (cluster - 2) * *self.bpb.sec_per_clus() as u32
+    /// - `Ok(FsInfo)`: The FSINFO structure, whether its signatures are valid or not.
+    /// - `Err(FATError::UnsupportedFATType)`: If the volume is not a FAT32 volume.
+    /// - `Err(FATError)`: If the sector cannot be read.
+    pub fn fs_info(&self) -> Result<FsInfo, FATError> {
+        if self.bpb.fat_type() != FATType::FAT32 {
+            return Err(FATError::UnsupportedFATType(
+                self.bpb.fat_type().to_string(),
+            ));
+        }
+
+        let sector = match self.bpb.fs_info_sector() {
+            Some(sector) => self.start as u64 + sector as u64,
+            None => return Err(FATError::InvalidBpbField(String::from("no FSINFO sector"))),
+        };
+        FsInfo::from_slice(&self.read_sectors(sector, 1)?)
+    }
+
+    /// Returns true if the given FAT entry value marks the end of a cluster chain.
+    pub fn is_eoc(&self, value: u32) -> bool {
+        DirEntry::is_eof(value, self.bpb.fat_type())
+    }
+
+    /// Returns the FAT entry value marking a bad cluster on this volume.
+    pub fn bad_cluster_marker(&self) -> u32 {
+        DirEntry::bad_cluster_marker(self.bpb.fat_type())
+    }
+
+    /// Returns the BPB of the volume.
+    pub(super) fn bpb(&self) -> &Bpb {
+        &self.bpb
     }
 
     /// Returns the starting cluster of the volume.
@@ -338,104 +1614,192 @@ impl FATVol {
         self.start()
     }
 
+    /// Returns the number of sectors of the reserved region, boot sector included.
+    pub fn reserved_sector_count(&self) -> u16 {
+        *self.bpb.rsvd_sec_cnt()
+    }
+
+    /// Returns the structure held by a sector of the reserved region, or `None` past the region.
+    ///
+    /// # Parameters
+    /// - `sector`: The sector, relative to the start of the volume.
+    pub fn reserved_sector_kind(&self, sector: u16) -> Option<ReservedSector> {
+        self.bpb.reserved_sector_kind(sector)
+    }
+
     /// Returns the starting sector of the first FAT.
-    fn fat_start(&self) -> u32 {
-        self.rsvd_start() + u32::from(*self.bpb.rsvd_sec_cnt())
+    pub(super) fn fat_start(&self) -> u32 {
+        self.rsvd_start()
+            .saturating_add(u32::from(*self.bpb.rsvd_sec_cnt()))
+    }
+
+    /// Returns the FAT the cluster chains are read from: the one set with
+    /// [`FATVol::set_active_fat`], or else the active FAT of the BPB, see [`Bpb::active_fat`].
+    pub fn active_fat(&self) -> u8 {
+        lock(&self.fat_override).unwrap_or(self.bpb.active_fat())
+    }
+
+    /// Returns whether the FAT the cluster chains are read from was set with
+    /// [`FATVol::set_active_fat`] rather than declared by the BPB.
+    pub fn is_active_fat_forced(&self) -> bool {
+        lock(&self.fat_override).is_some()
+    }
+
+    /// Reads the cluster chains from a FAT copy instead of the active FAT of the BPB, e.g. when
+    /// the copies disagree and one of them was deliberately corrupted.
+    ///
+    /// Without mirroring, the allocations are also written to this copy only.
+    ///
+    /// # Parameters
+    /// - `fat_nb`: The number of the FAT, from 0.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the FAT is changed.
+    /// - `Err(FATError::FatOutOfRange)` if the volume has no such FAT.
+    pub fn set_active_fat(&self, fat_nb: u8) -> Result<(), FATError> {
+        self.fat_copy_start(fat_nb)?;
+        *lock(&self.fat_override) = Some(fat_nb);
+        Ok(())
+    }
+
+    /// Reads the cluster chains from the active FAT of the BPB again.
+    pub fn reset_active_fat(&self) {
+        *lock(&self.fat_override) = None;
+    }
+
+    /// Returns the starting sector of the FAT the cluster chains are read from, see
+    /// [`FATVol::active_fat`].
+    pub(super) fn active_fat_start(&self) -> u32 {
+        self.fat_start()
+            .saturating_add(self.bpb.fat_sz().saturating_mul(self.active_fat() as u32))
     }
 
     /// Returns the starting sector of the root directory.
     fn root_start(&self) -> u32 {
-        self.fat_start() + self.bpb.fat_sz() * *self.bpb.num_fat() as u32
+        self.fat_start()
+            .saturating_add(self.bpb.fat_sz().saturating_mul(*self.bpb.num_fat() as u32))
     }
 
     /// Returns the starting sector of the data region.
     pub fn data_start(&self) -> u32 {
-        self.root_start()
-            + (*self.bpb.root_ent_cnt() as u32 * 32).div_ceil(*self.bpb.bytes_per_sec() as u32)
+        let root_dir_sectors = match *self.bpb.bytes_per_sec() {
+            0 => 0,
+            bytes_per_sec => (*self.bpb.root_ent_cnt() as u32 * 32).div_ceil(bytes_per_sec as u32),
+        };
+        self.root_start().saturating_add(root_dir_sectors)
     }
 
     /// Returns the ending sector of the data region.
-    fn data_end(&self) -> u32 {
-        self.data_start() + self.bpb.cluster_count() * *self.bpb.sec_per_clus() as u32
+    pub(super) fn data_end(&self) -> u32 {
+        self.data_start().saturating_add(
+            self.bpb
+                .cluster_count()
+                .saturating_mul(*self.bpb.sec_per_clus() as u32),
+        )
     }
 }
 
 /// Implements the LayoutDisplay trait for Bpb
 impl LayoutDisplay for FATVol {
-    fn display_layout(&self, indent: u8) -> Result<String, std::fmt::Error> {
+    fn display_layout(
+        &self,
+        indent: u8,
+        options: &LayoutOptions,
+    ) -> Result<String, std::fmt::Error> {
         let mut out = String::from("");
         let indent = " ".repeat(indent.into());
+        let sector_size = *self.bpb.bytes_per_sec() as u64;
 
-        writeln!(out, "{}┌{:─^55}┐", indent, " FAT32 Partition Layout ")?;
+        let title = format!(" {} Partition Layout ", self.bpb.fat_type());
+        writeln!(out, "{}┌{:─^68}┐", indent, title)?;
         writeln!(
             out,
-            "{}├{:^12}┬{:^12}┬{:^12}┬{:^16}┤",
-            indent, "Region", "Start", "End", "Description"
+            "{}├{:^12}┬{:^12}┬{:^12}┬{:^12}┬{:^16}┤",
+            indent, "Region", "Start", "End", "Length", "Description"
         )?;
         writeln!(
             out,
-            "{}├{:─<12}┼{:─<12}┼{:─<12}┼{:─<16}┤",
-            indent, "", "", "", ""
+            "{}├{:─<12}┼{:─<12}┼{:─<12}┼{:─<12}┼{:─<16}┤",
+            indent, "", "", "", "", ""
         )?;
 
-        writeln!(
-            out,
-            "{}│{:<12}│{:<12}│{:<12}│{:<16}│",
-            indent,
+        let row = |out: &mut String, region: &str, start: u64, end: u64, desc: &str| {
+            let [start, end, len] = options.fmt_range(start, end, sector_size);
+            writeln!(
+                out,
+                "{}│{:<12}│{:>12}│{:>12}│{:>12}│{:<16}│",
+                indent, region, start, end, len, desc
+            )
+        };
+
+        let rsvd_start = self.rsvd_start() as u64;
+        row(
+            &mut out,
             "Reserved",
-            self.rsvd_start(),
-            self.fat_start(),
-            "Boot + Reserved"
+            rsvd_start,
+            self.fat_start() as u64,
+            "Boot + Reserved",
         )?;
+        if options.all && self.bpb.fat_type() == FATType::FAT32 {
+            row(&mut out, "", rsvd_start, rsvd_start + 1, "Boot Sector")?;
+            if let Some(fs_info) = self.bpb.fs_info_sector() {
+                let fs_info = rsvd_start + fs_info as u64;
+                row(&mut out, "", fs_info, fs_info + 1, "FSINFO")?;
+            }
+            if let Some(bk_boot) = self.bpb.backup_boot_sector() {
+                let bk_boot = rsvd_start + bk_boot as u64;
+                row(&mut out, "", bk_boot, bk_boot + 1, "Backup Boot")?;
+            }
+            if let Some(bk_fs_info) = self.bpb.backup_fs_info_sector() {
+                let bk_fs_info = rsvd_start + bk_fs_info as u64;
+                row(&mut out, "", bk_fs_info, bk_fs_info + 1, "Backup FSINFO")?;
+            }
+        }
         for i in 0..*self.bpb.num_fat() {
-            let fat_i_start = self.fat_start() + i as u32 * self.bpb.fat_sz();
-            let fat_i_end = fat_i_start + self.bpb.fat_sz();
-            writeln!(
-                out,
-                "{}│{:<12}│{:<12}│{:<12}│{:<16}│",
-                indent,
-                format!("FAT #{}", i),
+            let fat_i_start = self.fat_start() as u64 + i as u64 * self.bpb.fat_sz() as u64;
+            let fat_i_end = fat_i_start + self.bpb.fat_sz() as u64;
+            let desc = match self.bpb.is_mirrored() || i == self.active_fat() {
+                true => "FAT Tables",
+                false => "Inactive FAT",
+            };
+            row(
+                &mut out,
+                &format!("FAT #{}", i),
                 fat_i_start,
                 fat_i_end,
-                "FAT Tables"
+                desc,
             )?;
         }
         if self.bpb.fat_type() != FATType::FAT32 {
-            writeln!(
-                out,
-                "{}│{:<12}│{:<12}│{:<12}│{:<16}│",
-                indent,
+            row(
+                &mut out,
                 "Root Dir",
-                self.root_start(),
-                self.data_start(),
-                "Root Directory"
+                self.root_start() as u64,
+                self.data_start() as u64,
+                "Root Directory",
             )?;
         }
-        writeln!(
-            out,
-            "{}│{:<12}│{:<12}│{:<12}│{:<16}│",
-            indent,
+        row(
+            &mut out,
             "Data",
-            self.data_start(),
-            self.data_end(),
-            "Cluster Data"
+            self.data_start() as u64,
+            self.data_end() as u64,
+            "Cluster Data",
         )?;
         if self.data_end() < self.end {
-            writeln!(
-                out,
-                "{}│{:<12}│{:<12}│{:<12}│{:<16}│",
-                indent,
+            row(
+                &mut out,
                 "",
-                self.data_end(),
-                self.end,
-                "Volume Slack"
+                self.data_end() as u64,
+                self.end as u64,
+                "Volume Slack",
             )?;
         }
 
         writeln!(
             out,
-            "{}└{:─<12}┴{:─<12}┴{:─<12}┴{:─<16}┘",
-            indent, "", "", "", ""
+            "{}└{:─<12}┴{:─<12}┴{:─<12}┴{:─<12}┴{:─<16}┘",
+            indent, "", "", "", "", ""
         )?;
 
         Ok(out)
@@ -443,15 +1807,8 @@ impl LayoutDisplay for FATVol {
 }
 
 impl TreeDisplay for FATVol {
-    fn display_tree(&self) -> Result<(), TraitError> {
-        match self.bpb.fat_type() {
-            FATType::FAT32 => self.print_dir_rec(*self.bpb.root_clus(), 0)?,
-            fat_type => {
-                return Err(TraitError::FATError(FATError::UnsupportedFATType(format!(
-                    "Displaying the directory tree for {fat_type} is currently not supported."
-                ))));
-            }
-        }
+    fn display_tree<W: io::Write>(&self, out: &mut W) -> Result<(), TraitError> {
+        self.print_dir_rec(self.root_cluster()?, 0, out)?;
 
         Ok(())
     }
@@ -471,10 +1828,9 @@ impl SlackWriter for FATVol {
             });
         }
 
-        writer.seek(std::io::SeekFrom::Start(
-            (self.data_end() * *self.bpb.bytes_per_sec() as u32).into(),
-        ))?;
-        writer.write_all(data)?;
+        let offset = self.data_end() as u64 * *self.bpb.bytes_per_sec() as u64;
+        write_at(writer, offset, data)?;
+        self.invalidate_cache(offset, data.len() as u64);
         Ok(())
     }
 
@@ -493,9 +1849,14 @@ impl SlackWriter for FATVol {
             });
         }
 
-        let clusters = self.list_clusters(entry.cluster_number())?;
+        // Only the length and the last cluster of the chain are needed
+        let (cluster_cnt, last_cluster) = self
+            .cluster_chain(entry.cluster_number())
+            .try_fold((0, None), |(cnt, _), cluster| {
+                cluster.map(|cluster| (cnt + 1, Some(cluster)))
+            })?;
         let slack_byte_size =
-            clusters.len() * *self.bpb.sec_per_clus() as usize * *self.bpb.bytes_per_sec() as usize
+            cluster_cnt * *self.bpb.sec_per_clus() as usize * *self.bpb.bytes_per_sec() as usize
                 - *entry.file_size() as usize;
         let cluster_size = *self.bpb.sec_per_clus() as u32 * *self.bpb.bytes_per_sec() as u32;
 
@@ -514,12 +1875,13 @@ impl SlackWriter for FATVol {
             ));
         }
 
-        match clusters.last() {
+        match last_cluster {
             Some(last_cluster) => {
-                let offset = (self.clus_to_sector(*last_cluster) as u64)
+                let offset = (self.clus_to_sector(last_cluster) as u64)
                     * *self.bpb.bytes_per_sec() as u64
                     + (*entry.file_size() as u64) % (cluster_size as u64);
                 write_at(disk_file, offset, data)?;
+                self.invalidate_cache(offset, data.len() as u64);
             }
             _ => {
                 return Err(FATError::InsufficientSlackSpace {
@@ -531,4 +1893,61 @@ impl SlackWriter for FATVol {
 
         Ok(())
     }
+
+    fn write_to_volume_slack_verified<T: io::Read + io::Write + io::Seek>(
+        &self,
+        disk: &mut T,
+        data: &[u8],
+    ) -> result::Result<SlackWrite, FATError> {
+        self.write_to_volume_slack(disk, data)?;
+        self.verify_write(disk, &[self.volume_slack_extent()], data)
+    }
+
+    fn write_to_file_slack_verified<T: io::Read + io::Write + io::Seek>(
+        &self,
+        disk: &mut T,
+        file_path: &Path,
+        data: &[u8],
+    ) -> result::Result<SlackWrite, FATError> {
+        self.write_to_file_slack(disk, file_path, data)?;
+        let extents = self.file_slack_extents(&self.find_file(file_path)?)?;
+        self.verify_write(disk, &extents, data)
+    }
+
+    fn read_to_end_of_volume_slack<T: io::Read + io::Seek>(
+        &self,
+        reader: &mut T,
+    ) -> result::Result<Vec<u8>, FATError> {
+        let (offset, len) = self.volume_slack_extent();
+        Ok(read_at(reader, offset, len as usize)?)
+    }
+
+    fn read_to_end_of_file_slack<T: io::Read + io::Seek>(
+        &self,
+        reader: &mut T,
+        file_path: &Path,
+    ) -> result::Result<Vec<u8>, FATError> {
+        let entry = self.find_file(file_path)?;
+        let mut data = vec![];
+        for (offset, len) in self.file_slack_extents(&entry)? {
+            data.extend(read_at(reader, offset, len as usize)?);
+        }
+
+        Ok(data)
+    }
+
+    fn erase_volume_slack<T: io::Write + io::Seek>(
+        &self,
+        writer: &mut T,
+    ) -> result::Result<u64, FATError> {
+        self.wipe_extents(writer, &[self.volume_slack_extent()], &[0])
+    }
+
+    fn erase_file_slack<T: io::Write + io::Seek>(
+        &self,
+        writer: &mut T,
+        file_path: &Path,
+    ) -> result::Result<u64, FATError> {
+        self.wipe_file_slack(writer, file_path, &[0])
+    }
 }
@@ -5,7 +5,7 @@
 //!
 //! The BPB is a data structure that describes the physical layout and properties of a FAT file system.
 
-use std::io;
+use std::{fmt, io};
 use thiserror;
 
 /// Errors that can occur during BPB parsing and validation.
@@ -41,8 +41,9 @@ pub enum FATError {
     InvalidNumFat(u8),
 
     /// For FAT32 volumes, the root directory entries count must be 0 as the root directory is stored as a regular cluster chain.
+    /// FAT16 volumes store it in a fixed region, which must hold at least one entry.
     #[error(
-        "Invalid count of directory entries in the root directory: `{0}`. It should be 0 for a FAT32 volume. "
+        "Invalid count of directory entries in the root directory: `{0}`. It should be 0 for a FAT32 volume, and greater than 0 for a FAT12 or FAT16 volume."
     )]
     InvalidRootEntCnt(u16),
 
@@ -54,10 +55,10 @@ pub enum FATError {
     #[error("Invalid FAT size:`{0}`")]
     InvalidFatSz(String),
 
-    /// The root directory's first cluster number must be greater than 2.
+    /// The root directory's first cluster number must address a cluster of the data region.
     /// Clusters 0 and 1 are reserved, and the data area starts at cluster 2.
     #[error(
-        "Invalid cluster number of the first cluster of the root directory: `{0}`. This value should be greater than 2."
+        "Invalid cluster number of the first cluster of the root directory: `{0}`. This value should be between 2 and the last cluster of the volume."
     )]
     InvalidRootClus(u32),
 
@@ -69,7 +70,7 @@ pub enum FATError {
     #[error("IO Error: `{0}`")]
     IOError(io::Error),
 
-    /// The detected FAT type is not supported (only FAT32 is supported).
+    /// The detected FAT type is not supported by an operation.
     #[error("Unsupported FAT type: `{0}`")]
     UnsupportedFATType(String),
 
@@ -85,6 +86,14 @@ pub enum FATError {
     #[error("No chain of `{0}` free clusters found.")]
     NoFreeClusterChain(u32),
 
+    /// A cluster expected free is allocated, bad or reserved
+    #[error("Cluster `{0}` is not free.")]
+    ClusterNotFree(u32),
+
+    /// Data read back after a write differs from the data written
+    #[error("The data read back at 0x{0:X} differs from the data written.")]
+    SlackVerificationFailed(u64),
+
     /// Unsupported feature
     #[error("Unsupported feature.")]
     UnsupportedFeature(String),
@@ -97,9 +106,57 @@ pub enum FATError {
     #[error("Invalid cluster number: `{0}`")]
     InvalidClusterError(u32),
 
+    /// A cluster chain loops back onto one of its own clusters
+    #[error("Cluster chain loops back to cluster `{0}`.")]
+    ChainLoop(u32),
+
+    /// A cluster chain references a cluster outside of the data region
+    #[error("Cluster `{0}` is out of the data region range.")]
+    ClusterOutOfRange(u32),
+
+    /// A read addresses sectors outside of the volume
+    #[error("Sectors `{start}` to `{end}` are out of the volume range.")]
+    SectorOutOfRange { start: u64, end: u64 },
+
+    /// A directory entry index past the end of its cluster
+    #[error("Entry `{index}` is past the end of cluster `{cluster}`.")]
+    DirEntryOutOfRange { cluster: u32, index: u32 },
+
+    /// A walk or scan exceeding one of the limits of the volume
+    #[error("Limit exceeded: more than {max} {what}.")]
+    LimitExceeded { what: &'static str, max: u64 },
+
+    /// A FAT number past the FATs of the volume
+    #[error("FAT #{fat_nb} doesn't exist: the volume has {num_fat} FATs.")]
+    FatOutOfRange { fat_nb: u8, num_fat: u8 },
+
+    /// An imported FAT whose size differs from the FATs of the volume
+    #[error("Invalid FAT size: {actual} bytes, expected {expected} bytes.")]
+    FatSizeMismatch { expected: u64, actual: u64 },
+
+    /// An imported boot sector that cannot replace the boot sector of the volume
+    #[error("Invalid boot sector: {0}.")]
+    InvalidBootSector(String),
+
+    /// The checksum sector of an exFAT boot region doesn't match the sectors it covers
+    #[error("Invalid boot region checksum: 0x{actual:08X}, expected 0x{expected:08X}.")]
+    BootChecksumMismatch { expected: u32, actual: u32 },
+
+    /// A walk or scan stopped by the cancellation token of the volume
+    #[error("Operation cancelled.")]
+    Cancelled,
+
+    /// Formatting error while rendering an output
+    #[error("Formatting error: `{0}`")]
+    FmtError(fmt::Error),
+
     /// Invalid file/dir name
     #[error("Invalid file or directory name: `{0}`")]
     InvalidFilenameError(String),
+
+    /// A value that cannot be stored in a BPB field
+    #[error("Invalid BPB field value: {0}")]
+    InvalidBpbField(String),
 }
 
 /// Converts standard I/O errors into FATError.
@@ -109,6 +166,13 @@ impl From<io::Error> for FATError {
     }
 }
 
+/// Converts formatting errors into FATError.
+impl From<fmt::Error> for FATError {
+    fn from(err: fmt::Error) -> Self {
+        FATError::FmtError(err)
+    }
+}
+
 /// Converts BinRead errors into FATError.
 impl From<binread::Error> for FATError {
     fn from(err: binread::Error) -> Self {
@@ -11,9 +11,7 @@ use std::fmt;
 /// - `FAT12`: 12-bit File Allocation Table entries
 /// - `FAT16`: 16-bit File Allocation Table entries
 /// - `FAT32`: 32-bit File Allocation Table entries (most common on large volumes)
-///
-/// Note: Currently only FAT32 is fully supported for analysis.
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FATType {
     FAT12,
     FAT16,
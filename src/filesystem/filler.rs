@@ -0,0 +1,220 @@
+//! Filling of free space with plausible leftovers, for lab preparation.
+//!
+//! A freshly formatted image has zeroed free clusters, so any header found there is a planted file
+//! and carving exercises become trivial. Real volumes are full of remnants: fragments of old text
+//! files, pieces of deleted pictures, copies of clusters of files that were moved. This module
+//! fills a share of the free clusters with such content, reproducibly from a seed.
+
+use super::clusters::ClusterKind;
+use super::fat::{FATVol, contiguous_runs};
+use super::fat_error::FATError;
+use crate::journal::ImageWriter;
+use crate::utils::write_at;
+
+/// Words the text fragments are made of.
+const WORDS: &[&str] = &[
+    "the",
+    "report",
+    "meeting",
+    "invoice",
+    "budget",
+    "draft",
+    "please",
+    "review",
+    "attached",
+    "project",
+    "deadline",
+    "client",
+    "password",
+    "account",
+    "update",
+    "final",
+    "version",
+    "notes",
+    "schedule",
+    "team",
+    "thanks",
+    "regards",
+    "monday",
+    "friday",
+    "office",
+    "payment",
+    "order",
+    "shipping",
+    "contract",
+    "confidential",
+    "agenda",
+    "minutes",
+    "backup",
+    "server",
+    "login",
+];
+
+/// Header of a JFIF image, up to the start of its quantization tables.
+const JPEG_HEADER: &[u8] = b"\xFF\xD8\xFF\xE0\x00\x10JFIF\x00\x01\x01\x00\x00\x01\x00\x01\x00\x00";
+
+/// Maximum number of clusters of a partial JPEG.
+const MAX_JPEG_CLUSTERS: u32 = 8;
+
+/// The content written to the filled clusters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillContent {
+    /// Pseudo-random bytes, like encrypted or compressed remnants.
+    Random,
+    /// A mix of text fragments, partial JPEG images and copies of clusters of allocated files.
+    Plausible,
+}
+
+/// The options of [`FATVol::fill_free`].
+#[derive(Debug, Clone, Copy)]
+pub struct FillOptions {
+    /// The share of the free clusters to fill, from 0 to 1.
+    pub fraction: f64,
+    /// The content written to the filled clusters.
+    pub content: FillContent,
+    /// The seed of the pseudo-random generator: the same seed fills an image the same way.
+    pub seed: u64,
+}
+
+impl Default for FillOptions {
+    fn default() -> Self {
+        Self {
+            fraction: 0.5,
+            content: FillContent::Plausible,
+            seed: 0,
+        }
+    }
+}
+
+/// A xorshift64* pseudo-random generator, good enough for filler content.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state must never be 0
+        Self(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a number uniformly drawn from `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a number uniformly drawn from `[0, bound)`.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound.max(1)
+    }
+
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+impl FATVol {
+    /// Fills a share of the free clusters with pseudo-random or plausible content.
+    ///
+    /// The FAT is left untouched: the filled clusters stay free. With [`FillContent::Plausible`],
+    /// each filled cluster holds a text fragment or a copy of a cluster of an allocated file, and
+    /// some start a partial JPEG image spanning a few contiguous clusters, whose end is missing.
+    ///
+    /// # Parameters
+    /// - `options`: The share of clusters to fill, the content and the seed.
+    ///
+    /// # Returns
+    /// - `Ok(u32)`: The number of clusters filled.
+    /// - `Err(FATError)`: If the FAT or a file cannot be read, or writing fails.
+    pub fn fill_free(&self, options: &FillOptions) -> Result<u32, FATError> {
+        let mut rng = Rng::new(options.seed);
+        let cluster_size = self.cluster_size() as usize;
+        let free_clusters: Vec<u32> = self
+            .clusters(ClusterKind::Free)?
+            .map(|region| *region.cluster())
+            .collect();
+        let allocated_clusters: Vec<u32> = self
+            .clusters(ClusterKind::Allocated)?
+            .map(|region| *region.cluster())
+            .collect();
+
+        let mut disk_file = self.image.open_for_write()?;
+        let mut filled = 0;
+        for (first, count) in contiguous_runs(&free_clusters, u32::MAX) {
+            let mut cluster = first;
+            while cluster < first + count {
+                if rng.next_f64() >= options.fraction {
+                    cluster += 1;
+                    continue;
+                }
+
+                let mut data = vec![0; cluster_size];
+                match (options.content, rng.below(10)) {
+                    (FillContent::Random, _) => rng.fill_bytes(&mut data),
+                    // A partial JPEG: a header, then entropy-coded data cut short
+                    (FillContent::Plausible, 0..2) => {
+                        rng.fill_bytes(&mut data);
+                        data[..JPEG_HEADER.len()].copy_from_slice(JPEG_HEADER);
+                        let len = 1 + rng.below(MAX_JPEG_CLUSTERS as u64) as u32;
+                        for next in cluster + 1..(cluster + len).min(first + count) {
+                            let mut next_data = vec![0; cluster_size];
+                            rng.fill_bytes(&mut next_data);
+                            self.write_filler(&mut disk_file, next, &next_data)?;
+                            filled += 1;
+                        }
+                        self.write_filler(&mut disk_file, cluster, &data)?;
+                        filled += 1;
+                        cluster += len;
+                        continue;
+                    }
+                    (FillContent::Plausible, 2..6) if !allocated_clusters.is_empty() => {
+                        let source =
+                            allocated_clusters[rng.below(allocated_clusters.len() as u64) as usize];
+                        data = self.read_cluster(source)?;
+                    }
+                    (FillContent::Plausible, _) => data = text_fragment(&mut rng, cluster_size),
+                }
+
+                self.write_filler(&mut disk_file, cluster, &data)?;
+                filled += 1;
+                cluster += 1;
+            }
+        }
+
+        Ok(filled)
+    }
+
+    fn write_filler(
+        &self,
+        disk_file: &mut ImageWriter,
+        cluster: u32,
+        data: &[u8],
+    ) -> Result<(), FATError> {
+        write_at(disk_file, self.cluster_offset(cluster), data)?;
+        self.invalidate_cache(self.cluster_offset(cluster), data.len() as u64);
+        Ok(())
+    }
+}
+
+/// Generates a cluster holding a text fragment: words, then the zeros of an old file end.
+fn text_fragment(rng: &mut Rng, len: usize) -> Vec<u8> {
+    let text_len = len / 4 + rng.below(len as u64 * 3 / 4) as usize;
+    let mut text = Vec::with_capacity(len);
+    while text.len() < text_len {
+        text.extend_from_slice(WORDS[rng.below(WORDS.len() as u64) as usize].as_bytes());
+        text.push(match rng.below(12) {
+            0 => b'\n',
+            1 => b'.',
+            _ => b' ',
+        });
+    }
+    text.resize(len, 0);
+    text
+}
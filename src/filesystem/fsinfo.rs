@@ -0,0 +1,93 @@
+//! FAT32 FSINFO structure and parsing.
+//!
+//! The FSINFO sector caches the count of free clusters and a hint of where to look for the next
+//! free cluster. Both values are advisory: drivers may leave them stale.
+
+use binread::{BinRead, BinReaderExt};
+use getset::Getters;
+use std::fmt;
+use std::io;
+
+use super::fat_error::FATError;
+
+/// Value of a free count or next free hint which is not known.
+const UNKNOWN: u32 = 0xFFFFFFFF;
+
+/// FSINFO structure of a FAT32 volume.
+#[derive(BinRead, Debug, Clone, Getters)]
+#[br(little)]
+pub struct FsInfo {
+    /// Lead signature (0x41615252)
+    lead_sig: u32,
+    /// Reserved
+    #[br(count = 480)]
+    _reserved: Vec<u8>,
+    /// Structure signature (0x61417272)
+    struc_sig: u32,
+    /// Last known count of free clusters
+    #[get = "pub"]
+    free_count: u32,
+    /// Cluster from which to start looking for free clusters
+    #[get = "pub"]
+    nxt_free: u32,
+    /// Reserved
+    _reserved_2: [u8; 12],
+    /// Trail signature (0xAA550000)
+    trail_sig: u32,
+}
+
+impl FsInfo {
+    const LEAD_SIG: u32 = 0x41615252;
+    const STRUC_SIG: u32 = 0x61417272;
+    const TRAIL_SIG: u32 = 0xAA550000;
+
+    /// The byte ranges of the reserved fields in the sector, zeroed on a genuine volume.
+    pub const RESERVED_RANGES: [(usize, usize); 2] = [(4, 484), (496, 508)];
+    /// Offset of the free cluster count in the sector.
+    pub(super) const FREE_COUNT_OFFSET: u64 = 488;
+    /// Offset of the next free hint in the sector, right after the free cluster count.
+    pub(super) const NXT_FREE_OFFSET: u64 = 492;
+
+    /// Parses the FSINFO structure from a byte slice.
+    ///
+    /// # Parameters
+    /// - `buf`: The content of the FSINFO sector
+    ///
+    /// # Returns
+    /// - `Ok(FsInfo)`: The parsed structure, whether its signatures are valid or not
+    /// - `Err(FATError)`: If the slice is too short
+    pub fn from_slice(buf: &[u8]) -> Result<FsInfo, FATError> {
+        Ok(io::Cursor::new(buf).read_le()?)
+    }
+
+    /// Returns true if the three signatures of the structure are valid.
+    pub fn is_valid(&self) -> bool {
+        self.lead_sig == FsInfo::LEAD_SIG
+            && self.struc_sig == FsInfo::STRUC_SIG
+            && self.trail_sig == FsInfo::TRAIL_SIG
+    }
+
+    /// Returns the last known count of free clusters, if known.
+    pub fn known_free_count(&self) -> Option<u32> {
+        (self.free_count != UNKNOWN).then_some(self.free_count)
+    }
+
+    /// Returns the hint of the next free cluster, if known.
+    pub fn known_nxt_free(&self) -> Option<u32> {
+        (self.nxt_free != UNKNOWN).then_some(self.nxt_free)
+    }
+}
+
+impl fmt::Display for FsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let known = |value: Option<u32>| value.map_or("unknown".to_string(), |v| v.to_string());
+
+        writeln!(
+            f,
+            "  Signatures:     {}",
+            if self.is_valid() { "valid" } else { "invalid" }
+        )?;
+        writeln!(f, "  Free clusters:  {}", known(self.known_free_count()))?;
+        write!(f, "  Next free:      {}", known(self.known_nxt_free()))
+    }
+}
@@ -0,0 +1,236 @@
+//! Full description of a FAT volume, as shown by the `fsstat` command.
+
+use getset::Getters;
+use std::fmt;
+
+use super::bpb::Bpb;
+use super::fat::{FAT_READ_LEN, FATVol};
+use super::fat_error::FATError;
+use super::fat_type::FATType;
+use super::fsinfo::FsInfo;
+
+/// How the FAT copies are kept up to date, as declared by the `ext_flags` field of the BPB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatMirroring {
+    /// Every FAT copy is updated.
+    Mirrored,
+    /// Only the FAT with the given index is active.
+    ActiveOnly(u8),
+}
+
+impl fmt::Display for FatMirroring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FatMirroring::Mirrored => write!(f, "mirrored"),
+            FatMirroring::ActiveOnly(fat_nb) => write!(f, "disabled, only FAT #{fat_nb} is active"),
+        }
+    }
+}
+
+/// Volume details gathered from the BPB, the FSINFO structure and the FATs.
+#[derive(Debug, Getters)]
+pub struct FsStat<'a> {
+    /// The BPB of the volume.
+    #[get = "pub"]
+    bpb: &'a Bpb,
+    /// The FSINFO structure, for FAT32 volumes.
+    #[get = "pub"]
+    fs_info: Option<FsInfo>,
+    /// The FAT type (FAT12, FAT16 or FAT32).
+    #[get = "pub"]
+    fat_type: String,
+    /// The starting sector of the first FAT.
+    #[get = "pub"]
+    fat_start: u32,
+    /// The starting sector of the data region.
+    #[get = "pub"]
+    data_start: u32,
+    /// The ending sector of the data region.
+    #[get = "pub"]
+    data_end: u32,
+    /// The number of clusters of the data region.
+    #[get = "pub"]
+    cluster_count: u32,
+    /// The size of a cluster in bytes.
+    #[get = "pub"]
+    cluster_size: u32,
+    /// The mirroring state declared by the BPB.
+    #[get = "pub"]
+    mirroring: FatMirroring,
+    /// The FAT the cluster chains are read from, authoritative for every analysis.
+    #[get = "pub"]
+    active_fat: u8,
+    /// Whether the active FAT was forced rather than declared by the BPB, see
+    /// [`FATVol::set_active_fat`].
+    #[get = "pub"]
+    active_fat_forced: bool,
+    /// The indices of the FAT copies whose content differs from the active FAT.
+    #[get = "pub"]
+    diverging_fats: Vec<u8>,
+    /// Whether the volume was cleanly unmounted, if recorded.
+    #[get = "pub"]
+    clean_shutdown: Option<bool>,
+    /// Whether a disk I/O error was encountered, if recorded.
+    #[get = "pub"]
+    hard_error: Option<bool>,
+    /// The volume label stored in the BPB.
+    #[get = "pub"]
+    label: String,
+    /// The volume label stored in the root directory, if any.
+    #[get = "pub"]
+    root_label: Option<String>,
+    /// The volume serial number.
+    #[get = "pub"]
+    serial: u32,
+    /// The unusual fields of the layout (see [`Bpb::layout_oddities`]).
+    #[get = "pub"]
+    oddities: Vec<String>,
+}
+
+impl FATVol {
+    /// Gathers the details of the volume.
+    ///
+    /// # Returns
+    /// - `Ok(FsStat)`: The details of the volume.
+    /// - `Err(FATError)`: If the FATs or the root directory cannot be read.
+    pub fn fsstat(&self) -> Result<FsStat<'_>, FATError> {
+        let bpb = self.bpb();
+        let fat_type = bpb.fat_type();
+
+        let mirroring = match *bpb.ext_flags() & 0x80 {
+            0 => FatMirroring::Mirrored,
+            _ => FatMirroring::ActiveOnly((*bpb.ext_flags() & 0x0F) as u8),
+        };
+
+        // The high bits of the second FAT entry hold the dirty flags
+        let (clean_mask, error_mask) = match fat_type {
+            FATType::FAT32 => (0x08000000, 0x04000000),
+            FATType::FAT16 => (0x8000, 0x4000),
+            FATType::FAT12 => (0, 0),
+        };
+        let (clean_shutdown, hard_error) = match fat_type {
+            FATType::FAT12 => (None, None),
+            _ => {
+                let entry = self.read_fat_entry(1, self.active_fat())?;
+                (Some(entry & clean_mask != 0), Some(entry & error_mask == 0))
+            }
+        };
+
+        let root_label = match self.root_cluster() {
+            Ok(root_cluster) => self
+                .list_dir(root_cluster)?
+                .iter()
+                .find(|entry| entry.is_volume_id() && !entry.is_deleted())
+                .map(|entry| entry.raw_name()),
+            Err(_) => None,
+        };
+
+        Ok(FsStat {
+            bpb,
+            fs_info: self.fs_info().ok(),
+            fat_type: fat_type.to_string(),
+            fat_start: self.fat_start(),
+            data_start: self.data_start(),
+            data_end: self.data_end(),
+            cluster_count: self.cluster_count(),
+            cluster_size: self.cluster_size(),
+            mirroring,
+            active_fat: self.active_fat(),
+            active_fat_forced: self.is_active_fat_forced(),
+            diverging_fats: self.diverging_fats()?,
+            clean_shutdown,
+            hard_error,
+            label: bpb.label(),
+            root_label,
+            serial: *bpb.vol_id(),
+            oddities: bpb.layout_oddities(),
+        })
+    }
+
+    /// Returns the indices of the FAT copies whose content differs from the active FAT.
+    fn diverging_fats(&self) -> Result<Vec<u8>, FATError> {
+        let fat_sz = self.bpb().fat_sz() as u64;
+        let active = self.active_fat();
+        let mut diverging = vec![];
+
+        for fat_nb in (0..*self.bpb().num_fat()).filter(|fat_nb| *fat_nb != active) {
+            let mut sector = 0;
+            while sector < fat_sz {
+                let count = FAT_READ_LEN.min(fat_sz - sector);
+                let first = self.read_sectors(self.active_fat_start() as u64 + sector, count)?;
+                let copy = self.read_sectors(
+                    self.fat_start() as u64 + fat_nb as u64 * fat_sz + sector,
+                    count,
+                )?;
+                if first != copy {
+                    diverging.push(fat_nb);
+                    break;
+                }
+                sector += count;
+            }
+        }
+
+        Ok(diverging)
+    }
+}
+
+impl fmt::Display for FsStat<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |value: Option<bool>| value.map_or("-".to_string(), |v| v.to_string());
+
+        writeln!(f, "File System Information:")?;
+        writeln!(f, "  FAT type:       {}", self.fat_type)?;
+        writeln!(
+            f,
+            "  OEM name:       {}",
+            String::from_utf8_lossy(self.bpb.oem_name())
+        )?;
+        writeln!(f, "  Volume label:   {}", self.label)?;
+        writeln!(
+            f,
+            "  Root label:     {}",
+            self.root_label.as_deref().unwrap_or("-")
+        )?;
+        writeln!(f, "  Serial number:  {:08X}", self.serial)?;
+        writeln!(f, "  Clean shutdown: {}", flag(self.clean_shutdown))?;
+        writeln!(f, "  Hard error:     {}", flag(self.hard_error))?;
+
+        writeln!(f, "\nLayout:")?;
+        writeln!(f, "  FAT start:      {}", self.fat_start)?;
+        writeln!(f, "  Data start:     {}", self.data_start)?;
+        writeln!(f, "  Data end:       {}", self.data_end)?;
+        writeln!(f, "  Cluster count:  {}", self.cluster_count)?;
+        writeln!(f, "  Cluster size:   {} bytes", self.cluster_size)?;
+        match self.bpb.backup_boot_sector() {
+            Some(sector) => writeln!(f, "  Backup boot:    {sector}")?,
+            None => writeln!(f, "  Backup boot:    none")?,
+        }
+        for oddity in &self.oddities {
+            writeln!(f, "  Oddity:         {oddity}")?;
+        }
+
+        writeln!(f, "\nFATs:")?;
+        writeln!(f, "  Count:          {}", self.bpb.num_fat())?;
+        writeln!(f, "  Size:           {} sectors", self.bpb.fat_sz())?;
+        writeln!(f, "  Mirroring:      {}", self.mirroring)?;
+        match self.active_fat_forced {
+            true => writeln!(f, "  Read from:      FAT #{} (set fat)", self.active_fat)?,
+            false => writeln!(f, "  Read from:      FAT #{} (BPB)", self.active_fat)?,
+        }
+        match self.diverging_fats.is_empty() {
+            true => writeln!(f, "  Consistency:    all copies match")?,
+            false => writeln!(
+                f,
+                "  Consistency:    FAT(s) {:?} differ from FAT #{}",
+                self.diverging_fats, self.active_fat
+            )?,
+        }
+
+        if let Some(fs_info) = &self.fs_info {
+            writeln!(f, "\nFSINFO (sector {}):", self.bpb.fs_info())?;
+            writeln!(f, "{fs_info}")?;
+        }
+
+        write!(f, "\n{}", self.bpb)
+    }
+}
@@ -0,0 +1,61 @@
+//! Summary of the geometry and identity of a FAT volume.
+
+use getset::Getters;
+
+use super::fat::FATVol;
+use super::fat_type::FATType;
+
+/// The basic facts of a volume, read from its BPB.
+///
+/// Unlike [`FsStat`](super::fsstat::FsStat), gathering them reads nothing from the image.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct VolumeInfo {
+    /// The FAT type (FAT12, FAT16 or FAT32).
+    #[get = "pub"]
+    fat_type: FATType,
+    /// The size of a sector in bytes.
+    #[get = "pub"]
+    bytes_per_sector: u16,
+    /// The number of sectors of a cluster.
+    #[get = "pub"]
+    sectors_per_cluster: u8,
+    /// The number of FAT copies.
+    #[get = "pub"]
+    fat_count: u8,
+    /// The size of a FAT in sectors.
+    #[get = "pub"]
+    fat_size: u32,
+    /// The number of usable clusters of the data region.
+    #[get = "pub"]
+    cluster_count: u32,
+    /// The total number of sectors declared by the BPB.
+    #[get = "pub"]
+    total_sectors: u32,
+    /// The volume label stored in the BPB.
+    #[get = "pub"]
+    label: String,
+    /// The volume serial number.
+    #[get = "pub"]
+    serial: u32,
+}
+
+impl FATVol {
+    /// Summarizes the geometry and identity of the volume.
+    ///
+    /// # Returns
+    /// - `VolumeInfo`: The facts declared by the BPB.
+    pub fn info(&self) -> VolumeInfo {
+        let bpb = self.bpb();
+        VolumeInfo {
+            fat_type: bpb.fat_type(),
+            bytes_per_sector: *bpb.bytes_per_sec(),
+            sectors_per_cluster: *bpb.sec_per_clus(),
+            fat_count: *bpb.num_fat(),
+            fat_size: bpb.fat_sz(),
+            cluster_count: self.cluster_count(),
+            total_sectors: bpb.tot_sec(),
+            label: bpb.label(),
+            serial: *bpb.vol_id(),
+        }
+    }
+}
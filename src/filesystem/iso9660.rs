@@ -0,0 +1,646 @@
+//! ISO 9660 volume descriptor and El Torito boot catalog detection.
+//!
+//! Optical media dumps have no partition table: their first 16 sectors of 2048 bytes are a system
+//! area, followed by a set of volume descriptors closed by a terminator. The primary volume
+//! descriptor gives the identifiers, the size and the root directory of the volume; a
+//! supplementary descriptor adds Joliet names; a boot record points to the El Torito boot catalog,
+//! listing the boot images of bootable media. Hybrid images prepend an MBR to the system area, so
+//! that the same image boots from a USB stick.
+
+use binread::{BinRead, BinReaderExt};
+use getset::Getters;
+use std::fmt::{self, Write};
+use std::sync::Arc;
+
+use super::fat_error::FATError;
+use crate::image::ImageSource;
+use crate::traits::{LayoutDisplay, LayoutOptions};
+use crate::utils;
+
+/// The size in bytes of a sector of optical media.
+pub const ISO_SECTOR_SIZE: u64 = 2048;
+
+/// The number of sectors of the system area, preceding the volume descriptors.
+pub const SYSTEM_AREA_SECTORS: u64 = 16;
+
+/// The standard identifier of the volume descriptors.
+pub const STANDARD_ID: &[u8; 5] = b"CD001";
+
+/// The boot system identifier of an El Torito boot record, NUL-padded.
+const EL_TORITO_ID: &[u8] = b"EL TORITO SPECIFICATION";
+
+/// The type of a boot record.
+const TYPE_BOOT_RECORD: u8 = 0;
+
+/// The type of the primary volume descriptor.
+const TYPE_PRIMARY: u8 = 1;
+
+/// The type of a supplementary volume descriptor, e.g. Joliet.
+const TYPE_SUPPLEMENTARY: u8 = 2;
+
+/// The type of the descriptor closing the set.
+const TYPE_TERMINATOR: u8 = 255;
+
+/// The maximum number of volume descriptors read before the terminator.
+const MAX_DESCRIPTORS: u64 = 64;
+
+/// The size in bytes of an entry of the boot catalog.
+const CATALOG_ENTRY_SIZE: usize = 32;
+
+/// ISO 9660 primary volume descriptor structure, the fields not used by the detection being
+/// skipped. Numbers are stored in both byte orders, only the little-endian copy is read.
+#[derive(BinRead, Debug, Getters)]
+#[br(little)]
+pub struct PrimaryVolumeDescriptor {
+    /// Volume descriptor type (1)
+    descriptor_type: u8,
+    /// Standard identifier ("CD001")
+    standard_id: [u8; 5],
+    /// Volume descriptor version (1)
+    #[get = "pub"]
+    version: u8,
+    /// Identifier of the system that can use the system area, space-padded
+    #[br(pad_before = 1)]
+    system_id: [u8; 32],
+    /// Volume identifier, space-padded
+    volume_id: [u8; 32],
+    /// Number of logical blocks of the volume
+    #[br(pad_before = 8)]
+    #[get = "pub"]
+    volume_space_size: u32,
+    /// Size of a logical block in bytes, usually 2048, after the volume set size and number
+    #[br(pad_before = 44)]
+    #[get = "pub"]
+    logical_block_size: u16,
+    /// Size of the path table in bytes
+    #[br(pad_before = 2)]
+    #[get = "pub"]
+    path_table_size: u32,
+    /// First logical block of the little-endian path table
+    #[br(pad_before = 4)]
+    #[get = "pub"]
+    path_table_block: u32,
+    /// First logical block of the root directory, from its directory record
+    #[br(pad_before = 14)]
+    #[get = "pub"]
+    root_block: u32,
+    /// Size of the root directory in bytes
+    #[br(pad_before = 4)]
+    #[get = "pub"]
+    root_size: u32,
+    /// Publisher identifier, space-padded, after the volume set identifier
+    #[br(pad_before = 148, count = 128)]
+    publisher_id: Vec<u8>,
+    /// Application identifier, space-padded, after the data preparer identifier
+    #[br(pad_before = 128, count = 128)]
+    application_id: Vec<u8>,
+    /// Creation date and time, as digits followed by the offset from UTC in 15 minute intervals,
+    /// after the copyright, abstract and bibliographic file identifiers
+    #[br(pad_before = 111)]
+    creation_date: [u8; 17],
+    /// Modification date and time, in the same format
+    modification_date: [u8; 17],
+}
+
+impl PrimaryVolumeDescriptor {
+    /// Returns the system identifier, without its padding.
+    pub fn system_id(&self) -> String {
+        padded_string(&self.system_id)
+    }
+
+    /// Returns the volume identifier, without its padding.
+    pub fn volume_id(&self) -> String {
+        padded_string(&self.volume_id)
+    }
+
+    /// Returns the publisher identifier, without its padding.
+    pub fn publisher_id(&self) -> String {
+        padded_string(&self.publisher_id)
+    }
+
+    /// Returns the application identifier, without its padding.
+    pub fn application_id(&self) -> String {
+        padded_string(&self.application_id)
+    }
+
+    /// Returns the creation date, `None` if unset.
+    pub fn creation_date(&self) -> Option<String> {
+        format_date(&self.creation_date)
+    }
+
+    /// Returns the modification date, `None` if unset.
+    pub fn modification_date(&self) -> Option<String> {
+        format_date(&self.modification_date)
+    }
+
+    /// Validates the fields of the descriptor.
+    ///
+    /// # Parameters
+    /// - `image_len`: The size in bytes of the image holding the volume.
+    fn validate(&self, image_len: u64) -> Result<(), FATError> {
+        let invalid = |msg: String| Err(FATError::InvalidBootSector(msg));
+
+        if self.descriptor_type != TYPE_PRIMARY || &self.standard_id != STANDARD_ID {
+            return invalid(String::from("no primary volume descriptor"));
+        }
+        if self.version != 1 {
+            return invalid(format!(
+                "unsupported volume descriptor version {}",
+                self.version
+            ));
+        }
+        if !self.logical_block_size.is_power_of_two()
+            || !(512..=2048).contains(&self.logical_block_size)
+        {
+            return Err(FATError::InvalidBytesPerSec(self.logical_block_size));
+        }
+        let volume_len = self.volume_space_size as u64 * self.logical_block_size as u64;
+        if volume_len > image_len {
+            return Err(FATError::InvalidTotSec(format!(
+                "{} blocks of {} bytes in an image of {image_len} bytes",
+                self.volume_space_size, self.logical_block_size
+            )));
+        }
+        if self.root_block >= self.volume_space_size {
+            return invalid(format!(
+                "the root directory (block {}) is past the {} blocks of the volume",
+                self.root_block, self.volume_space_size
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes a space- or NUL-padded identifier.
+fn padded_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches([' ', '\0'])
+        .to_string()
+}
+
+/// Formats a date of a volume descriptor.
+///
+/// # Returns
+/// - `Some(date)` as `YYYY-MM-DD HH:MM:SS UTC+HH:MM`.
+/// - `None` if the date is unset, its digits being zeros, or isn't made of digits.
+fn format_date(raw: &[u8; 17]) -> Option<String> {
+    let digits = std::str::from_utf8(&raw[..16]).ok()?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) || digits.bytes().all(|b| b == b'0') {
+        return None;
+    }
+    let minutes = raw[16] as i8 as i32 * 15;
+    Some(format!(
+        "{}-{}-{} {}:{}:{} UTC{}{:02}:{:02}",
+        &digits[0..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14],
+        if minutes < 0 { '-' } else { '+' },
+        minutes.abs() / 60,
+        minutes.abs() % 60
+    ))
+}
+
+/// A boot image listed by the El Torito boot catalog.
+#[derive(Debug, Getters)]
+pub struct BootEntry {
+    /// Whether the image is bootable.
+    #[get = "pub"]
+    bootable: bool,
+    /// The platform of the image: 0 for x86, 1 for PowerPC, 2 for Mac, 0xEF for EFI.
+    #[get = "pub"]
+    platform: u8,
+    /// The emulation of the image: 0 for none, 1 to 3 for floppies, 4 for a hard disk.
+    #[get = "pub"]
+    media: u8,
+    /// The number of 512-byte sectors loaded by the BIOS, for images without emulation.
+    #[get = "pub"]
+    sector_count: u16,
+    /// The first sector of the image, in sectors of 2048 bytes.
+    #[get = "pub"]
+    load_rba: u32,
+}
+
+impl BootEntry {
+    /// Parses an initial or section entry of the boot catalog.
+    fn parse(raw: &[u8], platform: u8) -> Self {
+        Self {
+            bootable: raw[0] == 0x88,
+            platform,
+            media: raw[1] & 0x0F,
+            sector_count: utils::u16_at(raw, 6),
+            load_rba: utils::u32_at(raw, 8),
+        }
+    }
+
+    /// Returns the size in bytes of the image: the size of the emulated floppy, or the loaded
+    /// sectors otherwise.
+    pub fn image_len(&self) -> u64 {
+        match self.media {
+            1 => 1_228_800,
+            2 => 1_474_560,
+            3 => 2_949_120,
+            _ => self.sector_count as u64 * 512,
+        }
+    }
+
+    /// Returns the name of the platform of the image.
+    pub fn platform_name(&self) -> String {
+        match self.platform {
+            0 => String::from("x86"),
+            1 => String::from("PowerPC"),
+            2 => String::from("Mac"),
+            0xEF => String::from("EFI"),
+            other => format!("platform 0x{other:02X}"),
+        }
+    }
+
+    /// Returns the name of the emulation of the image.
+    pub fn media_name(&self) -> &'static str {
+        match self.media {
+            0 => "no emulation",
+            1 => "1.2M floppy",
+            2 => "1.44M floppy",
+            3 => "2.88M floppy",
+            4 => "hard disk",
+            _ => "unknown emulation",
+        }
+    }
+}
+
+impl fmt::Display for BootEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, {}, {}",
+            self.platform_name(),
+            if self.bootable {
+                "bootable"
+            } else {
+                "not bootable"
+            },
+            self.media_name()
+        )
+    }
+}
+
+/// The El Torito boot catalog of a bootable volume.
+#[derive(Debug, Getters)]
+pub struct BootCatalog {
+    /// The sector of the catalog, in sectors of 2048 bytes.
+    #[get = "pub"]
+    sector: u32,
+    /// The boot images, the default one first.
+    #[get = "pub"]
+    entries: Vec<BootEntry>,
+}
+
+impl BootCatalog {
+    /// Parses a boot catalog.
+    ///
+    /// # Parameters
+    /// - `sector`: The sector of the catalog, in sectors of 2048 bytes.
+    /// - `raw`: The content of the sector.
+    ///
+    /// # Returns
+    /// - `Ok(BootCatalog)`: The catalog, with its default entry and the entries of its sections.
+    /// - `Err(FATError::InvalidBootSector)`: If the validation entry is invalid.
+    fn parse(sector: u32, raw: &[u8]) -> Result<Self, FATError> {
+        let validation = &raw[..CATALOG_ENTRY_SIZE];
+        let checksum = (0..CATALOG_ENTRY_SIZE).step_by(2).fold(0u16, |sum, idx| {
+            sum.wrapping_add(utils::u16_at(validation, idx))
+        });
+        if validation[0] != 0x01 || validation[30..32] != [0x55, 0xAA] || checksum != 0 {
+            return Err(FATError::InvalidBootSector(format!(
+                "the boot catalog at sector {sector} has an invalid validation entry"
+            )));
+        }
+
+        let mut entries = vec![BootEntry::parse(
+            &raw[CATALOG_ENTRY_SIZE..2 * CATALOG_ENTRY_SIZE],
+            validation[1],
+        )];
+        let mut slots = raw[2 * CATALOG_ENTRY_SIZE..].chunks_exact(CATALOG_ENTRY_SIZE);
+        // Each section header, 0x91 for the last one, is followed by its entries
+        while let Some(header) = slots.next() {
+            if !matches!(header[0], 0x90 | 0x91) {
+                break;
+            }
+            let count = utils::u16_at(header, 2);
+            for _ in 0..count {
+                match slots.next() {
+                    Some(entry) => entries.push(BootEntry::parse(entry, header[1])),
+                    None => break,
+                }
+            }
+            if header[0] == 0x91 {
+                break;
+            }
+        }
+
+        Ok(Self { sector, entries })
+    }
+}
+
+/// Represents an ISO 9660 volume, such as an optical media dump, recognized but not analyzed.
+#[derive(Debug, Getters)]
+pub struct IsoVol {
+    /// The primary volume descriptor.
+    #[get = "pub"]
+    pvd: PrimaryVolumeDescriptor,
+    /// The absolute byte offset of the volume in the image.
+    #[get = "pub"]
+    offset: u64,
+    /// The size in bytes of the image holding the volume.
+    #[get = "pub"]
+    image_len: u64,
+    /// The sector and type of each volume descriptor, terminator included, in sectors of 2048
+    /// bytes.
+    #[get = "pub"]
+    descriptors: Vec<(u64, u8)>,
+    /// Whether a supplementary volume descriptor holds Joliet names.
+    #[get = "pub"]
+    joliet: bool,
+    /// The sector of the El Torito boot catalog, in sectors of 2048 bytes, if the volume has a
+    /// boot record.
+    #[get = "pub"]
+    catalog_sector: Option<u32>,
+    /// The El Torito boot catalog, `None` if the volume isn't bootable or the catalog is
+    /// unreadable.
+    #[get = "pub"]
+    boot_catalog: Option<BootCatalog>,
+    /// The size in bytes of a sector of the disk, the unit of the layout.
+    #[get = "pub"]
+    sector_size: usize,
+}
+
+impl IsoVol {
+    /// Reads the volume descriptors and the boot catalog of an ISO 9660 volume.
+    ///
+    /// # Parameters
+    /// - `image`: The disk image containing the volume
+    /// - `start`: The sector number where the volume starts, its system area included
+    /// - `sector_cnt`: The number of sectors of the image from the start of the volume
+    /// - `validate`: Whether to validate the primary volume descriptor
+    /// - `sector_size`: The size of each sector of the disk in bytes
+    ///
+    /// # Returns
+    /// - `Ok(IsoVol)`: The ISO 9660 volume
+    /// - `Err(FATError)`: If reading fails, the volume has no primary volume descriptor or
+    ///   validation fails
+    pub fn from_backend(
+        image: Arc<ImageSource>,
+        start: u32,
+        sector_cnt: u32,
+        validate: bool,
+        sector_size: usize,
+    ) -> Result<IsoVol, FATError> {
+        let offset = start as u64 * sector_size as u64;
+        let image_len = sector_cnt as u64 * sector_size as u64;
+
+        let mut pvd = None;
+        let mut descriptors = vec![];
+        let mut joliet = false;
+        let mut catalog_sector = None;
+        for sector in SYSTEM_AREA_SECTORS..SYSTEM_AREA_SECTORS + MAX_DESCRIPTORS {
+            let raw = image.read_at(offset + sector * ISO_SECTOR_SIZE, ISO_SECTOR_SIZE as usize)?;
+            if &raw[1..6] != STANDARD_ID {
+                break;
+            }
+            descriptors.push((sector, raw[0]));
+            match raw[0] {
+                TYPE_PRIMARY if pvd.is_none() => {
+                    pvd = Some(std::io::Cursor::new(&raw).read_be::<PrimaryVolumeDescriptor>()?)
+                }
+                // The escape sequences of the UCS-2 levels of Joliet
+                TYPE_SUPPLEMENTARY => {
+                    joliet |= raw[88..90] == *b"%/" && matches!(raw[90], b'@' | b'C' | b'E')
+                }
+                TYPE_BOOT_RECORD if raw[7..7 + EL_TORITO_ID.len()] == *EL_TORITO_ID => {
+                    catalog_sector = Some(utils::u32_at(&raw, 71))
+                }
+                TYPE_TERMINATOR => break,
+                _ => {}
+            }
+        }
+        let Some(pvd) = pvd else {
+            return Err(FATError::InvalidBootSector(String::from(
+                "no primary volume descriptor",
+            )));
+        };
+        if validate {
+            pvd.validate(image_len)?;
+        }
+
+        // A damaged boot catalog doesn't prevent recognizing the volume
+        let boot_catalog = catalog_sector.and_then(|sector| {
+            image
+                .read_at(
+                    offset + sector as u64 * ISO_SECTOR_SIZE,
+                    ISO_SECTOR_SIZE as usize,
+                )
+                .ok()
+                .and_then(|raw| BootCatalog::parse(sector, &raw).ok())
+        });
+
+        Ok(IsoVol {
+            pvd,
+            offset,
+            image_len,
+            descriptors,
+            joliet,
+            catalog_sector,
+            boot_catalog,
+            sector_size,
+        })
+    }
+
+    /// Checks whether the volume at the given position starts its descriptor set with an ISO 9660
+    /// volume descriptor.
+    ///
+    /// # Parameters
+    /// - `image`: The disk image
+    /// - `start`: The sector number where the candidate volume starts
+    /// - `sector_size`: The size of each sector of the disk in bytes
+    pub fn is_iso(image: &ImageSource, start: u32, sector_size: usize) -> bool {
+        image
+            .read_at(
+                start as u64 * sector_size as u64 + SYSTEM_AREA_SECTORS * ISO_SECTOR_SIZE,
+                8,
+            )
+            .is_ok_and(|raw| &raw[1..6] == STANDARD_ID)
+    }
+
+    /// Returns the size in bytes of the volume.
+    pub fn volume_len(&self) -> u64 {
+        self.pvd.volume_space_size as u64 * self.pvd.logical_block_size as u64
+    }
+}
+
+impl LayoutDisplay for IsoVol {
+    fn display_layout(
+        &self,
+        indent: u8,
+        options: &LayoutOptions,
+    ) -> Result<String, std::fmt::Error> {
+        let mut out = String::from("");
+        let indent = " ".repeat(indent.into());
+        let pvd = &self.pvd;
+        let sector_size = self.sector_size as u64;
+
+        writeln!(out, "{}┌{:─^68}┐", indent, " ISO 9660 Volume Layout ")?;
+        let identifiers = [
+            ("Volume ID", pvd.volume_id()),
+            ("System ID", pvd.system_id()),
+            ("Publisher", pvd.publisher_id()),
+            ("Application", pvd.application_id()),
+            ("Created", pvd.creation_date().unwrap_or_default()),
+            ("Modified", pvd.modification_date().unwrap_or_default()),
+        ];
+        for (label, value) in identifiers.iter().filter(|(_, value)| !value.is_empty()) {
+            let value: String = value.chars().take(52).collect();
+            writeln!(out, "{}├{:<16}{:>52}┤", indent, label, value)?;
+        }
+        writeln!(
+            out,
+            "{}├{:<56}{:>12}┤",
+            indent,
+            "Block Size",
+            pvd.logical_block_size()
+        )?;
+        writeln!(
+            out,
+            "{}├{:<56}{:>12}┤",
+            indent,
+            "Blocks",
+            pvd.volume_space_size()
+        )?;
+        let joliet = match self.joliet {
+            true => "yes",
+            false => "no",
+        };
+        writeln!(out, "{}├{:<56}{:>12}┤", indent, "Joliet", joliet)?;
+        match (self.catalog_sector, &self.boot_catalog) {
+            (None, _) => writeln!(out, "{}├{:<56}{:>12}┤", indent, "El Torito", "no")?,
+            (Some(_), None) => {
+                writeln!(out, "{}├{:<56}{:>12}┤", indent, "El Torito", "unreadable")?
+            }
+            (Some(_), Some(catalog)) => {
+                for (idx, entry) in catalog.entries().iter().enumerate() {
+                    writeln!(
+                        out,
+                        "{}├{:<16}{:>52}┤",
+                        indent,
+                        format!("Boot Image {}", idx + 1),
+                        entry.to_string()
+                    )?;
+                }
+            }
+        }
+        writeln!(out, "{}├{:─^68}┤", indent, "")?;
+        writeln!(
+            out,
+            "{}├{:^12}┬{:^12}┬{:^12}┬{:^12}┬{:^16}┤",
+            indent, "Region", "Start", "End", "Length", "Description"
+        )?;
+        writeln!(
+            out,
+            "{}├{:─<12}┼{:─<12}┼{:─<12}┼{:─<12}┼{:─<16}┤",
+            indent, "", "", "", "", ""
+        )?;
+
+        // Positions are absolute byte offsets, shown in sectors of the disk
+        let row = |out: &mut String, region: &str, start: u64, end: u64, desc: &str| {
+            let [start, end, len] =
+                options.fmt_range(start / sector_size, end.div_ceil(sector_size), sector_size);
+            writeln!(
+                out,
+                "{}│{:<12}│{:>12}│{:>12}│{:>12}│{:<16}│",
+                indent, region, start, end, len, desc
+            )
+        };
+        let sector = |number: u64| self.offset + number * ISO_SECTOR_SIZE;
+        let block_size = pvd.logical_block_size as u64;
+        let block = |number: u64| {
+            self.offset
+                .saturating_add(number.saturating_mul(block_size))
+        };
+
+        row(
+            &mut out,
+            "System Area",
+            self.offset,
+            sector(SYSTEM_AREA_SECTORS),
+            "Boot Code / MBR",
+        )?;
+        for (number, kind) in &self.descriptors {
+            let desc = match *kind {
+                TYPE_BOOT_RECORD => "Boot Record",
+                TYPE_PRIMARY => "Primary",
+                TYPE_SUPPLEMENTARY if self.joliet => "Joliet",
+                TYPE_SUPPLEMENTARY => "Supplementary",
+                TYPE_TERMINATOR => "Terminator",
+                _ => "Other",
+            };
+            row(
+                &mut out,
+                "Descriptor",
+                sector(*number),
+                sector(number + 1),
+                desc,
+            )?;
+        }
+        let path_table = block(pvd.path_table_block as u64);
+        row(
+            &mut out,
+            "Path Table",
+            path_table,
+            path_table + pvd.path_table_size as u64,
+            "Directories",
+        )?;
+        let root = block(pvd.root_block as u64);
+        row(
+            &mut out,
+            "Root Dir",
+            root,
+            root + pvd.root_size as u64,
+            "Root Directory",
+        )?;
+        if let Some(catalog) = &self.boot_catalog {
+            let catalog_start = sector(*catalog.sector() as u64);
+            row(
+                &mut out,
+                "Boot Catalog",
+                catalog_start,
+                catalog_start + ISO_SECTOR_SIZE,
+                "El Torito",
+            )?;
+            for (idx, entry) in catalog.entries().iter().enumerate() {
+                let image_start = sector(*entry.load_rba() as u64);
+                row(
+                    &mut out,
+                    "Boot Image",
+                    image_start,
+                    image_start + entry.image_len(),
+                    &format!("{} #{}", entry.platform_name(), idx + 1),
+                )?;
+            }
+        }
+        let volume_end = self.offset.saturating_add(self.volume_len());
+        let end = self.offset + self.image_len;
+        if volume_end < end {
+            row(&mut out, "", volume_end, end, "Volume Slack")?;
+        }
+
+        writeln!(
+            out,
+            "{}└{:─<12}┴{:─<12}┴{:─<12}┴{:─<12}┴{:─<16}┘",
+            indent, "", "", "", "", ""
+        )?;
+
+        Ok(out)
+    }
+}
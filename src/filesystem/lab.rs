@@ -0,0 +1,157 @@
+//! Crafting of the boot sector of an existing volume, for lab preparation.
+//!
+//! Instructors can personalize the images given to each cohort: OEM name, volume label, serial
+//! number and boot code. Every change is written to the boot sector and to its backup copy, along
+//! with the boot sector signature, so that the crafted fields don't show up as damage.
+
+use super::bpb::Bpb;
+use super::dir_entry::DirEntry;
+use super::fat::FATVol;
+use super::fat_error::FATError;
+use super::fat_type::FATType;
+use crate::utils::write_at;
+
+/// Offsets of the crafted fields in the boot sector of a FAT32 volume.
+const OEM_NAME_OFFSET: u64 = 0x03;
+const VOL_ID_OFFSET: u64 = 0x43;
+const VOL_LAB_OFFSET: u64 = 0x47;
+const BOOT_CODE_OFFSET: u64 = 0x5A;
+const SIGNATURE_OFFSET: u64 = 0x1FE;
+
+const OEM_NAME_LEN: usize = 8;
+const VOL_LAB_LEN: usize = 11;
+const BOOT_CODE_LEN: usize = 420;
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+/// The label of a volume without a name.
+const NO_NAME_LABEL: &str = "NO NAME";
+
+impl FATVol {
+    /// Sets the OEM name of the volume.
+    ///
+    /// # Parameters
+    /// - `name`: Up to 8 ASCII characters, padded with spaces.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the boot sector and its backup are updated.
+    /// - `Err(FATError::InvalidBpbField)` if the name is too long or not ASCII.
+    pub fn set_oem_name(&mut self, name: &str) -> Result<(), FATError> {
+        if !name.is_ascii() || name.len() > OEM_NAME_LEN {
+            return Err(FATError::InvalidBpbField(format!(
+                "OEM name {name:?} must hold at most {OEM_NAME_LEN} ASCII characters"
+            )));
+        }
+
+        self.write_boot_field(
+            OEM_NAME_OFFSET,
+            &padded(name.as_bytes(), OEM_NAME_LEN, b' '),
+        )
+    }
+
+    /// Sets the volume label, in the boot sector and in the volume label entry of the root
+    /// directory if any.
+    ///
+    /// # Parameters
+    /// - `label`: Up to 11 characters valid in a short name, lowercase excluded. An empty label
+    ///   gives the volume no name.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the label is updated.
+    /// - `Err(FATError::InvalidBpbField)` if the label is too long or holds invalid characters.
+    pub fn set_volume_label(&mut self, label: &str) -> Result<(), FATError> {
+        let valid_char = |c: u8| {
+            (0x20..0x7F).contains(&c)
+                && !c.is_ascii_lowercase()
+                && !DirEntry::INVALID_CHARS.contains(&c)
+        };
+        if label.len() > VOL_LAB_LEN || !label.bytes().all(valid_char) || label.starts_with(' ') {
+            return Err(FATError::InvalidBpbField(format!(
+                "volume label {label:?} must hold at most {VOL_LAB_LEN} uppercase characters valid in a short name"
+            )));
+        }
+
+        let label = match label.is_empty() {
+            true => padded(NO_NAME_LABEL.as_bytes(), VOL_LAB_LEN, b' '),
+            false => padded(label.as_bytes(), VOL_LAB_LEN, b' '),
+        };
+        self.write_boot_field(VOL_LAB_OFFSET, &label)?;
+
+        // The label entry lists the volume as named by the system, it must agree with the BPB
+        if let Some(entry) = self
+            .list_dir(self.root_cluster()?)?
+            .into_iter()
+            .find(|entry| entry.is_volume_id() && !entry.is_deleted())
+        {
+            let mut disk_file = self.image.open_for_write()?;
+            write_at(&mut disk_file, *entry.location(), &label)?;
+            self.invalidate_cache(*entry.location(), label.len() as u64);
+        }
+        Ok(())
+    }
+
+    /// Sets the serial number of the volume.
+    ///
+    /// # Parameters
+    /// - `serial`: The new serial number.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the boot sector and its backup are updated.
+    /// - `Err(FATError)` if writing fails.
+    pub fn set_serial(&mut self, serial: u32) -> Result<(), FATError> {
+        self.write_boot_field(VOL_ID_OFFSET, &serial.to_le_bytes())
+    }
+
+    /// Sets the boot code of the volume.
+    ///
+    /// # Parameters
+    /// - `code`: Up to 420 bytes, padded with zeros.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the boot sector and its backup are updated.
+    /// - `Err(FATError::InvalidBpbField)` if the code is too long.
+    pub fn set_boot_code(&mut self, code: &[u8]) -> Result<(), FATError> {
+        if code.len() > BOOT_CODE_LEN {
+            return Err(FATError::InvalidBpbField(format!(
+                "boot code of {} bytes exceeds {BOOT_CODE_LEN} bytes",
+                code.len()
+            )));
+        }
+
+        self.write_boot_field(BOOT_CODE_OFFSET, &padded(code, BOOT_CODE_LEN, 0))
+    }
+
+    /// Writes a field to the boot sector and its backup, then reloads the BPB.
+    ///
+    /// The boot sector signature is rewritten as well, so the sectors stay valid.
+    ///
+    /// # Parameters
+    /// - `offset`: The offset of the field in the boot sector.
+    /// - `data`: The new value of the field.
+    fn write_boot_field(&mut self, offset: u64, data: &[u8]) -> Result<(), FATError> {
+        let bytes_per_sec = *self.bpb.bytes_per_sec() as u64;
+        let mut sectors = vec![self.start() as u64];
+        if self.bpb.fat_type() == FATType::FAT32
+            && let Some(bk_boot) = self.bpb.backup_boot_sector()
+        {
+            sectors.push(self.start() as u64 + bk_boot as u64);
+        }
+
+        let mut disk_file = self.image.open_for_write()?;
+        for sector in sectors {
+            let base = self.sector_offset(sector);
+            write_at(&mut disk_file, base + offset, data)?;
+            write_at(&mut disk_file, base + SIGNATURE_OFFSET, &BOOT_SIGNATURE)?;
+            self.invalidate_cache(base, bytes_per_sec);
+        }
+
+        self.bpb = Bpb::from(&mut disk_file, self.start(), false, bytes_per_sec as usize)?;
+        Ok(())
+    }
+}
+
+/// Pads a value to the length of its field.
+fn padded(value: &[u8], len: usize, fill: u8) -> Vec<u8> {
+    let mut field = value.to_vec();
+    field.resize(len, fill);
+    field
+}
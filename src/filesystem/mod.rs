@@ -1,5 +1,23 @@
-mod bpb;
-mod dir_entry;
+pub(crate) mod addressing;
+pub(crate) mod bootsec;
+pub(crate) mod bpb;
+pub(crate) mod cluster_iter;
+pub(crate) mod clusters;
+pub(crate) mod datetime;
+pub(crate) mod dir_entry;
+pub(crate) mod exfat;
+pub(crate) mod ext;
 pub(crate) mod fat;
 pub(crate) mod fat_error;
-mod fat_type;
+pub(crate) mod fat_type;
+#[cfg(feature = "lab")]
+pub(crate) mod filler;
+pub(crate) mod fsinfo;
+pub(crate) mod fsstat;
+pub(crate) mod info;
+pub(crate) mod iso9660;
+#[cfg(feature = "lab")]
+pub(crate) mod lab;
+pub(crate) mod ntfs;
+pub(crate) mod recovery;
+pub(crate) mod stat;
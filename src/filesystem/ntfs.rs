@@ -0,0 +1,519 @@
+//! NTFS boot sector and MFT detection.
+//!
+//! NTFS volumes are not analyzed, but recognizing them keeps the partitions of mixed-OS images
+//! from being silently skipped. The boot sector gives the cluster size and the location of the
+//! Master File Table (MFT) and of its mirror; the first record of the MFT, describing the MFT
+//! itself, gives its size and the runs of clusters holding it. A copy of the boot sector is stored
+//! in the last sector of the volume.
+
+use binread::{BinRead, BinReaderExt};
+use getset::Getters;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use super::fat_error::FATError;
+use crate::image::ImageSource;
+use crate::traits::{LayoutDisplay, LayoutOptions};
+use crate::utils;
+
+/// The OEM name of an NTFS boot sector.
+pub const NTFS_NAME: &[u8; 8] = b"NTFS    ";
+
+/// The signature starting the records of the MFT.
+const FILE_SIGNATURE: &[u8; 4] = b"FILE";
+
+/// The type of the $DATA attribute.
+const ATTR_DATA: u32 = 0x80;
+
+/// The type ending the attributes of an MFT record.
+const ATTR_END: u32 = 0xFFFF_FFFF;
+
+/// The number of MFT records copied in the $MFTMirr file.
+const MIRROR_RECORDS: u64 = 4;
+
+/// The largest MFT record read, even when the boot sector isn't validated.
+const MAX_RECORD_SIZE: u64 = 64 * 1024;
+
+/// NTFS boot sector structure.
+#[derive(BinRead, Debug, Getters)]
+#[br(little)]
+pub struct NtfsBoot {
+    /// OEM identifier ("NTFS    "), after the jump instruction
+    #[br(pad_before = 3)]
+    #[get = "pub"]
+    oem_name: [u8; 8],
+    /// Number of bytes per sector
+    #[get = "pub"]
+    bytes_per_sec: u16,
+    /// Number of sectors per cluster, or the negated base-2 logarithm if above 0x80
+    sec_per_clus: u8,
+    /// Media descriptor (0xF8 for fixed disk), after the zeroed BPB fields of FAT volumes
+    #[br(pad_before = 7)]
+    #[get = "pub"]
+    media: u8,
+    /// Sectors per track
+    #[br(pad_before = 2)]
+    #[get = "pub"]
+    sec_per_trk: u16,
+    /// Number of heads
+    #[get = "pub"]
+    num_heads: u16,
+    /// Number of hidden sectors preceding the partition
+    #[get = "pub"]
+    hidd_sec: u32,
+    /// Number of sectors of the volume, the backup boot sector excluded
+    #[br(pad_before = 8)]
+    #[get = "pub"]
+    tot_sec: u64,
+    /// First cluster of the MFT
+    #[get = "pub"]
+    mft_clus: u64,
+    /// First cluster of the MFT mirror
+    #[get = "pub"]
+    mft_mirr_clus: u64,
+    /// Size of an MFT record, in clusters, or the negated base-2 logarithm of its size in bytes
+    clus_per_mft_record: i8,
+    /// Size of an index record, in clusters, or the negated base-2 logarithm of its size in bytes
+    #[br(pad_before = 3)]
+    clus_per_index_record: i8,
+    /// Volume serial number
+    #[br(pad_before = 3)]
+    #[get = "pub"]
+    serial: u64,
+    /// Boot code, after the unused checksum
+    #[br(pad_before = 4, count = 426)]
+    #[get = "pub"]
+    boot_code: Vec<u8>,
+    /// Boot sector signature (0x55 0xAA)
+    sig: [u8; 2],
+}
+
+impl NtfsBoot {
+    /// Checks whether a sector holds the OEM name of an NTFS boot sector.
+    ///
+    /// # Parameters
+    /// - `sector`: The content of the sector.
+    pub fn is_ntfs(sector: &[u8]) -> bool {
+        sector.get(3..11) == Some(NTFS_NAME.as_slice())
+    }
+
+    /// Returns the number of sectors per cluster.
+    pub fn sectors_per_cluster(&self) -> u64 {
+        match self.sec_per_clus {
+            0..=0x80 => self.sec_per_clus as u64,
+            shift => 1u64.checked_shl(256 - shift as u32).unwrap_or(0),
+        }
+    }
+
+    /// Returns the size in bytes of a cluster.
+    pub fn cluster_size(&self) -> u64 {
+        (self.bytes_per_sec as u64).saturating_mul(self.sectors_per_cluster())
+    }
+
+    /// Returns the size in bytes of an MFT record, usually 1024.
+    pub fn mft_record_size(&self) -> u64 {
+        record_size(self.clus_per_mft_record, self.cluster_size())
+    }
+
+    /// Returns the size in bytes of an index record, usually 4096.
+    pub fn index_record_size(&self) -> u64 {
+        record_size(self.clus_per_index_record, self.cluster_size())
+    }
+
+    /// Validates the fields of the boot sector.
+    ///
+    /// # Parameters
+    /// - `sector_cnt`: The number of sectors of the partition holding the volume.
+    fn validate(&self, sector_cnt: u64) -> Result<(), FATError> {
+        if !self.bytes_per_sec.is_power_of_two() || !(256..=4096).contains(&self.bytes_per_sec) {
+            return Err(FATError::InvalidBytesPerSec(self.bytes_per_sec));
+        }
+        let cluster_size = self.cluster_size();
+        if cluster_size == 0 || !cluster_size.is_power_of_two() || cluster_size > 2 << 20 {
+            return Err(FATError::InvalidSecPerClus(self.sec_per_clus));
+        }
+        if self.tot_sec == 0 || self.tot_sec > sector_cnt {
+            return Err(FATError::InvalidTotSec(format!(
+                "{} sectors in a partition of {sector_cnt} sectors",
+                self.tot_sec
+            )));
+        }
+        let cluster_cnt = self.tot_sec / self.sectors_per_cluster();
+        if self.mft_clus >= cluster_cnt || self.mft_mirr_clus >= cluster_cnt {
+            return Err(FATError::InvalidBootSector(format!(
+                "the MFT (cluster {}) or its mirror (cluster {}) is past the {cluster_cnt} clusters of the volume",
+                self.mft_clus, self.mft_mirr_clus
+            )));
+        }
+        if !(512..=4096).contains(&self.mft_record_size()) {
+            return Err(FATError::InvalidBootSector(format!(
+                "invalid MFT record size: {} bytes",
+                self.mft_record_size()
+            )));
+        }
+        if self.sig != [0x55, 0xAA] {
+            return Err(FATError::InvalidSignature(format!("{:02X?}", self.sig)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes the size of a record: a number of clusters if positive, the base-2 logarithm of the
+/// size in bytes, negated, otherwise.
+fn record_size(value: i8, cluster_size: u64) -> u64 {
+    match value {
+        0.. => (value as u64).saturating_mul(cluster_size),
+        _ => 1u64.checked_shl(-(value as i32) as u32).unwrap_or(0),
+    }
+}
+
+/// Represents an NTFS volume within a disk image, recognized but not analyzed.
+#[derive(Debug, Getters)]
+pub struct NtfsVol {
+    /// The boot sector of the volume.
+    #[get = "pub"]
+    boot: NtfsBoot,
+    /// The absolute byte offset of the volume in the image.
+    #[get = "pub"]
+    offset: u64,
+    /// The number of sectors of the partition holding the volume, in sectors of the volume.
+    #[get = "pub"]
+    partition_sectors: u64,
+    /// The size in bytes of the MFT, from its first record, `None` if the record is unreadable.
+    #[get = "pub"]
+    mft_size: Option<u64>,
+    /// The first cluster and the number of clusters of each run of the MFT, in order.
+    #[get = "pub"]
+    mft_runs: Vec<(u64, u64)>,
+    /// The source of the bytes of the disk image.
+    image: Arc<ImageSource>,
+}
+
+impl NtfsVol {
+    /// Reads the boot sector and the first MFT record of an NTFS volume.
+    ///
+    /// # Parameters
+    /// - `image`: The disk image containing the filesystem
+    /// - `start`: The sector number where the boot sector is located
+    /// - `sector_cnt`: The number of sectors of the partition
+    /// - `validate`: Whether to validate the boot sector
+    /// - `sector_size`: The size of each sector of the disk in bytes
+    ///
+    /// # Returns
+    /// - `Ok(NtfsVol)`: The NTFS volume
+    /// - `Err(FATError)`: If reading fails, the sector isn't an NTFS boot sector or validation
+    ///   fails
+    pub fn from_backend(
+        image: Arc<ImageSource>,
+        start: u32,
+        sector_cnt: u32,
+        validate: bool,
+        sector_size: usize,
+    ) -> Result<NtfsVol, FATError> {
+        let offset = start as u64 * sector_size as u64;
+        let sector = image.read_at(offset, 512)?;
+        if !NtfsBoot::is_ntfs(&sector) {
+            return Err(FATError::InvalidBootSector(String::from(
+                "the OEM name isn't NTFS",
+            )));
+        }
+        let boot: NtfsBoot = std::io::Cursor::new(&sector).read_be()?;
+        let bytes_per_sec = (boot.bytes_per_sec as u64).max(1);
+        let partition_sectors = sector_cnt as u64 * sector_size as u64 / bytes_per_sec;
+        if validate {
+            boot.validate(partition_sectors)?;
+        }
+
+        let mut vol = NtfsVol {
+            boot,
+            offset,
+            partition_sectors,
+            mft_size: None,
+            mft_runs: vec![],
+            image,
+        };
+        // A damaged MFT doesn't prevent recognizing the volume
+        if let Ok((size, runs)) = vol.read_mft_data() {
+            vol.mft_size = Some(size);
+            vol.mft_runs = runs;
+        }
+
+        Ok(vol)
+    }
+
+    /// Checks whether the sector at the given position holds an NTFS boot sector.
+    ///
+    /// # Parameters
+    /// - `image`: The disk image
+    /// - `start`: The sector number of the candidate boot sector
+    /// - `sector_size`: The size of each sector of the disk in bytes
+    pub fn is_ntfs(image: &ImageSource, start: u32, sector_size: usize) -> bool {
+        image
+            .read_at(start as u64 * sector_size as u64, 512)
+            .is_ok_and(|sector| NtfsBoot::is_ntfs(&sector))
+    }
+
+    /// Returns the absolute byte offset of a cluster of the volume.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The offset of the first byte of the cluster.
+    /// - `Err(FATError::InvalidBootSector)`: If the offset doesn't fit in 64 bits, the cluster or
+    ///   the cluster size of a crafted boot sector being too large.
+    pub fn cluster_offset(&self, cluster: u64) -> Result<u64, FATError> {
+        cluster
+            .checked_mul(self.boot.cluster_size())
+            .and_then(|offset| offset.checked_add(self.offset))
+            .ok_or_else(|| {
+                FATError::InvalidBootSector(format!(
+                    "cluster {cluster} is past the addressable space"
+                ))
+            })
+    }
+
+    /// Reads a record of the MFT, lying in the first run of the MFT, and undoes its fixups.
+    ///
+    /// # Parameters
+    /// - `number`: The number of the record, 0 for the record of the MFT itself.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The content of the record.
+    /// - `Err(FATError::InvalidBootSector)`: If the record size or offset is invalid, the record
+    ///   doesn't start with the `FILE` signature, or its update sequence doesn't match.
+    pub fn mft_record(&self, number: u64) -> Result<Vec<u8>, FATError> {
+        let record_size = self.boot.mft_record_size();
+        if record_size == 0 || record_size > MAX_RECORD_SIZE {
+            return Err(FATError::InvalidBootSector(format!(
+                "invalid MFT record size: {record_size} bytes"
+            )));
+        }
+        let (first, _) = self
+            .mft_runs
+            .first()
+            .copied()
+            .unwrap_or((*self.boot.mft_clus(), 0));
+        let offset = number
+            .checked_mul(record_size)
+            .and_then(|offset| offset.checked_add(self.cluster_offset(first).ok()?))
+            .ok_or_else(|| {
+                FATError::InvalidBootSector(format!(
+                    "MFT record {number} is past the addressable space"
+                ))
+            })?;
+        let mut record = self.image.read_at(offset, record_size as usize)?;
+        if !record.starts_with(FILE_SIGNATURE) {
+            return Err(FATError::InvalidBootSector(format!(
+                "MFT record {number} at 0x{offset:X} has no FILE signature"
+            )));
+        }
+
+        // The last two bytes of each sector were replaced by the update sequence number
+        let usa_offset = utils::u16_at(&record, 4) as usize;
+        let usa_count = utils::u16_at(&record, 6) as usize;
+        let sector_len = *self.boot.bytes_per_sec() as usize;
+        if usa_count == 0 || usa_offset + 2 * usa_count > record.len() {
+            return Err(FATError::InvalidBootSector(format!(
+                "MFT record {number} has an invalid update sequence"
+            )));
+        }
+        let usn = [record[usa_offset], record[usa_offset + 1]];
+        for idx in 1..usa_count {
+            let end = idx * sector_len;
+            if end > record.len() || record[end - 2..end] != usn {
+                return Err(FATError::InvalidBootSector(format!(
+                    "MFT record {number} is torn at sector {}",
+                    idx - 1
+                )));
+            }
+            let fixup = usa_offset + 2 * idx;
+            let value = [record[fixup], record[fixup + 1]];
+            record[end - 2..end].copy_from_slice(&value);
+        }
+
+        Ok(record)
+    }
+
+    /// Reads the size and the runs of the MFT from the $DATA attribute of its first record.
+    fn read_mft_data(&self) -> Result<(u64, Vec<(u64, u64)>), FATError> {
+        let record = self.mft_record(0)?;
+        let invalid =
+            || FATError::InvalidBootSector(String::from("the MFT record 0 has no $DATA runs"));
+
+        let mut pos = utils::u16_at(&record, 20) as usize;
+        while pos + 16 <= record.len() {
+            let kind = utils::u32_at(&record, pos);
+            let len = utils::u32_at(&record, pos + 4) as usize;
+            if kind == ATTR_END || len == 0 || pos + len > record.len() {
+                break;
+            }
+            // The $DATA attribute of the MFT is always non-resident
+            if kind == ATTR_DATA && record[pos + 8] == 1 && len >= 64 {
+                let attr = &record[pos..pos + len];
+                let runs_offset = utils::u16_at(attr, 32) as usize;
+                let size = u64::from_le_bytes(attr[48..56].try_into().expect("invalid slice"));
+                let runs = data_runs(attr.get(runs_offset..).ok_or_else(invalid)?);
+                return match runs.is_empty() {
+                    true => Err(invalid()),
+                    false => Ok((size, runs)),
+                };
+            }
+            pos += len;
+        }
+
+        Err(invalid())
+    }
+}
+
+/// Decodes the runs of a non-resident attribute.
+///
+/// Each run starts with a header byte holding the size of its length field in the low nibble and
+/// the size of its offset field, relative to the previous run, in the high nibble. Sparse runs,
+/// without offset, are skipped.
+///
+/// # Returns
+/// - The first cluster and the number of clusters of each run, in order.
+fn data_runs(mut data: &[u8]) -> Vec<(u64, u64)> {
+    let mut runs = vec![];
+    let mut cluster: i64 = 0;
+    while let Some(&header) = data.first() {
+        let (len_size, offset_size) = ((header & 0x0F) as usize, (header >> 4) as usize);
+        if header == 0 || len_size > 8 || offset_size > 8 || data.len() < 1 + len_size + offset_size
+        {
+            break;
+        }
+        let field = |bytes: &[u8], signed: bool| {
+            let mut buf = match signed && bytes.last().is_some_and(|b| b & 0x80 != 0) {
+                true => [0xFF; 8],
+                false => [0; 8],
+            };
+            buf[..bytes.len()].copy_from_slice(bytes);
+            i64::from_le_bytes(buf)
+        };
+        let len = field(&data[1..1 + len_size], false) as u64;
+        if offset_size > 0 {
+            cluster += field(&data[1 + len_size..1 + len_size + offset_size], true);
+            if cluster < 0 {
+                break;
+            }
+            runs.push((cluster as u64, len));
+        }
+        data = &data[1 + len_size + offset_size..];
+    }
+    runs
+}
+
+impl LayoutDisplay for NtfsVol {
+    fn display_layout(
+        &self,
+        indent: u8,
+        options: &LayoutOptions,
+    ) -> Result<String, std::fmt::Error> {
+        let mut out = String::from("");
+        let indent = " ".repeat(indent.into());
+        let sector_size = (*self.boot.bytes_per_sec() as u64).max(1);
+        let sectors_per_cluster = self.boot.sectors_per_cluster();
+
+        writeln!(out, "{}┌{:─^68}┐", indent, " NTFS Partition Layout ")?;
+        writeln!(
+            out,
+            "{}├{:<56}{:>12}┤",
+            indent,
+            "Cluster Size",
+            self.boot.cluster_size()
+        )?;
+        writeln!(
+            out,
+            "{}├{:<56}{:>12}┤",
+            indent,
+            "MFT Record Size",
+            self.boot.mft_record_size()
+        )?;
+        let records = match self.mft_size {
+            Some(size) => (size / self.boot.mft_record_size().max(1)).to_string(),
+            None => String::from("unreadable"),
+        };
+        writeln!(out, "{}├{:<56}{:>12}┤", indent, "MFT Records", records)?;
+        writeln!(out, "{}├{:─^68}┤", indent, "")?;
+        writeln!(
+            out,
+            "{}├{:^12}┬{:^12}┬{:^12}┬{:^12}┬{:^16}┤",
+            indent, "Region", "Start", "End", "Length", "Description"
+        )?;
+        writeln!(
+            out,
+            "{}├{:─<12}┼{:─<12}┼{:─<12}┼{:─<12}┼{:─<16}┤",
+            indent, "", "", "", "", ""
+        )?;
+
+        let row = |out: &mut String, region: &str, start: u64, end: u64, desc: &str| {
+            let [start, end, len] = options.fmt_range(start, end, sector_size);
+            writeln!(
+                out,
+                "{}│{:<12}│{:>12}│{:>12}│{:>12}│{:<16}│",
+                indent, region, start, end, len, desc
+            )
+        };
+
+        // Positions are absolute sectors, in sectors of the volume. The fields of a crafted boot
+        // sector can overflow them, the rows are then clamped rather than wrapped.
+        let start = self.offset / sector_size;
+        let cluster_sector =
+            |cluster: u64| start.saturating_add(cluster.saturating_mul(sectors_per_cluster));
+        let volume_end = start.saturating_add(*self.boot.tot_sec());
+        row(&mut out, "Boot", start, start + 1, "Boot Sector")?;
+        match self.mft_runs.is_empty() {
+            true => {
+                let mft_start = cluster_sector(*self.boot.mft_clus());
+                row(
+                    &mut out,
+                    "$MFT",
+                    mft_start,
+                    mft_start.saturating_add(1),
+                    "MFT (first)",
+                )?;
+            }
+            false => {
+                for (idx, (cluster, len)) in self.mft_runs.iter().enumerate() {
+                    let run_start = cluster_sector(*cluster);
+                    let desc = match idx {
+                        0 => String::from("MFT"),
+                        _ => format!("MFT (run {})", idx + 1),
+                    };
+                    row(
+                        &mut out,
+                        "$MFT",
+                        run_start,
+                        run_start.saturating_add(len.saturating_mul(sectors_per_cluster)),
+                        &desc,
+                    )?;
+                }
+            }
+        }
+        let mirror_start = cluster_sector(*self.boot.mft_mirr_clus());
+        let mirror_len = MIRROR_RECORDS
+            .saturating_mul(self.boot.mft_record_size())
+            .div_ceil(self.boot.cluster_size().max(1))
+            .saturating_mul(sectors_per_cluster);
+        row(
+            &mut out,
+            "$MFTMirr",
+            mirror_start,
+            mirror_start.saturating_add(mirror_len),
+            "MFT Mirror",
+        )?;
+        let backup_end = volume_end.saturating_add(1);
+        row(&mut out, "", volume_end, backup_end, "Backup Boot")?;
+        let end = start + self.partition_sectors;
+        if backup_end < end {
+            row(&mut out, "", backup_end, end, "Volume Slack")?;
+        }
+
+        writeln!(
+            out,
+            "{}└{:─<12}┴{:─<12}┴{:─<12}┴{:─<12}┴{:─<16}┘",
+            indent, "", "", "", "", ""
+        )?;
+
+        Ok(out)
+    }
+}
@@ -0,0 +1,312 @@
+//! Recovery of the directory tree when the BPB or the FAT is damaged.
+//!
+//! The root directory of a FAT32 volume is an ordinary cluster chain whose first cluster is only
+//! recorded in the `root_clus` field of the BPB. If that field is damaged, the whole tree is lost
+//! although the directory itself is intact. Its first cluster can be told from other directories:
+//! it usually holds the volume label entry and never starts with "." and ".." entries.
+//!
+//! The other directories start with "." and ".." entries pointing to themselves and to their
+//! parent, so the tree can be rebuilt even without the FAT.
+
+use getset::Getters;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::dir_entry::DirEntry;
+use super::fat::{FATVol, lock};
+use super::fat_error::FATError;
+use super::fat_type::FATType;
+use crate::events::Event;
+use crate::utils::escape_name;
+
+/// Maximum number of clusters read at once while scanning the data region.
+const SCAN_RUN_LEN: u32 = 2048;
+
+/// A cluster that may be the first cluster of the root directory.
+#[derive(Debug, Getters)]
+pub struct RootCandidate {
+    /// The cluster number.
+    #[get = "pub"]
+    cluster: u32,
+    /// The volume label found in the cluster.
+    #[get = "pub"]
+    label: String,
+    /// Whether the label matches the one of the boot sector.
+    #[get = "pub"]
+    label_matches: bool,
+    /// The number of files and directories listed in the cluster, deleted ones included.
+    #[get = "pub"]
+    entry_count: usize,
+    /// Whether the cluster is the root directory currently in use.
+    #[get = "pub"]
+    is_current: bool,
+}
+
+impl fmt::Display for RootCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Cluster {}: label '{}'{}, {} entries",
+            self.cluster,
+            escape_name(&self.label),
+            match self.label_matches {
+                true => " (matches the boot sector)",
+                false => "",
+            },
+            self.entry_count
+        )?;
+        if self.is_current {
+            write!(f, " [current root]")?;
+        }
+        Ok(())
+    }
+}
+
+impl FATVol {
+    /// Scans the data region for clusters that look like the first cluster of the root directory.
+    ///
+    /// A candidate holds a volume label entry, no "." or ".." entry, and only plausible entries up
+    /// to the end of the directory.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<RootCandidate>)`: The candidates, the most likely first: those whose label
+    ///   matches the boot sector, then those with the most entries.
+    /// - `Err(FATError)`: If the data region cannot be read.
+    pub fn find_root_candidates(&self) -> Result<Vec<RootCandidate>, FATError> {
+        let boot_label = self.bpb().label();
+        let current_root = self.root_cluster().ok();
+
+        let mut candidates = vec![];
+        self.scan_dir_clusters(|cluster, entries| {
+            if entries.iter().any(DirEntry::is_dot) {
+                return;
+            }
+            let Some(label) = entries
+                .iter()
+                .find(|entry| entry.is_volume_id() && !entry.is_deleted())
+                .map(DirEntry::raw_name)
+            else {
+                return;
+            };
+
+            candidates.push(RootCandidate {
+                cluster,
+                label_matches: label == boot_label,
+                label,
+                entry_count: entries
+                    .iter()
+                    .filter(|entry| !entry.is_long_name() && !entry.is_volume_id())
+                    .count(),
+                is_current: current_root == Some(cluster),
+            });
+        })?;
+
+        candidates
+            .sort_by_key(|candidate| (!candidate.label_matches, Reverse(candidate.entry_count)));
+        Ok(candidates)
+    }
+
+    /// Sets the cluster used as the root directory instead of the one of the BPB.
+    ///
+    /// # Parameters
+    /// - `cluster`: The first cluster of the root directory.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the root directory is changed.
+    /// - `Err(FATError::ClusterOutOfRange)` if the cluster isn't part of the data region.
+    pub fn set_root_cluster(&self, cluster: u32) -> Result<(), FATError> {
+        if !self.is_valid_cluster(cluster) {
+            return Err(FATError::ClusterOutOfRange(cluster));
+        }
+
+        *lock(&self.root_override) = Some(cluster);
+        Ok(())
+    }
+
+    /// Uses the root directory of the BPB again.
+    pub fn reset_root_cluster(&self) {
+        *lock(&self.root_override) = None;
+    }
+
+    /// Compares the boot sector with its backup.
+    ///
+    /// # Returns
+    /// - `Ok(Some((sector, matches)))`: The absolute sector of the backup and whether it is
+    ///   identical to the boot sector.
+    /// - `Ok(None)`: If the volume has no backup boot sector, e.g. it isn't a FAT32 volume.
+    /// - `Err(FATError)`: If a sector cannot be read.
+    pub fn check_backup_boot(&self) -> Result<Option<(u64, bool)>, FATError> {
+        let backup = match self.bpb().backup_boot_sector() {
+            _ if self.bpb().fat_type() != FATType::FAT32 => return Ok(None),
+            None => return Ok(None),
+            Some(sector) => self.start() as u64 + sector as u64,
+        };
+
+        let matches = self.read_sectors(self.start() as u64, 1)? == self.read_sectors(backup, 1)?;
+        Ok(Some((backup, matches)))
+    }
+
+    /// Finds the directories of the volume from their "." and ".." entries, without the FAT.
+    ///
+    /// The first cluster of every directory but the root starts with a "." entry pointing to
+    /// itself and a ".." entry pointing to its parent, which rebuilds the tree even if the FAT is
+    /// destroyed. Directories are named after the entries pointing to them, wherever found.
+    ///
+    /// # Returns
+    /// - `Ok(DiscoveredTree)`: The directories found.
+    /// - `Err(FATError)`: If the data region cannot be read.
+    pub fn discover_dirs(&self) -> Result<DiscoveredTree, FATError> {
+        let mut dirs = vec![];
+        let mut names: HashMap<u32, String> = HashMap::new();
+        self.scan_dir_clusters(|cluster, entries| {
+            for entry in entries.iter().filter(|entry| entry.is_regular_dir()) {
+                let name = entry.short_name();
+                names.entry(entry.cluster_number()).or_insert(name);
+            }
+
+            if let [dot, dot_dot, ..] = entries.as_slice()
+                && dot.is_dot()
+                && dot.cluster_number() == cluster
+                && dot_dot.is_dot()
+            {
+                dirs.push(DiscoveredDir {
+                    cluster,
+                    parent: dot_dot.cluster_number(),
+                    name: None,
+                    entry_count: entries.len() - 2,
+                });
+            }
+        })?;
+
+        for dir in dirs.iter_mut() {
+            dir.name = names.remove(&dir.cluster);
+        }
+        Ok(DiscoveredTree { dirs })
+    }
+
+    /// Parses every cluster of the data region that looks like a directory cluster.
+    ///
+    /// # Parameters
+    /// - `f`: Called with the cluster number and the entries of each directory cluster.
+    fn scan_dir_clusters<F: FnMut(u32, Vec<DirEntry>)>(&self, mut f: F) -> Result<(), FATError> {
+        let last_cluster = self.cluster_count() + 1;
+        for first in (2..=last_cluster).step_by(SCAN_RUN_LEN as usize) {
+            let clusters: Vec<u32> =
+                (first..(first + SCAN_RUN_LEN).min(last_cluster + 1)).collect();
+            let data = self.read_chain(&clusters)?;
+
+            for (cluster, buf) in clusters
+                .iter()
+                .zip(data.chunks(self.cluster_size() as usize))
+            {
+                if let Some(entries) = parse_dir_cluster(buf) {
+                    f(*cluster, entries);
+                }
+            }
+
+            self.emit(|| Event::Progress {
+                task: "directory scan",
+                done: (clusters.last().unwrap_or(&1) - 1) as u64,
+                total: self.cluster_count() as u64,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// A directory found from its "." and ".." entries.
+#[derive(Debug, Getters)]
+pub struct DiscoveredDir {
+    /// The first cluster of the directory.
+    #[get = "pub"]
+    cluster: u32,
+    /// The first cluster of the parent directory, 0 for the root directory.
+    #[get = "pub"]
+    parent: u32,
+    /// The short name of the directory, if an entry pointing to it was found.
+    #[get = "pub"]
+    name: Option<String>,
+    /// The number of entries in the first cluster, "." and ".." excluded.
+    #[get = "pub"]
+    entry_count: usize,
+}
+
+/// The directories found by [`FATVol::discover_dirs`], displayed as a tree.
+#[derive(Debug, Getters)]
+pub struct DiscoveredTree {
+    /// The directories, in cluster order.
+    #[get = "pub"]
+    dirs: Vec<DiscoveredDir>,
+}
+
+impl DiscoveredTree {
+    fn fmt_children(&self, f: &mut fmt::Formatter<'_>, parent: u32, depth: usize) -> fmt::Result {
+        for dir in self.dirs.iter().filter(|dir| dir.parent == parent) {
+            // A directory that is its own parent would recurse forever
+            if dir.cluster == parent {
+                continue;
+            }
+            writeln!(
+                f,
+                "{}{} (cluster {}, {} entries)",
+                "   ".repeat(depth),
+                dir.name.as_deref().map_or(String::from("?"), escape_name),
+                dir.cluster,
+                dir.entry_count
+            )?;
+            self.fmt_children(f, dir.cluster, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DiscoveredTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "/")?;
+        self.fmt_children(f, 0, 1)?;
+
+        // Directories whose parent wasn't found are shown under their missing parent
+        let clusters: HashSet<u32> = self.dirs.iter().map(|dir| dir.cluster).collect();
+        let mut orphan_parents: Vec<u32> = self
+            .dirs
+            .iter()
+            .map(|dir| dir.parent)
+            .filter(|parent| *parent != 0 && !clusters.contains(parent))
+            .collect();
+        orphan_parents.sort_unstable();
+        orphan_parents.dedup();
+        for parent in orphan_parents {
+            writeln!(f, "? (cluster {parent}, not found)")?;
+            self.fmt_children(f, parent, 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the entries of a cluster that may belong to a directory.
+///
+/// # Returns
+/// - `Some(Vec<DirEntry>)`: The entries up to the end of the directory, if they are all plausible
+///   and at least one is in use.
+/// - `None`: If the cluster doesn't look like a directory cluster.
+pub(super) fn parse_dir_cluster(buf: &[u8]) -> Option<Vec<DirEntry>> {
+    let mut entries = vec![];
+    for off in (0..buf.len()).step_by(DirEntry::SIZE as usize) {
+        // A free entry starting with 0 ends the directory
+        if buf[off] == 0 {
+            break;
+        }
+        let entry = DirEntry::from_slice(&buf[off..]).ok()?;
+        if !entry.is_plausible() {
+            return None;
+        }
+        entries.push(entry);
+    }
+
+    match entries.is_empty() {
+        true => None,
+        false => Some(entries),
+    }
+}
@@ -0,0 +1,261 @@
+//! Full metadata of a file or directory, as shown by the `stat` command, and of a raw directory
+//! entry record, as shown by the `dirent` command.
+
+use getset::Getters;
+use std::fmt;
+use std::path::Path;
+
+use super::addressing::DirEntryAddress;
+use super::dir_entry::DirEntry;
+use super::fat::{FATVol, contiguous_runs};
+use super::fat_error::FATError;
+use crate::utils::escape_name;
+
+/// Metadata of a file or directory, gathered from its directory entry and cluster chain.
+#[derive(Debug, Getters)]
+pub struct FileStat {
+    /// The directory entry of the file.
+    #[get = "pub"]
+    entry: DirEntry,
+    /// The address of the directory entry.
+    #[get = "pub"]
+    address: Option<DirEntryAddress>,
+    /// The runs of consecutive clusters of the chain: first cluster and length.
+    #[get = "pub"]
+    runs: Vec<(u32, u32)>,
+    /// The number of bytes allocated to the file (whole clusters).
+    #[get = "pub"]
+    allocated_size: u64,
+    /// The number of allocated bytes past the end of the file.
+    #[get = "pub"]
+    slack_size: u64,
+}
+
+/// A directory entry read at an arbitrary address, whether or not it is reachable from the tree.
+#[derive(Debug, Getters)]
+pub struct DirEntryRecord {
+    /// The address of the record.
+    #[get = "pub"]
+    address: DirEntryAddress,
+    /// The 32 bytes of the record, as stored on disk.
+    #[get = "pub"]
+    raw: Vec<u8>,
+    /// The decoded record. Its long name is assembled from the long name entries preceding it in
+    /// the same cluster.
+    #[get = "pub"]
+    entry: DirEntry,
+}
+
+impl FATVol {
+    /// Reads and decodes the directory entry record at an arbitrary address.
+    ///
+    /// The record doesn't need to be reachable from the directory tree, so remnants of deleted or
+    /// orphaned directories can be inspected.
+    ///
+    /// # Parameters
+    /// - `address`: The cluster of the record and its index in the cluster, cluster 0 addressing
+    ///   the root directory region of a FAT12/16 volume.
+    ///
+    /// # Returns
+    /// - `Ok(DirEntryRecord)`: The raw and decoded record.
+    /// - `Err(FATError::ClusterOutOfRange)`: If the cluster isn't part of the data region.
+    /// - `Err(FATError::DirEntryOutOfRange)`: If the index exceeds the entries of a cluster.
+    /// - `Err(FATError)`: If the cluster cannot be read.
+    pub fn dir_entry_at(&self, address: DirEntryAddress) -> Result<DirEntryRecord, FATError> {
+        let data = match (*address.cluster(), self.root_dir_extent()) {
+            (0, Some((offset, len))) => self.read_bytes(offset, len as usize)?,
+            (cluster, _) => self.read_cluster(cluster)?,
+        };
+        let entry_size = DirEntry::SIZE as usize;
+        let index = *address.index() as usize;
+        let Some(raw) = data.get(index * entry_size..(index + 1) * entry_size) else {
+            return Err(FATError::DirEntryOutOfRange {
+                cluster: *address.cluster(),
+                index: *address.index(),
+            });
+        };
+
+        let mut entry = DirEntry::from_slice(raw)?;
+        if let Some(offset) = self.dir_entry_offset(address) {
+            entry.set_location(offset);
+        }
+        if !entry.is_long_name() {
+            // The long name entries preceding the record, in on-disk order
+            let parts = data[..index * entry_size]
+                .chunks(entry_size)
+                .rev()
+                .map_while(|raw| {
+                    DirEntry::from_slice(raw)
+                        .ok()
+                        .filter(DirEntry::is_long_name)
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .collect::<Vec<_>>();
+            entry.set_long_name(DirEntry::long_name_from(&parts, &entry));
+        }
+
+        Ok(DirEntryRecord {
+            address,
+            raw: raw.to_vec(),
+            entry,
+        })
+    }
+
+    /// Gathers the metadata of a file or directory.
+    ///
+    /// # Parameters
+    /// - `path`: The path of the file or directory. Each component can be a short or a long name.
+    ///
+    /// # Returns
+    /// - `Ok(FileStat)`: The metadata of the entry.
+    /// - `Err(FATError)`: If the entry cannot be found or its cluster chain cannot be read.
+    pub fn stat(&self, path: &Path) -> Result<FileStat, FATError> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(FATError::FileNotFound)?;
+        let parent_cluster = match path.parent() {
+            Some(parent) if parent.components().count() > 0 => self.find_dir(parent)?,
+            _ => self.root_cluster()?,
+        };
+
+        let entry = self
+            .list_dir(parent_cluster)?
+            .into_iter()
+            .find(|entry| !entry.is_long_name() && !entry.is_deleted() && entry.same_name(name))
+            .ok_or(FATError::FileNotFound)?;
+
+        let clusters = match entry.cluster_number() {
+            0 => vec![],
+            cluster => self.cluster_chain(cluster).collect::<Result<Vec<_>, _>>()?,
+        };
+        let allocated_size = clusters.len() as u64 * self.cluster_size() as u64;
+        let slack_size = match entry.is_dir() {
+            true => 0,
+            false => allocated_size.saturating_sub(*entry.file_size() as u64),
+        };
+
+        Ok(FileStat {
+            address: self.offset_to_dir_entry(*entry.location()),
+            runs: contiguous_runs(&clusters, u32::MAX),
+            allocated_size,
+            slack_size,
+            entry,
+        })
+    }
+}
+
+impl fmt::Display for FileStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let timestamp = |ts: Option<_>| ts.map_or("-".to_string(), |ts| format!("{ts}"));
+        let cluster_cnt: u32 = self.runs.iter().map(|(_, len)| len).sum();
+        let runs: Vec<String> = self
+            .runs
+            .iter()
+            .map(|(first, len)| match len {
+                1 => format!("{first}"),
+                _ => format!("{first}-{}", first + len - 1),
+            })
+            .collect();
+
+        writeln!(
+            f,
+            "Short name:     {}",
+            escape_name(&self.entry.short_name())
+        )?;
+        writeln!(
+            f,
+            "Long name:      {}",
+            escape_name(self.entry.long_name().as_deref().unwrap_or("-"))
+        )?;
+        writeln!(f, "Attributes:     {}", self.entry.attributes())?;
+        writeln!(f, "Created:        {}", timestamp(self.entry.created()))?;
+        writeln!(f, "Modified:       {}", timestamp(self.entry.modified()))?;
+        writeln!(f, "Accessed:       {}", timestamp(self.entry.accessed()))?;
+        writeln!(f, "First cluster:  {}", self.entry.cluster_number())?;
+        writeln!(
+            f,
+            "Chain:          {} cluster(s) in {} fragment(s): {}",
+            cluster_cnt,
+            self.runs.len(),
+            runs.join(", ")
+        )?;
+        writeln!(f, "Logical size:   {} bytes", self.entry.file_size())?;
+        writeln!(f, "Allocated size: {} bytes", self.allocated_size)?;
+        writeln!(f, "Slack:          {} bytes", self.slack_size)?;
+        match self.address {
+            Some(address) => write!(
+                f,
+                "Entry location: {:#x} (cluster {}, entry {})",
+                self.entry.location(),
+                address.cluster(),
+                address.index()
+            ),
+            None => write!(f, "Entry location: {:#x}", self.entry.location()),
+        }
+    }
+}
+
+impl fmt::Display for DirEntryRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let timestamp = |ts: Option<_>| ts.map_or("-".to_string(), |ts| format!("{ts}"));
+
+        writeln!(
+            f,
+            "Entry location: {:#x} (cluster {}, entry {})",
+            self.entry.location(),
+            self.address.cluster(),
+            self.address.index()
+        )?;
+        for (i, line) in self.raw.chunks(16).enumerate() {
+            let hex: Vec<String> = line.iter().map(|b| format!("{b:02X}")).collect();
+            let ascii: String = line
+                .iter()
+                .map(|b| match b.is_ascii_graphic() || *b == b' ' {
+                    true => *b as char,
+                    false => '.',
+                })
+                .collect();
+            writeln!(f, "  {:02X}: {}  {ascii}", i * 16, hex.join(" "))?;
+        }
+
+        if self.raw.first() == Some(&0x00) {
+            return write!(f, "Status:         free (end of directory)");
+        }
+        let status = match self.entry.is_deleted() {
+            true => "deleted",
+            false => "in use",
+        };
+        writeln!(f, "Status:         {status}")?;
+
+        if let Some((sequence, checksum, text)) = self.entry.long_name_part() {
+            // Deleting a long name entry overwrites its sequence number
+            let sequence = match (self.entry.is_deleted(), sequence & 0x40) {
+                (true, _) => "?".to_string(),
+                (false, 0) => format!("{}", sequence & 0x1F),
+                (false, _) => format!("{} (last)", sequence & 0x1F),
+            };
+            writeln!(f, "Long name part: #{sequence}, checksum {checksum:#04x}")?;
+            return write!(f, "Characters:     {text:?}");
+        }
+
+        writeln!(
+            f,
+            "Short name:     {}",
+            escape_name(&self.entry.short_name())
+        )?;
+        writeln!(
+            f,
+            "Long name:      {}",
+            escape_name(self.entry.long_name().as_deref().unwrap_or("-"))
+        )?;
+        writeln!(f, "Attributes:     {}", self.entry.attributes())?;
+        writeln!(f, "Created:        {}", timestamp(self.entry.created()))?;
+        writeln!(f, "Modified:       {}", timestamp(self.entry.modified()))?;
+        writeln!(f, "Accessed:       {}", timestamp(self.entry.accessed()))?;
+        writeln!(f, "First cluster:  {}", self.entry.cluster_number())?;
+        write!(f, "Size:           {} bytes", self.entry.file_size())
+    }
+}
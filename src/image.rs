@@ -0,0 +1,807 @@
+//! Access to the bytes of a disk image, raw or compressed.
+//!
+//! Evidence is often archived compressed. With the `compressed` feature, gzip and zstd images are
+//! read in place: the image is split into chunks that decompress independently (gzip members,
+//! zstd frames), and an index mapping the offsets of the raw image to the chunks is built when the
+//! image is opened. A read only decompresses the chunks it overlaps, so the image never has to be
+//! decompressed to temporary storage.
+//!
+//! The index of a zstd image comes from its seek table if it has one (seekable zstd format), and
+//! is otherwise built by decompressing the frames once. Images made of a single gzip member or
+//! zstd frame are supported, but every read decompresses the image from its start: compress them
+//! in independent chunks, e.g. with `bgzip` or a seekable zstd compressor.
+//!
+//! Compressed images are read-only.
+//!
+//! Fixed VHD images are read as raw images, their footer being left out of the disk (see
+//! [`crate::vhd`]). [`convert_image`] re-encodes an image as a raw or fixed VHD image.
+//!
+//! Lab images are often sparse files, mostly made of holes. Where the platform supports
+//! `SEEK_DATA`/`SEEK_HOLE`, [`ImageSource::data_extents`] reports the ranges actually holding
+//! data, so that whole-image operations skip the holes instead of reading gigabytes of zeros.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "compressed")]
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use getset::Getters;
+
+use crate::batch_read::read_batch;
+use crate::journal::{ImageWriter, Journal};
+use crate::utils::write_at;
+use crate::vhd;
+
+/// The source of the bytes of a disk image.
+#[derive(Debug)]
+pub enum ImageSource {
+    /// A raw image, read from its file, with the journal of the writes made to it and its I/O
+    /// counters.
+    Raw(PathBuf, Journal, IoCounters),
+    /// A compressed image, read through its chunk index, and its I/O counters.
+    #[cfg(feature = "compressed")]
+    Compressed(Arc<compressed::CompressedImage>, IoCounters),
+}
+
+/// The number of bytes read from and written to an image.
+#[derive(Debug, Default)]
+pub struct IoCounters {
+    read: AtomicU64,
+    written: AtomicU64,
+}
+
+impl IoCounters {
+    /// Counts bytes read from the image.
+    pub(crate) fn add_read(&self, len: u64) {
+        self.read.fetch_add(len, Ordering::Relaxed);
+    }
+
+    /// Counts bytes written to the image.
+    pub(crate) fn add_written(&self, len: u64) {
+        self.written.fetch_add(len, Ordering::Relaxed);
+    }
+
+    /// Returns the current counts.
+    pub fn stats(&self) -> IoStats {
+        IoStats {
+            bytes_read: self.read.load(Ordering::Relaxed),
+            bytes_written: self.written.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The number of bytes read from and written to an image since it was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Getters)]
+pub struct IoStats {
+    /// Number of bytes of the raw image read through [`ImageSource::read_at`],
+    /// [`ImageSource::read_batch`] and image writers.
+    #[get = "pub"]
+    bytes_read: u64,
+    /// Number of bytes written.
+    #[get = "pub"]
+    bytes_written: u64,
+}
+
+impl IoStats {
+    /// Returns the bytes read and written since an earlier count.
+    pub fn since(&self, earlier: &IoStats) -> IoStats {
+        IoStats {
+            bytes_read: self.bytes_read.saturating_sub(earlier.bytes_read),
+            bytes_written: self.bytes_written.saturating_sub(earlier.bytes_written),
+        }
+    }
+}
+
+impl ImageSource {
+    /// Opens a disk image, detecting its compression from its magic bytes.
+    ///
+    /// # Parameters
+    /// - `path`: The path of the image.
+    ///
+    /// # Returns
+    /// - `Ok(ImageSource)`: The source of the image. Without the `compressed` feature, every
+    ///   image is read raw.
+    /// - `Err(io::Error)`: If the image cannot be read, or its chunks cannot be indexed.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        #[cfg(feature = "compressed")]
+        if let Some(image) = compressed::CompressedImage::open(path)? {
+            return Ok(ImageSource::Compressed(
+                Arc::new(image),
+                IoCounters::default(),
+            ));
+        }
+
+        File::open(path)?;
+        Ok(ImageSource::Raw(
+            path.to_path_buf(),
+            Journal::default(),
+            IoCounters::default(),
+        ))
+    }
+
+    /// Returns the path of the image file.
+    pub fn path(&self) -> &Path {
+        match self {
+            ImageSource::Raw(path, _, _) => path,
+            #[cfg(feature = "compressed")]
+            ImageSource::Compressed(image, _) => image.path(),
+        }
+    }
+
+    /// Returns whether the image is compressed, hence read-only.
+    pub fn is_compressed(&self) -> bool {
+        !matches!(self, ImageSource::Raw(..))
+    }
+
+    /// Returns the size of the raw image in bytes.
+    pub fn len(&self) -> io::Result<u64> {
+        match self {
+            ImageSource::Raw(path, _, _) => {
+                let file = File::open(path)?;
+                match vhd::fixed_disk_size(&file)? {
+                    Some(size) => Ok(size),
+                    None => Ok(file.metadata()?.len()),
+                }
+            }
+            #[cfg(feature = "compressed")]
+            ImageSource::Compressed(image, _) => Ok(image.len()),
+        }
+    }
+
+    /// Returns whether the raw image is empty.
+    pub fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Opens a reader over the raw image.
+    pub fn reader(&self) -> io::Result<ImageReader> {
+        match self {
+            ImageSource::Raw(path, _, _) => Ok(ImageReader::Raw(File::open(path)?)),
+            #[cfg(feature = "compressed")]
+            ImageSource::Compressed(image, _) => Ok(ImageReader::Compressed {
+                image: image.clone(),
+                pos: 0,
+            }),
+        }
+    }
+
+    /// Reads `len` bytes of the raw image starting at the given absolute byte offset.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The bytes read.
+    /// - `Err(io::Error)`: If the range isn't entirely part of the image, or cannot be read.
+    pub fn read_at(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; len];
+        let mut reader = self.reader()?;
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut buf).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Failed to read {len} bytes at offset {offset}: {err}"),
+            )
+        })?;
+        self.counters().add_read(len as u64);
+        Ok(buf)
+    }
+
+    /// Reads a batch of byte ranges of the raw image (see [`read_batch`]).
+    ///
+    /// # Parameters
+    /// - `requests`: The absolute byte offset and length of each range.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<Vec<u8>>)`: The content of each range, in the order of the requests.
+    /// - `Err(io::Error)`: If a range cannot be read entirely.
+    pub fn read_batch(&self, requests: &[(u64, usize)]) -> io::Result<Vec<Vec<u8>>> {
+        match self {
+            ImageSource::Raw(path, _, counters) if !requests.is_empty() => {
+                let bufs = read_batch(&File::open(path)?, requests)?;
+                counters.add_read(bufs.iter().map(|buf| buf.len() as u64).sum());
+                Ok(bufs)
+            }
+            _ => requests
+                .iter()
+                .map(|(offset, len)| self.read_at(*offset, *len))
+                .collect(),
+        }
+    }
+
+    /// Returns the ranges of the raw image holding data, skipping the holes of a sparse file.
+    ///
+    /// Holes read as zeros. Compressed images, and platforms without `SEEK_DATA`/`SEEK_HOLE`,
+    /// report the whole range as data.
+    ///
+    /// # Parameters
+    /// - `offset`: The absolute byte offset of the range.
+    /// - `len`: The length of the range in bytes.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<(u64, u64)>)`: The offset and length of each data extent within the range, in
+    ///   order.
+    /// - `Err(io::Error)`: If the image cannot be read.
+    pub fn data_extents(&self, offset: u64, len: u64) -> io::Result<Vec<(u64, u64)>> {
+        match self {
+            ImageSource::Raw(path, _, _) => data_extents(&File::open(path)?, offset, len),
+            #[cfg(feature = "compressed")]
+            ImageSource::Compressed(..) => Ok(vec![(offset, len)]),
+        }
+    }
+
+    /// Returns the journal of the writes made to the image, if it is raw.
+    pub fn journal(&self) -> Option<&Journal> {
+        match self {
+            ImageSource::Raw(_, journal, _) => Some(journal),
+            #[cfg(feature = "compressed")]
+            ImageSource::Compressed(..) => None,
+        }
+    }
+
+    /// Enables or disables the dry-run mode of the image, see [`Journal::set_dry_run`]. Does
+    /// nothing for compressed images, which are never written.
+    pub fn set_dry_run(&self, dry_run: bool) {
+        if let Some(journal) = self.journal() {
+            journal.set_dry_run(dry_run);
+        }
+    }
+
+    /// Returns whether the writes to the image are planned rather than applied, see
+    /// [`Journal::set_dry_run`]. Compressed images are never written.
+    pub fn is_dry_run(&self) -> bool {
+        self.journal().is_some_and(Journal::is_dry_run)
+    }
+
+    /// Returns the I/O counters of the image.
+    fn counters(&self) -> &IoCounters {
+        match self {
+            ImageSource::Raw(_, _, counters) => counters,
+            #[cfg(feature = "compressed")]
+            ImageSource::Compressed(_, counters) => counters,
+        }
+    }
+
+    /// Returns the number of bytes read from and written to the image since it was opened.
+    pub fn io_stats(&self) -> IoStats {
+        self.counters().stats()
+    }
+
+    /// Opens the image for writing.
+    ///
+    /// # Returns
+    /// - `Ok(ImageWriter)`: The raw image, opened for reading and writing, recording its writes
+    ///   in the journal of the image.
+    /// - `Err(io::Error)`: If the image is compressed, or cannot be opened.
+    pub fn open_for_write(&self) -> io::Result<ImageWriter<'_>> {
+        match self {
+            ImageSource::Raw(path, journal, counters) => Ok(ImageWriter::new(
+                File::options().read(true).write(true).open(path)?,
+                journal,
+                counters,
+            )),
+            #[cfg(feature = "compressed")]
+            ImageSource::Compressed(..) => Err(io::Error::new(
+                io::ErrorKind::ReadOnlyFilesystem,
+                "Compressed images are read-only",
+            )),
+        }
+    }
+}
+
+/// The format of a converted image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// The bytes of the disk, as is.
+    Raw,
+    /// The bytes of the disk followed by the footer of a fixed VHD.
+    Vhd,
+}
+
+impl ImageFormat {
+    /// Finds a format by its name.
+    ///
+    /// # Parameters
+    /// - `name`: `raw` or `vhd`.
+    ///
+    /// # Returns
+    /// - `Some(ImageFormat)`: The format.
+    /// - `None`: If no format has this name.
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name {
+            "raw" => Some(ImageFormat::Raw),
+            "vhd" => Some(ImageFormat::Vhd),
+            _ => None,
+        }
+    }
+}
+
+/// The number of bytes copied at once by [`convert_image`].
+const CONVERT_BUFFER_LEN: u64 = 1024 * 1024;
+
+/// Re-encodes a disk image in another format, a buffer at a time.
+///
+/// The holes of a sparse source, and the zeroed buffers, are left as holes in the converted
+/// image. A fixed VHD is padded to a whole number of sectors before its footer.
+///
+/// # Parameters
+/// - `source`: The image to convert: raw, fixed VHD or compressed.
+/// - `out`: The path of the converted image, replaced if it exists.
+/// - `format`: The format of the converted image.
+/// - `progress`: Called after each buffer with the bytes of the disk copied so far and its size.
+///
+/// # Returns
+/// - `Ok(u64)`: The size of the disk in bytes, without the VHD footer.
+/// - `Err(io::Error)`: If `out` is the source image, or an image cannot be read or written.
+pub fn convert_image(
+    source: &ImageSource,
+    out: &Path,
+    format: ImageFormat,
+    mut progress: impl FnMut(u64, u64),
+) -> io::Result<u64> {
+    if out.exists() && out.canonicalize()? == source.path().canonicalize()? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The converted image would overwrite its source",
+        ));
+    }
+
+    let len = source.len()?;
+    let disk_len = match format {
+        ImageFormat::Raw => len,
+        ImageFormat::Vhd => len.next_multiple_of(512),
+    };
+    let mut file = File::create(out)?;
+    file.set_len(disk_len)?;
+
+    let mut reader = source.reader()?;
+    let mut done = 0;
+    for (start, extent_len) in source.data_extents(0, len)? {
+        let mut pos = start;
+        while pos < start + extent_len {
+            let chunk = CONVERT_BUFFER_LEN.min(start + extent_len - pos);
+            let mut buf = vec![0; chunk as usize];
+            reader.seek(SeekFrom::Start(pos))?;
+            reader.read_exact(&mut buf)?;
+            if buf.iter().any(|byte| *byte != 0) {
+                write_at(&mut file, pos, &buf)?;
+            }
+            pos += chunk;
+            done += chunk;
+            progress(done, len);
+        }
+    }
+
+    if format == ImageFormat::Vhd {
+        write_at(&mut file, disk_len, &vhd::fixed_footer(disk_len))?;
+    }
+    file.sync_all()?;
+    Ok(disk_len)
+}
+
+/// Returns the ranges of a file holding data, skipping the holes of a sparse file.
+///
+/// # Parameters
+/// - `file`: The file to inspect.
+/// - `offset`: The absolute byte offset of the range.
+/// - `len`: The length of the range in bytes.
+///
+/// # Returns
+/// - `Ok(Vec<(u64, u64)>)`: The offset and length of each data extent within the range, in order.
+///   The whole range if the platform or the file system cannot report holes.
+/// - `Err(io::Error)`: If the file cannot be inspected.
+pub fn data_extents(file: &File, offset: u64, len: u64) -> io::Result<Vec<(u64, u64)>> {
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    if let Some(extents) = sparse::data_extents(file, offset, len)? {
+        return Ok(extents);
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+    let _ = file;
+    Ok(match len {
+        0 => vec![],
+        _ => vec![(offset, len)],
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+mod sparse {
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::AsRawFd;
+
+    /// Seeks to the next data or hole at or after an offset.
+    ///
+    /// # Returns
+    /// - `Ok(Some(u64))`: The offset found.
+    /// - `Ok(None)`: If there is no data after the offset (`ENXIO`).
+    /// - `Err(io::Error)`: If the seek fails.
+    fn seek(file: &File, offset: u64, whence: libc::c_int) -> io::Result<Option<u64>> {
+        let offset = libc::off_t::try_from(offset)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Offset too large"))?;
+        // SAFETY: lseek only repositions the offset of a valid open file descriptor
+        match unsafe { libc::lseek(file.as_raw_fd(), offset, whence) } {
+            -1 => match io::Error::last_os_error() {
+                err if err.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+                err => Err(err),
+            },
+            found => Ok(Some(found as u64)),
+        }
+    }
+
+    /// Lists the data extents of a range through `SEEK_DATA`/`SEEK_HOLE`.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Vec<(u64, u64)>))`: The data extents within the range.
+    /// - `Ok(None)`: If the file system doesn't support the seeks.
+    /// - `Err(io::Error)`: If a seek fails.
+    pub(super) fn data_extents(
+        file: &File,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<Option<Vec<(u64, u64)>>> {
+        // The range past the end of the file is reported as data, so that reading it still fails
+        let requested_end = offset.saturating_add(len);
+        let end = requested_end.min(file.metadata()?.len());
+        let mut extents = vec![];
+        let mut pos = offset;
+        while pos < end {
+            let start = match seek(file, pos, libc::SEEK_DATA) {
+                Ok(Some(start)) => start,
+                Ok(None) => break,
+                Err(err) if err.raw_os_error() == Some(libc::EINVAL) => return Ok(None),
+                Err(err) => return Err(err),
+            };
+            if start >= end {
+                break;
+            }
+            let stop = seek(file, start, libc::SEEK_HOLE)?.unwrap_or(end).min(end);
+            extents.push((start, stop - start));
+            pos = stop;
+        }
+        if requested_end > end {
+            let start = end.max(offset);
+            match extents.last_mut() {
+                Some((last_start, last_len)) if *last_start + *last_len == start => {
+                    *last_len += requested_end - start
+                }
+                _ => extents.push((start, requested_end - start)),
+            }
+        }
+        Ok(Some(extents))
+    }
+}
+
+/// A reader over the raw image of an [`ImageSource`].
+pub enum ImageReader {
+    /// The file of a raw image.
+    Raw(File),
+    /// A compressed image, and the position of the reader in the raw image.
+    #[cfg(feature = "compressed")]
+    Compressed {
+        image: Arc<compressed::CompressedImage>,
+        pos: u64,
+    },
+}
+
+impl Read for ImageReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ImageReader::Raw(file) => file.read(buf),
+            #[cfg(feature = "compressed")]
+            ImageReader::Compressed { image, pos } => {
+                let read = image.read_at(*pos, buf)?;
+                *pos += read as u64;
+                Ok(read)
+            }
+        }
+    }
+}
+
+impl Seek for ImageReader {
+    fn seek(&mut self, target: SeekFrom) -> io::Result<u64> {
+        match self {
+            ImageReader::Raw(file) => file.seek(target),
+            #[cfg(feature = "compressed")]
+            ImageReader::Compressed { image, pos } => {
+                let new_pos = match target {
+                    SeekFrom::Start(offset) => Some(offset),
+                    SeekFrom::End(delta) => image.len().checked_add_signed(delta),
+                    SeekFrom::Current(delta) => pos.checked_add_signed(delta),
+                };
+                *pos = new_pos.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start")
+                })?;
+                Ok(*pos)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "compressed")]
+mod compressed {
+    use flate2::bufread::GzDecoder;
+    use ruzstd::decoding::StreamingDecoder;
+    use ruzstd::decoding::errors::FrameDecoderError;
+    use std::collections::VecDeque;
+    use std::fs::File;
+    use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+
+    use crate::utils::u32_at;
+
+    const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+    const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+    /// Magic number of the skippable frame holding the seek table of a seekable zstd image.
+    const SEEK_TABLE_FRAME_MAGIC: u32 = 0x184D_2A5E;
+    /// Magic number ending the seek table.
+    const SEEK_TABLE_MAGIC: u32 = 0x8F92_EAB1;
+    /// Size of the footer of the seek table: frame count, descriptor and magic number.
+    const SEEK_TABLE_FOOTER_LEN: u64 = 9;
+
+    /// Number of decompressed chunks kept in memory.
+    const CHUNK_CACHE_LEN: usize = 4;
+    /// Chunks larger than this are streamed on every read rather than cached.
+    const MAX_CACHED_CHUNK_LEN: u64 = 64 << 20;
+
+    /// The compression format of an image.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Format {
+        Gzip,
+        Zstd,
+    }
+
+    /// A part of the image that decompresses on its own.
+    #[derive(Debug, Clone, Copy)]
+    struct Chunk {
+        /// Byte offset of the chunk in the compressed file.
+        comp_offset: u64,
+        /// Length of the chunk in the compressed file.
+        comp_len: u64,
+        /// Byte offset of the content of the chunk in the raw image.
+        raw_offset: u64,
+        /// Length of the content of the chunk.
+        raw_len: u64,
+    }
+
+    /// A compressed image and the index of its chunks.
+    #[derive(Debug)]
+    pub struct CompressedImage {
+        path: PathBuf,
+        format: Format,
+        /// The chunks, in raw image order.
+        chunks: Vec<Chunk>,
+        /// The recently decompressed chunks, by index.
+        cache: Mutex<VecDeque<(usize, Vec<u8>)>>,
+    }
+
+    impl CompressedImage {
+        /// Returns the path of the compressed file.
+        pub(super) fn path(&self) -> &Path {
+            &self.path
+        }
+
+        /// Opens and indexes a compressed image.
+        ///
+        /// # Returns
+        /// - `Ok(Some(CompressedImage))`: If the image is compressed with gzip or zstd.
+        /// - `Ok(None)`: If the image isn't compressed.
+        /// - `Err(io::Error)`: If the image cannot be read or decompressed.
+        pub(super) fn open(path: &Path) -> io::Result<Option<Self>> {
+            let mut file = File::open(path)?;
+            let mut magic = [0; 4];
+            let magic_len = file.read(&mut magic)?;
+            let format = match &magic[..magic_len] {
+                m if m.starts_with(&GZIP_MAGIC) => Format::Gzip,
+                m if m.starts_with(&ZSTD_MAGIC) => Format::Zstd,
+                _ => return Ok(None),
+            };
+
+            let chunks = match format {
+                Format::Zstd => match read_seek_table(&mut file)? {
+                    Some(chunks) => chunks,
+                    None => index_chunks(&file, format)?,
+                },
+                Format::Gzip => index_chunks(&file, format)?,
+            };
+
+            Ok(Some(Self {
+                path: path.to_path_buf(),
+                format,
+                chunks,
+                cache: Mutex::new(VecDeque::new()),
+            }))
+        }
+
+        /// Returns the size of the raw image in bytes.
+        pub(super) fn len(&self) -> u64 {
+            self.chunks
+                .last()
+                .map(|chunk| chunk.raw_offset + chunk.raw_len)
+                .unwrap_or(0)
+        }
+
+        /// Reads the raw image at an offset, up to the end of the chunk holding it.
+        ///
+        /// # Returns
+        /// - `Ok(usize)`: The number of bytes read, 0 at the end of the image.
+        /// - `Err(io::Error)`: If the chunk cannot be decompressed.
+        pub(super) fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+            let idx = self
+                .chunks
+                .partition_point(|chunk| chunk.raw_offset + chunk.raw_len <= offset);
+            let Some(chunk) = self.chunks.get(idx) else {
+                return Ok(0);
+            };
+            let skip = offset - chunk.raw_offset;
+            let len = buf.len().min((chunk.raw_len - skip) as usize);
+
+            if chunk.raw_len > MAX_CACHED_CHUNK_LEN {
+                let mut decoder = self.decoder(chunk)?;
+                io::copy(&mut (&mut decoder).take(skip), &mut io::sink())?;
+                decoder.read_exact(&mut buf[..len])?;
+                return Ok(len);
+            }
+
+            let mut cache = self.cache.lock().unwrap_or_else(|err| err.into_inner());
+            let pos = match cache.iter().position(|(cached, _)| *cached == idx) {
+                Some(pos) => pos,
+                None => {
+                    let mut data = Vec::with_capacity(chunk.raw_len as usize);
+                    self.decoder(chunk)?.read_to_end(&mut data)?;
+                    if data.len() as u64 != chunk.raw_len {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "Chunk at offset {} holds {} bytes instead of {}",
+                                chunk.comp_offset,
+                                data.len(),
+                                chunk.raw_len
+                            ),
+                        ));
+                    }
+                    if cache.len() == CHUNK_CACHE_LEN {
+                        cache.pop_back();
+                    }
+                    cache.push_front((idx, data));
+                    0
+                }
+            };
+
+            let data = &cache[pos].1;
+            buf[..len].copy_from_slice(&data[skip as usize..skip as usize + len]);
+            Ok(len)
+        }
+
+        /// Opens a decoder over the content of a chunk.
+        fn decoder(&self, chunk: &Chunk) -> io::Result<Box<dyn Read>> {
+            let mut file = File::open(&self.path)?;
+            file.seek(SeekFrom::Start(chunk.comp_offset))?;
+            let comp = BufReader::new(file.take(chunk.comp_len));
+            match self.format {
+                Format::Gzip => Ok(Box::new(GzDecoder::new(comp))),
+                Format::Zstd => Ok(Box::new(
+                    StreamingDecoder::new(comp).map_err(io::Error::other)?,
+                )),
+            }
+        }
+    }
+
+    /// Reads the seek table at the end of a seekable zstd image.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Vec<Chunk>))`: The frames listed in the seek table.
+    /// - `Ok(None)`: If the image has no seek table.
+    fn read_seek_table(file: &mut File) -> io::Result<Option<Vec<Chunk>>> {
+        let file_len = file.metadata()?.len();
+        if file_len < SEEK_TABLE_FOOTER_LEN {
+            return Ok(None);
+        }
+        let mut footer = [0; SEEK_TABLE_FOOTER_LEN as usize];
+        file.seek(SeekFrom::Start(file_len - SEEK_TABLE_FOOTER_LEN))?;
+        file.read_exact(&mut footer)?;
+        if u32_at(&footer, 5) != SEEK_TABLE_MAGIC {
+            return Ok(None);
+        }
+
+        let frame_cnt = u32_at(&footer, 0) as u64;
+        let entry_len = match footer[4] & 0x80 {
+            0 => 8,
+            _ => 12,
+        };
+        let table_len = frame_cnt * entry_len + SEEK_TABLE_FOOTER_LEN;
+        let Some(table_start) = file_len.checked_sub(table_len + 8) else {
+            return Ok(None);
+        };
+        let mut table = vec![0; (table_len + 8) as usize];
+        file.seek(SeekFrom::Start(table_start))?;
+        file.read_exact(&mut table)?;
+        if u32_at(&table, 0) != SEEK_TABLE_FRAME_MAGIC {
+            return Ok(None);
+        }
+
+        let mut chunks = vec![];
+        let (mut comp_offset, mut raw_offset) = (0, 0);
+        for entry in table[8..]
+            .chunks_exact(entry_len as usize)
+            .take(frame_cnt as usize)
+        {
+            let (comp_len, raw_len) = (u32_at(entry, 0) as u64, u32_at(entry, 4) as u64);
+            chunks.push(Chunk {
+                comp_offset,
+                comp_len,
+                raw_offset,
+                raw_len,
+            });
+            comp_offset += comp_len;
+            raw_offset += raw_len;
+        }
+        Ok(Some(chunks))
+    }
+
+    /// Indexes the chunks of an image by decompressing them once.
+    fn index_chunks(file: &File, format: Format) -> io::Result<Vec<Chunk>> {
+        let mut reader = CountingReader {
+            inner: BufReader::new(file),
+            pos: 0,
+        };
+        reader.inner.seek(SeekFrom::Start(0))?;
+
+        let mut chunks = vec![];
+        let mut raw_offset = 0;
+        while !reader.fill_buf()?.is_empty() {
+            let comp_offset = reader.pos;
+            let raw_len = match format {
+                Format::Gzip => io::copy(&mut GzDecoder::new(&mut reader), &mut io::sink())?,
+                Format::Zstd => match StreamingDecoder::new(&mut reader) {
+                    Ok(mut decoder) => io::copy(&mut decoder, &mut io::sink())?,
+                    // Skippable frames hold metadata only
+                    Err(FrameDecoderError::ReadFrameHeaderError(
+                        ruzstd::decoding::errors::ReadFrameHeaderError::SkipFrame {
+                            length, ..
+                        },
+                    )) => {
+                        io::copy(&mut (&mut reader).take(length as u64), &mut io::sink())?;
+                        continue;
+                    }
+                    Err(err) => return Err(io::Error::other(err)),
+                },
+            };
+
+            chunks.push(Chunk {
+                comp_offset,
+                comp_len: reader.pos - comp_offset,
+                raw_offset,
+                raw_len,
+            });
+            raw_offset += raw_len;
+        }
+
+        Ok(chunks)
+    }
+
+    /// A buffered reader counting the bytes consumed.
+    struct CountingReader<R: BufRead> {
+        inner: R,
+        pos: u64,
+    }
+
+    impl<R: BufRead> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = self.inner.read(buf)?;
+            self.pos += read as u64;
+            Ok(read)
+        }
+    }
+
+    impl<R: BufRead> BufRead for CountingReader<R> {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            self.inner.fill_buf()
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.inner.consume(amt);
+            self.pos += amt as u64;
+        }
+    }
+}
@@ -0,0 +1,484 @@
+//! Journal of the writes made to a disk image, exported and replayed as patch scripts.
+//!
+//! Every write to a raw image goes through an [`ImageWriter`], which records it in the
+//! [`Journal`] of the image. The journal can be exported as a patch script, a portable text file
+//! holding one operation per line, and the script replayed onto a fresh copy of the base image. A
+//! lab scenario is then shipped as a base image and a patch script, rather than as a mutated
+//! binary artifact.
+//!
+//! A patch script starts with a `#` comment header, followed by one operation per line:
+//! - `write <offset> <hex bytes>`: Writes bytes at an absolute byte offset.
+//! - `fill <offset> <len> <hex pattern>`: Writes `len` bytes repeating a pattern, as wipes do.
+//! - `resize <len>`: Truncates or extends the image to `len` bytes.
+//!
+//! Offsets and lengths are hexadecimal with a `0x` prefix, or decimal.
+//!
+//! The journal keeps at most [`MAX_RETAINED_BYTES`] of written data. The content of the writes
+//! past that limit, e.g. those of a large snapshot restore, is dropped: they are still listed,
+//! but the journal can no longer be exported.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::image::IoCounters;
+use crate::utils::{write_at, write_pattern};
+
+/// The first line of a patch script.
+const SCRIPT_HEADER: &str = "# fat_forensics patch script v1";
+
+/// The longest pattern recorded as a fill rather than as raw bytes.
+const MAX_PATTERN_LEN: usize = 16;
+
+/// The most bytes of written data kept by a journal, and by the writes planned in dry-run mode.
+pub const MAX_RETAINED_BYTES: usize = 64 << 20;
+
+/// An operation on a disk image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalEntry {
+    /// Bytes written at an absolute byte offset.
+    Write { offset: u64, data: Vec<u8> },
+    /// `len` bytes repeating a pattern, written at an absolute byte offset.
+    Fill {
+        offset: u64,
+        len: u64,
+        pattern: Vec<u8>,
+    },
+    /// The image truncated or extended to a length in bytes.
+    Resize(u64),
+    /// `len` bytes written at an absolute byte offset, whose content wasn't kept as the journal
+    /// had reached [`MAX_RETAINED_BYTES`].
+    Omitted { offset: u64, len: u64 },
+}
+
+impl JournalEntry {
+    /// Records a write, as a fill if the data repeats a short pattern.
+    ///
+    /// # Parameters
+    /// - `offset`: The absolute byte offset of the write.
+    /// - `data`: The bytes written.
+    /// - `keep`: Whether the bytes may be kept, the write being recorded as omitted otherwise
+    ///   unless it is a fill.
+    fn from_write(offset: u64, data: &[u8], keep: bool) -> Self {
+        let period = (1..=MAX_PATTERN_LEN.min(data.len() / 2))
+            .find(|period| data.chunks(*period).all(|chunk| data.starts_with(chunk)));
+        match period {
+            Some(period) => JournalEntry::Fill {
+                offset,
+                len: data.len() as u64,
+                pattern: data[..period].to_vec(),
+            },
+            None if keep => JournalEntry::Write {
+                offset,
+                data: data.to_vec(),
+            },
+            None => JournalEntry::Omitted {
+                offset,
+                len: data.len() as u64,
+            },
+        }
+    }
+
+    /// Returns the bytes changed by the entry, as an absolute byte offset and a length, or `None`
+    /// for a resize.
+    pub fn extent(&self) -> Option<(u64, u64)> {
+        match self {
+            JournalEntry::Write { offset, data } => Some((*offset, data.len() as u64)),
+            JournalEntry::Fill { offset, len, .. } | JournalEntry::Omitted { offset, len } => {
+                Some((*offset, *len))
+            }
+            JournalEntry::Resize(_) => None,
+        }
+    }
+
+    /// Formats the entry as a line of a patch script.
+    pub fn script_line(&self) -> String {
+        match self {
+            JournalEntry::Write { offset, data } => format!("write 0x{offset:X} {}", hex(data)),
+            JournalEntry::Fill {
+                offset,
+                len,
+                pattern,
+            } => format!("fill 0x{offset:X} 0x{len:X} {}", hex(pattern)),
+            JournalEntry::Resize(len) => format!("resize 0x{len:X}"),
+            JournalEntry::Omitted { offset, len } => format!("omitted 0x{offset:X} 0x{len:X}"),
+        }
+    }
+
+    /// Parses a line of a patch script.
+    ///
+    /// # Returns
+    /// - `Ok(JournalEntry)`: The operation of the line.
+    /// - `Err(String)`: A description of the syntax error.
+    pub fn from_script_line(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["write", offset, data] => Ok(JournalEntry::Write {
+                offset: number(offset)?,
+                data: unhex(data)?,
+            }),
+            ["fill", offset, len, pattern] if !pattern.is_empty() => Ok(JournalEntry::Fill {
+                offset: number(offset)?,
+                len: number(len)?,
+                pattern: unhex(pattern)?,
+            }),
+            ["resize", len] => Ok(JournalEntry::Resize(number(len)?)),
+            _ => Err(format!("unknown operation '{line}'")),
+        }
+    }
+
+    /// Returns the number of bytes of written data held by the entry.
+    fn retained_len(&self) -> usize {
+        match self {
+            JournalEntry::Write { data, .. } => data.len(),
+            _ => 0,
+        }
+    }
+
+    /// Applies the entry to an image.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the entry is applied.
+    /// - `Err(io::Error)`: If writing fails, a fill reaches past the end of the image, or the
+    ///   content of the entry wasn't kept.
+    fn apply(&self, file: &mut File) -> io::Result<()> {
+        match self {
+            JournalEntry::Write { offset, data } => write_at(file, *offset, data),
+            JournalEntry::Fill {
+                offset,
+                len,
+                pattern,
+            } => {
+                // The length comes from the script, a fill must not grow the image
+                let image_len = file.metadata()?.len();
+                if offset.checked_add(*len).is_none_or(|end| end > image_len) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{self}: past the end of the image, {image_len} bytes"),
+                    ));
+                }
+                write_pattern(file, *offset, *len, pattern)
+            }
+            JournalEntry::Resize(len) => file.set_len(*len),
+            JournalEntry::Omitted { .. } => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{self}: the content of the write wasn't kept"),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalEntry::Write { offset, data } => {
+                write!(f, "0x{:X}: {} bytes written", offset, data.len())
+            }
+            JournalEntry::Fill {
+                offset,
+                len,
+                pattern,
+            } => write!(f, "0x{offset:X}: {len} bytes filled with {}", hex(pattern)),
+            JournalEntry::Resize(len) => write!(f, "image resized to {len} bytes"),
+            JournalEntry::Omitted { offset, len } => {
+                write!(f, "0x{offset:X}: {len} bytes written, content not kept")
+            }
+        }
+    }
+}
+
+/// Encodes bytes as uppercase hexadecimal.
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+/// Decodes a hexadecimal byte string.
+fn unhex(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) {
+        return Err(format!("odd number of hexadecimal digits in '{text}'"));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| format!("invalid hexadecimal bytes '{text}'"))
+        })
+        .collect()
+}
+
+/// Parses a decimal number, or a hexadecimal one with a `0x` prefix.
+fn number(text: &str) -> Result<u64, String> {
+    match text.strip_prefix("0x") {
+        Some(digits) => u64::from_str_radix(digits, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| format!("invalid number '{text}'"))
+}
+
+/// Operations in order, with the number of bytes of written data they hold.
+#[derive(Debug, Default)]
+struct Entries {
+    entries: Vec<JournalEntry>,
+    retained: usize,
+}
+
+impl Entries {
+    /// Appends an operation.
+    fn push(&mut self, entry: JournalEntry) {
+        self.retained += entry.retained_len();
+        self.entries.push(entry);
+    }
+
+    /// Appends a write, dropping its content past [`MAX_RETAINED_BYTES`].
+    fn push_write(&mut self, offset: u64, data: &[u8]) {
+        let keep = self.retained + data.len() <= MAX_RETAINED_BYTES;
+        self.push(JournalEntry::from_write(offset, data, keep));
+    }
+
+    /// Removes and returns the operations.
+    fn take(&mut self) -> Vec<JournalEntry> {
+        self.retained = 0;
+        std::mem::take(&mut self.entries)
+    }
+}
+
+/// Locks the operations of a journal, even if a thread panicked while holding them: every
+/// operation is pushed whole, so they stay consistent.
+fn lock(mutex: &Mutex<Entries>) -> MutexGuard<'_, Entries> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// The writes made to an image, in order.
+#[derive(Debug, Default)]
+pub struct Journal {
+    entries: Mutex<Entries>,
+    /// Whether the writes are only planned, the image being left untouched.
+    dry_run: AtomicBool,
+    /// The writes planned in dry-run mode, in order.
+    planned: Mutex<Entries>,
+}
+
+impl Journal {
+    /// Records an operation.
+    fn record(&self, entry: JournalEntry) {
+        lock(&self.entries).push(entry);
+    }
+
+    /// Records a write.
+    fn record_write(&self, offset: u64, data: &[u8]) {
+        lock(&self.entries).push_write(offset, data);
+    }
+
+    /// Returns the recorded operations, in order.
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        lock(&self.entries).entries.clone()
+    }
+
+    /// Returns the number of recorded operations.
+    pub fn len(&self) -> usize {
+        lock(&self.entries).entries.len()
+    }
+
+    /// Returns whether no operation was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes and returns the recorded operations.
+    pub fn take(&self) -> Vec<JournalEntry> {
+        lock(&self.entries).take()
+    }
+
+    /// Appends operations, e.g. those recorded before the image was opened again.
+    pub fn extend(&self, entries: Vec<JournalEntry>) {
+        let mut recorded = lock(&self.entries);
+        entries.into_iter().for_each(|entry| recorded.push(entry));
+    }
+
+    /// Enables or disables the dry-run mode, in which the writes to the image are planned rather
+    /// than applied.
+    pub fn set_dry_run(&self, dry_run: bool) {
+        self.dry_run.store(dry_run, Ordering::Relaxed);
+    }
+
+    /// Returns whether the writes to the image are planned rather than applied.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.load(Ordering::Relaxed)
+    }
+
+    /// Records an operation planned in dry-run mode.
+    fn plan(&self, entry: JournalEntry) {
+        lock(&self.planned).push(entry);
+    }
+
+    /// Records a write planned in dry-run mode.
+    fn plan_write(&self, offset: u64, data: &[u8]) {
+        lock(&self.planned).push_write(offset, data);
+    }
+
+    /// Removes and returns the operations planned in dry-run mode.
+    pub fn take_planned(&self) -> Vec<JournalEntry> {
+        lock(&self.planned).take()
+    }
+}
+
+/// A raw image opened for writing, recording its writes in the journal of the image.
+///
+/// In dry-run mode, the writes are planned in the journal and the image is left untouched.
+pub struct ImageWriter<'a> {
+    file: File,
+    journal: &'a Journal,
+    counters: &'a IoCounters,
+}
+
+impl<'a> ImageWriter<'a> {
+    /// Wraps an image opened for reading and writing.
+    pub(crate) fn new(file: File, journal: &'a Journal, counters: &'a IoCounters) -> Self {
+        Self {
+            file,
+            journal,
+            counters,
+        }
+    }
+
+    /// Returns the underlying file, e.g. to read its metadata.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Truncates or extends the image, recording the resize if the length changes.
+    ///
+    /// # Parameters
+    /// - `len`: The new length of the image in bytes.
+    pub fn set_len(&self, len: u64) -> io::Result<()> {
+        if self.journal.is_dry_run() {
+            self.journal.plan(JournalEntry::Resize(len));
+        } else if self.file.metadata()?.len() != len {
+            self.file.set_len(len)?;
+            self.journal.record(JournalEntry::Resize(len));
+        }
+        Ok(())
+    }
+
+    /// Flushes the writes to the storage.
+    pub fn sync_all(&self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+}
+
+impl Write for ImageWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let offset = self.file.stream_position()?;
+        if self.journal.is_dry_run() {
+            // The position moves on as if the bytes were written
+            self.journal.plan_write(offset, buf);
+            self.file.seek(SeekFrom::Current(buf.len() as i64))?;
+            return Ok(buf.len());
+        }
+        let written = self.file.write(buf)?;
+        if written > 0 {
+            self.journal.record_write(offset, &buf[..written]);
+            self.counters.add_written(written as u64);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Read for ImageWriter<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        self.counters.add_read(read as u64);
+        Ok(read)
+    }
+}
+
+impl Seek for ImageWriter<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+/// Writes operations as a patch script.
+///
+/// # Parameters
+/// - `entries`: The operations, in order.
+/// - `base`: The name of the image the operations were recorded on, noted in the header.
+/// - `out`: The sink the script is written to.
+///
+/// # Returns
+/// - `Ok(())`: If the script is written.
+/// - `Err(io::Error)`: If writing fails, or the content of a write wasn't kept.
+pub fn write_script<W: Write>(entries: &[JournalEntry], base: &str, mut out: W) -> io::Result<()> {
+    let omitted = entries
+        .iter()
+        .filter(|entry| matches!(entry, JournalEntry::Omitted { .. }))
+        .count();
+    if omitted > 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the content of {omitted} writes wasn't kept, the journal holding at most {} MiB",
+                MAX_RETAINED_BYTES >> 20
+            ),
+        ));
+    }
+    writeln!(out, "{SCRIPT_HEADER}")?;
+    writeln!(out, "# base: {base}")?;
+    for entry in entries {
+        writeln!(out, "{}", entry.script_line())?;
+    }
+    out.flush()
+}
+
+/// Reads the operations of a patch script, skipping the blank and comment lines.
+///
+/// # Parameters
+/// - `script`: The content of the script.
+///
+/// # Returns
+/// - `Ok(Vec<JournalEntry>)`: The operations, in order.
+/// - `Err(io::Error)`: If the script cannot be read, or a line is not an operation.
+pub fn read_script<R: BufRead>(script: R) -> io::Result<Vec<JournalEntry>> {
+    let mut entries = vec![];
+    for (line_nb, line) in script.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        entries.push(JournalEntry::from_script_line(line).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {err}", line_nb + 1),
+            )
+        })?);
+    }
+    Ok(entries)
+}
+
+/// Copies a base image and replays operations onto the copy.
+///
+/// # Parameters
+/// - `entries`: The operations, in order.
+/// - `base`: The base image, left untouched.
+/// - `copy`: The path of the patched copy, replaced if it exists.
+///
+/// # Returns
+/// - `Ok(())`: If the copy is patched.
+/// - `Err(io::Error)`: If the base image cannot be copied, or the copy cannot be written.
+pub fn replay(entries: &[JournalEntry], base: &Path, copy: &Path) -> io::Result<()> {
+    std::fs::copy(base, copy)?;
+    let mut file = File::options().read(true).write(true).open(copy)?;
+    for entry in entries {
+        entry.apply(&mut file)?;
+    }
+    file.sync_all()
+}
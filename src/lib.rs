@@ -6,21 +6,97 @@
 //! - Interacting with FAT32 filesystems
 //! - Handling user commands for disk and filesystem operations
 //! - Printing disk and filesystem layouts
+//! - Converting disk images between the raw and fixed VHD formats
+//! - Running forensic analysis passes (e.g. wiped region detection)
+//! - Exporting volume structures (e.g. Graphviz DOT graphs)
+//! - Serving read-only analyses over HTTP/JSON (`serve` feature)
 //!
 //! The library is designed for extensibility and can be used both as a CLI tool and as a Rust library.
 //!
 //! # Re-exports
 //! - [`FATVol`]: FAT volume abstraction
 //! - [`Disk`]: Disk abstraction with partition and volume management
-//! - [`Volume`]: Enum for supported volume types
+//! - [`Bpb`], [`Mbr`], [`DirEntry`]: On-disk structures, exposed for fuzzing and custom parsing
 
+pub mod analysis;
+pub mod batch_read;
+pub mod cache;
+pub mod cancel;
 pub mod commands;
+pub mod events;
+pub mod export;
 pub mod filesystem;
+pub mod image;
+pub mod journal;
+pub mod limits;
+#[cfg(feature = "cli")]
+pub mod logging;
 pub mod partition;
+#[cfg(feature = "serve")]
+pub mod server;
+pub mod snapshot;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 pub mod traits;
 pub mod utils;
+pub mod vhd;
 
+/// Location of a directory entry (see [`filesystem::addressing::DirEntryAddress`]).
+pub use crate::filesystem::addressing::DirEntryAddress;
+/// BIOS Parameter Block and the structures of the reserved region (see
+/// [`filesystem::bpb::Bpb`]).
+pub use crate::filesystem::bpb::{Bpb, ReservedSector};
+/// Lazy iterator over a cluster chain (see [`filesystem::cluster_iter::ClusterIter`]).
+pub use crate::filesystem::cluster_iter::ClusterIter;
+/// Classification of the clusters of a volume (see [`filesystem::clusters::ClusterKind`]).
+pub use crate::filesystem::clusters::{ClusterKind, ClusterRegion, ClusterRegions};
+/// Decoded directory entry timestamp (see [`filesystem::datetime::FatDateTime`]).
+pub use crate::filesystem::datetime::FatDateTime;
+/// FAT directory entry and its attributes (see [`filesystem::dir_entry::DirEntry`]).
+pub use crate::filesystem::dir_entry::{Attribute, DirEntry};
+/// exFAT volume abstraction, its boot sector, allocation bitmap and directory entry sets (see
+/// [`filesystem::exfat::ExFatVol`]).
+pub use crate::filesystem::exfat::{
+    ExFatVol, bitmap::AllocationBitmap, boot::ExFatBoot, entry_set::EntrySet, entry_set::ExFatDir,
+};
+/// ext2/3/4 volume detection and its superblock (see [`filesystem::ext::ExtVol`]).
+pub use crate::filesystem::ext::{ExtSuperblock, ExtVol};
 /// FAT volume abstraction (see [`filesystem::fat::FATVol`]).
 pub use crate::filesystem::fat::FATVol;
+/// Errors of FAT volume operations (see [`filesystem::fat_error::FATError`]).
+pub use crate::filesystem::fat_error::FATError;
+/// FAT type of a volume (see [`filesystem::fat_type::FATType`]).
+pub use crate::filesystem::fat_type::FATType;
+/// Free space filling options for lab preparation (see [`FillOptions`] and [`FillContent`]).
+#[cfg(feature = "lab")]
+pub use crate::filesystem::filler::{FillContent, FillOptions};
+/// FAT32 FSINFO structure (see [`filesystem::fsinfo::FsInfo`]).
+pub use crate::filesystem::fsinfo::FsInfo;
+/// Volume details (see [`filesystem::fsstat::FsStat`]).
+pub use crate::filesystem::fsstat::{FatMirroring, FsStat};
+/// Summary of the geometry and identity of a volume (see [`filesystem::info::VolumeInfo`]).
+pub use crate::filesystem::info::VolumeInfo;
+/// ISO 9660 volume detection, its primary volume descriptor and El Torito boot catalog (see
+/// [`filesystem::iso9660::IsoVol`]).
+pub use crate::filesystem::iso9660::{BootCatalog, BootEntry, IsoVol, PrimaryVolumeDescriptor};
+/// NTFS volume detection and its boot sector (see [`filesystem::ntfs::NtfsVol`]).
+pub use crate::filesystem::ntfs::{NtfsBoot, NtfsVol};
+/// Directory tree recovery (see [`DiscoveredTree`], [`DiscoveredDir`] and [`RootCandidate`]).
+pub use crate::filesystem::recovery::{DiscoveredDir, DiscoveredTree, RootCandidate};
+/// Metadata of a file or directory, and raw directory entry records (see
+/// [`filesystem::stat::FileStat`] and [`filesystem::stat::DirEntryRecord`]).
+pub use crate::filesystem::stat::{DirEntryRecord, FileStat};
 /// Disk abstraction with partition and volume management (see [`partition::disk::Disk`]).
 pub use crate::partition::disk::Disk;
+/// A partition of a disk and the filesystem found in it (see
+/// [`partition::disk::PartitionInfo`] and [`partition::disk::Filesystem`]).
+pub use crate::partition::disk::{Filesystem, PartitionInfo};
+/// Errors of disk and partition operations (see [`partition::disk_error::DiskError`]).
+pub use crate::partition::disk_error::DiskError;
+/// GUID Partition Table read behind a protective or hybrid MBR, its entries and their GUIDs (see
+/// [`Gpt`], [`GptEntry`] and [`Guid`]).
+pub use crate::partition::gpt::{Gpt, GptEntry, Guid};
+/// Master Boot Record, its parsing options, partition types, overlapping partitions and boot
+/// indicator anomalies (see [`partition::mbr::Mbr`], [`partition::mbr::TableOptions`],
+/// [`partition::mbr::PTType`], [`partition::mbr::Overlap`] and [`partition::mbr::BootAnomaly`]).
+pub use crate::partition::mbr::{BootAnomaly, Geometry, Mbr, Overlap, PTType, TableOptions};
@@ -0,0 +1,41 @@
+//! Resource limits of the walks and scans of a volume.
+//!
+//! A crafted image can hold a directory that contains itself, millions of entries in a single
+//! directory or cluster chains spanning the whole volume. The [`Limits`] of a volume bound the
+//! work done on such structures: a walk or scan exceeding one fails with
+//! [`FATError::LimitExceeded`](crate::filesystem::fat_error::FATError::LimitExceeded) instead of
+//! exhausting the memory or the time of the examiner.
+
+/// Default maximum depth of nested directories.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Default maximum number of entries in a directory, the limit set by the FAT specification.
+pub const DEFAULT_MAX_DIR_ENTRIES: usize = 65536;
+
+/// Default maximum number of bytes recovered by a carving run.
+pub const DEFAULT_MAX_CARVED_BYTES: u64 = 1 << 30;
+
+/// The limits enforced by the walks and scans of a volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum depth of nested directories walked from the root.
+    pub max_depth: usize,
+    /// The maximum number of entries, long name entries included, listed in a directory.
+    pub max_dir_entries: usize,
+    /// The maximum number of clusters of a chain. Chains are always bounded by the number of
+    /// clusters of the volume.
+    pub max_chain_len: u32,
+    /// The maximum number of bytes recovered by a carving run.
+    pub max_carved_bytes: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_dir_entries: DEFAULT_MAX_DIR_ENTRIES,
+            max_chain_len: u32::MAX,
+            max_carved_bytes: DEFAULT_MAX_CARVED_BYTES,
+        }
+    }
+}
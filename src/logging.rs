@@ -0,0 +1,164 @@
+//! Logging setup shared by the binaries.
+//!
+//! Messages are printed on the standard error at a verbosity chosen on the command line, and
+//! optionally appended to a log file as JSON lines, one object per message with its timestamp,
+//! level, module and text, so that the logs of long automated runs can be filtered and reviewed
+//! with standard tools.
+//!
+//! The options are taken from the arguments of the binary:
+//! - `-v`, `-vv`, `-vvv`: Print info, debug or trace messages as well.
+//! - `-q`: Print errors only.
+//! - `--log-file <path>`: Append the messages to a JSON lines file, at the info level at least
+//!   whatever the verbosity of the standard error.
+
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The logging options of a binary.
+#[derive(Debug, Default)]
+pub struct LogOptions {
+    /// The number of levels added to (`-v`) or removed from (`-q`) the default verbosity.
+    verbosity: i8,
+    /// The JSON lines file the messages are appended to, if any.
+    log_file: Option<PathBuf>,
+}
+
+impl LogOptions {
+    /// Removes the logging options from the arguments of a binary.
+    ///
+    /// # Parameters
+    /// - `args`: The arguments, without the name of the binary. The logging options are removed.
+    ///
+    /// # Returns
+    /// - `Ok(LogOptions)`: The options found, the defaults if none.
+    /// - `Err(String)`: If `--log-file` lacks its path.
+    pub fn take_from_args(args: &mut Vec<String>) -> Result<Self, String> {
+        let mut options = LogOptions::default();
+        let mut remaining = vec![];
+        let mut iter = std::mem::take(args).into_iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-q" => options.verbosity -= 1,
+                "--log-file" => {
+                    let path = iter.next().ok_or("--log-file expects a path")?;
+                    options.log_file = Some(PathBuf::from(path));
+                }
+                flag if is_verbose_flag(flag) => options.verbosity += (flag.len() - 1) as i8,
+                _ => remaining.push(arg),
+            }
+        }
+        *args = remaining;
+        Ok(options)
+    }
+
+    /// Installs the logger of the binary.
+    ///
+    /// # Parameters
+    /// - `default`: The verbosity of the standard error without `-v` or `-q`.
+    /// - `modules`: The modules whose messages are logged, e.g. the binary and `fat_forensics`.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the logger is installed.
+    /// - `Err(io::Error)`: If the log file cannot be opened.
+    pub fn init(&self, default: LevelFilter, modules: &[&str]) -> io::Result<()> {
+        let stderr_level = shifted(default, self.verbosity);
+        let mut stderr = stderrlog::new();
+        stderr
+            .modules(modules.iter().copied())
+            .verbosity(stderr_level);
+        if stderr_level == LevelFilter::Off {
+            stderr.quiet(true);
+        }
+
+        let file = match &self.log_file {
+            Some(path) => Some(Mutex::new(
+                File::options().create(true).append(true).open(path)?,
+            )),
+            None => None,
+        };
+        let file_level = match file {
+            Some(_) => stderr_level.max(LevelFilter::Info),
+            None => LevelFilter::Off,
+        };
+
+        log::set_max_level(stderr_level.max(file_level));
+        log::set_boxed_logger(Box::new(CliLogger {
+            stderr,
+            file,
+            file_level,
+            modules: modules.iter().map(|module| module.to_string()).collect(),
+        }))
+        .map_err(io::Error::other)
+    }
+}
+
+/// Returns whether an argument is `-v`, `-vv`, ...
+fn is_verbose_flag(arg: &str) -> bool {
+    arg.len() > 1 && arg.starts_with('-') && arg[1..].bytes().all(|c| c == b'v')
+}
+
+/// Raises or lowers a verbosity by a number of levels, within `Off` and `Trace`.
+fn shifted(level: LevelFilter, levels: i8) -> LevelFilter {
+    let index = (level as i64 + levels as i64).clamp(0, LevelFilter::Trace as i64);
+    LevelFilter::iter()
+        .nth(index as usize)
+        .unwrap_or(LevelFilter::Trace)
+}
+
+/// Prints messages on the standard error and appends them to the log file.
+struct CliLogger {
+    stderr: stderrlog::StdErrLog,
+    file: Option<Mutex<File>>,
+    file_level: LevelFilter,
+    /// The modules whose messages are written to the log file.
+    modules: Vec<String>,
+}
+
+impl CliLogger {
+    /// Returns whether a message is written to the log file.
+    fn file_enabled(&self, metadata: &Metadata) -> bool {
+        self.file.is_some()
+            && metadata.level() <= self.file_level
+            && self.modules.iter().any(|module| {
+                metadata.target() == module || metadata.target().starts_with(&format!("{module}::"))
+            })
+    }
+}
+
+impl Log for CliLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata) || self.file_enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.stderr.log(record);
+        if !self.file_enabled(record.metadata()) {
+            return;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |elapsed| elapsed.as_secs_f64());
+        let line = serde_json::json!({
+            "timestamp": timestamp,
+            "level": record.level().as_str(),
+            "module": record.target(),
+            "message": record.args().to_string(),
+        });
+        if let Some(file) = &self.file {
+            // A log that cannot be written must not stop the analysis
+            let _ = writeln!(file.lock().unwrap(), "{line}");
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
@@ -3,22 +3,80 @@
 //! This module provides functionality for:
 //! - Opening and parsing disk images
 //! - Handling different partition table types (currently only MBR)
-//! - Managing volume analysis (currently only FAT32 filesystems)
+//! - Managing volume analysis (FAT12, FAT16, FAT32 and exFAT filesystems), and recognizing NTFS,
+//!   ext2/3/4 and ISO 9660 volumes
 //! - Displaying disk layout information
 
-use getset::Getters;
-use std::fs::File;
+use getset::{Getters, MutGetters};
+use std::fmt;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use super::disk_error::DiskError;
-use super::mbr::Mbr;
-use super::mbr::PTType;
+use super::mbr::{BOOTABLE, Mbr, PTType, TableOptions};
+use crate::filesystem::exfat::ExFatVol;
+use crate::filesystem::ext::ExtVol;
 use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_type::FATType;
+use crate::filesystem::iso9660::IsoVol;
+use crate::filesystem::ntfs::NtfsVol;
+use crate::image::ImageSource;
 use crate::traits::TreeDisplay;
-use crate::traits::{LayoutDisplay, TraitError};
+use crate::traits::{EventSink, LayoutDisplay, LayoutOptions, TraitError};
+use crate::utils::write_at;
+
+/// The filesystem detected in a partition when the disk was opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filesystem {
+    /// A FAT12, FAT16 or FAT32 volume, analyzed.
+    Fat(FATType),
+    /// An exFAT volume, whose layout and directory tree are read.
+    ExFat,
+    /// An NTFS volume, only recognized.
+    Ntfs,
+    /// An ext2, ext3 or ext4 volume, only recognized, encapsulating its version.
+    Ext(&'static str),
+    /// An ISO 9660 volume starting with the partition, only recognized.
+    Iso9660,
+}
+
+impl fmt::Display for Filesystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filesystem::Fat(fat_type) => write!(f, "{fat_type}"),
+            Filesystem::ExFat => write!(f, "exFAT"),
+            Filesystem::Ntfs => write!(f, "NTFS"),
+            Filesystem::Ext(version) => write!(f, "{version}"),
+            Filesystem::Iso9660 => write!(f, "ISO 9660"),
+        }
+    }
+}
+
+/// A partition of the disk, as returned by [`Disk::partitions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionInfo {
+    /// The 1-based number of the partition, as in the layout and the `part` command.
+    pub number: usize,
+    /// The raw type byte of the partition entry.
+    pub type_byte: u8,
+    /// The name of the partition type.
+    pub type_name: String,
+    /// The first sector of the partition.
+    pub start: u64,
+    /// The sector following the partition.
+    pub end: u64,
+    /// The size of the partition in bytes.
+    pub size: u64,
+    /// Whether the boot indicator of the entry is set.
+    pub bootable: bool,
+    /// The filesystem found in the partition, `None` if it isn't recognized or the partition
+    /// overlaps another one and was left unopened.
+    pub filesystem: Option<Filesystem>,
+}
 
 /// Represents a disk image with its partition table and volumes.
-#[derive(Getters)]
+#[derive(Getters, MutGetters)]
 pub struct Disk<T: TreeDisplay + LayoutDisplay, U: LayoutDisplay> {
     /// The open disk image file path.
     #[get = "pub"]
@@ -27,11 +85,52 @@ pub struct Disk<T: TreeDisplay + LayoutDisplay, U: LayoutDisplay> {
     #[get = "pub"]
     part_table: U,
     /// List of volumes found on the disk
-    #[get = "pub"]
+    #[getset(get = "pub", get_mut = "pub")]
     volumes: Vec<T>,
+    /// List of exFAT volumes found on the disk, in partition table order
+    #[get = "pub"]
+    exfat_volumes: Vec<ExFatVol>,
+    /// List of NTFS volumes found on the disk, in partition table order, only recognized
+    #[get = "pub"]
+    ntfs_volumes: Vec<NtfsVol>,
+    /// List of ext2/3/4 volumes found on the disk, in partition table order, only recognized
+    #[get = "pub"]
+    ext_volumes: Vec<ExtVol>,
+    /// List of ISO 9660 volumes found on the disk, starting at sector 0, only recognized
+    #[get = "pub"]
+    iso_volumes: Vec<IsoVol>,
     /// The size in bytes of a sector
     #[get = "pub"]
     sector_size: usize,
+    /// The source of the bytes of the disk image, shared with the volumes
+    #[get = "pub"]
+    image: Arc<ImageSource>,
+}
+
+impl<T: TreeDisplay + LayoutDisplay, U: LayoutDisplay> Disk<T, U> {
+    /// Returns a volume of the disk.
+    ///
+    /// # Parameters
+    /// - `idx`: The 0-based index of the volume, in partition table order.
+    ///
+    /// # Returns
+    /// - `Some(&T)`: The volume, parsed when the disk was opened.
+    /// - `None`: If the disk has fewer volumes.
+    pub fn volume(&self, idx: usize) -> Option<&T> {
+        self.volumes.get(idx)
+    }
+
+    /// Returns a volume of the disk, mutably.
+    ///
+    /// # Parameters
+    /// - `idx`: The 0-based index of the volume, in partition table order.
+    ///
+    /// # Returns
+    /// - `Some(&mut T)`: The volume, parsed when the disk was opened.
+    /// - `None`: If the disk has fewer volumes.
+    pub fn volume_mut(&mut self, idx: usize) -> Option<&mut T> {
+        self.volumes.get_mut(idx)
+    }
 }
 
 impl Disk<FATVol, Mbr> {
@@ -50,22 +149,144 @@ impl Disk<FATVol, Mbr> {
     /// - Returns `DiskError::Io` if the file cannot be opened or read
     /// - Returns `DiskError::ParsingError` if the MBR or a volume cannot be parsed
     pub fn from_file(path: &Path, sector_size: usize, validation: bool) -> Result<Self, DiskError> {
-        let mut f = File::options().read(true).write(true).open(path)?;
-        let f_len = f.metadata()?.len();
+        Self::from_file_with_options(path, sector_size, validation, &TableOptions::default())
+    }
 
-        let mbr = Mbr::from(&mut f, f_len, sector_size)?;
+    /// Opens a disk image file with custom partition table parsing, see [`Disk::from_file`].
+    ///
+    /// # Parameters
+    /// - `path`: Path to the disk image file
+    /// - `sector_size`: Size of each sector in bytes
+    /// - `validation`: Whether to validate volume structures (like Bpb)
+    /// - `options`: The geometry converting the CHS addresses of the partition entries whose
+    ///   LBA start is zero, as found in very old partition tables, and whether a table with
+    ///   overlapping partitions is opened without them rather than rejected
+    ///
+    /// # Returns
+    /// - `Ok(Disk)`: Successfully parsed disk with its partition table and volumes
+    /// - `Err(DiskError)`: If any error occurs during parsing
+    pub fn from_file_with_options(
+        path: &Path,
+        sector_size: usize,
+        validation: bool,
+        options: &TableOptions,
+    ) -> Result<Self, DiskError> {
+        let image = Arc::new(ImageSource::open(path)?);
+        let disk_len = image.len()?;
+        let mut mbr = match Mbr::from(&mut image.reader()?, disk_len, sector_size, options) {
+            Ok(mbr)
+                if mbr
+                    .pt_entries()
+                    .iter()
+                    .any(|entry| entry.pt_type().is_fat()) =>
+            {
+                mbr
+            }
+            // Sector 0 may be the boot sector of a volume rather than an MBR
+            _ if Self::is_bare_volume(&image, disk_len, sector_size) => {
+                Mbr::bare(disk_len, sector_size, PTType::LBAFat32)
+            }
+            _ if ExFatVol::is_exfat(&image, 0, sector_size) => {
+                Mbr::bare(disk_len, sector_size, PTType::Ifs)
+            }
+            // Optical media dumps have no MBR, hybrid ones keep theirs
+            Err(_) if IsoVol::is_iso(&image, 0, sector_size) => {
+                Mbr::bare(disk_len, sector_size, PTType::Iso9660)
+            }
+            result => result?,
+        };
 
         let mut vol = vec![];
+        let mut exfat_vol = vec![];
+        let mut ntfs_vol = vec![];
+        let mut ext_vol = vec![];
+        let mut claims = vec![];
         for (part_idx, pt_entry) in mbr.pt_entries().iter().enumerate() {
-            if let PTType::LBAFat32 = *pt_entry.pt_type() {
-                match FATVol::from_file(
-                    path,
+            // Type 0x07 is shared with NTFS, only the boot sector tells them apart
+            if matches!(pt_entry.pt_type(), PTType::Ifs)
+                && !mbr.is_overlapping(part_idx + 1)
+                && ExFatVol::is_exfat(&image, *pt_entry.lba_start(), sector_size)
+            {
+                match ExFatVol::from_backend(
+                    image.clone(),
+                    *pt_entry.lba_start(),
+                    *pt_entry.sector_cnt(),
+                    validation,
+                    sector_size,
+                ) {
+                    Ok(exfat) => {
+                        let tot_sec = *exfat.boot().volume_length()
+                            * exfat.boot().bytes_per_sector() as u64
+                            / sector_size as u64;
+                        claims.push((part_idx + 1, tot_sec));
+                        exfat_vol.push(exfat);
+                    }
+                    Err(error) => {
+                        return Err(DiskError::ParsingError(format!(
+                            "Error while reading partition #{part_idx}: {error}"
+                        )));
+                    }
+                }
+            }
+            // NTFS volumes also hide behind other types, e.g. recovery partitions (0x27)
+            if !pt_entry.pt_type().is_fat()
+                && !mbr.is_overlapping(part_idx + 1)
+                && NtfsVol::is_ntfs(&image, *pt_entry.lba_start(), sector_size)
+            {
+                match NtfsVol::from_backend(
+                    image.clone(),
+                    *pt_entry.lba_start(),
+                    *pt_entry.sector_cnt(),
+                    validation,
+                    sector_size,
+                ) {
+                    Ok(ntfs) => {
+                        // The backup boot sector follows the sectors of the volume
+                        let tot_sec = (*ntfs.boot().tot_sec() + 1)
+                            * *ntfs.boot().bytes_per_sec() as u64
+                            / sector_size as u64;
+                        claims.push((part_idx + 1, tot_sec));
+                        ntfs_vol.push(ntfs);
+                    }
+                    Err(error) => {
+                        return Err(DiskError::ParsingError(format!(
+                            "Error while reading partition #{part_idx}: {error}"
+                        )));
+                    }
+                }
+            }
+            if matches!(pt_entry.pt_type(), PTType::Linux)
+                && !mbr.is_overlapping(part_idx + 1)
+                && ExtVol::is_ext(&image, *pt_entry.lba_start(), sector_size)
+            {
+                match ExtVol::from_backend(
+                    image.clone(),
+                    *pt_entry.lba_start(),
+                    *pt_entry.sector_cnt(),
+                    validation,
+                    sector_size,
+                ) {
+                    Ok(ext) => {
+                        claims.push((part_idx + 1, ext.volume_len() / sector_size as u64));
+                        ext_vol.push(ext);
+                    }
+                    Err(error) => {
+                        return Err(DiskError::ParsingError(format!(
+                            "Error while reading partition #{part_idx}: {error}"
+                        )));
+                    }
+                }
+            }
+            if pt_entry.pt_type().is_fat() && !mbr.is_overlapping(part_idx + 1) {
+                match FATVol::from_backend(
+                    image.clone(),
                     *pt_entry.lba_start(),
                     *pt_entry.sector_cnt(),
                     validation,
                     sector_size,
                 ) {
                     Ok(fat_vol) => {
+                        claims.push((part_idx + 1, *fat_vol.info().total_sectors() as u64));
                         vol.push(fat_vol);
                     }
                     Err(error) => {
@@ -76,21 +297,150 @@ impl Disk<FATVol, Mbr> {
                 }
             }
         }
+        // The volume of a hybrid image spans the disk from sector 0, its partitions included
+        let mut iso_vol = vec![];
+        let bare_fat = mbr.is_bare() && !matches!(mbr.pt_entries()[0].pt_type(), PTType::Iso9660);
+        if !bare_fat && IsoVol::is_iso(&image, 0, sector_size) {
+            let sector_cnt = (disk_len / sector_size as u64).min(u32::MAX as u64) as u32;
+            match IsoVol::from_backend(image.clone(), 0, sector_cnt, validation, sector_size) {
+                Ok(iso) => iso_vol.push(iso),
+                Err(error) => {
+                    return Err(DiskError::ParsingError(format!(
+                        "Error while reading the ISO 9660 volume: {error}"
+                    )));
+                }
+            }
+        }
+        for (number, tot_sec) in claims {
+            mbr.add_volume_claim(number, tot_sec);
+        }
 
         let disk = Disk {
             file_path: path.to_path_buf(),
             part_table: mbr,
             volumes: vol,
+            exfat_volumes: exfat_vol,
+            ntfs_volumes: ntfs_vol,
+            ext_volumes: ext_vol,
+            iso_volumes: iso_vol,
             sector_size,
+            image,
         };
 
         Ok(disk)
     }
 
+    /// Checks whether sector 0 of an image holds a valid FAT boot sector.
+    fn is_bare_volume(image: &Arc<ImageSource>, disk_len: u64, sector_size: usize) -> bool {
+        let sector_cnt = (disk_len / sector_size as u64).min(u32::MAX as u64) as u32;
+        FATVol::from_backend(image.clone(), 0, sector_cnt, true, sector_size).is_ok()
+    }
+
+    /// Lists the partitions of the disk with their location and the filesystem found in them.
+    ///
+    /// # Returns
+    /// - The used entries of the partition table, in table order. A bare volume is listed as a
+    ///   single partition spanning the disk, with the type byte given to it (0x0C for FAT, 0x07
+    ///   for exFAT, 0x96 for ISO 9660) and no boot indicator.
+    pub fn partitions(&self) -> Vec<PartitionInfo> {
+        self.part_table
+            .pt_entries()
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                let start = *entry.lba_start() as u64;
+                let end = start + *entry.sector_cnt() as u64;
+                PartitionInfo {
+                    number: idx + 1,
+                    type_byte: entry.pt_type().to_byte(),
+                    type_name: entry.pt_type().to_string(),
+                    start,
+                    end,
+                    size: (end - start) * self.sector_size as u64,
+                    bootable: entry.is_bootable(),
+                    filesystem: self.filesystem_at(start),
+                }
+            })
+            .collect()
+    }
+
+    /// Sets or clears the boot indicator of a partition in the partition table.
+    ///
+    /// The other entries are left as they are, so marking a second partition bootable yields a
+    /// table with several active partitions, reported by the layout.
+    ///
+    /// # Parameters
+    /// - `number`: The 1-based number of the partition, as in the layout.
+    /// - `bootable`: Whether the partition is marked bootable (0x80) or not (0x00).
+    ///
+    /// # Returns
+    /// - `Ok(u8)`: The previous boot indicator of the entry.
+    /// - `Err(DiskError::NoPartitionTable)`: If the disk is a bare volume.
+    /// - `Err(DiskError::NoSuchPartition)`: If the table has no such partition.
+    /// - `Err(DiskError::Io)`: If the image cannot be written.
+    pub fn set_bootable(&mut self, number: usize, bootable: bool) -> Result<u8, DiskError> {
+        if self.is_bare() {
+            return Err(DiskError::NoPartitionTable);
+        }
+        let offset = self
+            .part_table
+            .entry_offset(number)
+            .ok_or(DiskError::NoSuchPartition(number))?;
+        let previous = *self.part_table.pt_entries()[number - 1].status();
+        let status = if bootable { BOOTABLE } else { 0 };
+
+        let mut disk_file = self.image.open_for_write()?;
+        write_at(&mut disk_file, offset, &[status])?;
+
+        // A dry run leaves the table as it is on disk
+        if !self.image.is_dry_run() {
+            self.part_table.set_status(number, status);
+        }
+        Ok(previous)
+    }
+
+    /// Returns the filesystem of the volume starting at a sector, if one was opened there.
+    fn filesystem_at(&self, sector: u64) -> Option<Filesystem> {
+        let offset = sector * self.sector_size as u64;
+        if let Some(vol) = self.volumes.iter().find(|vol| vol.start() as u64 == sector) {
+            return Some(Filesystem::Fat(*vol.info().fat_type()));
+        }
+        if self.exfat_volumes.iter().any(|vol| *vol.offset() == offset) {
+            return Some(Filesystem::ExFat);
+        }
+        if self.ntfs_volumes.iter().any(|vol| *vol.offset() == offset) {
+            return Some(Filesystem::Ntfs);
+        }
+        if let Some(vol) = self.ext_volumes.iter().find(|vol| *vol.offset() == offset) {
+            return Some(Filesystem::Ext(vol.superblock().version()));
+        }
+        self.iso_volumes
+            .iter()
+            .any(|vol| *vol.offset() == offset)
+            .then_some(Filesystem::Iso9660)
+    }
+
+    /// Checks whether the disk has no partition table, its image being a bare FAT, exFAT or ISO
+    /// 9660 volume.
+    pub fn is_bare(&self) -> bool {
+        self.part_table.is_bare()
+    }
+
+    /// Installs the sink receiving the events of every volume, or removes it with `None`.
+    ///
+    /// # Parameters
+    /// - `sink`: The sink, shared by the volumes.
+    pub fn set_event_sink(&self, sink: Option<Arc<dyn EventSink>>) {
+        for vol in self.volumes.iter() {
+            vol.set_event_sink(sink.clone());
+        }
+    }
+
     /// Prints a hierarchical layout of the disk structure.
     ///
     /// # Parameters
     /// - `indent`: Number of spaces to indent the layout
+    /// - `options`: The unit of the ranges and the regions to show
     ///
     /// # Returns
     /// - `Ok(())` if the layout is printed successfully
@@ -98,22 +448,74 @@ impl Disk<FATVol, Mbr> {
     ///
     /// The layout includes:
     /// - Partition table information
-    /// - Volume information for each partition
-    pub fn print_layout(&self, indent: u8) -> Result<(), std::fmt::Error> {
-        print!("{}", self.part_table.display_layout(indent)?);
+    /// - Volume information for each partition, exFAT, NTFS, ext then ISO 9660 volumes following
+    ///   the FAT ones
+    pub fn print_layout(&self, indent: u8, options: &LayoutOptions) -> Result<(), std::fmt::Error> {
+        print!("{}", self.layout(indent, options)?);
+        Ok(())
+    }
+
+    /// Formats the layout of the disk, as printed by [`Disk::print_layout`].
+    ///
+    /// # Parameters
+    /// - `indent`: The number of spaces to indent the output
+    /// - `options`: The unit of the ranges and the regions to show
+    ///
+    /// # Returns
+    /// - `Ok(String)` containing the layout
+    /// - `Err(std::fmt::Error)` if formatting fails
+    pub fn layout(&self, indent: u8, options: &LayoutOptions) -> Result<String, std::fmt::Error> {
+        let mut layout = self.part_table.display_layout(indent, options)?;
 
         for vol in self.volumes.iter() {
-            print!("\n{}", vol.display_layout(indent + 3)?);
+            layout.push('\n');
+            layout.push_str(&vol.display_layout(indent + 3, options)?);
+        }
+        for vol in self.exfat_volumes.iter() {
+            layout.push('\n');
+            layout.push_str(&vol.display_layout(indent + 3, options)?);
+        }
+        for vol in self.ntfs_volumes.iter() {
+            layout.push('\n');
+            layout.push_str(&vol.display_layout(indent + 3, options)?);
+        }
+        for vol in self.ext_volumes.iter() {
+            layout.push('\n');
+            layout.push_str(&vol.display_layout(indent + 3, options)?);
+        }
+        for vol in self.iso_volumes.iter() {
+            layout.push('\n');
+            layout.push_str(&vol.display_layout(indent + 3, options)?);
         }
 
-        Ok(())
+        Ok(layout)
     }
 
-    pub fn print_tree(&self) -> Result<(), TraitError> {
+    /// Writes the directory tree of every volume.
+    ///
+    /// # Parameters
+    /// - `out`: The sink the trees are written to.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the trees are written successfully
+    /// - `Err(TraitError)` if a tree cannot be read or written
+    pub fn write_tree<W: io::Write>(&self, out: &mut W) -> Result<(), TraitError> {
         for vol in self.volumes.iter() {
-            vol.display_tree()?;
+            vol.display_tree(out)?;
+        }
+        for vol in self.exfat_volumes.iter() {
+            vol.display_tree(out)?;
         }
 
         Ok(())
     }
+
+    /// Prints the directory tree of every volume to the standard output.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the trees are printed successfully
+    /// - `Err(TraitError)` if a tree cannot be read or printed
+    pub fn print_tree(&self) -> Result<(), TraitError> {
+        self.write_tree(&mut io::stdout().lock())
+    }
 }
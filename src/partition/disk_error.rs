@@ -6,6 +6,8 @@
 use std::io;
 use thiserror;
 
+use super::mbr::Overlap;
+
 /// Represents errors that can occur during MBR parsing.
 #[derive(thiserror::Error, Debug)]
 pub enum DiskError {
@@ -16,17 +18,33 @@ pub enum DiskError {
     #[error("Partition table is not sorted")]
     PartitionTableNotSorted,
     /// Indicates that two or more partitions have overlapping sectors.
-    #[error("Some partitions are overlapping")]
-    OverlappingPartitions,
+    /// Contains every pair of overlapping partitions.
+    #[error("Some partitions are overlapping: {}", list_overlaps(.0))]
+    OverlappingPartitions(Vec<Overlap>),
     /// Indicates that the boot signature is not valid.
     /// Contains the invalid signature value that was found.
     #[error("Invalid signature: {0}")]
     InvalidSignature(u16),
+    /// Indicates that the disk is a bare volume, without a partition table to edit.
+    #[error("The disk has no partition table")]
+    NoPartitionTable,
+    /// Indicates that the partition table has no partition of the given 1-based number.
+    #[error("No partition #{0} in the partition table")]
+    NoSuchPartition(usize),
     /// Parsing error
     #[error("Parsing error: {0}")]
     ParsingError(String),
 }
 
+/// Joins the descriptions of overlapping partitions.
+fn list_overlaps(overlaps: &[Overlap]) -> String {
+    overlaps
+        .iter()
+        .map(|overlap| overlap.to_string())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 /// Converts standard I/O errors into MBRError.
 impl From<io::Error> for DiskError {
     fn from(err: io::Error) -> Self {
@@ -0,0 +1,227 @@
+//! GUID Partition Table (GPT) parsing.
+//!
+//! Only the primary header, at LBA 1, and its partition entries are read. The GPT is parsed to
+//! show the second view of the disk given by a protective or hybrid MBR; its partitions aren't
+//! opened.
+
+use binread::io;
+use getset::Getters;
+use std::fmt::{self, Display};
+
+use crate::utils;
+
+/// The signature at the start of a GPT header.
+const SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// The smallest GPT header, the one of revision 1.0.
+const MIN_HEADER_SIZE: u32 = 92;
+
+/// The size of a partition entry defined by the UEFI specification, the smallest one.
+const MIN_ENTRY_SIZE: u32 = 128;
+
+/// The largest partition entry accepted, to bound the table read from a crafted header.
+const MAX_ENTRY_SIZE: u32 = 4096;
+
+/// The most partition entries accepted, to bound the table read from a crafted header.
+const MAX_ENTRY_CNT: u32 = 1024;
+
+/// Well-known partition type GUIDs and their names.
+const KNOWN_TYPES: [(&str, &str); 9] = [
+    ("C12A7328-F81F-11D2-BA4B-00A0C93EC93B", "EFI System"),
+    ("21686148-6449-6E6F-744E-656564454649", "BIOS Boot"),
+    ("E3C9E316-0B5C-4DB8-817D-F92DF00215AE", "MS Reserved"),
+    ("EBD0A0A2-B9E5-4433-87C0-68B6B72699C7", "Basic Data"),
+    ("DE94BBA4-06D1-4D40-A16A-BFD50179D6AC", "WinRE"),
+    ("0FC63DAF-8483-4772-8E79-3D69D8477DE4", "Linux"),
+    ("0657FD6D-A4AB-43C4-84E5-0933C84B4F4F", "Linux Swap"),
+    ("E6D6D379-F507-44C2-A23C-238F2A3DF928", "Linux LVM"),
+    ("48465300-0000-11AA-AA11-00306543ECAC", "Apple HFS+"),
+];
+
+/// A GUID, as stored on disk: its first three fields are little-endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Guid([u8; 16]);
+
+impl Guid {
+    /// Checks whether the GUID is all zeros, the type of an unused partition entry.
+    pub fn is_nil(&self) -> bool {
+        self.0 == [0; 16]
+    }
+}
+
+impl Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        let node: String = b[10..].iter().map(|byte| format!("{byte:02X}")).collect();
+        f.pad(&format!(
+            "{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{node}",
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9]
+        ))
+    }
+}
+
+/// A used entry of a GPT.
+#[derive(Debug, Getters)]
+#[get = "pub"]
+pub struct GptEntry {
+    /// The 1-based index of the entry in the table.
+    number: usize,
+    /// The type of the partition.
+    type_guid: Guid,
+    /// The first sector of the partition.
+    first_lba: u64,
+    /// The last sector of the partition, included.
+    last_lba: u64,
+    /// The name of the partition.
+    name: String,
+}
+
+impl GptEntry {
+    /// Parses a partition entry.
+    ///
+    /// # Parameters
+    /// - `number`: The 1-based index of the entry in the table.
+    /// - `raw`: The bytes of the entry.
+    ///
+    /// # Returns
+    /// - The entry, or `None` if it is unused, its type being the nil GUID.
+    fn parse(number: usize, raw: &[u8]) -> Option<GptEntry> {
+        let type_guid = guid_at(raw, 0);
+        if type_guid.is_nil() {
+            return None;
+        }
+        let name: Vec<u16> = raw[56..128]
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|unit| *unit != 0)
+            .collect();
+
+        Some(GptEntry {
+            number,
+            type_guid,
+            first_lba: u64_at(raw, 32),
+            last_lba: u64_at(raw, 40),
+            name: String::from_utf16_lossy(&name),
+        })
+    }
+
+    /// Returns the name of the partition type, or `Unknown` for an unlisted GUID.
+    pub fn type_name(&self) -> &'static str {
+        let guid = self.type_guid.to_string();
+        KNOWN_TYPES
+            .iter()
+            .find(|(known, _)| *known == guid)
+            .map_or("Unknown", |(_, name)| name)
+    }
+
+    /// Returns the number of sectors of the partition.
+    pub fn sector_cnt(&self) -> u64 {
+        self.last_lba
+            .saturating_add(1)
+            .saturating_sub(self.first_lba)
+    }
+}
+
+/// The primary GUID Partition Table of a disk.
+#[derive(Debug, Getters)]
+pub struct Gpt {
+    /// The identifier of the disk.
+    #[get = "pub"]
+    disk_guid: Guid,
+    /// The used partition entries, in table order.
+    #[get = "pub"]
+    entries: Vec<GptEntry>,
+    /// Whether the CRC32 of the header matches the one it stores.
+    #[get = "pub"]
+    header_crc_valid: bool,
+    /// Whether the CRC32 of the partition entries matches the one stored in the header.
+    #[get = "pub"]
+    entries_crc_valid: bool,
+}
+
+impl Gpt {
+    /// Reads the primary GPT of a disk, from LBA 1.
+    ///
+    /// # Parameters
+    /// - `file`: A mutable reference to the disk image.
+    /// - `disk_len`: The size of the disk in bytes.
+    /// - `sector_size`: The size in bytes of a sector.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Gpt))`: The GPT, its CRCs checked but not enforced.
+    /// - `Ok(None)`: If LBA 1 holds no GPT header, or the header locates its entries out of the
+    ///   disk.
+    /// - `Err(io::Error)`: If reading fails.
+    pub fn from<T: io::Read + io::Seek>(
+        file: &mut T,
+        disk_len: u64,
+        sector_size: usize,
+    ) -> io::Result<Option<Gpt>> {
+        let sector_size_u64 = sector_size as u64;
+        if disk_len < 2 * sector_size_u64 {
+            return Ok(None);
+        }
+        let header = utils::read_at(file, sector_size_u64, sector_size)?;
+        let header_size = utils::u32_at(&header, 12);
+        if !header.starts_with(SIGNATURE)
+            || !(MIN_HEADER_SIZE..=sector_size as u32).contains(&header_size)
+        {
+            return Ok(None);
+        }
+
+        let mut raw_header = header[..header_size as usize].to_vec();
+        raw_header[16..20].fill(0);
+        let header_crc_valid = crc32fast::hash(&raw_header) == utils::u32_at(&header, 16);
+
+        let entries_lba = u64_at(&header, 72);
+        let entry_cnt = utils::u32_at(&header, 80);
+        let entry_size = utils::u32_at(&header, 84);
+        if entry_cnt > MAX_ENTRY_CNT
+            || !(MIN_ENTRY_SIZE..=MAX_ENTRY_SIZE).contains(&entry_size)
+            || !entry_size.is_multiple_of(8)
+        {
+            return Ok(None);
+        }
+        let table_len = entry_cnt as usize * entry_size as usize;
+        let Some(table_end) = entries_lba
+            .checked_mul(sector_size_u64)
+            .and_then(|start| start.checked_add(table_len as u64))
+            .filter(|end| *end <= disk_len)
+        else {
+            return Ok(None);
+        };
+        let table = utils::read_at(file, table_end - table_len as u64, table_len)?;
+        let entries_crc_valid = crc32fast::hash(&table) == utils::u32_at(&header, 88);
+
+        let entries = table
+            .chunks_exact(entry_size as usize)
+            .enumerate()
+            .filter_map(|(idx, raw)| GptEntry::parse(idx + 1, raw))
+            .collect();
+
+        Ok(Some(Gpt {
+            disk_guid: guid_at(&header, 56),
+            entries,
+            header_crc_valid,
+            entries_crc_valid,
+        }))
+    }
+}
+
+/// Extracts a GUID from a buffer at a given offset.
+fn guid_at(buffer: &[u8], offset: usize) -> Guid {
+    Guid(
+        buffer[offset..offset + 16]
+            .try_into()
+            .expect("invalid slice"),
+    )
+}
+
+/// Extracts a 64-bit unsigned integer from a buffer at a given offset.
+fn u64_at(buffer: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(
+        buffer[offset..offset + 8]
+            .try_into()
+            .expect("invalid slice"),
+    )
+}
@@ -4,13 +4,16 @@
 //! - Structures and methods to interpret partition table entries
 //! - Validation of partition tables and boot signatures
 //! - Extraction of partition metadata from disk images
+//! - Conversion of the CHS addresses of old partition tables to LBA, see [`Geometry`]
+//! - The GPT view of the disk given by a protective or hybrid MBR, see [`Gpt`]
 
 use binread::io;
 use getset::Getters;
 use std::vec;
 
 use super::disk_error::DiskError;
-use crate::traits::LayoutDisplay;
+use super::gpt::Gpt;
+use crate::traits::{LayoutDisplay, LayoutOptions};
 use crate::utils;
 use std::fmt::Write;
 use std::fmt::{self, Display};
@@ -18,11 +21,168 @@ use std::fmt::{self, Display};
 /// The number of primary partitions supported by MBR.
 pub const PART_CNT: usize = 4;
 
+/// The boot indicator of the active partition; an inactive partition has 0x00.
+pub const BOOTABLE: u8 = 0x80;
+
+/// Default number of heads of the disk geometry, the one used by BIOS translation.
+pub const DEFAULT_HEADS: u8 = 255;
+
+/// Default number of sectors per track of the disk geometry, the one used by BIOS translation.
+pub const DEFAULT_SECTORS_PER_TRACK: u8 = 63;
+
+/// The geometry used to convert the CHS (cylinder, head, sector) addresses of partition entries
+/// to LBA.
+///
+/// Very old partition tables only fill the CHS fields of their entries, leaving the LBA start
+/// and sector count zero. Such entries are located from their CHS addresses, which depend on the
+/// geometry the disk was partitioned with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    /// The number of heads per cylinder, from 1 to 255.
+    pub heads: u8,
+    /// The number of sectors per track, from 1 to 63.
+    pub sectors_per_track: u8,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Self {
+            heads: DEFAULT_HEADS,
+            sectors_per_track: DEFAULT_SECTORS_PER_TRACK,
+        }
+    }
+}
+
+impl Display for Geometry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} heads, {} sectors/track",
+            self.heads, self.sectors_per_track
+        )
+    }
+}
+
+/// How the partition table of a disk is parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableOptions {
+    /// The geometry converting the CHS addresses of the entries whose LBA start is zero.
+    pub geometry: Geometry,
+    /// Whether a table with overlapping partitions is accepted, the overlapping partitions
+    /// being left unopened, rather than rejected.
+    pub lenient: bool,
+}
+
+/// A range of sectors claimed by two partitions at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    /// The 1-based number of the partition whose claim reaches into the other one.
+    pub first: usize,
+    /// The 1-based number of the other partition.
+    pub second: usize,
+    /// The first sector claimed by both.
+    pub start: u64,
+    /// The number of sectors claimed by both.
+    pub sectors: u64,
+    /// Whether the claim of `first` is the size declared by the boot sector of its volume,
+    /// rather than its partition entry.
+    pub by_volume: bool,
+}
+
+impl Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (first, second, start, sectors) = (self.first, self.second, self.start, self.sectors);
+        match self.by_volume {
+            true => write!(
+                f,
+                "the volume of partition #{first} spans {sectors} sectors of partition #{second} \
+                 from sector {start}, per its boot sector"
+            ),
+            false => write!(
+                f,
+                "partitions #{first} and #{second} overlap by {sectors} sectors from sector {start}"
+            ),
+        }
+    }
+}
+
+/// An inconsistency of the boot indicators of a partition table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BootAnomaly {
+    /// Several partitions are marked bootable, encapsulating their 1-based numbers. The boot code
+    /// of a standard MBR starts the first one, or refuses to boot.
+    MultipleBootable(Vec<usize>),
+    /// The boot indicator of a partition is neither 0x00 nor 0x80: (1-based number, raw value).
+    /// Most boot code rejects such a table.
+    InvalidIndicator(usize, u8),
+}
+
+impl Display for BootAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BootAnomaly::MultipleBootable(numbers) => {
+                let numbers: Vec<String> = numbers.iter().map(|nb| format!("#{nb}")).collect();
+                write!(
+                    f,
+                    "partitions {} are marked bootable, while a single one should be",
+                    numbers.join(", ")
+                )
+            }
+            BootAnomaly::InvalidIndicator(number, status) => write!(
+                f,
+                "partition #{number} has an invalid boot indicator 0x{status:02X}, neither 0x00 nor 0x80"
+            ),
+        }
+    }
+}
+
+impl Geometry {
+    /// Converts the 3-byte CHS address of a partition entry to LBA.
+    ///
+    /// # Parameters
+    /// - `chs`: The address as stored in the entry: head, then sector in the low 6 bits and the
+    ///   high 2 bits of the cylinder, then the low 8 bits of the cylinder.
+    ///
+    /// # Returns
+    /// - `Some(lba)`: The address of the sector.
+    /// - `None`: If the address is zero or doesn't fit the geometry.
+    pub fn chs_to_lba(&self, chs: [u8; 3]) -> Option<u32> {
+        let head = u32::from(chs[0]);
+        let sector = u32::from(chs[1] & 0x3F);
+        let cylinder = (u32::from(chs[1] & 0xC0) << 2) | u32::from(chs[2]);
+        let heads = u32::from(self.heads);
+        let sectors_per_track = u32::from(self.sectors_per_track);
+
+        // Sectors are numbered from 1, so a zero address is an unused field
+        if chs == [0; 3] || sector == 0 || sector > sectors_per_track || head >= heads {
+            return None;
+        }
+        Some((cylinder * heads + head) * sectors_per_track + sector - 1)
+    }
+}
+
 /// Represents the type of a partition table entry.
 #[derive(Debug)]
 pub enum PTType {
     /// Logical Block Addressing (LBA) FAT32 partition type.
     LBAFat32,
+    /// FAT32 partition type of old tables, addressed with CHS (0x0B).
+    ChsFat32,
+    /// Logical Block Addressing (LBA) FAT16 partition type (0x0E).
+    LBAFat16,
+    /// FAT16 partition type of old tables, addressed with CHS (0x04 below 32 MiB, 0x06 above).
+    ChsFat16(u8),
+    /// FAT12 partition type (0x01), found on small embedded partitions.
+    Fat12,
+    /// Installable file system partition type (0x07), holding an exFAT or an NTFS volume.
+    Ifs,
+    /// Linux native partition type (0x83), holding an ext2, ext3 or ext4 volume among others.
+    Linux,
+    /// ISO 9660 partition type (0x96), also given to the optical media dumps without partition
+    /// table.
+    Iso9660,
+    /// Protective entry of a disk partitioned with a GUID Partition Table (GPT).
+    GptProtective,
     /// Unsupported partition type, encapsulating the raw type byte.
     Unsupported(u8),
 }
@@ -31,6 +191,14 @@ impl Display for PTType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PTType::LBAFat32 => write!(f, "LBA FAT32"),
+            PTType::ChsFat32 => write!(f, "CHS FAT32"),
+            PTType::LBAFat16 => write!(f, "LBA FAT16"),
+            PTType::ChsFat16(b) => write!(f, "CHS FAT16 (0x{b:02X})"),
+            PTType::Fat12 => write!(f, "FAT12"),
+            PTType::Ifs => write!(f, "exFAT/NTFS"),
+            PTType::Linux => write!(f, "Linux"),
+            PTType::Iso9660 => write!(f, "ISO 9660"),
+            PTType::GptProtective => write!(f, "GPT Protective"),
             PTType::Unsupported(b) => write!(f, "Unsupported: 0x{b:02X}"),
         }
     }
@@ -44,13 +212,58 @@ impl PTType {
     ///
     /// # Returns
     /// - `PTType::LBAFat32` if the byte matches the FAT32 LBA type (0x0C).
+    /// - `PTType::ChsFat32` if the byte matches the FAT32 CHS type (0x0B).
+    /// - `PTType::LBAFat16` if the byte matches the FAT16 LBA type (0x0E).
+    /// - `PTType::ChsFat16(byte)` if the byte matches a FAT16 CHS type (0x04 or 0x06).
+    /// - `PTType::Fat12` if the byte matches the FAT12 type (0x01).
+    /// - `PTType::Ifs` if the byte matches the installable file system type (0x07).
+    /// - `PTType::Linux` if the byte matches the Linux native type (0x83).
+    /// - `PTType::Iso9660` if the byte matches the ISO 9660 type (0x96).
+    /// - `PTType::GptProtective` if the byte matches the GPT protective type (0xEE).
     /// - `PTType::Unsupported(byte)` for any other value.
     fn from_byte(byte: u8) -> Self {
         match byte {
             0x0C => PTType::LBAFat32,
+            0x0B => PTType::ChsFat32,
+            0x0E => PTType::LBAFat16,
+            0x04 | 0x06 => PTType::ChsFat16(byte),
+            0x01 => PTType::Fat12,
+            0x07 => PTType::Ifs,
+            0x83 => PTType::Linux,
+            0x96 => PTType::Iso9660,
+            0xEE => PTType::GptProtective,
             _ => PTType::Unsupported(byte),
         }
     }
+
+    /// Returns the raw type byte of the partition, as stored in its partition table entry.
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            PTType::LBAFat32 => 0x0C,
+            PTType::ChsFat32 => 0x0B,
+            PTType::LBAFat16 => 0x0E,
+            PTType::ChsFat16(byte) => *byte,
+            PTType::Fat12 => 0x01,
+            PTType::Ifs => 0x07,
+            PTType::Linux => 0x83,
+            PTType::Iso9660 => 0x96,
+            PTType::GptProtective => 0xEE,
+            PTType::Unsupported(byte) => *byte,
+        }
+    }
+
+    /// Checks whether the partition holds a FAT32 volume, whatever its addressing.
+    pub fn is_fat32(&self) -> bool {
+        matches!(self, PTType::LBAFat32 | PTType::ChsFat32)
+    }
+
+    /// Checks whether the partition holds a FAT volume supported by [`FATVol`]: FAT12, FAT16 or
+    /// FAT32.
+    ///
+    /// [`FATVol`]: crate::FATVol
+    pub fn is_fat(&self) -> bool {
+        self.is_fat32() || matches!(self, PTType::LBAFat16 | PTType::ChsFat16(_) | PTType::Fat12)
+    }
 }
 
 /// Represents a single partition table entry.
@@ -65,6 +278,55 @@ pub struct PTEntry {
     /// The number of sectors in the partition.
     #[get = "pub(super)"]
     sector_cnt: u32,
+    /// Whether the start and size were converted from the CHS fields, the LBA ones being zero.
+    #[get = "pub(super)"]
+    from_chs: bool,
+    /// The boot indicator of the entry: 0x80 for the active partition, 0x00 otherwise.
+    #[get = "pub(super)"]
+    status: u8,
+}
+
+impl PTEntry {
+    /// Parses a partition table entry.
+    ///
+    /// # Parameters
+    /// - `raw`: The 16 bytes of the entry.
+    /// - `geometry`: The geometry converting the CHS addresses, used if the LBA start is zero.
+    ///
+    /// # Returns
+    /// - The entry. A used entry with a zero LBA start and valid CHS addresses is located from
+    ///   them, and sized from them too if its sector count is zero.
+    fn parse(raw: &[u8], geometry: &Geometry) -> PTEntry {
+        let mut entry = PTEntry {
+            pt_type: PTType::from_byte(utils::u8_at(raw, 0x04)),
+            lba_start: utils::u32_at(raw, 0x08),
+            sector_cnt: utils::u32_at(raw, 0x0C),
+            from_chs: false,
+            status: utils::u8_at(raw, 0x00),
+        };
+        if entry.lba_start != 0 || matches!(entry.pt_type, PTType::Unsupported(0)) {
+            return entry;
+        }
+
+        let chs = |offset: usize| [raw[offset], raw[offset + 1], raw[offset + 2]];
+        let Some(start) = geometry.chs_to_lba(chs(0x01)) else {
+            return entry;
+        };
+        if entry.sector_cnt == 0 {
+            match geometry.chs_to_lba(chs(0x05)) {
+                Some(end) if end >= start => entry.sector_cnt = end - start + 1,
+                _ => return entry,
+            }
+        }
+        entry.lba_start = start;
+        entry.from_chs = true;
+        entry
+    }
+
+    /// Checks whether the entry is marked bootable, its boot indicator being 0x80.
+    pub(super) fn is_bootable(&self) -> bool {
+        self.status == BOOTABLE
+    }
 }
 
 /// Represents the boot signature of a Master Boot Record (MBR).
@@ -114,6 +376,14 @@ pub struct Mbr {
     boot_signature: BootSignature,
     /// The total number of sectors on the disk.
     sector_cnt: u64,
+    /// The size in bytes of a sector.
+    sector_size: usize,
+    /// Whether the disk has no partition table, sector 0 being the boot sector of a volume.
+    bare: bool,
+    /// The ranges of sectors claimed by two partitions, or by a volume and another partition.
+    overlaps: Vec<Overlap>,
+    /// The primary GPT, read if the MBR holds a protective entry and found at LBA 1.
+    gpt: Option<Gpt>,
 }
 
 impl Mbr {
@@ -121,6 +391,10 @@ impl Mbr {
     ///
     /// # Parameters
     /// - `file`: A mutable reference to a `File` object representing the disk image.
+    /// - `disk_len`: The size of the disk in bytes.
+    /// - `sector_size`: The size in bytes of a sector.
+    /// - `options`: The geometry of the CHS addresses, and whether overlapping partitions are
+    ///   accepted.
     ///
     /// # Returns
     /// - `Ok(MBR)` if the MBR is successfully parsed.
@@ -129,26 +403,74 @@ impl Mbr {
         file: &mut T,
         disk_len: u64,
         sector_size: usize,
+        options: &TableOptions,
     ) -> Result<Mbr, DiskError> {
         let mut buffer = vec![0; sector_size];
         utils::read_sector(file, 0, sector_size, &mut buffer)?;
 
         let pt_entries: [PTEntry; PART_CNT] = core::array::from_fn(|i| {
             let offset = 446 + i * 16;
-            PTEntry {
-                pt_type: PTType::from_byte(utils::u8_at(&buffer, offset + 0x04)),
-                lba_start: utils::u32_at(&buffer, offset + 0x08),
-                sector_cnt: utils::u32_at(&buffer, offset + 0x0C),
-            }
+            PTEntry::parse(&buffer[offset..offset + 16], &options.geometry)
         });
 
-        let mbr = Mbr {
+        let mut mbr = Mbr {
             pt_entries,
             boot_signature: BootSignature::from_u16(utils::u16_at(&buffer, 510)),
             sector_cnt: disk_len / sector_size as u64,
+            sector_size,
+            bare: false,
+            overlaps: vec![],
+            gpt: None,
+        };
+        mbr.overlaps = mbr.partition_overlaps();
+        if mbr.has_protective_entry() {
+            mbr.gpt = Gpt::from(file, disk_len, sector_size)?;
+        }
+
+        mbr.validate(options.lenient)
+    }
+
+    /// Describes a disk without a partition table, holding a single volume from sector 0.
+    ///
+    /// # Parameters
+    /// - `disk_len`: The size of the disk in bytes.
+    /// - `sector_size`: The size in bytes of a sector.
+    /// - `pt_type`: The type given to the entry of the volume, [`PTType::Ifs`] for an exFAT
+    ///   volume, [`PTType::Iso9660`] for an ISO 9660 volume and a FAT type otherwise.
+    ///
+    /// # Returns
+    /// - A table with a single entry spanning the whole disk.
+    pub fn bare(disk_len: u64, sector_size: usize, pt_type: PTType) -> Mbr {
+        let sector_cnt = disk_len / sector_size as u64;
+        let volume = PTEntry {
+            pt_type,
+            lba_start: 0,
+            sector_cnt: sector_cnt.min(u32::MAX as u64) as u32,
+            from_chs: false,
+            status: 0,
+        };
+        let empty = || PTEntry {
+            pt_type: PTType::Unsupported(0),
+            lba_start: 0,
+            sector_cnt: 0,
+            from_chs: false,
+            status: 0,
         };
 
-        mbr.validate()
+        Mbr {
+            pt_entries: [volume, empty(), empty(), empty()],
+            boot_signature: BootSignature::Mbr(0xAA55),
+            sector_cnt,
+            sector_size,
+            bare: true,
+            overlaps: vec![],
+            gpt: None,
+        }
+    }
+
+    /// Checks whether the disk has no partition table, see [`Mbr::bare`].
+    pub fn is_bare(&self) -> bool {
+        self.bare
     }
 
     /// Returns a vector of references to non-empty partition table entries.
@@ -165,15 +487,297 @@ impl Mbr {
             .collect()
     }
 
+    /// Returns the ranges of sectors claimed twice: by two partition entries, found when the
+    /// table is parsed, or by the boot sector of a volume and another entry, found when the
+    /// volumes are opened.
+    pub fn overlaps(&self) -> &[Overlap] {
+        &self.overlaps
+    }
+
+    /// Checks whether a partition overlaps another one, and is therefore left unopened.
+    ///
+    /// # Parameters
+    /// - `number`: The 1-based number of the partition, as in the layout.
+    pub fn is_overlapping(&self, number: usize) -> bool {
+        self.overlaps.iter().any(|overlap| {
+            !overlap.by_volume && (overlap.first == number || overlap.second == number)
+        })
+    }
+
+    /// Records the sectors of other partitions claimed by the boot sector of a volume.
+    ///
+    /// # Parameters
+    /// - `number`: The 1-based number of the partition of the volume.
+    /// - `tot_sec`: The number of sectors of the volume declared by its boot sector.
+    pub(super) fn add_volume_claim(&mut self, number: usize, tot_sec: u64) {
+        let entries = self.pt_entries();
+        let Some(entry) = entries.get(number - 1) else {
+            return;
+        };
+        let start = entry.lba_start as u64;
+        let overlaps: Vec<Overlap> = entries
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| idx + 1 != number)
+            .filter_map(|(idx, other)| {
+                let other_start = other.lba_start as u64;
+                let shared_start = start.max(other_start);
+                let shared_end = (start + tot_sec).min(other_start + other.sector_cnt as u64);
+                (shared_start < shared_end).then(|| Overlap {
+                    first: number,
+                    second: idx + 1,
+                    start: shared_start,
+                    sectors: shared_end - shared_start,
+                    by_volume: true,
+                })
+            })
+            .collect();
+        self.overlaps.extend(overlaps);
+    }
+
+    /// Returns the inconsistencies of the boot indicators of the partitions: several partitions
+    /// marked bootable, and indicators other than 0x00 and 0x80.
+    pub fn boot_anomalies(&self) -> Vec<BootAnomaly> {
+        let entries = self.pt_entries();
+        let bootable: Vec<usize> = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.is_bootable())
+            .map(|(idx, _)| idx + 1)
+            .collect();
+        let mut anomalies = vec![];
+        if bootable.len() > 1 {
+            anomalies.push(BootAnomaly::MultipleBootable(bootable));
+        }
+        anomalies.extend(
+            entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| !matches!(entry.status, 0 | BOOTABLE))
+                .map(|(idx, entry)| BootAnomaly::InvalidIndicator(idx + 1, entry.status)),
+        );
+        anomalies
+    }
+
+    /// Returns the offset in sector 0 of the entry of a partition.
+    ///
+    /// # Parameters
+    /// - `number`: The 1-based number of the partition, as in the layout.
+    ///
+    /// # Returns
+    /// - `Some(offset)`: The offset in bytes of the 16-byte entry, its boot indicator first.
+    /// - `None`: If the table has no such partition, or the disk has no partition table.
+    pub(super) fn entry_offset(&self, number: usize) -> Option<u64> {
+        if self.bare || number == 0 {
+            return None;
+        }
+        let idx = self
+            .pt_entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.sector_cnt != 0)
+            .nth(number - 1)?
+            .0;
+        Some(446 + 16 * idx as u64)
+    }
+
+    /// Changes the boot indicator of a partition, once written to the image.
+    ///
+    /// # Parameters
+    /// - `number`: The 1-based number of the partition, as in the layout.
+    /// - `status`: The new boot indicator.
+    pub(super) fn set_status(&mut self, number: usize, status: u8) {
+        if let Some(entry) = self
+            .pt_entries
+            .iter_mut()
+            .filter(|entry| entry.sector_cnt != 0)
+            .nth(number.wrapping_sub(1))
+        {
+            entry.status = status;
+        }
+    }
+
+    /// Returns the gap between the MBR and the first partition.
+    ///
+    /// # Returns
+    /// - `Some((first, count))`: The first sector of the gap (always 1) and its number of sectors.
+    /// - `None`: If there is no partition, or the first partition starts right after the MBR.
+    pub fn post_mbr_gap(&self) -> Option<(u64, u64)> {
+        let first_start = self
+            .pt_entries()
+            .iter()
+            .map(|entry| entry.lba_start as u64)
+            .filter(|start| *start > 0)
+            .min()?;
+        (first_start > 1).then_some((1, first_start - 1))
+    }
+
+    /// Returns the primary GPT of the disk, read if the MBR holds a protective entry.
+    ///
+    /// # Returns
+    /// - `None` if the MBR has no protective entry, or LBA 1 holds no valid GPT header.
+    pub fn gpt(&self) -> Option<&Gpt> {
+        self.gpt.as_ref()
+    }
+
+    /// Checks whether an entry of the MBR is a GPT protective entry.
+    fn has_protective_entry(&self) -> bool {
+        self.pt_entries()
+            .iter()
+            .any(|entry| matches!(entry.pt_type, PTType::GptProtective))
+    }
+
+    /// Checks whether the disk is partitioned with a GPT, the MBR only holding protective entries.
+    pub fn is_protective(&self) -> bool {
+        let entries = self.pt_entries();
+        !entries.is_empty()
+            && entries
+                .iter()
+                .all(|entry| matches!(entry.pt_type, PTType::GptProtective))
+    }
+
+    /// Checks whether the MBR is a hybrid MBR.
+    ///
+    /// A hybrid MBR holds a GPT protective entry along with regular entries, so that legacy
+    /// systems see some of the GPT partitions. Both tables describe the disk and can disagree:
+    /// a partition listed in only one of them is hidden from the systems reading the other.
+    ///
+    /// # Returns
+    /// - `true` if the MBR holds both a protective entry and a regular entry.
+    pub fn is_hybrid(&self) -> bool {
+        self.has_protective_entry() && !self.is_protective()
+    }
+
+    /// Returns the partitions described by a single table of a hybrid MBR.
+    ///
+    /// # Returns
+    /// - The 1-based numbers of the regular MBR entries missing from the GPT, hidden from
+    ///   GPT-aware systems, and the numbers of the GPT entries missing from the MBR, hidden from
+    ///   legacy systems. Two entries match if they span the same sectors.
+    pub fn hybrid_mismatches(&self) -> (Vec<usize>, Vec<usize>) {
+        let Some(gpt) = &self.gpt else {
+            return (vec![], vec![]);
+        };
+        let mbr_ranges: Vec<(usize, u64, u64)> = self
+            .pt_entries()
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !matches!(entry.pt_type, PTType::GptProtective))
+            .map(|(i, entry)| (i + 1, entry.lba_start as u64, entry.sector_cnt as u64))
+            .collect();
+        let gpt_ranges: Vec<(usize, u64, u64)> = gpt
+            .entries()
+            .iter()
+            .map(|entry| (*entry.number(), *entry.first_lba(), entry.sector_cnt()))
+            .collect();
+
+        let missing = |ranges: &[(usize, u64, u64)], others: &[(usize, u64, u64)]| {
+            ranges
+                .iter()
+                .filter(|(_, start, cnt)| !others.iter().any(|(_, s, c)| s == start && c == cnt))
+                .map(|(number, _, _)| *number)
+                .collect()
+        };
+        (
+            missing(&mbr_ranges, &gpt_ranges),
+            missing(&gpt_ranges, &mbr_ranges),
+        )
+    }
+
+    /// Writes the partitions of the GPT, and the partitions listed in a single table of a hybrid
+    /// MBR.
+    fn display_gpt(
+        &self,
+        out: &mut String,
+        gpt: &Gpt,
+        indent: &str,
+        options: &LayoutOptions,
+    ) -> fmt::Result {
+        let sector_size = self.sector_size as u64;
+        writeln!(out, "{}┌{:─^68}┐", indent, " GUID Partition Table Layout ")?;
+        writeln!(
+            out,
+            "{}├{:<30}{:>38}┤",
+            indent,
+            "Disk GUID",
+            gpt.disk_guid()
+        )?;
+        writeln!(
+            out,
+            "{}├{:^12}┬{:^12}┬{:^12}┬{:^12}┬{:^16}┤",
+            indent, "Region", "Start", "End", "Length", "Description"
+        )?;
+        writeln!(
+            out,
+            "{}├{:─<12}┼{:─<12}┼{:─<12}┼{:─<12}┼{:─<16}┤",
+            indent, "", "", "", "", ""
+        )?;
+        for entry in gpt.entries() {
+            let start = *entry.first_lba();
+            let [start, end, len] =
+                options.fmt_range(start, start.saturating_add(entry.sector_cnt()), sector_size);
+            writeln!(
+                out,
+                "{}│{:^12}│{:>12}│{:>12}│{:>12}│{:^16}│",
+                indent,
+                format!("GPT #{}", entry.number()),
+                start,
+                end,
+                len,
+                entry.type_name()
+            )?;
+        }
+        writeln!(
+            out,
+            "{}└{:─<12}┴{:─<12}┴{:─<12}┴{:─<12}┴{:─<16}┘",
+            indent, "", "", "", "", ""
+        )?;
+
+        if !gpt.header_crc_valid() {
+            writeln!(
+                out,
+                "{indent}Warning: the CRC32 of the GPT header doesn't match."
+            )?;
+        }
+        if !gpt.entries_crc_valid() {
+            writeln!(
+                out,
+                "{indent}Warning: the CRC32 of the GPT entries doesn't match."
+            )?;
+        }
+        if self.is_hybrid() {
+            let (mbr_only, gpt_only) = self.hybrid_mismatches();
+            for number in mbr_only {
+                writeln!(
+                    out,
+                    "{indent}Warning: MBR partition #{number} isn't in the GPT, hidden from GPT-aware systems."
+                )?;
+            }
+            for number in gpt_only {
+                writeln!(
+                    out,
+                    "{indent}Warning: GPT partition #{number} isn't in the MBR, hidden from legacy systems."
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     /// Validates the MBR by checking the partition table and boot signature.
     ///
+    /// # Parameters
+    /// - `lenient`: Whether overlapping partitions are accepted.
+    ///
     /// # Returns
     /// - `Ok(Self)` if the MBR is valid.
     /// - `Err(DiskError)` if any validation step fails.
-    fn validate(self) -> Result<Self, DiskError> {
-        self.check_partition_table_sorted()?
-            .check_partitions_non_overlapping()?
-            .check_signature()
+    fn validate(self, lenient: bool) -> Result<Self, DiskError> {
+        let mbr = self.check_partition_table_sorted()?;
+        let mbr = match lenient {
+            true => mbr,
+            false => mbr.check_partitions_non_overlapping()?,
+        };
+        mbr.check_signature()
     }
 
     /// Checks if the boot signature is valid.
@@ -208,17 +812,37 @@ impl Mbr {
     ///
     /// # Returns
     /// - `Ok(Self)` if the entries do not overlap.
-    /// - `Err(DiskError::OverlappingPartitions)` if any entries overlap.
+    /// - `Err(DiskError::OverlappingPartitions)` with every overlap if any entries overlap.
     fn check_partitions_non_overlapping(self) -> Result<Self, DiskError> {
-        match self
-            .pt_entries()
-            .windows(2)
-            .any(|pair| pair[0].lba_start + pair[0].sector_cnt > pair[1].lba_start)
-        {
-            true => Err(DiskError::OverlappingPartitions),
-            false => Ok(self),
+        match self.overlaps.is_empty() {
+            true => Ok(self),
+            false => Err(DiskError::OverlappingPartitions(self.overlaps)),
         }
     }
+
+    /// Returns the ranges of sectors claimed by two partition entries, comparing every pair of
+    /// entries rather than only neighbours so that an entry nested in a distant one is found.
+    fn partition_overlaps(&self) -> Vec<Overlap> {
+        let entries = self.pt_entries();
+        let mut overlaps = vec![];
+        for (i, first) in entries.iter().enumerate() {
+            for (j, second) in entries.iter().enumerate().skip(i + 1) {
+                let start = (first.lba_start as u64).max(second.lba_start as u64);
+                let end = (first.lba_start as u64 + first.sector_cnt as u64)
+                    .min(second.lba_start as u64 + second.sector_cnt as u64);
+                if start < end {
+                    overlaps.push(Overlap {
+                        first: i + 1,
+                        second: j + 1,
+                        start,
+                        sectors: end - start,
+                        by_volume: false,
+                    });
+                }
+            }
+        }
+        overlaps
+    }
 }
 
 /// Prints the layout of the disk based on the provided Master Boot Record (MBR).
@@ -227,77 +851,153 @@ impl Mbr {
 /// - `mbr`: A reference to an `MBR` instance representing the parsed Master Boot Record.
 ///
 /// # Behavior
-/// - Prints the MBR sector range.
-/// - Iterates through the partition table entries and prints their sector ranges.
+/// - Prints the disk size and the boot signature.
+/// - Iterates through the partition table entries and prints their ranges in the selected unit,
+///   marking the bootable ones with `*`.
+/// - Warns about overlapping partitions and inconsistent boot indicators.
+/// - With `options.all`, also prints the partition table sector and the unallocated gaps.
 impl LayoutDisplay for Mbr {
-    fn display_layout(&self, indent: u8) -> Result<String, std::fmt::Error> {
+    fn display_layout(
+        &self,
+        indent: u8,
+        options: &LayoutOptions,
+    ) -> Result<String, std::fmt::Error> {
         let mut out = String::from("");
         let indent = " ".repeat(indent.into());
+        let sector_size = self.sector_size as u64;
 
         let mut last_end = 0;
         let disk_end = self.sector_cnt;
 
-        writeln!(out, "{}┌{:─^55}┐", indent, " Master Boot Record Layout ")?;
-        writeln!(out, "{}├{:<45}{:>10}┤", indent, "Disk Size", disk_end,)?;
+        let [_, _, disk_size] = options.fmt_range(0, disk_end, sector_size);
+        if self.bare {
+            writeln!(out, "{}┌{:─^68}┐", indent, " No Partition Table ")?;
+            writeln!(out, "{}├{:<56}{:>12}┤", indent, "Disk Size", disk_size)?;
+            let kind = match self.pt_entries[0].pt_type {
+                PTType::Ifs => "exFAT",
+                PTType::Iso9660 => "ISO 9660",
+                _ => "FAT",
+            };
+            writeln!(
+                out,
+                "{}├{:<68}┤",
+                indent,
+                format!("Bare {kind} volume starting at sector 0")
+            )?;
+            writeln!(out, "{}└{:─<68}┘", indent, "")?;
+            return Ok(out);
+        }
+        writeln!(out, "{}┌{:─^68}┐", indent, " Master Boot Record Layout ")?;
+        writeln!(out, "{}├{:<56}{:>12}┤", indent, "Disk Size", disk_size)?;
         writeln!(
             out,
-            "{}├{:<45}{:>10}┤",
+            "{}├{:<56}{:>12}┤",
             indent,
             "Boot Signature",
-            format!("{:>10}", self.boot_signature)
+            format!("{:>12}", self.boot_signature)
         )?;
-        writeln!(out, "{}├{:─^55}┤", indent, "")?;
+        writeln!(out, "{}├{:─^68}┤", indent, "")?;
 
         writeln!(
             out,
-            "{}├{:^12}┬{:^12}┬{:^12}┬{:^16}┤",
-            indent, "Region", "Start", "End", "Description"
+            "{}├{:^12}┬{:^12}┬{:^12}┬{:^12}┬{:^16}┤",
+            indent, "Region", "Start", "End", "Length", "Description"
         )?;
         writeln!(
             out,
-            "{}├{:─<12}┼{:─<12}┼{:─<12}┼{:─<16}┤",
-            indent, "", "", "", ""
+            "{}├{:─<12}┼{:─<12}┼{:─<12}┼{:─<12}┼{:─<16}┤",
+            indent, "", "", "", "", ""
         )?;
 
+        let row = |out: &mut String, region: &str, start: u64, end: u64, desc: &str| {
+            let [start, end, len] = options.fmt_range(start, end, sector_size);
+            writeln!(
+                out,
+                "{}│{:^12}│{:>12}│{:>12}│{:>12}│{:^16}│",
+                indent, region, start, end, len, desc
+            )
+        };
+
+        if options.all {
+            row(&mut out, "Meta", 0, 1, "Partition Table")?;
+            last_end = 1;
+        }
+
         for (i, entry) in self.pt_entries().iter().enumerate() {
             let start = u64::from(*entry.lba_start());
             let end = start + u64::from(*entry.sector_cnt());
 
-            if start > last_end {
-                writeln!(
-                    out,
-                    "{}│{:^12}│{:>12}│{:>12}│{:^16}│",
-                    indent, "", last_end, start, "Unallocated"
-                )?;
+            if options.all && start > last_end {
+                row(&mut out, "", last_end, start, "Unallocated")?;
             }
 
-            writeln!(
-                out,
-                "{}│{:^12}│{:>12}│{:>12}│{:^16}│",
-                indent,
-                format!("Part #{}", i + 1),
-                start,
-                end,
-                format!("{:}", entry.pt_type())
-            )?;
+            let mut desc = entry.pt_type().to_string();
+            if entry.from_chs {
+                desc.push_str(" (CHS)");
+            }
+            if self.is_overlapping(i + 1) {
+                desc = String::from("Overlapping");
+            }
+            let region = match entry.is_bootable() {
+                true => format!("Part #{} *", i + 1),
+                false => format!("Part #{}", i + 1),
+            };
+            row(&mut out, &region, start, end, &desc)?;
 
-            last_end = end;
+            last_end = last_end.max(end);
         }
 
-        if last_end < disk_end {
-            writeln!(
-                out,
-                "{}│{:^12}│{:>12}│{:>12}│{:^16}│",
-                indent, "", last_end, disk_end, "Unallocated"
-            )?;
+        if options.all && last_end < disk_end {
+            row(&mut out, "", last_end, disk_end, "Unallocated")?;
         }
 
         writeln!(
             out,
-            "{}└{:─<12}┴{:─<12}┴{:─<12}┴{:─<16}┘",
-            indent, "", "", "", ""
+            "{}└{:─<12}┴{:─<12}┴{:─<12}┴{:─<12}┴{:─<16}┘",
+            indent, "", "", "", "", ""
         )?;
 
+        if self.pt_entries().iter().any(|entry| entry.is_bootable()) {
+            writeln!(out, "{indent}* bootable (active) partition")?;
+        }
+
+        for overlap in self.overlaps.iter() {
+            match overlap.by_volume {
+                true => writeln!(out, "{indent}Warning: {overlap}.")?,
+                false => writeln!(out, "{indent}Warning: {overlap}; neither is opened.")?,
+            }
+        }
+        for anomaly in self.boot_anomalies() {
+            writeln!(out, "{indent}Warning: {anomaly}.")?;
+        }
+
+        if self.is_hybrid() {
+            writeln!(
+                out,
+                "{indent}Warning: hybrid MBR. UEFI firmware and GPT-aware systems honor the GPT and ignore"
+            )?;
+            writeln!(
+                out,
+                "{indent}the regular entries above, BIOS firmware and legacy systems honor these entries only."
+            )?;
+            writeln!(
+                out,
+                "{indent}A partition listed in a single table is hidden from the systems reading the other."
+            )?;
+        } else if self.is_protective() {
+            writeln!(
+                out,
+                "{indent}Protective MBR: the partitions are described by a GPT, whose volumes aren't opened."
+            )?;
+        }
+
+        if self.has_protective_entry() {
+            match &self.gpt {
+                Some(gpt) => self.display_gpt(&mut out, gpt, &indent, options)?,
+                None => writeln!(out, "{indent}Warning: no valid GPT header at LBA 1.")?,
+            }
+        }
+
         Ok(out)
     }
 }
@@ -1,3 +1,4 @@
 pub(crate) mod disk;
-mod disk_error;
-mod mbr;
+pub(crate) mod disk_error;
+pub(crate) mod gpt;
+pub(crate) mod mbr;
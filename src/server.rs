@@ -0,0 +1,477 @@
+//! Read-only HTTP/JSON service exposing the analyses of a disk image.
+//!
+//! The service lets a web UI or remote automation drive the engine without linking the crate.
+//! Requests are handled by a pool of worker threads sharing the parsed disk and the sector caches
+//! of its volumes. No endpoint writes to the image. Volumes are numbered from 1, as in the
+//! interactive CLI.
+//!
+//! Endpoints:
+//! - `GET /layout`: The layout of the disk, its partitions with the filesystem found in each, and
+//!   the details of each volume.
+//! - `GET /volumes/{n}/tree`: The directory tree, deleted entries included. Live entries list
+//!   the byte runs of their content, as in search results.
+//! - `GET /volumes/{n}/files/{path}`: The content of a file.
+//! - `GET /volumes/{n}/search?name=<text>`: The files whose path contains a text, ignoring case,
+//!   with the byte runs of their content: the absolute offset in the image and the length of each
+//!   run of consecutive clusters, so that the content can be read from the raw image.
+//! - `GET /volumes/{n}/search?pattern=<regex>`: The strings of the volume matching a pattern.
+//! - `GET /volumes/{n}/slack`: The content of the volume slack and of the slack of every file.
+//! - `GET /volumes/{n}/findings`: The hidden payloads, the data in file slack and the deleted
+//!   entries, each with its provenance: the partition, the byte ranges and the clusters holding it.
+//!
+//! Errors are returned as `{"error": "<message>"}`.
+
+use regex::Regex;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::analysis::Provenance;
+use crate::analysis::payload::detect_payloads;
+use crate::analysis::recover::deleted_entries;
+use crate::analysis::slack::scan_file_slack;
+use crate::analysis::strings::{StringsOptions, volume_strings};
+use crate::filesystem::fat::FATVol;
+use crate::filesystem::fat_error::FATError;
+use crate::partition::disk::Disk;
+use crate::partition::mbr::Mbr;
+use crate::traits::LayoutOptions;
+
+/// The answer to a request.
+#[derive(Debug)]
+pub struct Reply {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The MIME type of the body.
+    pub content_type: &'static str,
+    /// The content of the reply.
+    pub body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(value: Value) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json",
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: json!({ "error": message.into() }).to_string().into_bytes(),
+        }
+    }
+
+    /// Maps an error of the file system to a status code.
+    fn fat_error(err: FATError) -> Self {
+        let status = match err {
+            FATError::FileNotFound | FATError::InvalidFilenameError(_) => 404,
+            FATError::IOError(_) => 500,
+            _ => 422,
+        };
+        Self::error(status, err.to_string())
+    }
+}
+
+/// Serves the analyses of a disk until the listener fails.
+///
+/// # Parameters
+/// - `disk`: The disk to expose.
+/// - `addr`: The address to listen on, e.g. `127.0.0.1:8080`.
+/// - `workers`: The number of requests handled in parallel, at least 1.
+///
+/// # Returns
+/// - `Err(io::Error)`: If the address cannot be bound or the listener fails.
+pub fn serve(disk: &Disk<FATVol, Mbr>, addr: &str, workers: usize) -> io::Result<()> {
+    let server = Server::http(addr).map_err(io::Error::other)?;
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    for request in server.incoming_requests() {
+                        // A client gone before its reply is written must not stop the worker
+                        if let Err(err) = respond(disk, request) {
+                            log::warn!("Can't send a reply: {err}");
+                        }
+                    }
+                })
+            })
+            .collect();
+        handles.into_iter().try_for_each(|handle| {
+            handle
+                .join()
+                .map_err(|_| io::Error::other("worker panicked"))
+        })
+    })
+}
+
+fn respond(disk: &Disk<FATVol, Mbr>, request: Request) -> io::Result<()> {
+    let reply = match request.method() {
+        Method::Get => handle(disk, request.url()),
+        _ => Reply::error(405, "Only GET requests are supported"),
+    };
+
+    let content_type =
+        Header::from_bytes("Content-Type", reply.content_type).expect("static header is valid");
+    request.respond(
+        Response::from_data(reply.body)
+            .with_status_code(reply.status)
+            .with_header(content_type),
+    )
+}
+
+/// Answers a `GET` request.
+///
+/// # Parameters
+/// - `disk`: The disk to analyze.
+/// - `url`: The path and query of the request, e.g. `/volumes/1/search?name=txt`.
+///
+/// # Returns
+/// - The reply, an error reply if the request is invalid or the analysis fails.
+pub fn handle(disk: &Disk<FATVol, Mbr>, url: &str) -> Reply {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match segments.as_slice() {
+        ["layout"] => layout(disk),
+        ["volumes", vol_nb, rest @ ..] => {
+            let Some(vol) = vol_nb
+                .parse::<usize>()
+                .ok()
+                .and_then(|nb| nb.checked_sub(1))
+                .and_then(|idx| disk.volume(idx))
+            else {
+                return Reply::error(404, format!("No volume {vol_nb}"));
+            };
+
+            match rest {
+                ["tree"] => tree(vol),
+                ["files", file_path @ ..] if !file_path.is_empty() => {
+                    match percent_decode(&file_path.join("/"), false) {
+                        Some(file_path) => file(vol, &file_path),
+                        None => Reply::error(400, "Invalid percent-encoding in the path"),
+                    }
+                }
+                ["search"] => search(vol, query),
+                ["slack"] => slack(vol),
+                ["findings"] => findings(vol),
+                _ => Reply::error(404, format!("Unknown endpoint {path}")),
+            }
+        }
+        _ => Reply::error(404, format!("Unknown endpoint {path}")),
+    }
+}
+
+fn layout(disk: &Disk<FATVol, Mbr>) -> Reply {
+    let text = match disk.layout(0, &LayoutOptions::default()) {
+        Ok(text) => text,
+        Err(err) => return Reply::error(500, err.to_string()),
+    };
+
+    let mut volumes = vec![];
+    for (i, vol) in disk.volumes().iter().enumerate() {
+        let fsstat = match vol.fsstat() {
+            Ok(fsstat) => fsstat,
+            Err(err) => return Reply::fat_error(err),
+        };
+        volumes.push(json!({
+            "number": i + 1,
+            "start_sector": vol.start(),
+            "fat_type": fsstat.fat_type(),
+            "label": fsstat.label(),
+            "fat_start": fsstat.fat_start(),
+            "data_start": fsstat.data_start(),
+            "data_end": fsstat.data_end(),
+            "cluster_size": fsstat.cluster_size(),
+            "cluster_count": fsstat.cluster_count(),
+            "diverging_fats": fsstat.diverging_fats(),
+        }));
+    }
+
+    let partitions: Vec<Value> = disk
+        .partitions()
+        .iter()
+        .map(|part| {
+            json!({
+                "number": part.number,
+                "type_byte": part.type_byte,
+                "type": part.type_name,
+                "start_sector": part.start,
+                "end_sector": part.end,
+                "size": part.size,
+                "bootable": part.bootable,
+                "filesystem": part.filesystem.map(|fs| fs.to_string()),
+            })
+        })
+        .collect();
+
+    Reply::json(json!({
+        "sector_size": disk.sector_size(),
+        "layout": text,
+        "partitions": partitions,
+        "volumes": volumes,
+    }))
+}
+
+fn tree(vol: &FATVol) -> Reply {
+    let mut visited = HashSet::new();
+    match vol
+        .root_cluster()
+        .and_then(|root| tree_rec(vol, root, 0, &mut visited))
+    {
+        Ok(children) => Reply::json(json!({ "name": "/", "children": children })),
+        Err(err) => Reply::fat_error(err),
+    }
+}
+
+/// Lists a directory and its live subdirectories.
+///
+/// # Parameters
+/// - `depth`: The depth of the directory below the root.
+/// - `visited`: The directories already listed, so that a looping tree is listed once.
+fn tree_rec(
+    vol: &FATVol,
+    cluster: u32,
+    depth: usize,
+    visited: &mut HashSet<u32>,
+) -> Result<Vec<Value>, FATError> {
+    vol.check_depth(depth)?;
+    visited.insert(cluster);
+
+    let mut nodes = vec![];
+    for entry in vol.list_dir(cluster)? {
+        if entry.is_long_name() || entry.is_volume_id() || entry.is_dot() {
+            continue;
+        }
+
+        let mut node = json!({
+            "name": entry.short_name(),
+            "long_name": entry.long_name(),
+            "dir": entry.is_dir(),
+            "deleted": entry.is_deleted(),
+            "cluster": entry.cluster_number(),
+            "size": entry.file_size(),
+        });
+        // A broken chain leaves the runs of the entry out, rather than failing the whole tree
+        if !entry.is_deleted()
+            && let Ok(runs) = vol.file_byte_runs(&entry)
+        {
+            node["byte_runs"] = byte_runs(&runs);
+        }
+        if entry.is_regular_dir()
+            && !entry.is_deleted()
+            && !visited.contains(&entry.cluster_number())
+        {
+            node["children"] = json!(tree_rec(vol, entry.cluster_number(), depth + 1, visited)?);
+        }
+        nodes.push(node);
+    }
+
+    Ok(nodes)
+}
+
+fn file(vol: &FATVol, file_path: &str) -> Reply {
+    match vol.read_file(Path::new(file_path)) {
+        Ok(body) => Reply {
+            status: 200,
+            content_type: "application/octet-stream",
+            body,
+        },
+        Err(err) => Reply::fat_error(err),
+    }
+}
+
+fn search(vol: &FATVol, query: &str) -> Reply {
+    let params: Vec<(&str, Option<String>)> = query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key, percent_decode(value, true)))
+        .collect();
+
+    match params.as_slice() {
+        [("name", Some(name))] => {
+            let name = name.to_lowercase();
+            let found = vol.walk_files().and_then(|files| {
+                files
+                    .iter()
+                    .filter(|(path, _)| path.to_string_lossy().to_lowercase().contains(&name))
+                    .map(|(path, entry)| {
+                        Ok(json!({
+                            "path": path,
+                            "cluster": entry.cluster_number(),
+                            "size": entry.file_size(),
+                            "byte_runs": byte_runs(&vol.file_byte_runs(entry)?),
+                        }))
+                    })
+                    .collect::<Result<Vec<_>, FATError>>()
+            });
+            match found {
+                Ok(files) => Reply::json(json!(files)),
+                Err(err) => Reply::fat_error(err),
+            }
+        }
+        [("pattern", Some(pattern))] => {
+            let filter = match Regex::new(pattern) {
+                Ok(filter) => filter,
+                Err(err) => return Reply::error(400, err.to_string()),
+            };
+            let options = StringsOptions {
+                filter: Some(filter),
+                ..Default::default()
+            };
+            match volume_strings(vol, &options) {
+                Ok(strings) => Reply::json(json!(
+                    strings
+                        .iter()
+                        .map(|string| json!({
+                            "region": string.class().to_string(),
+                            "offset": string.offset(),
+                            "encoding": string.encoding().to_string(),
+                            "text": string.text(),
+                        }))
+                        .collect::<Vec<_>>()
+                )),
+                Err(err) => Reply::fat_error(err),
+            }
+        }
+        _ => Reply::error(400, "Expected a single 'name' or 'pattern' parameter"),
+    }
+}
+
+fn slack(vol: &FATVol) -> Reply {
+    let dump = |offset: u64, len: u64| -> Result<Value, FATError> {
+        Ok(json!({
+            "offset": offset,
+            "len": len,
+            "data": hex(&vol.read_bytes(offset, len as usize)?),
+        }))
+    };
+
+    let slack = || -> Result<Value, FATError> {
+        let (offset, len) = vol.volume_slack_extent();
+        let volume = dump(offset, len)?;
+
+        let mut files = vec![];
+        for (path, entry) in vol.walk_files()? {
+            let extents = vol
+                .file_slack_extents(&entry)?
+                .into_iter()
+                .map(|(offset, len)| dump(offset, len))
+                .collect::<Result<Vec<_>, _>>()?;
+            files.push(json!({ "path": path, "extents": extents }));
+        }
+
+        Ok(json!({ "volume": volume, "files": files }))
+    };
+
+    match slack() {
+        Ok(value) => Reply::json(value),
+        Err(err) => Reply::fat_error(err),
+    }
+}
+
+fn findings(vol: &FATVol) -> Reply {
+    let findings = || -> Result<Value, FATError> {
+        let payloads: Vec<Value> = detect_payloads(vol)?
+            .iter()
+            .map(|candidate| {
+                json!({
+                    "region": candidate.class().to_string(),
+                    "kind": candidate.kind().to_string(),
+                    "preview": candidate.preview(),
+                    "provenance": provenance(candidate.provenance()),
+                })
+            })
+            .collect();
+        let slack: Vec<Value> = scan_file_slack(vol)?
+            .iter()
+            .map(|finding| {
+                json!({
+                    "path": finding.path(),
+                    "hidden_bytes": finding.hidden_bytes(),
+                    "provenance": provenance(finding.provenance()),
+                })
+            })
+            .collect();
+        let deleted: Vec<Value> = deleted_entries(vol)?
+            .iter()
+            .map(|scored| {
+                let reuse = scored.reuse().as_ref();
+                json!({
+                    "item": scored.item().to_string(),
+                    "confidence": scored.confidence(),
+                    "recoverable": reuse.map(|reuse| reuse.recoverable()),
+                    "reused_by": reuse.map(|reuse| {
+                        reuse
+                            .owners()
+                            .iter()
+                            .map(|(path, clusters)| json!({ "path": path, "clusters": clusters }))
+                            .collect::<Vec<_>>()
+                    }),
+                    "provenance": provenance(scored.provenance()),
+                })
+            })
+            .collect();
+
+        Ok(json!({ "payloads": payloads, "slack": slack, "deleted": deleted }))
+    };
+
+    match findings() {
+        Ok(value) => Reply::json(value),
+        Err(err) => Reply::fat_error(err),
+    }
+}
+
+/// Encodes the provenance of a finding.
+fn provenance(provenance: &Provenance) -> Value {
+    json!({
+        "partition_start_sector": provenance.partition_start(),
+        "extents": byte_runs(provenance.extents()),
+        "clusters": provenance.clusters(),
+    })
+}
+
+/// Encodes byte runs: the absolute offset and length of each range.
+fn byte_runs(runs: &[(u64, u64)]) -> Value {
+    json!(
+        runs.iter()
+            .map(|(offset, len)| json!({ "offset": offset, "len": len }))
+            .collect::<Vec<_>>()
+    )
+}
+
+/// Encodes bytes as lowercase hexadecimal.
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Decodes a percent-encoded URL component.
+///
+/// # Parameters
+/// - `value`: The encoded component.
+/// - `form`: Whether the component is a form-encoded query value, in which `+` stands for a
+///   space. In a path, `+` is a literal character.
+///
+/// # Returns
+/// - `None` if an escape is malformed or the result isn't UTF-8.
+fn percent_decode(value: &str, form: bool) -> Option<String> {
+    let mut bytes = vec![];
+    let mut chars = value.bytes();
+    while let Some(b) = chars.next() {
+        match b {
+            b'%' => {
+                let hex = [chars.next()?, chars.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b'+' if form => bytes.push(b' '),
+            _ => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
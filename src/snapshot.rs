@@ -0,0 +1,252 @@
+//! Snapshots of working disk images.
+//!
+//! Building a hiding scenario is trial and error: write a file, wipe some slack, check what the
+//! analyses find, and start over. A snapshot saves the state of a raw image under a name, in the
+//! `<image>.snapshots` directory next to it, and restoring it rolls the image back.
+//!
+//! Both operations are sized by the data of the images rather than their length. A snapshot is a
+//! sparse copy holding only the data extents of the image (see [`data_extents`]), and a restore
+//! only rewrites the blocks that differ from the snapshot, so iterating on a mostly empty lab image
+//! of several gigabytes takes a fraction of a second.
+//!
+//! Compressed images are read-only and cannot be snapshotted.
+
+use getset::Getters;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::image::{ImageSource, data_extents};
+use crate::utils::human_size;
+
+/// Size of the blocks copied and compared.
+const BLOCK_SIZE: u64 = 1 << 20;
+
+/// A snapshot of an image.
+#[derive(Debug, Getters)]
+pub struct Snapshot {
+    /// The name of the snapshot.
+    #[get = "pub"]
+    name: String,
+    /// The path of the snapshot file.
+    #[get = "pub"]
+    path: PathBuf,
+    /// The length of the image when the snapshot was taken.
+    #[get = "pub"]
+    len: u64,
+    /// The number of bytes of data stored by the snapshot, holes excluded.
+    #[get = "pub"]
+    stored: u64,
+}
+
+impl Snapshot {
+    /// Reads the description of a snapshot file.
+    fn from_path(name: &str, path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let stored = data_extents(&file, 0, len)?
+            .iter()
+            .map(|(_, len)| len)
+            .sum();
+        Ok(Self {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            len,
+            stored,
+        })
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<20} {:>12} image, {:>12} stored",
+            self.name,
+            human_size(self.len),
+            human_size(self.stored)
+        )
+    }
+}
+
+/// Returns the directory holding the snapshots of an image.
+///
+/// # Parameters
+/// - `image`: The path of the image.
+///
+/// # Returns
+/// The `<image>.snapshots` directory next to the image.
+pub fn snapshot_dir(image: &Path) -> PathBuf {
+    let mut dir = image.as_os_str().to_owned();
+    dir.push(".snapshots");
+    PathBuf::from(dir)
+}
+
+/// Returns the path of a raw image, or an error for a compressed one.
+fn raw_path(image: &ImageSource) -> io::Result<&Path> {
+    match image {
+        ImageSource::Raw(path, _, _) => Ok(path),
+        #[cfg(feature = "compressed")]
+        ImageSource::Compressed(..) => Err(io::Error::new(
+            io::ErrorKind::ReadOnlyFilesystem,
+            "Compressed images are read-only and cannot be snapshotted",
+        )),
+    }
+}
+
+/// Returns the path of a named snapshot of an image, rejecting names that aren't file names.
+fn snapshot_path(image: &Path, name: &str) -> io::Result<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid snapshot name '{name}'"),
+        ));
+    }
+    Ok(snapshot_dir(image).join(format!("{name}.img")))
+}
+
+/// Saves the current state of an image under a name, replacing any snapshot of the same name.
+///
+/// # Parameters
+/// - `image`: The image to snapshot.
+/// - `name`: The name of the snapshot.
+///
+/// # Returns
+/// - `Ok(Snapshot)`: The snapshot taken.
+/// - `Err(io::Error)`: If the image is compressed, the name is invalid, or the image or the
+///   snapshot cannot be read or written.
+pub fn create(image: &ImageSource, name: &str) -> io::Result<Snapshot> {
+    let image = raw_path(image)?;
+    let path = snapshot_path(image, name)?;
+    fs::create_dir_all(snapshot_dir(image))?;
+
+    // Written aside, so that a failed snapshot doesn't destroy the previous one
+    let partial = path.with_extension("partial");
+    let mut src = File::open(image)?;
+    let len = src.metadata()?.len();
+    let mut dst = File::create(&partial)?;
+    let mut buffer = vec![0; BLOCK_SIZE as usize];
+    for (offset, extent_len) in data_extents(&src, 0, len)? {
+        src.seek(SeekFrom::Start(offset))?;
+        dst.seek(SeekFrom::Start(offset))?;
+        let mut left = extent_len;
+        while left > 0 {
+            let chunk = &mut buffer[..left.min(BLOCK_SIZE) as usize];
+            src.read_exact(chunk)?;
+            dst.write_all(chunk)?;
+            left -= chunk.len() as u64;
+        }
+    }
+    // Trailing holes are not written, the length restores them
+    dst.set_len(len)?;
+    dst.sync_all()?;
+    fs::rename(&partial, &path)?;
+
+    Snapshot::from_path(name, &path)
+}
+
+/// Rolls an image back to a snapshot, rewriting only the blocks that differ.
+///
+/// Volumes of the image cache its sectors: clear their caches, or reopen the image, afterwards.
+///
+/// # Parameters
+/// - `image`: The image to roll back.
+/// - `name`: The name of the snapshot.
+///
+/// # Returns
+/// - `Ok(u64)`: The number of bytes rewritten.
+/// - `Err(io::Error)`: If the image is compressed, the snapshot doesn't exist, or the image or
+///   the snapshot cannot be read or written.
+pub fn restore(image: &ImageSource, name: &str) -> io::Result<u64> {
+    let mut dst = image.open_for_write()?;
+    let image = raw_path(image)?;
+    let mut src = File::open(snapshot_path(image, name)?).map_err(|err| {
+        io::Error::new(err.kind(), format!("Cannot open snapshot '{name}': {err}"))
+    })?;
+    let len = src.metadata()?.len();
+
+    // Outside the data of both files, both read as zeros
+    let mut extents = data_extents(&src, 0, len)?;
+    extents.extend(data_extents(
+        dst.file(),
+        0,
+        len.min(dst.file().metadata()?.len()),
+    )?);
+    let mut rewritten = 0;
+    let mut expected = vec![0; BLOCK_SIZE as usize];
+    let mut current = vec![0; BLOCK_SIZE as usize];
+    for (offset, extent_len) in merge_extents(extents) {
+        let mut pos = offset;
+        while pos < offset + extent_len {
+            let size = (offset + extent_len - pos).min(BLOCK_SIZE) as usize;
+            src.seek(SeekFrom::Start(pos))?;
+            src.read_exact(&mut expected[..size])?;
+            dst.seek(SeekFrom::Start(pos))?;
+            let read = read_up_to(&mut dst, &mut current[..size])?;
+            if read < size || expected[..size] != current[..size] {
+                dst.seek(SeekFrom::Start(pos))?;
+                dst.write_all(&expected[..size])?;
+                rewritten += size as u64;
+            }
+            pos += size as u64;
+        }
+    }
+    dst.set_len(len)?;
+    dst.sync_all()?;
+    Ok(rewritten)
+}
+
+/// Lists the snapshots of an image, sorted by name.
+///
+/// # Parameters
+/// - `image`: The image whose snapshots are listed.
+///
+/// # Returns
+/// - `Ok(Vec<Snapshot>)`: The snapshots of the image, empty if none was taken.
+/// - `Err(io::Error)`: If the image is compressed, or the snapshot directory cannot be read.
+pub fn list(image: &ImageSource) -> io::Result<Vec<Snapshot>> {
+    let entries = match fs::read_dir(snapshot_dir(raw_path(image)?)) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+    let mut snapshots = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "img")
+            && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+        {
+            snapshots.push(Snapshot::from_path(name, &path)?);
+        }
+    }
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(snapshots)
+}
+
+/// Sorts extents and merges the overlapping or adjacent ones.
+fn merge_extents(mut extents: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    extents.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = vec![];
+    for (offset, len) in extents {
+        match merged.last_mut() {
+            Some((last, last_len)) if offset <= *last + *last_len => {
+                *last_len = (*last_len).max(offset + len - *last)
+            }
+            _ => merged.push((offset, len)),
+        }
+    }
+    merged
+}
+
+/// Reads as many bytes as available into `buffer`, stopping at the end of the file.
+fn read_up_to<R: Read>(file: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match file.read(&mut buffer[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
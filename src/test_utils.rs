@@ -0,0 +1,410 @@
+//! In-memory FAT32 image builder for tests.
+//!
+//! [`FatImageBuilder`] constructs small, valid MBR-partitioned FAT32 disk images in a `Vec<u8>`,
+//! with the requested files, directories, deleted entries and fragmented files, so tests don't need
+//! to ship binary fixtures.
+//!
+//! This module is only available with the `test-utils` feature.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const FAT_EOC: u32 = 0x0FFFFFFF;
+const DIR_ENTRY_SIZE: usize = 32;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_ARCHIVE: u8 = 0x20;
+
+/// A node of the directory tree to build.
+enum Node {
+    Dir {
+        name: String,
+        children: Vec<Node>,
+    },
+    File {
+        name: String,
+        content: Vec<u8>,
+        deleted: bool,
+        fragmented: bool,
+    },
+}
+
+impl Node {
+    fn name(&self) -> &str {
+        match self {
+            Node::Dir { name, .. } | Node::File { name, .. } => name,
+        }
+    }
+}
+
+/// Builder of MBR-partitioned FAT32 disk images.
+///
+/// # Example
+/// ```ignore
+/// let img = FatImageBuilder::new()
+///     .file("DOCS/A.TXT", b"hello")
+///     .deleted_file("GONE.TXT", b"bye")
+///     .build();
+/// ```
+pub struct FatImageBuilder {
+    bytes_per_sec: u16,
+    sec_per_clus: u8,
+    cluster_count: u32,
+    partition_start: u32,
+    volume_slack: u32,
+    label: String,
+    root: Vec<Node>,
+}
+
+impl Default for FatImageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FatImageBuilder {
+    /// Number of reserved sectors preceding the first FAT.
+    const RSVD_SEC_CNT: u16 = 32;
+    /// Number of FATs on the volume.
+    const NUM_FAT: u8 = 2;
+    /// Sector of the FSINFO structure, relative to the volume start.
+    const FS_INFO_SEC: u16 = 1;
+    /// Sector of the backup boot sector, relative to the volume start.
+    const BK_BOOT_SEC: u16 = 6;
+
+    /// Creates a builder for the smallest valid FAT32 volume (65525 clusters of one 512-byte sector).
+    pub fn new() -> Self {
+        Self {
+            bytes_per_sec: 512,
+            sec_per_clus: 1,
+            cluster_count: 65525,
+            partition_start: 1,
+            volume_slack: 0,
+            label: String::from("NO NAME"),
+            root: vec![],
+        }
+    }
+
+    /// Sets the number of sectors per cluster.
+    pub fn sec_per_clus(mut self, sec_per_clus: u8) -> Self {
+        self.sec_per_clus = sec_per_clus;
+        self
+    }
+
+    /// Sets the number of data clusters. Values below 65525 produce a volume detected as FAT12/16.
+    pub fn cluster_count(mut self, cluster_count: u32) -> Self {
+        self.cluster_count = cluster_count;
+        self
+    }
+
+    /// Sets the first sector of the partition.
+    pub fn partition_start(mut self, sector: u32) -> Self {
+        self.partition_start = sector;
+        self
+    }
+
+    /// Sets the number of sectors of volume slack after the data region.
+    pub fn volume_slack(mut self, sectors: u32) -> Self {
+        self.volume_slack = sectors;
+        self
+    }
+
+    /// Sets the volume label, stored in the Bpb and as a volume ID entry of the root directory.
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = label.to_string();
+        self
+    }
+
+    /// Adds a directory, creating its parents if needed.
+    pub fn dir(mut self, path: &str) -> Self {
+        Self::dir_mut(&mut self.root, path);
+        self
+    }
+
+    /// Adds a file stored in contiguous clusters, creating its parent directories if needed.
+    pub fn file(self, path: &str, content: &[u8]) -> Self {
+        self.add_file(path, content, false, false)
+    }
+
+    /// Adds a file whose clusters are separated by one free cluster each.
+    pub fn fragmented_file(self, path: &str, content: &[u8]) -> Self {
+        self.add_file(path, content, false, true)
+    }
+
+    /// Adds a deleted file: its content is on disk but its entry is marked as deleted and its
+    /// clusters are free in the FAT.
+    pub fn deleted_file(self, path: &str, content: &[u8]) -> Self {
+        self.add_file(path, content, true, false)
+    }
+
+    /// Writes the built image to a file.
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.build())
+    }
+
+    /// Builds the disk image.
+    ///
+    /// # Panics
+    /// Panics if the files and directories don't fit in the volume.
+    pub fn build(&self) -> Vec<u8> {
+        let bps = self.bytes_per_sec as usize;
+        let fat_sz = ((self.cluster_count as usize + 2) * 4).div_ceil(bps) as u32;
+        let tot_sec = Self::RSVD_SEC_CNT as u32
+            + Self::NUM_FAT as u32 * fat_sz
+            + self.cluster_count * self.sec_per_clus as u32;
+        let part_sec_cnt = tot_sec + self.volume_slack;
+
+        let mut img = vec![0u8; (self.partition_start + part_sec_cnt) as usize * bps];
+        self.write_mbr(&mut img, part_sec_cnt);
+
+        let vol_start = self.partition_start as usize * bps;
+        let boot_sector = self.boot_sector(fat_sz, tot_sec);
+        img[vol_start..vol_start + bps].copy_from_slice(&boot_sector);
+        let bk_start = vol_start + Self::BK_BOOT_SEC as usize * bps;
+        img[bk_start..bk_start + bps].copy_from_slice(&boot_sector);
+
+        let mut layout = Layout {
+            fat: vec![0; self.cluster_count as usize + 2],
+            next_free: 2,
+            data_start: vol_start
+                + (Self::RSVD_SEC_CNT as usize + Self::NUM_FAT as usize * fat_sz as usize) * bps,
+            cluster_size: bps * self.sec_per_clus as usize,
+        };
+        layout.fat[0] = 0x0FFFFFF8;
+        layout.fat[1] = FAT_EOC;
+
+        let mut root_entries = vec![];
+        if !self.label.is_empty() {
+            root_entries.push(dir_entry(&pad_label(&self.label), ATTR_VOLUME_ID, 0, 0));
+        }
+        let root_cluster = layout.write_dir(&mut img, &self.root, root_entries, None);
+        assert_eq!(
+            root_cluster, 2,
+            "the root directory must start at cluster 2"
+        );
+
+        let free_cnt = layout.fat.iter().skip(2).filter(|v| **v == 0).count() as u32;
+        let fs_info = vol_start + Self::FS_INFO_SEC as usize * bps;
+        img[fs_info..fs_info + 4].copy_from_slice(&0x41615252u32.to_le_bytes());
+        img[fs_info + 484..fs_info + 488].copy_from_slice(&0x61417272u32.to_le_bytes());
+        img[fs_info + 488..fs_info + 492].copy_from_slice(&free_cnt.to_le_bytes());
+        img[fs_info + 492..fs_info + 496].copy_from_slice(&layout.next_free.to_le_bytes());
+        img[fs_info + 508..fs_info + 512].copy_from_slice(&0xAA550000u32.to_le_bytes());
+
+        let fat_bytes: Vec<u8> = layout.fat.iter().flat_map(|v| v.to_le_bytes()).collect();
+        for i in 0..Self::NUM_FAT as usize {
+            let off = vol_start + (Self::RSVD_SEC_CNT as usize + i * fat_sz as usize) * bps;
+            img[off..off + fat_bytes.len()].copy_from_slice(&fat_bytes);
+        }
+
+        img
+    }
+
+    fn add_file(mut self, path: &str, content: &[u8], deleted: bool, fragmented: bool) -> Self {
+        let (parent, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (Self::dir_mut(&mut self.root, parent), name),
+            None => (&mut self.root, path),
+        };
+        parent.push(Node::File {
+            name: name.to_string(),
+            content: content.to_vec(),
+            deleted,
+            fragmented,
+        });
+        self
+    }
+
+    /// Returns the children of the directory at `path`, creating it and its parents if needed.
+    fn dir_mut<'a>(mut children: &'a mut Vec<Node>, path: &str) -> &'a mut Vec<Node> {
+        for part in path.split('/').filter(|part| !part.is_empty()) {
+            let idx = match children
+                .iter()
+                .position(|node| matches!(node, Node::Dir { .. }) && node.name() == part)
+            {
+                Some(idx) => idx,
+                None => {
+                    children.push(Node::Dir {
+                        name: part.to_string(),
+                        children: vec![],
+                    });
+                    children.len() - 1
+                }
+            };
+            children = match &mut children[idx] {
+                Node::Dir { children, .. } => children,
+                Node::File { .. } => unreachable!(),
+            };
+        }
+
+        children
+    }
+
+    fn write_mbr(&self, img: &mut [u8], part_sec_cnt: u32) {
+        img[446 + 4] = 0x0C;
+        img[446 + 8..446 + 12].copy_from_slice(&self.partition_start.to_le_bytes());
+        img[446 + 12..446 + 16].copy_from_slice(&part_sec_cnt.to_le_bytes());
+        img[510] = 0x55;
+        img[511] = 0xAA;
+    }
+
+    fn boot_sector(&self, fat_sz: u32, tot_sec: u32) -> Vec<u8> {
+        let mut bs = vec![0u8; self.bytes_per_sec as usize];
+        bs[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        bs[3..11].copy_from_slice(b"MSWIN4.1");
+        bs[11..13].copy_from_slice(&self.bytes_per_sec.to_le_bytes());
+        bs[13] = self.sec_per_clus;
+        bs[14..16].copy_from_slice(&Self::RSVD_SEC_CNT.to_le_bytes());
+        bs[16] = Self::NUM_FAT;
+        bs[21] = 0xF8;
+        bs[28..32].copy_from_slice(&self.partition_start.to_le_bytes());
+        bs[32..36].copy_from_slice(&tot_sec.to_le_bytes());
+        bs[36..40].copy_from_slice(&fat_sz.to_le_bytes());
+        bs[44..48].copy_from_slice(&2u32.to_le_bytes());
+        bs[48..50].copy_from_slice(&Self::FS_INFO_SEC.to_le_bytes());
+        bs[50..52].copy_from_slice(&Self::BK_BOOT_SEC.to_le_bytes());
+        bs[64] = 0x80;
+        bs[66] = 0x29;
+        bs[67..71].copy_from_slice(&0x1234ABCDu32.to_le_bytes());
+        bs[71..82].copy_from_slice(&pad_label(&self.label));
+        bs[82..90].copy_from_slice(b"FAT32   ");
+        bs[510] = 0x55;
+        bs[511] = 0xAA;
+        bs
+    }
+}
+
+/// Cluster allocation state while building an image.
+struct Layout {
+    fat: Vec<u32>,
+    next_free: u32,
+    data_start: usize,
+    cluster_size: usize,
+}
+
+impl Layout {
+    /// Allocates `count` clusters, spaced by `gap` free clusters, and returns their numbers.
+    fn allocate(&mut self, count: usize, gap: u32) -> Vec<u32> {
+        let clusters: Vec<u32> = (0..count as u32)
+            .map(|i| self.next_free + i * (gap + 1))
+            .collect();
+        if let Some(last) = clusters.last() {
+            assert!(
+                (*last as usize) < self.fat.len(),
+                "the content doesn't fit in the volume"
+            );
+            self.next_free = last + 1;
+        }
+
+        clusters
+    }
+
+    /// Writes data to a list of clusters, and optionally links them in the FAT.
+    fn write_chain(&mut self, img: &mut [u8], clusters: &[u32], data: &[u8], link: bool) {
+        for (i, cluster) in clusters.iter().enumerate() {
+            let chunk = &data[(i * self.cluster_size).min(data.len())
+                ..((i + 1) * self.cluster_size).min(data.len())];
+            let off = self.data_start + (*cluster as usize - 2) * self.cluster_size;
+            img[off..off + chunk.len()].copy_from_slice(chunk);
+
+            if link {
+                self.fat[*cluster as usize] = clusters.get(i + 1).copied().unwrap_or(FAT_EOC);
+            }
+        }
+    }
+
+    /// Writes a directory and its content, and returns its first cluster.
+    ///
+    /// `parent_cluster` is `None` for the root directory, which has no dot entries, and `Some(0)`
+    /// for the directories it contains.
+    fn write_dir(
+        &mut self,
+        img: &mut [u8],
+        children: &[Node],
+        mut entries: Vec<[u8; DIR_ENTRY_SIZE]>,
+        parent_cluster: Option<u32>,
+    ) -> u32 {
+        let entry_cnt = entries.len() + children.len() + 2;
+        let clusters = self.allocate((entry_cnt * DIR_ENTRY_SIZE).div_ceil(self.cluster_size), 0);
+        let first = clusters[0];
+
+        if let Some(parent_cluster) = parent_cluster {
+            entries.insert(0, dir_entry(b".          ", ATTR_DIRECTORY, first, 0));
+            entries.insert(
+                1,
+                dir_entry(b"..         ", ATTR_DIRECTORY, parent_cluster, 0),
+            );
+        }
+        let children_parent = if parent_cluster.is_some() { first } else { 0 };
+
+        for child in children {
+            match child {
+                Node::Dir { name, children } => {
+                    let cluster = self.write_dir(img, children, vec![], Some(children_parent));
+                    entries.push(dir_entry(&short_name(name), ATTR_DIRECTORY, cluster, 0));
+                }
+                Node::File {
+                    name,
+                    content,
+                    deleted,
+                    fragmented,
+                } => {
+                    let gap = if *fragmented { 1 } else { 0 };
+                    let clusters = self.allocate(content.len().div_ceil(self.cluster_size), gap);
+                    self.write_chain(img, &clusters, content, !deleted);
+
+                    let mut entry = dir_entry(
+                        &short_name(name),
+                        ATTR_ARCHIVE,
+                        clusters.first().copied().unwrap_or(0),
+                        content.len() as u32,
+                    );
+                    if *deleted {
+                        entry[0] = 0xE5;
+                    }
+                    entries.push(entry);
+                }
+            }
+        }
+
+        self.write_chain(img, &clusters, &entries.concat(), true);
+        first
+    }
+}
+
+/// Converts a `NAME.EXT` file name to its 11-byte 8.3 representation.
+///
+/// # Panics
+/// Panics if the name doesn't fit the 8.3 format.
+fn short_name(name: &str) -> [u8; 11] {
+    let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+    assert!(
+        !base.is_empty() && base.len() <= 8 && ext.len() <= 3,
+        "'{name}' is not a valid 8.3 name"
+    );
+
+    let mut out = [b' '; 11];
+    out[..base.len()].copy_from_slice(base.to_ascii_uppercase().as_bytes());
+    out[8..8 + ext.len()].copy_from_slice(ext.to_ascii_uppercase().as_bytes());
+    out
+}
+
+/// Pads a volume label to 11 bytes.
+fn pad_label(label: &str) -> [u8; 11] {
+    let mut out = [b' '; 11];
+    let label = label.to_ascii_uppercase();
+    let len = label.len().min(11);
+    out[..len].copy_from_slice(&label.as_bytes()[..len]);
+    out
+}
+
+/// Serializes a short directory entry.
+fn dir_entry(name: &[u8; 11], attr: u8, cluster: u32, size: u32) -> [u8; DIR_ENTRY_SIZE] {
+    let mut entry = [0u8; DIR_ENTRY_SIZE];
+    entry[0..11].copy_from_slice(name);
+    entry[11] = attr;
+    entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+    entry
+}
@@ -1,21 +1,69 @@
 //! Declaration of traits reused across the codebase.
 //!
-//! These traits provide extensibility for displaying layouts and writing to slack space
-//! in FAT-family filesystems and disk images.
+//! These traits provide extensibility for displaying layouts, writing, reading back and erasing
+//! slack space in FAT-family filesystems and disk images, and receiving the events of the
+//! library.
 
+use getset::Getters;
 use std::{
-    io::{Seek, Write},
+    fmt,
+    io::{self, Read, Seek, Write},
     path::Path,
 };
 use thiserror::Error;
 
+use crate::events::Event;
 use crate::filesystem::fat_error::FATError;
+use crate::utils::human_size;
 
 /// Wrapper around all the potential errors for the different trait implementers.
 #[derive(Error, Debug)]
 pub enum TraitError {
     #[error("FAT Error: {0}")]
     FATError(#[from] FATError),
+    #[error("I/O Error: {0}")]
+    IOError(#[from] io::Error),
+}
+
+/// Unit of the positions and lengths shown in a layout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LayoutUnit {
+    /// Sector numbers.
+    #[default]
+    Sectors,
+    /// Byte offsets.
+    Bytes,
+    /// Byte offsets with a binary prefix (e.g. `1.5 MiB`).
+    Human,
+}
+
+/// Options of a layout display.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LayoutOptions {
+    /// The unit of the start, end and length columns.
+    pub unit: LayoutUnit,
+    /// Whether to show the unallocated and metadata regions as well.
+    pub all: bool,
+}
+
+impl LayoutOptions {
+    /// Formats a sector range in the selected unit.
+    ///
+    /// # Parameters
+    /// - `start`: The first sector of the range.
+    /// - `end`: The sector following the range.
+    /// - `sector_size`: The size in bytes of a sector.
+    ///
+    /// # Returns
+    /// - The start, end and length of the range.
+    pub fn fmt_range(&self, start: u64, end: u64, sector_size: u64) -> [String; 3] {
+        let len = end.saturating_sub(start);
+        [start, end, len].map(|value| match self.unit {
+            LayoutUnit::Sectors => value.to_string(),
+            LayoutUnit::Bytes => value.saturating_mul(sector_size).to_string(),
+            LayoutUnit::Human => human_size(value.saturating_mul(sector_size)),
+        })
+    }
 }
 
 /// Trait for displaying the layout of a structure (e.g., disk, partition, volume).
@@ -26,20 +74,68 @@ pub trait LayoutDisplay {
     ///
     /// # Parameters
     /// - `indent`: The number of spaces to indent the output.
+    /// - `options`: The unit of the ranges and the regions to show.
     ///
     /// # Returns
     /// - `Ok(str)` A `String` containing the formatted layout.
     /// - `Err(Error)` if the string formatting failed.
-    fn display_layout(&self, indent: u8) -> Result<String, std::fmt::Error>;
+    fn display_layout(
+        &self,
+        indent: u8,
+        options: &LayoutOptions,
+    ) -> Result<String, std::fmt::Error>;
 }
 
+/// Trait for displaying the directory tree of a structure (e.g., volume).
+///
+/// Implementors write the tree to the provided sink rather than printing it, so the output
+/// can be captured by library consumers and alternative frontends.
 pub trait TreeDisplay {
-    fn display_tree(&self) -> Result<(), TraitError>;
+    /// Writes the directory tree of the structure.
+    ///
+    /// # Parameters
+    /// - `out`: The sink the tree is written to, one entry per line.
+    ///
+    /// # Returns
+    /// - `Ok(())` if the tree is written successfully.
+    /// - `Err(TraitError)` if the tree cannot be read or written.
+    fn display_tree<W: Write>(&self, out: &mut W) -> Result<(), TraitError>;
+}
+
+/// The evidence of data written to slack space, read back and verified.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct SlackWrite {
+    /// The absolute byte offset and length of each range holding the data, in order.
+    #[get = "pub"]
+    extents: Vec<(u64, u64)>,
+    /// The SHA-256 digest of the data, as read back.
+    #[get = "pub"]
+    sha256: String,
+}
+
+impl SlackWrite {
+    /// Records data read back from the image.
+    pub fn new(extents: Vec<(u64, u64)>, sha256: String) -> Self {
+        Self { extents, sha256 }
+    }
+}
+
+impl fmt::Display for SlackWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let extents: Vec<String> = self
+            .extents
+            .iter()
+            .map(|(offset, len)| format!("0x{:X}-0x{:X}", offset, offset + len))
+            .collect();
+        write!(f, "bytes {} (SHA-256 {})", extents.join(" "), self.sha256)
+    }
 }
 
 /// Trait for writing data to slack space in a volume or file.
 ///
-/// Slack space is the unused space at the end of a cluster or file.
+/// Slack space is the unused space at the end of a cluster or file. Every write has a matching
+/// read, to verify the hidden data after placement, and a matching erase, to clean it up when
+/// regenerating an image.
 pub trait SlackWriter {
     /// Write data to the slack space of a volume.
     ///
@@ -72,4 +168,154 @@ pub trait SlackWriter {
         file_path: &Path,
         data: &[u8],
     ) -> Result<(), FATError>;
+
+    /// Write data to the slack space of a volume, then read it back and compare its digest with
+    /// the digest of the data.
+    ///
+    /// # Parameters
+    /// - `disk`: A mutable reference to a type implementing `Read + Write + Seek`.
+    /// - `data`: The data to write into slack space.
+    ///
+    /// # Returns
+    /// - `Ok(SlackWrite)`: Where the data landed, and its digest.
+    /// - `Err(FATError::SlackVerificationFailed)` if the data read back differs.
+    /// - `Err(FATError)` if writing or reading fails.
+    fn write_to_volume_slack_verified<T: Read + Write + Seek>(
+        &self,
+        disk: &mut T,
+        data: &[u8],
+    ) -> Result<SlackWrite, FATError>;
+
+    /// Write data to the slack space of a specific file, then read it back and compare its digest
+    /// with the digest of the data.
+    ///
+    /// # Parameters
+    /// - `disk`: A mutable reference to a type implementing `Read + Write + Seek`.
+    /// - `file_path`: The path to the file whose slack space will be written.
+    /// - `data`: The data to write into slack space.
+    ///
+    /// # Returns
+    /// - `Ok(SlackWrite)`: Where the data landed, and its digest.
+    /// - `Err(FATError::SlackVerificationFailed)` if the data read back differs.
+    /// - `Err(FATError)` if the file cannot be found, or writing or reading fails.
+    fn write_to_file_slack_verified<T: Read + Write + Seek>(
+        &self,
+        disk: &mut T,
+        file_path: &Path,
+        data: &[u8],
+    ) -> Result<SlackWrite, FATError>;
+
+    /// Read the slack space of a volume, from its start to its end.
+    ///
+    /// # Parameters
+    /// - `reader`: A mutable reference to a type implementing `Read + Seek`.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The content of the slack space.
+    /// - `Err(FATError)` if reading fails.
+    fn read_to_end_of_volume_slack<T: Read + Seek>(
+        &self,
+        reader: &mut T,
+    ) -> Result<Vec<u8>, FATError>;
+
+    /// Read the slack space of a specific file, from the end of the file to the end of its last
+    /// cluster.
+    ///
+    /// # Parameters
+    /// - `reader`: A mutable reference to a type implementing `Read + Seek`.
+    /// - `file_path`: The path to the file whose slack space will be read.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)`: The content of the slack space.
+    /// - `Err(FATError)` if the file cannot be found or reading fails.
+    fn read_to_end_of_file_slack<T: Read + Seek>(
+        &self,
+        reader: &mut T,
+        file_path: &Path,
+    ) -> Result<Vec<u8>, FATError>;
+
+    /// Zero the slack space of a volume.
+    ///
+    /// # Parameters
+    /// - `writer`: A mutable reference to a type implementing `Write + Seek`.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of bytes erased.
+    /// - `Err(FATError)` if writing fails.
+    fn erase_volume_slack<T: Write + Seek>(&self, writer: &mut T) -> Result<u64, FATError>;
+
+    /// Zero the slack space of a specific file.
+    ///
+    /// # Parameters
+    /// - `writer`: A mutable reference to a type implementing `Write + Seek`.
+    /// - `file_path`: The path to the file whose slack space will be erased.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of bytes erased.
+    /// - `Err(FATError)` if the file cannot be found or writing fails.
+    fn erase_file_slack<T: Write + Seek>(
+        &self,
+        writer: &mut T,
+        file_path: &Path,
+    ) -> Result<u64, FATError>;
+}
+
+/// Trait for recovering the files of a format from unallocated space.
+///
+/// A carver recognises the header of its format at a cluster boundary, then decides how much of
+/// the following data belongs to the file. Implement it to recover formats not built into the
+/// crate, and register it in a `CarverRegistry`.
+#[cfg(feature = "carving")]
+pub trait Carver {
+    /// Returns the name of the format.
+    fn name(&self) -> &str;
+
+    /// Returns the extension given to the carved files, without the dot.
+    fn extension(&self) -> &str;
+
+    /// Returns the bytes starting every file of the format.
+    fn header(&self) -> &[u8];
+
+    /// Returns the bytes ending every file of the format, if any.
+    fn footer(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Returns the maximum size of a file, in bytes.
+    fn max_size(&self) -> u64;
+
+    /// Validates a candidate and computes its length.
+    ///
+    /// The default implementation cuts the data after the first footer, or keeps all of it if the
+    /// format has no footer.
+    ///
+    /// # Parameters
+    /// - `data`: The data following the header, starting with it, up to the maximum size.
+    ///
+    /// # Returns
+    /// - `Some(len)`: The length of the file.
+    /// - `None`: If the data isn't a file of the format.
+    fn trim(&self, data: &[u8]) -> Option<usize> {
+        match self.footer() {
+            Some(footer) => data
+                .windows(footer.len())
+                .position(|window| window == footer)
+                .map(|pos| pos + footer.len()),
+            None => Some(data.len()),
+        }
+    }
+}
+
+/// Trait for receiving the events reported by the walks and scans of a volume.
+///
+/// Sinks are shared with the volumes, hence `Send + Sync`. Any `Fn(&Event)` closure is a sink.
+pub trait EventSink: Send + Sync {
+    /// Receives an event.
+    fn emit(&self, event: &Event);
+}
+
+impl<F: Fn(&Event) + Send + Sync> EventSink for F {
+    fn emit(&self, event: &Event) {
+        self(event)
+    }
 }
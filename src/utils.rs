@@ -4,6 +4,7 @@
 //! values from byte buffers, and handling file operations in a generic and reusable way.
 
 use std::io;
+use std::path::Path;
 
 /// Reads a specific sector from a file into a buffer.
 ///
@@ -48,6 +49,24 @@ pub fn write_at<T: io::Write + io::Seek>(disk: &mut T, offset: u64, data: &[u8])
     disk.write_all(data)
 }
 
+/// Reads data from a file at a specific offset.
+///
+/// # Arguments
+///
+/// - `disk`: A mutable reference to the file to read from.
+/// - `offset`: The offset in bytes where the data starts.
+/// - `len`: The number of bytes to read.
+pub fn read_at<T: io::Read + io::Seek>(
+    disk: &mut T,
+    offset: u64,
+    len: usize,
+) -> io::Result<Vec<u8>> {
+    let mut data = vec![0; len];
+    disk.seek(io::SeekFrom::Start(offset))?;
+    disk.read_exact(&mut data)?;
+    Ok(data)
+}
+
 /// Writes the contents of a file to a specific offset in a disk.
 ///
 /// # Arguments
@@ -82,6 +101,122 @@ pub fn write_file_at<T: io::Write + io::Seek, S: io::Read>(
     Ok(())
 }
 
+/// Builds a buffer of the given length by repeating a byte pattern.
+///
+/// # Arguments
+///
+/// - `pattern`: The byte pattern to repeat. An empty pattern yields zeros.
+/// - `len`: The length in bytes of the resulting buffer.
+pub fn fill_pattern(pattern: &[u8], len: usize) -> Vec<u8> {
+    if pattern.is_empty() {
+        return vec![0; len];
+    }
+
+    pattern.iter().copied().cycle().take(len).collect()
+}
+
+/// Size of the blocks written by [`write_pattern`].
+const PATTERN_BLOCK_SIZE: u64 = 1 << 20;
+
+/// Writes `len` bytes repeating a byte pattern at a specific offset in a disk, one block at a
+/// time so that large regions are never built in memory.
+///
+/// # Arguments
+///
+/// - `disk`: A mutable reference to the disk to write to.
+/// - `offset`: The offset in bytes where the region starts.
+/// - `len`: The length in bytes of the region.
+/// - `pattern`: The byte pattern to repeat from the start of the region. An empty pattern writes
+///   zeros.
+pub fn write_pattern<T: io::Write + io::Seek>(
+    disk: &mut T,
+    offset: u64,
+    len: u64,
+    pattern: &[u8],
+) -> io::Result<()> {
+    // One spare period so that every block can start at the phase of its own offset
+    let period = pattern.len().max(1);
+    let block = fill_pattern(pattern, (len.min(PATTERN_BLOCK_SIZE) as usize) + period);
+
+    disk.seek(io::SeekFrom::Start(offset))?;
+    let mut written = 0;
+    while written < len {
+        let size = (len - written).min(PATTERN_BLOCK_SIZE) as usize;
+        let phase = (written % period as u64) as usize;
+        disk.write_all(&block[phase..phase + size])?;
+        written += size as u64;
+    }
+    Ok(())
+}
+
+/// Returns true if a character of a name can corrupt or mislead a terminal.
+///
+/// Control characters move the cursor or change the colours, invalid code points are decoded as
+/// U+FFFD, and bidirectional formatting characters reorder the text, e.g. to disguise an
+/// extension (`invoice\u{202E}fdp.exe` shows as `invoiceexe.pdf`).
+pub fn is_unsafe_char(c: char) -> bool {
+    c.is_control() || c == char::REPLACEMENT_CHARACTER || is_bidi_control(c)
+}
+
+/// Returns true if a character is a bidirectional formatting character, changing the direction
+/// of the text displayed after it.
+pub fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+/// Renders a file name safely on a terminal, escaping the characters flagged by
+/// [`is_unsafe_char`] as `\xNN` (ASCII) or `\u{NNNN}`, and `\` as `\\` so that an escape
+/// can't be mistaken for a name holding its text.
+///
+/// # Arguments
+///
+/// - `name`: The name to render.
+pub fn escape_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            c if !is_unsafe_char(c) => escaped.push(c),
+            c if c.is_ascii() => escaped.push_str(&format!("\\x{:02X}", c as u32)),
+            c => escaped.push_str(&format!("\\u{{{:04X}}}", c as u32)),
+        }
+    }
+    escaped
+}
+
+/// Renders a path safely on a terminal (see [`escape_name`]).
+///
+/// # Arguments
+///
+/// - `path`: The path to render.
+pub fn escape_path(path: &Path) -> String {
+    escape_name(&path.to_string_lossy())
+}
+
+/// Formats a byte count with a binary prefix (e.g. `1.5 MiB`).
+///
+/// # Arguments
+///
+/// - `bytes`: The number of bytes.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{value:.1} {}", UNITS[unit]),
+    }
+}
+
 /// Extracts a 32-bit unsigned integer from a buffer at a given offset.
 ///
 /// # Arguments
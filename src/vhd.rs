@@ -0,0 +1,121 @@
+//! Fixed VHD images: a raw image followed by a 512-byte footer.
+//!
+//! The data of a fixed VHD is stored as is, so it is read and written like a raw image; only its
+//! footer, holding the size and geometry of the virtual disk, has to be recognized and left out of
+//! the disk. Dynamic and differencing VHDs, which map blocks through a table, are not supported.
+//!
+//! The footer fields are big-endian.
+
+use md5::{Digest, Md5};
+use std::fs::File;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::utils::read_at;
+
+/// The length of the footer.
+pub const FOOTER_LEN: u64 = 512;
+
+/// The cookie starting the footer.
+const COOKIE: &[u8; 8] = b"conectix";
+
+/// The disk type of a fixed VHD.
+const DISK_TYPE_FIXED: u32 = 2;
+
+/// The seconds between the Unix epoch and the VHD epoch, January 1st 2000 UTC.
+const VHD_EPOCH: u64 = 946_684_800;
+
+/// Returns the size of the virtual disk of a fixed VHD.
+///
+/// # Parameters
+/// - `file`: The image file.
+///
+/// # Returns
+/// - `Ok(Some(u64))`: The size of the disk, if the file ends with the footer of a fixed VHD.
+/// - `Ok(None)`: If the file is not a fixed VHD.
+/// - `Err(io::Error)`: If the file cannot be read.
+pub fn fixed_disk_size(file: &File) -> io::Result<Option<u64>> {
+    let len = file.metadata()?.len();
+    if len < FOOTER_LEN {
+        return Ok(None);
+    }
+    let mut reader = file;
+    let footer = read_at(&mut reader, len - FOOTER_LEN, FOOTER_LEN as usize)?;
+    let size = u64::from_be_bytes(footer[48..56].try_into().unwrap());
+    let disk_type = u32::from_be_bytes(footer[60..64].try_into().unwrap());
+    Ok(
+        (footer.starts_with(COOKIE) && disk_type == DISK_TYPE_FIXED && size <= len - FOOTER_LEN)
+            .then_some(size),
+    )
+}
+
+/// Builds the footer of a fixed VHD.
+///
+/// # Parameters
+/// - `size`: The size of the virtual disk in bytes, a multiple of 512.
+///
+/// # Returns
+/// The footer, to be appended to the data of the disk.
+pub fn fixed_footer(size: u64) -> [u8; FOOTER_LEN as usize] {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs().saturating_sub(VHD_EPOCH)) as u32;
+    let (cylinders, heads, sectors) = chs_geometry(size);
+    // Any unique identifier will do, without pulling a random number generator
+    let unique_id = Md5::new()
+        .chain_update(size.to_le_bytes())
+        .chain_update(timestamp.to_le_bytes())
+        .chain_update(std::process::id().to_le_bytes())
+        .finalize();
+
+    let mut footer = [0u8; FOOTER_LEN as usize];
+    footer[0..8].copy_from_slice(COOKIE);
+    footer[8..12].copy_from_slice(&2u32.to_be_bytes());
+    footer[12..16].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+    footer[16..24].copy_from_slice(&u64::MAX.to_be_bytes());
+    footer[24..28].copy_from_slice(&timestamp.to_be_bytes());
+    footer[28..32].copy_from_slice(b"ffor");
+    footer[32..36].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+    footer[36..40].copy_from_slice(b"Wi2k");
+    footer[40..48].copy_from_slice(&size.to_be_bytes());
+    footer[48..56].copy_from_slice(&size.to_be_bytes());
+    footer[56..58].copy_from_slice(&cylinders.to_be_bytes());
+    footer[58] = heads;
+    footer[59] = sectors;
+    footer[60..64].copy_from_slice(&DISK_TYPE_FIXED.to_be_bytes());
+    footer[68..84].copy_from_slice(&unique_id);
+
+    // One's complement of the sum of the bytes, the checksum field being zero
+    let checksum = !footer
+        .iter()
+        .fold(0u32, |sum, b| sum.wrapping_add(*b as u32));
+    footer[64..68].copy_from_slice(&checksum.to_be_bytes());
+    footer
+}
+
+/// Computes the CHS geometry of a virtual disk, as specified by the VHD format.
+///
+/// # Returns
+/// The number of cylinders, heads and sectors per track.
+fn chs_geometry(size: u64) -> (u16, u8, u8) {
+    let total = (size / 512).min(65535 * 16 * 255);
+    let (sectors, heads, cylinder_heads) = if total >= 65535 * 16 * 63 {
+        (255, 16, total / 255)
+    } else {
+        let mut sectors = 17;
+        let mut cylinder_heads = total / sectors;
+        let mut heads = cylinder_heads.div_ceil(1024).max(4);
+        if cylinder_heads >= heads * 1024 || heads > 16 {
+            sectors = 31;
+            heads = 16;
+            cylinder_heads = total / sectors;
+        }
+        if cylinder_heads >= heads * 1024 {
+            sectors = 63;
+            heads = 16;
+            cylinder_heads = total / sectors;
+        }
+        (sectors, heads, cylinder_heads)
+    };
+    ((cylinder_heads / heads) as u16, heads as u8, sectors as u8)
+}
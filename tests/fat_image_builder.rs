@@ -0,0 +1,65 @@
+//! Opens the images built by [`FatImageBuilder`] the way the CLI opens disk images.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use fat_forensics::test_utils::FatImageBuilder;
+use fat_forensics::{Disk, FATVol, Mbr};
+
+/// Writes an image to a file of the temporary directory, removed when dropped.
+struct TempImage(PathBuf);
+
+impl TempImage {
+    fn new(name: &str, builder: &FatImageBuilder) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("fat_forensics_{name}_{}.img", std::process::id()));
+        builder.write_to(&path).expect("the image can't be written");
+        Self(path)
+    }
+}
+
+impl Drop for TempImage {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn built_image_opens_with_its_tree() {
+    let content: Vec<u8> = (0..1500).map(|i| i as u8).collect();
+    let image = TempImage::new(
+        "tree",
+        &FatImageBuilder::new()
+            .label("BUILT")
+            .file("DOCS/A.TXT", b"hello")
+            .fragmented_file("FRAG.BIN", &content)
+            .deleted_file("GONE.TXT", b"bye"),
+    );
+
+    let disk: Disk<FATVol, Mbr> =
+        Disk::from_file(&image.0, 512, true).expect("the image can't be opened");
+    let mut tree = vec![];
+    disk.write_tree(&mut tree)
+        .expect("the tree can't be written");
+    let tree = String::from_utf8(tree).expect("the tree isn't UTF-8");
+    assert_eq!(
+        tree.lines().map(str::trim).collect::<Vec<_>>(),
+        [
+            "BUILT 0B",
+            "DOCS 0B",
+            ". 0B",
+            ".. 0B",
+            "A.TXT 5B",
+            "FRAG.BIN 1500B",
+            "?ONE.TXT 3B",
+        ]
+    );
+
+    let vol = disk.volume(0).expect("the volume isn't opened");
+    let frag = vol
+        .stat(Path::new("FRAG.BIN"))
+        .expect("the fragmented file isn't found");
+    assert_eq!(*frag.entry().file_size(), 1500);
+    assert_eq!(frag.runs().len(), 3, "runs: {:?}", frag.runs());
+    assert!(frag.runs().iter().all(|(_, len)| *len == 1));
+}